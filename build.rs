@@ -42,19 +42,19 @@ fn main() -> io::Result<()> {
     let control_code = Regex::new(r##"\\.+?m"##).unwrap();
 
     // Pretty version text
-    let mut f = File::create(&out.join("version.pretty.txt"))?;
-    write!(f, "{}\n", ver.replace("\\", "\x1B["))?;
+    let mut f = File::create(out.join("version.pretty.txt"))?;
+    writeln!(f, "{}", ver.replace("\\", "\x1B["))?;
 
     // Bland version text
-    let mut f = File::create(&out.join("version.bland.txt"))?;
-    write!(f, "{}\n", control_code.replace_all(&ver, ""))?;
+    let mut f = File::create(out.join("version.bland.txt"))?;
+    writeln!(f, "{}", control_code.replace_all(&ver, ""))?;
 
     // Pretty usage text
-    let mut f = File::create(&out.join("usage.pretty.txt"))?;
+    let mut f = File::create(out.join("usage.pretty.txt"))?;
     write!(f, "{}\n\n{}", tagline.replace("\\", "\x1B["), usage.replace("\\", "\x1B["))?;
 
     // Bland usage text
-    let mut f = File::create(&out.join("usage.bland.txt"))?;
+    let mut f = File::create(out.join("usage.bland.txt"))?;
     write!(f, "{}\n\n{}", control_code.replace_all(tagline, ""), control_code.replace_all(usage, ""))?;
 
     Ok(())
@@ -67,7 +67,7 @@ fn git_hash() -> String {
 
     String::from_utf8_lossy(
         &Command::new("git")
-            .args(&["rev-parse", "--short", "HEAD"])
+            .args(["rev-parse", "--short", "HEAD"])
             .output().unwrap()
             .stdout).trim().to_string()
 }
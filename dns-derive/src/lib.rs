@@ -0,0 +1,196 @@
+#![warn(deprecated_in_future)]
+#![warn(future_incompatible)]
+#![warn(missing_copy_implementations)]
+#![warn(missing_docs)]
+#![warn(nonstandard_style)]
+#![warn(rust_2018_compatibility)]
+#![warn(rust_2018_idioms)]
+#![warn(single_use_lifetimes)]
+#![warn(trivial_casts, trivial_numeric_casts)]
+#![warn(unused)]
+
+#![deny(unsafe_code)]
+
+
+//! A derive macro for the dns crate’s `Wire` trait, for records whose data
+//! is just a run of fields, one after another. It gets re-exported from
+//! the dns crate when its `derive` feature is turned on.
+//!
+//! The structure needs a `#[wire(name = "...", rr_type = ...)]` attribute
+//! giving the record’s type, and its fields are read and written in the
+//! order they are declared. Each field’s type decides how it is encoded:
+//!
+//! - `u8`, `u16`, and `u32` are numbers, in network byte order;
+//! - `Ipv4Addr` and `Ipv6Addr` are addresses, in 4 and 16 bytes;
+//! - `Name` is a domain name, which can be compressed when read;
+//! - `String` and `Vec<u8>` are character-strings, with a length byte;
+//! - a `Vec<u8>` marked `#[wire(rest)]` is the rest of the record’s data,
+//!   which has to be the last field.
+//!
+//! # Examples
+//!
+//! ```
+//! use dns::{Name, Wire};
+//!
+//! /// An RP record, naming who is responsible for a domain.
+//! #[derive(Wire, PartialEq, Debug)]
+//! #[wire(name = "RP", rr_type = 17)]
+//! struct RP {
+//!     mailbox: Name,
+//!     text: Name,
+//! }
+//!
+//! let record = RP { mailbox: "hostmaster.lookup.dog.".parse().unwrap(), text: ".".parse().unwrap() };
+//! let mut bytes = Vec::new();
+//! record.write(&mut bytes).unwrap();
+//!
+//! let mut c = std::io::Cursor::new(&bytes[..]);
+//! assert_eq!(RP::read(bytes.len() as u16, &mut c), Ok(record));
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitInt, LitStr};
+
+
+/// Derives `Wire` for a structure with named fields. See the crate
+/// documentation for the attributes and field types it understands.
+#[proc_macro_derive(Wire, attributes(wire))]
+pub fn derive_wire(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e)     => e.to_compile_error().into(),
+    }
+}
+
+
+/// The type of record given in the attribute on the structure.
+struct RecordType {
+    name: LitStr,
+    rr_type: LitInt,
+}
+
+/// Reads the `#[wire(name = "...", rr_type = ...)]` attribute on the
+/// structure, both parts of which are needed.
+fn record_type(input: &DeriveInput) -> Result<RecordType, Error> {
+    let mut name = None;
+    let mut rr_type = None;
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("wire")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse()?);
+                Ok(())
+            }
+            else if meta.path.is_ident("rr_type") {
+                rr_type = Some(meta.value()?.parse()?);
+                Ok(())
+            }
+            else {
+                Err(meta.error("unknown wire attribute; expected `name` or `rr_type`"))
+            }
+        })?;
+    }
+
+    match (name, rr_type) {
+        (Some(name), Some(rr_type)) => Ok(RecordType { name, rr_type }),
+        _ => Err(Error::new_spanned(&input.ident, "deriving Wire needs a #[wire(name = \"...\", rr_type = ...)] attribute")),
+    }
+}
+
+/// Whether a field is marked `#[wire(rest)]`.
+fn is_rest(field: &syn::Field) -> Result<bool, Error> {
+    let mut rest = false;
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("wire")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rest") {
+                rest = true;
+                Ok(())
+            }
+            else {
+                Err(meta.error("unknown wire attribute; expected `rest`"))
+            }
+        })?;
+    }
+
+    Ok(rest)
+}
+
+/// Writes the `Wire` implementation.
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let RecordType { name, rr_type } = record_type(input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "Wire can only be derived for structures with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "Wire can only be derived for structures")),
+    };
+
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    let mut has_rest = false;
+
+    for (index, field) in fields.iter().enumerate() {
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+
+        if is_rest(field)? {
+            if index + 1 != fields.len() {
+                return Err(Error::new_spanned(ident, "only the last field can be #[wire(rest)]"));
+            }
+
+            has_rest = true;
+            reads.push(quote! { #ident: ::dns::derive::read_rest(c, start, len)? });
+            writes.push(quote! { ::dns::derive::write_rest(&self.#ident, bytes)?; });
+        }
+        else {
+            reads.push(quote! { #ident: <#ty as ::dns::derive::Field>::read_field(c, options)? });
+            writes.push(quote! { ::dns::derive::Field::write_field(&self.#ident, bytes)?; });
+        }
+    }
+
+    // A field marked `#[wire(rest)]` reads up to the end of the data, so
+    // only records without one need to check that the length was right.
+    let check_length = if has_rest {
+        quote! {}
+    }
+    else {
+        quote! { ::dns::derive::check_length(c, start, len)?; }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // The fields are read inside the structure literal, which evaluates
+    // them in the order they are written, so that no local variable has
+    // to be named after a field.
+    Ok(quote! {
+        impl #impl_generics ::dns::Wire for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+            const RR_TYPE: u16 = #rr_type;
+
+            fn read(len: u16, c: &mut ::std::io::Cursor<&[u8]>) -> ::std::result::Result<Self, ::dns::WireError> {
+                Self::read_with(len, c, &::dns::ParseOptions::default())
+            }
+
+            #[allow(unused_variables)]
+            fn read_with(len: u16, c: &mut ::std::io::Cursor<&[u8]>, options: &::dns::ParseOptions) -> ::std::result::Result<Self, ::dns::WireError> {
+                let start = c.position();
+                let record = Self { #( #reads, )* };
+                #check_length
+                ::std::result::Result::Ok(record)
+            }
+
+            fn write(&self, bytes: &mut ::std::vec::Vec<u8>) -> ::std::result::Result<(), ::dns::WireError> {
+                #( #writes )*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use std::io::Cursor;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use dns::{Answer, Name, ParseOptions, Response, Wire, WireError};
+use dns::record::{Record, RecordRegistry};
+
+
+/// A record with one of every kind of field.
+#[derive(Wire, PartialEq, Debug)]
+#[wire(name = "EVERY", rr_type = 65400)]
+struct Everything {
+    small: u8,
+    medium: u16,
+    large: u32,
+    v4: Ipv4Addr,
+    v6: Ipv6Addr,
+    host: Name,
+    label: String,
+    blob: Vec<u8>,
+    #[wire(rest)]
+    rest: Vec<u8>,
+}
+
+/// A record with fields named the same as the variables in the derived
+/// implementation.
+#[derive(Wire, PartialEq, Debug)]
+#[wire(name = "SHADOW", rr_type = 65401)]
+struct Shadowing {
+    c: u8,
+    len: u16,
+    options: Name,
+}
+
+/// A record holding a single name.
+#[derive(Wire, PartialEq, Debug)]
+#[wire(name = "PLACE", rr_type = 65402)]
+struct Place {
+    priority: u16,
+    target: Name,
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)
+    }
+}
+
+
+fn read<T: Wire>(bytes: &[u8]) -> Result<T, WireError> {
+    T::read(bytes.len() as u16, &mut Cursor::new(bytes))
+}
+
+fn name(input: &str) -> Name {
+    input.parse().unwrap()
+}
+
+fn everything() -> Everything {
+    Everything {
+        small: 0x01,
+        medium: 0x0203,
+        large: 0x04050607,
+        v4: Ipv4Addr::new(127, 0, 0, 1),
+        v6: Ipv6Addr::LOCALHOST,
+        host: name("dog."),
+        label: String::from("hi"),
+        blob: vec![ 0xab ],
+        rest: vec![ 0xde, 0xad ],
+    }
+}
+
+
+#[test]
+fn constants() {
+    assert_eq!(Everything::NAME, "EVERY");
+    assert_eq!(Everything::RR_TYPE, 65400);
+}
+
+#[test]
+fn writes_fields_in_order() {
+    let mut bytes = Vec::new();
+    everything().write(&mut bytes).unwrap();
+
+    assert_eq!(bytes, &[
+        0x01,  // small
+        0x02, 0x03,  // medium
+        0x04, 0x05, 0x06, 0x07,  // large
+        0x7f, 0x00, 0x00, 0x01,  // v4
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,  // v6
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // host
+        0x02, 0x68, 0x69,  // label
+        0x01, 0xab,  // blob
+        0xde, 0xad,  // rest
+    ][..]);
+}
+
+#[test]
+fn round_trip() {
+    let mut bytes = Vec::new();
+    everything().write(&mut bytes).unwrap();
+    assert_eq!(read::<Everything>(&bytes), Ok(everything()));
+}
+
+#[test]
+fn empty_rest() {
+    let mut record = everything();
+    record.rest.clear();
+
+    let mut bytes = Vec::new();
+    record.write(&mut bytes).unwrap();
+    assert_eq!(read::<Everything>(&bytes), Ok(record));
+}
+
+#[test]
+fn fields_named_like_variables() {
+    let buf = &[ 0x01, 0x00, 0x02, 0x00 ];
+    assert_eq!(read::<Shadowing>(buf),
+               Ok(Shadowing { c: 1, len: 2, options: name(".") }));
+}
+
+#[test]
+fn lossy_strings() {
+    #[derive(Wire, PartialEq, Debug)]
+    #[wire(name = "WORDS", rr_type = 65403)]
+    struct Words { words: String }
+
+    assert_eq!(read::<Words>(&[ 0x02, 0x68, 0xff ]),
+               Ok(Words { words: String::from("h\u{fffd}") }));
+}
+
+#[test]
+fn record_too_short() {
+    assert_eq!(read::<Place>(&[ 0x00 ]),
+               Err(WireError::IO));
+}
+
+#[test]
+fn rest_after_end() {
+    #[derive(Wire, PartialEq, Debug)]
+    #[wire(name = "TAIL", rr_type = 65404)]
+    struct Tail { number: u16, #[wire(rest)] tail: Vec<u8> }
+
+    let mut c = Cursor::new(&[ 0x00, 0x01, 0x02 ][..]);
+    assert_eq!(Tail::read(1, &mut c),
+               Err(WireError::WrongLength { expected: 1, got: 2 }));
+}
+
+#[test]
+fn length_too_long() {
+    let mut c = Cursor::new(&[ 0x00, 0x0a, 0x00, 0xff ][..]);
+    assert_eq!(Place::read(4, &mut c),
+               Err(WireError::WrongLength { expected: 4, got: 3 }));
+}
+
+#[test]
+fn string_too_long() {
+    let mut record = everything();
+    record.label = "a".repeat(256);
+
+    assert_eq!(record.write(&mut Vec::new()),
+               Err(WireError::StringTooLong(256)));
+}
+
+#[test]
+fn follows_pointers_with_options() {
+    // The target is a pointer back to the name at the start of the buffer.
+    let buf = &[ 0x03, 0x64, 0x6f, 0x67, 0x00,  0x00, 0x0a, 0xc0, 0x00 ];
+    let mut c = Cursor::new(&buf[..]);
+    c.set_position(5);
+
+    assert_eq!(Place::read_with(4, &mut c, &ParseOptions::default()),
+               Ok(Place { priority: 10, target: name("dog.") }));
+
+    c.set_position(5);
+    let options = ParseOptions { max_pointer_jumps: 0, .. ParseOptions::default() };
+    assert!(Place::read_with(4, &mut c, &options).is_err());
+}
+
+#[test]
+fn registered_in_response() {
+    let mut registry = RecordRegistry::new();
+    registry.register::<Place>();
+    let options = ParseOptions { registry: Some(Arc::new(registry)), .. ParseOptions::default() };
+
+    let buf = &[
+        0xbe, 0xef,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,  // counts (0, 1, 0, 0)
+
+        // answer:
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // name
+        0xff, 0x7a,  // type PLACE
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x01, 0x2c,  // TTL
+        0x00, 0x04,  // data length
+        0x00, 0x0a, 0xc0, 0x0c,  // priority, and a pointer to the name
+    ];
+
+    let response = Response::from_bytes_with(buf, options).unwrap();
+    let record = match &response.answers[0] {
+        Answer::Standard { record, .. } => record,
+        otherwise => panic!("Expected standard answer, got {:?}", otherwise),
+    };
+
+    match record {
+        Record::Custom(custom) => {
+            assert_eq!(custom.downcast_ref::<Place>(), Some(&Place { priority: 10, target: name("dog.") }));
+        }
+        otherwise => panic!("Expected custom record, got {:?}", otherwise),
+    }
+
+    assert_eq!(record.type_mnemonic(), "PLACE");
+    assert_eq!(record.to_string(), "10 dog.");
+}
@@ -0,0 +1,143 @@
+#ifndef DNS_H
+#define DNS_H
+
+/* This file is generated by cbindgen from dns-ffi/src/lib.rs. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Whether a function succeeded, and if not, why not.
+typedef enum DnsStatus {
+  // The function succeeded.
+  DNS_STATUS_OK,
+  // One of the pointers passed in was null.
+  DNS_STATUS_NULL_POINTER,
+  // The domain name was not valid UTF-8, or was not a valid name.
+  DNS_STATUS_INVALID_NAME,
+  // The bytes did not parse as a DNS message.
+  DNS_STATUS_MALFORMED_MESSAGE,
+  // The buffer was too small to hold the message. The length it needs
+  // to be has been written to the output length.
+  DNS_STATUS_BUFFER_TOO_SMALL,
+} DnsStatus;
+
+// One of the sections of records in a response.
+//
+// Functions take sections as plain numbers rather than as this type, as
+// C lets any number be passed where an enum is expected, and a number
+// that is not one of these would be undefined behaviour on the Rust side.
+typedef enum DnsSection {
+  // The answers section.
+  DNS_SECTION_ANSWER = 0,
+  // The authoritative nameservers section.
+  DNS_SECTION_AUTHORITY = 1,
+  // The additional records section.
+  DNS_SECTION_ADDITIONAL = 2,
+} DnsSection;
+
+// A parsed response. Its fields can only be got at through functions.
+typedef struct DnsResponse DnsResponse;
+
+// A record from a response, with its data both as bytes and as text.
+//
+// Every pointer in it is owned by the response it came from.
+typedef struct DnsRecord {
+  // The domain name the record is for, as a null-terminated string
+  // ending in a dot.
+  const char *name;
+  // The record’s type number.
+  uint16_t qtype;
+  // The record’s class number. For an OPT pseudo-record, this is the
+  // UDP payload size instead.
+  uint16_t qclass;
+  // The record’s time-to-live in seconds. For an OPT pseudo-record,
+  // this holds the extended error code, version, and flags instead.
+  uint32_t ttl;
+  // The record’s data, as it would be written in a message.
+  const uint8_t *rdata;
+  // How many bytes of data there are.
+  size_t rdata_len;
+  // The record’s data in presentation format, as it would be written
+  // in a zone file, as a null-terminated string.
+  const char *text;
+} DnsRecord;
+
+// Parses the given bytes as a response message. On success, the response
+// is written to `out`, and must be freed with `dns_response_free`.
+//
+// # Safety
+//
+// `bytes` must point to at least `len` readable bytes, and `out` must be
+// a valid pointer to write to.
+enum DnsStatus dns_parse_response(const uint8_t *bytes, size_t len, struct DnsResponse **out);
+
+// Frees a response returned by `dns_parse_response`, along with every
+// string and buffer its records point to. Passing null does nothing.
+//
+// # Safety
+//
+// `response` must be null or a pointer from `dns_parse_response` that
+// has not already been freed.
+void dns_response_free(struct DnsResponse *response);
+
+// Returns the transaction ID of the response.
+//
+// # Safety
+//
+// `response` must be a valid pointer from `dns_parse_response`.
+uint16_t dns_response_transaction_id(const struct DnsResponse *response);
+
+// Returns the flags of the response, as the sixteen bits they are sent
+// as, including the error code in the lowest four.
+//
+// # Safety
+//
+// `response` must be a valid pointer from `dns_parse_response`.
+uint16_t dns_response_flags(const struct DnsResponse *response);
+
+// Returns the number of records in the given section of the response,
+// which is one of the `DnsSection` values. Returns zero if the section is
+// not one of them.
+//
+// # Safety
+//
+// `response` must be a valid pointer from `dns_parse_response`.
+size_t dns_response_count(const struct DnsResponse *response, uint32_t section);
+
+// Returns the record at the given index in the given section of the
+// response, which is one of the `DnsSection` values, or null if there are
+// not that many records in it or the section is not one of them.
+//
+// # Safety
+//
+// `response` must be a valid pointer from `dns_parse_response`. The
+// record must not be used after the response is freed.
+const struct DnsRecord *dns_response_record(const struct DnsResponse *response,
+                                            uint32_t section,
+                                            size_t index);
+
+// Builds a recursive query for the given domain name and record type in
+// the Internet class, and writes it to the given buffer. The length of
+// the message gets written to `out_len`, even if the buffer was too
+// small to hold it, so a buffer of the right size can be tried again.
+//
+// # Safety
+//
+// `name` must be a null-terminated string, `buf` must point to at least
+// `buf_len` writable bytes, and `out_len` must be a valid pointer to
+// write to.
+enum DnsStatus dns_build_query(const char *name,
+                               uint16_t qtype,
+                               uint16_t transaction_id,
+                               uint8_t *buf,
+                               size_t buf_len,
+                               size_t *out_len);
+
+// Returns a description of the given status, as a null-terminated string
+// that never needs to be freed.
+const char *dns_status_message(enum DnsStatus status);
+
+#endif  /* DNS_H */
@@ -0,0 +1,498 @@
+#![warn(deprecated_in_future)]
+#![warn(future_incompatible)]
+#![warn(missing_copy_implementations)]
+#![warn(missing_docs)]
+#![warn(nonstandard_style)]
+#![warn(rust_2018_compatibility)]
+#![warn(rust_2018_idioms)]
+#![warn(single_use_lifetimes)]
+#![warn(trivial_casts, trivial_numeric_casts)]
+#![warn(unused)]
+
+
+//! A C interface to the dns crate, so that network tools written in C or
+//! C++ can parse responses and build queries with dog’s code instead of
+//! their own. The header for it is in `include/dns.h`.
+//!
+//! Responses are parsed all at once into an opaque `DnsResponse`, which
+//! owns every string and byte buffer its records point to. They stay valid
+//! until the response is freed with `dns_response_free`.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use dns::{Answer, MessageBuilder, Name, Response, WireError};
+use dns::record::OPT;
+
+
+/// Whether a function succeeded, and if not, why not.
+#[repr(C)]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DnsStatus {
+
+    /// The function succeeded.
+    Ok,
+
+    /// One of the pointers passed in was null.
+    NullPointer,
+
+    /// The domain name was not valid UTF-8, or was not a valid name.
+    InvalidName,
+
+    /// The bytes did not parse as a DNS message.
+    MalformedMessage,
+
+    /// The buffer was too small to hold the message. The length it needs
+    /// to be has been written to the output length.
+    BufferTooSmall,
+}
+
+/// One of the sections of records in a response.
+///
+/// Functions take sections as plain numbers rather than as this type, as
+/// C lets any number be passed where an enum is expected, and a number
+/// that is not one of these would be undefined behaviour on the Rust side.
+#[repr(C)]
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum DnsSection {
+
+    /// The answers section.
+    Answer = 0,
+
+    /// The authoritative nameservers section.
+    Authority = 1,
+
+    /// The additional records section.
+    Additional = 2,
+}
+
+impl DnsSection {
+
+    /// Returns the section with the given number, if there is one.
+    fn from_u32(number: u32) -> Option<Self> {
+        match number {
+            0 => Some(Self::Answer),
+            1 => Some(Self::Authority),
+            2 => Some(Self::Additional),
+            _ => None,
+        }
+    }
+}
+
+/// A record from a response, with its data both as bytes and as text.
+///
+/// Every pointer in it is owned by the response it came from.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct DnsRecord {
+
+    /// The domain name the record is for, as a null-terminated string
+    /// ending in a dot.
+    pub name: *const c_char,
+
+    /// The record’s type number.
+    pub qtype: u16,
+
+    /// The record’s class number. For an OPT pseudo-record, this is the
+    /// UDP payload size instead.
+    pub qclass: u16,
+
+    /// The record’s time-to-live in seconds. For an OPT pseudo-record,
+    /// this holds the extended error code, version, and flags instead.
+    pub ttl: u32,
+
+    /// The record’s data, as it would be written in a message.
+    pub rdata: *const u8,
+
+    /// How many bytes of data there are.
+    pub rdata_len: usize,
+
+    /// The record’s data in presentation format, as it would be written
+    /// in a zone file, as a null-terminated string.
+    pub text: *const c_char,
+}
+
+/// A parsed response. Its fields can only be got at through functions.
+#[derive(Debug)]
+pub struct DnsResponse {
+    transaction_id: u16,
+    flags: u16,
+    sections: [Vec<DnsRecord>; 3],
+
+    /// The strings and bytes that the records’ pointers point into. These
+    /// live on the heap, so they stay put when the response is moved.
+    strings: Vec<CString>,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl DnsResponse {
+    fn new(response: Response) -> Result<Self, WireError> {
+        let mut this = Self {
+            transaction_id: response.transaction_id,
+            flags: response.flags.to_u16(),
+            sections: [ Vec::new(), Vec::new(), Vec::new() ],
+            strings: Vec::new(),
+            buffers: Vec::new(),
+        };
+
+        let sections = [ response.answers, response.authorities, response.additionals ];
+        for (index, answers) in sections.iter().enumerate() {
+            for answer in answers {
+                let record = this.record(answer)?;
+                this.sections[index].push(record);
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn record(&mut self, answer: &Answer) -> Result<DnsRecord, WireError> {
+        let (name, qtype, qclass, ttl, rdata, text) = match answer {
+            Answer::Standard { qname, qclass, ttl, record } => {
+                let mut rdata = Vec::new();
+                record.write(&mut rdata)?;
+                (qname, record.type_number(), qclass.to_u16(), *ttl, rdata, record.to_string())
+            }
+            Answer::Pseudo { qname, opt } => {
+                (qname, OPT::RR_TYPE, opt.udp_payload_size, opt.ttl(), opt.data.clone(), String::new())
+            }
+        };
+
+        let record = DnsRecord {
+            name: self.string(name.to_fully_qualified()),
+            qtype, qclass, ttl,
+            rdata: rdata.as_ptr(),
+            rdata_len: rdata.len(),
+            text: self.string(text),
+        };
+
+        self.buffers.push(rdata);
+        Ok(record)
+    }
+
+    /// Stores a string for a record to point to. C strings cannot contain
+    /// nulls, so any that were in the data get escaped.
+    fn string(&mut self, text: String) -> *const c_char {
+        let string = CString::new(text.replace('\0', "\\000")).expect("Nulls were escaped");
+        let pointer = string.as_ptr();
+        self.strings.push(string);
+        pointer
+    }
+
+    /// Returns the records in the section with the given number, or `None`
+    /// if there is no section with that number.
+    fn section(&self, number: u32) -> Option<&[DnsRecord]> {
+        DnsSection::from_u32(number).map(|section| &self.sections[section as usize][..])
+    }
+}
+
+
+/// Parses the given bytes as a response message. On success, the response
+/// is written to `out`, and must be freed with `dns_response_free`.
+///
+/// # Safety
+///
+/// `bytes` must point to at least `len` readable bytes, and `out` must be
+/// a valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn dns_parse_response(bytes: *const u8, len: usize, out: *mut *mut DnsResponse) -> DnsStatus {
+    if bytes.is_null() || out.is_null() {
+        return DnsStatus::NullPointer;
+    }
+
+    let bytes = slice::from_raw_parts(bytes, len);
+    match Response::from_bytes(bytes).and_then(DnsResponse::new) {
+        Ok(response) => {
+            *out = Box::into_raw(Box::new(response));
+            DnsStatus::Ok
+        }
+        Err(_) => {
+            *out = ptr::null_mut();
+            DnsStatus::MalformedMessage
+        }
+    }
+}
+
+/// Frees a response returned by `dns_parse_response`, along with every
+/// string and buffer its records point to. Passing null does nothing.
+///
+/// # Safety
+///
+/// `response` must be null or a pointer from `dns_parse_response` that
+/// has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn dns_response_free(response: *mut DnsResponse) {
+    if ! response.is_null() {
+        drop(Box::from_raw(response));
+    }
+}
+
+/// Returns the transaction ID of the response.
+///
+/// # Safety
+///
+/// `response` must be a valid pointer from `dns_parse_response`.
+#[no_mangle]
+pub unsafe extern "C" fn dns_response_transaction_id(response: *const DnsResponse) -> u16 {
+    (*response).transaction_id
+}
+
+/// Returns the flags of the response, as the sixteen bits they are sent
+/// as, including the error code in the lowest four.
+///
+/// # Safety
+///
+/// `response` must be a valid pointer from `dns_parse_response`.
+#[no_mangle]
+pub unsafe extern "C" fn dns_response_flags(response: *const DnsResponse) -> u16 {
+    (*response).flags
+}
+
+/// Returns the number of records in the given section of the response,
+/// which is one of the `DnsSection` values. Returns zero if the section is
+/// not one of them.
+///
+/// # Safety
+///
+/// `response` must be a valid pointer from `dns_parse_response`.
+#[no_mangle]
+pub unsafe extern "C" fn dns_response_count(response: *const DnsResponse, section: u32) -> usize {
+    let response = &*response;
+    response.section(section).map_or(0, <[DnsRecord]>::len)
+}
+
+/// Returns the record at the given index in the given section of the
+/// response, which is one of the `DnsSection` values, or null if there are
+/// not that many records in it or the section is not one of them.
+///
+/// # Safety
+///
+/// `response` must be a valid pointer from `dns_parse_response`. The
+/// record must not be used after the response is freed.
+#[no_mangle]
+pub unsafe extern "C" fn dns_response_record(response: *const DnsResponse, section: u32, index: usize) -> *const DnsRecord {
+    let response = &*response;
+    match response.section(section).and_then(|records| records.get(index)) {
+        Some(record)  => record,
+        None          => ptr::null(),
+    }
+}
+
+/// Builds a recursive query for the given domain name and record type in
+/// the Internet class, and writes it to the given buffer. The length of
+/// the message gets written to `out_len`, even if the buffer was too
+/// small to hold it, so a buffer of the right size can be tried again.
+///
+/// # Safety
+///
+/// `name` must be a null-terminated string, `buf` must point to at least
+/// `buf_len` writable bytes, and `out_len` must be a valid pointer to
+/// write to.
+#[no_mangle]
+pub unsafe extern "C" fn dns_build_query(name: *const c_char, qtype: u16, transaction_id: u16, buf: *mut u8, buf_len: usize, out_len: *mut usize) -> DnsStatus {
+    if name.is_null() || buf.is_null() || out_len.is_null() {
+        return DnsStatus::NullPointer;
+    }
+
+    let qname = match CStr::from_ptr(name).to_str().ok().and_then(|n| n.parse::<Name>().ok()) {
+        Some(qname)  => qname,
+        None         => return DnsStatus::InvalidName,
+    };
+
+    let request = MessageBuilder::query(qname, qtype).transaction_id(transaction_id).build();
+    let bytes = match request.to_bytes() {
+        Ok(bytes)  => bytes,
+        Err(_)     => return DnsStatus::InvalidName,
+    };
+
+    *out_len = bytes.len();
+    if bytes.len() > buf_len {
+        return DnsStatus::BufferTooSmall;
+    }
+
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+    DnsStatus::Ok
+}
+
+/// Returns a description of the given status, as a null-terminated string
+/// that never needs to be freed.
+#[no_mangle]
+pub extern "C" fn dns_status_message(status: DnsStatus) -> *const c_char {
+    let message: &'static [u8] = match status {
+        DnsStatus::Ok                => b"OK\0",
+        DnsStatus::NullPointer       => b"Null pointer\0",
+        DnsStatus::InvalidName       => b"Invalid domain name\0",
+        DnsStatus::MalformedMessage  => b"Malformed message\0",
+        DnsStatus::BufferTooSmall    => b"Buffer too small\0",
+    };
+
+    message.as_ptr().cast()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A response to an MX query for `dog`, with one answer and an OPT
+    /// record in the additional section.
+    const RESPONSE: &[u8] = &[
+        0x0d, 0xcd,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,  // counts (1, 1, 0, 1)
+
+        // query:
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // name
+        0x00, 0x0f,  // type MX
+        0x00, 0x01,  // class IN
+
+        // answer:
+        0xc0, 0x0c,  // name (pointer to the query)
+        0x00, 0x0f,  // type MX
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x03, 0xa5,  // TTL
+        0x00, 0x09,  // data length
+        0x00, 0x0a,  // preference
+        0x04, 0x6d, 0x61, 0x69, 0x6c, 0xc0, 0x0c,  // exchange (mail + pointer)
+
+        // additional:
+        0x00,  // name
+        0x00, 0x29,  // type OPT
+        0x02, 0x00,  // UDP payload size
+        0x00, 0x00, 0x80, 0x00,  // higher bits, version, flags
+        0x00, 0x00,  // data length
+    ];
+
+    fn parse(bytes: &[u8]) -> *mut DnsResponse {
+        let mut response = ptr::null_mut();
+        assert_eq!(unsafe { dns_parse_response(bytes.as_ptr(), bytes.len(), &mut response) }, DnsStatus::Ok);
+        response
+    }
+
+    fn string(pointer: *const c_char) -> &'static str {
+        unsafe { CStr::from_ptr(pointer) }.to_str().unwrap()
+    }
+
+    #[test]
+    fn parse_response() {
+        let response = parse(RESPONSE);
+
+        unsafe {
+            assert_eq!(dns_response_transaction_id(response), 0x0dcd);
+            assert_eq!(dns_response_flags(response), 0x8180);
+            assert_eq!(dns_response_count(response, DnsSection::Answer as u32), 1);
+            assert_eq!(dns_response_count(response, DnsSection::Authority as u32), 0);
+            assert_eq!(dns_response_count(response, DnsSection::Additional as u32), 1);
+            dns_response_free(response);
+        }
+    }
+
+    #[test]
+    fn standard_record() {
+        let response = parse(RESPONSE);
+
+        unsafe {
+            let record = *dns_response_record(response, DnsSection::Answer as u32, 0);
+            assert_eq!(string(record.name), "dog.");
+            assert_eq!((record.qtype, record.qclass, record.ttl), (15, 1, 933));
+            assert_eq!(slice::from_raw_parts(record.rdata, record.rdata_len),
+                       &[ 0x00, 0x0a, 0x04, 0x6d, 0x61, 0x69, 0x6c, 0x03, 0x64, 0x6f, 0x67, 0x00 ]);
+            assert_eq!(string(record.text), "10 mail.dog.");
+            dns_response_free(response);
+        }
+    }
+
+    #[test]
+    fn opt_record() {
+        let response = parse(RESPONSE);
+
+        unsafe {
+            let record = *dns_response_record(response, DnsSection::Additional as u32, 0);
+            assert_eq!(string(record.name), ".");
+            assert_eq!((record.qtype, record.qclass, record.ttl), (41, 512, 0x8000));
+            assert_eq!(record.rdata_len, 0);
+            dns_response_free(response);
+        }
+    }
+
+    #[test]
+    fn record_out_of_range() {
+        let response = parse(RESPONSE);
+
+        unsafe {
+            assert!(dns_response_record(response, DnsSection::Answer as u32, 1).is_null());
+            assert!(dns_response_record(response, DnsSection::Authority as u32, 0).is_null());
+            dns_response_free(response);
+        }
+    }
+
+    #[test]
+    fn invalid_section() {
+        let response = parse(RESPONSE);
+
+        unsafe {
+            assert_eq!(dns_response_count(response, 3), 0);
+            assert!(dns_response_record(response, 3, 0).is_null());
+            assert!(dns_response_record(response, u32::MAX, 0).is_null());
+            dns_response_free(response);
+        }
+    }
+
+    #[test]
+    fn malformed_response() {
+        let mut response = ptr::null_mut();
+        let bytes = &RESPONSE[.. 20];
+        assert_eq!(unsafe { dns_parse_response(bytes.as_ptr(), bytes.len(), &mut response) }, DnsStatus::MalformedMessage);
+        assert!(response.is_null());
+    }
+
+    #[test]
+    fn null_pointers() {
+        let mut response = ptr::null_mut();
+        assert_eq!(unsafe { dns_parse_response(ptr::null(), 0, &mut response) }, DnsStatus::NullPointer);
+        assert_eq!(unsafe { dns_parse_response(RESPONSE.as_ptr(), RESPONSE.len(), ptr::null_mut()) }, DnsStatus::NullPointer);
+        unsafe { dns_response_free(ptr::null_mut()) };
+    }
+
+    #[test]
+    fn build_query() {
+        let mut buf = [ 0_u8; 512 ];
+        let mut len = 0;
+        let status = unsafe { dns_build_query(b"lookup.dog\0".as_ptr().cast(), 15, 0xbeef, buf.as_mut_ptr(), buf.len(), &mut len) };
+
+        assert_eq!(status, DnsStatus::Ok);
+        assert_eq!(&buf[.. len], &[
+            0xbe, 0xef, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x06, 0x6c, 0x6f, 0x6f, 0x6b, 0x75, 0x70, 0x03, 0x64, 0x6f, 0x67, 0x00,
+            0x00, 0x0f, 0x00, 0x01,
+        ]);
+    }
+
+    #[test]
+    fn build_query_buffer_too_small() {
+        let mut buf = [ 0_u8; 8 ];
+        let mut len = 0;
+        let status = unsafe { dns_build_query(b"lookup.dog\0".as_ptr().cast(), 15, 0xbeef, buf.as_mut_ptr(), buf.len(), &mut len) };
+
+        assert_eq!(status, DnsStatus::BufferTooSmall);
+        assert_eq!(len, 28);
+    }
+
+    #[test]
+    fn build_query_invalid_name() {
+        let mut buf = [ 0_u8; 512 ];
+        let mut len = 0;
+        let name = format!("{}.dog\0", "a".repeat(64));
+        let status = unsafe { dns_build_query(name.as_ptr().cast(), 15, 0xbeef, buf.as_mut_ptr(), buf.len(), &mut len) };
+
+        assert_eq!(status, DnsStatus::InvalidName);
+    }
+
+    #[test]
+    fn status_messages() {
+        assert_eq!(string(dns_status_message(DnsStatus::Ok)), "OK");
+        assert_eq!(string(dns_status_message(DnsStatus::BufferTooSmall)), "Buffer too small");
+    }
+}
@@ -0,0 +1,450 @@
+#![warn(deprecated_in_future)]
+#![warn(future_incompatible)]
+#![warn(missing_copy_implementations)]
+#![warn(missing_docs)]
+#![warn(nonstandard_style)]
+#![warn(rust_2018_compatibility)]
+#![warn(rust_2018_idioms)]
+#![warn(single_use_lifetimes)]
+#![warn(trivial_casts, trivial_numeric_casts)]
+#![warn(unused)]
+
+#![deny(unsafe_code)]
+
+
+//! Python bindings for the dns crate, so that scripts can parse responses,
+//! build queries, and look up records with dog’s code. Build and install
+//! the `dogdns` module with `maturin develop` or `pip install .`:
+//!
+//! ```python
+//! import dogdns
+//!
+//! query = dogdns.build_query("lookup.dog", "MX")
+//! response = dogdns.parse_response(data)
+//! for record in response.answers:
+//!     print(record.name, record.type, record.data)
+//!
+//! records = dogdns.lookup("lookup.dog", "A", ["1.1.1.1"])
+//! ```
+
+use std::time::Duration;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use dns::{Answer, ErrorCode, MessageBuilder, Name, Request, Response, TypeInt};
+use dns::presentation::{parse_type_mnemonic, type_mnemonic};
+use dns::record::OPT;
+use dns_transport::{Resolver, ResolveError};
+
+
+create_exception!(dogdns, DnsError, PyException, "The base class of every error raised by this module.");
+create_exception!(dogdns, ParseError, DnsError, "A message could not be parsed, or a name or type was invalid.");
+create_exception!(dogdns, LookupError, DnsError, "A lookup failed, or the nameservers returned an error code.");
+
+
+/// A query from the question section of a response.
+#[pyclass(frozen, get_all, module = "dogdns")]
+#[derive(Debug, Clone)]
+pub struct Query {
+
+    /// The domain name being asked about, ending in a dot.
+    name: String,
+
+    /// The mnemonic of the record type being asked for, such as `"MX"`.
+    #[pyo3(name = "type")]
+    qtype: String,
+
+    /// The number of the record type being asked for.
+    type_number: TypeInt,
+
+    /// The mnemonic of the class being asked about, such as `"IN"`.
+    qclass: String,
+}
+
+#[pymethods]
+impl Query {
+    fn __repr__(&self) -> String {
+        format!("Query({:?}, {:?}, {:?})", self.name, self.qtype, self.qclass)
+    }
+}
+
+impl From<&dns::Query> for Query {
+    fn from(query: &dns::Query) -> Self {
+        Self {
+            name: query.qname.to_fully_qualified(),
+            qtype: type_mnemonic(query.qtype),
+            type_number: query.qtype,
+            qclass: query.qclass.to_string(),
+        }
+    }
+}
+
+
+/// A record from one of the sections of a response.
+#[pyclass(frozen, get_all, module = "dogdns")]
+#[derive(Debug, Clone)]
+pub struct Record {
+
+    /// The domain name the record is for, ending in a dot.
+    name: String,
+
+    /// The mnemonic of the record’s type, such as `"MX"`.
+    #[pyo3(name = "type")]
+    qtype: String,
+
+    /// The number of the record’s type.
+    type_number: TypeInt,
+
+    /// The mnemonic of the record’s class, such as `"IN"`. For an OPT
+    /// pseudo-record, this holds the UDP payload size instead.
+    qclass: String,
+
+    /// The record’s time-to-live in seconds. For an OPT pseudo-record,
+    /// this holds the extended error code, version, and flags instead.
+    ttl: u32,
+
+    /// The record’s data in presentation format, as it would be written
+    /// in a zone file. This is empty for an OPT pseudo-record.
+    data: String,
+
+    /// The record’s data as it was written in the message.
+    rdata: Vec<u8>,
+
+    /// The whole record as a line of a zone file.
+    text: String,
+}
+
+#[pymethods]
+impl Record {
+    fn __repr__(&self) -> String {
+        format!("Record({:?}, {:?}, {:?})", self.name, self.qtype, self.data)
+    }
+
+    fn __str__(&self) -> String {
+        self.text.clone()
+    }
+}
+
+impl Record {
+    fn new(answer: &Answer) -> Result<Self, dns::WireError> {
+        let text = answer.to_string();
+
+        Ok(match answer {
+            Answer::Standard { qname, qclass, ttl, record } => {
+                let mut rdata = Vec::new();
+                record.write(&mut rdata)?;
+
+                Self {
+                    name: qname.to_fully_qualified(),
+                    qtype: record.type_mnemonic(),
+                    type_number: record.type_number(),
+                    qclass: qclass.to_string(),
+                    ttl: *ttl,
+                    data: record.to_string(),
+                    rdata,
+                    text,
+                }
+            }
+            Answer::Pseudo { qname, opt } => {
+                Self {
+                    name: qname.to_fully_qualified(),
+                    qtype: String::from("OPT"),
+                    type_number: OPT::RR_TYPE,
+                    qclass: opt.udp_payload_size.to_string(),
+                    ttl: opt.ttl(),
+                    data: String::new(),
+                    rdata: opt.data.clone(),
+                    text,
+                }
+            }
+        })
+    }
+
+    fn list(answers: &[Answer]) -> PyResult<Vec<Self>> {
+        answers.iter()
+               .map(|answer| Self::new(answer).map_err(|e| ParseError::new_err(e.to_string())))
+               .collect()
+    }
+}
+
+
+/// A parsed response message.
+#[pyclass(frozen, get_all, module = "dogdns")]
+#[derive(Debug, Clone)]
+pub struct ParsedResponse {
+
+    /// The transaction ID, which matches the one in the request.
+    transaction_id: u16,
+
+    /// The flags, as the sixteen bits they are sent as.
+    flags: u16,
+
+    /// The response code, such as `"NOERROR"` or `"NXDOMAIN"`.
+    rcode: String,
+
+    /// The queries from the question section.
+    queries: Vec<Query>,
+
+    /// The records from the answer section.
+    answers: Vec<Record>,
+
+    /// The records from the authority section.
+    authorities: Vec<Record>,
+
+    /// The records from the additional section, including any OPT
+    /// pseudo-record.
+    additionals: Vec<Record>,
+}
+
+#[pymethods]
+impl ParsedResponse {
+    fn __repr__(&self) -> String {
+        format!("ParsedResponse(transaction_id={:#06x}, rcode={:?}, answers={})", self.transaction_id, self.rcode, self.answers.len())
+    }
+}
+
+impl ParsedResponse {
+    fn new(response: &Response) -> PyResult<Self> {
+        Ok(Self {
+            transaction_id: response.transaction_id,
+            flags: response.flags.to_u16(),
+            rcode: rcode_name(response.flags.error_code),
+            queries: response.queries.iter().map(Query::from).collect(),
+            answers: Record::list(&response.answers)?,
+            authorities: Record::list(&response.authorities)?,
+            additionals: Record::list(&response.additionals)?,
+        })
+    }
+}
+
+
+/// A record type, which Python code can give either by its mnemonic, such
+/// as `"MX"`, or by its number.
+#[derive(FromPyObject)]
+enum TypeArgument {
+    Number(TypeInt),
+    Mnemonic(String),
+}
+
+impl TypeArgument {
+    fn to_number(&self) -> PyResult<TypeInt> {
+        match self {
+            Self::Number(number)    => Ok(*number),
+            Self::Mnemonic(text)    => parse_type_mnemonic(text).ok_or_else(|| ParseError::new_err(format!("Unknown record type {:?}", text))),
+        }
+    }
+}
+
+
+/// Parses the bytes of a response message.
+///
+/// Raises `ParseError` if they are not a valid message.
+#[pyfunction]
+fn parse_response(data: &[u8]) -> PyResult<ParsedResponse> {
+    let response = Response::from_bytes(data).map_err(|e| ParseError::new_err(e.to_string()))?;
+    ParsedResponse::new(&response)
+}
+
+/// Builds the bytes of a recursive query for the given domain name and
+/// record type, with an OPT record unless `edns` is false. A random
+/// transaction ID is used unless one is given.
+#[pyfunction]
+#[pyo3(signature = (name, qtype, transaction_id=None, edns=true))]
+fn build_query<'py>(py: Python<'py>, name: &str, qtype: TypeArgument, transaction_id: Option<u16>, edns: bool) -> PyResult<Bound<'py, PyBytes>> {
+    let mut builder = MessageBuilder::query(parse_name(name)?, qtype.to_number()?);
+
+    if let Some(transaction_id) = transaction_id {
+        builder = builder.transaction_id(transaction_id);
+    }
+
+    if edns {
+        builder = builder.edns(Request::additional_record());
+    }
+
+    let bytes = builder.build().to_bytes().map_err(|e| ParseError::new_err(e.to_string()))?;
+    Ok(PyBytes::new(py, &bytes))
+}
+
+/// Looks up the records of the given type for the given domain name by
+/// querying the given nameservers, blocking until the lookup is complete,
+/// and returns the records that answer it. CNAME records get followed.
+///
+/// Raises `LookupError` if the lookup fails, including when the name does
+/// not exist, and `ValueError` if the timeout is negative or not a number.
+#[pyfunction]
+#[pyo3(signature = (name, qtype, nameservers, timeout=5.0, attempts=2))]
+fn lookup(py: Python<'_>, name: &str, qtype: TypeArgument, nameservers: Vec<String>, timeout: f64, attempts: usize) -> PyResult<Vec<Record>> {
+    let name = parse_name(name)?;
+    let qtype = qtype.to_number()?;
+
+    let mut resolver = Resolver::new(nameservers);
+    resolver.timeout = Duration::try_from_secs_f64(timeout)
+                               .map_err(|_| PyValueError::new_err(format!("Invalid timeout: {}", timeout)))?;
+    resolver.attempts = attempts;
+
+    // Let other Python threads run while waiting for the network.
+    let lookup = py.allow_threads(|| resolver.lookup(&name, qtype))
+                   .map_err(|e| LookupError::new_err(resolve_error_message(e)))?;
+
+    Record::list(&lookup.answers)
+}
+
+
+/// The `dogdns` Python module.
+#[pymodule]
+fn dogdns(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse_response, m)?)?;
+    m.add_function(wrap_pyfunction!(build_query, m)?)?;
+    m.add_function(wrap_pyfunction!(lookup, m)?)?;
+
+    m.add_class::<ParsedResponse>()?;
+    m.add_class::<Query>()?;
+    m.add_class::<Record>()?;
+
+    let py = m.py();
+    m.add("DnsError", py.get_type::<DnsError>())?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("LookupError", py.get_type::<LookupError>())?;
+    Ok(())
+}
+
+
+fn parse_name(name: &str) -> PyResult<Name> {
+    name.parse().map_err(|e: dns::WireError| ParseError::new_err(format!("Invalid domain name {:?}: {}", name, e)))
+}
+
+fn rcode_name(rcode: Option<ErrorCode>) -> String {
+    match ErrorCode::mnemonic_of(rcode) {
+        Some(mnemonic)  => mnemonic.into(),
+        None            => format!("RESERVED{}", rcode.map_or(0, ErrorCode::to_bits)),
+    }
+}
+
+fn resolve_error_message(error: ResolveError) -> String {
+    match error {
+        ResolveError::NoNameservers    => "No nameservers were given".into(),
+        ResolveError::Transport(e)     => format!("Failed to reach any nameserver: {:?}", e),
+        ResolveError::TimedOut         => "Timed out".into(),
+        ResolveError::ErrorCode(code)  => format!("Nameserver returned {}", rcode_name(Some(code))),
+        ResolveError::CnameLoop(name)  => format!("CNAME loop at {}", name.to_fully_qualified()),
+        ResolveError::TooManyCnames    => "Too many CNAME records to follow".into(),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CString;
+    use pyo3::types::PyDict;
+
+    /// A response to an MX query for `dog`, with one answer and an OPT
+    /// record in the additional section.
+    const RESPONSE: &[u8] = &[
+        0x0d, 0xcd,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,  // counts (1, 1, 0, 1)
+
+        // query:
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // name
+        0x00, 0x0f,  // type MX
+        0x00, 0x01,  // class IN
+
+        // answer:
+        0xc0, 0x0c,  // name (pointer to the query)
+        0x00, 0x0f,  // type MX
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x03, 0xa5,  // TTL
+        0x00, 0x09,  // data length
+        0x00, 0x0a,  // preference
+        0x04, 0x6d, 0x61, 0x69, 0x6c, 0xc0, 0x0c,  // exchange (mail + pointer)
+
+        // additional:
+        0x00,  // name
+        0x00, 0x29,  // type OPT
+        0x02, 0x00,  // UDP payload size
+        0x00, 0x00, 0x00, 0x00,  // higher bits, version, flags
+        0x00, 0x00,  // data length
+    ];
+
+    /// Runs some Python code with the module imported as `dogdns`.
+    fn run(code: &str) -> PyResult<()> {
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "dogdns")?;
+            dogdns(&module)?;
+
+            let locals = PyDict::new(py);
+            locals.set_item("dogdns", module)?;
+            locals.set_item("RESPONSE", PyBytes::new(py, RESPONSE))?;
+            let code = CString::new(code)?;
+            py.run(&code, None, Some(&locals))
+        })
+    }
+
+    #[test]
+    fn parse() {
+        run(r#"
+response = dogdns.parse_response(RESPONSE)
+assert response.transaction_id == 0x0dcd
+assert response.rcode == "NOERROR"
+assert [ q.name for q in response.queries ] == [ "dog." ]
+
+[ mx ] = response.answers
+assert (mx.name, mx.type, mx.type_number, mx.qclass, mx.ttl) == ("dog.", "MX", 15, "IN", 933)
+assert mx.data == "10 mail.dog."
+assert mx.rdata == b"\x00\x0a\x04mail\x03dog\x00"
+assert str(mx) == "dog. 933 IN MX 10 mail.dog."
+
+[ opt ] = response.additionals
+assert (opt.name, opt.type) == (".", "OPT")
+"#).unwrap();
+    }
+
+    #[test]
+    fn parse_error() {
+        run(r#"
+try:
+    dogdns.parse_response(RESPONSE[:20])
+    assert False
+except dogdns.ParseError as e:
+    assert isinstance(e, dogdns.DnsError)
+    assert "insufficient data" in str(e)
+"#).unwrap();
+    }
+
+    #[test]
+    fn build() {
+        run(r#"
+query = dogdns.build_query("lookup.dog", "MX", transaction_id=0xbeef, edns=False)
+assert query == b"\xbe\xef\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x06lookup\x03dog\x00\x00\x0f\x00\x01"
+assert dogdns.build_query("lookup.dog", 15, transaction_id=0xbeef, edns=False) == query
+assert len(dogdns.build_query("lookup.dog", "A")) == len(query) + 11
+"#).unwrap();
+    }
+
+    #[test]
+    fn build_errors() {
+        run(r#"
+for name, qtype in [ ("a" * 64 + ".dog", "A"), ("lookup.dog", "NOTATYPE") ]:
+    try:
+        dogdns.build_query(name, qtype)
+        assert False
+    except dogdns.ParseError:
+        pass
+"#).unwrap();
+    }
+
+    #[test]
+    fn lookup_without_nameservers() {
+        run(r#"
+try:
+    dogdns.lookup("lookup.dog", "A", [])
+    assert False
+except dogdns.LookupError as e:
+    assert str(e) == "No nameservers were given"
+"#).unwrap();
+    }
+}
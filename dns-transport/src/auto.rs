@@ -1,8 +1,12 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
 use log::*;
+use tokio::time::timeout;
 
-use dns::{Request, Response};
-use super::{Transport, Error, UdpTransport, TcpTransport};
+use dns::Request;
+use super::{Transport, Reply, Error, UdpTransport, TcpTransport};
+use super::udp::{timed_out, DEFAULT_TIMEOUT};
 
 
 /// The **automatic transport**, which uses the UDP transport, then tries
@@ -15,7 +19,7 @@ use super::{Transport, Error, UdpTransport, TcpTransport};
 /// use dns::{Request, Flags, Query, QClass, qtype, record::NS};
 ///
 /// let query = Query {
-///     qname: String::from("dns.lookup.dog"),
+///     qname: "dns.lookup.dog".parse().unwrap(),
 ///     qclass: QClass::IN,
 ///     qtype: qtype!(NS),
 /// };
@@ -28,11 +32,23 @@ use super::{Transport, Error, UdpTransport, TcpTransport};
 /// };
 ///
 /// let transport = AutoTransport::new("8.8.8.8");
-/// transport.send(&request);
+/// transport.exchange(&request);
 /// ```
 #[derive(Debug)]
 pub struct AutoTransport {
     addr: String,
+
+    /// Whether to randomise the case of query names sent over UDP. This is
+    /// on by default.
+    pub randomise_case: bool,
+
+    /// How long to keep listening for more UDP packets after one arrives,
+    /// which is zero by default. See `UdpTransport::receive_window`.
+    pub receive_window: Duration,
+
+    /// How long to wait for each response, over UDP and then over TCP if
+    /// it comes to that, which is five seconds by default.
+    pub timeout: Duration,
 }
 
 impl AutoTransport {
@@ -40,25 +56,29 @@ impl AutoTransport {
     /// Creates a new automatic transport that connects to the given host.
     pub fn new(sa: impl Into<String>) -> Self {
         let addr = sa.into();
-        Self { addr }
+        Self { addr, randomise_case: true, receive_window: Duration::ZERO, timeout: DEFAULT_TIMEOUT }
     }
 }
 
 
 #[async_trait]
 impl Transport for AutoTransport {
-    async fn send(&self, request: &Request) -> Result<Response, Error> {
-        let udp_transport = UdpTransport::new(&self.addr);
-        let udp_response = udp_transport.send(&request).await?;
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        let mut udp_transport = UdpTransport::new(&self.addr);
+        udp_transport.randomise_case = self.randomise_case;
+        udp_transport.receive_window = self.receive_window;
+        udp_transport.timeout = self.timeout;
+        let udp_reply = udp_transport.send_with_details(request).await?;
 
-        if ! udp_response.flags.truncated {
-            return Ok(udp_response);
+        if ! udp_reply.response.flags.truncated {
+            return Ok(udp_reply);
         }
 
         debug!("Truncated flag set, so switching to TCP");
 
         let tcp_transport = TcpTransport::new(&self.addr);
-        let tcp_response = tcp_transport.send(&request).await?;
-        Ok(tcp_response)
+        let tcp_reply = timeout(self.timeout, tcp_transport.send_with_details(request)).await
+                            .map_err(|_| timed_out(self.timeout))??;
+        Ok(Reply { round_trip: udp_reply.round_trip + tcp_reply.round_trip, attempts: udp_reply.attempts + 1, ..tcp_reply })
     }
 }
@@ -0,0 +1,158 @@
+//! Finding the address of a DNS-over-TLS or DNS-over-HTTPS server that was
+//! given by host name, which has to happen before the first request can be
+//! sent to it.
+//!
+//! Usually the operating system looks the name up, as it would any other.
+//! But on a network whose own DNS is broken or tampered with, which is
+//! often the reason for using an encrypted resolver in the first place,
+//! that lookup is the weak spot, so it can be sent to a nameserver of the
+//! user’s choosing instead, or skipped altogether by giving the address.
+
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use log::*;
+use tokio::time::timeout;
+
+use dns::{Request, Answer, Flags, Query, QClass, qtype};
+use dns::record::{Record, A};
+use super::{Transport, AutoTransport, Error};
+
+
+/// How long to wait for the operating system to look up a host, as the
+/// lookup happens on a thread that would otherwise be waited on forever.
+const SYSTEM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the address of a server given by host name gets found.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Bootstrap {
+
+    /// Look it up with the operating system’s resolver, as any other host
+    /// name would be.
+    System,
+
+    /// Look it up by sending a plain DNS query to the given nameserver.
+    Nameserver(String),
+
+    /// Skip looking it up, and connect to the given addresses instead,
+    /// trying each in turn.
+    Addresses(Vec<IpAddr>),
+}
+
+impl Bootstrap {
+
+    /// Finds the addresses of the given host, to be tried in order. Hosts
+    /// that are already IP addresses are returned as they are.
+    pub async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>, Error> {
+        if let Ok(address) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+            return Ok(vec![ address ]);
+        }
+
+        match self {
+            Self::System               => lookup_system(host).await,
+            Self::Addresses(addresses) => Ok(addresses.clone()),
+            Self::Nameserver(server)   => lookup(server, host).await,
+        }
+    }
+}
+
+/// Looks up the addresses of a host with the operating system’s resolver,
+/// giving up if it takes too long.
+async fn lookup_system(host: &str) -> Result<Vec<IpAddr>, Error> {
+    debug!("Looking up {:?} using the system resolver", host);
+
+    let addresses = match timeout(SYSTEM_TIMEOUT, tokio::net::lookup_host((host, 0))).await {
+        Ok(addresses)  => addresses?.map(|sa| sa.ip()).collect::<Vec<_>>(),
+        Err(_)         => return Err(super::udp::timed_out(SYSTEM_TIMEOUT)),
+    };
+
+    if addresses.is_empty() {
+        let message = format!("Host {} has no addresses", host);
+        return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+    }
+
+    Ok(addresses)
+}
+
+/// Looks up the IPv4 addresses of a host by querying the given nameserver.
+async fn lookup(server: &str, host: &str) -> Result<Vec<IpAddr>, Error> {
+    info!("Looking up {:?} using bootstrap nameserver {}", host, server);
+
+    let qname = host.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid host name {:?}", host)))?;
+    let request = Request {
+        transaction_id: rand::random(),
+        flags: Flags::query(),
+        queries: vec![ Query { qname, qclass: QClass::IN, qtype: qtype!(A) } ],
+        additional: None,
+    };
+
+    let response = AutoTransport::new(server).exchange(&request).await?;
+    let addresses = response.answers.iter().filter_map(|answer| match answer {
+        Answer::Standard { record: Record::A(a), .. }  => Some(IpAddr::V4(a.address)),
+        _                                              => None,
+    }).collect::<Vec<_>>();
+
+    if addresses.is_empty() {
+        let message = format!("Bootstrap nameserver {} has no addresses for {}", server, host);
+        return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+    }
+
+    debug!("Bootstrapped {:?} to {:?}", host, addresses);
+    Ok(addresses)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use dns::testing::{MockServer, response_to};
+
+    fn resolve(bootstrap: &Bootstrap, host: &str) -> Result<Vec<IpAddr>, Error> {
+        tokio::runtime::Runtime::new().unwrap().block_on(bootstrap.resolve(host))
+    }
+
+    #[test]
+    fn pinned_address() {
+        let address = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53));
+        assert!(matches!(resolve(&Bootstrap::Addresses(vec![ address ]), "dns.lookup.dog"), Ok(a) if a == vec![ address ]));
+    }
+
+    #[test]
+    fn system() {
+        assert!(matches!(resolve(&Bootstrap::System, "localhost"), Ok(a) if ! a.is_empty()));
+    }
+
+    #[test]
+    fn address_given() {
+        let address = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53));
+        assert!(matches!(resolve(&Bootstrap::System, "192.0.2.53"), Ok(a) if a == vec![ address ]));
+    }
+
+    #[test]
+    fn nameserver() {
+        let server = MockServer::reply_with(|request| {
+            let mut response = response_to(request);
+            response.answers.push(Answer::Standard {
+                qname: request.queries[0].qname.clone(),
+                qclass: QClass::IN,
+                ttl: 300,
+                record: Record::A(A { address: Ipv4Addr::new(192, 0, 2, 80) }),
+            });
+            response
+        }).unwrap();
+
+        let bootstrap = Bootstrap::Nameserver(server.address().to_string());
+        let address = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 80));
+        assert!(matches!(resolve(&bootstrap, "dns.lookup.dog"), Ok(a) if a == vec![ address ]));
+        assert_eq!(server.requests()[0].queries[0].qname.as_str().to_ascii_lowercase(), "dns.lookup.dog.");
+    }
+
+    #[test]
+    fn nameserver_without_addresses() {
+        let server = MockServer::reply_with(response_to).unwrap();
+        let bootstrap = Bootstrap::Nameserver(server.address().to_string());
+        assert!(resolve(&bootstrap, "dns.lookup.dog").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use log::*;
+
+use dns::{Request, Cache};
+use super::{Transport, Reply, Protocol, Connection, Error};
+
+
+/// The **caching transport**, which answers requests from a cache when it
+/// has a fresh response to their query, and sends them down another
+/// transport when it does not, adding the response to the cache.
+///
+/// The cache is shared rather than owned, so every transport going to the
+/// same nameserver can use the same one. Responses from different
+/// nameservers should be kept in different caches, as they can differ.
+pub struct CachingTransport<T = Box<dyn Transport>> {
+    inner: T,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl<T: Transport> CachingTransport<T> {
+
+    /// Creates a new caching transport that sends requests down the given
+    /// transport when the given cache cannot answer them.
+    pub fn new(inner: T, cache: Arc<Mutex<Cache>>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+
+#[async_trait]
+impl<T: Transport> Transport for CachingTransport<T> {
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        // Only requests with one question get cached, so requests with
+        // more than one always get sent.
+        let query = match &request.queries[..] {
+            [ q ]  => q,
+            _      => return self.inner.send_with_details(request).await,
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let cached = self.cache.lock().expect("Cache lock poisoned").get(request, now);
+
+        if let Some(mut response) = cached {
+            debug!("Answering query for {} from the cache", query.qname);
+            response.transaction_id = request.transaction_id;
+            response.queries = request.queries.clone();
+
+            let bytes = response.to_bytes()?;
+            return Ok(Reply { response, bytes, sent: Vec::new(), round_trip: Duration::default(), attempts: 0, protocol: Protocol::Cache, connection: Connection::default() });
+        }
+
+        let reply = self.inner.send_with_details(request).await?;
+        if self.cache.lock().expect("Cache lock poisoned").insert(request, &reply.response, now) {
+            debug!("Added response for {} to the cache", query.qname);
+        }
+
+        Ok(reply)
+    }
+}
@@ -0,0 +1,133 @@
+//! Randomising the case of query names, known as “0x20 encoding”.
+//!
+//! Servers copy the question into their responses byte-for-byte, so a
+//! response to a query for `dNs.LoOkUp.DoG` should contain exactly that
+//! name. Someone trying to spoof a response would have to guess the case
+//! of every letter as well as the transaction ID, which makes forged UDP
+//! responses much harder to get accepted.
+//!
+//! # References
+//!
+//! - [draft-vixie-dnsext-dns0x20](https://tools.ietf.org/html/draft-vixie-dnsext-dns0x20-00) — Use of Bit 0x20 in DNS Labels to Improve Transaction Identity (March 2008)
+
+use log::*;
+
+use dns::{Request, Response, Answer};
+use super::Error;
+
+
+/// Returns a copy of the request with the case of every letter in each
+/// query name picked at random.
+pub fn randomise(request: &Request) -> Request {
+    let mut request = request.clone();
+
+    for query in &mut request.queries {
+        let mixed = query.qname.as_str().chars().map(|c| {
+            if rand::random() { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() }
+        }).collect::<String>();
+
+        query.qname = mixed.parse().expect("Changing case made a name invalid");
+    }
+
+    request
+}
+
+/// Checks that the response echoes the query names from the mixed-case
+/// request exactly, and then puts the names in the response back to how
+/// they were in the original request.
+pub fn verify_and_restore(mixed: &Request, original: &Request, response: &mut Response) -> Result<(), Error> {
+    for ((sent, query), received) in mixed.queries.iter().zip(&original.queries).zip(&mut response.queries) {
+        let (sent, query) = (sent.qname.as_str(), query.qname.as_str());
+
+        if strip_dot(received.qname.as_str()) != strip_dot(sent) {
+            warn!("Sent query for {:?} but response was for {:?}", sent, received.qname);
+            return Err(Error::CaseMismatch { sent: sent.into(), received: received.qname.to_string() });
+        }
+
+        received.qname = restore(received.qname.as_str(), sent, query).parse()?;
+
+        let sections = response.answers.iter_mut()
+                               .chain(response.authorities.iter_mut())
+                               .chain(response.additionals.iter_mut());
+
+        for answer in sections {
+            if let Answer::Standard { qname, .. } = answer {
+                *qname = restore(qname.as_str(), sent, query).parse()?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// If the name ends with the mixed-case query name, replaces that part
+/// with the name as it was originally written.
+fn restore(name: &str, mixed: &str, original: &str) -> String {
+    let (name_stripped, mixed, original) = (strip_dot(name), strip_dot(mixed), strip_dot(original));
+
+    match name_stripped.strip_suffix(mixed) {
+        Some(prefix) if prefix.is_empty() || prefix.ends_with('.') => {
+            let dot = if name.ends_with('.') { "." } else { "" };
+            format!("{}{}{}", prefix, original, dot)
+        }
+        _ => {
+            name.into()
+        }
+    }
+}
+
+fn strip_dot(name: &str) -> &str {
+    name.strip_suffix('.').unwrap_or(name)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dns::{Flags, Query, QClass};
+
+    fn request(name: &str) -> Request {
+        let query = Query { qname: name.parse().unwrap(), qclass: QClass::IN, qtype: 1 };
+        Request { transaction_id: 1, flags: Flags::query(), queries: vec![ query ], additional: None }
+    }
+
+    fn response(name: &str) -> Response {
+        let query = Query { qname: name.parse().unwrap(), qclass: QClass::IN, qtype: 1 };
+        Response {
+            transaction_id: 1,
+            flags: Flags::query(),
+            queries: vec![ query ],
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn randomising_keeps_letters() {
+        let mixed = randomise(&request("dns.lookup.dog"));
+        assert!(mixed.queries[0].qname.as_str().eq_ignore_ascii_case("dns.lookup.dog"));
+    }
+
+    #[test]
+    fn echoed_case_is_restored() {
+        let mut response = response("dNs.LoOkUp.DoG.");
+        let result = verify_and_restore(&request("dNs.LoOkUp.DoG"), &request("dns.lookup.dog"), &mut response);
+        assert!(result.is_ok());
+        assert_eq!(response.queries[0].qname.as_str(), "dns.lookup.dog.");
+    }
+
+    #[test]
+    fn wrong_case_is_rejected() {
+        let mut response = response("dns.lookup.dog.");
+        let result = verify_and_restore(&request("dNs.LoOkUp.DoG"), &request("dns.lookup.dog"), &mut response);
+        assert!(matches!(result, Err(Error::CaseMismatch { .. })));
+    }
+
+    #[test]
+    fn restoring_subdomains() {
+        assert_eq!(restore("_x.LoOkUp.DoG.", "LoOkUp.DoG", "lookup.dog"), "_x.lookup.dog.");
+        assert_eq!(restore("xLoOkUp.DoG.", "LoOkUp.DoG", "lookup.dog"), "xLoOkUp.DoG.");
+        assert_eq!(restore("other.dog.", "LoOkUp.DoG", "lookup.dog"), "other.dog.");
+    }
+}
@@ -0,0 +1,332 @@
+//! Checking the certificates of DNS-over-TLS and DNS-over-HTTPS servers.
+//!
+//! By default, a server’s certificate has to be signed by one of the
+//! certificate authorities the operating system trusts, and be for the host
+//! being connected to. Resolvers in a lab, or inside a company, often have
+//! certificates signed by their own authority instead, which can be trusted
+//! as well, or not checked at all. Going the other way, the certificate can
+//! be required to have one of a list of public keys, so a resolver can be
+//! trusted no matter which authority has signed it.
+//!
+//! Some resolvers check who the client is, too, and only answer clients
+//! with a certificate they trust.
+
+use std::fmt;
+
+use log::*;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslConnector, SslMethod, SslRef, SslVerifyMode};
+use openssl::x509::{X509, X509NameRef, X509Ref};
+
+use super::{Error, Connection};
+
+
+/// How to check the certificates of encrypted resolvers.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TlsSettings {
+
+    /// Whether to accept any certificate at all, for any host.
+    pub insecure: bool,
+
+    /// Extra certificate authorities to trust, in DER format, as well as
+    /// the ones the operating system trusts.
+    pub ca_certificates: Vec<Vec<u8>>,
+
+    /// The SHA-256 digests of the public keys the server’s certificate is
+    /// allowed to have. If any are given, the certificate has to have one
+    /// of them, whoever signed it.
+    pub pins: Vec<Vec<u8>>,
+
+    /// The certificate and key to present to resolvers that ask the client
+    /// who it is, if any.
+    pub identity: Option<ClientIdentity>,
+}
+
+/// A certificate and private key that a client presents to prove who it
+/// is.
+#[derive(PartialEq, Clone)]
+pub struct ClientIdentity {
+
+    /// The client’s certificate, followed by any intermediate ones needed
+    /// to check it, in DER format.
+    certificates: Vec<Vec<u8>>,
+
+    /// The private key that goes with the client’s certificate, in DER
+    /// format.
+    private_key: Vec<u8>,
+}
+
+/// Something wrong with a client identity.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum IdentityError {
+
+    /// There were no certificates that could be read.
+    InvalidCertificate,
+
+    /// The private key could not be read.
+    InvalidKey,
+
+    /// The private key is not the one for the certificate.
+    KeyMismatch,
+}
+
+impl TlsSettings {
+
+    /// Creates an OpenSSL connector that checks certificates with these
+    /// settings, offering the given ALPN protocols, which are in the wire
+    /// format of length-prefixed strings.
+    pub(crate) fn ssl_connector(&self, alpn: &[u8]) -> Result<SslConnector, Error> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        builder.set_alpn_protos(alpn)?;
+
+        if self.insecure {
+            warn!("Not checking the server’s certificate");
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+
+        for der in &self.ca_certificates {
+            let certificate = X509::from_der(der)?;
+            builder.cert_store_mut().add_cert(certificate)?;
+        }
+
+        if let Some(identity) = &self.identity {
+            let certificate = X509::from_der(&identity.certificates[0])?;
+            builder.set_certificate(&certificate)?;
+            for der in &identity.certificates[1 ..] {
+                builder.add_extra_chain_cert(X509::from_der(der)?)?;
+            }
+
+            let key = PKey::private_key_from_der(&identity.private_key)?;
+            builder.set_private_key(&key)?;
+            builder.check_private_key()?;
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Logs the details of the connection that was negotiated, and checks
+    /// the server’s certificate against the pinned public keys, if there
+    /// are any. Returns the details for the reply to report.
+    pub(crate) fn check_connection(&self, ssl: &SslRef) -> Result<Connection, Error> {
+        info!("Negotiated {} with cipher {}", ssl.version_str(), ssl.current_cipher().map_or("(none)", |c| c.name()));
+
+        // The server’s own certificate comes first in the chain it sent.
+        let certificate = match ssl.peer_cert_chain().and_then(|chain| chain.get(0)) {
+            Some(certificate)             => certificate,
+            None if self.pins.is_empty()  => return Ok(negotiated(ssl)),
+            None                          => return Err(Error::PinMismatch { received: Vec::new() }),
+        };
+
+        let pin = spki_pin(certificate)?;
+        info!("Certificate subject: {}", name_string(certificate.subject_name()));
+        info!("Certificate issuer: {}", name_string(certificate.issuer_name()));
+        info!("Certificate valid from {} to {}", certificate.not_before(), certificate.not_after());
+        info!("Certificate public key: sha256/{}", dns::presentation::base64(&pin));
+
+        if self.pins.is_empty() || self.pins.contains(&pin) {
+            Ok(negotiated(ssl))
+        }
+        else {
+            Err(Error::PinMismatch { received: pin })
+        }
+    }
+}
+
+
+impl ClientIdentity {
+
+    /// Reads a client identity from a PEM file of certificates, with the
+    /// client’s own one first, and a PEM file with its private key.
+    pub fn from_pem(certificates: &[u8], private_key: &[u8]) -> Result<Self, IdentityError> {
+        let certificates = certificates_from_pem(certificates).ok_or(IdentityError::InvalidCertificate)?;
+        let key = PKey::private_key_from_pem(private_key).map_err(|_| IdentityError::InvalidKey)?;
+
+        let certificate = X509::from_der(&certificates[0]).map_err(|_| IdentityError::InvalidCertificate)?;
+        let public_key = certificate.public_key().map_err(|_| IdentityError::InvalidCertificate)?;
+        if ! public_key.public_eq(&key) {
+            return Err(IdentityError::KeyMismatch);
+        }
+
+        let private_key = key.private_key_to_der().map_err(|_| IdentityError::InvalidKey)?;
+        Ok(Self { certificates, private_key })
+    }
+}
+
+/// The private key gets left out, so that the identity can be logged along
+/// with the other options without giving it away.
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity")
+         .field("certificates", &self.certificates.len())
+         .field("private_key", &"(redacted)")
+         .finish()
+    }
+}
+
+
+/// Parses a public key pin in the form “sha256/BASE64”, returning the
+/// digest if it is the right length for SHA-256.
+pub fn parse_pin(input: &str) -> Option<Vec<u8>> {
+    let digest = dns::presentation::decode_base64(input.strip_prefix("sha256/")?)?;
+    Some(digest).filter(|d| d.len() == 32)
+}
+
+/// Reads every certificate out of a PEM file, returning them in DER
+/// format, or `None` if there are none.
+pub fn certificates_from_pem(pem: &[u8]) -> Option<Vec<Vec<u8>>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+
+    // Each certificate gets read on its own, as the way OpenSSL signals
+    // the end of a file of several has changed between its versions.
+    let pem = std::str::from_utf8(pem).ok()?;
+    let ders = pem.match_indices(BEGIN)
+                  .map(|(start, _)| X509::from_pem(&pem.as_bytes()[start ..]).and_then(|c| c.to_der()))
+                  .collect::<Result<Vec<_>, _>>().ok()?;
+
+    Some(ders).filter(|d| ! d.is_empty())
+}
+
+/// The version, cipher, and protocol that were negotiated for a
+/// connection.
+fn negotiated(ssl: &SslRef) -> Connection {
+    Connection {
+        tls_version: Some(ssl.version_str().into()),
+        tls_cipher: ssl.current_cipher().map(|c| c.name().into()),
+        alpn: ssl.selected_alpn_protocol().map(|p| String::from_utf8_lossy(p).into()),
+        .. Connection::default()
+    }
+}
+
+/// Returns the SHA-256 digest of a certificate’s DER-encoded subject
+/// public key info, which is what gets pinned.
+fn spki_pin(certificate: &X509Ref) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let spki = certificate.public_key()?.public_key_to_der()?;
+    Ok(openssl::sha::sha256(&spki).to_vec())
+}
+
+/// Writes out a certificate name as its entries separated by commas, such
+/// as “CN=dns.google”.
+fn name_string(name: &X509NameRef) -> String {
+    name.entries().map(|entry| {
+        let key = entry.object().nid().short_name().unwrap_or("?");
+        let value = entry.data().as_utf8().map(|v| v.to_string()).unwrap_or_default();
+        format!("{}={}", key, value)
+    }).collect::<Vec<_>>().join(", ")
+}
+
+
+/// Starts a server on localhost that accepts one TLS connection with a
+/// self-signed certificate, and hands it to the given function to serve.
+/// Returns its address, and the pin of its certificate.
+#[cfg(test)]
+pub(crate) fn tls_server(serve: impl FnOnce(openssl::ssl::SslStream<std::net::TcpStream>) + Send + 'static) -> (std::net::SocketAddr, Vec<u8>) {
+    use openssl::ssl::SslAcceptor;
+
+    let key = test::key();
+    let certificate = test::certificate_for(&key);
+    let pin = spki_pin(&certificate).unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&certificate).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = std::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        if let Ok(stream) = acceptor.accept(stream) {
+            serve(stream);
+        }
+    });
+
+    (address, pin)
+}
+
+/// Starts a server that only completes the handshake, for testing what
+/// clients make of its certificate.
+#[cfg(test)]
+pub(crate) fn handshake_server() -> (std::net::SocketAddr, Vec<u8>) {
+    tls_server(drop)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+
+    pub(super) fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    pub(super) fn certificate_for(key: &PKey<Private>) -> X509 {
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    fn certificate() -> X509 {
+        certificate_for(&key())
+    }
+
+    #[test]
+    fn pin_round_trip() {
+        let certificate = certificate();
+        let pin = spki_pin(&certificate).unwrap();
+        assert_eq!(parse_pin(&format!("sha256/{}", dns::presentation::base64(&pin))), Some(pin));
+    }
+
+    #[test]
+    fn pin_wrong_length() {
+        assert_eq!(parse_pin("sha256/AAAA"), None);
+    }
+
+    #[test]
+    fn pin_without_algorithm() {
+        assert_eq!(parse_pin("47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="), None);
+    }
+
+    #[test]
+    fn pem_certificates() {
+        let certificate = certificate();
+        let pem = [ certificate.to_pem().unwrap(), certificate.to_pem().unwrap() ].concat();
+        assert_eq!(certificates_from_pem(&pem), Some(vec![ certificate.to_der().unwrap(); 2 ]));
+    }
+
+    #[test]
+    fn pem_without_certificates() {
+        assert_eq!(certificates_from_pem(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn identity() {
+        let key = key();
+        let certificate = certificate_for(&key);
+        let identity = ClientIdentity::from_pem(&certificate.to_pem().unwrap(), &key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        assert_eq!(identity.certificates, vec![ certificate.to_der().unwrap() ]);
+    }
+
+    #[test]
+    fn identity_key_redacted() {
+        let key = key();
+        let identity = ClientIdentity::from_pem(&certificate_for(&key).to_pem().unwrap(), &key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        assert_eq!(format!("{:?}", identity), "ClientIdentity { certificates: 1, private_key: \"(redacted)\" }");
+    }
+
+    #[test]
+    fn identity_key_mismatch() {
+        let certificate = certificate();
+        assert_eq!(ClientIdentity::from_pem(&certificate.to_pem().unwrap(), &key().private_key_to_pem_pkcs8().unwrap()),
+                   Err(IdentityError::KeyMismatch));
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use js_sys::{Date, Uint8Array};
+use log::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::RequestInit;
+
+use dns::Request;
+use super::{Transport, Reply, Protocol, Connection, Error, verify};
+
+
+/// The **fetch transport**, which sends DNS-over-HTTPS requests using the
+/// browser’s fetch API, for when dog is compiled to WebAssembly.
+///
+/// It works in both windows and web workers. The server has to allow
+/// cross-origin requests with CORS, as most public DoH servers do.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dns_transport::{Transport, FetchTransport};
+/// use dns::{Request, Flags, Query, QClass, qtype, record::A};
+///
+/// let query = Query {
+///     qname: "dns.lookup.dog".parse().unwrap(),
+///     qclass: QClass::IN,
+///     qtype: qtype!(A),
+/// };
+///
+/// let request = Request {
+///     transaction_id: 0xABCD,
+///     flags: Flags::query(),
+///     queries: vec![ query ],
+///     additional: None,
+/// };
+///
+/// let transport = FetchTransport::new("https://cloudflare-dns.com/dns-query");
+/// transport.exchange(&request);
+/// ```
+#[derive(Debug)]
+pub struct FetchTransport {
+    url: String,
+}
+
+impl FetchTransport {
+
+    /// Creates a new fetch transport that sends requests to the given URL.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for FetchTransport {
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        // Instant panics in the browser, so time the request with the
+        // JavaScript clock instead.
+        let started = Date::now();
+        let bytes = request.to_bytes()?;
+        info!("Sending {} bytes of data to {:?}", bytes.len(), self.url);
+
+        let init = RequestInit::new();
+        init.set_method("POST");
+        init.set_body(&Uint8Array::from(&bytes[..]));
+
+        let http_request = web_sys::Request::new_with_str_and_init(&self.url, &init).map_err(fetch_error)?;
+        http_request.headers().set("Content-Type", "application/dns-message").map_err(fetch_error)?;
+        http_request.headers().set("Accept",       "application/dns-message").map_err(fetch_error)?;
+
+        let response: web_sys::Response = JsFuture::from(fetch(&http_request)).await
+            .map_err(fetch_error)?
+            .unchecked_into();
+        debug!("Response: {}", response.status());
+
+        if response.status() != 200 {
+            return Err(Error::BadRequest);
+        }
+
+        debug!("Reading body...");
+        let body = JsFuture::from(response.array_buffer().map_err(fetch_error)?).await.map_err(fetch_error)?;
+        let buf = Uint8Array::new(&body).to_vec();
+
+        info!("Received {} bytes of data", buf.len());
+        let response = verify::parse_response(&buf)?;
+        verify::check_response(request, &response)?;
+
+        let round_trip = Duration::from_secs_f64((Date::now() - started).max(0.0) / 1000.0);
+        Ok(Reply { response, bytes: buf, sent: bytes, round_trip, attempts: 1, protocol: Protocol::HTTPS, connection: Connection::default() })
+    }
+}
+
+
+#[wasm_bindgen]
+extern "C" {
+
+    /// The global `fetch` function, which exists in both windows and web
+    /// workers, unlike the methods on `Window` and `WorkerGlobalScope`.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch(request: &web_sys::Request) -> js_sys::Promise;
+}
+
+/// Converts an error thrown by the browser into a transport error.
+fn fetch_error(error: JsValue) -> Error {
+    let message = match error.dyn_ref::<js_sys::Error>() {
+        Some(error)  => String::from(error.message()),
+        None         => format!("{:?}", error),
+    };
+
+    warn!("Fetch failed: {}", message);
+    Error::FetchError(message)
+}
@@ -1,12 +1,26 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
 use async_trait::async_trait;
-use hyper_tls::HttpsConnector;
-use hyper::Body;
+use hyper::{Body, Uri};
 use hyper::body::HttpBody as _;
 use hyper::Client;
+use hyper::client::HttpConnector;
+use hyper::client::connect::{Connected, Connection as HyperConnection, HttpInfo};
+use hyper::client::connect::dns::Name;
+use hyper::service::Service;
 use log::*;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 
-use dns::{Request, Response};
-use super::{Transport, Error};
+use dns::Request;
+use super::{Transport, Reply, Protocol, Connection, Error, Bootstrap, TlsSettings, verify};
+use super::ssl::{self, SslStream};
 
 
 /// The **HTTPS transport**, which uses Hyper.
@@ -18,7 +32,7 @@ use super::{Transport, Error};
 /// use dns::{Request, Flags, Query, QClass, qtype, record::A};
 ///
 /// let query = Query {
-///     qname: String::from("dns.lookup.dog"),
+///     qname: "dns.lookup.dog".parse().unwrap(),
 ///     qclass: QClass::IN,
 ///     qtype: qtype!(A),
 /// };
@@ -31,39 +45,53 @@ use super::{Transport, Error};
 /// };
 ///
 /// let transport = HttpsTransport::new("https://cloudflare-dns.com/dns-query");
-/// transport.send(&request);
+/// transport.exchange(&request);
 /// ```
 #[derive(Debug)]
 pub struct HttpsTransport {
     url: String,
+
+    /// The client that sends every request, which keeps its connections
+    /// open so later requests to the same server can re-use them.
+    client: Client<HttpsConnector>,
 }
 
 impl HttpsTransport {
 
     /// Creates a new HTTPS transport that connects to the given URL.
     pub fn new(url: impl Into<String>) -> Self {
-        Self { url: url.into() }
+        Self::with_settings(url, Bootstrap::System, &TlsSettings::default())
+    }
+
+    /// Creates a new HTTPS transport that connects to the given URL,
+    /// finding the address of its host in the given way, and checking its
+    /// certificate with the given settings.
+    pub fn with_settings(url: impl Into<String>, bootstrap: Bootstrap, tls: &TlsSettings) -> Self {
+        let mut http = HttpConnector::new_with_resolver(BootstrapResolver { bootstrap });
+        http.enforce_http(false);
+
+        let https = HttpsConnector { http, tls: tls.clone() };
+        let client = Client::builder().build::<_, hyper::Body>(https);
+        Self { url: url.into(), client }
     }
 }
 
 #[async_trait]
 impl Transport for HttpsTransport {
-    async fn send(&self, request: &Request) -> Result<Response, Error> {
-        let https = HttpsConnector::new();
-        let client = Client::builder().build::<_, hyper::Body>(https);
-
-        let bytes = request.to_bytes().expect("failed to serialise request");
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        let started = Instant::now();
+        let bytes = request.to_bytes()?;
         info!("Sending {} bytes of data to {:?}", bytes.len(), self.url);
 
-        let request = hyper::Request::builder()
+        let http_request = hyper::Request::builder()
             .method("POST")
             .uri(&self.url)
             .header("Content-Type", "application/dns-message")
             .header("Accept",       "application/dns-message")
-            .body(Body::from(bytes))
+            .body(Body::from(bytes.clone()))
             .expect("Failed to build request");  // we control the request, so this should never fail
 
-        let mut response = client.request(request).await?;
+        let mut response = self.client.request(http_request).await.map_err(connect_error)?;
         debug!("Response: {}", response.status());
         debug!("Headers: {:#?}", response.headers());
 
@@ -71,6 +99,13 @@ impl Transport for HttpsTransport {
             return Err(Error::BadRequest);
         }
 
+        let connection = Connection {
+            address: response.extensions().get::<HttpInfo>().map(HttpInfo::remote_addr),
+            http_version: Some(format!("{:?}", response.version())),
+            http_status: Some(response.status().as_u16()),
+            .. response.extensions().get::<Connection>().cloned().unwrap_or_default()
+        };
+
         debug!("Reading body...");
         let mut buf = Vec::new();
         while let Some(chunk) = response.body_mut().data().await {
@@ -78,8 +113,175 @@ impl Transport for HttpsTransport {
         }
 
         info!("Received {} bytes of data", buf.len());
-        let response = Response::from_bytes(&buf)?;
+        let response = verify::parse_response(&buf)?;
+        verify::check_response(request, &response)?;
+
+        Ok(Reply { response, bytes: buf, sent: bytes, round_trip: started.elapsed(), attempts: 1, protocol: Protocol::HTTPS, connection })
+    }
+}
+
+
+/// Hyper wraps up the errors that happen while connecting, so ones that
+/// came from negotiating TLS, such as the certificate not having a pinned
+/// key, get dug back out to be reported as they are.
+fn connect_error(error: hyper::Error) -> Error {
+    let is_ours = std::error::Error::source(&error).is_some_and(|cause| cause.is::<ConnectFailure>());
+    if ! is_ours {
+        return error.into();
+    }
+
+    match error.into_cause().map(|cause| cause.downcast::<ConnectFailure>()) {
+        Some(Ok(failure))  => failure.0,
+        _                  => unreachable!("Cause was checked above"),
+    }
+}
+
+
+/// Connects to the server for Hyper, negotiating TLS with OpenSSL so that
+/// the server’s certificate can be checked against the pinned keys, and
+/// the details of the connection reported, as with DNS-over-TLS.
+#[derive(Clone)]
+struct HttpsConnector {
+    http: HttpConnector<BootstrapResolver>,
+    tls: TlsSettings,
+}
+
+impl Service<Uri> for HttpsConnector {
+    type Response = HttpsStream;
+    type Error = ConnectFailure;
+    type Future = Pin<Box<dyn Future<Output = Result<HttpsStream, ConnectFailure>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ConnectFailure>> {
+        self.http.poll_ready(cx).map_err(|e| ConnectFailure(io::Error::other(e).into()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let mut http = self.http.clone();
+        let tls = self.tls.clone();
+
+        Box::pin(async move {
+            connect(&mut http, &tls, uri).await.map_err(ConnectFailure)
+        })
+    }
+}
+
+/// Opens a TCP connection to the host in the URI, and negotiates TLS over
+/// it.
+async fn connect(http: &mut HttpConnector<BootstrapResolver>, tls: &TlsSettings, uri: Uri) -> Result<HttpsStream, Error> {
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_owned();
+    let stream = http.call(uri).await.map_err(io::Error::other)?;
+
+    info!("Connecting");
+    let configuration = tls.ssl_connector(b"\x08http/1.1")?.configure()?.verify_hostname(! tls.insecure);
+    let stream = ssl::connect(configuration, &host, stream).await?;
+    let connection = tls.check_connection(stream.ssl())?;
+
+    Ok(HttpsStream { stream, connection })
+}
+
+impl fmt::Debug for HttpsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpsConnector").field("tls", &self.tls).finish()
+    }
+}
+
+/// A connection to the server, along with the details of how its TLS was
+/// negotiated, which get added to every response sent down it.
+struct HttpsStream {
+    stream: SslStream<TcpStream>,
+    connection: Connection,
+}
+
+impl HyperConnection for HttpsStream {
+    fn connected(&self) -> Connected {
+        self.stream.get_ref().connected().extra(self.connection.clone())
+    }
+}
+
+impl AsyncRead for HttpsStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for HttpsStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Something that went wrong while connecting, carried through Hyper so
+/// that it can be reported as it is.
+#[derive(Debug)]
+struct ConnectFailure(Error);
+
+impl fmt::Display for ConnectFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl std::error::Error for ConnectFailure {}
+
+
+/// Looks up the host in the URL for Hyper, in whichever way the transport
+/// was told to, falling back to the operating system’s resolver.
+#[derive(Debug, Clone)]
+struct BootstrapResolver {
+    bootstrap: Bootstrap,
+}
+
+impl Service<Name> for BootstrapResolver {
+    type Response = std::vec::IntoIter<IpAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let bootstrap = self.bootstrap.clone();
+
+        Box::pin(async move {
+            let addresses = bootstrap.resolve(name.as_str()).await
+                .map_err(|e| match e {
+                    Error::NetworkError(e)  => e,
+                    e                       => io::Error::other(format!("{:?}", e)),
+                })?;
+
+            Ok(addresses.into_iter())
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dns::{Flags, Query, QClass};
+    use crate::certs::handshake_server;
+
+    #[test]
+    fn pin_mismatch() {
+        let (address, pin) = handshake_server();
+
+        let tls = TlsSettings { insecure: true, pins: vec![ vec![0; 32] ], .. TlsSettings::default() };
+        let transport = HttpsTransport::with_settings(format!("https://{}/dns-query", address), Bootstrap::System, &tls);
+
+        let query = Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 };
+        let request = Request { transaction_id: 0x1234, flags: Flags::query(), queries: vec![ query ], additional: None };
 
-        Ok(response)
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(matches!(runtime.block_on(transport.send_with_details(&request)),
+                         Err(Error::PinMismatch { received }) if received == pin));
     }
 }
@@ -13,6 +13,8 @@
 
 #![deny(unsafe_code)]
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 use derive_more::From;
 
@@ -22,54 +24,357 @@ use dns::{Request, Response};
 // Re-export the five transport types, as well as the Tokio runtime, so that
 // the dog crate can just use something called “Runtime” without worrying
 // about which runtime it actually is.
+//
+// None of these can open sockets from inside a browser, so when compiling
+// to WebAssembly, the only transport is the one that uses fetch instead.
 
+#[cfg(not(target_arch = "wasm32"))]
 mod auto;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::auto::AutoTransport;
 
+#[cfg(not(target_arch = "wasm32"))]
 mod udp;
-pub use self::udp::UdpTransport;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::udp::{UdpTransport, DEFAULT_TIMEOUT};
 
+#[cfg(not(target_arch = "wasm32"))]
 mod tcp;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::tcp::TcpTransport;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod ssl;
+
+#[cfg(not(target_arch = "wasm32"))]
 mod tls;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::tls::TlsTransport;
 
+#[cfg(not(target_arch = "wasm32"))]
 mod https;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::https::HttpsTransport;
 
+#[cfg(target_arch = "wasm32")]
+mod fetch;
+#[cfg(target_arch = "wasm32")]
+pub use self::fetch::FetchTransport;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod bootstrap;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::bootstrap::Bootstrap;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod certs;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::certs::{TlsSettings, ClientIdentity, IdentityError, parse_pin, certificates_from_pem};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::cache::CachingTransport;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod resolver;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::resolver::{Resolver, Lookup, ResolveError};
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub use self::resolver::AsyncResolver;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod case;
+mod verify;
+
+#[cfg(not(target_arch = "wasm32"))]
 pub use tokio::runtime::Runtime;
 
 
 
-/// The trait implemented by all four transport types.
-#[async_trait]
-pub trait Transport {
+/// The trait implemented by all four transport types. Other crates can
+/// implement it too, to send requests some other way, and have a
+/// `Resolver` use it with `Resolver::with_transports`.
+///
+/// Futures in a browser cannot be sent between threads, so when compiling
+/// to WebAssembly, the futures this returns are not `Send`.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait Transport: Sync {
 
     /// Convert the request to bytes, send it over the network, wait for a
     /// response, deserialise it from bytes, and return it, asynchronously.
-    async fn send(&self, request: &Request) -> Result<Response, Error>;
+    async fn exchange(&self, request: &Request) -> Result<Response, Error> {
+        let reply = self.send_with_details(request).await?;
+        Ok(reply.response)
+    }
+
+    /// Send the request in the same way as `send`, but also return the
+    /// bytes of the response exactly as they were received, along with how
+    /// long it took to arrive and how it got here.
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error>;
+
+    /// Send every request at once, returning the replies in the same order
+    /// as the requests. By default each one gets sent on its own, but the
+    /// TCP and TLS transports send them all down a single connection.
+    async fn send_all(&self, requests: &[Request]) -> Vec<Result<Reply, Error>> {
+        futures_util::future::join_all(requests.iter().map(|request| self.send_with_details(request))).await
+    }
+}
+
+/// Boxed transports are transports too, so code that is generic over the
+/// transport it uses can be given one picked at runtime.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        (**self).send_with_details(request).await
+    }
+
+    async fn send_all(&self, requests: &[Request]) -> Vec<Result<Reply, Error>> {
+        (**self).send_all(requests).await
+    }
+}
+
+/// A response received by a transport, along with the details of how it
+/// was received.
+#[derive(Debug)]
+pub struct Reply {
+
+    /// The response that was received.
+    pub response: Response,
+
+    /// The bytes of the response message exactly as they were received,
+    /// without any length prefix the transport may have added.
+    pub bytes: Vec<u8>,
+
+    /// The bytes of the request message exactly as they were sent, after
+    /// any changes the transport made to it, such as randomising the case
+    /// of its names or asking to keep the connection alive. This is empty
+    /// if the response did not come from sending one.
+    pub sent: Vec<u8>,
+
+    /// How long it took from starting to send the request to having the
+    /// response, including any time spent connecting, and any earlier
+    /// attempts that had to be retried.
+    pub round_trip: Duration,
+
+    /// How many times the request had to be sent, which is zero if the
+    /// response came from a cache, and two if the automatic transport got
+    /// a truncated response over UDP and sent the request again over TCP.
+    /// That is the only time a transport sends a request more than once:
+    /// requests that time out or fail are not retried.
+    pub attempts: u32,
+
+    /// The protocol the response arrived over.
+    pub protocol: Protocol,
+
+    /// The details of the connection the response arrived over.
+    pub connection: Connection,
+}
+
+/// Details of the connection a response arrived over, as far as the
+/// transport that received it knows them.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Connection {
+
+    /// The address the request was sent to, after looking up the host
+    /// name of the nameserver, if it had one.
+    pub address: Option<std::net::SocketAddr>,
+
+    /// The version of TLS that was negotiated, such as “TLSv1.3”.
+    pub tls_version: Option<String>,
+
+    /// The TLS cipher suite that was negotiated.
+    pub tls_cipher: Option<String>,
+
+    /// The application protocol the server picked using ALPN, if it
+    /// picked one.
+    pub alpn: Option<String>,
+
+    /// The version of HTTP the response arrived over, such as “HTTP/1.1”.
+    pub http_version: Option<String>,
+
+    /// The status code of the HTTP response.
+    pub http_status: Option<u16>,
+}
+
+/// The protocols a response can arrive over.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Protocol {
+
+    /// Plain DNS over UDP.
+    UDP,
+
+    /// Plain DNS over TCP.
+    TCP,
+
+    /// DNS-over-TLS.
+    TLS,
+
+    /// DNS-over-HTTPS.
+    HTTPS,
+
+    /// No protocol at all, as the response came from a cache rather than
+    /// being sent anywhere.
+    Cache,
+
+    /// No protocol at all, as the response was made up from the entries in
+    /// a hosts file rather than being sent anywhere.
+    Hosts,
 }
 
+
 /// Something that can go wrong making a DNS request.
 #[derive(Debug, From)]  // can't be PartialEq due to tokio error
 pub enum Error {
 
     /// There was a problem with the network sending the request or receiving
     /// a response asynchorously.
+    #[cfg(not(target_arch = "wasm32"))]
     NetworkError(tokio::io::Error),
 
     /// There was a problem making an HTTPS request.
+    #[cfg(not(target_arch = "wasm32"))]
     HttpError(hyper::Error),
 
-    /// There was a problem making a TLS request.
-    TlsError(native_tls::Error),
+    /// There was a problem setting up a TLS connection, such as the
+    /// server’s certificate failing to check out.
+    #[cfg(not(target_arch = "wasm32"))]
+    SslError(openssl::ssl::Error),
 
-    /// The data in the response did not parse correctly from the DNS wire
-    /// protocol format.
+    /// The server’s certificate did not have any of the public keys it was
+    /// pinned to.
+    #[cfg(not(target_arch = "wasm32"))]
+    PinMismatch {
+
+        /// The SHA-256 digest of the public key the certificate did have,
+        /// which is empty if the server sent no certificate.
+        received: Vec<u8>,
+    },
+
+    /// The browser failed to fetch the response, or to read its body.
+    /// Contains the error it threw, converted to a string.
+    #[cfg(target_arch = "wasm32")]
+    #[from(ignore)]
+    FetchError(String),
+
+    /// The data in a message did not parse correctly from, or could not be
+    /// written in, the DNS wire protocol format.
     WireError(dns::WireError),
 
+    /// A response arrived, but did not parse correctly from the DNS wire
+    /// protocol format.
+    #[from(ignore)]
+    MalformedResponse {
+
+        /// What was wrong with the response.
+        error: dns::WireError,
+
+        /// The bytes of the response exactly as they were received, so
+        /// they can still be looked at.
+        bytes: Vec<u8>,
+    },
+
     /// The server specifically indicated that the request we sent it was
     /// malformed.
     BadRequest,
+
+    /// The transaction ID in the response did not match the one in the
+    /// request.
+    TransactionIdMismatch {
+
+        /// The transaction ID that was sent.
+        sent: u16,
+
+        /// The transaction ID in the response.
+        received: u16,
+    },
+
+    /// The question section of the response did not ask the same thing as
+    /// the request.
+    QuestionMismatch {
+
+        /// The queries that were sent.
+        sent: Vec<dns::Query>,
+
+        /// The queries in the response.
+        received: Vec<dns::Query>,
+    },
+
+    /// A UDP response came from a different address than the one the
+    /// request was sent to.
+    WrongSource {
+
+        /// The address the request was sent to.
+        expected: std::net::SocketAddr,
+
+        /// The address the response came from.
+        received: std::net::SocketAddr,
+    },
+
+    /// The query name in the response did not match the mixed-case name
+    /// that was sent, so the response may have been spoofed.
+    CaseMismatch {
+
+        /// The query name that was sent.
+        sent: String,
+
+        /// The query name in the response.
+        received: String,
+    },
+
+    /// Nothing was listening on the port a UDP request was sent to, which
+    /// the network reported with an ICMP port unreachable message.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[from(ignore)]
+    PortUnreachable(std::net::SocketAddr),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(stack: openssl::error::ErrorStack) -> Self {
+        Self::SslError(stack.into())
+    }
+}
+
+/// The ways of failing to reach a nameserver that get told apart, so that
+/// scripts can react to each one differently.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum NetworkFailure {
+
+    /// Nothing was listening on the nameserver’s UDP port.
+    PortUnreachable,
+
+    /// There was no route to the nameserver’s network or host.
+    NetworkUnreachable,
+
+    /// Connecting to the nameserver, or waiting for it, took too long.
+    Timeout,
+
+    /// The nameserver refused the TCP connection.
+    ConnectionRefused,
+}
+
+impl Error {
+
+    /// Works out which way of failing to reach the nameserver this error
+    /// is, if it is one of the ones that get told apart.
+    pub fn network_failure(&self) -> Option<NetworkFailure> {
+        #[cfg(not(target_arch = "wasm32"))]
+        use std::io::ErrorKind;
+
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::PortUnreachable(_)  => Some(NetworkFailure::PortUnreachable),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::NetworkError(e)     => match e.kind() {
+                ErrorKind::NetworkUnreachable | ErrorKind::HostUnreachable  => Some(NetworkFailure::NetworkUnreachable),
+                ErrorKind::TimedOut                                         => Some(NetworkFailure::Timeout),
+                ErrorKind::ConnectionRefused                                => Some(NetworkFailure::ConnectionRefused),
+                _                                                           => None,
+            },
+            _                         => None,
+        }
+    }
 }
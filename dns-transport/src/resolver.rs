@@ -0,0 +1,560 @@
+//! A stub resolver, for programs that want the records for a domain
+//! rather than the response to one query.
+//!
+//! It sends queries to recursive nameservers over the transports in this
+//! crate, retrying and moving between nameservers when one fails, and
+//! follows CNAME records to the records the caller asked for.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use log::*;
+use tokio::time::timeout;
+
+use dns::{Request, Response, Answer, Flags, Query, QClass, ErrorCode, TypeInt, Name, TxidGenerator, RandomTxids, qtype};
+use dns::record::{Record, CNAME};
+use super::{Transport, AutoTransport, Error, Runtime};
+
+
+/// A **stub resolver**, which sends queries to a list of recursive
+/// nameservers and returns the records for a domain, without making the
+/// caller deal with the transports directly.
+///
+/// Each query is sent to the nameservers in turn, retrying a few times
+/// before moving on to the next one, and starting with whichever server
+/// answered most recently. If the answer is an alias, the resolver follows
+/// the chain of CNAME records, issuing further queries if the server did
+/// not include the records for the target.
+///
+/// Queries get sent over UDP, switching to TCP for truncated responses,
+/// unless the resolver has been given some other way to reach each
+/// nameserver with `with_transports`. The resolver is generic over the
+/// type of transport it uses, which can be a boxed one picked at runtime.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dns_transport::Resolver;
+/// use dns::{qtype, record::A};
+///
+/// let resolver = Resolver::new(vec![ "1.1.1.1".into(), "8.8.8.8".into() ]);
+/// let lookup = resolver.lookup(&"dns.lookup.dog".parse().unwrap(), qtype!(A));
+/// ```
+///
+/// This blocks the thread until the lookup is complete, creating a Tokio
+/// runtime to run it in, so it cannot be used from inside one. Code that
+/// is already running in a runtime should use an `AsyncResolver` instead,
+/// which needs the `async` feature.
+pub struct Resolver<T = AutoTransport> {
+
+    /// The addresses of the nameservers to send queries to, in order of
+    /// preference.
+    pub nameservers: Vec<String>,
+
+    /// The class of records to look up.
+    pub qclass: QClass,
+
+    /// How many times to send a query to each nameserver before moving on
+    /// to the next one.
+    pub attempts: usize,
+
+    /// How long to wait for each response before giving up on it.
+    pub timeout: Duration,
+
+    /// The most CNAME records to follow before giving up.
+    pub max_cnames: usize,
+
+    /// The index of the nameserver that most recently sent a response.
+    preferred: AtomicUsize,
+
+    /// Creates the transport to send queries to each nameserver with.
+    make_transport: Box<MakeTransport<T>>,
+
+    /// Picks the transaction ID of each query.
+    txids: Box<dyn TxidGenerator>,
+}
+
+/// A function that creates the transport for a nameserver, given its
+/// address.
+type MakeTransport<T> = dyn Fn(&str) -> T + Send + Sync;
+
+/// A stub resolver that works in the same way as a `Resolver`, but whose
+/// lookups are futures that run in the caller’s Tokio runtime, rather than
+/// blocking the thread in a runtime of their own.
+///
+/// This is only available with the `async` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dns_transport::{Resolver, AsyncResolver};
+/// use dns::{qtype, record::A};
+///
+/// # async fn lookup() {
+/// let mut resolver = Resolver::new(vec![ "1.1.1.1".into() ]);
+/// resolver.attempts = 3;
+///
+/// let resolver = AsyncResolver::from(resolver);
+/// let lookup = resolver.lookup(&"dns.lookup.dog".parse().unwrap(), qtype!(A)).await;
+/// # }
+/// ```
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncResolver<T = AutoTransport> {
+    resolver: Resolver<T>,
+}
+
+/// The result of a successful lookup.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Lookup {
+
+    /// The domain name that was originally asked for.
+    pub name: Name,
+
+    /// The type of record that was asked for.
+    pub qtype: TypeInt,
+
+    /// The targets of each CNAME record that was followed, in order. The
+    /// records in `answers` belong to the last of these, or to the original
+    /// name if this is empty.
+    pub aliases: Vec<Name>,
+
+    /// The records of the requested type. This is empty if the name exists
+    /// but has no records of that type.
+    pub answers: Vec<Answer>,
+
+    /// Every response that was received during the lookup.
+    pub responses: Vec<Response>,
+}
+
+/// Something that can go wrong resolving a domain name.
+#[derive(Debug)]
+pub enum ResolveError {
+
+    /// The resolver was not given any nameservers to query.
+    NoNameservers,
+
+    /// None of the nameservers could be reached, or they all sent back
+    /// unusable responses. Contains the error from the last attempt.
+    Transport(Error),
+
+    /// The query timed out on every nameserver.
+    TimedOut,
+
+    /// The nameservers responded with an error code, such as NXDOMAIN.
+    ErrorCode(ErrorCode),
+
+    /// A CNAME record pointed back to a name that had already been seen.
+    CnameLoop(Name),
+
+    /// There were more CNAME records to follow than the limit allows.
+    TooManyCnames,
+}
+
+
+impl Resolver {
+
+    /// Creates a new resolver that queries the given nameservers, using
+    /// the default number of attempts, timeout, and CNAME limit.
+    pub fn new(nameservers: Vec<String>) -> Self {
+        Self {
+            nameservers,
+            qclass: QClass::IN,
+            attempts: 2,
+            timeout: Duration::from_secs(5),
+            max_cnames: 8,
+            preferred: AtomicUsize::new(0),
+            make_transport: Box::new(|nameserver| AutoTransport::new(nameserver)),
+            txids: Box::new(RandomTxids),
+        }
+    }
+}
+
+impl<T: Transport> Resolver<T> {
+
+    /// Makes the resolver send queries over the transports that the
+    /// function creates for each nameserver, rather than over UDP and TCP.
+    /// This lets lookups use any implementation of `Transport`, such as
+    /// one that talks to a local daemon or a test double.
+    pub fn with_transports<U: Transport>(self, make_transport: impl Fn(&str) -> U + Send + Sync + 'static) -> Resolver<U> {
+        Resolver {
+            nameservers: self.nameservers,
+            qclass: self.qclass,
+            attempts: self.attempts,
+            timeout: self.timeout,
+            max_cnames: self.max_cnames,
+            preferred: self.preferred,
+            make_transport: Box::new(make_transport),
+            txids: self.txids,
+        }
+    }
+
+    /// Makes the resolver pick the transaction ID of each query from the
+    /// given source, rather than at random, so tests can send the same
+    /// queries every time.
+    pub fn with_txids(mut self, txids: impl TxidGenerator + 'static) -> Self {
+        self.txids = Box::new(txids);
+        self
+    }
+
+    /// Looks up the records of the given type for the given domain name,
+    /// blocking until the lookup is complete.
+    pub fn lookup(&self, name: &Name, qtype: TypeInt) -> Result<Lookup, ResolveError> {
+        let mut runtime = Runtime::new().map_err(|e| ResolveError::Transport(e.into()))?;
+        runtime.block_on(self.lookup_async(name, qtype))
+    }
+
+    /// Looks up the records of the given type for the given domain name,
+    /// without blocking. This is what both kinds of resolver use.
+    async fn lookup_async(&self, name: &Name, qtype: TypeInt) -> Result<Lookup, ResolveError> {
+        let mut current = name.clone();
+        let mut aliases: Vec<Name> = Vec::new();
+        let mut responses = Vec::new();
+
+        loop {
+            let response = self.query(&current, qtype).await?;
+            let chain = follow_chain(&response.answers, &current, qtype);
+
+            for target in chain.aliases {
+                if target == *name || aliases.contains(&target) {
+                    return Err(ResolveError::CnameLoop(target));
+                }
+                else if aliases.len() >= self.max_cnames {
+                    return Err(ResolveError::TooManyCnames);
+                }
+
+                debug!("Following CNAME from {:?} to {:?}", current, target);
+                aliases.push(target.clone());
+                current = target;
+            }
+
+            responses.push(response);
+
+            // If the chain ended at a name whose records were not included,
+            // go and ask for them; otherwise, this is as far as it goes.
+            if ! chain.answers.is_empty() || ! chain.followed {
+                let name = name.clone();
+                return Ok(Lookup { name, qtype, aliases, answers: chain.answers, responses });
+            }
+
+            debug!("Response did not include records for {:?}, so querying again", current);
+        }
+    }
+
+    /// Sends a single query, trying each nameserver in turn until one of
+    /// them sends back a usable response.
+    async fn query(&self, name: &Name, qtype: TypeInt) -> Result<Response, ResolveError> {
+        let count = self.nameservers.len();
+        if count == 0 {
+            return Err(ResolveError::NoNameservers);
+        }
+
+        let start = self.preferred.load(Ordering::Relaxed) % count;
+        let mut last_error = ResolveError::TimedOut;
+
+        for offset in 0 .. count {
+            let index = (start + offset) % count;
+            let nameserver = &self.nameservers[index];
+            let transport = (self.make_transport)(nameserver);
+
+            for attempt in 1 ..= self.attempts {
+                let request = self.make_request(name, qtype);
+                info!("Querying {:?} (attempt {} of {})", nameserver, attempt, self.attempts);
+
+                match timeout(self.timeout, transport.exchange(&request)).await {
+                    Ok(Ok(response)) => {
+                        match response.flags.error_code {
+                            Some(rcode @ ErrorCode::ServerFailure) |
+                            Some(rcode @ ErrorCode::QueryRefused) |
+                            Some(rcode @ ErrorCode::NotImplemented) => {
+                                // Asking the same server again won’t help,
+                                // but a different one might have better luck.
+                                warn!("Nameserver {:?} responded with {:?}", nameserver, rcode);
+                                last_error = ResolveError::ErrorCode(rcode);
+                                break;
+                            }
+                            Some(rcode) => {
+                                self.preferred.store(index, Ordering::Relaxed);
+                                return Err(ResolveError::ErrorCode(rcode));
+                            }
+                            None => {
+                                self.preferred.store(index, Ordering::Relaxed);
+                                return Ok(response);
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        warn!("Query to {:?} failed: {:?}", nameserver, e);
+                        last_error = ResolveError::Transport(e);
+                    }
+                    Err(_) => {
+                        warn!("Query to {:?} timed out", nameserver);
+                        last_error = ResolveError::TimedOut;
+                    }
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn make_request(&self, name: &Name, qtype: TypeInt) -> Request {
+        Request {
+            transaction_id: self.txids.next_txid(),
+            flags: Flags::query(),
+            queries: vec![ Query { qname: name.clone(), qclass: self.qclass, qtype } ],
+            additional: Some(Request::additional_record()),
+        }
+    }
+}
+
+
+impl<T> fmt::Debug for Resolver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver")
+         .field("nameservers", &self.nameservers)
+         .field("qclass", &self.qclass)
+         .field("attempts", &self.attempts)
+         .field("timeout", &self.timeout)
+         .field("max_cnames", &self.max_cnames)
+         .field("preferred", &self.preferred)
+         .finish()
+    }
+}
+
+
+#[cfg(feature = "async")]
+impl AsyncResolver {
+
+    /// Creates a new resolver that queries the given nameservers, using
+    /// the default number of attempts, timeout, and CNAME limit. To change
+    /// any of those, create a `Resolver` and convert it instead.
+    pub fn new(nameservers: Vec<String>) -> Self {
+        Self::from(Resolver::new(nameservers))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: Transport> AsyncResolver<T> {
+
+    /// Looks up the records of the given type for the given domain name.
+    pub async fn lookup(&self, name: &Name, qtype: TypeInt) -> Result<Lookup, ResolveError> {
+        self.resolver.lookup_async(name, qtype).await
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> From<Resolver<T>> for AsyncResolver<T> {
+    fn from(resolver: Resolver<T>) -> Self {
+        Self { resolver }
+    }
+}
+
+
+/// The records found by walking the answer section of one response.
+#[derive(PartialEq, Debug)]
+struct Chain {
+
+    /// The targets of the CNAME records that were followed.
+    aliases: Vec<Name>,
+
+    /// The records of the requested type belonging to the last name.
+    answers: Vec<Answer>,
+
+    /// Whether any CNAME records were followed at all.
+    followed: bool,
+}
+
+/// Walks through the answers in a response, starting at the given name and
+/// following any CNAME records, until reaching records of the requested type
+/// or a name with no more aliases.
+fn follow_chain(answers: &[Answer], name: &Name, qtype: TypeInt) -> Chain {
+    let mut current = name.clone();
+    let mut aliases = Vec::new();
+
+    loop {
+        let matching = answers.iter()
+                              .filter(|a| answer_matches(a, &current, qtype))
+                              .cloned()
+                              .collect::<Vec<_>>();
+
+        if ! matching.is_empty() || qtype == qtype!(CNAME) {
+            let followed = ! aliases.is_empty();
+            return Chain { aliases, answers: matching, followed };
+        }
+
+        let target = answers.iter().find_map(|a| match a {
+            Answer::Standard { qname, record: Record::CNAME(cname), .. } if *qname == current => Some(cname.domain.clone()),
+            _ => None,
+        });
+
+        match target {
+            Some(target) if ! aliases.contains(&target) && target != *name => {
+                aliases.push(target.clone());
+                current = target;
+            }
+            _ => {
+                let followed = ! aliases.is_empty();
+                return Chain { aliases, answers: Vec::new(), followed };
+            }
+        }
+    }
+}
+
+fn answer_matches(answer: &Answer, name: &Name, qtype: TypeInt) -> bool {
+    match answer {
+        Answer::Standard { qname, record, .. }  => qname == name && record.type_number() == qtype,
+        Answer::Pseudo { .. }                   => false,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dns::record::A;
+    use std::net::Ipv4Addr;
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn cname(owner: &str, target: &str) -> Answer {
+        let record = Record::CNAME(CNAME { domain: name(target) });
+        Answer::Standard { qname: name(owner), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    fn a(owner: &str, last_octet: u8) -> Answer {
+        let record = Record::A(A { address: Ipv4Addr::new(10, 0, 0, last_octet) });
+        Answer::Standard { qname: name(owner), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    #[test]
+    fn direct_answer() {
+        let answers = vec![ a("lookup.dog.", 1) ];
+
+        assert_eq!(follow_chain(&answers, &name("lookup.dog"), qtype!(A)), Chain {
+            aliases: vec![],
+            answers: vec![ a("lookup.dog.", 1) ],
+            followed: false,
+        });
+    }
+
+    #[test]
+    fn chain_in_one_response() {
+        let answers = vec![ cname("www.lookup.dog.", "lookup.dog."), a("lookup.dog.", 1) ];
+
+        assert_eq!(follow_chain(&answers, &name("www.lookup.dog"), qtype!(A)), Chain {
+            aliases: vec![ name("lookup.dog.") ],
+            answers: vec![ a("lookup.dog.", 1) ],
+            followed: true,
+        });
+    }
+
+    #[test]
+    fn chain_with_missing_target() {
+        let answers = vec![ cname("www.lookup.dog.", "lookup.dog.") ];
+
+        assert_eq!(follow_chain(&answers, &name("www.lookup.dog"), qtype!(A)), Chain {
+            aliases: vec![ name("lookup.dog.") ],
+            answers: vec![],
+            followed: true,
+        });
+    }
+
+    #[test]
+    fn chain_loop_stops() {
+        let answers = vec![ cname("one.dog.", "two.dog."), cname("two.dog.", "one.dog.") ];
+
+        assert_eq!(follow_chain(&answers, &name("one.dog"), qtype!(A)), Chain {
+            aliases: vec![ name("two.dog.") ],
+            answers: vec![],
+            followed: true,
+        });
+    }
+
+    #[test]
+    fn asking_for_cname() {
+        let answers = vec![ cname("www.lookup.dog.", "lookup.dog."), a("lookup.dog.", 1) ];
+
+        assert_eq!(follow_chain(&answers, &name("www.lookup.dog"), qtype!(CNAME)), Chain {
+            aliases: vec![],
+            answers: vec![ cname("www.lookup.dog.", "lookup.dog.") ],
+            followed: false,
+        });
+    }
+
+    fn mock_server() -> dns::testing::MockServer {
+        dns::testing::MockServer::reply_with(|request| {
+            let mut response = dns::testing::response_to(request);
+            let qname = request.queries[0].qname.clone();
+            if qname.as_str().eq_ignore_ascii_case("www.lookup.dog.") {
+                response.answers.push(cname("www.lookup.dog.", "lookup.dog."));
+            }
+            else {
+                response.answers.push(a(qname.as_str(), 1));
+            }
+            response
+        }).unwrap()
+    }
+
+    #[test]
+    fn lookup_following_cname() {
+        let server = mock_server();
+        let resolver = Resolver::new(vec![ server.address().to_string() ]);
+
+        let lookup = resolver.lookup(&name("www.lookup.dog"), qtype!(A)).unwrap();
+        assert_eq!(lookup.aliases, vec![ name("lookup.dog.") ]);
+        assert_eq!(lookup.answers.len(), 1);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn lookup_with_own_txids() {
+        let server = mock_server();
+        let resolver = Resolver::new(vec![ server.address().to_string() ])
+                           .with_txids(dns::SequentialTxids::new(0x1000));
+
+        resolver.lookup(&name("www.lookup.dog"), qtype!(A)).unwrap();
+        let txids = server.requests().iter().map(|r| r.transaction_id).collect::<Vec<_>>();
+        assert_eq!(txids, vec![ 0x1000, 0x1001 ]);
+    }
+
+    /// A transport that answers without going anywhere, and fails for
+    /// the nameserver called `broken`.
+    struct Canned(String);
+
+    #[async_trait::async_trait]
+    impl Transport for Canned {
+        async fn send_with_details(&self, request: &Request) -> Result<crate::Reply, Error> {
+            if self.0 == "broken" {
+                return Err(Error::NetworkError(std::io::ErrorKind::ConnectionRefused.into()));
+            }
+
+            let mut response = dns::testing::response_to(request);
+            response.answers.push(a(request.queries[0].qname.as_str(), 9));
+            Ok(crate::Reply { response, bytes: Vec::new(), sent: Vec::new(), round_trip: Duration::default(), attempts: 1, protocol: crate::Protocol::UDP, connection: crate::Connection::default() })
+        }
+    }
+
+    #[test]
+    fn lookup_with_own_transports() {
+        let resolver = Resolver::new(vec![ "broken".into(), "canned".into() ])
+                           .with_transports(|nameserver| Canned(nameserver.into()));
+
+        let lookup = resolver.lookup(&name("lookup.dog"), qtype!(A)).unwrap();
+        assert_eq!(lookup.answers, vec![ a("lookup.dog.", 9) ]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_lookup() {
+        let server = mock_server();
+        let resolver = AsyncResolver::new(vec![ server.address().to_string() ]);
+
+        let mut runtime = Runtime::new().unwrap();
+        let lookup = runtime.block_on(resolver.lookup(&name("lookup.dog"), qtype!(A))).unwrap();
+        assert_eq!(lookup.answers, vec![ a("lookup.dog.", 1) ]);
+    }
+}
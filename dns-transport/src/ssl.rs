@@ -0,0 +1,150 @@
+//! Running OpenSSL’s TLS streams on top of Tokio’s asynchronous ones.
+//!
+//! OpenSSL reads from and writes to a blocking stream. Handing it one that
+//! says “would block” whenever the Tokio stream underneath is not ready
+//! makes it give up and report the same, at which point the task waits to
+//! be woken by Tokio, and then tries again from where it left off.
+
+use std::future::poll_fn;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use openssl::ssl::{self, ConnectConfiguration, HandshakeError, MidHandshakeSslStream, SslRef};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::Error;
+
+
+/// A TLS stream over a Tokio stream.
+#[derive(Debug)]
+pub(crate) struct SslStream<S>(ssl::SslStream<Blocking<S>>);
+
+/// Negotiates TLS over the stream, sending the given domain in SNI, and
+/// checking the certificate against it if the configuration says to.
+pub(crate) async fn connect<S>(configuration: ConnectConfiguration, domain: &str, stream: S) -> Result<SslStream<S>, Error>
+where S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut start = Some((configuration, stream));
+    let mut mid: Option<MidHandshakeSslStream<Blocking<S>>> = None;
+
+    poll_fn(|cx| {
+        let result = match (start.take(), mid.take()) {
+            (Some((configuration, stream)), _)  => configuration.connect(domain, Blocking { stream, waker: Some(cx.waker().clone()) }),
+            (None, Some(mut handshake))         => { handshake.get_mut().waker = Some(cx.waker().clone()); handshake.handshake() }
+            (None, None)                        => unreachable!("Handshake polled after it finished"),
+        };
+
+        match result {
+            Ok(stream)                              => Poll::Ready(Ok(SslStream(stream))),
+            Err(HandshakeError::WouldBlock(again))  => { mid = Some(again); Poll::Pending }
+            Err(e)                                  => Poll::Ready(Err(handshake_error(e))),
+        }
+    }).await
+}
+
+/// Turns a failed handshake into the error that caused it.
+fn handshake_error<S>(error: HandshakeError<S>) -> Error {
+    match error {
+        HandshakeError::SetupFailure(stack)  => stack.into(),
+        HandshakeError::Failure(mid) |
+        HandshakeError::WouldBlock(mid)      => Error::SslError(mid.into_error()),
+    }
+}
+
+impl<S> SslStream<S> {
+
+    /// The negotiated TLS session, to get its details from.
+    pub(crate) fn ssl(&self) -> &SslRef {
+        self.0.ssl()
+    }
+
+    /// The stream underneath.
+    pub(crate) fn get_ref(&self) -> &S {
+        &self.0.get_ref().stream
+    }
+
+    /// Runs an OpenSSL operation with the task’s waker to hand, turning
+    /// its “would block” into waiting for Tokio to wake the task up.
+    fn poll_with<T>(&mut self, cx: &mut Context<'_>, operation: impl FnOnce(&mut ssl::SslStream<Blocking<S>>) -> io::Result<T>) -> Poll<io::Result<T>> {
+        self.0.get_mut().waker = Some(cx.waker().clone());
+        let result = operation(&mut self.0);
+        self.0.get_mut().waker = None;
+
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock  => Poll::Pending,
+            result                                           => Poll::Ready(result),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for SslStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_with(cx, |stream| stream.read(buf))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for SslStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_with(cx, |stream| stream.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_with(cx, |stream| stream.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let closed = this.poll_with(cx, |stream| match stream.shutdown() {
+            Ok(_)                                                 => Ok(()),
+            Err(e) if e.code() == ssl::ErrorCode::ZERO_RETURN    => Ok(()),
+            Err(e)                                                => Err(e.into_io_error().unwrap_or_else(io::Error::other)),
+        });
+
+        match closed {
+            Poll::Ready(Ok(()))  => Pin::new(&mut this.0.get_mut().stream).poll_shutdown(cx),
+            otherwise            => otherwise,
+        }
+    }
+}
+
+
+/// A Tokio stream made to look like a blocking one to OpenSSL, which says
+/// it would block whenever the stream is not ready, after arranging for
+/// the task to be woken up when it is.
+#[derive(Debug)]
+struct Blocking<S> {
+    stream: S,
+
+    /// The waker of the task that is running an OpenSSL operation, which
+    /// is only there while it is.
+    waker: Option<Waker>,
+}
+
+impl<S> Blocking<S> {
+    fn poll<T>(&mut self, poll: impl FnOnce(Pin<&mut S>, &mut Context<'_>) -> Poll<io::Result<T>>) -> io::Result<T>
+    where S: Unpin,
+    {
+        let waker = self.waker.as_ref().expect("Stream used outside of a task");
+        match poll(Pin::new(&mut self.stream), &mut Context::from_waker(waker)) {
+            Poll::Ready(result)  => result,
+            Poll::Pending        => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> Read for Blocking<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.poll(|stream, cx| stream.poll_read(cx, buf))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Write for Blocking<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll(|stream, cx| stream.poll_write(cx, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll(|stream, cx| stream.poll_flush(cx))
+    }
+}
@@ -1,10 +1,14 @@
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
 use async_trait::async_trait;
 use log::*;
 use tokio::net::TcpStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use dns::{Request, Response};
-use super::{Transport, Error};
+use dns::{Answer, Request, TcpMessageBuffer, frame_tcp_message};
+use super::{Transport, Reply, Protocol, Connection, Error, verify};
 
 
 /// The **TCP transport**, which uses the stdlib.
@@ -16,7 +20,7 @@ use super::{Transport, Error};
 /// use dns::{Request, Flags, Query, QClass, qtype, record::MX};
 ///
 /// let query = Query {
-///     qname: String::from("dns.lookup.dog"),
+///     qname: "dns.lookup.dog".parse().unwrap(),
 ///     qclass: QClass::IN,
 ///     qtype: qtype!(MX),
 /// };
@@ -29,92 +33,366 @@ use super::{Transport, Error};
 /// };
 ///
 /// let transport = TcpTransport::new("8.8.8.8");
-/// transport.send(&request);
+/// transport.exchange(&request);
 /// ```
 ///
 /// # Reference
 ///
 /// - [RFC 1035 §4.2.2](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 /// - [RFC 7766](https://tools.ietf.org/html/rfc1035) — DNS Transport over TCP, Implementation Requirements (March 2016)
+/// - [RFC 7828](https://tools.ietf.org/html/rfc7828) — The edns-tcp-keepalive EDNS0 Option (April 2016)
 #[derive(Debug)]
 pub struct TcpTransport {
     addr: String,
+
+    /// The connection left open after pipelining, if the server said it
+    /// would keep it open, along with when the server will close it.
+    idle: Mutex<Option<(TcpStream, Instant)>>,
 }
 
 impl TcpTransport {
 
     /// Creates a new TCP transport that connects to the given host.
     pub fn new(sa: impl Into<String>) -> Self {
-        Self { addr: sa.into() }
+        Self { addr: sa.into(), idle: Mutex::new(None) }
     }
 }
 
 
 #[async_trait]
 impl Transport for TcpTransport {
-    async fn send(&self, request: &Request) -> Result<Response, Error> {
-        let mut stream =
-            if self.addr.contains(':') {
-                TcpStream::connect(&*self.addr).await?
-            }
-            else {
-                TcpStream::connect((&*self.addr, 53)).await?
-            };
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        let started = Instant::now();
+        let mut stream = self.connect().await?;
+        let connection = Connection { address: stream.peer_addr().ok(), .. Connection::default() };
         info!("Created stream");
 
-        // The message is prepended with the length when sent over TCP,
-        // so the server knows how long it is (RFC 1035 §4.2.2)
-        let mut bytes = request.to_bytes().expect("failed to serialise request");
-        let len_bytes = (bytes.len() as u16).to_be_bytes();
-        bytes.insert(0, len_bytes[0]);
-        bytes.insert(1, len_bytes[1]);
+        info!("Sending request to {} over TCP", self.addr);
+        let sent = with_keepalive(request).to_bytes()?;
+        write_message(&mut stream, &sent).await?;
+
+        info!("Waiting to receive...");
+        let bytes = read_message(&mut stream, &mut TcpMessageBuffer::new()).await?;
+        info!("Received {} bytes of data", bytes.len());
+
+        let response = verify::parse_response(&bytes)?;
+        verify::check_response(request, &response)?;
+        Ok(Reply { response, bytes, sent, round_trip: started.elapsed(), attempts: 1, protocol: Protocol::TCP, connection })
+    }
 
-        info!("Sending {} bytes of data to {} over TCP", bytes.len(), self.addr);
+    async fn send_all(&self, requests: &[Request]) -> Vec<Result<Reply, Error>> {
+        self.send_pipelined(requests).await
+    }
+}
 
-        let written_len = stream.write(&bytes).await?;
-        debug!("Wrote {} bytes", written_len);
+impl TcpTransport {
 
-        info!("Waiting to receive...");
-        let mut buf = [0; 4096];
-        let mut read_len = stream.read(&mut buf[..]).await?;
+    /// Sends every request down a single connection without waiting for
+    /// responses in between, and matches up the responses by transaction
+    /// ID in whatever order the server sends them (RFC 7766 §6.2.1.1). The
+    /// results are in the same order as the requests, which should each
+    /// have a different transaction ID.
+    ///
+    /// If the server advertises an idle timeout with the edns-tcp-keepalive
+    /// option, the connection is kept open for that long afterwards, and
+    /// the next batch of requests gets sent down it instead of a new one.
+    pub async fn send_pipelined(&self, requests: &[Request]) -> Vec<Result<Reply, Error>> {
+        let requests = requests.iter().map(with_keepalive).collect::<Vec<_>>();
+        let mut results = requests.iter().map(|_| None).collect::<Vec<_>>();
 
-        if read_len == 0 {
-            panic!("Received no bytes!");
+        let mut stream = None;
+        if let Some(mut idle) = self.take_idle() {
+            info!("Re-using connection kept alive from last time");
+            let connection = tcp_connection(&idle);
+            match pipeline(&mut idle, &requests, &mut results, &connection).await {
+                Ok(())  => stream = Some(idle),
+                Err(e)  => {
+                    // The server may have closed the connection without us
+                    // noticing, so the requests that have not been
+                    // answered get another go down a new connection.
+                    debug!("Kept-alive connection failed: {:?}", e);
+                }
+            }
         }
-        else if read_len == 1 {
-            info!("Received one byte of data");
-            let second_read_len = stream.read(&mut buf[1..]).await?;
-            if second_read_len == 0 {
-                panic!("Received no bytes the second time!");
+
+        if results.iter().any(Option::is_none) {
+            let unanswered = (0 .. requests.len()).filter(|i| results[*i].is_none()).collect::<Vec<_>>();
+            let retry_requests = unanswered.iter().map(|i| requests[*i].clone()).collect::<Vec<_>>();
+            let mut retry_results = unanswered.iter().map(|_| None).collect::<Vec<_>>();
+
+            let outcome = match self.connect().await {
+                Ok(mut new)  => {
+                    let connection = tcp_connection(&new);
+                    let outcome = pipeline(&mut new, &retry_requests, &mut retry_results, &connection).await;
+                    stream = Some(new);
+                    outcome
+                }
+                Err(e) => Err(e.into()),
+            };
+
+            if let Err(e) = outcome {
+                stream = None;
+                fail_remaining(&mut retry_results, e);
             }
 
-            read_len += second_read_len;
+            for (i, result) in unanswered.into_iter().zip(retry_results) {
+                results[i] = result;
+            }
+        }
+
+        let results = results.into_iter().flatten().collect::<Vec<_>>();
+        if let (Some(stream), Some(timeout)) = (stream, keepalive_timeout(&results)) {
+            info!("Keeping connection open for {:?}", timeout);
+            self.keep_idle(stream, Instant::now() + timeout);
+        }
+
+        results
+    }
+
+    /// Takes the connection left open last time, if there is one and the
+    /// server should not have closed it yet.
+    fn take_idle(&self) -> Option<TcpStream> {
+        let (stream, deadline) = self.idle.lock().unwrap().take()?;
+        if Instant::now() < deadline { Some(stream) } else { None }
+    }
+
+    fn keep_idle(&self, stream: TcpStream, deadline: Instant) {
+        *self.idle.lock().unwrap() = Some((stream, deadline));
+    }
+
+    async fn connect(&self) -> io::Result<TcpStream> {
+        if self.addr.contains(':') {
+            TcpStream::connect(&*self.addr).await
         }
         else {
-            info!("Received {} bytes of data", read_len);
+            TcpStream::connect((&*self.addr, 53)).await
+        }
+    }
+}
+
+
+/// Adds the edns-tcp-keepalive option to the request’s OPT record, if it
+/// has one, as the option is only allowed over TCP (RFC 7828 §3.2.1).
+fn with_keepalive(request: &Request) -> Request {
+    let mut request = request.clone();
+    if let Some(opt) = &mut request.additional {
+        opt.add_tcp_keepalive();
+    }
+
+    request
+}
+
+/// The idle timeout the server advertised in the last response that had
+/// one, which is what the connection gets kept open for. A timeout of
+/// zero means the server wants the connection closed.
+fn keepalive_timeout(results: &[Result<Reply, Error>]) -> Option<Duration> {
+    let timeout = results.iter().rev().filter_map(|result| result.as_ref().ok()).find_map(|reply| {
+        reply.response.additionals.iter().find_map(|answer| {
+            match answer {
+                Answer::Pseudo { opt, .. }  => opt.tcp_keepalive(),
+                _                           => None,
+            }
+        })
+    })?;
+
+    if timeout.as_millis() > 0 { Some(timeout) } else { None }
+}
+
+/// Writes a message to the stream with its length in front, as is needed
+/// for both TCP and TLS, in a single write.
+pub(crate) async fn write_message(stream: &mut (impl AsyncWrite + Unpin), message: &[u8]) -> io::Result<()> {
+    stream.write_all(&frame_tcp_message(message)?).await
+}
+
+/// Reads the next length-prefixed message from the stream, carrying on
+/// from whatever is left in the buffer from previous reads.
+pub(crate) async fn read_message(stream: &mut (impl AsyncRead + Unpin), buffer: &mut TcpMessageBuffer) -> io::Result<Vec<u8>> {
+    let mut chunk = [0; 4096];
+
+    loop {
+        if let Some(message) = buffer.next_message() {
+            return Ok(message);
+        }
+
+        let len = stream.read(&mut chunk).await?;
+        debug!("Read {} bytes", len);
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed before the whole response arrived"));
         }
 
-        let total_len = u16::from_be_bytes([buf[0], buf[1]]);
-        if read_len - 2 == usize::from(total_len) {
-            let response = Response::from_bytes(&buf[2 .. read_len])?;
-            return Ok(response);
+        buffer.extend(&chunk[.. len]);
+    }
+}
+
+/// The details of a plain TCP connection, which are only where it goes.
+fn tcp_connection(stream: &TcpStream) -> Connection {
+    Connection { address: stream.peer_addr().ok(), .. Connection::default() }
+}
+
+/// Sends all the requests down the stream at once, then reads responses
+/// into the results until every request has one. The replies are given the
+/// details of the connection, and TLS as their protocol if it says a
+/// version was negotiated, or TCP otherwise.
+pub(crate) async fn pipeline(stream: &mut (impl AsyncRead + AsyncWrite + Unpin), requests: &[Request], results: &mut [Option<Result<Reply, Error>>], connection: &Connection) -> Result<(), Error> {
+    let protocol = if connection.tls_version.is_some() { Protocol::TLS } else { Protocol::TCP };
+    let started = Instant::now();
+
+    let sent = requests.iter().map(Request::to_bytes).collect::<Result<Vec<_>, _>>()?;
+    let mut bytes = Vec::new();
+    for message in &sent {
+        bytes.extend(frame_tcp_message(message)?);
+    }
+
+    info!("Sending {} pipelined requests", requests.len());
+    stream.write_all(&bytes).await?;
+
+    let mut buffer = TcpMessageBuffer::new();
+    while results.iter().any(Option::is_none) {
+        let bytes = read_message(stream, &mut buffer).await?;
+        let response = verify::parse_response(&bytes)?;
+
+        let index = requests.iter().zip(results.iter()).position(|(request, result)| {
+            result.is_none() && request.transaction_id == response.transaction_id
+        });
+
+        match index {
+            Some(index) => {
+                let reply = verify::check_response(&requests[index], &response).map(|()| {
+                    Reply { response, bytes, sent: sent[index].clone(), round_trip: started.elapsed(), attempts: 1, protocol, connection: connection.clone() }
+                });
+
+                results[index] = Some(reply);
+            }
+            None => {
+                warn!("Received response with unexpected transaction ID {:#06x}", response.transaction_id);
+            }
         }
+    }
+
+    Ok(())
+}
+
+/// Gives every request still without a result an error, after the
+/// connection failed: the first one gets the error itself, and the rest
+/// only find out that they are not getting a response.
+pub(crate) fn fail_remaining(results: &mut [Option<Result<Reply, Error>>], error: Error) {
+    let mut error = Some(error);
+
+    for result in results.iter_mut().filter(|r| r.is_none()) {
+        let e = error.take().unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::ConnectionAborted, "Connection failed before a response arrived").into()
+        });
+
+        *result = Some(Err(e));
+    }
+}
+
 
-        debug!("We need to read {} bytes total", total_len);
-        let mut combined_buffer = buf[2..read_len].to_vec();
-        while combined_buffer.len() < usize::from(total_len) {
-            let mut buf = [0; 4096];
-            let read_len = stream.read(&mut buf[..]).await?;
-            info!("Received further {} bytes of data (of {})", read_len, total_len);
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+    use dns::{Flags, Query, QClass, read_tcp_message, write_tcp_message};
+    use dns::record::OPT;
+    use dns::testing::response_to;
 
-            if read_len == 0 {
-                panic!("Read zero bytes!");
+    fn request(transaction_id: u16) -> Request {
+        let query = Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 };
+        Request { transaction_id, flags: Flags::query(), queries: vec![ query ], additional: None }
+    }
+
+    /// Runs the pipeline against a server that reads every request, then
+    /// answers the given number of them in reverse order.
+    fn pipeline_reversed(requests: &[Request], answered: usize) -> Vec<Result<Reply, Error>> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let count = requests.len();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            for _ in 0 .. count {
+                let message = read_tcp_message(&mut stream).unwrap().unwrap();
+                received.push(Request::from_bytes(&message).unwrap());
+            }
+
+            for request in received[.. answered].iter().rev() {
+                write_tcp_message(&mut stream, &response_to(request).to_bytes().unwrap()).unwrap();
+            }
+        });
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let results = runtime.block_on(async {
+            // Tokio’s own way of connecting does not work with every
+            // version of Rust, so the standard library’s gets used instead.
+            let mut stream = TcpStream::from_std(std::net::TcpStream::connect(address).unwrap()).unwrap();
+            let mut results = requests.iter().map(|_| None).collect::<Vec<_>>();
+            if let Err(e) = pipeline(&mut stream, requests, &mut results, &Connection::default()).await {
+                fail_remaining(&mut results, e);
             }
+            results.into_iter().flatten().collect()
+        });
+
+        server.join().unwrap();
+        results
+    }
 
-            combined_buffer.extend(&buf[0 .. read_len]);
+    #[test]
+    fn out_of_order() {
+        let results = pipeline_reversed(&[ request(1), request(2), request(3) ], 3);
+        let ids = results.iter().map(|r| r.as_ref().unwrap().response.transaction_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![ 1, 2, 3 ]);
+    }
+
+    #[test]
+    fn connection_closed_early() {
+        let results = pipeline_reversed(&[ request(1), request(2), request(3) ], 1);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_err());
+    }
+
+    fn reply_with_keepalive(units: Option<u16>) -> Result<Reply, Error> {
+        let mut response = response_to(&request(1));
+        if let Some(units) = units {
+            let mut data = vec![ 0x00, 0x0B, 0x00, 0x02 ];
+            data.extend(&units.to_be_bytes());
+            let opt = OPT { udp_payload_size: 1232, higher_bits: 0, edns0_version: 0, flags: 0, data };
+            response.additionals.push(Answer::Pseudo { qname: dns::Name::root(), opt });
         }
 
-        let response = Response::from_bytes(&combined_buffer)?;
-        Ok(response)
+        Ok(Reply { response, bytes: Vec::new(), sent: Vec::new(), round_trip: Duration::from_millis(1), attempts: 1, protocol: Protocol::TCP, connection: Connection::default() })
+    }
+
+    #[test]
+    fn keepalive_sent_with_edns() {
+        let mut edns = request(1);
+        edns.additional = Some(Request::additional_record());
+        assert_eq!(with_keepalive(&edns).additional.unwrap().tcp_keepalive(), None);
+        assert_eq!(with_keepalive(&edns).additional.unwrap().data, vec![ 0x00, 0x0B, 0x00, 0x00 ]);
+    }
+
+    #[test]
+    fn keepalive_not_sent_without_edns() {
+        assert_eq!(with_keepalive(&request(1)).additional, None);
+    }
+
+    #[test]
+    fn keepalive_timeout_advertised() {
+        let results = [ reply_with_keepalive(None), reply_with_keepalive(Some(150)) ];
+        assert_eq!(keepalive_timeout(&results), Some(Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn keepalive_timeout_zero() {
+        let results = [ reply_with_keepalive(Some(0)) ];
+        assert_eq!(keepalive_timeout(&results), None);
+    }
+
+    #[test]
+    fn keepalive_timeout_missing() {
+        let results = [ reply_with_keepalive(None) ];
+        assert_eq!(keepalive_timeout(&results), None);
     }
 }
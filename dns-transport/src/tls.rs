@@ -1,11 +1,22 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
 use async_trait::async_trait;
 use log::*;
-use native_tls::TlsConnector;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use dns::{Request, TcpMessageBuffer};
+use super::{Transport, Reply, Protocol, Connection, Error, Bootstrap, TlsSettings, verify};
+use super::ssl::{self, SslStream};
+use super::tcp::{read_message, write_message, pipeline, fail_remaining};
+use super::udp::timed_out;
 
-use dns::{Request, Response};
-use super::{Transport, Error};
+
+/// How long to wait for the server to connect, negotiate, and reply, all
+/// together.
+const TIMEOUT: Duration = Duration::from_secs(10);
 
 
 /// The **TLS transport**, which uses Tokio.
@@ -17,7 +28,7 @@ use super::{Transport, Error};
 /// use dns::{Request, Flags, Query, QClass, qtype, record::SRV};
 ///
 /// let query = Query {
-///     qname: String::from("dns.lookup.dog"),
+///     qname: "dns.lookup.dog".parse().unwrap(),
 ///     qclass: QClass::IN,
 ///     qtype: qtype!(SRV),
 /// };
@@ -30,11 +41,22 @@ use super::{Transport, Error};
 /// };
 ///
 /// let transport = TlsTransport::new("dns.google");
-/// transport.send(&request);
+/// transport.exchange(&request);
 /// ```
 #[derive(Debug)]
 pub struct TlsTransport {
     addr: String,
+
+    /// The name to send in SNI and check the server’s certificate against,
+    /// when it is not the host being connected to, such as when connecting
+    /// to an IP address.
+    pub server_name: Option<String>,
+
+    /// How to find the address of the host, when it is given by name.
+    pub bootstrap: Bootstrap,
+
+    /// How to check the server’s certificate.
+    pub tls: TlsSettings,
 }
 
 impl TlsTransport {
@@ -42,53 +64,102 @@ impl TlsTransport {
     /// Creates a new TLS transport that connects to the given host.
     pub fn new(sa: impl Into<String>) -> Self {
         let addr = sa.into();
-        Self { addr }
+        Self { addr, server_name: None, bootstrap: Bootstrap::System, tls: TlsSettings::default() }
     }
 }
 
 #[async_trait]
 impl Transport for TlsTransport {
-    async fn send(&self, request: &Request) -> Result<Response, Error> {
-        let connector = TlsConnector::new()?;
-        let connector = tokio_tls::TlsConnector::from(connector);
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, Error> {
+        let started = Instant::now();
+        let addresses = self.bootstrap.resolve(self.host()).await?;
 
-        info!("Opening TLS socket");
-        let stream =
-            if self.addr.contains(':') {
-                TcpStream::connect(&*self.addr).await?
-            }
-            else {
-                TcpStream::connect((&*self.addr, 853)).await?
-            };
-
-        info!("Connecting");
-        let mut stream = connector.connect(self.sni_domain(), stream).await?;
+        info!("Sending request to {}", self.addr);
+        let sent = request.to_bytes()?;
+        let (bytes, connection) = match timeout(TIMEOUT, self.exchange_bytes(&addresses, &sent)).await {
+            Ok(result)  => result?,
+            Err(_)      => return Err(timed_out(TIMEOUT)),
+        };
+        info!("Received {} bytes of data", bytes.len());
 
-        // As with TCP, we need to prepend the message with its length.
-        let mut bytes = request.to_bytes().expect("failed to serialise request");
-        let len_bytes = (bytes.len() as u16).to_be_bytes();
-        bytes.insert(0, len_bytes[0]);
-        bytes.insert(1, len_bytes[1]);
+        let response = verify::parse_response(&bytes)?;
+        verify::check_response(request, &response)?;
 
-        info!("Sending {} bytes of data to {}", bytes.len(), self.addr);
+        Ok(Reply { response, bytes, sent, round_trip: started.elapsed(), attempts: 1, protocol: Protocol::TLS, connection })
+    }
 
-        stream.write_all(&bytes).await?;
-        debug!("Sent");
+    async fn send_all(&self, requests: &[Request]) -> Vec<Result<Reply, Error>> {
+        let mut results = requests.iter().map(|_| None).collect::<Vec<_>>();
 
-        info!("Waiting to receive...");
-        let mut buf = [0; 4096];
-        let len = stream.read(&mut buf).await?;
+        info!("Sending {} requests to {} down one connection", requests.len(), self.addr);
+        let outcome = match timeout(TIMEOUT, self.pipeline_all(requests, &mut results)).await {
+            Ok(outcome)  => outcome,
+            Err(_)       => Err(timed_out(TIMEOUT)),
+        };
 
-        // Remember to deal with the length again.
-        info!("Received {} bytes of data", buf.len());
-        let response = Response::from_bytes(&buf[2..len])?;
+        if let Err(e) = outcome {
+            fail_remaining(&mut results, e);
+        }
 
-        Ok(response)
+        results.into_iter().flatten().collect()
     }
 }
 
 impl TlsTransport {
+
+    /// Connects to the server, sends it the message, and reads back the
+    /// message it responds with, along with the details of the connection.
+    async fn exchange_bytes(&self, addresses: &[IpAddr], message: &[u8]) -> Result<(Vec<u8>, Connection), Error> {
+        let mut stream = self.connect(addresses).await?;
+        let connection = Connection { address: stream.get_ref().peer_addr().ok(), .. self.tls.check_connection(stream.ssl())? };
+
+        // As with TCP, the message is prepended with its length.
+        write_message(&mut stream, message).await?;
+
+        info!("Waiting to receive...");
+        let bytes = read_message(&mut stream, &mut TcpMessageBuffer::new()).await?;
+        Ok((bytes, connection))
+    }
+
+    /// Connects to the server, and sends every request down the connection
+    /// without waiting for responses in between, as with TCP.
+    async fn pipeline_all(&self, requests: &[Request], results: &mut [Option<Result<Reply, Error>>]) -> Result<(), Error> {
+        let addresses = self.bootstrap.resolve(self.host()).await?;
+        let mut stream = self.connect(&addresses).await?;
+        let connection = Connection { address: stream.get_ref().peer_addr().ok(), .. self.tls.check_connection(stream.ssl())? };
+        pipeline(&mut stream, requests, results, &connection).await
+    }
+
+    /// Opens a TCP connection to the first of the addresses that accepts
+    /// one, and negotiates TLS over it.
+    async fn connect(&self, addresses: &[IpAddr]) -> Result<SslStream<TcpStream>, Error> {
+        info!("Opening TLS socket");
+        let mut stream = Err(io::Error::new(io::ErrorKind::NotFound, "Nameserver has no addresses"));
+        for address in addresses {
+            stream = TcpStream::connect(SocketAddr::new(*address, self.port())).await;
+            match &stream {
+                Ok(_)   => break,
+                Err(e)  => debug!("Failed to connect to {}: {}", address, e),
+            }
+        }
+
+        // Offer “dot”, the ALPN protocol ID for DNS-over-TLS (RFC 7858 §8),
+        // so servers that support it can say so.
+        info!("Connecting");
+        let configuration = self.tls.ssl_connector(b"\x03dot")?.configure()?.verify_hostname(! self.tls.insecure);
+        ssl::connect(configuration, self.sni_domain(), stream?).await
+    }
+
     fn sni_domain(&self) -> &str {
+        if let Some(server_name) = &self.server_name {
+            server_name
+        }
+        else {
+            self.host()
+        }
+    }
+
+    fn host(&self) -> &str {
         if let Some(colon_index) = self.addr.find(':') {
             &self.addr[.. colon_index]
         }
@@ -96,4 +167,61 @@ impl TlsTransport {
             &self.addr[..]
         }
     }
+
+    fn port(&self) -> u16 {
+        self.addr.find(':')
+            .and_then(|colon_index| self.addr[colon_index + 1 ..].parse().ok())
+            .unwrap_or(853)
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dns::{Flags, Query, QClass, read_tcp_message, write_tcp_message};
+    use dns::testing::response_to;
+    use crate::certs::{handshake_server, tls_server};
+
+    fn request(transaction_id: u16) -> Request {
+        let query = Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 };
+        Request { transaction_id, flags: Flags::query(), queries: vec![ query ], additional: None }
+    }
+
+    #[test]
+    fn pipelined() {
+        let (address, _) = tls_server(|mut stream| {
+            let mut received = Vec::new();
+            for _ in 0 .. 3 {
+                let message = read_tcp_message(&mut stream).unwrap().unwrap();
+                received.push(Request::from_bytes(&message).unwrap());
+            }
+
+            for request in received.iter().rev() {
+                write_tcp_message(&mut stream, &response_to(request).to_bytes().unwrap()).unwrap();
+            }
+        });
+
+        let mut transport = TlsTransport::new(address.to_string());
+        transport.tls = TlsSettings { insecure: true, .. TlsSettings::default() };
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let results = runtime.block_on(transport.send_all(&[ request(1), request(2), request(3) ]));
+        let ids = results.iter().map(|r| r.as_ref().unwrap().response.transaction_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![ 1, 2, 3 ]);
+        assert!(results.iter().all(|r| r.as_ref().unwrap().protocol == Protocol::TLS));
+    }
+
+    #[test]
+    fn pin_mismatch() {
+        let (address, pin) = handshake_server();
+
+        let mut transport = TlsTransport::new(address.to_string());
+        transport.tls = TlsSettings { insecure: true, pins: vec![ vec![0; 32] ], .. TlsSettings::default() };
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(matches!(runtime.block_on(transport.send_with_details(&request(0x1234))),
+                         Err(Error::PinMismatch { received }) if received == pin));
+    }
 }
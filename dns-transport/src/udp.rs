@@ -1,11 +1,19 @@
-use std::net::Ipv4Addr;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use log::*;
-use tokio::net::UdpSocket;
+use tokio::net::{UdpSocket, lookup_host};
+use tokio::time::timeout;
 
 use dns::{Request, Response};
-use super::{Transport, Error};
+use super::{Transport, Reply, Protocol, Connection, Error, case, verify};
+
+
+/// How long the UDP and automatic transports wait for a response by
+/// default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
 
 /// The **UDP transport**, which uses the stdlib.
@@ -17,7 +25,7 @@ use super::{Transport, Error};
 /// use dns::{Request, Flags, Query, QClass, qtype, record::NS};
 ///
 /// let query = Query {
-///     qname: String::from("dns.lookup.dog"),
+///     qname: "dns.lookup.dog".parse().unwrap(),
 ///     qclass: QClass::IN,
 ///     qtype: qtype!(NS),
 /// };
@@ -30,11 +38,27 @@ use super::{Transport, Error};
 /// };
 ///
 /// let transport = UdpTransport::new("8.8.8.8");
-/// transport.send(&request);
+/// transport.exchange(&request);
 /// ```
 #[derive(Debug)]
 pub struct UdpTransport {
     addr: String,
+
+    /// Whether to randomise the case of query names, and reject responses
+    /// that do not echo it back exactly. This is on by default.
+    pub randomise_case: bool,
+
+    /// How long to keep listening after a packet arrives, to catch any
+    /// duplicates of the response or late answers to it, and to wait for
+    /// the real response if the packet turns out not to be it. Everything
+    /// caught this way gets logged. This is zero by default, which takes
+    /// the first packet that arrives.
+    pub receive_window: Duration,
+
+    /// How long to wait for the first packet to arrive before giving up,
+    /// which is five seconds by default. Without this, a request to a
+    /// server that never answers would wait forever.
+    pub timeout: Duration,
 }
 
 impl UdpTransport {
@@ -42,37 +66,255 @@ impl UdpTransport {
     /// Creates a new UDP transport that connects to the given host.
     pub fn new(sa: impl Into<String>) -> Self {
         let addr = sa.into();
-        Self { addr }
+        Self { addr, randomise_case: true, receive_window: Duration::ZERO, timeout: DEFAULT_TIMEOUT }
     }
 }
 
 
 #[async_trait]
 impl Transport for UdpTransport {
-    async fn send(&self, request: &Request) -> Result<Response, Error> {
+    async fn send_with_details(&self, original_request: &Request) -> Result<Reply, Error> {
+        let started = Instant::now();
+        let request = if self.randomise_case { case::randomise(original_request) }
+                                         else { original_request.clone() };
+
+        let address = if self.addr.contains(':') { lookup_host(&*self.addr).await?.next() }
+                                              else { lookup_host((&*self.addr, 53)).await?.next() };
+        let address = address.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Nameserver address did not resolve"))?;
+
         info!("Opening UDP socket");
-        let mut socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        let mut socket = if address.is_ipv6() { UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).await? }
+                                         else { UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await? };
 
-        if self.addr.contains(':') {
-            socket.connect(&*self.addr).await?;
-        }
-        else {
-            socket.connect((&*self.addr, 53)).await?;
-        }
+        // Connecting the socket means any ICMP error the network sends back
+        // gets reported when receiving, rather than the socket waiting for
+        // a response that will never come.
+        socket.connect(address).await?;
 
-        let bytes = request.to_bytes().expect("failed to serialise request");
-        info!("Sending {} bytes of data to {} over UDP", bytes.len(), self.addr);
+        let bytes = request.to_bytes()?;
+        info!("Sending {} bytes of data to {} over UDP", bytes.len(), address);
 
-        let len = socket.send(&bytes).await?;
+        let len = socket.send(&bytes).await.map_err(|e| unreachable(e, address))?;
         debug!("Sent {} bytes", len);
 
         info!("Waiting to receive...");
-        let mut buf = vec![0u8; 1024];
-        let len = socket.recv(&mut buf).await?;
+        let (mut response, buf, round_trip) = self.receive(&mut socket, address, &request, started).await?;
+
+        if self.randomise_case {
+            case::verify_and_restore(&request, original_request, &mut response)?;
+        }
+
+        let connection = Connection { address: Some(address), .. Connection::default() };
+        Ok(Reply { response, bytes: buf, sent: bytes, round_trip, attempts: 1, protocol: Protocol::UDP, connection })
+    }
+}
+
+impl UdpTransport {
+
+    /// Waits for the response to the request to arrive, skipping over any
+    /// packets that are not it while the receive window is open, and then
+    /// listening for the rest of the window for any more packets. Returns
+    /// the response along with how long it took to arrive.
+    async fn receive(&self, socket: &mut UdpSocket, address: SocketAddr, request: &Request, started: Instant) -> Result<(Response, Vec<u8>, Duration), Error> {
+        let mut skipped: Option<(Error, Instant)> = None;
+
+        let (response, bytes, round_trip) = loop {
+            let packet = match skipped.as_ref().map(|(_, since)| *since) {
+                None         => match timeout(self.timeout, receive_packet(socket)).await {
+                    Ok(packet)  => packet.map_err(|e| unreachable(e, address))?,
+                    Err(_)      => return Err(timed_out(self.timeout)),
+                },
+                Some(since)  => match timeout(self.receive_window.saturating_sub(since.elapsed()), receive_packet(socket)).await {
+                    Ok(packet)  => packet.map_err(|e| unreachable(e, address))?,
+                    Err(_)      => return Err(skipped.unwrap().0),
+                },
+            };
+
+            match check_packet(packet, address, request) {
+                Ok((response, bytes))                   => break (response, bytes, started.elapsed()),
+                Err(e) if self.receive_window.is_zero()  => return Err(e),
+                Err(e)                                  => {
+                    warn!("Skipping packet that is not the response: {:?}", e);
+                    skipped.get_or_insert((e, Instant::now()));
+                }
+            }
+        };
+
+        if ! self.receive_window.is_zero() {
+            let mut duplicates = 0;
+            let mut others = 0;
+
+            let deadline = Instant::now() + self.receive_window;
+            while let Ok(packet) = timeout(deadline.saturating_duration_since(Instant::now()), receive_packet(socket)).await {
+                let (late, source) = packet?;
+                if late == bytes && source == address {
+                    debug!("Received a duplicate of the response from {}", source);
+                    duplicates += 1;
+                }
+                else {
+                    warn!("Received another {} bytes of data from {} after the response", late.len(), source);
+                    others += 1;
+                }
+            }
+
+            if duplicates + others > 0 {
+                warn!("Received {} duplicates of the response and {} other packets after it", duplicates, others);
+            }
+        }
+
+        Ok((response, bytes, round_trip))
+    }
+}
+
+/// Receives one datagram from the socket, returning its bytes and where
+/// it came from.
+async fn receive_packet(socket: &mut UdpSocket) -> io::Result<(Vec<u8>, SocketAddr)> {
+    let mut buf = vec![0u8; 1024];
+    let (len, source) = socket.recv_from(&mut buf).await?;
+
+    info!("Received {} bytes of data from {}", len, source);
+    buf.truncate(len);
+    Ok((buf, source))
+}
+
+/// The error for a response that did not arrive in time, which is a network
+/// error so that it gets told apart like other ones that are.
+pub(crate) fn timed_out(timeout: Duration) -> Error {
+    warn!("No response arrived within {:?}", timeout);
+    Error::NetworkError(io::Error::new(io::ErrorKind::TimedOut, "No response arrived in time"))
+}
+
+/// Turns the error the socket gives when the network says nothing is
+/// listening on the port into an error that says so.
+fn unreachable(error: io::Error, address: SocketAddr) -> Error {
+    if error.kind() == io::ErrorKind::ConnectionRefused {
+        warn!("Port unreachable at {}", address);
+        Error::PortUnreachable(address)
+    }
+    else {
+        Error::NetworkError(error)
+    }
+}
+
+/// Checks that a datagram is the response to the request: that it came
+/// from where the request was sent, parses, and answers the request.
+fn check_packet((bytes, source): (Vec<u8>, SocketAddr), address: SocketAddr, request: &Request) -> Result<(Response, Vec<u8>), Error> {
+    if source != address {
+        warn!("Sent request to {} but received response from {}", address, source);
+        return Err(Error::WrongSource { expected: address, received: source });
+    }
+
+    let response = verify::parse_response(&bytes)?;
+    verify::check_response(request, &response)?;
+    Ok((response, bytes))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dns::{Flags, Query, QClass};
+    use dns::testing::{MockServer, response_to};
+    use crate::NetworkFailure;
+
+    fn request() -> Request {
+        let query = Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 };
+        Request { transaction_id: 0x1234, flags: Flags::query(), queries: vec![ query ], additional: None }
+    }
+
+    /// Starts a server that answers one request with a packet that has the
+    /// wrong transaction ID, followed by the real response, twice.
+    fn noisy_server() -> SocketAddr {
+        let socket = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = socket.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let mut buf = vec![0; 512];
+            let (len, client) = socket.recv_from(&mut buf).unwrap();
+            let response = response_to(&Request::from_bytes(&buf[.. len]).unwrap());
+
+            let mut stray = response.clone();
+            stray.transaction_id = stray.transaction_id.wrapping_add(1);
+
+            for packet in [ &stray, &response, &response ] {
+                socket.send_to(&packet.to_bytes().unwrap(), client).unwrap();
+            }
+        });
+
+        address
+    }
+
+    #[test]
+    fn round_trip() {
+        let server = MockServer::reply_with(response_to).unwrap();
+        let transport = UdpTransport::new(server.address().to_string());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let reply = runtime.block_on(transport.send_with_details(&request())).unwrap();
+        assert_eq!(reply.protocol, Protocol::UDP);
+        assert_eq!(server.requests().len(), 1);
+
+        // The name was sent with its case randomised, but it comes back
+        // the way it was asked for.
+        assert_eq!(reply.response.queries, request().queries);
+    }
+
+    #[test]
+    fn stray_packet_skipped() {
+        let mut transport = UdpTransport::new(noisy_server().to_string());
+        transport.receive_window = Duration::from_millis(200);
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let reply = runtime.block_on(transport.send_with_details(&request())).unwrap();
+        assert_eq!(reply.response.transaction_id, 0x1234);
+    }
+
+    #[test]
+    fn stray_packet_without_window() {
+        let transport = UdpTransport::new(noisy_server().to_string());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(matches!(runtime.block_on(transport.send_with_details(&request())),
+                         Err(Error::TransactionIdMismatch { sent: 0x1234, received: 0x1235 })));
+    }
+
+    #[test]
+    fn port_unreachable() {
+        let closed = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = closed.local_addr().unwrap();
+        drop(closed);
+
+        let transport = UdpTransport::new(address.to_string());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let error = runtime.block_on(transport.send_with_details(&request())).unwrap_err();
+        assert_eq!(error.network_failure(), Some(NetworkFailure::PortUnreachable));
+    }
+
+    #[test]
+    fn no_answer() {
+        let silent = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        let mut transport = UdpTransport::new(silent.local_addr().unwrap().to_string());
+        transport.timeout = Duration::from_millis(100);
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let error = runtime.block_on(transport.send_with_details(&request())).unwrap_err();
+        assert_eq!(error.network_failure(), Some(NetworkFailure::Timeout));
+    }
+
+    #[test]
+    fn case_not_echoed() {
+        let server = MockServer::reply_with(|request| {
+            let mut response = response_to(request);
+            response.queries[0].qname.make_ascii_lowercase();
+            response
+        }).unwrap();
 
-        info!("Received {} bytes of data", len);
-        let response = Response::from_bytes(&buf[..len])?;
+        let mut request = request();
+        request.queries[0].qname = "lookup.dog.lookup.dog.lookup.dog.".parse().unwrap();
 
-        Ok(response)
+        let transport = UdpTransport::new(server.address().to_string());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime.block_on(transport.send_with_details(&request)).is_err());
     }
 }
@@ -0,0 +1,121 @@
+//! Checking that a response really is the answer to the request that was
+//! sent, rather than a stray or spoofed packet that happens to parse.
+
+use log::*;
+
+use dns::{Request, Response};
+use super::Error;
+
+
+/// Parses the bytes of a response, keeping hold of them if they do not
+/// parse, so whatever gets the error can still show what arrived.
+pub fn parse_response(bytes: &[u8]) -> Result<Response, Error> {
+    Response::from_bytes(bytes).map_err(|error| {
+        warn!("Response did not parse: {}", error);
+        Error::MalformedResponse { error, bytes: bytes.to_vec() }
+    })
+}
+
+/// Checks that the response has the same transaction ID as the request,
+/// and that its question section asks the same thing. Responses with an
+/// error code are allowed to leave out the question section, or all but
+/// the start of it, as some servers do when they cannot parse the request
+/// or refuse to answer more than one question.
+pub fn check_response(request: &Request, response: &Response) -> Result<(), Error> {
+    if response.transaction_id != request.transaction_id {
+        warn!("Sent transaction ID {:#06x} but received {:#06x}", request.transaction_id, response.transaction_id);
+        return Err(Error::TransactionIdMismatch { sent: request.transaction_id, received: response.transaction_id });
+    }
+
+    if response.flags.error_code.is_some() && request.queries.starts_with(&response.queries) {
+        return Ok(());
+    }
+
+    // Names compare without regard to case, so this doesn’t get confused
+    // by servers that don’t preserve it.
+    if response.queries != request.queries {
+        warn!("Sent questions {:?} but received {:?}", request.queries, response.queries);
+        return Err(Error::QuestionMismatch { sent: request.queries.clone(), received: response.queries.clone() });
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dns::{Flags, Query, QClass, ErrorCode};
+
+    fn query(name: &str, qtype: u16) -> Query {
+        Query { qname: name.parse().unwrap(), qclass: QClass::IN, qtype }
+    }
+
+    fn request() -> Request {
+        Request { transaction_id: 0x1234, flags: Flags::query(), queries: vec![ query("lookup.dog", 1) ], additional: None }
+    }
+
+    fn response(transaction_id: u16, queries: Vec<Query>) -> Response {
+        Response {
+            transaction_id,
+            flags: Flags::query(),
+            queries,
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn matching() {
+        let response = response(0x1234, vec![ query("Lookup.Dog.", 1) ]);
+        assert!(check_response(&request(), &response).is_ok());
+    }
+
+    #[test]
+    fn wrong_transaction_id() {
+        let response = response(0x4321, vec![ query("lookup.dog.", 1) ]);
+        assert!(matches!(check_response(&request(), &response),
+                         Err(Error::TransactionIdMismatch { sent: 0x1234, received: 0x4321 })));
+    }
+
+    #[test]
+    fn wrong_name() {
+        let response = response(0x1234, vec![ query("other.dog.", 1) ]);
+        assert!(matches!(check_response(&request(), &response), Err(Error::QuestionMismatch { .. })));
+    }
+
+    #[test]
+    fn wrong_type() {
+        let response = response(0x1234, vec![ query("lookup.dog.", 28) ]);
+        assert!(matches!(check_response(&request(), &response), Err(Error::QuestionMismatch { .. })));
+    }
+
+    #[test]
+    fn missing_question() {
+        let mut response = response(0x1234, vec![]);
+        assert!(matches!(check_response(&request(), &response), Err(Error::QuestionMismatch { .. })));
+
+        response.flags.error_code = Some(ErrorCode::FormatError);
+        assert!(check_response(&request(), &response).is_ok());
+    }
+
+    #[test]
+    fn several_questions_refused() {
+        let mut request = request();
+        request.queries.push(query("lookup.dog.", 28));
+
+        let mut response = response(0x1234, vec![ query("lookup.dog.", 1) ]);
+        assert!(matches!(check_response(&request, &response), Err(Error::QuestionMismatch { .. })));
+
+        response.flags.error_code = Some(ErrorCode::FormatError);
+        assert!(check_response(&request, &response).is_ok());
+    }
+
+    #[test]
+    fn malformed_bytes_kept() {
+        let bytes = vec![ 0x12, 0x34, 0x81 ];
+        assert!(matches!(parse_response(&bytes),
+                         Err(Error::MalformedResponse { bytes: received, .. }) if received == bytes));
+    }
+}
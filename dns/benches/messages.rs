@@ -0,0 +1,135 @@
+//! Benchmarks for encoding and decoding whole messages of the kinds dog
+//! sees in practice, from a one-answer response up to a zone transfer
+//! filling a TCP message. Run them with `cargo bench -p dns`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput, black_box};
+
+use dns::{Request, Response, Query, Answer, Flags, QClass, Name, qtype};
+use dns::record::*;
+
+
+fn name(input: &str) -> Name {
+    input.parse().unwrap()
+}
+
+fn answer(qname: &str, ttl: u32, record: Record) -> Answer {
+    Answer::Standard { qname: name(qname), qclass: QClass::IN, ttl, record }
+}
+
+fn response(query: Query, answers: Vec<Answer>, additionals: Vec<Answer>) -> Response {
+    let mut flags = Flags::query();
+    flags.response = true;
+    flags.recursion_available = true;
+
+    Response {
+        transaction_id: 0xbeef,
+        flags,
+        queries: vec![ query ],
+        answers,
+        authorities: Vec::new(),
+        additionals,
+    }
+}
+
+fn query(qname: &str, qtype: u16) -> Query {
+    Query { qname: name(qname), qclass: QClass::IN, qtype }
+}
+
+fn edns() -> Answer {
+    Answer::Pseudo { qname: Name::root(), opt: Request::additional_record() }
+}
+
+
+/// A response with a single A record, like most lookups get back.
+fn small_a_answer() -> Vec<u8> {
+    let record = Record::A(A { address: "93.184.216.34".parse().unwrap() });
+    let answers = vec![ answer("lookup.dog.", 3600, record) ];
+    response(query("lookup.dog.", qtype!(A)), answers, vec![ edns() ]).to_bytes().unwrap()
+}
+
+/// A response to a DNSKEY query with the DO bit set: two keys of the
+/// usual sizes, each signed by an RRSIG with a 2048-bit signature.
+fn dnssec_answer() -> Vec<u8> {
+    let key = |flags, len| Record::DNSKEY(DNSKEY { flags, protocol: 3, algorithm: 8, public_key: vec![ 0x03; len ] });
+    let signature = |key_tag| Record::RRSIG(RRSIG {
+        type_covered: qtype!(DNSKEY), algorithm: 8, labels: 1, original_ttl: 172800,
+        expiration: 1_600_000_000, inception: 1_598_000_000, key_tag,
+        signer_name: name("dog."), signature: vec![ 0x5a; 256 ],
+    });
+
+    let answers = vec![
+        answer("dog.", 172800, key(257, 260)),
+        answer("dog.", 172800, key(256, 132)),
+        answer("dog.", 172800, signature(20326)),
+        answer("dog.", 172800, signature(46780)),
+    ];
+
+    response(query("dog.", qtype!(DNSKEY)), answers, vec![ edns() ]).to_bytes().unwrap()
+}
+
+/// One message of a zone transfer, packed with a mix of records for
+/// hosts in the zone until it nearly reaches the 64 KiB limit for TCP.
+fn axfr_chunk() -> Vec<u8> {
+    let mut answers = vec![
+        answer("lookup.dog.", 3600, Record::SOA(SOA {
+            mname: name("ns1.lookup.dog."), rname: name("hostmaster.lookup.dog."),
+            serial: 2020090801, refresh_interval: 7200, retry_interval: 3600,
+            expire_limit: 1209600, minimum_ttl: 300,
+        })),
+    ];
+
+    let mut bytes = Vec::new();
+    for host in 0 .. {
+        let owner = format!("host{}.lookup.dog.", host);
+        answers.push(answer(&owner, 300, Record::A(A { address: [ 10, 0, (host / 256) as u8, (host % 256) as u8 ].into() })));
+        answers.push(answer(&owner, 300, Record::AAAA(AAAA { address: [ 0xfd00, 0, 0, 0, 0, 0, 0, host as u16 ].into() })));
+        answers.push(answer(&owner, 300, Record::MX(MX { preference: 10, exchange: name("mail.lookup.dog.") })));
+        answers.push(answer(&owner, 300, Record::TXT(TXT::from_text(format!("v=spf1 ip4:10.0.{}.0/24 -all", host / 256)))));
+
+        let candidate = response(query("lookup.dog.", 252), answers.clone(), Vec::new()).to_bytes().unwrap();
+        if candidate.len() > 60_000 {
+            break;
+        }
+
+        bytes = candidate;
+    }
+
+    bytes
+}
+
+fn request() -> Request {
+    Request {
+        transaction_id: 0xbeef,
+        flags: Flags::query(),
+        queries: vec![ query("lookup.dog.", qtype!(A)) ],
+        additional: Some(Request::additional_record()),
+    }
+}
+
+
+fn decoding(c: &mut Criterion) {
+    let cases = vec![
+        ("small a answer", small_a_answer()),
+        ("dnssec answer",  dnssec_answer()),
+        ("axfr chunk",     axfr_chunk()),
+    ];
+
+    let mut group = c.benchmark_group("Response::from_bytes");
+    for (name, bytes) in &cases {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| Response::from_bytes(black_box(bytes)).unwrap()));
+    }
+    group.finish();
+}
+
+fn encoding(c: &mut Criterion) {
+    let request = request();
+    c.bench_function("Request::to_bytes", |b| b.iter(|| black_box(&request).to_bytes().unwrap()));
+
+    let response = Response::from_bytes(&axfr_chunk()).unwrap();
+    c.bench_function("Response::to_bytes/axfr chunk", |b| b.iter(|| black_box(&response).to_bytes().unwrap()));
+}
+
+
+criterion_group!(benches, decoding, encoding);
+criterion_main!(benches);
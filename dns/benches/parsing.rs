@@ -0,0 +1,79 @@
+//! Benchmarks for reading responses off the wire. Run them with
+//! `cargo bench -p dns`, and save a baseline with `--save-baseline` to
+//! compare a change against.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput, black_box};
+
+use dns::Response;
+
+
+/// Builds a response with the given number of copies of a record with the
+/// given type and data, all for `lookup.dog`, using a compression pointer
+/// for the name of every one.
+fn response(count: u16, qtype: u16, rdata: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![
+        0xbe, 0xef,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x01,  // one query
+    ];
+
+    bytes.extend(&count.to_be_bytes());  // answers
+    bytes.extend(&[ 0x00, 0x00, 0x00, 0x00 ]);  // no authorities or additionals
+
+    bytes.extend(b"\x06lookup\x03dog\x00");
+    bytes.extend(&qtype.to_be_bytes());
+    bytes.extend(&[ 0x00, 0x01 ]);
+
+    for _ in 0 .. count {
+        bytes.extend(&[ 0xc0, 0x0c ]);
+        bytes.extend(&qtype.to_be_bytes());
+        bytes.extend(&[ 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10 ]);
+        bytes.extend(&(rdata.len() as u16).to_be_bytes());
+        bytes.extend(rdata);
+    }
+
+    bytes
+}
+
+/// A TXT record’s data, made of seven full 255-byte strings and an empty
+/// one to end it.
+fn long_txt() -> Vec<u8> {
+    let mut rdata = Vec::new();
+
+    for _ in 0 .. 7 {
+        rdata.push(255);
+        rdata.extend(&[ b'x'; 255 ]);
+    }
+
+    rdata.push(0);
+    rdata
+}
+
+
+fn parsing(c: &mut Criterion) {
+    let cases = vec![
+        ("a",       response(32, 1,  &[ 127, 0, 0, 1 ])),
+        ("aaaa",    response(32, 28, &[ 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1 ])),
+        ("txt",     response(4,  16, &long_txt())),
+        ("dnskey",  response(4,  48, &[ &[ 0x01, 0x01, 0x03, 0x08 ][..], &[ 0xab; 256 ] ].concat())),
+        ("unknown", response(4,  65280, &[ 0xcd; 512 ])),
+    ];
+
+    let mut group = c.benchmark_group("from_bytes");
+    for (name, bytes) in &cases {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| Response::from_bytes(black_box(bytes)).unwrap()));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("parse_ref");
+    for (name, bytes) in &cases {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| Response::parse_ref(black_box(bytes)).unwrap().answers().len()));
+    }
+    group.finish();
+}
+
+
+criterion_group!(benches, parsing);
+criterion_main!(benches);
@@ -1,8 +1,36 @@
+//! Reads responses out of random bytes, as dog does with whatever comes
+//! back from the network or out of a capture file.
+
 #![no_main]
-#[macro_use] extern crate libfuzzer_sys;
-extern crate dns;
-use dns::Response;
 
-fuzz_target!(|data: &[u8]| {
-    let _ = Response::from_bytes(data);
+use libfuzzer_sys::fuzz_target;
+use dns::{Response, Answer};
+
+
+fuzz_target!(|bytes: &[u8]| {
+    let owned = Response::from_bytes(bytes);
+
+    // The borrowed parser has to agree about every packet that can be
+    // read, even though the two can fail in different places.
+    let borrowed = Response::parse_ref(bytes).and_then(|response| response.to_response());
+    assert_eq!(owned.as_ref().ok(), borrowed.as_ref().ok());
+
+    let response = match owned {
+        Ok(response) => response,
+        Err(_) => return,
+    };
+
+    // Printing the response should never fail, however odd its contents.
+    for answer in response.answers.iter().chain(&response.authorities).chain(&response.additionals) {
+        let _ = answer.to_string();
+
+        let (Answer::Standard { qname, .. } | Answer::Pseudo { qname, .. }) = answer;
+        let _ = qname.to_unicode();
+    }
+
+    // Anything that was read in and can be written back out again should
+    // be readable a second time.
+    if let Ok(bytes) = response.to_bytes() {
+        Response::from_bytes(&bytes).expect("Failed to read a response that was written out");
+    }
 });
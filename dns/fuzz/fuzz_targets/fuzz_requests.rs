@@ -0,0 +1,23 @@
+//! Reads requests out of random bytes, as the serve command does with
+//! whatever clients send it.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dns::Request;
+
+
+fuzz_target!(|bytes: &[u8]| {
+    let request = match Request::from_bytes(bytes) {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+
+    for query in &request.queries {
+        let _ = query.qname.to_unicode();
+    }
+
+    if let Ok(bytes) = request.to_bytes() {
+        Request::from_bytes(&bytes).expect("Failed to read a request that was written out");
+    }
+});
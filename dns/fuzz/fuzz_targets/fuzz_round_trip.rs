@@ -0,0 +1,20 @@
+//! Writes out random responses and reads them back in, checking that
+//! nothing gets lost or changed on the way.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use dns::{Response, ParseOptions, WriteOptions};
+
+
+fuzz_target!(|response: Response| {
+    for options in &[ WriteOptions { compress_names: true }, WriteOptions { compress_names: false } ] {
+        let bytes = match response.to_bytes_with(*options) {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        };
+
+        let read = Response::from_bytes_with(&bytes, ParseOptions::strict()).expect("Failed to read a response that was written out");
+        assert_eq!(read, response);
+    }
+});
@@ -0,0 +1,330 @@
+//! Parsing responses without copying their contents out of the packet.
+//!
+//! `Response::from_bytes` allocates a string for every domain name and a
+//! structure for every record, which is wasted work when most of a
+//! message only gets looked at, or not looked at all, such as when
+//! proxying responses or skimming through a capture. The types here just
+//! note where each part of the message is, so names only get decompressed
+//! and records only get decoded when something asks for them.
+
+use log::debug;
+
+use crate::name::Name;
+use crate::record::{Record, OPT};
+use crate::strings::{ReadLabels, Labels};
+use crate::types::*;
+use crate::wire::*;
+
+
+impl Response {
+
+    /// Reads the structure of a response from the given slice, without
+    /// copying any names or record data out of it. This checks that every
+    /// section is present and that no record runs past the end of the
+    /// packet, but names and records only get checked when they are read,
+    /// so a response that parses this way may still fail to convert into
+    /// an owned one. Like `from_bytes`, trailing bytes are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dns::Response;
+    ///
+    /// let bytes = [
+    ///     0xab, 0xcd, 0x81, 0x80, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ///     0x03, 0x64, 0x6f, 0x67, 0x00, 0x00, 0x01, 0x00, 0x01,
+    /// ];
+    ///
+    /// let response = Response::parse_ref(&bytes).unwrap();
+    /// assert_eq!(response.transaction_id, 0xabcd);
+    /// assert!(response.queries()[0].qname.matches(&"dog.".parse().unwrap()));
+    /// assert!(response.answers().is_empty());
+    /// ```
+    pub fn parse_ref(bytes: &[u8]) -> Result<ResponseRef<'_>, WireError> {
+        Self::parse_ref_with(bytes, &DEFAULT_PARSE_OPTIONS)
+    }
+
+    /// Reads the structure of a response from the given slice, like
+    /// `parse_ref`, with the given options: trailing bytes are rejected if
+    /// they say to, and their limits and registry get used whenever a name
+    /// or record is read later.
+    pub fn parse_ref_with<'a>(bytes: &'a [u8], options: &'a ParseOptions) -> Result<ResponseRef<'a>, WireError> {
+        let mut c = Cursor::new(bytes);
+        let transaction_id = c.read_u16::<BigEndian>()?;
+        let flags = Flags::from_u16(c.read_u16::<BigEndian>()?);
+
+        let query_count      = c.read_u16::<BigEndian>()?;
+        let answer_count     = c.read_u16::<BigEndian>()?;
+        let authority_count  = c.read_u16::<BigEndian>()?;
+        let additional_count = c.read_u16::<BigEndian>()?;
+
+        let mut queries = Vec::with_capacity(usize::from(query_count));
+        for _ in 0 .. query_count {
+            let qname = skip_name(&mut c, options)?;
+            let qtype = c.read_u16::<BigEndian>()?;
+            let qclass = QClass::from_u16(c.read_u16::<BigEndian>()?);
+            queries.push(QueryRef { qname, qtype, qclass });
+        }
+
+        let record_count = usize::from(answer_count) + usize::from(authority_count) + usize::from(additional_count);
+        let mut records = Vec::with_capacity(record_count);
+        for _ in 0 .. record_count {
+            records.push(AnswerRef::skip(&mut c, options)?);
+        }
+
+        let leftover = bytes.len() - c.position() as usize;
+        if leftover > 0 && options.reject_trailing_bytes {
+            return Err(WireError::TrailingBytes(leftover));
+        }
+
+        debug!("Found {} queries and {} records without decoding them", queries.len(), records.len());
+        Ok(ResponseRef {
+            transaction_id,
+            flags,
+            queries,
+            records,
+            answer_count: usize::from(answer_count),
+            authority_count: usize::from(authority_count),
+        })
+    }
+}
+
+
+/// A response whose names and records still live in the packet it was
+/// read from. This gets returned by `Response::parse_ref`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ResponseRef<'a> {
+
+    /// The transaction ID of this response.
+    pub transaction_id: u16,
+
+    /// The flags that accompanied this response.
+    pub flags: Flags,
+
+    queries: Vec<QueryRef<'a>>,
+
+    /// The records from all three sections, in the order they appear.
+    records: Vec<AnswerRef<'a>>,
+    answer_count: usize,
+    authority_count: usize,
+}
+
+impl<'a> ResponseRef<'a> {
+
+    /// The queries section.
+    pub fn queries(&self) -> &[QueryRef<'a>] {
+        &self.queries
+    }
+
+    /// The answers section.
+    pub fn answers(&self) -> &[AnswerRef<'a>] {
+        &self.records[.. self.answer_count]
+    }
+
+    /// The authoritative nameservers section.
+    pub fn authorities(&self) -> &[AnswerRef<'a>] {
+        &self.records[self.answer_count .. self.answer_count + self.authority_count]
+    }
+
+    /// The additional records section.
+    pub fn additionals(&self) -> &[AnswerRef<'a>] {
+        &self.records[self.answer_count + self.authority_count ..]
+    }
+
+    /// Decodes every name and record, copying them into an owned response,
+    /// which is the same as the one `Response::from_bytes` would return.
+    pub fn to_response(&self) -> Result<Response, WireError> {
+        self.to_response_where(|_| true)
+    }
+
+    /// Decodes the names and records of only the records that pass the
+    /// given test, copying them into an owned response. The rest never
+    /// get decoded, so they cannot make this fail.
+    pub fn to_response_where(&self, keep: impl Fn(&AnswerRef<'a>) -> bool) -> Result<Response, WireError> {
+        let convert = |section: &[AnswerRef<'a>]| section.iter().filter(|a| keep(a)).map(AnswerRef::to_answer).collect::<Result<Vec<_>, _>>();
+
+        Ok(Response {
+            transaction_id: self.transaction_id,
+            flags: self.flags,
+            queries: self.queries.iter().map(QueryRef::to_query).collect::<Result<_, _>>()?,
+            answers: convert(self.answers())?,
+            authorities: convert(self.authorities())?,
+            additionals: convert(self.additionals())?,
+        })
+    }
+}
+
+
+/// A query whose name has not been decompressed yet.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct QueryRef<'a> {
+
+    /// The domain name being queried.
+    pub qname: NameRef<'a>,
+
+    /// The class number.
+    pub qclass: QClass,
+
+    /// The type number.
+    pub qtype: TypeInt,
+}
+
+impl QueryRef<'_> {
+
+    /// Decompresses this query’s name, copying it into an owned query.
+    pub fn to_query(&self) -> Result<Query, WireError> {
+        Ok(Query { qname: self.qname.to_name()?, qclass: self.qclass, qtype: self.qtype })
+    }
+}
+
+
+/// A record whose name has not been decompressed and whose data has not
+/// been decoded yet.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct AnswerRef<'a> {
+
+    /// The domain name being answered for.
+    pub qname: NameRef<'a>,
+
+    /// The type number of the record.
+    pub qtype: TypeInt,
+
+    /// The record’s data, exactly as it was in the packet. Any names in it
+    /// may be compressed, so they can only be read with the rest of the
+    /// packet; use `record` for that.
+    pub rdata: &'a [u8],
+
+    class: u16,
+    ttl: u32,
+    message: &'a [u8],
+    options: &'a ParseOptions,
+
+    /// Where the type field starts, for decoding the whole answer.
+    type_offset: usize,
+
+    /// Where the record data starts.
+    rdata_offset: usize,
+}
+
+impl<'a> AnswerRef<'a> {
+
+    /// Notes where each field of the record the cursor is at is, moving
+    /// the cursor past it.
+    fn skip(c: &mut Cursor<&'a [u8]>, options: &'a ParseOptions) -> Result<Self, WireError> {
+        let qname = skip_name(c, options)?;
+        let type_offset = c.position() as usize;
+        let qtype = c.read_u16::<BigEndian>()?;
+        let class = c.read_u16::<BigEndian>()?;
+        let ttl = c.read_u32::<BigEndian>()?;
+        let len = c.read_u16::<BigEndian>()?;
+        let rdata_offset = c.position() as usize;
+        let rdata = c.read_slice(usize::from(len))?;
+
+        Ok(Self { qname, qtype, rdata, class, ttl, message: *c.get_ref(), options, type_offset, rdata_offset })
+    }
+
+    /// Whether this is an OPT pseudo-record, in which case the class and
+    /// TTL fields have different meanings.
+    pub fn is_pseudo(&self) -> bool {
+        self.qtype == OPT::RR_TYPE
+    }
+
+    /// This record’s class.
+    pub fn qclass(&self) -> QClass {
+        QClass::from_u16(self.class)
+    }
+
+    /// The time-to-live duration, in seconds.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// Decodes this record’s data, decompressing any names in it. This
+    /// returns the data of OPT records as an unknown record type; use
+    /// `to_answer` to read them.
+    pub fn record(&self) -> Result<Record, WireError> {
+        let mut c = Cursor::new(self.message);
+        c.set_position(self.rdata_offset as u64);
+        Record::from_bytes(self.qtype, self.rdata.len() as u16, &mut c, self.options)
+    }
+
+    /// Decompresses this record’s name and decodes its data, copying them
+    /// into an owned answer.
+    pub fn to_answer(&self) -> Result<Answer, WireError> {
+        let qname = self.qname.to_name()?;
+        let mut c = Cursor::new(self.message);
+        c.set_position(self.type_offset as u64);
+        Answer::from_bytes(qname, &mut c, self.options)
+    }
+}
+
+
+/// A domain name that is still in the packet it was read from, possibly
+/// compressed. Its labels get followed through any compression pointers
+/// each time they are read.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct NameRef<'a> {
+    message: &'a [u8],
+    offset: usize,
+    options: &'a ParseOptions,
+}
+
+impl<'a> NameRef<'a> {
+
+    /// Iterates over the labels in this name, following compression
+    /// pointers, and stopping after the first error. The labels borrow
+    /// from the packet, so nothing gets allocated.
+    pub fn labels(&self) -> impl Iterator<Item=Result<&'a [u8], WireError>> {
+        Labels::new(self.message, self.offset, self.options)
+    }
+
+    /// Decompresses this name, copying it into an owned one.
+    pub fn to_name(&self) -> Result<Name, WireError> {
+        let mut c = Cursor::new(self.message);
+        c.set_position(self.offset as u64);
+        c.read_labels_with(self.options)
+    }
+
+    /// Whether this name is the same as the given one, ignoring case as
+    /// DNS does, without copying it. A name that cannot be read matches
+    /// nothing.
+    pub fn matches(&self, name: &Name) -> bool {
+        let mut theirs = name.labels();
+
+        for label in self.labels() {
+            match (label, theirs.next()) {
+                (Ok(ours), Some(theirs)) if ours.eq_ignore_ascii_case(theirs.as_bytes()) => {}
+                _ => return false,
+            }
+        }
+
+        theirs.next().is_none()
+    }
+}
+
+
+/// Moves the cursor past the name it is at, without following any
+/// compression pointer it ends with, and returns where the name is.
+fn skip_name<'a>(c: &mut Cursor<&'a [u8]>, options: &'a ParseOptions) -> Result<NameRef<'a>, WireError> {
+    let offset = c.position() as usize;
+
+    loop {
+        let byte = c.read_u8()?;
+
+        if byte == 0 {
+            break;
+        }
+        else if byte >= 0b_1100_0000 {
+            c.read_u8()?;
+            break;
+        }
+        else if usize::from(byte) > Name::MAX_LABEL_LENGTH {
+            return Err(WireError::LabelTooLong(usize::from(byte)));
+        }
+        else {
+            c.read_slice(usize::from(byte))?;
+        }
+    }
+
+    Ok(NameRef { message: c.get_ref(), offset, options })
+}
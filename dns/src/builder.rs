@@ -0,0 +1,167 @@
+//! Building requests without having to fill in every field by hand.
+
+use rand::Rng;
+
+use crate::name::Name;
+use crate::record::OPT;
+use crate::types::*;
+
+
+/// A builder for requests, which starts off with a single query and the
+/// usual flags for a recursive lookup, and lets the rest be changed one
+/// method call at a time.
+///
+/// # Examples
+///
+/// ```
+/// use dns::{MessageBuilder, Request, qtype};
+/// use dns::record::MX;
+///
+/// let request = MessageBuilder::query("lookup.dog".parse().unwrap(), qtype!(MX))
+///     .recursion(true)
+///     .edns(Request::additional_record())
+///     .build();
+///
+/// assert_eq!(request.queries[0].qtype, 15);
+/// assert!(request.additional.is_some());
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct MessageBuilder {
+    transaction_id: Option<u16>,
+    flags: Flags,
+    queries: Vec<Query>,
+    additional: Option<OPT>,
+}
+
+impl MessageBuilder {
+
+    /// Starts building a request for the given domain name and record
+    /// type, in the Internet class, with recursion desired.
+    pub fn query(qname: Name, qtype: TypeInt) -> Self {
+        Self {
+            transaction_id: None,
+            flags: Flags::query(),
+            queries: vec![ Query { qname, qtype, qclass: QClass::IN } ],
+            additional: None,
+        }
+    }
+
+    /// Sets the class of the query.
+    pub fn class(mut self, qclass: QClass) -> Self {
+        for query in &mut self.queries {
+            query.qclass = qclass;
+        }
+
+        self
+    }
+
+    /// Sets the transaction ID, rather than having one picked at random
+    /// when the request gets built.
+    pub fn transaction_id(mut self, transaction_id: u16) -> Self {
+        self.transaction_id = Some(transaction_id);
+        self
+    }
+
+    /// Adds another question to the request, after the ones already in it.
+    /// The header allows for more than one, but most servers refuse such
+    /// requests, or only answer the first question.
+    pub fn also_query(mut self, query: Query) -> Self {
+        self.queries.push(query);
+        self
+    }
+
+    /// Sets whether the server should recursively look up the answer.
+    pub fn recursion(mut self, recursion_desired: bool) -> Self {
+        self.flags.recursion_desired = recursion_desired;
+        self
+    }
+
+    /// Sets the `AD` (Authentic Data) flag, asking the server to say
+    /// whether it has validated the answer.
+    pub fn authentic_data(mut self, authentic_data: bool) -> Self {
+        self.flags.authentic_data = authentic_data;
+        self
+    }
+
+    /// Sets the `CD` (Checking Disabled) flag, asking the server to return
+    /// records even if they fail DNSSEC validation.
+    pub fn checking_disabled(mut self, checking_disabled: bool) -> Self {
+        self.flags.checking_disabled = checking_disabled;
+        self
+    }
+
+    /// Sends the given OPT record in the additional section.
+    pub fn edns(mut self, opt: OPT) -> Self {
+        self.additional = Some(opt);
+        self
+    }
+
+    /// Finishes building the request, picking a random transaction ID if
+    /// one has not been set.
+    pub fn build(self) -> Request {
+        self.build_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Finishes building the request, using the given random number
+    /// generator to pick the transaction ID if one has not been set.
+    pub fn build_with_rng<R: Rng + ?Sized>(self, rng: &mut R) -> Request {
+        let transaction_id = self.transaction_id.unwrap_or_else(|| rng.gen());
+        Request { transaction_id, flags: self.flags, queries: self.queries, additional: self.additional }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    fn name() -> Name {
+        "lookup.dog".parse().unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let request = MessageBuilder::query(name(), 1).transaction_id(0x1234).build();
+
+        assert_eq!(request, Request {
+            transaction_id: 0x1234,
+            flags: Flags::query(),
+            queries: vec![ Query { qname: name(), qtype: 1, qclass: QClass::IN } ],
+            additional: None,
+        });
+    }
+
+    #[test]
+    fn flags() {
+        let request = MessageBuilder::query(name(), 1)
+            .recursion(false)
+            .authentic_data(true)
+            .checking_disabled(true)
+            .build();
+
+        assert!(! request.flags.recursion_desired);
+        assert!(request.flags.authentic_data);
+        assert!(request.flags.checking_disabled);
+    }
+
+    #[test]
+    fn class() {
+        let request = MessageBuilder::query(name(), 16).class(QClass::CH).build();
+        assert_eq!(request.queries[0].qclass, QClass::CH);
+    }
+
+    #[test]
+    fn several_questions() {
+        let other = Query { qname: "other.dog".parse().unwrap(), qtype: 28, qclass: QClass::IN };
+        let request = MessageBuilder::query(name(), 1).also_query(other.clone()).build();
+        assert_eq!(request.queries, vec![ Query { qname: name(), qtype: 1, qclass: QClass::IN }, other ]);
+    }
+
+    #[test]
+    fn pluggable_rng() {
+        let mut rng = StepRng::new(0x4321, 1);
+        let request = MessageBuilder::query(name(), 1).build_with_rng(&mut rng);
+        assert_eq!(request.transaction_id, 0x4321);
+    }
+}
@@ -0,0 +1,363 @@
+//! Caching responses for as long as their TTLs allow, so the same query
+//! does not have to be sent again while its answer is still fresh.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+
+use log::*;
+
+use crate::name::Name;
+use crate::presentation::{hex, decode_hex};
+use crate::record::Record;
+use crate::types::*;
+
+
+/// A cache of responses, keyed by the name, type, and class they were
+/// queried for, along with the parts of the request that change what a
+/// server answers with: its RD and CD flags, and whether it used EDNS, and
+/// if so with which version, flags (such as DO), and options. Only
+/// requests with exactly one question get cached.
+///
+/// Positive responses are kept for the lowest TTL of their answers, and
+/// negative ones — `NXDOMAIN`, or no answers at all — are kept for the
+/// lower of the TTL and the minimum field of the SOA record in their
+/// authority section, as described in RFC 2308. Negative responses
+/// without an SOA record, and responses with any other error or with the
+/// truncated flag set, are not kept at all.
+///
+/// Rather than reading the clock itself, the cache gets told the current
+/// time, in seconds since the Unix epoch.
+#[derive(PartialEq, Debug, Default)]
+pub struct Cache {
+    entries: HashMap<Key, Entry>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug)]
+struct Key {
+    qname: Name,
+    qtype: TypeInt,
+    qclass: u16,
+    recursion_desired: bool,
+    checking_disabled: bool,
+
+    /// The version, flags, and options of the request’s OPT record, if it
+    /// had one. The UDP payload size is left out, as responses that did
+    /// not fit are not cached anyway.
+    edns: Option<(u8, u16, Vec<u8>)>,
+}
+
+#[derive(PartialEq, Debug)]
+struct Entry {
+
+    /// The request the response was for, so it can be saved along with it.
+    request: Request,
+
+    /// The response, with the TTLs it arrived with.
+    response: Response,
+
+    /// When the response was added to the cache.
+    stored: u64,
+
+    /// When the response stops being fresh.
+    expires: u64,
+}
+
+
+impl Cache {
+
+    /// Creates a new empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the response to a request to the cache, replacing any that was
+    /// there before, if the request can be cached and the response’s TTLs
+    /// allow it to be cached at all. Returns whether it was added.
+    pub fn insert(&mut self, request: &Request, response: &Response, now: u64) -> bool {
+        match (Key::of(request), cache_ttl(response)) {
+            (Some(key), Some(ttl)) if ttl > 0 => {
+                let entry = Entry { request: request.clone(), response: response.clone(), stored: now, expires: now + u64::from(ttl) };
+                self.entries.insert(key, entry);
+                true
+            }
+            _ => {
+                false
+            }
+        }
+    }
+
+    /// Returns the cached response to a request, if there is one that is
+    /// still fresh, with its TTLs counted down by the time it has spent in
+    /// the cache.
+    pub fn get(&self, request: &Request, now: u64) -> Option<Response> {
+        let entry = self.entries.get(&Key::of(request)?)?;
+        if now >= entry.expires {
+            return None;
+        }
+
+        let age = now.saturating_sub(entry.stored);
+        let age = u32::try_from(age).unwrap_or(u32::MAX);
+
+        let mut response = entry.response.clone();
+        for answer in response.answers.iter_mut().chain(&mut response.authorities).chain(&mut response.additionals) {
+            if let Answer::Standard { ttl, .. } = answer {
+                *ttl = ttl.saturating_sub(age);
+            }
+        }
+
+        Some(response)
+    }
+
+    /// Removes every response that is no longer fresh.
+    pub fn remove_expired(&mut self, now: u64) {
+        self.entries.retain(|_, entry| now < entry.expires);
+    }
+
+    /// Returns the number of responses in the cache, including any that
+    /// are no longer fresh but have not been removed yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache has no responses in it.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes the fresh responses out, one per line, as the time each was
+    /// stored followed by the bytes of its request and of itself in hex.
+    pub fn save(&self, writer: &mut impl Write, now: u64) -> io::Result<()> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e));
+
+        for entry in self.entries.values().filter(|e| now < e.expires) {
+            let request = entry.request.to_bytes().map_err(invalid)?;
+            let response = entry.response.to_bytes().map_err(invalid)?;
+            writeln!(writer, "{} {} {}", entry.stored, hex(&request), hex(&response))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads responses in the format written by `save`, skipping any that
+    /// are no longer fresh. Lines that cannot be read are skipped too, as
+    /// losing part of a cache only means sending a few more queries.
+    pub fn load(reader: impl BufRead, now: u64) -> io::Result<Self> {
+        let mut cache = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let mut fields = line.split(' ');
+            let entry = (|| {
+                let stored = fields.next()?.parse::<u64>().ok()?;
+                let request = Request::from_bytes(&decode_hex(fields.next()?)?).ok()?;
+                let response = Response::from_bytes(&decode_hex(fields.next()?)?).ok()?;
+                Some((stored, request, response))
+            })();
+
+            match entry {
+                Some((stored, request, response)) => {
+                    cache.insert(&request, &response, stored);
+                }
+                None => {
+                    warn!("Skipping unreadable cache line {:?}", line);
+                }
+            }
+        }
+
+        cache.remove_expired(now);
+        Ok(cache)
+    }
+}
+
+impl Key {
+    fn of(request: &Request) -> Option<Self> {
+        let query = match &request.queries[..] {
+            [ q ]  => q,
+            _      => return None,
+        };
+
+        Some(Self {
+            qname: query.qname.clone(),
+            qtype: query.qtype,
+            qclass: query.qclass.to_u16(),
+            recursion_desired: request.flags.recursion_desired,
+            checking_disabled: request.flags.checking_disabled,
+            edns: request.additional.as_ref().map(|opt| (opt.edns0_version, opt.flags, opt.data.clone())),
+        })
+    }
+}
+
+
+/// Returns how long a response can be cached for, or `None` if it should
+/// not be cached.
+pub fn cache_ttl(response: &Response) -> Option<u32> {
+    if response.flags.truncated {
+        return None;
+    }
+
+    let lowest = response.answers.iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { ttl, .. }  => Some(*ttl),
+            Answer::Pseudo { .. }         => None,
+        }
+    }).min();
+
+    match (response.flags.error_code, lowest) {
+        (None, Some(ttl))                               => Some(ttl),
+        (None, None) | (Some(ErrorCode::NXDomain), _)   => negative_ttl(response),
+        (Some(_), _)                                    => None,
+    }
+}
+
+/// Returns the TTL of a negative response, which comes from the SOA record
+/// in its authority section.
+fn negative_ttl(response: &Response) -> Option<u32> {
+    response.authorities.iter().find_map(|answer| {
+        match answer {
+            Answer::Standard { ttl, record: Record::SOA(soa), .. }  => Some((*ttl).min(soa.minimum_ttl)),
+            _                                                        => None,
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use crate::record::{A, SOA};
+
+    fn query(qtype: TypeInt) -> Query {
+        Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype }
+    }
+
+    fn request(qtype: TypeInt) -> Request {
+        Request { transaction_id: 1, flags: Flags::query(), queries: vec![ query(qtype) ], additional: None }
+    }
+
+    fn response(answers: Vec<(u32, Record)>, authorities: Vec<(u32, Record)>, error_code: Option<ErrorCode>) -> Response {
+        let mut flags = Flags::from_u16(0x8180);
+        flags.error_code = error_code;
+
+        let answer = |(ttl, record)| Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl, record };
+
+        Response {
+            transaction_id: 1,
+            flags,
+            queries: vec![ query(1) ],
+            answers: answers.into_iter().map(answer).collect(),
+            authorities: authorities.into_iter().map(answer).collect(),
+            additionals: Vec::new(),
+        }
+    }
+
+    fn a(last_octet: u8) -> Record {
+        Record::A(A { address: Ipv4Addr::new(127, 0, 0, last_octet) })
+    }
+
+    fn soa(minimum_ttl: u32) -> Record {
+        Record::SOA(SOA {
+            mname: "ns.lookup.dog.".parse().unwrap(),
+            rname: "hostmaster.lookup.dog.".parse().unwrap(),
+            serial: 1,
+            refresh_interval: 3600,
+            retry_interval: 600,
+            expire_limit: 86400,
+            minimum_ttl,
+        })
+    }
+
+    #[test]
+    fn lowest_answer_ttl() {
+        assert_eq!(cache_ttl(&response(vec![ (300, a(1)), (60, a(2)) ], vec![], None)), Some(60));
+    }
+
+    #[test]
+    fn negative_ttls() {
+        assert_eq!(cache_ttl(&response(vec![], vec![ (3600, soa(300)) ], Some(ErrorCode::NXDomain))), Some(300));
+        assert_eq!(cache_ttl(&response(vec![], vec![ (120, soa(300)) ], None)), Some(120));
+        assert_eq!(cache_ttl(&response(vec![], vec![], Some(ErrorCode::NXDomain))), None);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        assert_eq!(cache_ttl(&response(vec![], vec![ (3600, soa(300)) ], Some(ErrorCode::ServerFailure))), None);
+    }
+
+    #[test]
+    fn counting_down() {
+        let mut cache = Cache::new();
+        assert!(cache.insert(&request(1), &response(vec![ (300, a(1)) ], vec![], None), 1000));
+
+        let cached = cache.get(&request(1), 1100).unwrap();
+        assert_eq!(cached.answers, response(vec![ (200, a(1)) ], vec![], None).answers);
+        assert_eq!(cache.get(&request(28), 1100), None);
+    }
+
+    #[test]
+    fn expiry() {
+        let mut cache = Cache::new();
+        cache.insert(&request(1), &response(vec![ (300, a(1)) ], vec![], None), 1000);
+        assert_eq!(cache.get(&request(1), 1300), None);
+
+        cache.remove_expired(1300);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let mut cache = Cache::new();
+        cache.insert(&request(1), &response(vec![ (300, a(1)) ], vec![], None), 1000);
+
+        let mut shouty = request(1);
+        shouty.queries[0].qname = "LOOKUP.dog.".parse().unwrap();
+        assert!(cache.get(&shouty, 1000).is_some());
+    }
+
+    #[test]
+    fn request_settings_in_key() {
+        let mut cache = Cache::new();
+        cache.insert(&request(1), &response(vec![ (300, a(1)) ], vec![], None), 1000);
+
+        let mut checking_disabled = request(1);
+        checking_disabled.flags.checking_disabled = true;
+        assert_eq!(cache.get(&checking_disabled, 1000), None);
+
+        let mut no_recursion = request(1);
+        no_recursion.flags.recursion_desired = false;
+        assert_eq!(cache.get(&no_recursion, 1000), None);
+
+        let mut edns = request(1);
+        edns.additional = Some(Request::additional_record());
+        assert_eq!(cache.get(&edns, 1000), None);
+
+        let mut dnssec_ok = edns.clone();
+        dnssec_ok.additional.as_mut().unwrap().flags |= 0b_1000_0000_0000_0000;
+        cache.insert(&edns, &response(vec![ (300, a(2)) ], vec![], None), 1000);
+        assert_eq!(cache.get(&dnssec_ok, 1000), None);
+        assert!(cache.get(&edns, 1000).is_some());
+    }
+
+    #[test]
+    fn several_questions() {
+        let mut cache = Cache::new();
+        let mut both = request(1);
+        both.queries.push(query(28));
+        assert!(! cache.insert(&both, &response(vec![ (300, a(1)) ], vec![], None), 1000));
+        assert_eq!(cache.get(&both, 1000), None);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut cache = Cache::new();
+        cache.insert(&request(1), &response(vec![ (300, a(1)) ], vec![], None), 1000);
+
+        let mut saved = Vec::new();
+        cache.save(&mut saved, 1000).unwrap();
+
+        let loaded = Cache::load(&saved[..], 1100).unwrap();
+        assert_eq!(loaded, cache);
+        assert_eq!(Cache::load(&saved[..], 1300).unwrap().len(), 0);
+    }
+}
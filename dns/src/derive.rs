@@ -0,0 +1,164 @@
+//! The parts of the `Wire` implementations written by `#[derive(Wire)]`
+//! that need to reach inside this crate. The derive macro lives in the
+//! `dns-derive` crate; this module is public only so the code it writes
+//! can call into it, and nothing else should use it.
+
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::*;
+
+use crate::name::Name;
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::wire::*;
+
+
+/// A type that can be a field of a record with a derived `Wire`
+/// implementation, read and written one after another in field order.
+pub trait Field: Sized {
+
+    /// Reads this field from the cursor, reading any domain names with
+    /// the limits in the given options.
+    fn read_field(c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError>;
+
+    /// Writes this field to the end of the given buffer.
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError>;
+}
+
+impl Field for u8 {
+    fn read_field(c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        Ok(c.read_u8()?)
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        Ok(bytes.write_u8(*self)?)
+    }
+}
+
+impl Field for u16 {
+    fn read_field(c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        Ok(c.read_u16::<BigEndian>()?)
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        Ok(bytes.write_u16::<BigEndian>(*self)?)
+    }
+}
+
+impl Field for u32 {
+    fn read_field(c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        Ok(c.read_u32::<BigEndian>()?)
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        Ok(bytes.write_u32::<BigEndian>(*self)?)
+    }
+}
+
+impl Field for Ipv4Addr {
+    fn read_field(c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        let octets = <[u8; 4]>::try_from(c.read_slice(4)?).expect("four bytes");
+        Ok(Self::from(octets))
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.extend(&self.octets());
+        Ok(())
+    }
+}
+
+impl Field for Ipv6Addr {
+    fn read_field(c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        let octets = <[u8; 16]>::try_from(c.read_slice(16)?).expect("sixteen bytes");
+        Ok(Self::from(octets))
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.extend(&self.octets());
+        Ok(())
+    }
+}
+
+/// Domain names are read the same way as in every other record, following
+/// compression pointers, and written out in full.
+impl Field for Name {
+    fn read_field(c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        c.read_labels_with(options)
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_labels(self)
+    }
+}
+
+/// A byte vector is a character-string: a single length byte, followed by
+/// that many bytes.
+impl Field for Vec<u8> {
+    fn read_field(c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        let length = c.read_u8()?;
+        Ok(c.read_slice(usize::from(length))?.to_vec())
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        let length = u8::try_from(self.len()).map_err(|_| WireError::StringTooLong(self.len()))?;
+        bytes.write_u8(length)?;
+        bytes.extend(self);
+        Ok(())
+    }
+}
+
+/// A string is a character-string as well, with any invalid UTF-8 in it
+/// replaced, the same way the strings in TXT and CAA records are.
+impl Field for String {
+    fn read_field(c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        let bytes = Vec::<u8>::read_field(c, options)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn write_field(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        let length = u8::try_from(self.len()).map_err(|_| WireError::StringTooLong(self.len()))?;
+        bytes.write_u8(length)?;
+        bytes.extend(self.as_bytes());
+        Ok(())
+    }
+}
+
+
+/// Reads the rest of a record’s data, given the position its data started
+/// at and its length, for a field marked `#[wire(rest)]`. If the fields
+/// before it have already read past the end of the data, the length was
+/// lying, and the record is rejected.
+pub fn read_rest(c: &mut Cursor<&[u8]>, start: u64, len: u16) -> Result<Vec<u8>, WireError> {
+    let consumed = c.position() - start;
+
+    match u64::from(len).checked_sub(consumed) {
+        Some(remaining) => Ok(c.read_slice(remaining as usize)?.to_vec()),
+        None => {
+            warn!("Expected length {} but already read {} bytes", len, consumed);
+            Err(WireError::WrongLength { expected: len, got: consumed as u16 })
+        }
+    }
+}
+
+/// Checks that the fields of a record with no `#[wire(rest)]` field used
+/// up exactly as many bytes as its length said, given the position its
+/// data started at, so that a length that was lying gets rejected.
+pub fn check_length(c: &Cursor<&[u8]>, start: u64, len: u16) -> Result<(), WireError> {
+    let consumed = c.position() - start;
+
+    if consumed == u64::from(len) {
+        debug!("Length {} is correct", len);
+        Ok(())
+    }
+    else {
+        warn!("Expected length {} but read {} bytes", len, consumed);
+        Err(WireError::WrongLength { expected: len, got: consumed as u16 })
+    }
+}
+
+/// Writes the rest of a record’s data, which is written as-is.
+pub fn write_rest(rest: &[u8], bytes: &mut Vec<u8>) -> Result<(), WireError> {
+    bytes.extend(rest);
+    Ok(())
+}
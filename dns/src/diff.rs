@@ -0,0 +1,418 @@
+//! Comparing two messages, field by field and record by record, to find
+//! out how they differ.
+//!
+//! The records in each section are compared as sets, so two responses
+//! that hold the same records in different orders count as the same, and
+//! a record whose TTL is all that changed is reported as changed rather
+//! than as one record removed and another added.
+//!
+//! # Examples
+//!
+//! ```
+//! use dns::{Answer, Flags, QClass, Response};
+//! use dns::diff::{diff, DiffOptions};
+//! use dns::record::{Record, A};
+//!
+//! let answer = |ttl, address: &str| Answer::Standard {
+//!     qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl,
+//!     record: Record::A(A { address: address.parse().unwrap() }),
+//! };
+//!
+//! let response = |answers| Response {
+//!     transaction_id: 0x1234, flags: Flags::from_u16(0x8180),
+//!     queries: Vec::new(), answers, authorities: Vec::new(), additionals: Vec::new(),
+//! };
+//!
+//! let before = response(vec![ answer(300, "192.0.2.1") ]);
+//! let after = response(vec![ answer(60, "192.0.2.1"), answer(60, "192.0.2.2") ]);
+//!
+//! let changes = diff(&before, &after, DiffOptions::default());
+//! assert_eq!(changes.to_string(), "\
+//! ~ answer count: 1 -> 2
+//! ~ answer: lookup.dog. 60 IN A 192.0.2.1 (TTL was 300)
+//! + answer: lookup.dog. 60 IN A 192.0.2.2
+//! ");
+//!
+//! let changes = diff(&before, &after, DiffOptions { ignore_ttl: true, .. DiffOptions::default() });
+//! assert_eq!(changes.len(), 2);
+//! ```
+
+use std::fmt;
+
+use crate::name::Name;
+use crate::types::*;
+use crate::wire::{Section, find_qtype_name};
+
+
+/// What to leave out when comparing two messages.
+#[derive(PartialEq, Debug, Default, Copy, Clone)]
+pub struct DiffOptions {
+
+    /// Whether to ignore differences in TTLs, which change every time a
+    /// cached record gets served.
+    pub ignore_ttl: bool,
+
+    /// Whether to ignore a different transaction ID, which almost every
+    /// pair of messages has.
+    pub ignore_transaction_id: bool,
+}
+
+/// Every difference between two messages, in the order they appear in
+/// the messages.
+#[derive(PartialEq, Debug, Default, Clone)]
+pub struct ResponseDiff {
+
+    /// The differences.
+    pub changes: Vec<Change>,
+}
+
+/// One difference between two messages, with the value on the left
+/// first.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Change {
+
+    /// The transaction IDs differ.
+    TransactionId(u16, u16),
+
+    /// One of the single-bit flags differs. Contains its name.
+    Flag(&'static str, bool, bool),
+
+    /// The opcodes differ.
+    Opcode(u8, u8),
+
+    /// The response codes differ.
+    Rcode(Option<ErrorCode>, Option<ErrorCode>),
+
+    /// The number of records in a section differs.
+    Count(Section, usize, usize),
+
+    /// A query is only in the left message.
+    QueryRemoved(Query),
+
+    /// A query is only in the right message.
+    QueryAdded(Query),
+
+    /// A record is only in the left message.
+    Removed(Section, Answer),
+
+    /// A record is only in the right message.
+    Added(Section, Answer),
+
+    /// A record is in both messages, but with a different TTL. Contains
+    /// the record from the right message, and the TTL from the left.
+    TTL(Section, Answer, u32),
+}
+
+
+/// Compares two messages, returning the ways the right one differs from
+/// the left one.
+pub fn diff(left: &Response, right: &Response, options: DiffOptions) -> ResponseDiff {
+    let mut changes = Vec::new();
+
+    if left.transaction_id != right.transaction_id && ! options.ignore_transaction_id {
+        changes.push(Change::TransactionId(left.transaction_id, right.transaction_id));
+    }
+
+    diff_flags(&left.flags, &right.flags, &mut changes);
+
+    let sections = [
+        (Section::Answer,      &left.answers,      &right.answers),
+        (Section::Authority,   &left.authorities,  &right.authorities),
+        (Section::Additional,  &left.additionals,  &right.additionals),
+    ];
+
+    if left.queries.len() != right.queries.len() {
+        changes.push(Change::Count(Section::Query, left.queries.len(), right.queries.len()));
+    }
+
+    for (section, l, r) in &sections {
+        if l.len() != r.len() {
+            changes.push(Change::Count(*section, l.len(), r.len()));
+        }
+    }
+
+    for query in &left.queries {
+        if ! right.queries.iter().any(|q| same_query(q, query)) {
+            changes.push(Change::QueryRemoved(query.clone()));
+        }
+    }
+
+    for query in &right.queries {
+        if ! left.queries.iter().any(|q| same_query(q, query)) {
+            changes.push(Change::QueryAdded(query.clone()));
+        }
+    }
+
+    for (section, l, r) in &sections {
+        diff_section(*section, l, r, options, &mut changes);
+    }
+
+    ResponseDiff { changes }
+}
+
+fn diff_flags(left: &Flags, right: &Flags, changes: &mut Vec<Change>) {
+    let bits = [
+        ("qr", left.response,             right.response),
+        ("aa", left.authoritative,        right.authoritative),
+        ("tc", left.truncated,            right.truncated),
+        ("rd", left.recursion_desired,    right.recursion_desired),
+        ("ra", left.recursion_available,  right.recursion_available),
+        ("ad", left.authentic_data,       right.authentic_data),
+        ("cd", left.checking_disabled,    right.checking_disabled),
+        ("z",  left.reserved,             right.reserved),
+    ];
+
+    if left.opcode != right.opcode {
+        changes.push(Change::Opcode(left.opcode, right.opcode));
+    }
+
+    for (name, l, r) in bits.iter().copied() {
+        if l != r {
+            changes.push(Change::Flag(name, l, r));
+        }
+    }
+
+    if left.error_code != right.error_code {
+        changes.push(Change::Rcode(left.error_code, right.error_code));
+    }
+}
+
+/// Matches up the records in one section of each message. Each record on
+/// the left gets paired with the first unpaired record on the right that
+/// is the same apart from its TTL, so duplicates are counted properly.
+fn diff_section(section: Section, left: &[Answer], right: &[Answer], options: DiffOptions, changes: &mut Vec<Change>) {
+    let mut paired = vec![ false; right.len() ];
+    let mut added_or_changed = Vec::new();
+
+    for l in left {
+        let found = right.iter().enumerate().position(|(index, r)| ! paired[index] && same_apart_from_ttl(l, r));
+
+        match found {
+            Some(index) => {
+                paired[index] = true;
+
+                if let (Some(left_ttl), Some(right_ttl)) = (ttl(l), ttl(&right[index])) {
+                    if left_ttl != right_ttl && ! options.ignore_ttl {
+                        added_or_changed.push((index, Change::TTL(section, right[index].clone(), left_ttl)));
+                    }
+                }
+            }
+            None => {
+                changes.push(Change::Removed(section, l.clone()));
+            }
+        }
+    }
+
+    for (index, r) in right.iter().enumerate() {
+        if ! paired[index] {
+            added_or_changed.push((index, Change::Added(section, r.clone())));
+        }
+    }
+
+    // Changed and added records are listed in the order they appear in
+    // the right message, after the removed ones.
+    added_or_changed.sort_by_key(|(index, _)| *index);
+    changes.extend(added_or_changed.into_iter().map(|(_, change)| change));
+}
+
+/// Whether two records are the same, apart from their TTLs. Owner names
+/// are compared without regard to case.
+fn same_apart_from_ttl(left: &Answer, right: &Answer) -> bool {
+    match (left, right) {
+        (Answer::Standard { qname: ln, qclass: lc, record: lr, .. },
+         Answer::Standard { qname: rn, qclass: rc, record: rr, .. }) => {
+            same_name(ln, rn) && lc == rc && lr == rr
+        }
+        (Answer::Pseudo { qname: ln, opt: lo },
+         Answer::Pseudo { qname: rn, opt: ro }) => {
+            same_name(ln, rn) && lo == ro
+        }
+        _ => false,
+    }
+}
+
+fn same_query(left: &Query, right: &Query) -> bool {
+    same_name(&left.qname, &right.qname) && left.qclass == right.qclass && left.qtype == right.qtype
+}
+
+fn same_name(left: &Name, right: &Name) -> bool {
+    left.as_str().eq_ignore_ascii_case(right.as_str())
+}
+
+fn ttl(answer: &Answer) -> Option<u32> {
+    match answer {
+        Answer::Standard { ttl, .. }  => Some(*ttl),
+        Answer::Pseudo { .. }         => None,
+    }
+}
+
+
+impl ResponseDiff {
+
+    /// Whether the two messages are the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The number of differences between the two messages.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+/// A diff is displayed with one line per change, with `-` in front of
+/// records and queries that are only on the left, `+` in front of ones
+/// only on the right, and `~` in front of everything else.
+impl fmt::Display for ResponseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransactionId(l, r)       => write!(f, "~ transaction ID: {:#06x} -> {:#06x}", l, r),
+            Self::Flag(name, l, r)          => write!(f, "~ {} flag: {} -> {}", name, u8::from(*l), u8::from(*r)),
+            Self::Opcode(l, r)              => write!(f, "~ opcode: {} -> {}", l, r),
+            Self::Rcode(l, r)               => write!(f, "~ rcode: {} -> {}", rcode_mnemonic(*l), rcode_mnemonic(*r)),
+            Self::Count(section, l, r)      => write!(f, "~ {} count: {} -> {}", section, l, r),
+            Self::QueryRemoved(query)       => write!(f, "- query: {}", QueryDisplay(query)),
+            Self::QueryAdded(query)         => write!(f, "+ query: {}", QueryDisplay(query)),
+            Self::Removed(section, answer)  => write!(f, "- {}: {}", section, answer),
+            Self::Added(section, answer)    => write!(f, "+ {}: {}", section, answer),
+            Self::TTL(section, answer, ttl) => write!(f, "~ {}: {} (TTL was {})", section, answer, ttl),
+        }
+    }
+}
+
+/// Displays a query the way it would appear in a zone file.
+struct QueryDisplay<'a>(&'a Query);
+
+impl fmt::Display for QueryDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Query { qname, qclass, qtype } = self.0;
+
+        match find_qtype_name(*qtype) {
+            Some(name)  => write!(f, "{} {} {}", qname, qclass, name),
+            None        => write!(f, "{} {} TYPE{}", qname, qclass, qtype),
+        }
+    }
+}
+
+/// Returns the mnemonic for a response code, as used by other tools.
+fn rcode_mnemonic(rcode: Option<ErrorCode>) -> String {
+    match ErrorCode::mnemonic_of(rcode) {
+        Some(mnemonic)  => mnemonic.into(),
+        None            => format!("RCODE{}", rcode.map_or(0, ErrorCode::to_bits)),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::record::{Record, A};
+
+    fn a(ttl: u32, last_octet: u8) -> Answer {
+        Answer::Standard {
+            qname: "lookup.dog.".parse().unwrap(),
+            qclass: QClass::IN,
+            ttl,
+            record: Record::A(A { address: std::net::Ipv4Addr::new(192, 0, 2, last_octet) }),
+        }
+    }
+
+    fn response(answers: Vec<Answer>) -> Response {
+        Response {
+            transaction_id: 0x1234,
+            flags: Flags::from_u16(0x8180),
+            queries: vec![ Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 } ],
+            answers,
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn identical() {
+        let left = response(vec![ a(300, 1) ]);
+        assert!(diff(&left, &left.clone(), DiffOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn order_ignored() {
+        let left = response(vec![ a(300, 1), a(300, 2) ]);
+        let right = response(vec![ a(300, 2), a(300, 1) ]);
+        assert!(diff(&left, &right, DiffOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn ttl_change() {
+        let left = response(vec![ a(300, 1) ]);
+        let right = response(vec![ a(299, 1) ]);
+
+        assert_eq!(diff(&left, &right, DiffOptions::default()).changes,
+                   vec![ Change::TTL(Section::Answer, a(299, 1), 300) ]);
+
+        let options = DiffOptions { ignore_ttl: true, .. DiffOptions::default() };
+        assert!(diff(&left, &right, options).is_empty());
+    }
+
+    #[test]
+    fn records_added_and_removed() {
+        let left = response(vec![ a(300, 1), a(300, 2) ]);
+        let right = response(vec![ a(300, 3), a(300, 1) ]);
+
+        assert_eq!(diff(&left, &right, DiffOptions::default()).changes, vec![
+            Change::Removed(Section::Answer, a(300, 2)),
+            Change::Added(Section::Answer, a(300, 3)),
+        ]);
+    }
+
+    #[test]
+    fn duplicates_counted() {
+        let left = response(vec![ a(300, 1) ]);
+        let right = response(vec![ a(300, 1), a(300, 1) ]);
+
+        assert_eq!(diff(&left, &right, DiffOptions::default()).changes, vec![
+            Change::Count(Section::Answer, 1, 2),
+            Change::Added(Section::Answer, a(300, 1)),
+        ]);
+    }
+
+    #[test]
+    fn header_changes() {
+        let left = response(Vec::new());
+        let mut right = response(Vec::new());
+        right.transaction_id = 0x4321;
+        right.flags.authoritative = true;
+        right.flags.error_code = Some(ErrorCode::NXDomain);
+
+        let changes = diff(&left, &right, DiffOptions::default());
+        assert_eq!(changes.to_string(), "\
+~ transaction ID: 0x1234 -> 0x4321
+~ aa flag: 0 -> 1
+~ rcode: NOERROR -> NXDOMAIN
+");
+
+        let options = DiffOptions { ignore_transaction_id: true, .. DiffOptions::default() };
+        assert_eq!(diff(&left, &right, options).len(), 2);
+    }
+
+    #[test]
+    fn query_changed() {
+        let left = response(Vec::new());
+        let mut right = response(Vec::new());
+        right.queries[0].qtype = 28;
+
+        assert_eq!(diff(&left, &right, DiffOptions::default()).to_string(), "\
+- query: lookup.dog. IN A
++ query: lookup.dog. IN AAAA
+");
+    }
+}
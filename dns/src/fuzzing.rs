@@ -0,0 +1,260 @@
+//! `Arbitrary` implementations for messages and records, behind the
+//! `arbitrary` feature, so fuzz targets can build them out of random bytes.
+//!
+//! Names are always made of valid labels, and unknown records never use
+//! the type number of a record that dog knows how to parse, so that most
+//! generated messages can be encoded. The rest of each value is left as
+//! random as its type allows, including strings that are too long to fit
+//! in a record, which encoding should reject rather than mangle.
+
+use arbitrary::{Arbitrary, Unstructured, Result};
+
+use crate::name::Name;
+use crate::record::*;
+use crate::types::*;
+
+
+/// The characters that names get built from. Mixing cases checks that
+/// nothing along the way depends on it.
+const LABEL_CHARACTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_";
+
+// ---- names, classes, and flags ----
+
+impl<'a> Arbitrary<'a> for Name {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut text = String::new();
+
+        for _ in 0 .. u.int_in_range(0 ..= 4)? {
+            for _ in 0 .. u.int_in_range(1 ..= 20)? {
+                text.push(char::from(*u.choose(LABEL_CHARACTERS)?));
+            }
+
+            text.push('.');
+        }
+
+        if text.is_empty() {
+            return Ok(Name::root());
+        }
+
+        Ok(Name::unchecked(text))
+    }
+}
+
+impl<'a> Arbitrary<'a> for QClass {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_u16(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ErrorCode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        // Extended rcodes have twelve bits, and zero means no error.
+        Ok(Self::from_bits(u.int_in_range(1 ..= 0x0FFF)?).expect("Non-zero rcode"))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Flags {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_u16(u.arbitrary()?))
+    }
+}
+
+
+// ---- messages ----
+
+impl<'a> Arbitrary<'a> for Query {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { qname: u.arbitrary()?, qclass: u.arbitrary()?, qtype: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Answer {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.ratio(1, 8)? {
+            Ok(Self::Pseudo { qname: u.arbitrary()?, opt: u.arbitrary()? })
+        }
+        else {
+            Ok(Self::Standard { qname: u.arbitrary()?, qclass: u.arbitrary()?, ttl: u.arbitrary()?, record: u.arbitrary()? })
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for Request {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            transaction_id: u.arbitrary()?,
+            flags: u.arbitrary()?,
+            queries: u.arbitrary()?,
+            additional: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Response {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            transaction_id: u.arbitrary()?,
+            flags: u.arbitrary()?,
+            queries: u.arbitrary()?,
+            answers: u.arbitrary()?,
+            authorities: u.arbitrary()?,
+            additionals: u.arbitrary()?,
+        })
+    }
+}
+
+
+// ---- records ----
+
+impl<'a> Arbitrary<'a> for Record {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0 ..= 13)? {
+             0 => Self::A(u.arbitrary()?),
+             1 => Self::AAAA(u.arbitrary()?),
+             2 => Self::CAA(u.arbitrary()?),
+             3 => Self::CNAME(u.arbitrary()?),
+             4 => Self::DNSKEY(u.arbitrary()?),
+             5 => Self::DS(u.arbitrary()?),
+             6 => Self::MX(u.arbitrary()?),
+             7 => Self::NS(u.arbitrary()?),
+             8 => Self::PTR(u.arbitrary()?),
+             9 => Self::RRSIG(u.arbitrary()?),
+            10 => Self::SOA(u.arbitrary()?),
+            11 => Self::SRV(u.arbitrary()?),
+            12 => Self::TXT(u.arbitrary()?),
+             _ => Self::Other { type_number: u.arbitrary()?, bytes: u.arbitrary()? },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for UnknownQtype {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let number = u.arbitrary()?;
+
+        // An unknown record must not have the type number of a record
+        // that gets parsed into its own variant.
+        if is_parsed_type(number) {
+            // Move it into the range set aside for private use.
+            Ok(Self::from(0xFF00 | number))
+        }
+        else {
+            Ok(Self::from(number))
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for A {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { address: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AAAA {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { address: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CAA {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { critical: u.arbitrary()?, tag: u.arbitrary()?, value: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CNAME {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { domain: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for DNSKEY {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { flags: u.arbitrary()?, protocol: u.arbitrary()?, algorithm: u.arbitrary()?, public_key: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for DS {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { key_tag: u.arbitrary()?, algorithm: u.arbitrary()?, digest_type: u.arbitrary()?, digest: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MX {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { preference: u.arbitrary()?, exchange: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for NS {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { nameserver: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for OPT {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            udp_payload_size: u.arbitrary()?,
+            higher_bits: u.arbitrary()?,
+            edns0_version: u.arbitrary()?,
+            flags: u.arbitrary()?,
+            data: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for PTR {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { cname: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for RRSIG {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            type_covered: u.arbitrary()?,
+            algorithm: u.arbitrary()?,
+            labels: u.arbitrary()?,
+            original_ttl: u.arbitrary()?,
+            expiration: u.arbitrary()?,
+            inception: u.arbitrary()?,
+            key_tag: u.arbitrary()?,
+            signer_name: u.arbitrary()?,
+            signature: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SOA {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            mname: u.arbitrary()?,
+            rname: u.arbitrary()?,
+            serial: u.arbitrary()?,
+            refresh_interval: u.arbitrary()?,
+            retry_interval: u.arbitrary()?,
+            expire_limit: u.arbitrary()?,
+            minimum_ttl: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SRV {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self { priority: u.arbitrary()?, weight: u.arbitrary()?, port: u.arbitrary()?, target: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TXT {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        // There has to be at least one string, and none of them can be
+        // longer than a length byte can say.
+        let mut strings: Vec<Vec<u8>> = u.arbitrary()?;
+        strings.iter_mut().for_each(|string| string.truncate(255));
+        if strings.is_empty() {
+            strings.push(Vec::new());
+        }
+
+        Ok(Self { strings })
+    }
+}
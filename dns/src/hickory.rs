@@ -0,0 +1,205 @@
+//! Conversions between messages and records and their equivalents in
+//! hickory-dns, behind the `hickory` feature, so that code already using
+//! hickory can hand its messages to dog and get them back.
+//!
+//! Everything goes through the wire format on the way: one side writes
+//! the bytes, and the other side reads them. This means the conversions
+//! agree with what each library would see coming off the network, without
+//! having to be kept in step with every record type either of them adds.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::Cursor;
+
+use hickory_proto::ProtoError;
+use hickory_proto::op::Message;
+use hickory_proto::rr::{RData, RecordType};
+use hickory_proto::serialize::binary::{BinDecoder, BinEncodable, BinEncoder, Restrict};
+
+use crate::record::Record;
+use crate::types::Response;
+use crate::wire::{ParseOptions, WireError};
+
+
+/// Something that can go wrong converting to or from a hickory type.
+#[derive(Debug)]
+pub enum HickoryError {
+
+    /// dog could not write the value, or could not read what hickory wrote.
+    Wire(WireError),
+
+    /// hickory could not write the value, or could not read what dog wrote.
+    Hickory(ProtoError),
+
+    /// The record’s data was longer than a record can hold.
+    RecordTooLong(usize),
+}
+
+impl From<WireError> for HickoryError {
+    fn from(error: WireError) -> Self {
+        Self::Wire(error)
+    }
+}
+
+impl From<ProtoError> for HickoryError {
+    fn from(error: ProtoError) -> Self {
+        Self::Hickory(error)
+    }
+}
+
+impl fmt::Display for HickoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wire(e)             => write!(f, "{}", e),
+            Self::Hickory(e)          => write!(f, "hickory: {}", e),
+            Self::RecordTooLong(len)  => write!(f, "Record too long: {} bytes (maximum 65535)", len),
+        }
+    }
+}
+
+impl std::error::Error for HickoryError {}
+
+
+// ---- records ----
+
+impl TryFrom<&Record> for RData {
+    type Error = HickoryError;
+
+    fn try_from(record: &Record) -> Result<Self, Self::Error> {
+        let mut bytes = Vec::new();
+        record.write(&mut bytes)?;
+
+        if bytes.len() > usize::from(u16::MAX) {
+            return Err(HickoryError::RecordTooLong(bytes.len()));
+        }
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let record_type = RecordType::from(record.type_number());
+        Ok(RData::read(&mut decoder, record_type, Restrict::new(bytes.len() as u16))?)
+    }
+}
+
+impl TryFrom<&RData> for Record {
+    type Error = HickoryError;
+
+    fn try_from(rdata: &RData) -> Result<Self, Self::Error> {
+        let mut bytes = Vec::new();
+
+        // Names inside record data get written in full, as there is no
+        // message around them for compression pointers to point into.
+        let mut encoder = BinEncoder::new(&mut bytes);
+        encoder.with_canonical_names(|encoder| rdata.emit(encoder))?;
+
+        if bytes.len() > usize::from(u16::MAX) {
+            return Err(HickoryError::RecordTooLong(bytes.len()));
+        }
+
+        let mut c = Cursor::new(&bytes[..]);
+        let qtype = u16::from(rdata.record_type());
+        Ok(Record::from_bytes(qtype, bytes.len() as u16, &mut c, &ParseOptions::default())?)
+    }
+}
+
+
+// ---- messages ----
+
+impl TryFrom<&Response> for Message {
+    type Error = HickoryError;
+
+    fn try_from(response: &Response) -> Result<Self, Self::Error> {
+        Ok(Message::from_vec(&response.to_bytes()?)?)
+    }
+}
+
+impl TryFrom<&Message> for Response {
+    type Error = HickoryError;
+
+    fn try_from(message: &Message) -> Result<Self, Self::Error> {
+        Ok(Response::from_bytes(&message.to_vec()?)?)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hickory_proto::rr::rdata;
+    use hickory_proto::rr::Name as HickoryName;
+
+    use crate::name::Name;
+    use crate::record::*;
+    use crate::types::*;
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn hickory_name(input: &str) -> HickoryName {
+        HickoryName::from_ascii(input).unwrap()
+    }
+
+    #[test]
+    fn a_to_hickory() {
+        let record = Record::A(A { address: "127.0.0.1".parse().unwrap() });
+        assert_eq!(RData::try_from(&record).unwrap(),
+                   RData::A(rdata::A("127.0.0.1".parse().unwrap())));
+    }
+
+    #[test]
+    fn mx_from_hickory() {
+        let rdata = RData::MX(rdata::MX::new(10, hickory_name("mail.lookup.dog.")));
+        assert_eq!(Record::try_from(&rdata).unwrap(),
+                   Record::MX(MX { preference: 10, exchange: name("mail.lookup.dog.") }));
+    }
+
+    #[test]
+    fn soa_round_trip() {
+        // Both names end in the same labels, which must not be compressed.
+        let record = Record::SOA(SOA {
+            mname: name("ns1.lookup.dog."), rname: name("hostmaster.lookup.dog."),
+            serial: 2020090801, refresh_interval: 7200, retry_interval: 3600,
+            expire_limit: 1209600, minimum_ttl: 300,
+        });
+
+        let rdata = RData::try_from(&record).unwrap();
+        assert_eq!(Record::try_from(&rdata).unwrap(), record);
+    }
+
+    #[test]
+    fn unknown_round_trip() {
+        let record = Record::Other { type_number: UnknownQtype::UnheardOf(65280), bytes: vec![ 0xde, 0xad ] };
+        let rdata = RData::try_from(&record).unwrap();
+        assert_eq!(rdata.record_type(), RecordType::Unknown(65280));
+        assert_eq!(Record::try_from(&rdata).unwrap(), record);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let mut flags = Flags::query();
+        flags.response = true;
+
+        let response = Response {
+            transaction_id: 0xbeef,
+            flags,
+            queries: vec![ Query { qname: name("lookup.dog."), qclass: QClass::IN, qtype: 1 } ],
+            answers: vec![
+                Answer::Standard { qname: name("lookup.dog."), qclass: QClass::IN, ttl: 300, record: Record::A(A { address: "127.0.0.1".parse().unwrap() }) },
+            ],
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        };
+
+        let message = Message::try_from(&response).unwrap();
+        assert_eq!(message.id(), 0xbeef);
+        assert_eq!(message.answers().len(), 1);
+        assert_eq!(message.answers()[0].data(), &RData::A(rdata::A("127.0.0.1".parse().unwrap())));
+
+        assert_eq!(Response::try_from(&message).unwrap(), response);
+    }
+
+    #[test]
+    fn hickory_rejects_bad_data() {
+        let record = Record::Other { type_number: UnknownQtype::from(13), bytes: vec![ 0xff ] };
+        assert!(matches!(RData::try_from(&record), Err(HickoryError::Hickory(_))));
+    }
+}
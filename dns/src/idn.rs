@@ -0,0 +1,244 @@
+//! Preparing the labels of internationalised domain names to be encoded
+//! with Punycode: normalising them, and checking that IDNA2008 allows
+//! every code point in them.
+//!
+//! # References
+//!
+//! - [RFC 5891](https://tools.ietf.org/html/rfc5891) — Internationalized Domain Names in Applications (IDNA): Protocol (August 2010)
+//! - [RFC 5892](https://tools.ietf.org/html/rfc5892) — The Unicode Code Points and Internationalized Domain Names for Applications (IDNA) (August 2010)
+//! - [RFC 5895](https://tools.ietf.org/html/rfc5895) — Mapping Characters for Internationalized Domain Names in Applications (IDNA) 2008 (September 2010)
+
+use std::fmt;
+
+use icu_normalizer::ComposingNormalizerBorrowed;
+use icu_properties::{CodePointMapData, CodePointSetData};
+use icu_properties::props::{CanonicalCombiningClass, ChangesWhenNfkcCasefolded, DefaultIgnorableCodePoint, GeneralCategory, HangulSyllableType, NoncharacterCodePoint, Script, WhiteSpace};
+
+
+/// Lowercases a label and puts it into Normalization Form C, as RFC 5895
+/// suggests doing to what the user typed, then checks that the result is
+/// a valid U-label.
+pub fn prepare_label(label: &str) -> Result<String, IdnError> {
+    let lowercase = label.to_lowercase();
+    let normalised = ComposingNormalizerBorrowed::new_nfc().normalize(&lowercase).into_owned();
+    check_label(&normalised)?;
+    Ok(normalised)
+}
+
+/// Checks a label against the rules in RFC 5891 §4.2.3: that it does not
+/// start with a combining mark, that its hyphens are in allowed places,
+/// and that every code point in it is allowed by RFC 5892, including the
+/// contextual ones in their contexts.
+fn check_label(label: &str) -> Result<(), IdnError> {
+    let categories = CodePointMapData::<GeneralCategory>::new();
+
+    if let Some(first) = label.chars().next() {
+        if matches!(categories.get(first), GeneralCategory::NonspacingMark | GeneralCategory::SpacingMark | GeneralCategory::EnclosingMark) {
+            return Err(IdnError::LeadingCombiningMark(label.into()));
+        }
+    }
+
+    let chars = label.chars().collect::<Vec<_>>();
+    if label.starts_with('-') || label.ends_with('-') || chars.get(2 .. 4) == Some(&['-', '-']) {
+        return Err(IdnError::MisplacedHyphen(label.into()));
+    }
+
+    for (index, &c) in chars.iter().enumerate() {
+        let allowed = match derived_property(c) {
+            Property::Valid       => true,
+            Property::ContextJ    => joiner_allowed(&chars, index),
+            Property::ContextO    => other_allowed(&chars, index),
+            Property::Disallowed  => false,
+        };
+
+        if ! allowed {
+            return Err(IdnError::DisallowedCodePoint(label.into(), c));
+        }
+    }
+
+    Ok(())
+}
+
+/// What RFC 5892 says about whether a code point can go in a label.
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Property {
+
+    /// The code point is always allowed.
+    Valid,
+
+    /// The code point is a joiner, which is only allowed after a virama.
+    ContextJ,
+
+    /// The code point is only allowed next to certain other ones.
+    ContextO,
+
+    /// The code point is never allowed, or has not been assigned yet.
+    Disallowed,
+}
+
+/// Works out the derived property of a code point, going through the
+/// categories in the order that RFC 5892 §3 gives them.
+fn derived_property(c: char) -> Property {
+    match c {
+        // Exceptions (§2.6)
+        '\u{00DF}' | '\u{03C2}' | '\u{06FD}' | '\u{06FE}' | '\u{0F0B}' | '\u{3007}'  => return Property::Valid,
+        '\u{00B7}' | '\u{0375}' | '\u{05F3}' | '\u{05F4}' | '\u{30FB}'               => return Property::ContextO,
+        '\u{0660}' ..= '\u{0669}' | '\u{06F0}' ..= '\u{06F9}'                        => return Property::ContextO,
+        '\u{0640}' | '\u{07FA}' | '\u{302E}' | '\u{302F}' | '\u{303B}'               => return Property::Disallowed,
+        '\u{3031}' ..= '\u{3035}'                                                    => return Property::Disallowed,
+
+        // LDH (§2.5)
+        'a' ..= 'z' | '0' ..= '9' | '-'  => return Property::Valid,
+
+        // JoinControl (§2.8)
+        '\u{200C}' | '\u{200D}'  => return Property::ContextJ,
+
+        // IgnorableBlocks (§2.4)
+        '\u{20D0}' ..= '\u{20FF}' | '\u{1D100}' ..= '\u{1D24F}'  => return Property::Disallowed,
+
+        _ => {}
+    }
+
+    let category = CodePointMapData::<GeneralCategory>::new().get(c);
+    let unassigned = category == GeneralCategory::Unassigned && ! CodePointSetData::new::<NoncharacterCodePoint>().contains(c);
+    let unstable = CodePointSetData::new::<ChangesWhenNfkcCasefolded>().contains(c);
+    let ignorable = CodePointSetData::new::<DefaultIgnorableCodePoint>().contains(c)
+                 || CodePointSetData::new::<WhiteSpace>().contains(c)
+                 || CodePointSetData::new::<NoncharacterCodePoint>().contains(c);
+    let old_jamo = matches!(CodePointMapData::<HangulSyllableType>::new().get(c), HangulSyllableType::LeadingJamo | HangulSyllableType::VowelJamo | HangulSyllableType::TrailingJamo);
+
+    if unassigned || unstable || ignorable || old_jamo {
+        return Property::Disallowed;
+    }
+
+    match category {
+        GeneralCategory::LowercaseLetter | GeneralCategory::UppercaseLetter |
+        GeneralCategory::OtherLetter     | GeneralCategory::DecimalNumber   |
+        GeneralCategory::ModifierLetter  | GeneralCategory::NonspacingMark  |
+        GeneralCategory::SpacingMark     => Property::Valid,
+        _                                => Property::Disallowed,
+    }
+}
+
+/// Whether the zero width joiner or non-joiner at the given place comes
+/// straight after a virama (RFC 5892 Appendix A.1 and A.2). The rule that
+/// also lets the non-joiner go between certain Arabic letters is not
+/// followed, so those labels get rejected.
+fn joiner_allowed(chars: &[char], index: usize) -> bool {
+    index.checked_sub(1)
+         .map(|before| CodePointMapData::<CanonicalCombiningClass>::new().get(chars[before]))
+         .is_some_and(|class| class == CanonicalCombiningClass::Virama)
+}
+
+/// Whether the contextual code point at the given place has what it needs
+/// around it (RFC 5892 Appendix A.3 to A.9).
+fn other_allowed(chars: &[char], index: usize) -> bool {
+    let scripts = CodePointMapData::<Script>::new();
+    let before = index.checked_sub(1).map(|i| chars[i]);
+    let after = chars.get(index + 1).copied();
+
+    match chars[index] {
+        // A middle dot has to be between two ells, as in Catalan.
+        '\u{00B7}'  => before == Some('l') && after == Some('l'),
+
+        // The Greek lower numeral sign has to come before Greek.
+        '\u{0375}'  => after.is_some_and(|c| scripts.get(c) == Script::Greek),
+
+        // The Hebrew geresh and gershayim have to come after Hebrew.
+        '\u{05F3}' | '\u{05F4}'  => before.is_some_and(|c| scripts.get(c) == Script::Hebrew),
+
+        // The katakana middle dot needs Japanese somewhere in the label.
+        '\u{30FB}'  => chars.iter().any(|&c| matches!(scripts.get(c), Script::Hiragana | Script::Katakana | Script::Han)),
+
+        // The two sets of Arabic-Indic digits cannot be mixed.
+        '\u{0660}' ..= '\u{0669}'  => ! chars.iter().any(|c| ('\u{06F0}' ..= '\u{06F9}').contains(c)),
+        '\u{06F0}' ..= '\u{06F9}'  => ! chars.iter().any(|c| ('\u{0660}' ..= '\u{0669}').contains(c)),
+
+        _  => false,
+    }
+}
+
+
+/// Something that stops a label of an internationalised domain name from
+/// being encoded. Each contains the label, after being normalised.
+#[derive(PartialEq, Debug)]
+pub enum IdnError {
+
+    /// The label starts with a combining mark, which has nothing before
+    /// it to combine with.
+    LeadingCombiningMark(String),
+
+    /// The label starts or ends with a hyphen, or has two hyphens as its
+    /// third and fourth characters, which is kept for prefixes like `xn--`.
+    MisplacedHyphen(String),
+
+    /// The label contains a code point that IDNA2008 does not allow,
+    /// either at all or where it is. Contains the code point too.
+    DisallowedCodePoint(String, char),
+
+    /// The label is too long for the Punycode encoder to handle.
+    Unencodable(String),
+}
+
+impl fmt::Display for IdnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LeadingCombiningMark(label)   => write!(f, "label {:?} starts with a combining mark", label),
+            Self::MisplacedHyphen(label)        => write!(f, "label {:?} has a hyphen at its start, its end, or in its third and fourth places", label),
+            Self::DisallowedCodePoint(label, c) => write!(f, "label {:?} contains U+{:04X}, which is not allowed", label, u32::from(*c)),
+            Self::Unencodable(label)            => write!(f, "label {:?} is too long to encode", label),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lowercases_and_normalises() {
+        // ‘u’ followed by a combining diaeresis turns into ‘ü’.
+        assert_eq!(prepare_label("Bu\u{0308}cher"), Ok("bücher".into()));
+    }
+
+    #[test]
+    fn leading_combining_mark() {
+        assert_eq!(prepare_label("\u{0308}bc"), Err(IdnError::LeadingCombiningMark("\u{0308}bc".into())));
+    }
+
+    #[test]
+    fn disallowed_symbol() {
+        assert_eq!(prepare_label("i♥dns"), Err(IdnError::DisallowedCodePoint("i♥dns".into(), '♥')));
+    }
+
+    #[test]
+    fn disallowed_width() {
+        // Fullwidth letters change under NFKC, so they are not stable.
+        assert_eq!(prepare_label("ｄｏｇ"), Err(IdnError::DisallowedCodePoint("ｄｏｇ".into(), 'ｄ')));
+    }
+
+    #[test]
+    fn exceptions() {
+        assert_eq!(prepare_label("straße"), Ok("straße".into()));
+        assert_eq!(prepare_label("ab\u{0640}cd"), Err(IdnError::DisallowedCodePoint("ab\u{0640}cd".into(), '\u{0640}')));
+    }
+
+    #[test]
+    fn middle_dot() {
+        assert_eq!(prepare_label("col·lecció"), Ok("col·lecció".into()));
+        assert_eq!(prepare_label("a·lecció"), Err(IdnError::DisallowedCodePoint("a·lecció".into(), '·')));
+    }
+
+    #[test]
+    fn joiner_after_virama() {
+        assert!(prepare_label("क्\u{200D}ष").is_ok());
+        assert!(prepare_label("क\u{200D}ष").is_err());
+    }
+
+    #[test]
+    fn misplaced_hyphens() {
+        assert_eq!(prepare_label("bü--cher"), Err(IdnError::MisplacedHyphen("bü--cher".into())));
+        assert_eq!(prepare_label("bücher-"), Err(IdnError::MisplacedHyphen("bücher-".into())));
+    }
+}
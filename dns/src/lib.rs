@@ -19,9 +19,57 @@
 mod types;
 pub use self::types::*;
 
+mod name;
+pub use self::name::Name;
+
+mod punycode;
+
+mod idn;
+pub use self::idn::IdnError;
+
+pub mod diff;
+pub mod presentation;
+pub mod zonefile;
+
+#[cfg(feature = "serde")]
+mod serialise;
+
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+
+#[cfg(feature = "hickory")]
+mod hickory;
+#[cfg(feature = "hickory")]
+pub use self::hickory::HickoryError;
+
 mod strings;
 
+mod builder;
+pub use self::builder::MessageBuilder;
+
+mod cache;
+pub use self::cache::{Cache, cache_ttl};
+
+mod txid;
+pub use self::txid::{TxidGenerator, RandomTxids, SeededTxids, SequentialTxids};
+
+mod zone;
+pub use self::zone::{Zone, ZoneContentsError};
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
 mod wire;
-pub use self::wire::{Wire, WireError, find_qtype_number};
+pub use self::wire::{Wire, WireError, ErrorLocation, Section, ParseOptions, WriteOptions, find_qtype_number, find_qtype_name, qtype_names};
+pub use self::wire::{read_tcp_message, write_tcp_message, frame_tcp_message, TcpMessageBuffer};
+
+mod borrowed;
+pub use self::borrowed::{ResponseRef, QueryRef, AnswerRef, NameRef};
 
 pub mod record;
+
+#[doc(hidden)]
+pub mod derive;
+
+#[cfg(feature = "derive")]
+pub use dns_derive::Wire;
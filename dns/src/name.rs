@@ -0,0 +1,357 @@
+//! Domain names, which get compared without regard to case.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::idn::{self, IdnError};
+use crate::punycode;
+use crate::wire::WireError;
+
+
+/// A domain name, such as `dns.lookup.dog.`, that has been checked to be
+/// short enough to be encoded in a packet.
+///
+/// Names are kept exactly as they were written or received, so the case
+/// of each letter and whether there is a trailing dot are preserved when
+/// they get displayed. Comparing and hashing names ignores both, as DNS
+/// does (RFC 4343).
+///
+/// # Examples
+///
+/// ```
+/// use dns::Name;
+///
+/// let name: Name = "Lookup.Dog".parse().unwrap();
+/// assert_eq!(name, "lookup.dog.".parse().unwrap());
+/// assert_eq!(name.to_string(), "Lookup.Dog");
+///
+/// assert!("a".repeat(64).parse::<Name>().is_err());
+/// ```
+#[derive(Clone, Default)]
+pub struct Name {
+    text: String,
+}
+
+impl Name {
+
+    /// The longest a single label can be, as the top two bits of the
+    /// length byte are used for compression pointers (RFC 1035 §2.3.4).
+    pub const MAX_LABEL_LENGTH: usize = 63;
+
+    /// The longest an encoded name can be, including the length bytes and
+    /// the terminating zero (RFC 1035 §2.3.4).
+    pub const MAX_NAME_LENGTH: usize = 255;
+
+    /// The name of the root zone.
+    pub fn root() -> Self {
+        Self { text: ".".into() }
+    }
+
+    /// Converts a name that may contain non-ASCII characters into the form
+    /// that gets sent over the wire, with each internationalised label
+    /// lowercased, normalised to NFC, checked against IDNA2008, and encoded
+    /// with Punycode (RFC 5891 §4.4). The ideographic full stops are
+    /// accepted as label separators (RFC 3490 §3.1).
+    ///
+    /// Names that are entirely ASCII are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dns::Name;
+    ///
+    /// let name = Name::from_unicode("Bücher.example").unwrap();
+    /// assert_eq!(name.as_str(), "xn--bcher-kva.example");
+    /// assert_eq!(name.to_unicode(), "bücher.example");
+    /// ```
+    pub fn from_unicode(input: &str) -> Result<Self, WireError> {
+        if input.is_ascii() {
+            return input.parse();
+        }
+
+        let labels = input.split(['.', '\u{3002}', '\u{FF0E}', '\u{FF61}']).map(|label| {
+            if label.is_ascii() {
+                Ok(label.to_owned())
+            }
+            else {
+                let label = idn::prepare_label(label).map_err(WireError::InvalidIdn)?;
+                match punycode::encode(&label) {
+                    Some(encoded)  => Ok(format!("xn--{}", encoded)),
+                    None           => Err(WireError::InvalidIdn(IdnError::Unencodable(label))),
+                }
+            }
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        labels.join(".").parse()
+    }
+
+    /// Returns the name with any Punycode labels decoded back into Unicode,
+    /// for display. Labels that fail to decode are left as they are.
+    pub fn to_unicode(&self) -> String {
+        self.text.split('.').map(|label| {
+            match label.get(.. 4) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("xn--") => {
+                    punycode::decode(&label[4 ..]).unwrap_or_else(|| label.to_owned())
+                }
+                _ => {
+                    label.to_owned()
+                }
+            }
+        }).collect::<Vec<_>>().join(".")
+    }
+
+    /// Wraps a name that has already been checked, such as one that has
+    /// just been read from a packet.
+    pub(crate) fn unchecked(text: String) -> Self {
+        Self { text }
+    }
+
+    /// Returns the name as it was written.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the name with a trailing dot, which is always there, even
+    /// for the root, which can be parsed out of no text at all.
+    pub fn to_fully_qualified(&self) -> String {
+        format!("{}.", self.trimmed())
+    }
+
+    /// Iterates over the labels in this name, not including the empty
+    /// label for the root.
+    pub fn labels(&self) -> impl Iterator<Item=&str> {
+        self.text.split('.').filter(|l| ! l.is_empty())
+    }
+
+    /// Converts every letter in this name to lowercase, in place, as
+    /// needed for the canonical form of records (RFC 4034 §6.2).
+    pub fn make_ascii_lowercase(&mut self) {
+        self.text.make_ascii_lowercase();
+    }
+
+    /// Whether this is the name of the root zone.
+    pub fn is_root(&self) -> bool {
+        self.labels().next().is_none()
+    }
+
+    /// Whether any of this name’s labels are internationalised labels
+    /// encoded with Punycode, which start with `xn--`.
+    pub fn has_punycode(&self) -> bool {
+        self.labels().any(|l| l.len() >= 4 && l.as_bytes()[.. 4].eq_ignore_ascii_case(b"xn--"))
+    }
+
+    /// Whether this name is the same as, or a subdomain of, the given zone.
+    pub fn is_below_or_at(&self, zone: &Name) -> bool {
+        let (name, zone) = (self.trimmed().as_bytes(), zone.trimmed().as_bytes());
+
+        zone.is_empty()
+            || name.eq_ignore_ascii_case(zone)
+            || (name.len() > zone.len()
+                && name[name.len() - zone.len() ..].eq_ignore_ascii_case(zone)
+                && name[name.len() - zone.len() - 1] == b'.')
+    }
+
+    /// Returns the name of the zone one level up from this one, or `None`
+    /// if this is the root.
+    pub fn parent(&self) -> Option<Name> {
+        if self.is_root() {
+            return None;
+        }
+
+        match self.trimmed().split_once('.') {
+            Some((_, rest))  => Some(Self { text: format!("{}.", rest) }),
+            None             => Some(Self::root()),
+        }
+    }
+
+    /// The number of bytes this name takes up when written uncompressed.
+    pub fn wire_len(&self) -> usize {
+        self.labels().map(|l| 1 + l.len()).sum::<usize>() + 1
+    }
+
+    /// Checks that every label is short enough, and that the whole name is
+    /// short enough, to be encoded.
+    pub(crate) fn check(&self) -> Result<(), WireError> {
+        if let Some(label) = self.labels().find(|l| l.len() > Self::MAX_LABEL_LENGTH) {
+            return Err(WireError::LabelTooLong(label.len()));
+        }
+
+        if self.wire_len() > Self::MAX_NAME_LENGTH {
+            return Err(WireError::NameTooLong(self.wire_len()));
+        }
+
+        Ok(())
+    }
+
+    /// The name without any trailing dot, which is how it gets compared.
+    fn trimmed(&self) -> &str {
+        self.text.strip_suffix('.').unwrap_or(&self.text)
+    }
+}
+
+impl FromStr for Name {
+    type Err = WireError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let name = Self { text: input.into() };
+        name.check()?;
+        Ok(name)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.text)
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.trimmed().eq_ignore_ascii_case(other.trimmed())
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for byte in self.trimmed().bytes() {
+            state.write_u8(byte.to_ascii_lowercase());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn case_insensitive() {
+        let one: Name = "DNS.Lookup.Dog.".parse().unwrap();
+        let two: Name = "dns.lookup.dog".parse().unwrap();
+        assert_eq!(one, two);
+
+        let set = vec![ one, two ].into_iter().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn different_names() {
+        let one: Name = "lookup.dog".parse().unwrap();
+        let two: Name = "lookup.cat".parse().unwrap();
+        assert_ne!(one, two);
+    }
+
+    #[test]
+    fn preserves_case() {
+        let name: Name = "Lookup.Dog.".parse().unwrap();
+        assert_eq!(name.to_string(), "Lookup.Dog.");
+        assert_eq!(name.labels().collect::<Vec<_>>(), vec![ "Lookup", "Dog" ]);
+    }
+
+    #[test]
+    fn root() {
+        assert!(Name::root().is_root());
+        assert_eq!(Name::root(), Name::unchecked(String::new()));
+        assert_eq!(Name::root().wire_len(), 1);
+    }
+
+    #[test]
+    fn fully_qualified() {
+        assert_eq!(Name::unchecked(String::new()).to_fully_qualified(), ".");
+        assert_eq!("lookup.dog".parse::<Name>().unwrap().to_fully_qualified(), "lookup.dog.");
+        assert_eq!("lookup.dog.".parse::<Name>().unwrap().to_fully_qualified(), "lookup.dog.");
+    }
+
+    #[test]
+    fn label_too_long() {
+        let input = format!("{}.dog", "a".repeat(64));
+        assert_eq!(input.parse::<Name>().unwrap_err(), WireError::LabelTooLong(64));
+    }
+
+    #[test]
+    fn name_too_long() {
+        let input = vec![ "a".repeat(63); 4 ].join(".");
+        assert_eq!(input.parse::<Name>().unwrap_err(), WireError::NameTooLong(257));
+    }
+
+    #[test]
+    fn subdomains() {
+        let name = |input: &str| input.parse::<Name>().unwrap();
+
+        assert!(name("dns.lookup.dog").is_below_or_at(&name("lookup.dog.")));
+        assert!(name("LOOKUP.dog.").is_below_or_at(&name("lookup.DOG")));
+        assert!(name("lookup.dog").is_below_or_at(&Name::root()));
+        assert!(! name("lookup.dog").is_below_or_at(&name("kup.dog")));
+        assert!(! name("dog").is_below_or_at(&name("lookup.dog")));
+    }
+
+    #[test]
+    fn parents() {
+        let name: Name = "dns.lookup.dog.".parse().unwrap();
+        assert_eq!(name.parent().unwrap().as_str(), "lookup.dog.");
+        assert_eq!(name.parent().unwrap().parent().unwrap().parent(), Some(Name::root()));
+        assert_eq!(Name::root().parent(), None);
+    }
+
+    #[test]
+    fn punycode() {
+        assert!("xn--bcher-kva.example".parse::<Name>().unwrap().has_punycode());
+        assert!(! "books.example".parse::<Name>().unwrap().has_punycode());
+    }
+
+    #[test]
+    fn from_unicode() {
+        let name = Name::from_unicode("BÜCHER.example.").unwrap();
+        assert_eq!(name.as_str(), "xn--bcher-kva.example.");
+        assert!(name.has_punycode());
+    }
+
+    #[test]
+    fn from_unicode_ideographic_stops() {
+        let name = Name::from_unicode("テスト\u{3002}jp").unwrap();
+        assert_eq!(name.as_str(), "xn--zckzah.jp");
+    }
+
+    #[test]
+    fn from_unicode_too_long() {
+        let input = format!("{}.example", "ü".repeat(60));
+        assert!(matches!(Name::from_unicode(&input), Err(WireError::LabelTooLong(_))));
+    }
+
+    #[test]
+    fn from_unicode_normalises() {
+        let composed = Name::from_unicode("bücher.example").unwrap();
+        let decomposed = Name::from_unicode("bu\u{0308}cher.example").unwrap();
+        assert_eq!(composed.as_str(), decomposed.as_str());
+    }
+
+    #[test]
+    fn from_unicode_invalid() {
+        assert_eq!(Name::from_unicode("\u{0301}a.example"),
+                   Err(WireError::InvalidIdn(IdnError::LeadingCombiningMark("\u{0301}a".into()))));
+        assert_eq!(Name::from_unicode("i♥dns.example"),
+                   Err(WireError::InvalidIdn(IdnError::DisallowedCodePoint("i♥dns".into(), '♥'))));
+    }
+
+    #[test]
+    fn to_unicode() {
+        let name: Name = "www.XN--bcher-kva.example.".parse().unwrap();
+        assert_eq!(name.to_unicode(), "www.bücher.example.");
+    }
+
+    #[test]
+    fn to_unicode_invalid() {
+        let name: Name = "xn--!.example".parse().unwrap();
+        assert_eq!(name.to_unicode(), "xn--!.example");
+    }
+}
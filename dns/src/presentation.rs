@@ -0,0 +1,591 @@
+//! Formatting and parsing the fields of records in presentation format, the
+//! text form that they take in zone files.
+//!
+//! # References
+//!
+//! - [RFC 1035 §5.1](https://tools.ietf.org/html/rfc1035#section-5.1) — Master files format (November 1987)
+//! - [RFC 3597 §5](https://tools.ietf.org/html/rfc3597#section-5) — Text Representation of unknown RRs (September 2003)
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::name::Name;
+use crate::record::{UnknownQtype, find_other_qtype_number};
+use crate::types::TypeInt;
+use crate::wire::{WireError, find_qtype_name, find_qtype_number};
+
+
+/// Formats a byte slice as a string of uppercase hexadecimal digits, the
+/// way digests are written in zone files.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Formats a byte slice as Base64, the way keys and signatures are written
+/// in zone files.
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [ chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0) ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        output.push(ALPHABET[(n >> 18) as usize & 63] as char);
+        output.push(ALPHABET[(n >> 12) as usize & 63] as char);
+        output.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 63] as char } else { '=' });
+        output.push(if chunk.len() > 2 { ALPHABET[n as usize & 63] as char } else { '=' });
+    }
+
+    output
+}
+
+/// Parses a string of hexadecimal digits, in either case, into bytes.
+pub fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    if ! input.len().is_multiple_of(2) {
+        return None;
+    }
+
+    input.as_bytes()
+         .chunks(2)
+         .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+         .collect()
+}
+
+/// Parses Base64 into bytes, requiring padding at the end as zone files
+/// have it.
+pub fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    fn value(byte: u8) -> Option<u32> {
+        match byte {
+            b'A' ..= b'Z'  => Some(u32::from(byte - b'A')),
+            b'a' ..= b'z'  => Some(u32::from(byte - b'a') + 26),
+            b'0' ..= b'9'  => Some(u32::from(byte - b'0') + 52),
+            b'+'           => Some(62),
+            b'/'           => Some(63),
+            _              => None,
+        }
+    }
+
+    if ! input.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let chunks = input.as_bytes().chunks(4).collect::<Vec<_>>();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&b| b == b'=').count();
+        if padding > 2 || (padding > 0 && index + 1 != chunks.len()) {
+            return None;
+        }
+
+        let mut n = 0;
+        for &byte in &chunk[.. 4 - padding] {
+            n = (n << 6) | value(byte)?;
+        }
+        n <<= 6 * padding as u32;
+
+        output.push((n >> 16) as u8);
+        if padding < 2 { output.push((n >> 8) as u8); }
+        if padding < 1 { output.push(n as u8); }
+    }
+
+    Some(output)
+}
+
+/// Returns the mnemonic for a record type, or the generic `TYPE` form for
+/// types without one, such as `TYPE731`.
+pub fn type_mnemonic(type_number: TypeInt) -> String {
+    if let Some(name) = find_qtype_name(type_number) {
+        return name.into();
+    }
+
+    match UnknownQtype::from(type_number) {
+        UnknownQtype::HeardOf(name)   => name.into(),
+        UnknownQtype::UnheardOf(num)  => format!("TYPE{}", num),
+    }
+}
+
+/// Parses a record type from its mnemonic, in any case, or from the
+/// generic `TYPE` form.
+pub fn parse_type_mnemonic(input: &str) -> Option<TypeInt> {
+    let upper = input.to_ascii_uppercase();
+
+    if let Some(number) = upper.strip_prefix("TYPE") {
+        return number.parse().ok();
+    }
+
+    find_qtype_number(&upper).or_else(|| find_other_qtype_number(&upper))
+}
+
+/// Formats a time in seconds since the Unix epoch as `YYYYMMDDHHmmSS` in
+/// UTC, the way signature validity periods are written (RFC 4034 §3.2).
+pub fn timestamp(seconds: u32) -> String {
+    let days = i64::from(seconds / 86400);
+    let time = seconds % 86400;
+
+    // Converts a count of days into a proleptic Gregorian date, counting
+    // eras of 400 years from the 1st of March so leap days come last.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, time / 3600, (time % 3600) / 60, time % 60)
+}
+
+/// Parses a TTL or other duration, either as a plain number of seconds or
+/// with unit suffixes as BIND allows, such as `1h30m` or `1W`.
+pub fn parse_ttl(input: &str) -> Option<u32> {
+    if input.bytes().all(|b| b.is_ascii_digit()) {
+        return input.parse().ok();
+    }
+
+    let mut total = 0_u32;
+    let mut number = None;
+
+    for c in input.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = Some(number.unwrap_or(0_u32).checked_mul(10)?.checked_add(digit)?);
+            continue;
+        }
+
+        let multiplier = match c.to_ascii_lowercase() {
+            's'  => 1,
+            'm'  => 60,
+            'h'  => 60 * 60,
+            'd'  => 60 * 60 * 24,
+            'w'  => 60 * 60 * 24 * 7,
+            _    => return None,
+        };
+
+        total = total.checked_add(number.take()?.checked_mul(multiplier)?)?;
+    }
+
+    match number {
+        Some(_)  => None,
+        None     => Some(total),
+    }
+}
+
+/// Parses a time written as `YYYYMMDDHHmmSS`, or as a plain number of
+/// seconds since the Unix epoch (RFC 4034 §3.2).
+pub fn parse_timestamp(input: &str) -> Option<u32> {
+    if input.len() != 14 {
+        return input.parse().ok();
+    }
+
+    if ! input.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| input[range].parse::<i64>().ok();
+    let (year, month, day) = (field(0 .. 4)?, field(4 .. 6)?, field(6 .. 8)?);
+    let (hour, minute, second) = (field(8 .. 10)?, field(10 .. 12)?, field(12 .. 14)?);
+
+    if ! (1 ..= 12).contains(&month) || ! (1 ..= 31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    // The inverse of the conversion in `timestamp`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(seconds.rem_euclid(1 << 32) as u32)
+}
+
+/// Writes a domain name as it appears in a zone file: fully qualified,
+/// with a trailing dot, and with any special characters escaped.
+pub(crate) fn write_name(f: &mut fmt::Formatter<'_>, name: &Name) -> fmt::Result {
+    if name.is_root() {
+        return write!(f, ".");
+    }
+
+    for label in name.labels() {
+        for byte in label.bytes() {
+            match byte {
+                b'"' | b'(' | b')' | b';' | b'@' | b'$' | b'\\'  => write!(f, "\\{}", byte as char)?,
+                0x21 ..= 0x7E                                  => write!(f, "{}", byte as char)?,
+                _                                              => write!(f, "\\{:03}", byte)?,
+            }
+        }
+
+        write!(f, ".")?;
+    }
+
+    Ok(())
+}
+
+/// Writes a character-string as a quoted string, escaping quotes and
+/// backslashes, and writing anything unprintable as a decimal escape.
+pub(crate) fn write_character_string(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\'   => write!(f, "\\{}", byte as char)?,
+            0x20 ..= 0x7E  => write!(f, "{}", byte as char)?,
+            _              => write!(f, "\\{:03}", byte)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+
+/// Something that can go wrong parsing a record from presentation format.
+#[derive(PartialEq, Debug)]
+pub enum PresentationError {
+
+    /// There were fewer fields than the record type needs. The name of the
+    /// first missing field is included.
+    MissingField(&'static str),
+
+    /// There were more fields than the record type needs.
+    ExtraField(String),
+
+    /// A field could not be parsed as the kind of value it should hold.
+    InvalidField {
+
+        /// The name of the field.
+        field: &'static str,
+
+        /// The text that was in the field.
+        value: String,
+    },
+
+    /// A domain name was too long to be encoded.
+    InvalidName(WireError),
+
+    /// A quoted string was never closed, or a backslash escape was cut off.
+    UnterminatedString,
+
+    /// The record type was not recognised.
+    UnknownType(String),
+}
+
+impl From<WireError> for PresentationError {
+    fn from(error: WireError) -> Self {
+        Self::InvalidName(error)
+    }
+}
+
+impl fmt::Display for PresentationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(field)              => write!(f, "Missing {} field", field),
+            Self::ExtraField(value)                => write!(f, "Unexpected extra field {:?}", value),
+            Self::InvalidField { field, value }    => write!(f, "Invalid {} {:?}", field, value),
+            Self::InvalidName(WireError::LabelTooLong(len)) => write!(f, "Label too long: {} bytes (maximum 63)", len),
+            Self::InvalidName(WireError::NameTooLong(len))  => write!(f, "Domain name too long: {} bytes (maximum 255)", len),
+            Self::InvalidName(other)               => write!(f, "Invalid domain name ({:?})", other),
+            Self::UnterminatedString               => write!(f, "Unterminated string"),
+            Self::UnknownType(name)                => write!(f, "Unknown record type {:?}", name),
+        }
+    }
+}
+
+
+/// The fields of a record’s data in presentation format, which get taken
+/// off the front one at a time as the record is parsed.
+///
+/// Fields are separated by whitespace, unless it is inside quotes or
+/// escaped with a backslash. Parentheses, which let records span several
+/// lines, are ignored, as is anything after a semicolon.
+pub(crate) struct Fields<'a> {
+    fields: std::vec::IntoIter<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+
+    /// Splits the input into fields.
+    pub fn new(input: &'a str) -> Result<Self, PresentationError> {
+        let mut fields = Vec::new();
+        let mut start = None;
+        let mut end = input.len();
+        let mut quoted = false;
+        let mut chars = input.char_indices();
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    start.get_or_insert(index);
+                    chars.next().ok_or(PresentationError::UnterminatedString)?;
+                }
+                '"' => {
+                    start.get_or_insert(index);
+                    quoted = ! quoted;
+                }
+                _ if quoted => {}
+                ';' => {
+                    end = index;
+                    break;
+                }
+                c if c.is_whitespace() || c == '(' || c == ')' => {
+                    if let Some(start) = start.take() {
+                        fields.push(&input[start .. index]);
+                    }
+                }
+                _ => {
+                    start.get_or_insert(index);
+                }
+            }
+        }
+
+        if quoted {
+            return Err(PresentationError::UnterminatedString);
+        }
+
+        if let Some(start) = start {
+            fields.push(&input[start .. end]);
+        }
+
+        Ok(Self { fields: fields.into_iter() })
+    }
+
+    /// Takes the next field as it was written.
+    pub fn text(&mut self, field: &'static str) -> Result<&'a str, PresentationError> {
+        self.fields.next().ok_or(PresentationError::MissingField(field))
+    }
+
+    /// Takes the next field as a number, or some other value parsed with
+    /// its `FromStr` implementation.
+    pub fn parse<T: FromStr>(&mut self, field: &'static str) -> Result<T, PresentationError> {
+        let value = self.text(field)?;
+        value.parse().map_err(|_| invalid(field, value))
+    }
+
+    /// Takes the next field as a duration in seconds, which may use units.
+    pub fn ttl(&mut self, field: &'static str) -> Result<u32, PresentationError> {
+        let value = self.text(field)?;
+        parse_ttl(value).ok_or_else(|| invalid(field, value))
+    }
+
+    /// Takes the next field as a domain name.
+    pub fn name(&mut self, field: &'static str) -> Result<Name, PresentationError> {
+        let value = self.text(field)?;
+        let bytes = unescape(value).ok_or_else(|| invalid(field, value))?;
+
+        // Names are stored with their labels separated by dots, so there
+        // is no way to have a dot within a label.
+        if value.contains("\\.") {
+            return Err(invalid(field, value));
+        }
+
+        Ok(String::from_utf8_lossy(&bytes).parse()?)
+    }
+
+    /// Takes the next field as a character-string, which may be quoted.
+    pub fn character_string(&mut self, field: &'static str) -> Result<Vec<u8>, PresentationError> {
+        let value = self.text(field)?;
+        unescape(value).ok_or_else(|| invalid(field, value))
+    }
+
+    /// Takes every remaining field, joining them together, as Base64 and
+    /// hexadecimal data are allowed to contain spaces.
+    pub fn rest(&mut self, field: &'static str) -> Result<String, PresentationError> {
+        let rest = self.fields.by_ref().collect::<String>();
+        if rest.is_empty() {
+            Err(PresentationError::MissingField(field))
+        }
+        else {
+            Ok(rest)
+        }
+    }
+
+    /// Whether there are any fields left.
+    pub fn is_empty(&self) -> bool {
+        self.fields.len() == 0
+    }
+
+    /// Checks that every field has been used up.
+    pub fn finish(mut self) -> Result<(), PresentationError> {
+        match self.fields.next() {
+            Some(extra)  => Err(PresentationError::ExtraField(extra.into())),
+            None         => Ok(()),
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fields.next()
+    }
+}
+
+/// The error for a field that could not be parsed.
+pub(crate) fn invalid(field: &'static str, value: &str) -> PresentationError {
+    PresentationError::InvalidField { field, value: value.into() }
+}
+
+/// Removes the quotes from a field and turns its escapes back into the
+/// bytes they stand for: `\X` for any character `X`, and `\DDD` for the
+/// byte with the decimal value `DDD`.
+fn unescape(input: &str) -> Option<Vec<u8>> {
+    let input = match input.strip_prefix('"') {
+        Some(rest)  => rest.strip_suffix('"')?,
+        None        => input,
+    };
+
+    let mut output = Vec::with_capacity(input.len());
+    let mut bytes = input.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            output.push(byte);
+            continue;
+        }
+
+        let next = bytes.next()?;
+        if next.is_ascii_digit() {
+            let digits = [ next, bytes.next()?, bytes.next()? ];
+            let number = std::str::from_utf8(&digits).ok()?.parse::<u8>().ok()?;
+            output.push(number);
+        }
+        else {
+            output.push(next);
+        }
+    }
+
+    Some(output)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Shown<'a>(&'a Name);
+
+    impl fmt::Display for Shown<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write_name(f, self.0)
+        }
+    }
+
+    #[test]
+    fn names_are_fully_qualified() {
+        let name: Name = "dns.lookup.dog".parse().unwrap();
+        assert_eq!(Shown(&name).to_string(), "dns.lookup.dog.");
+        assert_eq!(Shown(&Name::root()).to_string(), ".");
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let name: Name = "a;b\\c d.dog.".parse().unwrap();
+        assert_eq!(Shown(&name).to_string(), "a\\;b\\\\c\\032d.dog.");
+    }
+
+    #[test]
+    fn hexes() {
+        assert_eq!(hex(&[ 0x0A, 0xBC ]), "0ABC");
+    }
+
+    #[test]
+    fn base64s() {
+        assert_eq!(base64(b"dog"), "ZG9n");
+        assert_eq!(base64(b"dogs"), "ZG9ncw==");
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0), "19700101000000");
+        assert_eq!(timestamp(1_609_459_199), "20201231235959");
+        assert_eq!(timestamp(951_782_400), "20000229000000");
+    }
+
+    #[test]
+    fn type_mnemonics() {
+        assert_eq!(type_mnemonic(15), "MX");
+        assert_eq!(type_mnemonic(731), "TYPE731");
+    }
+
+    #[test]
+    fn parsing_type_mnemonics() {
+        assert_eq!(parse_type_mnemonic("mx"), Some(15));
+        assert_eq!(parse_type_mnemonic("AFSDB"), Some(18));
+        assert_eq!(parse_type_mnemonic("TYPE731"), Some(731));
+        assert_eq!(parse_type_mnemonic("WOOF"), None);
+    }
+
+    #[test]
+    fn decoding_hex() {
+        assert_eq!(decode_hex("0abc"), Some(vec![ 0x0A, 0xBC ]));
+        assert_eq!(decode_hex("abc"), None);
+    }
+
+    #[test]
+    fn decoding_base64() {
+        assert_eq!(decode_base64("ZG9n"), Some(b"dog".to_vec()));
+        assert_eq!(decode_base64("ZG9ncw=="), Some(b"dogs".to_vec()));
+        assert_eq!(decode_base64("ZG9ncw"), None);
+        assert_eq!(decode_base64("ZG=="), Some(b"d".to_vec()));
+        assert_eq!(decode_base64("Z===ZG9n"), None);
+    }
+
+    #[test]
+    fn parsing_timestamps() {
+        for &seconds in &[ 0, 951_782_400, 1_609_459_199, u32::MAX ] {
+            assert_eq!(parse_timestamp(&timestamp(seconds)), Some(seconds));
+        }
+
+        assert_eq!(parse_timestamp("1234"), Some(1234));
+        assert_eq!(parse_timestamp("20201331000000"), None);
+    }
+
+    #[test]
+    fn ttls() {
+        assert_eq!(parse_ttl("3600"), Some(3600));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("1W2d"), Some(777_600));
+        assert_eq!(parse_ttl("1h30"), None);
+        assert_eq!(parse_ttl("h"), None);
+        assert_eq!(parse_ttl("99999999999"), None);
+    }
+
+    #[test]
+    fn splitting_fields() {
+        let mut fields = Fields::new(r#"10 "two words" a\ b ( multi ) ; comment"#).unwrap();
+        assert_eq!(fields.text("one"), Ok("10"));
+        assert_eq!(fields.character_string("two"), Ok(b"two words".to_vec()));
+        assert_eq!(fields.character_string("three"), Ok(b"a b".to_vec()));
+        assert_eq!(fields.text("four"), Ok("multi"));
+        assert!(fields.finish().is_ok());
+    }
+
+    #[test]
+    fn quotes_protect_semicolons() {
+        let mut fields = Fields::new(r#""a;b""#).unwrap();
+        assert_eq!(fields.character_string("text"), Ok(b"a;b".to_vec()));
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(Fields::new(r#""open"#).err(), Some(PresentationError::UnterminatedString));
+    }
+
+    #[test]
+    fn escaped_bytes() {
+        assert_eq!(unescape(r#"\065\\\""#), Some(b"A\\\"".to_vec()));
+        assert_eq!(unescape(r"\06"), None);
+    }
+
+    #[test]
+    fn escaped_dots_in_names() {
+        let mut fields = Fields::new(r"a\.b.dog.").unwrap();
+        assert!(fields.name("name").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Punycode, the encoding that internationalised domain name labels use to
+//! fit Unicode into the letters, digits, and hyphens that DNS allows.
+//!
+//! # References
+//!
+//! - [RFC 3492](https://tools.ietf.org/html/rfc3492) — Punycode: A Bootstring encoding of Unicode for Internationalized Domain Names in Applications (March 2003)
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 0x80;
+
+
+/// Encodes a label, without the `xn--` prefix. Returns `None` if the
+/// label is so long that the encoder would overflow (RFC 3492 §6.3).
+pub fn encode(input: &str) -> Option<String> {
+    let code_points = input.chars().map(u32::from).collect::<Vec<_>>();
+
+    let mut output = input.chars().filter(char::is_ascii).collect::<String>();
+    let basic_count = output.len() as u32;
+    if basic_count > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta = 0_u32;
+    let mut bias = INITIAL_BIAS;
+    let mut handled = basic_count;
+
+    while (handled as usize) < code_points.len() {
+        let m = code_points.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+
+        for &c in &code_points {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic_count);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+
+    Some(output)
+}
+
+/// Decodes a label, without the `xn--` prefix. Returns `None` if the
+/// input is not valid Punycode.
+pub fn decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(index)  => (&input[.. index], &input[index + 1 ..]),
+        None         => ("", input),
+    };
+
+    if ! basic.is_ascii() {
+        return None;
+    }
+
+    let mut output = basic.chars().collect::<Vec<_>>();
+    let mut n = INITIAL_N;
+    let mut i = 0_u32;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.bytes().peekable();
+
+    while digits.peek().is_some() {
+        let old_i = i;
+        let mut weight = 1_u32;
+        let mut k = BASE;
+
+        loop {
+            let d = digit_value(digits.next()?)?;
+            i = i.checked_add(d.checked_mul(weight)?)?;
+
+            let t = threshold(k, bias);
+            if d < t {
+                break;
+            }
+
+            weight = weight.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+
+        let length = output.len() as u32 + 1;
+        bias = adapt(i - old_i, length, old_i == 0);
+        n = n.checked_add(i / length)?;
+        i %= length;
+
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+
+    Some(output.into_iter().collect())
+}
+
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        T_MIN
+    }
+    else if k >= bias + T_MAX {
+        T_MAX
+    }
+    else {
+        k - bias
+    }
+}
+
+/// Adapts the bias after each code point, so that the lengths of the
+/// variable-length integers stay short (RFC 3492 §6.1).
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+    delta += delta / num_points;
+
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+fn digit(value: u32) -> char {
+    match value {
+        0  ..= 25  => (b'a' + value as u8) as char,
+        26 ..= 35  => (b'0' + (value - 26) as u8) as char,
+        _          => unreachable!("Punycode digit out of range"),
+    }
+}
+
+fn digit_value(byte: u8) -> Option<u32> {
+    match byte {
+        b'a' ..= b'z'  => Some(u32::from(byte - b'a')),
+        b'A' ..= b'Z'  => Some(u32::from(byte - b'A')),
+        b'0' ..= b'9'  => Some(u32::from(byte - b'0') + 26),
+        _              => None,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(encode("bücher"), Some("bcher-kva".into()));
+        assert_eq!(encode("münchen"), Some("mnchen-3ya".into()));
+    }
+
+    #[test]
+    fn encoding_without_basic_characters() {
+        // RFC 3492 §7.1, sample (A)
+        assert_eq!(encode("ليهمابتكلموشعربي؟"), Some("egbpdaj6bu4bxfgehfvwxn".into()));
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(decode("bcher-kva"), Some("bücher".into()));
+        assert_eq!(decode("egbpdaj6bu4bxfgehfvwxn"), Some("ليهمابتكلموشعربي؟".into()));
+    }
+
+    #[test]
+    fn round_trip() {
+        for label in &[ "δοκιμή", "テスト", "café-olé", "ascii" ] {
+            assert_eq!(decode(&encode(label).unwrap()).as_deref(), Some(*label));
+        }
+    }
+
+    #[test]
+    fn decoding_garbage() {
+        assert_eq!(decode("abc-!!"), None);
+        assert_eq!(decode("99999999999"), None);
+    }
+
+    #[test]
+    fn encoding_overflow() {
+        let input = format!("{}\u{10FFFF}", "a".repeat(5000));
+        assert_eq!(encode(&input), None);
+    }
+}
@@ -1,5 +1,8 @@
+use std::fmt;
+use std::str::FromStr;
 use std::net::Ipv4Addr;
 
+use crate::presentation::{Fields, PresentationError};
 use crate::wire::*;
 
 
@@ -9,6 +12,7 @@ use crate::wire::*;
 ///
 /// - [RFC 1035 §3.4.1](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 #[derive(PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct A {
 
     /// The IPv4 address contained in the packet.
@@ -20,10 +24,7 @@ impl Wire for A {
     const RR_TYPE: u16 = 1;
 
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let mut buf = Vec::new();
-        for _ in 0 .. len {
-            buf.push(c.read_u8()?);
-        }
+        let buf = c.read_slice(usize::from(len))?;
 
         if let [a, b, c, d] = *buf {
             let address = Ipv4Addr::new(a, b, c, d);
@@ -33,6 +34,28 @@ impl Wire for A {
             Err(WireError::WrongLength { expected: 4, got: buf.len() as u16 })
         }
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.extend(&self.address.octets());
+        Ok(())
+    }
+}
+
+impl fmt::Display for A {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.address)
+    }
+}
+
+impl FromStr for A {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let address = fields.parse("address")?;
+        fields.finish()?;
+        Ok(Self { address })
+    }
 }
 
 
@@ -69,4 +92,31 @@ mod test {
         assert_eq!(A::read(0, &mut Cursor::new(&[])),
                    Err(WireError::WrongLength { expected: 4, got: 0 }));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 127, 0, 0, 1 ];
+        let record = A::read(4, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = A { address: Ipv4Addr::new(127, 0, 0, 1) };
+        assert_eq!(record.to_string(), "127.0.0.1");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("127.0.0.1".parse::<A>(),
+                   Ok(A { address: Ipv4Addr::new(127, 0, 0, 1) }));
+    }
+
+    #[test]
+    fn presentation_invalid_address() {
+        assert!(matches!("127.0.0".parse::<A>(), Err(PresentationError::InvalidField { field: "address", .. })));
+    }
 }
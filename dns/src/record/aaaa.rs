@@ -1,5 +1,8 @@
+use std::fmt;
+use std::str::FromStr;
 use std::net::Ipv6Addr;
 
+use crate::presentation::{Fields, PresentationError};
 use crate::wire::*;
 
 
@@ -9,6 +12,7 @@ use crate::wire::*;
 ///
 /// - [RFC 3596](https://tools.ietf.org/html/rfc3596) — DNS Extensions to Support IP Version 6 (October 2003)
 #[derive(PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AAAA {
 
     /// The IPv6 address contained in the packet.
@@ -20,10 +24,7 @@ impl Wire for AAAA {
     const RR_TYPE: u16 = 28;
 
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let mut buf = Vec::new();
-        for _ in 0 .. len {
-            buf.push(c.read_u8()?);
-        }
+        let buf = c.read_slice(usize::from(len))?;
 
         if let [a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p] = *buf {
             let address = Ipv6Addr::from([a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p]);
@@ -34,6 +35,28 @@ impl Wire for AAAA {
             Err(WireError::WrongLength { expected: 16, got: buf.len() as u16 })
         }
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.extend(&self.address.octets());
+        Ok(())
+    }
+}
+
+impl fmt::Display for AAAA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.address)
+    }
+}
+
+impl FromStr for AAAA {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let address = fields.parse("address")?;
+        fields.finish()?;
+        Ok(Self { address })
+    }
 }
 
 
@@ -78,4 +101,26 @@ mod test {
         assert_eq!(AAAA::read(0, &mut Cursor::new(&[])),
                    Err(WireError::WrongLength { expected: 16, got: 0 }));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0 ];
+        let record = AAAA::read(16, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = AAAA { address: "2001:db8::1".parse().unwrap() };
+        assert_eq!(record.to_string(), "2001:db8::1");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("2001:db8::1".parse::<AAAA>(),
+                   Ok(AAAA { address: "2001:db8::1".parse().unwrap() }));
+    }
 }
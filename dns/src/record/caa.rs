@@ -1,3 +1,8 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::presentation::{Fields, PresentationError, write_character_string};
 use crate::wire::*;
 
 
@@ -8,6 +13,7 @@ use crate::wire::*;
 ///
 /// - [RFC 6844](https://tools.ietf.org/html/rfc6844) — DNS Certification Authority Authorization Resource Record (January 2013s
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CAA {
 
     /// Whether this record is marked as “critical” or not.
@@ -28,24 +34,53 @@ impl Wire for CAA {
         let flags = c.read_u8()?;
         let tag_length = c.read_u8()?;
 
-        let mut tag = Vec::new();
-        for _ in 0 .. tag_length {
-            tag.push(c.read_u8()?);
-        }
-
-        let mut value = Vec::new();
-        for _ in 0 .. len.saturating_sub(u16::from(tag_length)).saturating_sub(2) {
-            value.push(c.read_u8()?);
-        }
+        let tag = c.read_slice(usize::from(tag_length))?;
+        let value = c.read_slice(usize::from(len.saturating_sub(u16::from(tag_length)).saturating_sub(2)))?;
 
         Ok(CAA {
             critical: flags & 0b_1000_0000 == 0b_1000_0000,
-            tag: String::from_utf8_lossy(&tag).to_string(),
+            tag: String::from_utf8_lossy(tag).to_string(),
+            value: String::from_utf8_lossy(value).to_string(),
+        })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        let tag_length = u8::try_from(self.tag.len()).map_err(|_| WireError::StringTooLong(self.tag.len()))?;
+
+        bytes.write_u8(if self.critical { 0b_1000_0000 } else { 0 })?;
+        bytes.write_u8(tag_length)?;
+        bytes.extend(self.tag.as_bytes());
+        bytes.extend(self.value.as_bytes());
+        Ok(())
+    }
+}
+
+impl fmt::Display for CAA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ", if self.critical { 128 } else { 0 }, self.tag)?;
+        write_character_string(f, self.value.as_bytes())
+    }
+}
+
+impl FromStr for CAA {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let flags: u8 = fields.parse("flags")?;
+        let tag = fields.text("tag")?.into();
+        let value = fields.character_string("value")?;
+        fields.finish()?;
+
+        Ok(Self {
+            critical: flags & 0b_1000_0000 == 0b_1000_0000,
+            tag,
             value: String::from_utf8_lossy(&value).to_string(),
         })
     }
 }
 
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -69,4 +104,35 @@ mod test {
         assert_eq!(CAA::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x00, 0x09, 0x69, 0x73, 0x73, 0x75, 0x65, 0x77, 0x69,
+                     0x6c, 0x64, 0x65, 0x6e, 0x74, 0x72, 0x75, 0x73, 0x74,
+                     0x2e, 0x6e, 0x65, 0x74 ];
+        let record = CAA::read(22, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn tag_too_long() {
+        let record = CAA { critical: false, tag: "a".repeat(256), value: String::new() };
+        assert_eq!(record.write(&mut Vec::new()),
+                   Err(WireError::StringTooLong(256)));
+    }
+
+    #[test]
+    fn displays() {
+        let record = CAA { critical: true, tag: "issue".into(), value: "ca.example; \"x\"".into() };
+        assert_eq!(record.to_string(), "128 issue \"ca.example; \\\"x\\\"\"");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!(r#"0 issue "letsencrypt.org""#.parse::<CAA>(),
+                   Ok(CAA { critical: false, tag: "issue".into(), value: "letsencrypt.org".into() }));
+    }
 }
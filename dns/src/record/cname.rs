@@ -1,4 +1,9 @@
-use crate::strings::ReadLabels;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, write_name};
 use crate::wire::*;
 
 
@@ -8,20 +13,46 @@ use crate::wire::*;
 ///
 /// - [RFC 1035 §3.3.1](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CNAME {
 
     /// The domain name that this CNAME record is responding with.
-    pub domain: String,
+    pub domain: Name,
 }
 
 impl Wire for CNAME {
     const NAME: &'static str = "CNAME";
     const RR_TYPE: u16 = 5;
 
-    fn read(_len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let domain = c.read_labels()?;
+    fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        Self::read_with(len, c, &ParseOptions::default())
+    }
+
+    fn read_with(_len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        let domain = c.read_labels_with(options)?;
         Ok(CNAME { domain })
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_labels(&self.domain)
+    }
+}
+
+impl fmt::Display for CNAME {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_name(f, &self.domain)
+    }
+}
+
+impl FromStr for CNAME {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let domain = fields.name("domain")?;
+        fields.finish()?;
+        Ok(Self { domain })
+    }
 }
 
 
@@ -35,7 +66,7 @@ mod test {
 
         assert_eq!(CNAME::read(10, &mut Cursor::new(buf)).unwrap(),
                    CNAME {
-                       domain: String::from("bsago.me."),
+                       domain: "bsago.me.".parse().unwrap(),
                    });
     }
 
@@ -44,5 +75,26 @@ mod test {
         assert_eq!(CNAME::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
-}
 
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x05, 0x62, 0x73, 0x61, 0x67, 0x6f, 0x02, 0x6d, 0x65, 0x00, ];
+        let record = CNAME::read(10, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = CNAME { domain: "bsago.me".parse().unwrap() };
+        assert_eq!(record.to_string(), "bsago.me.");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("bsago.me.".parse::<CNAME>(),
+                   Ok(CNAME { domain: "bsago.me.".parse().unwrap() }));
+    }
+}
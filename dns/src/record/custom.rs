@@ -0,0 +1,314 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use log::*;
+
+use crate::types::TypeInt;
+use crate::wire::*;
+use super::is_parsed_type;
+
+
+/// A **registry** of record types that dog does not know about itself, so
+/// that applications using private-use type numbers (65280–65534) can have
+/// records of those types parsed into their own structures.
+///
+/// The registry is only consulted for types that none of the built-in
+/// records have, and records of types it has not been told about still
+/// get read as `Record::Other`. To use it, put it in the `registry` field
+/// of `ParseOptions`, wrapped in an `Arc` so that the options can be
+/// cloned and shared between threads without copying it.
+///
+/// # Examples
+///
+/// ```
+/// use dns::{Wire, WireError, ParseOptions};
+/// use dns::record::{Record, RecordRegistry};
+/// use std::io::Cursor;
+/// use std::fmt;
+/// use std::sync::Arc;
+///
+/// #[derive(Debug)]
+/// struct Shoe { size: u8 }
+///
+/// impl Wire for Shoe {
+///     const NAME: &'static str = "SHOE";
+///     const RR_TYPE: u16 = 65300;
+///
+///     fn read(_len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+///         let size = *c.get_ref().get(c.position() as usize).ok_or(WireError::IO)?;
+///         c.set_position(c.position() + 1);
+///         Ok(Shoe { size })
+///     }
+///
+///     fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+///         bytes.push(self.size);
+///         Ok(())
+///     }
+/// }
+///
+/// impl fmt::Display for Shoe {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "{}", self.size)
+///     }
+/// }
+///
+/// let mut registry = RecordRegistry::new();
+/// registry.register::<Shoe>();
+/// let options = ParseOptions { registry: Some(Arc::new(registry)), .. ParseOptions::default() };
+/// ```
+#[derive(Default)]
+pub struct RecordRegistry {
+    types: BTreeMap<TypeInt, Registered>,
+}
+
+/// How to read a record of one of the types in a registry.
+#[derive(Copy, Clone)]
+struct Registered {
+    name: &'static str,
+    read: ReadFn,
+}
+
+/// A function that reads a registered type’s data into a record.
+type ReadFn = fn(u16, &mut Cursor<&[u8]>, &ParseOptions) -> Result<CustomRecord, WireError>;
+
+impl RecordRegistry {
+
+    /// Creates a new registry with no types in it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a record type to the registry, so that records with its type
+    /// number get read with its `Wire` implementation. Registering a type
+    /// number that has already been registered replaces it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type number is one of the types that dog parses
+    /// itself, as those always take precedence.
+    pub fn register<T: CustomData + Wire>(&mut self) -> &mut Self {
+        assert!(! is_parsed_type(T::RR_TYPE),
+                "Record type {} ({}) is already parsed by dog", T::NAME, T::RR_TYPE);
+
+        let read = |len, c: &mut Cursor<&[u8]>, options: &ParseOptions| CustomRecord::new(T::read_with(len, c, options)?);
+        self.types.insert(T::RR_TYPE, Registered { name: T::NAME, read });
+        self
+    }
+
+    /// Whether a type with the given number has been registered.
+    pub fn contains(&self, type_number: TypeInt) -> bool {
+        self.types.contains_key(&type_number)
+    }
+
+    /// Reads a record of the given type, if it has been registered.
+    pub(crate) fn read(&self, qtype: TypeInt, len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Option<Result<CustomRecord, WireError>> {
+        let registered = self.types.get(&qtype)?;
+        info!("Deciphering registered {} record (type {}, len {})", registered.name, qtype, len);
+        Some((registered.read)(len, c, options))
+    }
+}
+
+impl fmt::Debug for RecordRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+         .entries(self.types.iter().map(|(number, registered)| (number, registered.name)))
+         .finish()
+    }
+}
+
+/// Registries are equal if they read the same types, by the same names.
+impl PartialEq for RecordRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.types.len() == other.types.len()
+            && self.types.iter().zip(&other.types).all(|(a, b)| a.0 == b.0 && a.1.name == b.1.name)
+    }
+}
+
+
+/// The data in a record of a type from a `RecordRegistry`. Anything that
+/// implements `Wire` can be one, as long as it can be debugged, displayed
+/// in presentation format, and shared between threads.
+pub trait CustomData: Any + fmt::Debug + fmt::Display + Send + Sync {
+
+    /// Returns this data as `Any`, so it can be downcast back into its
+    /// own type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any + fmt::Debug + fmt::Display + Send + Sync> CustomData for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+
+/// A record of a type that was read using a `RecordRegistry`.
+///
+/// It keeps the data both as the structure it was read into and as the
+/// bytes that structure writes, which are what records get compared by.
+#[derive(Debug, Clone)]
+pub struct CustomRecord {
+    type_number: TypeInt,
+    name: &'static str,
+    bytes: Vec<u8>,
+    data: Arc<dyn CustomData>,
+}
+
+impl CustomRecord {
+
+    /// Creates a record holding the given data, writing it out to check
+    /// that it can be.
+    pub fn new<T: CustomData + Wire>(data: T) -> Result<Self, WireError> {
+        let mut bytes = Vec::new();
+        data.write(&mut bytes)?;
+        Ok(Self { type_number: T::RR_TYPE, name: T::NAME, bytes, data: Arc::new(data) })
+    }
+
+    /// The number of this record’s type.
+    pub fn type_number(&self) -> TypeInt {
+        self.type_number
+    }
+
+    /// The name of this record’s type, such as `"SHOE"`.
+    pub fn type_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// This record’s data, as it gets written in a message.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the data this record was read into, if it is of the given
+    /// type.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        // Call it on the data itself, as the Arc around it implements the
+        // trait as well.
+        let data: &dyn CustomData = &*self.data;
+        data.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for CustomRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_number == other.type_number && self.bytes == other.bytes
+    }
+}
+
+impl fmt::Display for CustomRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.data.fmt(f)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::record::{Record, UnknownQtype};
+
+    /// A private-use record holding two numbers.
+    #[derive(PartialEq, Debug)]
+    struct Pair { left: u8, right: u8 }
+
+    impl Wire for Pair {
+        const NAME: &'static str = "PAIR";
+        const RR_TYPE: u16 = 65280;
+
+        fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+            if len != 2 {
+                return Err(WireError::WrongLength { expected: 2, got: len });
+            }
+
+            let bytes = c.read_slice(2)?;
+            Ok(Self { left: bytes[0], right: bytes[1] })
+        }
+
+        fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+            bytes.extend(&[ self.left, self.right ]);
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Pair {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} {}", self.left, self.right)
+        }
+    }
+
+    fn options() -> ParseOptions {
+        let mut registry = RecordRegistry::new();
+        registry.register::<Pair>();
+        ParseOptions { registry: Some(Arc::new(registry)), .. ParseOptions::default() }
+    }
+
+    fn read(qtype: TypeInt, bytes: &[u8], options: ParseOptions) -> Result<Record, WireError> {
+        Record::from_bytes(qtype, bytes.len() as u16, &mut Cursor::new(bytes), &options)
+    }
+
+    #[test]
+    fn parses_registered() {
+        let record = read(65280, &[ 0x04, 0x02 ], options()).unwrap();
+
+        let custom = match &record {
+            Record::Custom(custom)  => custom,
+            otherwise               => panic!("Expected custom record, got {:?}", otherwise),
+        };
+
+        assert_eq!(custom.downcast_ref::<Pair>(), Some(&Pair { left: 4, right: 2 }));
+        assert_eq!(record.type_number(), 65280);
+        assert_eq!(record.type_mnemonic(), "PAIR");
+        assert_eq!(record.to_string(), "4 2");
+    }
+
+    #[test]
+    fn writes_registered() {
+        let record = read(65280, &[ 0x04, 0x02 ], options()).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, &[ 0x04, 0x02 ]);
+    }
+
+    #[test]
+    fn registered_errors() {
+        assert_eq!(read(65280, &[ 0x04 ], options()),
+                   Err(WireError::WrongLength { expected: 2, got: 1 }));
+    }
+
+    #[test]
+    fn unregistered_without_registry() {
+        assert_eq!(read(65280, &[ 0x04, 0x02 ], ParseOptions::default()),
+                   Ok(Record::Other { type_number: UnknownQtype::UnheardOf(65280), bytes: vec![ 0x04, 0x02 ] }));
+    }
+
+    #[test]
+    fn unregistered_type() {
+        assert_eq!(read(65281, &[ 0x04, 0x02 ], options()),
+                   Ok(Record::Other { type_number: UnknownQtype::UnheardOf(65281), bytes: vec![ 0x04, 0x02 ] }));
+    }
+
+    #[test]
+    #[should_panic(expected = "already parsed by dog")]
+    fn cannot_replace_built_in() {
+        #[derive(Debug)]
+        struct NotA;
+
+        impl Wire for NotA {
+            const NAME: &'static str = "NOTA";
+            const RR_TYPE: u16 = 1;
+
+            fn read(_len: u16, _c: &mut Cursor<&[u8]>) -> Result<Self, WireError> { Ok(Self) }
+            fn write(&self, _bytes: &mut Vec<u8>) -> Result<(), WireError> { Ok(()) }
+        }
+
+        impl fmt::Display for NotA {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "nota") }
+        }
+
+        RecordRegistry::new().register::<NotA>();
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::presentation::{Fields, PresentationError, base64, decode_base64, invalid};
+use crate::wire::*;
+
+
+/// A **DNSKEY** record, which holds a public key that a zone uses to sign
+/// its records for DNSSEC.
+///
+/// # References
+///
+/// - [RFC 4034 §2](https://tools.ietf.org/html/rfc4034) — Resource Records for the DNS Security Extensions (March 2005)
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DNSKEY {
+
+    /// Flags describing the key. Bit 7 marks it as a zone key, and bit 15
+    /// marks it as a secure entry point.
+    pub flags: u16,
+
+    /// The protocol number, which must be 3.
+    pub protocol: u8,
+
+    /// The number of the cryptographic algorithm the key is used with.
+    pub algorithm: u8,
+
+    /// The public key material, in an algorithm-specific format.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialise::base64_bytes"))]
+    pub public_key: Vec<u8>,
+}
+
+impl Wire for DNSKEY {
+    const NAME: &'static str = "DNSKEY";
+    const RR_TYPE: u16 = 48;
+
+    fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        if len < 4 {
+            return Err(WireError::WrongLength { expected: 4, got: len });
+        }
+
+        let flags     = c.read_u16::<BigEndian>()?;
+        let protocol  = c.read_u8()?;
+        let algorithm = c.read_u8()?;
+
+        let public_key = c.read_slice(usize::from(len - 4))?.to_vec();
+
+        Ok(DNSKEY { flags, protocol, algorithm, public_key })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.extend(self.rdata());
+        Ok(())
+    }
+}
+
+impl DNSKEY {
+
+    /// Whether this key has the Zone Key flag set, which means it can be
+    /// used to verify signatures over the zone’s records.
+    pub fn is_zone_key(&self) -> bool {
+        self.flags & 0b_0000_0001_0000_0000 != 0
+    }
+
+    /// Whether this key has the Revoke flag set, which means it must no
+    /// longer be used as a trust anchor.
+    ///
+    /// See [RFC 5011 §7](https://tools.ietf.org/html/rfc5011#section-7).
+    pub fn is_revoked(&self) -> bool {
+        self.flags & 0b_0000_0000_1000_0000 != 0
+    }
+
+    /// Whether this key has the Secure Entry Point flag set, which
+    /// conventionally marks it as a key-signing key.
+    pub fn is_secure_entry_point(&self) -> bool {
+        self.flags & 0b_0000_0000_0000_0001 != 0
+    }
+
+    /// Serialises this key’s data into a vector of bytes, as it appears in
+    /// the record data on the wire.
+    pub fn rdata(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.public_key.len());
+        bytes.extend(&self.flags.to_be_bytes());
+        bytes.push(self.protocol);
+        bytes.push(self.algorithm);
+        bytes.extend(&self.public_key);
+        bytes
+    }
+
+    /// Calculates the key tag, a short number used to quickly find which
+    /// key a signature or DS record refers to.
+    ///
+    /// See [RFC 4034 Appendix B](https://tools.ietf.org/html/rfc4034#appendix-B).
+    pub fn key_tag(&self) -> u16 {
+        let rdata = self.rdata();
+        let mut accumulator: u32 = 0;
+
+        for (index, byte) in rdata.iter().enumerate() {
+            if index % 2 == 0 {
+                accumulator += u32::from(*byte) << 8;
+            }
+            else {
+                accumulator += u32::from(*byte);
+            }
+        }
+
+        accumulator += (accumulator >> 16) & 0xFFFF;
+        (accumulator & 0xFFFF) as u16
+    }
+}
+
+impl fmt::Display for DNSKEY {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.flags, self.protocol, self.algorithm, base64(&self.public_key))
+    }
+}
+
+impl FromStr for DNSKEY {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let flags = fields.parse("flags")?;
+        let protocol = fields.parse("protocol")?;
+        let algorithm = fields.parse("algorithm")?;
+
+        let key = fields.rest("public key")?;
+        let public_key = decode_base64(&key).ok_or_else(|| invalid("public key", &key))?;
+
+        Ok(Self { flags, protocol, algorithm, public_key })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let buf = &[ 0x01, 0x01, 0x03, 0x0d, 0xAB, 0xCD, 0xEF ];
+
+        assert_eq!(DNSKEY::read(7, &mut Cursor::new(buf)).unwrap(),
+                   DNSKEY {
+                       flags: 257,
+                       protocol: 3,
+                       algorithm: 13,
+                       public_key: vec![ 0xAB, 0xCD, 0xEF ],
+                   });
+    }
+
+    #[test]
+    fn key_tag() {
+        let key = DNSKEY::read(7, &mut Cursor::new(&[ 0x01, 0x01, 0x03, 0x0d, 0xAB, 0xCD, 0xEF ])).unwrap();
+        assert!(key.is_zone_key());
+        assert!(key.is_secure_entry_point());
+        assert!(! key.is_revoked());
+        assert_eq!(key.key_tag(), 0x9EDC);
+    }
+
+    #[test]
+    fn too_short() {
+        assert_eq!(DNSKEY::read(3, &mut Cursor::new(&[ 0x01, 0x01, 0x03 ])),
+                   Err(WireError::WrongLength { expected: 4, got: 3 }));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(DNSKEY::read(4, &mut Cursor::new(&[])),
+                   Err(WireError::IO));
+    }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x01, 0x01, 0x03, 0x0d, 0xAB, 0xCD, 0xEF ];
+        let record = DNSKEY::read(7, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = DNSKEY { flags: 257, protocol: 3, algorithm: 8, public_key: b"dog".to_vec() };
+        assert_eq!(record.to_string(), "257 3 8 ZG9n");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("257 3 8 ZG9n ZG9n".parse::<DNSKEY>(),
+                   Ok(DNSKEY { flags: 257, protocol: 3, algorithm: 8, public_key: b"dogdog".to_vec() }));
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::presentation::{Fields, PresentationError, decode_hex, hex, invalid};
+use crate::wire::*;
+
+
+/// A **DS** _(delegation signer)_ record, which is stored in a parent zone
+/// and contains the digest of a key from a child zone, linking the two
+/// zones together in the DNSSEC chain of trust.
+///
+/// # References
+///
+/// - [RFC 4034 §5](https://tools.ietf.org/html/rfc4034) — Resource Records for the DNS Security Extensions (March 2005)
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DS {
+
+    /// The key tag of the DNSKEY record this refers to.
+    pub key_tag: u16,
+
+    /// The algorithm number of the DNSKEY record this refers to.
+    pub algorithm: u8,
+
+    /// The number of the algorithm used to construct the digest.
+    pub digest_type: u8,
+
+    /// The digest of the DNSKEY record this refers to.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialise::hex_bytes"))]
+    pub digest: Vec<u8>,
+}
+
+impl Wire for DS {
+    const NAME: &'static str = "DS";
+    const RR_TYPE: u16 = 43;
+
+    fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        if len < 4 {
+            return Err(WireError::WrongLength { expected: 4, got: len });
+        }
+
+        let key_tag     = c.read_u16::<BigEndian>()?;
+        let algorithm   = c.read_u8()?;
+        let digest_type = c.read_u8()?;
+
+        let digest = c.read_slice(usize::from(len - 4))?.to_vec();
+
+        Ok(DS { key_tag, algorithm, digest_type, digest })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_u16::<BigEndian>(self.key_tag)?;
+        bytes.write_u8(self.algorithm)?;
+        bytes.write_u8(self.digest_type)?;
+        bytes.extend(&self.digest);
+        Ok(())
+    }
+}
+
+impl fmt::Display for DS {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.key_tag, self.algorithm, self.digest_type, hex(&self.digest))
+    }
+}
+
+impl FromStr for DS {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let key_tag = fields.parse("key tag")?;
+        let algorithm = fields.parse("algorithm")?;
+        let digest_type = fields.parse("digest type")?;
+
+        let digest_text = fields.rest("digest")?;
+        let digest = decode_hex(&digest_text).ok_or_else(|| invalid("digest", &digest_text))?;
+
+        Ok(Self { key_tag, algorithm, digest_type, digest })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let buf = &[ 0x4f, 0x66, 0x08, 0x02, 0xE0, 0x6D, 0x44, 0xB8 ];
+
+        assert_eq!(DS::read(8, &mut Cursor::new(buf)).unwrap(),
+                   DS {
+                       key_tag: 20326,
+                       algorithm: 8,
+                       digest_type: 2,
+                       digest: vec![ 0xE0, 0x6D, 0x44, 0xB8 ],
+                   });
+    }
+
+    #[test]
+    fn too_short() {
+        assert_eq!(DS::read(2, &mut Cursor::new(&[ 0x4f, 0x66 ])),
+                   Err(WireError::WrongLength { expected: 4, got: 2 }));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(DS::read(4, &mut Cursor::new(&[])),
+                   Err(WireError::IO));
+    }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x4f, 0x66, 0x08, 0x02, 0xE0, 0x6D, 0x44, 0xB8 ];
+        let record = DS::read(8, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = DS { key_tag: 20326, algorithm: 8, digest_type: 2, digest: vec![ 0xE0, 0x6D ] };
+        assert_eq!(record.to_string(), "20326 8 2 E06D");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("20326 8 2 E06D 44b8".parse::<DS>(),
+                   Ok(DS { key_tag: 20326, algorithm: 8, digest_type: 2, digest: vec![ 0xE0, 0x6D, 0x44, 0xB8 ] }));
+    }
+}
@@ -1,5 +1,12 @@
 //! All the DNS record types, as well as how to parse each type.
 
+use std::fmt;
+use std::io::Cursor;
+
+use crate::presentation::{Fields, PresentationError, decode_hex, hex, invalid, parse_type_mnemonic, type_mnemonic};
+use crate::types::TypeInt;
+use crate::wire::{Wire, ParseOptions};
+
 
 mod a;
 pub use self::a::A;
@@ -13,6 +20,12 @@ pub use self::caa::CAA;
 mod cname;
 pub use self::cname::CNAME;
 
+mod dnskey;
+pub use self::dnskey::DNSKEY;
+
+mod ds;
+pub use self::ds::DS;
+
 mod mx;
 pub use self::mx::MX;
 
@@ -25,8 +38,11 @@ pub use self::opt::OPT;
 mod ptr;
 pub use self::ptr::PTR;
 
+mod rrsig;
+pub use self::rrsig::RRSIG;
+
 mod soa;
-pub use self::soa::SOA;
+pub use self::soa::{SOA, compare_serials, add_to_serial};
 
 mod srv;
 pub use self::srv::SRV;
@@ -36,11 +52,50 @@ pub use self::txt::TXT;
 
 
 mod others;
-pub use self::others::{UnknownQtype, find_other_qtype_number};
+pub use self::others::{UnknownQtype, find_other_qtype_number, other_qtype_names};
+
+mod custom;
+pub use self::custom::{RecordRegistry, CustomRecord, CustomData};
+
+
+/// The names and numbers of the record types that get parsed into their
+/// own variant, apart from OPT, which every lookup of a type by name or by
+/// number goes through.
+pub(crate) const NAMED_TYPES: [(&str, TypeInt); 13] = {
+    macro_rules! entry {
+        ($record:tt) => { ($record::NAME, $record::RR_TYPE) }
+    }
+
+    [
+        entry!(A),
+        entry!(AAAA),
+        entry!(CAA),
+        entry!(CNAME),
+        entry!(DNSKEY),
+        entry!(DS),
+        entry!(MX),
+        entry!(NS),
+        // OPT is elsewhere
+        entry!(PTR),
+        entry!(RRSIG),
+        entry!(SOA),
+        entry!(SRV),
+        entry!(TXT),
+    ]
+};
+
+/// Whether records with the given type number get parsed into their own
+/// variant: one of the named types, or OPT, which gets parsed into a
+/// pseudo-record.
+pub(crate) fn is_parsed_type(type_number: TypeInt) -> bool {
+    type_number == OPT::RR_TYPE
+        || NAMED_TYPES.iter().any(|(_, number)| *number == type_number)
+}
 
 
 /// A record that’s been parsed from a byte buffer.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Record {
 
     /// An **A** record.
@@ -55,6 +110,12 @@ pub enum Record {
     /// A **CNAME** record.
     CNAME(CNAME),
 
+    /// A **DNSKEY** record.
+    DNSKEY(DNSKEY),
+
+    /// A **DS** record.
+    DS(DS),
+
     /// A **MX** record.
     MX(MX),
 
@@ -66,6 +127,9 @@ pub enum Record {
     /// A **PTR** record.
     PTR(PTR),
 
+    /// A **RRSIG** record.
+    RRSIG(RRSIG),
+
     /// A **SOA** record.
     SOA(SOA),
 
@@ -75,6 +139,11 @@ pub enum Record {
     /// A **TXT** record.
     TXT(TXT),
 
+    /// A record of a type from the `RecordRegistry` in the options it was
+    /// parsed with.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialise::custom_record", skip_deserializing))]
+    Custom(CustomRecord),
+
     /// A record with a type that we don’t recognise.
     Other {
 
@@ -82,6 +151,134 @@ pub enum Record {
         type_number: UnknownQtype,
 
         /// The undecodable bytes that were in this record.
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialise::base64_bytes"))]
         bytes: Vec<u8>,
     },
 }
+
+impl Record {
+
+    /// The type number of this record, such as `1` for an **A** record.
+    ///
+    /// An unknown record made by hand with a type name that dog has not
+    /// heard of has no number, so it gets 0, which is reserved.
+    pub fn type_number(&self) -> TypeInt {
+        match self {
+            Self::A(_)      => A::RR_TYPE,
+            Self::AAAA(_)   => AAAA::RR_TYPE,
+            Self::CAA(_)    => CAA::RR_TYPE,
+            Self::CNAME(_)  => CNAME::RR_TYPE,
+            Self::DNSKEY(_) => DNSKEY::RR_TYPE,
+            Self::DS(_)     => DS::RR_TYPE,
+            Self::MX(_)     => MX::RR_TYPE,
+            Self::NS(_)     => NS::RR_TYPE,
+            Self::PTR(_)    => PTR::RR_TYPE,
+            Self::RRSIG(_)  => RRSIG::RR_TYPE,
+            Self::SOA(_)    => SOA::RR_TYPE,
+            Self::SRV(_)    => SRV::RR_TYPE,
+            Self::TXT(_)    => TXT::RR_TYPE,
+            Self::Custom(custom)             => custom.type_number(),
+            Self::Other { type_number, .. }  => type_number.type_number().unwrap_or(0),
+        }
+    }
+
+    /// The mnemonic for this record’s type as it appears in zone files, such
+    /// as `MX`, or `TYPE731` for types without one. Records of registered
+    /// types use the name they were registered with.
+    pub fn type_mnemonic(&self) -> String {
+        match self {
+            Self::Custom(custom)  => custom.type_name().into(),
+            _                     => type_mnemonic(self.type_number()),
+        }
+    }
+
+    /// Parses a record from its type and its data in presentation format,
+    /// as they are written in a zone file, such as `"MX"` and
+    /// `"10 mail.lookup.dog."`. The type can be a mnemonic or the generic
+    /// `TYPE` form, and the data can be in the generic `\#` form from
+    /// RFC 3597, which is the only way to give data for types that this
+    /// crate cannot parse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dns::record::{Record, MX};
+    ///
+    /// let record = Record::from_presentation("MX", "10 mail.lookup.dog.").unwrap();
+    /// assert_eq!(record, Record::MX(MX { preference: 10, exchange: "mail.lookup.dog.".parse().unwrap() }));
+    /// ```
+    pub fn from_presentation(rtype: &str, data: &str) -> Result<Self, PresentationError> {
+        let type_number = parse_type_mnemonic(rtype).ok_or_else(|| PresentationError::UnknownType(rtype.into()))?;
+
+        if data.trim_start().starts_with("\\#") {
+            return Self::from_generic(type_number, data);
+        }
+
+        Ok(match type_number {
+            A::RR_TYPE       => Self::A(data.parse()?),
+            AAAA::RR_TYPE    => Self::AAAA(data.parse()?),
+            CAA::RR_TYPE     => Self::CAA(data.parse()?),
+            CNAME::RR_TYPE   => Self::CNAME(data.parse()?),
+            DNSKEY::RR_TYPE  => Self::DNSKEY(data.parse()?),
+            DS::RR_TYPE      => Self::DS(data.parse()?),
+            MX::RR_TYPE      => Self::MX(data.parse()?),
+            NS::RR_TYPE      => Self::NS(data.parse()?),
+            PTR::RR_TYPE     => Self::PTR(data.parse()?),
+            RRSIG::RR_TYPE   => Self::RRSIG(data.parse()?),
+            SOA::RR_TYPE     => Self::SOA(data.parse()?),
+            SRV::RR_TYPE     => Self::SRV(data.parse()?),
+            TXT::RR_TYPE     => Self::TXT(data.parse()?),
+            _                => return Err(PresentationError::UnknownType(rtype.into())),
+        })
+    }
+
+    /// Parses data in the generic `\# length hex` form, decoding it as the
+    /// given type if it is one that can be parsed.
+    fn from_generic(type_number: TypeInt, data: &str) -> Result<Self, PresentationError> {
+        let mut fields = Fields::new(data)?;
+        fields.text("\\#")?;
+        let length: u16 = fields.parse("length")?;
+
+        let bytes = if length == 0 {
+            fields.finish()?;
+            Vec::new()
+        }
+        else {
+            let text = fields.rest("data")?;
+            decode_hex(&text).ok_or_else(|| invalid("data", &text))?
+        };
+
+        if bytes.len() != usize::from(length) {
+            return Err(invalid("length", &length.to_string()));
+        }
+
+        Self::from_bytes(type_number, length, &mut Cursor::new(&bytes), &ParseOptions::default())
+            .map_err(|_| invalid("data", &hex(&bytes)))
+    }
+}
+
+/// Records are displayed in presentation format, the way their data is
+/// written in zone files. Records of types that cannot be parsed use the
+/// generic `\#` form from RFC 3597.
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::A(a)            => write!(f, "{}", a),
+            Self::AAAA(aaaa)      => write!(f, "{}", aaaa),
+            Self::CAA(caa)        => write!(f, "{}", caa),
+            Self::CNAME(cname)    => write!(f, "{}", cname),
+            Self::DNSKEY(dnskey)  => write!(f, "{}", dnskey),
+            Self::DS(ds)          => write!(f, "{}", ds),
+            Self::MX(mx)          => write!(f, "{}", mx),
+            Self::NS(ns)          => write!(f, "{}", ns),
+            Self::PTR(ptr)        => write!(f, "{}", ptr),
+            Self::RRSIG(rrsig)    => write!(f, "{}", rrsig),
+            Self::SOA(soa)        => write!(f, "{}", soa),
+            Self::SRV(srv)        => write!(f, "{}", srv),
+            Self::TXT(txt)        => write!(f, "{}", txt),
+            Self::Custom(custom)  => write!(f, "{}", custom),
+            Self::Other { bytes, .. } if bytes.is_empty() => write!(f, "\\# 0"),
+            Self::Other { bytes, .. } => write!(f, "\\# {} {}", bytes.len(), hex(bytes)),
+        }
+    }
+}
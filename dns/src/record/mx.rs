@@ -1,4 +1,9 @@
-use crate::strings::ReadLabels;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, write_name};
 use crate::wire::*;
 
 use log::{warn, debug};
@@ -11,6 +16,7 @@ use log::{warn, debug};
 ///
 /// - [RFC 1035 §3.3.s](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MX {
 
     /// The preference that clients should give to this MX record amongst all
@@ -18,7 +24,7 @@ pub struct MX {
     pub preference: u16,
 
     /// The domain name of the mail exchange server.
-    pub exchange: String,
+    pub exchange: Name,
 }
 
 impl Wire for MX {
@@ -26,11 +32,15 @@ impl Wire for MX {
     const RR_TYPE: u16 = 15;
 
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        Self::read_with(len, c, &ParseOptions::default())
+    }
+
+    fn read_with(len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
         let preference = c.read_u16::<BigEndian>()?;
-        let exchange = c.read_labels()?;
+        let exchange = c.read_labels_with(options)?;
 
-        if 2 + exchange.len() + 1 != len as usize {
-            warn!("Expected length {} but read {} bytes", len, 2 + exchange.len() + 1);
+        if 2 + exchange.as_str().len() + 1 != len as usize {
+            warn!("Expected length {} but read {} bytes", len, 2 + exchange.as_str().len() + 1);
         }
         else {
             debug!("Length {} is correct", len);
@@ -38,6 +48,30 @@ impl Wire for MX {
 
         Ok(MX { preference, exchange })
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_u16::<BigEndian>(self.preference)?;
+        bytes.write_labels(&self.exchange)
+    }
+}
+
+impl fmt::Display for MX {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.preference)?;
+        write_name(f, &self.exchange)
+    }
+}
+
+impl FromStr for MX {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let preference = fields.parse("preference")?;
+        let exchange = fields.name("exchange")?;
+        fields.finish()?;
+        Ok(Self { preference, exchange })
+    }
 }
 
 
@@ -53,7 +87,7 @@ mod test {
         assert_eq!(MX::read(12, &mut Cursor::new(buf)).unwrap(),
                    MX {
                        preference: 10,
-                       exchange: String::from("bsago.me."),
+                       exchange: "bsago.me.".parse().unwrap(),
                    });
     }
 
@@ -62,4 +96,37 @@ mod test {
         assert_eq!(MX::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x00, 0x0A, 0x05, 0x62, 0x73, 0x61, 0x67, 0x6f, 0x02,
+                     0x6d, 0x65, 0x00 ];
+        let record = MX::read(12, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = MX { preference: 10, exchange: "bsago.me".parse().unwrap() };
+        assert_eq!(record.to_string(), "10 bsago.me.");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("10 bsago.me.".parse::<MX>(),
+                   Ok(MX { preference: 10, exchange: "bsago.me.".parse().unwrap() }));
+    }
+
+    #[test]
+    fn presentation_missing_field() {
+        assert_eq!("10".parse::<MX>(), Err(PresentationError::MissingField("exchange")));
+    }
+
+    #[test]
+    fn presentation_extra_field() {
+        assert_eq!("10 bsago.me. 20".parse::<MX>(), Err(PresentationError::ExtraField("20".into())));
+    }
 }
@@ -1,4 +1,9 @@
-use crate::strings::ReadLabels;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, write_name};
 use crate::wire::*;
 
 use log::{warn, debug};
@@ -11,10 +16,11 @@ use log::{warn, debug};
 ///
 /// - [RFC 1035 §3.3.11](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NS {
 
     /// The address of a nameserver that provides this DNS response.
-    pub nameserver: String,
+    pub nameserver: Name,
 }
 
 impl Wire for NS {
@@ -22,17 +28,42 @@ impl Wire for NS {
     const RR_TYPE: u16 = 2;
 
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let nameserver = c.read_labels()?;
+        Self::read_with(len, c, &ParseOptions::default())
+    }
 
-        if nameserver.len() + 1 != len as usize {
-            warn!("Expected length {} but read {} bytes", len, nameserver.len() + 1);
+    fn read_with(len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        let nameserver = c.read_labels_with(options)?;
+
+        if nameserver.as_str().len() + 1 != len as usize {
+            warn!("Expected length {} but read {} bytes", len, nameserver.as_str().len() + 1);
         }
         else {
-            debug!("Length {} is correct", nameserver.len() + 1);
+            debug!("Length {} is correct", nameserver.as_str().len() + 1);
         }
 
         Ok(NS { nameserver })
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_labels(&self.nameserver)
+    }
+}
+
+impl fmt::Display for NS {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_name(f, &self.nameserver)
+    }
+}
+
+impl FromStr for NS {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let nameserver = fields.name("nameserver")?;
+        fields.finish()?;
+        Ok(Self { nameserver })
+    }
 }
 
 
@@ -48,7 +79,7 @@ mod test {
 
         assert_eq!(NS::read(20, &mut Cursor::new(buf)).unwrap(),
                    NS {
-                       nameserver: String::from("a.gtld-servers.net."),
+                       nameserver: "a.gtld-servers.net.".parse().unwrap(),
                    });
     }
 
@@ -57,4 +88,28 @@ mod test {
         assert_eq!(NS::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x01, 0x61, 0x0c, 0x67,
+                     0x74, 0x6c, 0x64, 0x2d, 0x73, 0x65, 0x72, 0x76,
+                     0x65, 0x72, 0x73, 0x03, 0x6e, 0x65, 0x74, 0x00, ];
+        let record = NS::read(20, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = NS { nameserver: "a.gtld-servers.net.".parse().unwrap() };
+        assert_eq!(record.to_string(), "a.gtld-servers.net.");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("a.gtld-servers.net.".parse::<NS>(),
+                   Ok(NS { nameserver: "a.gtld-servers.net.".parse().unwrap() }));
+    }
 }
@@ -1,4 +1,5 @@
 use std::io;
+use std::time::Duration;
 
 use crate::wire::*;
 
@@ -26,7 +27,9 @@ use crate::wire::*;
 /// # References
 ///
 /// - [RFC 6891](https://tools.ietf.org/html/rfc6891) — Extension Mechanisms for DNS (April 2013)
+/// - [RFC 7828](https://tools.ietf.org/html/rfc7828) — The edns-tcp-keepalive EDNS0 Option (April 2016)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OPT {
 
     /// The maximum size of a UDP packet that the client supports.
@@ -36,12 +39,14 @@ pub struct OPT {
     pub higher_bits: u8,
 
     /// The version number of the DNS extension mechanism.
+    #[cfg_attr(feature = "serde", serde(rename = "version"))]
     pub edns0_version: u8,
 
     /// Sixteen bits worth of flags.
     pub flags: u16,
 
     /// The payload of the OPT record.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialise::base64_bytes"))]
     pub data: Vec<u8>,
 }
 
@@ -50,6 +55,11 @@ impl OPT {
     /// The record type number associated with OPT.
     pub const RR_TYPE: u16 = 41;
 
+    /// The option code of the edns-tcp-keepalive option, which clients
+    /// send over TCP to ask how long the server will keep an idle
+    /// connection open for.
+    pub const TCP_KEEPALIVE: u16 = 11;
+
     /// Reads from the given cursor to parse an OPT record.
     ///
     /// The buffer will have slightly more bytes to read for an OPT record
@@ -65,10 +75,7 @@ impl OPT {
         let flags = c.read_u16::<BigEndian>()?;             // ...as does this
 
         let data_length = c.read_u16::<BigEndian>()?;
-        let mut data = Vec::new();
-        for _ in 0 .. data_length {
-            data.push(c.read_u8()?);
-        }
+        let data = c.read_slice(usize::from(data_length))?.to_vec();
 
         Ok(OPT { udp_payload_size, higher_bits, edns0_version, flags, data })
     }
@@ -91,6 +98,82 @@ impl OPT {
 
         Ok(bytes)
     }
+
+    /// The name of the EDNS option with the given code, as dig writes it,
+    /// if it is one that has been assigned.
+    pub fn option_name(code: u16) -> Option<&'static str> {
+        Some(match code {
+             3 => "NSID",
+             5 => "DAU",
+             6 => "DHU",
+             7 => "N3U",
+             8 => "CLIENT-SUBNET",
+             9 => "EXPIRE",
+            10 => "COOKIE",
+            11 => "TCP-KEEPALIVE",
+            12 => "PADDING",
+            13 => "CHAIN",
+            14 => "KEY-TAG",
+            15 => "EDE",
+            16 => "CLIENT-TAG",
+            17 => "SERVER-TAG",
+            18 => "REPORT-CHANNEL",
+            19 => "ZONEVERSION",
+             _ => return None,
+        })
+    }
+
+    /// The extended error code bits, version, and flags, packed into one
+    /// number, as they get sent in the place of a normal record’s TTL.
+    pub fn ttl(&self) -> u32 {
+        u32::from(self.higher_bits) << 24 | u32::from(self.edns0_version) << 16 | u32::from(self.flags)
+    }
+
+    /// Whether the `DO` (DNSSEC OK) bit is set in the flags.
+    pub fn dnssec_ok(&self) -> bool {
+        self.flags & 0b_1000_0000_0000_0000 != 0
+    }
+
+    /// Iterates over the options in the payload, as pairs of option codes
+    /// and their data, stopping at the first one that is cut short.
+    pub fn options(&self) -> impl Iterator<Item=(u16, &[u8])> {
+        let mut data = &self.data[..];
+
+        std::iter::from_fn(move || {
+            if data.len() < 4 {
+                return None;
+            }
+
+            let code = u16::from_be_bytes([ data[0], data[1] ]);
+            let length = usize::from(u16::from_be_bytes([ data[2], data[3] ]));
+            let value = data.get(4 .. 4 + length)?;
+
+            data = &data[4 + length ..];
+            Some((code, value))
+        })
+    }
+
+    /// Adds an empty edns-tcp-keepalive option to the payload, which is
+    /// how a client asks for it (RFC 7828 §3.2.1), unless there is one
+    /// already.
+    pub fn add_tcp_keepalive(&mut self) {
+        if self.options().all(|(code, _)| code != Self::TCP_KEEPALIVE) {
+            self.data.extend(&Self::TCP_KEEPALIVE.to_be_bytes());
+            self.data.extend(&[ 0, 0 ]);
+        }
+    }
+
+    /// The idle timeout a server advertised in its edns-tcp-keepalive
+    /// option, which is sent in units of 100 milliseconds. This is nothing
+    /// if the option is missing or has no timeout in it.
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        self.options().find_map(|(code, value)| {
+            match value {
+                [ a, b ] if code == Self::TCP_KEEPALIVE  => Some(Duration::from_millis(u64::from(u16::from_be_bytes([ *a, *b ])) * 100)),
+                _                                        => None,
+            }
+        })
+    }
 }
 
 
@@ -112,6 +195,51 @@ mod test {
                    });
     }
 
+    fn opt(data: Vec<u8>) -> OPT {
+        OPT { udp_payload_size: 1232, higher_bits: 0, edns0_version: 0, flags: 0, data }
+    }
+
+    #[test]
+    fn options() {
+        let opt = opt(vec![ 0x00, 0x0A, 0x00, 0x02, 0xAB, 0xCD,  0x00, 0x03, 0x00, 0x00 ]);
+        assert_eq!(opt.options().collect::<Vec<_>>(),
+                   vec![ (10, &[ 0xAB, 0xCD ][..]), (3, &[][..]) ]);
+    }
+
+    #[test]
+    fn ttl() {
+        let opt = OPT { higher_bits: 0x01, edns0_version: 0x02, flags: 0x8000, .. opt(vec![]) };
+        assert_eq!(opt.ttl(), 0x0102_8000);
+    }
+
+    #[test]
+    fn options_cut_short() {
+        let opt = opt(vec![ 0x00, 0x0A, 0x00, 0x08, 0xAB, 0xCD ]);
+        assert_eq!(opt.options().count(), 0);
+    }
+
+    #[test]
+    fn option_names() {
+        assert_eq!(OPT::option_name(3), Some("NSID"));
+        assert_eq!(OPT::option_name(15), Some("EDE"));
+        assert_eq!(OPT::option_name(65001), None);
+    }
+
+    #[test]
+    fn adds_tcp_keepalive_once() {
+        let mut opt = opt(vec![]);
+        opt.add_tcp_keepalive();
+        opt.add_tcp_keepalive();
+        assert_eq!(opt.data, vec![ 0x00, 0x0B, 0x00, 0x00 ]);
+        assert_eq!(opt.tcp_keepalive(), None);
+    }
+
+    #[test]
+    fn parses_tcp_keepalive() {
+        let opt = opt(vec![ 0x00, 0x0B, 0x00, 0x02, 0x01, 0x2C ]);
+        assert_eq!(opt.tcp_keepalive(), Some(Duration::from_secs(30)));
+    }
+
     #[test]
     fn empty() {
         assert_eq!(OPT::read(&mut Cursor::new(&[])),
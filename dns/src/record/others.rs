@@ -21,6 +21,19 @@ impl From<u16> for UnknownQtype {
     }
 }
 
+impl UnknownQtype {
+
+    /// The number that represents this record type, or `None` for a name
+    /// that is not in the list of types dog has heard of. Those can only
+    /// come from building a `HeardOf` value by hand.
+    pub fn type_number(self) -> Option<u16> {
+        match self {
+            Self::HeardOf(name)   => find_other_qtype_number(name),
+            Self::UnheardOf(num)  => Some(num),
+        }
+    }
+}
+
 impl fmt::Display for UnknownQtype {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -36,6 +49,12 @@ pub fn find_other_qtype_number(name: &str) -> Option<u16> {
     TYPES.iter().find(|t| t.0 == name).map(|t| t.1)
 }
 
+/// Returns the names of every record type dog knows about, but still
+/// doesn’t know how to parse.
+pub fn other_qtype_names() -> impl Iterator<Item = &'static str> {
+    TYPES.iter().map(|t| t.0)
+}
+
 /// Mapping of record type names to their assigned numbers.
 static TYPES: &[(&str, u16)] = &[
     ("AFSDB",      18),
@@ -49,8 +68,6 @@ static TYPES: &[(&str, u16)] = &[
     ("DHCID",      49),
     ("DLV",     32769),
     ("DNAME",      39),
-    ("DNSKEEYE",   48),
-    ("DS",         43),
     ("HINFO",      13),
     ("HIP",        55),
     ("IPSECKEY",   45),
@@ -63,7 +80,6 @@ static TYPES: &[(&str, u16)] = &[
     ("NSEC3",      50),
     ("NSEC3PARAM", 51),
     ("OPENPGPKEY", 61),
-    ("RRSIG",      46),
     ("RP",         17),
     ("SIG",        24),
     ("SMIMEA",     53),
@@ -74,3 +90,25 @@ static TYPES: &[(&str, u16)] = &[
     ("TSIG",      250),
     ("URI",       256),
 ];
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heard_of() {
+        assert_eq!(UnknownQtype::from(44), UnknownQtype::HeardOf("SSHFP"));
+        assert_eq!(UnknownQtype::HeardOf("SSHFP").type_number(), Some(44));
+    }
+
+    #[test]
+    fn unheard_of() {
+        assert_eq!(UnknownQtype::from(731).type_number(), Some(731));
+    }
+
+    #[test]
+    fn made_up_name() {
+        assert_eq!(UnknownQtype::HeardOf("WOOF").type_number(), None);
+    }
+}
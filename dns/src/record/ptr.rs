@@ -1,4 +1,10 @@
-use crate::strings::ReadLabels;
+use std::fmt;
+use std::str::FromStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, write_name};
 use crate::wire::*;
 
 
@@ -14,20 +20,123 @@ use crate::wire::*;
 ///
 /// - [RFC 1035 §3.3.14](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PTR {
 
     /// The CNAME contained in the record.
-    pub cname: String,
+    pub cname: Name,
 }
 
 impl Wire for PTR {
     const NAME: &'static str = "PTR";
     const RR_TYPE: u16 = 12;
 
-    fn read(_len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let cname = c.read_labels()?;
+    fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        Self::read_with(len, c, &ParseOptions::default())
+    }
+
+    fn read_with(_len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        let cname = c.read_labels_with(options)?;
         Ok(PTR { cname })
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_labels(&self.cname)
+    }
+}
+
+impl PTR {
+
+    /// Returns the domain name to query for PTR records in order to do a
+    /// reverse lookup of the given address. IPv4 addresses have their octets
+    /// reversed under `in-addr.arpa`, and IPv6 addresses have each of their
+    /// nibbles reversed under `ip6.arpa`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dns::record::PTR;
+    ///
+    /// assert_eq!(PTR::reverse_name("127.0.0.1".parse().unwrap()).to_string(),
+    ///            "1.0.0.127.in-addr.arpa");
+    /// ```
+    ///
+    /// # References
+    ///
+    /// - [RFC 1035 §3.5](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
+    /// - [RFC 3596 §2.5](https://tools.ietf.org/html/rfc3596) — DNS Extensions to Support IP Version 6 (October 2003)
+    pub fn reverse_name(address: IpAddr) -> Name {
+        let name = match address {
+            IpAddr::V4(v4) => {
+                let [a, b, c, d] = v4.octets();
+                format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+            }
+            IpAddr::V6(v6) => {
+                let mut name = String::with_capacity(72);
+                for byte in v6.octets().iter().rev() {
+                    name.push_str(&format!("{:x}.{:x}.", byte & 0x0F, byte >> 4));
+                }
+
+                name.push_str("ip6.arpa");
+                name
+            }
+        };
+
+        Name::unchecked(name)
+    }
+
+    /// Returns the address that the given name does a reverse lookup of,
+    /// the other way round from `reverse_name`, or `None` if the name is
+    /// not a complete address under `in-addr.arpa` or `ip6.arpa`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dns::record::PTR;
+    ///
+    /// assert_eq!(PTR::reversed_address(&"1.0.0.127.in-addr.arpa.".parse().unwrap()),
+    ///            Some("127.0.0.1".parse().unwrap()));
+    /// ```
+    pub fn reversed_address(name: &Name) -> Option<IpAddr> {
+        let labels = name.labels().collect::<Vec<_>>();
+
+        match labels.as_slice() {
+            [ d, c, b, a, arpa, root ] if arpa.eq_ignore_ascii_case("in-addr") && root.eq_ignore_ascii_case("arpa") => {
+                format!("{}.{}.{}.{}", a, b, c, d).parse::<Ipv4Addr>().ok().map(IpAddr::V4)
+            }
+            [ nibbles @ .., ip6, root ] if nibbles.len() == 32 && ip6.eq_ignore_ascii_case("ip6") && root.eq_ignore_ascii_case("arpa") => {
+                let mut octets = [0_u8; 16];
+                for (index, nibble) in nibbles.iter().rev().enumerate() {
+                    let value = match nibble.as_bytes() {
+                        [ digit ] => (*digit as char).to_digit(16)? as u8,
+                        _         => return None,
+                    };
+
+                    octets[index / 2] |= if index % 2 == 0 { value << 4 } else { value };
+                }
+
+                Some(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PTR {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_name(f, &self.cname)
+    }
+}
+
+impl FromStr for PTR {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let cname = fields.name("domain")?;
+        fields.finish()?;
+        Ok(Self { cname })
+    }
 }
 
 
@@ -42,13 +151,67 @@ mod test {
 
         assert_eq!(PTR::read(12, &mut Cursor::new(buf)).unwrap(),
                    PTR {
-                       cname: String::from("dns.google."),
+                       cname: "dns.google.".parse().unwrap(),
                    });
     }
 
+    #[test]
+    fn reverse_ipv4() {
+        assert_eq!(PTR::reverse_name("8.8.4.4".parse().unwrap()).to_string(),
+                   "4.4.8.8.in-addr.arpa");
+    }
+
+    #[test]
+    fn reverse_ipv6() {
+        assert_eq!(PTR::reverse_name("2001:db8::1".parse().unwrap()).to_string(),
+                   "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa");
+    }
+
+    #[test]
+    fn reversed_ipv4() {
+        assert_eq!(PTR::reversed_address(&"4.4.8.8.in-addr.arpa.".parse().unwrap()),
+                   Some("8.8.4.4".parse().unwrap()));
+    }
+
+    #[test]
+    fn reversed_ipv6() {
+        let address = "2001:db8::1".parse().unwrap();
+        assert_eq!(PTR::reversed_address(&PTR::reverse_name(address)), Some(address));
+    }
+
+    #[test]
+    fn reversed_partial_address() {
+        assert_eq!(PTR::reversed_address(&"8.8.in-addr.arpa.".parse().unwrap()), None);
+        assert_eq!(PTR::reversed_address(&"256.4.8.8.in-addr.arpa.".parse().unwrap()), None);
+        assert_eq!(PTR::reversed_address(&"dns.lookup.dog.".parse().unwrap()), None);
+    }
+
     #[test]
     fn empty() {
         assert_eq!(PTR::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x03, 0x64, 0x6e, 0x73, 0x06, 0x67,
+                     0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x00 ];
+        let record = PTR::read(12, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = PTR { cname: "dns.google".parse().unwrap() };
+        assert_eq!(record.to_string(), "dns.google.");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("dns.google.".parse::<PTR>(),
+                   Ok(PTR { cname: "dns.google.".parse().unwrap() }));
+    }
 }
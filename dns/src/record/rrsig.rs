@@ -0,0 +1,218 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, base64, decode_base64, invalid, parse_timestamp, parse_type_mnemonic, timestamp, type_mnemonic, write_name};
+use crate::wire::*;
+
+
+/// A **RRSIG** _(resource record signature)_ record, which contains a
+/// DNSSEC signature over the set of records with a particular name, class,
+/// and type.
+///
+/// # References
+///
+/// - [RFC 4034 §3](https://tools.ietf.org/html/rfc4034) — Resource Records for the DNS Security Extensions (March 2005)
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RRSIG {
+
+    /// The type number of the records covered by this signature.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialise::type_name"))]
+    pub type_covered: u16,
+
+    /// The number of the cryptographic algorithm used to create the signature.
+    pub algorithm: u8,
+
+    /// The number of labels in the original owner name, not counting the
+    /// root label or any wildcard label.
+    pub labels: u8,
+
+    /// The TTL of the covered records, as it appears in the zone.
+    pub original_ttl: u32,
+
+    /// The time after which the signature is no longer valid, in seconds
+    /// since the Unix epoch (modulo 2³²).
+    pub expiration: u32,
+
+    /// The time before which the signature is not yet valid, in seconds
+    /// since the Unix epoch (modulo 2³²).
+    pub inception: u32,
+
+    /// The key tag of the DNSKEY record that can verify this signature.
+    pub key_tag: u16,
+
+    /// The name of the zone containing the signing key.
+    pub signer_name: Name,
+
+    /// The cryptographic signature itself.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialise::base64_bytes"))]
+    pub signature: Vec<u8>,
+}
+
+impl Wire for RRSIG {
+    const NAME: &'static str = "RRSIG";
+    const RR_TYPE: u16 = 46;
+
+    fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        Self::read_with(len, c, &ParseOptions::default())
+    }
+
+    fn read_with(len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        let start = c.position();
+
+        let type_covered = c.read_u16::<BigEndian>()?;
+        let algorithm    = c.read_u8()?;
+        let labels       = c.read_u8()?;
+        let original_ttl = c.read_u32::<BigEndian>()?;
+        let expiration   = c.read_u32::<BigEndian>()?;
+        let inception    = c.read_u32::<BigEndian>()?;
+        let key_tag      = c.read_u16::<BigEndian>()?;
+        let signer_name  = c.read_labels_with(options)?;
+
+        let read_so_far = c.position() - start;
+        if read_so_far > u64::from(len) {
+            return Err(WireError::WrongLength { expected: len, got: read_so_far as u16 });
+        }
+
+        let signature = c.read_slice((u64::from(len) - read_so_far) as usize)?.to_vec();
+
+        Ok(RRSIG {
+            type_covered, algorithm, labels, original_ttl, expiration,
+            inception, key_tag, signer_name, signature,
+        })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_u16::<BigEndian>(self.type_covered)?;
+        bytes.write_u8(self.algorithm)?;
+        bytes.write_u8(self.labels)?;
+        bytes.write_u32::<BigEndian>(self.original_ttl)?;
+        bytes.write_u32::<BigEndian>(self.expiration)?;
+        bytes.write_u32::<BigEndian>(self.inception)?;
+        bytes.write_u16::<BigEndian>(self.key_tag)?;
+        bytes.write_labels(&self.signer_name)?;
+        bytes.extend(&self.signature);
+        Ok(())
+    }
+}
+
+impl fmt::Display for RRSIG {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {} {} {} {} ",
+            type_mnemonic(self.type_covered), self.algorithm, self.labels, self.original_ttl,
+            timestamp(self.expiration), timestamp(self.inception), self.key_tag)?;
+        write_name(f, &self.signer_name)?;
+        write!(f, " {}", base64(&self.signature))
+    }
+}
+
+impl FromStr for RRSIG {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+
+        let covered = fields.text("type covered")?;
+        let type_covered = parse_type_mnemonic(covered).ok_or_else(|| invalid("type covered", covered))?;
+        let algorithm = fields.parse("algorithm")?;
+        let labels = fields.parse("labels")?;
+        let original_ttl = fields.parse("original TTL")?;
+
+        let expiration = fields.text("expiration")?;
+        let expiration = parse_timestamp(expiration).ok_or_else(|| invalid("expiration", expiration))?;
+        let inception = fields.text("inception")?;
+        let inception = parse_timestamp(inception).ok_or_else(|| invalid("inception", inception))?;
+
+        let key_tag = fields.parse("key tag")?;
+        let signer_name = fields.name("signer name")?;
+
+        let signature_text = fields.rest("signature")?;
+        let signature = decode_base64(&signature_text).ok_or_else(|| invalid("signature", &signature_text))?;
+
+        Ok(Self {
+            type_covered, algorithm, labels, original_ttl, expiration, inception,
+            key_tag, signer_name, signature,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let buf = &[
+            0x00, 0x01,              // type covered
+            0x0d,                    // algorithm
+            0x02,                    // labels
+            0x00, 0x00, 0x0e, 0x10,  // original TTL
+            0x5f, 0x5e, 0x10, 0x00,  // expiration
+            0x5f, 0x4b, 0x9b, 0x00,  // inception
+            0x12, 0x34,              // key tag
+            0x03, 0x64, 0x6f, 0x67, 0x00,  // signer name
+            0xAA, 0xBB, 0xCC,        // signature
+        ];
+
+        assert_eq!(RRSIG::read(26, &mut Cursor::new(buf)).unwrap(),
+                   RRSIG {
+                       type_covered: 1,
+                       algorithm: 13,
+                       labels: 2,
+                       original_ttl: 3600,
+                       expiration: 0x5f5e1000,
+                       inception: 0x5f4b9b00,
+                       key_tag: 0x1234,
+                       signer_name: "dog.".parse().unwrap(),
+                       signature: vec![ 0xAA, 0xBB, 0xCC ],
+                   });
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(RRSIG::read(0, &mut Cursor::new(&[])),
+                   Err(WireError::IO));
+    }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[
+            0x00, 0x01,              // type covered
+            0x0d,                    // algorithm
+            0x02,                    // labels
+            0x00, 0x00, 0x0e, 0x10,  // original TTL
+            0x5f, 0x5e, 0x10, 0x00,  // expiration
+            0x5f, 0x4b, 0x9b, 0x00,  // inception
+            0x12, 0x34,              // key tag
+            0x03, 0x64, 0x6f, 0x67, 0x00,  // signer name
+            0xAA, 0xBB, 0xCC,        // signature
+        ];
+        let record = RRSIG::read(26, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    fn sample() -> RRSIG {
+        RRSIG {
+            type_covered: 1, algorithm: 13, labels: 2, original_ttl: 300,
+            expiration: 1_609_459_199, inception: 0, key_tag: 1234,
+            signer_name: "lookup.dog".parse().unwrap(), signature: b"dogs".to_vec(),
+        }
+    }
+
+    #[test]
+    fn displays() {
+        assert_eq!(sample().to_string(), "A 13 2 300 20201231235959 19700101000000 1234 lookup.dog. ZG9ncw==");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        let record = RRSIG::from_str(&sample().to_string()).unwrap();
+        assert_eq!(record, sample());
+    }
+}
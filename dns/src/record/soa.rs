@@ -1,4 +1,10 @@
-use crate::strings::ReadLabels;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, write_name};
 use crate::wire::*;
 
 use log::{warn, debug};
@@ -12,13 +18,14 @@ use log::{warn, debug};
 ///
 /// - [RFC 1035 §3.3.13](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SOA {
 
     /// The primary master name for this server.
-    pub mname: String,
+    pub mname: Name,
 
     /// The e-mail address of the administrator responsible for this DNS zone.
-    pub rname: String,
+    pub rname: Name,
 
     /// A serial number for this DNS zone.
     pub serial: u32,
@@ -46,8 +53,12 @@ impl Wire for SOA {
     const RR_TYPE: u16 = 6;
 
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let mname = c.read_labels()?;
-        let rname = c.read_labels()?;
+        Self::read_with(len, c, &ParseOptions::default())
+    }
+
+    fn read_with(len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
+        let mname = c.read_labels_with(options)?;
+        let rname = c.read_labels_with(options)?;
 
         let serial           = c.read_u32::<BigEndian>()?;
         let refresh_interval = c.read_u32::<BigEndian>()?;
@@ -55,7 +66,7 @@ impl Wire for SOA {
         let expire_limit     = c.read_u32::<BigEndian>()?;
         let minimum_ttl      = c.read_u32::<BigEndian>()?;
 
-        let got_length = mname.len() + rname.len() + 4 * 5 + 2;
+        let got_length = mname.as_str().len() + rname.as_str().len() + 4 * 5 + 2;
         if got_length != len as usize {
             warn!("Expected length {} but got {}", len, got_length);
         }
@@ -68,6 +79,122 @@ impl Wire for SOA {
             retry_interval, expire_limit, minimum_ttl,
         })
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_labels(&self.mname)?;
+        bytes.write_labels(&self.rname)?;
+
+        bytes.write_u32::<BigEndian>(self.serial)?;
+        bytes.write_u32::<BigEndian>(self.refresh_interval)?;
+        bytes.write_u32::<BigEndian>(self.retry_interval)?;
+        bytes.write_u32::<BigEndian>(self.expire_limit)?;
+        bytes.write_u32::<BigEndian>(self.minimum_ttl)?;
+        Ok(())
+    }
+}
+
+impl SOA {
+
+    /// Returns the administrator’s email address that the `rname` field
+    /// stands for, with its first label as the part before the `@`, such
+    /// as `hostmaster@lookup.dog` for `hostmaster.lookup.dog.` (RFC 1035
+    /// §8). A dot inside the first label is written escaped as `\.`.
+    /// Returns `None` if there are not enough labels to make an address.
+    pub fn email(&self) -> Option<String> {
+        let rname = self.rname.as_str().trim_end_matches('.');
+
+        let mut escaped = false;
+        let split = rname.char_indices().find(|&(_, c)| {
+            let unescaped_dot = c == '.' && ! escaped;
+            escaped = c == '\\' && ! escaped;
+            unescaped_dot
+        })?.0;
+
+        let (mailbox, domain) = (&rname[.. split], &rname[split + 1 ..]);
+        if mailbox.is_empty() || domain.is_empty() {
+            return None;
+        }
+
+        Some(format!("{}@{}", mailbox.replace("\\.", "."), domain))
+    }
+
+    /// Whether this record’s serial number is newer than the other one’s,
+    /// using serial number arithmetic, so a serial that has wrapped around
+    /// past zero still counts as newer. Secondary nameservers use this to
+    /// decide whether the zone has changed and needs transferring again.
+    pub fn serial_newer_than(&self, other: &Self) -> bool {
+        compare_serials(self.serial, other.serial) == Some(Ordering::Greater)
+    }
+}
+
+
+/// Compares two serial numbers using serial number arithmetic (RFC 1982
+/// §3.2), where each number counts as greater than the 2³¹ − 1 numbers
+/// after it once they wrap around. Two numbers exactly 2³¹ apart cannot
+/// be compared, so `None` is returned for them.
+///
+/// # Examples
+///
+/// ```
+/// use std::cmp::Ordering;
+/// use dns::record::compare_serials;
+///
+/// assert_eq!(compare_serials(2020010102, 2020010101), Some(Ordering::Greater));
+/// assert_eq!(compare_serials(5, 0xFFFF_FFF0), Some(Ordering::Greater));
+/// assert_eq!(compare_serials(0, 0x8000_0000), None);
+/// ```
+pub fn compare_serials(serial: u32, other: u32) -> Option<Ordering> {
+    match serial.wrapping_sub(other) {
+        0                      => Some(Ordering::Equal),
+        0x8000_0000            => None,
+        d if d < 0x8000_0000   => Some(Ordering::Greater),
+        _                      => Some(Ordering::Less),
+    }
+}
+
+/// Adds to a serial number using serial number arithmetic (RFC 1982
+/// §3.1), wrapping around past the largest number. Only up to 2³¹ − 1 can
+/// be added at once, as any more would make the new serial number compare
+/// as older, so `None` is returned for larger increments.
+pub fn add_to_serial(serial: u32, increment: u32) -> Option<u32> {
+    if increment < 0x8000_0000 {
+        Some(serial.wrapping_add(increment))
+    }
+    else {
+        None
+    }
+}
+
+impl fmt::Display for SOA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_name(f, &self.mname)?;
+        write!(f, " ")?;
+        write_name(f, &self.rname)?;
+        write!(f, " {} {} {} {} {}",
+            self.serial, self.refresh_interval, self.retry_interval,
+            self.expire_limit, self.minimum_ttl)
+    }
+}
+
+impl FromStr for SOA {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+
+        let soa = Self {
+            mname:             fields.name("primary nameserver")?,
+            rname:             fields.name("responsible mailbox")?,
+            serial:            fields.parse("serial")?,
+            refresh_interval:  fields.ttl("refresh interval")?,
+            retry_interval:    fields.ttl("retry interval")?,
+            expire_limit:      fields.ttl("expire limit")?,
+            minimum_ttl:       fields.ttl("minimum TTL")?,
+        };
+
+        fields.finish()?;
+        Ok(soa)
+    }
 }
 
 
@@ -89,8 +216,8 @@ mod test {
 
         assert_eq!(SOA::read(40, &mut Cursor::new(buf)).unwrap(),
                    SOA {
-                       mname: String::from("bsago.me."),
-                       rname: String::from("bsago.me."),
+                       mname: "bsago.me.".parse().unwrap(),
+                       rname: "bsago.me.".parse().unwrap(),
                        serial: 1564274434,
                        refresh_interval: 86400,
                        retry_interval: 7200,
@@ -104,4 +231,98 @@ mod test {
         assert_eq!(SOA::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[
+            0x05, 0x62, 0x73, 0x61, 0x67, 0x6f, 0x02, 0x6d, 0x65, 0x00,
+            0x05, 0x62, 0x73, 0x61, 0x67, 0x6f, 0x02, 0x6d, 0x65, 0x00,
+            0x5d, 0x3c, 0xef, 0x02,
+            0x00, 0x01, 0x51, 0x80,
+            0x00, 0x00, 0x1c, 0x20,
+            0x00, 0x09, 0x3a, 0x80,
+            0x00, 0x00, 0x01, 0x2c,
+        ];
+        let record = SOA::read(40, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    fn sample() -> SOA {
+        SOA {
+            mname: "ns1.lookup.dog.".parse().unwrap(),
+            rname: "hostmaster.lookup.dog.".parse().unwrap(),
+            serial: 2020010101, refresh_interval: 7200, retry_interval: 3600,
+            expire_limit: 1209600, minimum_ttl: 300,
+        }
+    }
+
+    #[test]
+    fn displays() {
+        assert_eq!(sample().to_string(), "ns1.lookup.dog. hostmaster.lookup.dog. 2020010101 7200 3600 1209600 300");
+    }
+
+    #[test]
+    fn email() {
+        assert_eq!(sample().email(), Some("hostmaster@lookup.dog".into()));
+    }
+
+    #[test]
+    fn email_with_escaped_dot() {
+        let soa = SOA { rname: Name::unchecked(r"first\.last.lookup.dog.".into()), .. sample() };
+        assert_eq!(soa.email(), Some("first.last@lookup.dog".into()));
+    }
+
+    #[test]
+    fn email_without_domain() {
+        let soa = SOA { rname: Name::root(), .. sample() };
+        assert_eq!(soa.email(), None);
+    }
+
+    #[test]
+    fn serial_newer() {
+        let older = sample();
+        let newer = SOA { serial: older.serial + 1, .. sample() };
+        assert!(newer.serial_newer_than(&older));
+        assert!(! older.serial_newer_than(&newer));
+        assert!(! older.serial_newer_than(&older));
+    }
+
+    #[test]
+    fn serial_newer_wrapped_around() {
+        let older = SOA { serial: 0xFFFF_FFFF, .. sample() };
+        let newer = SOA { serial: 1, .. sample() };
+        assert!(newer.serial_newer_than(&older));
+    }
+
+    #[test]
+    fn serials_too_far_apart() {
+        assert_eq!(compare_serials(0x8000_0000, 0), None);
+        assert_eq!(compare_serials(0, 0x8000_0000), None);
+        assert_eq!(compare_serials(0x7FFF_FFFF, 0), Some(Ordering::Greater));
+        assert_eq!(compare_serials(0x8000_0001, 0), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn serial_addition() {
+        assert_eq!(add_to_serial(0xFFFF_FFFF, 2), Some(1));
+        assert_eq!(add_to_serial(7, 0x7FFF_FFFF), Some(0x8000_0006));
+        assert_eq!(add_to_serial(7, 0x8000_0000), None);
+    }
+
+    #[test]
+    fn parses_presentation() {
+        let record = SOA::from_str(&sample().to_string()).unwrap();
+        assert_eq!(record, sample());
+    }
+
+    #[test]
+    fn parses_presentation_with_units() {
+        let record: SOA = "ns1.lookup.dog. hostmaster.lookup.dog. ( 1 2h 1h 2w 5m )".parse().unwrap();
+        assert_eq!(record.refresh_interval, 7200);
+        assert_eq!(record.expire_limit, 1_209_600);
+        assert_eq!(record.minimum_ttl, 300);
+    }
 }
@@ -1,4 +1,9 @@
-use crate::strings::ReadLabels;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::strings::{ReadLabels, WriteLabels};
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, write_name};
 use crate::wire::*;
 
 use log::{debug, warn};
@@ -11,6 +16,7 @@ use log::{debug, warn};
 ///
 /// - [RFC 2782](https://tools.ietf.org/html/rfc2782) — A DNS RR for specifying the location of services (February 2000)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SRV {
 
     /// The priority of this host among all that get returned. Lower values
@@ -25,7 +31,7 @@ pub struct SRV {
     pub port: u16,
 
     /// The hostname of the machine the service is running on.
-    pub target: String,
+    pub target: Name,
 }
 
 impl Wire for SRV {
@@ -33,12 +39,16 @@ impl Wire for SRV {
     const RR_TYPE: u16 = 33;
 
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+        Self::read_with(len, c, &ParseOptions::default())
+    }
+
+    fn read_with(len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
         let priority = c.read_u16::<BigEndian>()?;
         let weight   = c.read_u16::<BigEndian>()?;
         let port     = c.read_u16::<BigEndian>()?;
-        let target   = c.read_labels()?;
+        let target   = c.read_labels_with(options)?;
 
-        let got_length = 3 * 2 + target.len() + 1;
+        let got_length = 3 * 2 + target.as_str().len() + 1;
         if got_length != len as usize {
             warn!("Expected length {} but got {}", len, got_length);
         }
@@ -48,6 +58,34 @@ impl Wire for SRV {
 
         Ok(SRV { priority, weight, port, target })
     }
+
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        bytes.write_u16::<BigEndian>(self.priority)?;
+        bytes.write_u16::<BigEndian>(self.weight)?;
+        bytes.write_u16::<BigEndian>(self.port)?;
+        bytes.write_labels(&self.target)
+    }
+}
+
+impl fmt::Display for SRV {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} ", self.priority, self.weight, self.port)?;
+        write_name(f, &self.target)
+    }
+}
+
+impl FromStr for SRV {
+    type Err = PresentationError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let priority = fields.parse("priority")?;
+        let weight = fields.parse("weight")?;
+        let port = fields.parse("port")?;
+        let target = fields.name("target")?;
+        fields.finish()?;
+        Ok(Self { priority, weight, port, target })
+    }
 }
 
 
@@ -67,7 +105,7 @@ mod test {
                        priority: 1,
                        weight: 1,
                        port: 37500,
-                       target: String::from("ata.local.node.dc1.consul."),
+                       target: "ata.local.node.dc1.consul.".parse().unwrap(),
                    });
     }
 
@@ -76,4 +114,29 @@ mod test {
         assert_eq!(SRV::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x00, 0x01, 0x00, 0x01, 0x92, 0x7c, 0x03, 0x61, 0x74,
+                     0x61, 0x05, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x04, 0x6e,
+                     0x6f, 0x64, 0x65, 0x03, 0x64, 0x63, 0x31, 0x06, 0x63,
+                     0x6f, 0x6e, 0x73, 0x75, 0x6c, 0x00, ];
+        let record = SRV::read(33, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn displays() {
+        let record = SRV { priority: 1, weight: 2, port: 5060, target: "sip.lookup.dog".parse().unwrap() };
+        assert_eq!(record.to_string(), "1 2 5060 sip.lookup.dog.");
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!("1 2 5060 sip.lookup.dog.".parse::<SRV>(),
+                   Ok(SRV { priority: 1, weight: 2, port: 5060, target: "sip.lookup.dog.".parse().unwrap() }));
+    }
 }
@@ -1,3 +1,9 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::presentation::{Fields, PresentationError, write_character_string};
 use crate::wire::*;
 
 use log::*;
@@ -5,54 +11,144 @@ use log::*;
 
 /// A **TXT** record, which holds arbitrary descriptive text.
 ///
+/// The text is made up of one or more character-strings, each at most 255
+/// bytes long. Long values, such as SPF policies and DKIM keys, get split
+/// across several of them, and are meant to be joined back together by
+/// whoever reads them, so the strings are kept separate here.
+///
 /// # Encoding
 ///
-/// The text encoding is not specified, but this crate treats it as UTF-8.
-/// Invalid bytes are turned into the replacement character.
+/// The text encoding is not specified, so the strings are kept as bytes.
+/// `as_utf8_lossy` treats them as UTF-8, with invalid bytes turned into
+/// the replacement character.
 ///
 /// # References
 ///
 /// - [RFC 1035 §3.3.14](https://tools.ietf.org/html/rfc1035) — Domain Names, Implementation and Specification (November 1987)
+/// - [RFC 7208 §3.3](https://tools.ietf.org/html/rfc7208) — Sender Policy Framework, Multiple Strings in a Single DNS Record (April 2014)
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "crate::serialise::TxtStrings", try_from = "crate::serialise::TxtStrings"))]
 pub struct TXT {
 
-    /// The message contained in the record.
-    pub message: String,
+    /// The character-strings contained in the record, in order.
+    pub strings: Vec<Vec<u8>>,
+}
+
+impl TXT {
+
+    /// Creates a record holding the given text, split into strings of the
+    /// longest length allowed. Empty text becomes a single empty string.
+    pub fn from_text(text: impl AsRef<[u8]>) -> Self {
+        let text = text.as_ref();
+
+        if text.is_empty() {
+            Self { strings: vec![ Vec::new() ] }
+        }
+        else {
+            Self { strings: text.chunks(255).map(<[u8]>::to_vec).collect() }
+        }
+    }
+
+    /// Returns each of the strings decoded as UTF-8, with any invalid bytes
+    /// turned into the replacement character.
+    pub fn as_utf8_lossy(&self) -> Vec<Cow<'_, str>> {
+        self.strings.iter().map(|string| String::from_utf8_lossy(string)).collect()
+    }
+
+    /// Returns all the strings joined together with nothing between them,
+    /// which is how values split across several strings are reassembled.
+    pub fn join(&self) -> Vec<u8> {
+        self.strings.concat()
+    }
 }
 
 impl Wire for TXT {
     const NAME: &'static str = "TXT";
     const RR_TYPE: u16 = 16;
 
+    /// Strings are read until the record’s data runs out. There is always
+    /// at least one of them, even if it is empty.
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
-        let mut buf = Vec::new();
+        let mut strings = Vec::new();
         let mut total_len = 0_usize;
 
         loop {
             let next_len = c.read_u8()?;
-            total_len += next_len as usize + 1;
+            total_len += usize::from(next_len) + 1;
 
-            for _ in 0 .. next_len {
-                buf.push(c.read_u8()?);
-            }
+            strings.push(c.read_slice(usize::from(next_len))?.to_vec());
 
-            if next_len < 255 {
+            if total_len >= usize::from(len) {
                 break;
             }
-            else {
-                debug!("Got length 255 so looping");
-            }
         }
 
-        if total_len == len as usize {
+        if total_len == usize::from(len) {
             debug!("Length matches expected");
         }
         else {
-            warn!("Expected length {} but read {} bytes", len, buf.len());
+            warn!("Expected length {} but read {} bytes", len, total_len);
+        }
+
+        Ok(TXT { strings })
+    }
+
+    /// A record with no strings gets written with a single empty one, as
+    /// a TXT record cannot be empty.
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        if self.strings.is_empty() {
+            bytes.write_u8(0)?;
+        }
+
+        for string in &self.strings {
+            let length = u8::try_from(string.len()).map_err(|_| WireError::StringTooLong(string.len()))?;
+            bytes.write_u8(length)?;
+            bytes.extend(string);
         }
 
-        let message = String::from_utf8_lossy(&buf).to_string();
-        Ok(TXT { message })
+        Ok(())
+    }
+}
+
+impl fmt::Display for TXT {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.strings.is_empty() {
+            return write!(f, "\"\"");
+        }
+
+        for (index, string) in self.strings.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+
+            write_character_string(f, string)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for TXT {
+    type Err = PresentationError;
+
+    /// Each quoted string becomes one of the record’s strings. Any longer
+    /// than a character-string can be get split up, the same way as in
+    /// `from_text`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::new(input)?;
+        let mut strings = Vec::new();
+
+        loop {
+            let string = fields.character_string("text")?;
+            strings.extend(TXT::from_text(string).strings);
+
+            if fields.is_empty() {
+                break;
+            }
+        }
+
+        Ok(Self { strings })
     }
 }
 
@@ -65,9 +161,19 @@ mod test {
     fn parses() {
         let buf = &[ 0x06, 0x74, 0x78, 0x74, 0x20, 0x6d, 0x65 ];
 
-        assert_eq!(TXT::read(9, &mut Cursor::new(buf)).unwrap(),
+        assert_eq!(TXT::read(buf.len() as _, &mut Cursor::new(buf)).unwrap(),
                    TXT {
-                       message: String::from("txt me"),
+                       strings: vec![ b"txt me".to_vec() ],
+                   });
+    }
+
+    #[test]
+    fn parses_several_strings() {
+        let buf = &[ 0x03, 0x61, 0x62, 0x63,  0x00,  0x02, 0x64, 0x65 ];
+
+        assert_eq!(TXT::read(buf.len() as _, &mut Cursor::new(buf)).unwrap(),
+                   TXT {
+                       strings: vec![ b"abc".to_vec(), Vec::new(), b"de".to_vec() ],
                    });
     }
 
@@ -76,4 +182,88 @@ mod test {
         assert_eq!(TXT::read(0, &mut Cursor::new(&[])),
                    Err(WireError::IO));
     }
+
+    #[test]
+    fn round_trip() {
+        let buf = &[ 0x06, 0x74, 0x78, 0x74, 0x20, 0x6d, 0x65,  0x01, 0x21 ];
+        let record = TXT::read(buf.len() as _, &mut Cursor::new(buf)).unwrap();
+
+        let mut bytes = Vec::new();
+        record.write(&mut bytes).unwrap();
+        assert_eq!(bytes, buf);
+    }
+
+    #[test]
+    fn long_round_trip() {
+        for len in &[ 0, 254, 255, 256, 510 ] {
+            let record = TXT::from_text("x".repeat(*len));
+
+            let mut bytes = Vec::new();
+            record.write(&mut bytes).unwrap();
+            assert_eq!(TXT::read(bytes.len() as u16, &mut Cursor::new(&bytes[..])).unwrap(),
+                       record);
+        }
+    }
+
+    #[test]
+    fn string_too_long() {
+        let record = TXT { strings: vec![ vec![ b'x'; 256 ] ] };
+        assert_eq!(record.write(&mut Vec::new()),
+                   Err(WireError::StringTooLong(256)));
+    }
+
+    #[test]
+    fn writes_no_strings() {
+        let mut bytes = Vec::new();
+        TXT { strings: Vec::new() }.write(&mut bytes).unwrap();
+        assert_eq!(bytes, &[ 0x00 ]);
+    }
+
+    #[test]
+    fn splits_text() {
+        assert_eq!(TXT::from_text("a".repeat(300)).strings,
+                   vec![ vec![ b'a'; 255 ], vec![ b'a'; 45 ] ]);
+    }
+
+    #[test]
+    fn joins() {
+        let record = TXT { strings: vec![ b"v=DKIM1; k=rsa; ".to_vec(), b"p=MIGf".to_vec() ] };
+        assert_eq!(record.join(), b"v=DKIM1; k=rsa; p=MIGf".to_vec());
+    }
+
+    #[test]
+    fn decodes_lossily() {
+        let record = TXT { strings: vec![ b"woof".to_vec(), vec![ 0x62, 0xff ] ] };
+        assert_eq!(record.as_utf8_lossy(), vec![ "woof", "b\u{fffd}" ]);
+    }
+
+    #[test]
+    fn displays() {
+        let record = TXT::from_text("say \"woof\"\n");
+        assert_eq!(record.to_string(), "\"say \\\"woof\\\"\\010\"");
+    }
+
+    #[test]
+    fn displays_empty() {
+        let record = TXT::from_text("");
+        assert_eq!(record.to_string(), "\"\"");
+    }
+
+    #[test]
+    fn displays_segments() {
+        let record = TXT { strings: vec![ b"v=spf1 ".to_vec(), b"-all".to_vec() ] };
+        assert_eq!(record.to_string(), "\"v=spf1 \" \"-all\"");
+    }
+
+    #[test]
+    fn displays_long() {
+        let record = TXT::from_text("a".repeat(300));
+        assert_eq!(record.to_string(), format!("\"{}\" \"{}\"", "a".repeat(255), "a".repeat(45)));
+    }
+
+    #[test]
+    fn parses_presentation() {
+        assert_eq!(r#""v=spf1 " "-all""#.parse::<TXT>(),
+                   Ok(TXT { strings: vec![ b"v=spf1 ".to_vec(), b"-all".to_vec() ] }));
+    }
 }
@@ -0,0 +1,200 @@
+//! Serde support for responses and records, behind the `serde` feature.
+//!
+//! Most types derive their implementations, which are set up in the files
+//! that define them; this module has the ones written by hand, and the
+//! helpers that fields use with `#[serde(with = …)]`. Names, classes, and
+//! error codes become strings; public keys and signatures become base64,
+//! and digests become hex, as they are written in zone files.
+//!
+//! Nothing here asks the format what type is coming next, so everything
+//! can be read back from formats that cannot say, such as bincode.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::ser::SerializeStruct;
+
+use crate::name::Name;
+use crate::presentation::{decode_base64, decode_hex, parse_type_mnemonic};
+use crate::record::{CustomRecord, UnknownQtype, TXT};
+use crate::types::*;
+
+
+/// Reads a string, and parses it with the given function, failing with a
+/// message that says what it was meant to be.
+fn parse_string<'de, D, T>(deserializer: D, what: &str, parse: impl FnOnce(&str) -> Option<T>) -> Result<T, D::Error>
+where D: Deserializer<'de>
+{
+    let text = String::deserialize(deserializer)?;
+    parse(&text).ok_or_else(|| D::Error::custom(format!("invalid {} {:?}", what, text)))
+}
+
+
+// ---- names, classes, and error codes ----
+
+impl Serialize for Name {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        parse_string(deserializer, "domain name", |text| text.parse().ok())
+    }
+}
+
+impl Serialize for QClass {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for QClass {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        parse_string(deserializer, "class", |text| text.parse().ok())
+    }
+}
+
+/// Error codes use their mnemonics from the IANA registry, with numbers
+/// for the ones we do not know the name of.
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.mnemonic() {
+            Some(mnemonic)  => serializer.serialize_str(mnemonic),
+            None            => serializer.collect_str(&self.to_bits()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        parse_string(deserializer, "error code", |text| text.parse().ok())
+    }
+}
+
+/// Unknown record types are their type number, whether or not dog knows
+/// the name of the type.
+impl Serialize for UnknownQtype {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.type_number() {
+            Some(number)  => serializer.serialize_u16(number),
+            None          => Err(S::Error::custom(format!("unknown record type {:?}", self.to_string()))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownQtype {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u16::deserialize(deserializer).map(Self::from)
+    }
+}
+
+
+// ---- record data ----
+
+/// Records of types from a registry can only be read back with that
+/// registry, which serde has no way to pass along, so they get written
+/// with their type number and data, and cannot be read back at all.
+pub(crate) fn custom_record<S: Serializer>(custom: &CustomRecord, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut fields = serializer.serialize_struct("CustomRecord", 2)?;
+    fields.serialize_field("type_number", &custom.type_number())?;
+    fields.serialize_field("bytes", &crate::presentation::base64(custom.bytes()))?;
+    fields.end()
+}
+
+/// Binary data that gets written as base64, such as public keys.
+pub(crate) mod base64_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::presentation::base64(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        parse_string(deserializer, "base64", decode_base64)
+    }
+}
+
+/// Binary data that gets written as hex, such as digests.
+pub(crate) mod hex_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::presentation::hex(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        parse_string(deserializer, "hex", decode_hex)
+    }
+}
+
+/// Record type numbers that get written as their mnemonic, or as
+/// `TYPE123` if they do not have one.
+pub(crate) mod type_name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(type_number: &TypeInt, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::presentation::type_mnemonic(*type_number))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TypeInt, D::Error> {
+        parse_string(deserializer, "record type", parse_type_mnemonic)
+    }
+}
+
+
+/// How the strings in a TXT record get written. They are usually text, but
+/// nothing stops them holding any bytes at all, so a record with a string
+/// that is not valid UTF-8 has all its strings written as base64 instead,
+/// so that nothing gets lost.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TxtStrings {
+    encoding: TxtEncoding,
+    strings: Vec<String>,
+}
+
+/// Which of the two ways the strings in a TXT record were written.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+enum TxtEncoding {
+    Text,
+    Base64,
+}
+
+impl From<TXT> for TxtStrings {
+    fn from(txt: TXT) -> Self {
+        match txt.strings.iter().map(|s| String::from_utf8(s.clone())).collect::<Result<Vec<_>, _>>() {
+            Ok(strings) => Self { encoding: TxtEncoding::Text, strings },
+            Err(_) => {
+                let strings = txt.strings.iter().map(|s| crate::presentation::base64(s)).collect();
+                Self { encoding: TxtEncoding::Base64, strings }
+            }
+        }
+    }
+}
+
+impl TryFrom<TxtStrings> for TXT {
+    type Error = InvalidBase64;
+
+    fn try_from(form: TxtStrings) -> Result<Self, Self::Error> {
+        let strings = match form.encoding {
+            TxtEncoding::Text    => form.strings.into_iter().map(String::into_bytes).collect(),
+            TxtEncoding::Base64  => form.strings.iter().map(|s| decode_base64(s).ok_or_else(|| InvalidBase64(s.clone()))).collect::<Result<_, _>>()?,
+        };
+
+        Ok(Self { strings })
+    }
+}
+
+/// A string in a TXT record that was meant to be base64 but was not.
+pub(crate) struct InvalidBase64(String);
+
+impl fmt::Display for InvalidBase64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid base64 {:?}", self.0)
+    }
+}
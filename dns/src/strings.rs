@@ -1,10 +1,12 @@
 //! Reading strings from the DNS wire protocol.
 
-use std::io::{self, Write};
+use std::collections::HashMap;
+use std::io::Write;
 
 use log::debug;
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::WriteBytesExt;
 
+use crate::name::Name;
 use crate::wire::*;
 
 
@@ -12,15 +14,23 @@ use crate::wire::*;
 /// from DNS packets.
 pub(crate) trait ReadLabels {
 
-    /// Read and expand a compressed domain name.
-    fn read_labels(&mut self) -> Result<String, WireError>;
+    /// Read and expand a compressed domain name, following no more
+    /// pointers and producing no longer a name than the options allow.
+    fn read_labels_with(&mut self, options: &ParseOptions) -> Result<Name, WireError>;
 }
 
 impl ReadLabels for Cursor<&[u8]> {
-    fn read_labels(&mut self) -> Result<String, WireError> {
+    fn read_labels_with(&mut self, options: &ParseOptions) -> Result<Name, WireError> {
+        let mut labels = Labels::new(self.get_ref(), self.position() as usize, options);
         let mut name_buf = Vec::new();
-        read_string_recursive(&mut name_buf, self, &mut Vec::new())?;
-        Ok(String::from_utf8_lossy(&*name_buf).to_string())
+
+        for label in &mut labels {
+            name_buf.extend_from_slice(label?);
+            name_buf.push(b'.');
+        }
+
+        self.set_position(labels.end().expect("Name read without an end") as u64);
+        Ok(Name::unchecked(String::from_utf8_lossy(&name_buf).to_string()))
     }
 }
 
@@ -35,18 +45,21 @@ pub(crate) trait WriteLabels {
     /// whole thing ending with a segment of zero length.
     ///
     /// So “dns.lookup.dog” would be encoded as:
-    /// “3, dns, 6, lookup, 3, dog, 0”.
-    fn write_labels(&mut self, input: &str) -> io::Result<()>;
+    /// “3, dns, 6, lookup, 3, dog, 0”. A trailing dot is ignored, so the
+    /// root zone, “.”, is encoded as a single zero.
+    ///
+    /// Names with a label longer than 63 bytes, or that would take up more
+    /// than 255 bytes once encoded, cannot be represented, so they are
+    /// rejected before anything gets written.
+    fn write_labels(&mut self, input: &Name) -> Result<(), WireError>;
 }
 
 impl<W: Write> WriteLabels for W {
-    fn write_labels(&mut self, input: &str) -> io::Result<()> {
-        for label in input.split('.') {
-            self.write_u8(label.len() as u8)?;
+    fn write_labels(&mut self, input: &Name) -> Result<(), WireError> {
+        let labels = checked_labels(input)?;
 
-            for b in label.as_bytes() {
-                self.write_u8(*b)?;
-            }
+        for label in labels {
+            write_label(self, label)?;
         }
 
         self.write_u8(0)?;  // terminate the string
@@ -55,49 +68,279 @@ impl<W: Write> WriteLabels for W {
 }
 
 
-const RECURSION_LIMIT: usize = 8;
+/// A dictionary of the names that have already been written to a message,
+/// and where, so that later names ending in the same labels can be written
+/// as a pointer back to the earlier name instead (RFC 1035 §4.1.4).
+///
+/// Suffixes are matched exactly, rather than case-insensitively, so that
+/// the case of every name in the message stays the way it was written.
+#[derive(Debug, Default)]
+pub(crate) struct NameCompressor {
+    enabled: bool,
+    offsets: HashMap<String, u16>,
+}
+
+impl NameCompressor {
 
-fn read_string_recursive(name_buf: &mut Vec<u8>, c: &mut Cursor<&[u8]>, recursions: &mut Vec<u16>) -> Result<(), WireError> {
-    loop {
-        let byte = c.read_u8()?;
+    /// Creates a new compressor with an empty dictionary. If compression
+    /// is not enabled, every name gets written out in full.
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, offsets: HashMap::new() }
+    }
 
-        if byte == 0 {
-            break;
+    /// Writes a domain name to the end of the message being built up in
+    /// the given buffer, compressing it if any of its suffixes have already
+    /// been written, and remembering its own suffixes for later names.
+    pub fn write_name(&mut self, bytes: &mut Vec<u8>, input: &Name) -> Result<(), WireError> {
+        if ! self.enabled {
+            return bytes.write_labels(input);
         }
 
-        else if byte >= 0b_1100_0000 {
-            if recursions.len() >= RECURSION_LIMIT {
-                return Err(WireError::TooMuchRecursion(recursions.clone()));
+        let labels = checked_labels(input)?;
+
+        for index in 0 .. labels.len() {
+            let suffix = labels[index ..].join(".");
+
+            if let Some(offset) = self.offsets.get(&suffix) {
+                debug!("Compressing {:?} to offset {}", suffix, offset);
+                bytes.write_u16::<BigEndian>(0b_1100_0000_0000_0000 | offset)?;
+                return Ok(());
             }
 
-            let name_one = byte - 0b1100_0000;
-            let name_two = c.read_u8()?;
-            let offset = u16::from_be_bytes([name_one, name_two]);
+            // Pointers only have fourteen bits for the offset, so names
+            // further into the message than that can’t be pointed to.
+            if bytes.len() <= MAX_POINTER_OFFSET {
+                self.offsets.insert(suffix, bytes.len() as u16);
+            }
+
+            write_label(bytes, labels[index])?;
+        }
+
+        bytes.write_u8(0)?;  // terminate the string
+        Ok(())
+    }
+}
+
+
+/// Splits a name into its labels, making sure that neither the labels nor
+/// the whole name are too long to be encoded.
+fn checked_labels(input: &Name) -> Result<Vec<&str>, WireError> {
+    input.check()?;
+    Ok(input.labels().collect())
+}
+
+fn write_label<W: Write>(w: &mut W, label: &str) -> Result<(), WireError> {
+    w.write_u8(label.len() as u8)?;
+
+    for b in label.as_bytes() {
+        w.write_u8(*b)?;
+    }
+
+    Ok(())
+}
 
-            debug!("Backtracking to offset {}", offset);
-            let new_pos = c.position();
-            c.set_position(u64::from(offset));
-            recursions.push(offset);
 
-            read_string_recursive(name_buf, c, recursions)?;
+/// How many compression pointers a name can go through by default.
+pub(crate) const RECURSION_LIMIT: usize = 8;
 
-            debug!("Coming back to {}", new_pos);
-            c.set_position(new_pos);
-            recursions.pop();
-            break;
+/// The furthest into a message that a compression pointer can point.
+const MAX_POINTER_OFFSET: usize = 0b_0011_1111_1111_1111;
+
+/// An iterator over the labels of a name in a message, following any
+/// compression pointers on the way, and stopping after the first error.
+/// This is the one place names get decompressed: reading an owned name
+/// copies the labels out of it, and a borrowed name just hands them out.
+pub(crate) struct Labels<'a> {
+    message: &'a [u8],
+    position: usize,
+
+    /// Where the name stops in the message, after its terminating zero or
+    /// the first pointer in it, once that has been read.
+    end: Option<usize>,
+
+    /// The offsets of the pointers that have been followed so far.
+    pointers: Vec<u16>,
+    max_pointer_jumps: usize,
+    max_name_length: usize,
+
+    /// How many bytes the name would take up uncompressed so far,
+    /// including the terminating zero.
+    encoded_len: usize,
+    finished: bool,
+}
+
+impl<'a> Labels<'a> {
+
+    /// Starts reading the name at the given offset in the message, with
+    /// the limits in the given options.
+    pub(crate) fn new(message: &'a [u8], offset: usize, options: &ParseOptions) -> Self {
+        Self {
+            message,
+            position: offset,
+            end: None,
+            pointers: Vec::new(),
+            max_pointer_jumps: options.max_pointer_jumps,
+            max_name_length: options.max_name_length.min(Name::MAX_NAME_LENGTH),
+            encoded_len: 1,
+            finished: false,
         }
+    }
+
+    /// Where the name stops in the message, which is where whatever comes
+    /// after it starts. This is only known once every label has been read.
+    pub(crate) fn end(&self) -> Option<usize> {
+        self.end
+    }
 
-        // Otherwise, treat the byte as the length of a label, and read that
-        // many characters.
-        else {
-            for _ in 0 .. byte {
-                let c = c.read_u8()?;
-                name_buf.push(c);
+    /// Reads the next label, following any compression pointers on the
+    /// way, or returns `None` at the end of the name.
+    fn read_label(&mut self) -> Result<Option<&'a [u8]>, WireError> {
+        loop {
+            let byte = *self.message.get(self.position).ok_or(WireError::IO)?;
+
+            if byte == 0 {
+                self.end.get_or_insert(self.position + 1);
+                return Ok(None);
             }
 
-            name_buf.push(b'.');
+            else if byte >= 0b_1100_0000 {
+                if self.pointers.len() >= self.max_pointer_jumps {
+                    return Err(WireError::TooMuchRecursion(self.pointers.clone()));
+                }
+
+                let name_two = *self.message.get(self.position + 1).ok_or(WireError::IO)?;
+                let offset = u16::from_be_bytes([byte - 0b_1100_0000, name_two]);
+
+                debug!("Backtracking to offset {}", offset);
+                self.end.get_or_insert(self.position + 2);
+                self.pointers.push(offset);
+                self.position = usize::from(offset);
+            }
+
+            else if usize::from(byte) > Name::MAX_LABEL_LENGTH {
+                return Err(WireError::LabelTooLong(usize::from(byte)));
+            }
+
+            // Otherwise, treat the byte as the length of a label, and read
+            // that many characters.
+            else {
+                let start = self.position + 1;
+                let label = self.message.get(start .. start + usize::from(byte)).ok_or(WireError::IO)?;
+                self.position = start + label.len();
+
+                // Each label takes up as many bytes as it does on the wire
+                // with its length, plus one for the terminating zero.
+                self.encoded_len += label.len() + 1;
+                if self.encoded_len > self.max_name_length {
+                    return Err(WireError::NameTooLong(self.encoded_len));
+                }
+
+                return Ok(Some(label));
+            }
         }
     }
+}
 
-    Ok(())
+impl<'a> Iterator for Labels<'a> {
+    type Item = Result<&'a [u8], WireError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.read_label();
+        if ! matches!(result, Ok(Some(_))) {
+            self.finished = true;
+        }
+
+        result.transpose()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn name(input: &str) -> Name {
+        Name::unchecked(input.into())
+    }
+
+    #[test]
+    fn write_name() {
+        let mut bytes = Vec::new();
+        bytes.write_labels(&name("dns.lookup.dog.")).unwrap();
+        assert_eq!(bytes, b"\x03dns\x06lookup\x03dog\x00");
+    }
+
+    #[test]
+    fn write_label_too_long() {
+        let input = format!("{}.dog", "a".repeat(64));
+        assert_eq!(Vec::new().write_labels(&name(&input)), Err(WireError::LabelTooLong(64)));
+    }
+
+    #[test]
+    fn write_name_too_long() {
+        let input = vec![ "a".repeat(63); 4 ].join(".");
+        assert_eq!(Vec::new().write_labels(&name(&input)), Err(WireError::NameTooLong(257)));
+    }
+
+    #[test]
+    fn compressed_suffixes() {
+        let mut bytes = vec![ 0xFF; 12 ];
+        let mut compressor = NameCompressor::new(true);
+        compressor.write_name(&mut bytes, &name("dns.lookup.dog")).unwrap();
+        compressor.write_name(&mut bytes, &name("www.lookup.dog")).unwrap();
+        compressor.write_name(&mut bytes, &name("dns.lookup.dog")).unwrap();
+
+        assert_eq!(&bytes[12 ..], &b"\x03dns\x06lookup\x03dog\x00\x03www\xc0\x10\xc0\x0c"[..]);
+
+        let mut c = Cursor::new(&bytes[..]);
+        c.set_position(28);
+        assert_eq!(c.read_labels_with(&ParseOptions::default()), Ok(name("www.lookup.dog.")));
+    }
+
+    #[test]
+    fn compression_disabled() {
+        let mut bytes = Vec::new();
+        let mut compressor = NameCompressor::new(false);
+        compressor.write_name(&mut bytes, &name("lookup.dog")).unwrap();
+        compressor.write_name(&mut bytes, &name("lookup.dog")).unwrap();
+
+        assert_eq!(bytes, b"\x06lookup\x03dog\x00\x06lookup\x03dog\x00");
+    }
+
+    #[test]
+    fn compression_is_case_sensitive() {
+        let mut bytes = Vec::new();
+        let mut compressor = NameCompressor::new(true);
+        compressor.write_name(&mut bytes, &name("lookup.dog")).unwrap();
+        compressor.write_name(&mut bytes, &name("LOOKUP.DOG")).unwrap();
+
+        assert_eq!(bytes, b"\x06lookup\x03dog\x00\x06LOOKUP\x03DOG\x00");
+    }
+
+    #[test]
+    fn read_label_too_long() {
+        let mut bytes = vec![ 64 ];
+        bytes.extend(&[ b'a'; 64 ]);
+        bytes.push(0);
+
+        let mut c = Cursor::new(&bytes[..]);
+        assert_eq!(c.read_labels_with(&ParseOptions::default()), Err(WireError::LabelTooLong(64)));
+    }
+
+    #[test]
+    fn read_name_too_long() {
+        let mut bytes = Vec::new();
+        for _ in 0 .. 5 {
+            bytes.push(63);
+            bytes.extend(&[ b'a'; 63 ]);
+        }
+        bytes.push(0);
+
+        let mut c = Cursor::new(&bytes[..]);
+        assert_eq!(c.read_labels_with(&ParseOptions::default()), Err(WireError::NameTooLong(257)));
+    }
 }
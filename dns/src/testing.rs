@@ -0,0 +1,298 @@
+//! A nameserver that runs in the same process, for testing code that
+//! sends requests over the network without needing a real one.
+//!
+//! This module is only available with the `testing` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::net::UdpSocket;
+//! use dns::{Request, Response, Flags, Query, QClass, Answer, qtype};
+//! use dns::record::{Record, A};
+//! use dns::testing::{MockServer, response_to};
+//!
+//! let server = MockServer::reply_with(|request| {
+//!     let mut response = response_to(request);
+//!     let record = Record::A(A { address: "127.0.0.1".parse().unwrap() });
+//!     response.answers.push(Answer::Standard { qname: request.queries[0].qname.clone(), qclass: QClass::IN, ttl: 60, record });
+//!     response
+//! }).unwrap();
+//!
+//! let request = Request {
+//!     transaction_id: 0xABCD,
+//!     flags: Flags::query(),
+//!     queries: vec![ Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: qtype!(A) } ],
+//!     additional: None,
+//! };
+//!
+//! let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+//! socket.send_to(&request.to_bytes().unwrap(), server.address()).unwrap();
+//!
+//! let mut buf = [0; 512];
+//! let len = socket.recv(&mut buf).unwrap();
+//! let response = Response::from_bytes(&buf[.. len]).unwrap();
+//! assert_eq!(response.answers.len(), 1);
+//! assert_eq!(server.requests(), vec![ request ]);
+//! ```
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket, TcpListener, TcpStream, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::*;
+
+use crate::types::*;
+use crate::wire::{read_tcp_message, write_tcp_message};
+
+
+/// How often the server’s threads check whether it has been dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How many times to try finding a port that is free for both UDP and TCP
+/// before giving up.
+const BIND_ATTEMPTS: usize = 16;
+
+
+/// A nameserver that listens on a random port on `127.0.0.1`, over both
+/// UDP and TCP, and answers each request it receives with whatever a
+/// function returns for it. It keeps running, in background threads,
+/// until it gets dropped.
+pub struct MockServer {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+    stopped: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// The function that works out the response to each request. Returning
+/// `None` means no response gets sent, and, over TCP, that the
+/// connection gets closed.
+type Responder = dyn Fn(&Request) -> Option<Response> + Send + Sync;
+
+impl MockServer {
+
+    /// Starts a server that answers every request with the response the
+    /// function returns for it.
+    pub fn reply_with(responder: impl Fn(&Request) -> Response + Send + Sync + 'static) -> io::Result<Self> {
+        Self::reply_or_ignore(move |request| Some(responder(request)))
+    }
+
+    /// Starts a server that answers requests with the response the
+    /// function returns for them, and ignores the ones it returns `None`
+    /// for, which is useful for testing timeouts.
+    pub fn reply_or_ignore(responder: impl Fn(&Request) -> Option<Response> + Send + Sync + 'static) -> io::Result<Self> {
+        let (udp_socket, tcp_listener) = bind_both()?;
+        let address = udp_socket.local_addr()?;
+        udp_socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        tcp_listener.set_nonblocking(true)?;
+
+        let responder: Arc<Responder> = Arc::new(responder);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let udp_thread = {
+            let (responder, requests, stopped) = (Arc::clone(&responder), Arc::clone(&requests), Arc::clone(&stopped));
+            thread::spawn(move || serve_udp(&udp_socket, &*responder, &requests, &stopped))
+        };
+
+        let tcp_thread = {
+            let (responder, requests, stopped) = (Arc::clone(&responder), Arc::clone(&requests), Arc::clone(&stopped));
+            thread::spawn(move || serve_tcp(&tcp_listener, &*responder, &requests, &stopped))
+        };
+
+        debug!("Mock server listening on {}", address);
+        Ok(Self { address, requests, stopped, threads: vec![ udp_thread, tcp_thread ] })
+    }
+
+    /// The address the server is listening on, which is the same for both
+    /// UDP and TCP.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns every request the server has received so far, over either
+    /// protocol, in the order they arrived.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+
+/// Creates an empty response to a request, with the same transaction ID
+/// and queries, and the flags a recursive nameserver would send back, for
+/// a responder to add records to.
+pub fn response_to(request: &Request) -> Response {
+    let mut flags = request.flags;
+    flags.response = true;
+    flags.recursion_available = true;
+
+    Response {
+        transaction_id: request.transaction_id,
+        flags,
+        queries: request.queries.clone(),
+        answers: Vec::new(),
+        authorities: Vec::new(),
+        additionals: Vec::new(),
+    }
+}
+
+
+/// Binds a UDP socket and a TCP listener to the same random port, trying
+/// again if the port the UDP socket got is already taken for TCP.
+fn bind_both() -> io::Result<(UdpSocket, TcpListener)> {
+    let mut last_error = None;
+
+    for _ in 0 .. BIND_ATTEMPTS {
+        let udp_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        match TcpListener::bind(udp_socket.local_addr()?) {
+            Ok(tcp_listener)  => return Ok((udp_socket, tcp_listener)),
+            Err(e)            => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::AddrInUse, "No free port")))
+}
+
+/// Works out the bytes to send back in response to a message, recording
+/// the request if it could be read.
+fn respond(message: &[u8], responder: &Responder, requests: &Mutex<Vec<Request>>) -> Option<Vec<u8>> {
+    let request = match Request::from_bytes(message) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Mock server received malformed request: {:?}", e);
+            return None;
+        }
+    };
+
+    let response = responder(&request);
+    requests.lock().unwrap().push(request);
+
+    match response?.to_bytes() {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            warn!("Mock server failed to encode response: {:?}", e);
+            None
+        }
+    }
+}
+
+fn serve_udp(socket: &UdpSocket, responder: &Responder, requests: &Mutex<Vec<Request>>, stopped: &AtomicBool) {
+    let mut buf = vec![0; 4096];
+
+    while ! stopped.load(Ordering::Relaxed) {
+        let (len, client) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => {
+                warn!("Mock server failed to receive: {}", e);
+                continue;
+            }
+        };
+
+        if let Some(bytes) = respond(&buf[.. len], responder, requests) {
+            if let Err(e) = socket.send_to(&bytes, client) {
+                warn!("Mock server failed to send response to {}: {}", client, e);
+            }
+        }
+    }
+}
+
+fn serve_tcp(listener: &TcpListener, responder: &Responder, requests: &Mutex<Vec<Request>>, stopped: &AtomicBool) {
+    while ! stopped.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, client)) => {
+                if let Err(e) = serve_tcp_connection(stream, responder, requests) {
+                    debug!("Mock server connection from {} ended: {}", client, e);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(e) => {
+                warn!("Mock server failed to accept connection: {}", e);
+            }
+        }
+    }
+}
+
+/// Answers every length-prefixed message sent down the connection, one
+/// at a time, until the client closes it.
+fn serve_tcp_connection(mut stream: TcpStream, responder: &Responder, requests: &Mutex<Vec<Request>>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+
+    while let Some(message) = read_tcp_message(&mut stream)? {
+        match respond(&message, responder, requests) {
+            Some(bytes) => write_tcp_message(&mut stream, &bytes)?,
+            None        => return Ok(()),
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(transaction_id: u16) -> Request {
+        Request {
+            transaction_id,
+            flags: Flags::query(),
+            queries: vec![ Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 } ],
+            additional: None,
+        }
+    }
+
+    #[test]
+    fn udp() {
+        let server = MockServer::reply_with(response_to).unwrap();
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        socket.send_to(&request(0x1234).to_bytes().unwrap(), server.address()).unwrap();
+
+        let mut buf = [0; 512];
+        let len = socket.recv(&mut buf).unwrap();
+        let response = Response::from_bytes(&buf[.. len]).unwrap();
+        assert_eq!(response.transaction_id, 0x1234);
+        assert!(response.flags.response);
+    }
+
+    #[test]
+    fn tcp() {
+        let server = MockServer::reply_with(response_to).unwrap();
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+
+        for transaction_id in 1 ..= 2 {
+            write_tcp_message(&mut stream, &request(transaction_id).to_bytes().unwrap()).unwrap();
+            let bytes = read_tcp_message(&mut stream).unwrap().unwrap();
+            assert_eq!(Response::from_bytes(&bytes).unwrap().transaction_id, transaction_id);
+        }
+
+        assert_eq!(server.requests(), vec![ request(1), request(2) ]);
+    }
+
+    #[test]
+    fn ignored() {
+        let server = MockServer::reply_or_ignore(|_| None).unwrap();
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        socket.send_to(&request(0x1234).to_bytes().unwrap(), server.address()).unwrap();
+
+        let mut buf = [0; 512];
+        assert!(socket.recv(&mut buf).is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+//! Picking the transaction IDs of requests.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+
+/// A source of transaction IDs, which gets asked for a new one for each
+/// request that gets sent.
+///
+/// IDs are normally picked at random, as that makes it harder for someone
+/// to spoof a response. Tests that need the same requests every time they
+/// run can use one of the predictable sources instead, or their own.
+///
+/// # Examples
+///
+/// ```
+/// use dns::{TxidGenerator, SeededTxids, SequentialTxids};
+///
+/// let first = SeededTxids::new(42);
+/// let second = SeededTxids::new(42);
+/// assert_eq!(first.next_txid(), second.next_txid());
+///
+/// let sequence = SequentialTxids::new(0xFFFF);
+/// assert_eq!(sequence.next_txid(), 0xFFFF);
+/// assert_eq!(sequence.next_txid(), 0x0000);
+/// ```
+pub trait TxidGenerator: Send + Sync {
+
+    /// Returns the transaction ID to use for the next request.
+    fn next_txid(&self) -> u16;
+}
+
+/// Picks each transaction ID at random from the thread’s random number
+/// generator, which is what gets used unless something else is asked for.
+#[derive(PartialEq, Debug, Default, Copy, Clone)]
+pub struct RandomTxids;
+
+/// Picks transaction IDs from a random number generator seeded with a
+/// fixed value, so the same seed always gives the same IDs in the same
+/// order, at least with the same version of this crate.
+#[derive(Debug)]
+pub struct SeededTxids {
+    rng: Mutex<StdRng>,
+}
+
+/// Counts up from a starting transaction ID, wrapping around to zero
+/// after reaching the highest one.
+#[derive(Debug)]
+pub struct SequentialTxids {
+    next: AtomicU16,
+}
+
+
+impl TxidGenerator for RandomTxids {
+    fn next_txid(&self) -> u16 {
+        rand::random()
+    }
+}
+
+impl SeededTxids {
+
+    /// Creates a new source of transaction IDs from the given seed.
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Mutex::new(StdRng::seed_from_u64(seed)) }
+    }
+}
+
+impl TxidGenerator for SeededTxids {
+    fn next_txid(&self) -> u16 {
+        self.rng.lock().expect("Transaction ID lock poisoned").gen()
+    }
+}
+
+impl SequentialTxids {
+
+    /// Creates a new source of transaction IDs that starts at the given
+    /// one.
+    pub fn new(start: u16) -> Self {
+        Self { next: AtomicU16::new(start) }
+    }
+}
+
+impl TxidGenerator for SequentialTxids {
+    fn next_txid(&self) -> u16 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn take(txids: &dyn TxidGenerator, count: usize) -> Vec<u16> {
+        (0 .. count).map(|_| txids.next_txid()).collect()
+    }
+
+    #[test]
+    fn same_seed() {
+        assert_eq!(take(&SeededTxids::new(1234), 8), take(&SeededTxids::new(1234), 8));
+    }
+
+    #[test]
+    fn different_seeds() {
+        assert_ne!(take(&SeededTxids::new(1234), 8), take(&SeededTxids::new(4321), 8));
+    }
+
+    #[test]
+    fn sequence() {
+        assert_eq!(take(&SequentialTxids::new(0x1234), 3), vec![ 0x1234, 0x1235, 0x1236 ]);
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        assert_eq!(take(&SequentialTxids::new(0xFFFE), 3), vec![ 0xFFFE, 0xFFFF, 0x0000 ]);
+    }
+}
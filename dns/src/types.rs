@@ -3,6 +3,11 @@
 //! with the request packet having zero answer fields, and the response packet
 //! having at least one record in its answer fields.
 
+use std::fmt;
+use std::str::FromStr;
+
+use crate::name::Name;
+use crate::presentation::write_name;
 use crate::record::{Record, OPT};
 
 
@@ -27,6 +32,7 @@ pub struct Request {
 
 /// A response obtained from a DNS server.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Response {
 
     /// The transaction ID, which should match the ID of the request.
@@ -51,30 +57,37 @@ pub struct Response {
 
 /// A DNS query section.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Query {
 
     /// The domain name being queried, in human-readable dotted notation.
-    pub qname: String,
+    #[cfg_attr(feature = "serde", serde(rename = "name"))]
+    pub qname: Name,
 
     /// The class number.
+    #[cfg_attr(feature = "serde", serde(rename = "class"))]
     pub qclass: QClass,
 
     /// The type number.
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
     pub qtype: TypeInt,
 }
 
 
 /// A DNS answer section.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Answer {
 
     /// This is a standard answer with every field.
     Standard {
 
         /// The domain name being answered for.
-        qname: String,
+        #[cfg_attr(feature = "serde", serde(rename = "name"))]
+        qname: Name,
 
         /// This answer’s class.
+        #[cfg_attr(feature = "serde", serde(rename = "class"))]
         qclass: QClass,
 
         /// The time-to-live duration, in seconds.
@@ -89,7 +102,8 @@ pub enum Answer {
     Pseudo {
 
         /// The domain name being answered for.
-        qname: String,
+        #[cfg_attr(feature = "serde", serde(rename = "name"))]
+        qname: Name,
 
         /// The OPT record contained in this answer.
         opt: OPT,
@@ -123,6 +137,7 @@ pub type TypeInt = u16;
 
 /// The flags that accompany every DNS packet.
 #[derive(PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Flags {
 
     /// Whether this packet is a response packet.
@@ -151,6 +166,12 @@ pub struct Flags {
     /// checking for the request’s queries.
     pub checking_disabled: bool,
 
+    /// The `Z` bit, which is reserved and should always be zero. It gets
+    /// kept rather than thrown away so that anything in the middle that
+    /// sets it can be spotted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reserved: bool,
+
     /// In a response, a code indicating an error if one occurred.
     pub error_code: Option<ErrorCode>,
 }
@@ -191,3 +212,111 @@ impl Answer {
         matches!(self, Self::Standard { .. })
     }
 }
+
+/// Standard answers are displayed as resource records in zone files are
+/// written: `name ttl class type data`. Pseudo-records do not have a
+/// presentation format, so they get displayed as a comment instead.
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard { qname, qclass, ttl, record } => {
+                write_name(f, qname)?;
+                write!(f, " {} {} {} {}", ttl, qclass, record.type_mnemonic(), record)
+            }
+            Self::Pseudo { qname, opt } => {
+                write!(f, "; ")?;
+                write_name(f, qname)?;
+                write!(f, " OPT {} {} {} {}", opt.udp_payload_size, opt.higher_bits, opt.edns0_version, opt.flags)
+            }
+        }
+    }
+}
+
+/// Classes are parsed from their mnemonics, in any case, or from the
+/// generic `CLASS` form.
+impl FromStr for QClass {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let upper = input.to_ascii_uppercase();
+
+        match upper.as_str() {
+            "IN"  => Ok(Self::IN),
+            "CH"  => Ok(Self::CH),
+            "HS"  => Ok(Self::HS),
+            _     => {
+                let number = upper.strip_prefix("CLASS").ok_or(())?;
+                number.parse().map(Self::from_u16).map_err(|_| ())
+            }
+        }
+    }
+}
+
+/// Classes are displayed by their mnemonics, or in the generic `CLASS`
+/// form for other numbers (RFC 3597 §5).
+impl fmt::Display for QClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IN         => write!(f, "IN"),
+            Self::CH         => write!(f, "CH"),
+            Self::HS         => write!(f, "HS"),
+            Self::Other(num) => write!(f, "CLASS{}", num),
+        }
+    }
+}
+
+
+/// The rcodes that have mnemonics in the IANA registry, by number. Zero
+/// means there was no error, so it has no `ErrorCode`, but it still has a
+/// mnemonic that gets parsed and displayed.
+const RCODE_MNEMONICS: [(u16, &str); 7] = [
+    ( 0, "NOERROR"),
+    ( 1, "FORMERR"),
+    ( 2, "SERVFAIL"),
+    ( 3, "NXDOMAIN"),
+    ( 4, "NOTIMP"),
+    ( 5, "REFUSED"),
+    (16, "BADVERS"),
+];
+
+impl ErrorCode {
+
+    /// Returns the mnemonic for this rcode, such as `NXDOMAIN`, or `None`
+    /// if it does not have one.
+    pub fn mnemonic(self) -> Option<&'static str> {
+        let number = self.to_bits();
+        RCODE_MNEMONICS.iter().find(|(n, _)| *n == number).map(|(_, mnemonic)| *mnemonic)
+    }
+
+    /// Returns the mnemonic for the rcode of a response, which is
+    /// `NOERROR` if it had none.
+    pub fn mnemonic_of(rcode: Option<Self>) -> Option<&'static str> {
+        match rcode {
+            Some(rcode)  => rcode.mnemonic(),
+            None         => Some(RCODE_MNEMONICS[0].1),
+        }
+    }
+
+    /// Parses the rcode of a response from its mnemonic, in any case, or
+    /// from its number. `NOERROR` and zero give `Some(None)`, as the
+    /// response had no error, and input that is not an rcode gives `None`.
+    pub fn parse_optional(input: &str) -> Option<Option<Self>> {
+        let number = match RCODE_MNEMONICS.iter().find(|(_, mnemonic)| mnemonic.eq_ignore_ascii_case(input)) {
+            Some((number, _))  => *number,
+            None               => input.parse::<u16>().ok().filter(|n| *n < 4096)?,
+        };
+
+        Some(Self::from_bits(number))
+    }
+}
+
+/// Error codes are parsed from their mnemonics, in any case, or from their
+/// numbers. `NOERROR` is not an error code, so it does not parse.
+impl FromStr for ErrorCode {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::parse_optional(input).flatten().ok_or(())
+    }
+}
+
@@ -3,19 +3,32 @@
 pub(crate) use std::io::Cursor;
 pub(crate) use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
+use std::convert::TryFrom;
+use std::fmt;
 use std::io;
-use log::{error, info, debug};
+use std::sync::Arc;
+use log::{error, warn, info, debug};
 
-use crate::record::{Record, OPT};
-use crate::strings::{ReadLabels, WriteLabels};
+use crate::idn::IdnError;
+use crate::name::Name;
+use crate::record::{Record, RecordRegistry, OPT, NAMED_TYPES};
+use crate::strings::{ReadLabels, NameCompressor, RECURSION_LIMIT};
 use crate::types::*;
 
 
 impl Request {
 
-    /// Converts this request to a vector of bytes.
-    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+    /// Converts this request to a vector of bytes. This fails if any of the
+    /// query names are too long to be encoded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WireError> {
+        self.to_bytes_with(WriteOptions::default())
+    }
+
+    /// Converts this request to a vector of bytes, writing it the way the
+    /// given options say to.
+    pub fn to_bytes_with(&self, options: WriteOptions) -> Result<Vec<u8>, WireError> {
         let mut bytes = Vec::with_capacity(32);
+        let mut compressor = NameCompressor::new(options.compress_names);
 
         bytes.write_u16::<BigEndian>(self.transaction_id)?;
         bytes.write_u16::<BigEndian>(self.flags.to_u16())?;
@@ -26,7 +39,7 @@ impl Request {
         bytes.write_u16::<BigEndian>(if self.additional.is_some() { 1 } else { 0 })?;  // additional RRs
 
         for query in &self.queries {
-            bytes.write_labels(&query.qname)?;
+            compressor.write_name(&mut bytes, &query.qname)?;
             bytes.write_u16::<BigEndian>(query.qtype)?;
             bytes.write_u16::<BigEndian>(query.qclass.to_u16())?;
         }
@@ -50,54 +63,291 @@ impl Request {
             data: Vec::new(),
         }
     }
+
+    /// Reads bytes off of the given slice, parsing them into a request, as
+    /// a server would. Any OPT record in the additional section is kept;
+    /// other records, which requests don’t usually contain, are skipped.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        Self::from_bytes_with(bytes, ParseOptions::lenient())
+    }
+
+    /// Reads bytes off of the given slice, parsing them into a request,
+    /// and rejecting any packet that the given options say is malformed.
+    pub fn from_bytes_with(bytes: &[u8], options: ParseOptions) -> Result<Self, WireError> {
+        debug!("Parsing bytes -> {:?}", bytes);
+
+        let mut c = Cursor::new(bytes);
+        let (transaction_id, flags, counts) = read_header(&mut c)?;
+        let [ query_count, answer_count, authority_count, additional_count ] = counts;
+
+        let queries = read_queries(&mut c, &options, query_count)?;
+
+        let mut additional = None;
+        for (section, count) in [ (Section::Answer, answer_count), (Section::Authority, authority_count), (Section::Additional, additional_count) ] {
+            for answer in read_section(&mut c, &options, section, count)? {
+                match answer {
+                    Answer::Pseudo { opt, .. }  => additional = Some(opt),
+                    Answer::Standard { .. }     => info!("Skipping record in request"),
+                }
+            }
+        }
+
+        check_leftover(bytes, &c, &options, additional_count)?;
+        Ok(Request { transaction_id, flags, queries, additional })
+    }
 }
 
 
 impl Response {
 
+    /// Converts this response to a vector of bytes, as a server would send
+    /// it. This fails if any of the names are too long to be encoded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WireError> {
+        self.to_bytes_with(WriteOptions::default())
+    }
+
+    /// Converts this response to a vector of bytes, writing it the way the
+    /// given options say to.
+    pub fn to_bytes_with(&self, options: WriteOptions) -> Result<Vec<u8>, WireError> {
+        let mut bytes = Vec::with_capacity(512);
+        let mut compressor = NameCompressor::new(options.compress_names);
+
+        bytes.write_u16::<BigEndian>(self.transaction_id)?;
+        bytes.write_u16::<BigEndian>(self.flags.to_u16())?;
+
+        bytes.write_u16::<BigEndian>(self.queries.len() as u16)?;
+        bytes.write_u16::<BigEndian>(self.answers.len() as u16)?;
+        bytes.write_u16::<BigEndian>(self.authorities.len() as u16)?;
+        bytes.write_u16::<BigEndian>(self.additionals.len() as u16)?;
+
+        for query in &self.queries {
+            compressor.write_name(&mut bytes, &query.qname)?;
+            bytes.write_u16::<BigEndian>(query.qtype)?;
+            bytes.write_u16::<BigEndian>(query.qclass.to_u16())?;
+        }
+
+        for answer in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            answer.write(&mut bytes, &mut compressor)?;
+        }
+
+        Ok(bytes)
+    }
+
     /// Reads bytes off of the given slice, parsing them into a response.
+    /// This is lenient about malformed packets, so that responses from
+    /// broken servers can still be examined.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        Self::from_bytes_with(bytes, ParseOptions::lenient())
+    }
+
+    /// Reads bytes off of the given slice, parsing them into a response,
+    /// and rejecting any packet that the given options say is malformed.
+    pub fn from_bytes_with(bytes: &[u8], options: ParseOptions) -> Result<Self, WireError> {
         debug!("Parsing bytes -> {:?}", bytes);
 
         let mut c = Cursor::new(bytes);
+        let (transaction_id, flags, counts) = read_header(&mut c)?;
+        let [ query_count, answer_count, authority_count, additional_count ] = counts;
+
+        let queries     = read_queries(&mut c, &options, query_count)?;
+        let answers     = read_section(&mut c, &options, Section::Answer, answer_count)?;
+        let authorities = read_section(&mut c, &options, Section::Authority, authority_count)?;
+        let additionals = read_section(&mut c, &options, Section::Additional, additional_count)?;
+
+        check_leftover(bytes, &c, &options, additional_count)?;
+        Ok(Response { transaction_id, flags, queries, answers, authorities, additionals })
+    }
+}
+
+
+/// Reads the header of a message: its transaction ID, its flags, and how
+/// many entries there are in each of its four sections.
+fn read_header(c: &mut Cursor<&[u8]>) -> Result<(u16, Flags, [u16; 4]), WireError> {
+    let read = |c: &mut Cursor<&[u8]>| -> Result<_, WireError> {
         let transaction_id = c.read_u16::<BigEndian>()?;
         let flags = Flags::from_u16(c.read_u16::<BigEndian>()?);
         debug!("Read flags: {:#?}", flags);
 
-        let query_count      = c.read_u16::<BigEndian>()?;
-        let answer_count     = c.read_u16::<BigEndian>()?;
-        let authority_count  = c.read_u16::<BigEndian>()?;
-        let additional_count = c.read_u16::<BigEndian>()?;
-
-        let mut queries = Vec::new();
-        debug!("Reading {}x query from response", query_count);
-        for _ in 0 .. query_count {
-            let qname = c.read_labels()?;
-            queries.push(Query::from_bytes(qname, &mut c)?);
+        let mut counts = [0; 4];
+        for count in &mut counts {
+            *count = c.read_u16::<BigEndian>()?;
         }
 
-        let mut answers = Vec::new();
-        debug!("Reading {}x answer from response", answer_count);
-        for _ in 0 .. answer_count {
-            let qname = c.read_labels()?;
-            answers.push(Answer::from_bytes(qname, &mut c)?);
-        }
+        Ok((transaction_id, flags, counts))
+    };
 
-        let mut authorities = Vec::new();
-        debug!("Reading {}x authority from response", authority_count);
-        for _ in 0 .. authority_count {
-            let qname = c.read_labels()?;
-            authorities.push(Answer::from_bytes(qname, &mut c)?);
-        }
+    read(c).map_err(|e| e.at(ErrorLocation { section: Section::Header, index: 0, qtype: None, offset: 0 }))
+}
+
+/// Reads the given number of queries from the query section of a message.
+fn read_queries(c: &mut Cursor<&[u8]>, options: &ParseOptions, count: u16) -> Result<Vec<Query>, WireError> {
+    debug!("Reading {}x query", count);
+    let mut queries = Vec::new();
+
+    for index in 0 .. usize::from(count) {
+        check_not_ended(c, Section::Query, count, index)?;
+        let offset = c.position() as usize;
+        let location = |qtype| ErrorLocation { section: Section::Query, index, qtype, offset };
+
+        let qname = c.read_labels_with(options).map_err(|e| e.at(location(None)))?;
+        let qtype = peek_type(c);
+        queries.push(Query::from_bytes(qname, c).map_err(|e| e.at(location(qtype)))?);
+    }
+
+    Ok(queries)
+}
+
+/// Reads the given number of records from one of the record sections of a
+/// message, noting where in the message any of them failed to be read.
+fn read_section(c: &mut Cursor<&[u8]>, options: &ParseOptions, section: Section, count: u16) -> Result<Vec<Answer>, WireError> {
+    debug!("Reading {}x record from {} section", count, section);
+    let mut answers = Vec::new();
+
+    for index in 0 .. usize::from(count) {
+        check_not_ended(c, section, count, index)?;
+        let offset = c.position() as usize;
+        let location = |qtype| ErrorLocation { section, index, qtype, offset };
+
+        let qname = c.read_labels_with(options).map_err(|e| e.at(location(None)))?;
+        let qtype = peek_type(c);
+        answers.push(Answer::from_bytes(qname, c, options).map_err(|e| e.at(location(qtype)))?);
+    }
+
+    Ok(answers)
+}
 
-        let mut additionals = Vec::new();
-        debug!("Reading {}x additional answer from response", additional_count);
-        for _ in 0 .. additional_count {
-            let qname = c.read_labels()?;
-            additionals.push(Answer::from_bytes(qname, &mut c)?);
+/// Returns the type number the cursor is at, without moving past it, if
+/// there are enough bytes left to hold one.
+fn peek_type(c: &Cursor<&[u8]>) -> Option<TypeInt> {
+    let start = c.position() as usize;
+    let bytes = c.get_ref().get(start .. start + 2)?;
+    Some(u16::from_be_bytes([ bytes[0], bytes[1] ]))
+}
+
+/// Fails if the message ends exactly where the next entry of a section
+/// should start, which means the header’s count for that section was
+/// higher than the number of entries actually in it.
+fn check_not_ended(c: &Cursor<&[u8]>, section: Section, count: u16, index: usize) -> Result<(), WireError> {
+    if c.position() as usize == c.get_ref().len() {
+        warn!("Header says {} {} entries, but the message ends after {}", count, section, index);
+        return Err(WireError::WrongCount { section, declared: count, found: index as u16 });
+    }
+
+    Ok(())
+}
+
+/// Fails if there are bytes left over after the last record and the
+/// options say to reject them. If the bytes left over are whole records,
+/// the header’s count for the last section was too low, so that gets
+/// reported instead of the bytes being treated as garbage.
+fn check_leftover(bytes: &[u8], c: &Cursor<&[u8]>, options: &ParseOptions, additional_count: u16) -> Result<(), WireError> {
+    let leftover = bytes.len() - c.position() as usize;
+    if leftover > 0 {
+        if options.reject_trailing_bytes {
+            if let Some(extra) = count_whole_records(c, options) {
+                let found = additional_count.saturating_add(extra);
+                return Err(WireError::WrongCount { section: Section::Additional, declared: additional_count, found });
+            }
+
+            return Err(WireError::TrailingBytes(leftover));
         }
 
-        Ok(Response { transaction_id, flags, queries, answers, authorities, additionals })
+        info!("Ignoring {} trailing bytes after the last record", leftover);
+    }
+
+    Ok(())
+}
+
+/// Returns how many records the rest of the message holds, if it can be
+/// read as nothing but whole records.
+fn count_whole_records(c: &Cursor<&[u8]>, options: &ParseOptions) -> Option<u16> {
+    let mut c = c.clone();
+    let mut count = 0_u16;
+
+    while (c.position() as usize) < c.get_ref().len() {
+        let qname = c.read_labels_with(options).ok()?;
+        Answer::from_bytes(qname, &mut c, options).ok()?;
+        count = count.checked_add(1)?;
+    }
+
+    Some(count)
+}
+
+
+/// Options that control how forgiving parsing a message should be about
+/// packets that are malformed but can still be understood, and how much
+/// work reading a hostile packet can take.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseOptions {
+
+    /// Whether to reject packets with bytes left over after the last record
+    /// that the header’s section counts say they contain. If those bytes
+    /// are whole records, the count was too low, and the error says so.
+    /// (Counts that are too *high* always cause an error, as the records
+    /// run out.)
+    pub reject_trailing_bytes: bool,
+
+    /// How many compression pointers reading a single name can follow
+    /// before giving up with `WireError::TooMuchRecursion`, which stops a
+    /// loop of pointers from being followed forever. Defaults to 8.
+    pub max_pointer_jumps: usize,
+
+    /// How long a name can be once it has been decompressed, counting the
+    /// length bytes and the terminating zero, before reading it fails with
+    /// `WireError::NameTooLong`. Defaults to 255, which is the most that
+    /// the protocol allows, so this can only be used to lower the limit.
+    pub max_name_length: usize,
+
+    /// Extra record types to parse, beyond the ones built into dog, such
+    /// as those with private-use type numbers. Records of types that are
+    /// in neither get read as `Record::Other`.
+    pub registry: Option<Arc<RecordRegistry>>,
+}
+
+impl ParseOptions {
+
+    /// The permissive options used by `Response::from_bytes`, which accept
+    /// anything that can be parsed.
+    pub fn lenient() -> Self {
+        Self::default()
+    }
+
+    /// Options that reject every kind of malformed packet that can be
+    /// detected.
+    pub fn strict() -> Self {
+        Self { reject_trailing_bytes: true, .. Self::default() }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        DEFAULT_PARSE_OPTIONS.clone()
+    }
+}
+
+/// The default options, for anything that needs to borrow them for as
+/// long as the program runs.
+pub(crate) static DEFAULT_PARSE_OPTIONS: ParseOptions = ParseOptions {
+    reject_trailing_bytes: false,
+    max_pointer_jumps: RECURSION_LIMIT,
+    max_name_length: Name::MAX_NAME_LENGTH,
+    registry: None,
+};
+
+
+/// Options that control how messages get written out.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct WriteOptions {
+
+    /// Whether to compress domain names that share a suffix with one that
+    /// has already been written. This can be turned off to test servers
+    /// that cannot handle compression pointers.
+    pub compress_names: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self { compress_names: true }
     }
 }
 
@@ -106,7 +356,7 @@ impl Query {
 
     /// Reads bytes from the given cursor, and parses them into a query with
     /// the given domain name.
-    fn from_bytes(qname: String, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+    pub(crate) fn from_bytes(qname: Name, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
         let qtype = c.read_u16::<BigEndian>()?;
         let qclass = QClass::from_u16(c.read_u16::<BigEndian>()?);
 
@@ -118,8 +368,9 @@ impl Query {
 impl Answer {
 
     /// Reads bytes from the given cursor, and parses them into an answer with
-    /// the given domain name.
-    fn from_bytes(qname: String, c: &mut Cursor<&[u8]>) -> Result<Self, WireError> {
+    /// the given domain name, reading any names in its data with the limits
+    /// in the given options.
+    pub(crate) fn from_bytes(qname: Name, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Self, WireError> {
         let qtype = c.read_u16::<BigEndian>()?;
         if qtype == OPT::RR_TYPE {
             let opt = OPT::read(c)?;
@@ -130,7 +381,7 @@ impl Answer {
             let ttl = c.read_u32::<BigEndian>()?;
 
             let len = c.read_u16::<BigEndian>()?;
-            let record = Record::from_bytes(qtype, len, c)?;
+            let record = Record::from_bytes(qtype, len, c, options)?;
 
             Ok(Answer::Standard { qclass, qname, record, ttl })
         }
@@ -139,18 +390,97 @@ impl Answer {
 }
 
 
+impl Answer {
+
+    /// Writes this answer to the end of the message being built up in the
+    /// given buffer, compressing its name if possible.
+    fn write(&self, bytes: &mut Vec<u8>, compressor: &mut NameCompressor) -> Result<(), WireError> {
+        match self {
+            Answer::Standard { qname, qclass, ttl, record } => {
+                compressor.write_name(bytes, qname)?;
+                bytes.write_u16::<BigEndian>(record.type_number())?;
+                bytes.write_u16::<BigEndian>(qclass.to_u16())?;
+                bytes.write_u32::<BigEndian>(*ttl)?;
+
+                let mut rdata = Vec::new();
+                record.write(&mut rdata)?;
+                if rdata.len() > usize::from(u16::MAX) {
+                    return Err(WireError::RecordTooLong(rdata.len()));
+                }
+
+                bytes.write_u16::<BigEndian>(rdata.len() as u16)?;
+                bytes.extend(rdata);
+            }
+            Answer::Pseudo { qname, opt } => {
+                compressor.write_name(bytes, qname)?;
+                bytes.write_u16::<BigEndian>(OPT::RR_TYPE)?;
+                bytes.extend(opt.to_bytes()?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
 impl Record {
 
+    /// Writes this record’s data to the end of the given buffer, without
+    /// its type or length.
+    pub fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError> {
+        match self {
+            Self::A(a)             => a.write(bytes),
+            Self::AAAA(aaaa)       => aaaa.write(bytes),
+            Self::CAA(caa)         => caa.write(bytes),
+            Self::CNAME(cname)     => cname.write(bytes),
+            Self::DNSKEY(dnskey)   => dnskey.write(bytes),
+            Self::DS(ds)           => ds.write(bytes),
+            Self::MX(mx)           => mx.write(bytes),
+            Self::NS(ns)           => ns.write(bytes),
+            Self::PTR(ptr)         => ptr.write(bytes),
+            Self::RRSIG(rrsig)     => rrsig.write(bytes),
+            Self::SOA(soa)         => soa.write(bytes),
+            Self::SRV(srv)         => srv.write(bytes),
+            Self::TXT(txt)         => txt.write(bytes),
+            Self::Custom(custom)   => {
+                bytes.extend(custom.bytes());
+                Ok(())
+            }
+            Self::Other { bytes: other, .. }  => {
+                bytes.extend(other);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads exactly `len` bytes from the given cursor, and parses them into
+    /// a record structure depending on the type number, which has already
+    /// been read. A record that reads past the end of its data, or stops
+    /// short of it, means the length field was lying, so it gets rejected
+    /// rather than letting it eat into the next record.
+    pub(crate) fn from_bytes(qtype: TypeInt, len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Record, WireError> {
+        let start = c.position();
+        let record = Self::read_data(qtype, len, c, options)?;
+
+        let consumed = c.position() - start;
+        if consumed != u64::from(len) {
+            warn!("Record of type {} should have been {} bytes long, but read {}", qtype, len, consumed);
+            return Err(WireError::WrongLength { expected: len, got: consumed as u16 });
+        }
+
+        Ok(record)
+    }
+
     /// Reads at most `len` bytes from the given curser, and parses them into
-    /// a record structure depending on the type number, which has already been read.
-    fn from_bytes(qtype: TypeInt, len: u16, c: &mut Cursor<&[u8]>) -> Result<Record, WireError> {
+    /// a record structure depending on the type number.
+    fn read_data(qtype: TypeInt, len: u16, c: &mut Cursor<&[u8]>, options: &ParseOptions) -> Result<Record, WireError> {
         use crate::record::*;
 
         macro_rules! try_record {
             ($record:tt) => {
                 if $record::RR_TYPE == qtype {
                     info!("Deciphering {} record (type {}, len {})", $record::NAME, qtype, len);
-                    return Wire::read(len, c).map(Record::$record)
+                    return Wire::read_with(len, c, options).map(Record::$record)
                 }
             }
         }
@@ -161,20 +491,25 @@ impl Record {
         try_record!(AAAA);
         try_record!(CAA);
         try_record!(CNAME);
+        try_record!(DNSKEY);
+        try_record!(DS);
         try_record!(MX);
         try_record!(NS);
         // OPT is handled separately
         try_record!(PTR);
+        try_record!(RRSIG);
         try_record!(SOA);
         try_record!(SRV);
         try_record!(TXT);
 
+        // Then try any types the application has registered.
+        if let Some(result) = options.registry.as_ref().and_then(|r| r.read(qtype, len, c, options)) {
+            return result.map(Record::Custom);
+        }
+
         // Otherwise, collect the bytes into a vector and return an unknown
         // record type.
-        let mut bytes = Vec::new();
-        for _ in 0 .. len {
-            bytes.push(c.read_u8()?);
-        }
+        let bytes = c.read_slice(usize::from(len))?.to_vec();
 
         let type_number = UnknownQtype::from(qtype);
         Ok(Record::Other { type_number, bytes })
@@ -182,8 +517,36 @@ impl Record {
 }
 
 
+/// An extension for `Cursor` that reads a run of bytes all at once, rather
+/// than one at a time.
+pub(crate) trait ReadSlice<'a> {
+
+    /// Reads the next `len` bytes, borrowing them from the buffer, or fails
+    /// without moving if there are not that many left.
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], WireError>;
+}
+
+impl<'a> ReadSlice<'a> for Cursor<&'a [u8]> {
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        let buffer: &'a [u8] = self.get_ref();
+        let start = self.position() as usize;
+
+        match start.checked_add(len).and_then(|end| buffer.get(start .. end)) {
+            Some(bytes) => {
+                self.set_position((start + len) as u64);
+                Ok(bytes)
+            }
+            None => {
+                error!("Wanted {} bytes at position {}, but only {} were left", len, start, buffer.len().saturating_sub(start));
+                Err(WireError::IO)
+            }
+        }
+    }
+}
+
+
 impl QClass {
-    fn from_u16(uu: u16) -> Self {
+    pub(crate) fn from_u16(uu: u16) -> Self {
         match uu {
             0x0001 => QClass::IN,
             0x0003 => QClass::CH,
@@ -192,7 +555,8 @@ impl QClass {
         }
     }
 
-    fn to_u16(self) -> u16 {
+    /// Returns the number that represents this class on the wire.
+    pub fn to_u16(self) -> u16 {
         match self {
             QClass::IN        => 0x0001,
             QClass::CH        => 0x0003,
@@ -205,31 +569,125 @@ impl QClass {
 
 /// Determines the record type number to signify a record with the given name.
 pub fn find_qtype_number(record_type: &str) -> Option<TypeInt> {
+    NAMED_TYPES.iter().find(|(name, _)| *name == record_type).map(|(_, number)| *number)
+}
+
+/// Returns the name of every record type that can be queried for by name:
+/// the ones that can be parsed first, followed by the ones that dog knows
+/// about but cannot parse.
+pub fn qtype_names() -> Vec<&'static str> {
+    NAMED_TYPES.iter().map(|(name, _)| *name)
+                .chain(crate::record::other_qtype_names())
+                .collect()
+}
+
+
+/// Determines the name of the record type with the given number, including
+/// types that dog knows about but cannot parse.
+pub fn find_qtype_name(type_number: TypeInt) -> Option<&'static str> {
     use crate::record::*;
 
-    macro_rules! try_record {
-        ($record:tt) => {
-            if $record::NAME == record_type {
-                return Some($record::RR_TYPE);
-            }
+    if let Some((name, _)) = NAMED_TYPES.iter().find(|(_, number)| *number == type_number) {
+        return Some(name);
+    }
+
+    if type_number == OPT::RR_TYPE {
+        return Some("OPT");
+    }
+
+    match UnknownQtype::from(type_number) {
+        UnknownQtype::HeardOf(name)  => Some(name),
+        UnknownQtype::UnheardOf(_)   => None,
+    }
+}
+
+
+/// Writes a message to a TCP stream, prefixed with its length as two bytes
+/// so the other end knows where it stops (RFC 1035 §4.2.2). Messages too
+/// long for their length to fit are rejected.
+pub fn write_tcp_message(writer: &mut impl io::Write, message: &[u8]) -> io::Result<()> {
+    writer.write_all(&frame_tcp_message(message)?)
+}
+
+/// Reads one length-prefixed message from a TCP stream, however many reads
+/// it takes to arrive. Returns `None` if the stream ends before another
+/// message starts, and an error if it ends part of the way through one,
+/// even if only half of the length has arrived.
+pub fn read_tcp_message(reader: &mut impl io::Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 2];
+    loop {
+        match reader.read(&mut len[.. 1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
         }
     }
 
-    try_record!(A);
-    try_record!(AAAA);
-    try_record!(CAA);
-    try_record!(CNAME);
-    try_record!(MX);
-    try_record!(NS);
-    // OPT is elsewhere
-    try_record!(PTR);
-    try_record!(SOA);
-    try_record!(SRV);
-    try_record!(TXT);
+    reader.read_exact(&mut len[1 ..])?;
 
-    None
+    let mut message = vec![0; usize::from(u16::from_be_bytes(len))];
+    reader.read_exact(&mut message)?;
+    Ok(Some(message))
 }
 
+/// Returns the message with its two-byte length prefix, ready to be sent
+/// over TCP in a single write.
+pub fn frame_tcp_message(message: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u16::try_from(message.len()).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Message of {} bytes is too long to send over TCP", message.len()))
+    })?;
+
+    let mut framed = Vec::with_capacity(message.len() + 2);
+    framed.extend_from_slice(&len.to_be_bytes());
+    framed.extend_from_slice(message);
+    Ok(framed)
+}
+
+/// Collects the bytes read from a TCP stream, however they were split up
+/// between reads, and hands back each length-prefixed message once all of
+/// it has arrived. This is for reading from streams that cannot be read
+/// with `read_tcp_message`, such as asynchronous ones.
+#[derive(PartialEq, Debug, Default)]
+pub struct TcpMessageBuffer {
+    bytes: Vec<u8>,
+}
+
+impl TcpMessageBuffer {
+
+    /// Creates a new empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds bytes that have just been read to the end of the buffer.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Removes and returns the first message in the buffer, if all of it
+    /// has arrived.
+    pub fn next_message(&mut self) -> Option<Vec<u8>> {
+        if self.bytes.len() < 2 {
+            return None;
+        }
+
+        let len = usize::from(u16::from_be_bytes([ self.bytes[0], self.bytes[1] ]));
+        if self.bytes.len() < len + 2 {
+            return None;
+        }
+
+        let message = self.bytes[2 .. len + 2].to_vec();
+        self.bytes.drain(.. len + 2);
+        Some(message)
+    }
+
+    /// Whether there are no bytes left over in the buffer, which is only
+    /// the case between messages.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
 
 impl Flags {
 
@@ -242,17 +700,19 @@ impl Flags {
     pub fn to_u16(self) -> u16 {                 // 0123 4567 89AB CDEF
         let mut                          bits  = 0b_0000_0000_0000_0000;
         if self.response               { bits += 0b_1000_0000_0000_0000; }
-        match self.opcode {
-                                _ =>   { bits += 0b_0000_0000_0000_0000; }
-        }
+        bits += u16::from(self.opcode & 0b_1111) << 11;
         if self.authoritative          { bits += 0b_0000_0100_0000_0000; }
         if self.truncated              { bits += 0b_0000_0010_0000_0000; }
         if self.recursion_desired      { bits += 0b_0000_0001_0000_0000; }
         if self.recursion_available    { bits += 0b_0000_0000_1000_0000; }
-        // (the Z bit is reserved)               0b_0000_0000_0100_0000
+        if self.reserved               { bits += 0b_0000_0000_0100_0000; }
         if self.authentic_data         { bits += 0b_0000_0000_0010_0000; }
         if self.checking_disabled      { bits += 0b_0000_0000_0001_0000; }
 
+        if let Some(rcode) = self.error_code {
+            bits += rcode.to_bits() & 0b_1111;
+        }
+
         bits
     }
 
@@ -262,13 +722,14 @@ impl Flags {
 
         Flags {
             response:               has_bit(0b_1000_0000_0000_0000),
-            opcode:                 0,
+            opcode:                 ((bits >> 11) & 0b_1111) as u8,
             authoritative:          has_bit(0b_0000_0100_0000_0000),
             truncated:              has_bit(0b_0000_0010_0000_0000),
             recursion_desired:      has_bit(0b_0000_0001_0000_0000),
             recursion_available:    has_bit(0b_0000_0000_1000_0000),
             authentic_data:         has_bit(0b_0000_0000_0010_0000),
             checking_disabled:      has_bit(0b_0000_0000_0001_0000),
+            reserved:               has_bit(0b_0000_0000_0100_0000),
             error_code:             ErrorCode::from_bits(bits & 0b_1111),
         }
     }
@@ -277,8 +738,10 @@ impl Flags {
 
 impl ErrorCode {
 
-    /// Extracts the rcode from the last four bits of the flags field.
-    fn from_bits(bits: u16) -> Option<Self> {
+    /// Extracts the rcode from the last four bits of the flags field, or
+    /// from the number made by adding the extended bits from an OPT record
+    /// to them. Zero means there was no error.
+    pub fn from_bits(bits: u16) -> Option<Self> {
         match bits {
             0 => None,
             1 => Some(Self::FormatError),
@@ -290,6 +753,20 @@ impl ErrorCode {
             n => Some(Self::Other(n)),
         }
     }
+
+    /// Returns the number for this rcode. Only the last four bits of it
+    /// fit in the flags field; the rest go in an OPT record.
+    pub fn to_bits(self) -> u16 {
+        match self {
+            Self::FormatError     => 1,
+            Self::ServerFailure   => 2,
+            Self::NXDomain        => 3,
+            Self::NotImplemented  => 4,
+            Self::QueryRefused    => 5,
+            Self::BadVersion      => 16,
+            Self::Other(n)        => n,
+        }
+    }
 }
 
 
@@ -307,6 +784,20 @@ pub trait Wire: Sized {
     /// throughout the complete data — by this point, we have read the entire
     /// response into a buffer.
     fn read(len: u16, c: &mut Cursor<&[u8]>) -> Result<Self, WireError>;
+
+    /// Read at most `len` bytes from the given `Cursor`, like `read`, but
+    /// reading any domain names with the limits in the given options.
+    /// Records that contain names override this, and have `read` call it
+    /// with the default options; the rest have no use for the options.
+    fn read_with(len: u16, c: &mut Cursor<&[u8]>, _options: &ParseOptions) -> Result<Self, WireError> {
+        Self::read(len, c)
+    }
+
+    /// Write this record’s data to the end of the given buffer, in the form
+    /// that `read` would read it back in. Domain names are written out in
+    /// full, as compression inside record data is only allowed for a few
+    /// of the oldest record types.
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), WireError>;
 }
 
 
@@ -354,8 +845,181 @@ pub enum WireError {
     /// When the data contained a string with a pointer to an index outside of
     /// the packet. Contains the invalid index.
     OutOfBounds(u16),
+
+    /// When the number of entries in a section did not match the count for
+    /// it in the header: either the message ended before all of them were
+    /// read, or, when the packet was being parsed strictly, there were
+    /// whole records left over after the last section.
+    WrongCount {
+
+        /// The section whose count was wrong.
+        section: Section,
+
+        /// The number of entries the header said the section had.
+        declared: u16,
+
+        /// The number of entries that were actually there.
+        found: u16,
+    },
+
+    /// When there was data left over after the last record in the packet,
+    /// and the packet was being parsed strictly. Contains the number of
+    /// bytes left over.
+    TrailingBytes(usize),
+
+    /// When a domain name contained a label longer than 63 bytes. Contains
+    /// the length of the label.
+    LabelTooLong(usize),
+
+    /// When a domain name would take up more than 255 bytes on the wire.
+    /// Contains the encoded length of the name.
+    NameTooLong(usize),
+
+    /// When a record’s data was too long for its length to fit in the
+    /// length field. Contains the length of the data.
+    RecordTooLong(usize),
+
+    /// When a string in a record’s data, such as a CAA tag, was too long
+    /// for its length to fit in the single byte before it. Contains the
+    /// length of the string.
+    StringTooLong(usize),
+
+    /// When a label of an internationalised domain name was not valid
+    /// under IDNA2008, or could not be encoded with Punycode.
+    InvalidIdn(IdnError),
+
+    /// When one of the other errors happened while reading a particular
+    /// part of a message, which says where it happened.
+    At {
+
+        /// The part of the message that was being read.
+        location: ErrorLocation,
+
+        /// What went wrong reading it.
+        error: Box<WireError>,
+    },
+}
+
+impl WireError {
+
+    /// Returns the error that caused this one, without the location it
+    /// happened at, if it has one.
+    pub fn cause(&self) -> &Self {
+        match self {
+            Self::At { error, .. }  => error.cause(),
+            otherwise               => otherwise,
+        }
+    }
+
+    /// Returns where in the message this error happened, if it is known.
+    pub fn location(&self) -> Option<ErrorLocation> {
+        match self {
+            Self::At { location, .. }  => Some(*location),
+            _                          => None,
+        }
+    }
+
+    /// Notes that this error happened at the given place in a message.
+    fn at(self, location: ErrorLocation) -> Self {
+        Self::At { location, error: Box::new(self) }
+    }
 }
 
+
+/// Where in a message an error happened while reading it.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct ErrorLocation {
+
+    /// The section that was being read.
+    pub section: Section,
+
+    /// Which query or record in the section was being read, counting from
+    /// zero.
+    pub index: usize,
+
+    /// The type number of the query or record, if there were enough bytes
+    /// to read one.
+    pub qtype: Option<TypeInt>,
+
+    /// How many bytes into the message the query or record starts.
+    pub offset: usize,
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.section == Section::Header {
+            return write!(f, "the header");
+        }
+
+        write!(f, "{} #{}", self.section, self.index + 1)?;
+
+        if let Some(qtype) = self.qtype {
+            match find_qtype_name(qtype) {
+                Some(name)  => write!(f, " ({})", name)?,
+                None        => write!(f, " (type {})", qtype)?,
+            }
+        }
+
+        write!(f, " at byte {}", self.offset)
+    }
+}
+
+/// One of the parts of a message.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Section {
+
+    /// The twelve bytes at the start, with the flags and section counts.
+    Header,
+
+    /// The queries section.
+    Query,
+
+    /// The answers section.
+    Answer,
+
+    /// The authoritative nameservers section.
+    Authority,
+
+    /// The additional records section.
+    Additional,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header      => write!(f, "header"),
+            Self::Query       => write!(f, "query"),
+            Self::Answer      => write!(f, "answer"),
+            Self::Authority   => write!(f, "authority"),
+            Self::Additional  => write!(f, "additional"),
+        }
+    }
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO                                       => write!(f, "Malformed packet: insufficient data"),
+            Self::WrongLength { expected, got }            => write!(f, "Malformed packet: expected length {}, got {}", expected, got),
+            Self::TooMuchRecursion(indices)                => write!(f, "Malformed packet: too much recursion: {:?}", indices),
+            Self::OutOfBounds(index)                       => write!(f, "Malformed packet: out of bounds ({})", index),
+            Self::WrongCount { section, declared, found }  => write!(f, "Malformed packet: header says {} {} entries, found {}", declared, section, found),
+            Self::TrailingBytes(count)                     => write!(f, "Malformed packet: {} trailing bytes", count),
+            Self::LabelTooLong(len)                        => write!(f, "Label too long: {} bytes (maximum 63)", len),
+            Self::NameTooLong(len)                         => write!(f, "Domain name too long: {} bytes (maximum 255)", len),
+            Self::RecordTooLong(len)                       => write!(f, "Record data too long: {} bytes (maximum 65535)", len),
+            Self::StringTooLong(len)                       => write!(f, "String in record data too long: {} bytes (maximum 255)", len),
+            Self::InvalidIdn(e)                            => write!(f, "Invalid internationalised domain name: {}", e),
+            Self::At { location, error }                   => write!(f, "{} in {}", error, location),
+        }
+    }
+}
+
+/// An error with a location already includes the error that happened
+/// there in its message, so it gets no source, which would print it twice
+/// for anything walking the chain of errors. Use `cause` to get at it.
+impl std::error::Error for WireError {}
+
 impl From<io::Error> for WireError {
     fn from(ioe: io::Error) -> Self {
         error!("IO error -> {:?}", ioe);
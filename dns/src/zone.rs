@@ -0,0 +1,530 @@
+//! Answering queries from the records in a zone, the way an authoritative
+//! nameserver for that zone would.
+//!
+//! # References
+//!
+//! - [RFC 1034 §4.3.2](https://tools.ietf.org/html/rfc1034#section-4.3.2) — Algorithm (November 1987)
+//! - [RFC 2308 §3](https://tools.ietf.org/html/rfc2308#section-3) — Negative Answers from Authoritative Servers (March 1998)
+//! - [RFC 4592](https://tools.ietf.org/html/rfc4592) — The Role of Wildcards in the Domain Name System (July 2006)
+
+use std::fmt;
+
+use crate::name::Name;
+use crate::record::{Record, SOA, A, AAAA, CNAME, DS, NS};
+use crate::types::*;
+use crate::wire::Wire;
+
+
+/// The most CNAME records to follow, within the zone, while answering a
+/// single query.
+const MAX_CNAMES: usize = 8;
+
+/// The type number for queries that ask for every record at a name.
+const ANY: TypeInt = 255;
+
+
+/// The records in a zone, which can be used to answer queries for the
+/// names in it.
+///
+/// The zone’s origin is the owner of its SOA record, and every other
+/// record has to be at or below it. Names below the origin with NS
+/// records are delegated to other nameservers, so queries for them get
+/// referrals rather than answers. Queries for names that do not exist get
+/// answered from a wildcard, if there is one at their closest encloser.
+#[derive(PartialEq, Debug)]
+pub struct Zone {
+    origin: Name,
+    qclass: QClass,
+    soa_ttl: u32,
+    soa: SOA,
+    records: Vec<Answer>,
+}
+
+/// Something wrong with a set of records that stops them from being used
+/// as a zone.
+#[derive(PartialEq, Debug)]
+pub enum ZoneContentsError {
+
+    /// There was no SOA record to say where the zone starts.
+    MissingSOA,
+
+    /// There was more than one SOA record, at the given names.
+    MultipleSOAs(Name, Name),
+
+    /// A record was outside the zone, or in a different class to its SOA
+    /// record.
+    OutOfZone(Name),
+}
+
+impl fmt::Display for ZoneContentsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSOA            => write!(f, "Zone has no SOA record"),
+            Self::MultipleSOAs(a, b)    => write!(f, "Zone has more than one SOA record, at {} and {}", a, b),
+            Self::OutOfZone(name)       => write!(f, "Record for {} is outside the zone", name),
+        }
+    }
+}
+
+
+impl Zone {
+
+    /// Creates a zone from a list of records, such as the ones read from a
+    /// zone file, checking that they have exactly one SOA record and that
+    /// all the others belong under it.
+    pub fn new(records: Vec<Answer>) -> Result<Self, ZoneContentsError> {
+        let mut soas = records.iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { qname, qclass, ttl, record: Record::SOA(soa) }  => Some((qname, *qclass, *ttl, soa)),
+                _                                                                   => None,
+            }
+        });
+
+        let (origin, qclass, soa_ttl, soa) = soas.next().ok_or(ZoneContentsError::MissingSOA)?;
+        if let Some((other, ..)) = soas.next() {
+            return Err(ZoneContentsError::MultipleSOAs(origin.clone(), other.clone()));
+        }
+
+        let (origin, soa) = (origin.clone(), soa.clone());
+        for answer in &records {
+            match answer {
+                Answer::Standard { qname, qclass: c, .. } if qname.is_below_or_at(&origin) && *c == qclass => {}
+                Answer::Standard { qname, .. } | Answer::Pseudo { qname, .. } => {
+                    return Err(ZoneContentsError::OutOfZone(qname.clone()));
+                }
+            }
+        }
+
+        Ok(Self { origin, qclass, soa_ttl, soa, records })
+    }
+
+    /// The name of the zone, which is the owner of its SOA record.
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// Works out the response to a request: the matching records, following
+    /// any CNAME records that stay in the zone; a referral, if the name has
+    /// been delegated; or the SOA record, if there are no matching records
+    /// or, with `NXDOMAIN`, no records at that name or from a wildcard at
+    /// all. Queries for names outside the zone are refused.
+    pub fn answer(&self, request: &Request) -> Response {
+        let mut response = Response {
+            transaction_id: request.transaction_id,
+            flags: request.flags,
+            queries: request.queries.clone(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        };
+
+        response.flags.response = true;
+        response.flags.authoritative = true;
+        response.flags.truncated = false;
+        response.flags.recursion_available = false;
+        response.flags.authentic_data = false;
+        response.flags.error_code = None;
+
+        if let Some(opt) = &request.additional {
+            let opt = crate::record::OPT { udp_payload_size: opt.udp_payload_size.max(512), .. Request::additional_record() };
+            response.additionals.push(Answer::Pseudo { qname: Name::root(), opt });
+        }
+
+        let query = match &request.queries[..] {
+            _ if request.flags.opcode != 0  => return self.error(response, ErrorCode::NotImplemented),
+            [ query ]                      => query,
+            _                              => return self.error(response, ErrorCode::FormatError),
+        };
+
+        if query.qclass != self.qclass || ! query.qname.is_below_or_at(&self.origin) {
+            return self.error(response, ErrorCode::QueryRefused);
+        }
+
+        let mut qname = query.qname.clone();
+        for _ in 0 ..= MAX_CNAMES {
+            if let Some(cut) = self.delegation(&qname, query.qtype) {
+                // A referral is only given for the name that was asked
+                // for, not for a CNAME that points below a zone cut.
+                if response.answers.is_empty() {
+                    response.flags.authoritative = false;
+                    response.authorities.extend(self.records_at(&cut).filter(|a| is_type(a, NS::RR_TYPE)).cloned());
+                    let glue = self.additional_records(&response.authorities);
+                    response.additionals.splice(0..0, glue);
+                }
+
+                return response;
+            }
+
+            let at_name = self.records_for(&qname);
+            let matching = at_name.iter().filter(|a| query.qtype == ANY || is_type(a, query.qtype)).cloned().collect::<Vec<_>>();
+            let cname = at_name.iter().find_map(|answer| {
+                match answer {
+                    Answer::Standard { record: Record::CNAME(CNAME { domain }), .. }  => Some(domain.clone()),
+                    _                                                                  => None,
+                }
+            });
+
+            if ! matching.is_empty() {
+                let extra = self.additional_records(&matching);
+                response.answers.extend(matching);
+                response.additionals.splice(0..0, extra);
+                return response;
+            }
+
+            match cname {
+                Some(target) => {
+                    response.answers.extend(at_name.into_iter().filter(|a| is_type(a, CNAME::RR_TYPE)));
+                    if ! target.is_below_or_at(&self.origin) {
+                        return response;
+                    }
+
+                    qname = target;
+                }
+                None if ! at_name.is_empty() || self.has_names_below(&qname) => {
+                    response.authorities.push(self.negative_soa());
+                    return response;
+                }
+                None => {
+                    response.authorities.push(self.negative_soa());
+                    response.flags.error_code = Some(ErrorCode::NXDomain);
+                    return response;
+                }
+            }
+        }
+
+        // The CNAME records go round in a loop, so give up on them.
+        self.error(response, ErrorCode::ServerFailure)
+    }
+
+    /// Returns the records in the zone owned by exactly this name.
+    fn records_at<'a>(&'a self, qname: &'a Name) -> impl Iterator<Item=&'a Answer> {
+        self.records.iter().filter(move |answer| {
+            matches!(answer, Answer::Standard { qname: owner, .. } if owner == qname)
+        })
+    }
+
+    /// Returns the records for a name: the ones it owns, if it exists, or
+    /// otherwise the ones owned by the wildcard at its closest encloser,
+    /// with the name put in place of the wildcard (RFC 4592 §3.3.1).
+    fn records_for(&self, qname: &Name) -> Vec<Answer> {
+        if self.exists(qname) {
+            return self.records_at(qname).cloned().collect();
+        }
+
+        let wildcard = match self.source_of_synthesis(qname) {
+            Some(wildcard)  => wildcard,
+            None            => return Vec::new(),
+        };
+
+        self.records_at(&wildcard).map(|answer| {
+            let mut answer = answer.clone();
+            if let Answer::Standard { qname: owner, .. } = &mut answer {
+                *owner = qname.clone();
+            }
+            answer
+        }).collect()
+    }
+
+    /// Returns the name of the wildcard that could answer for a name that
+    /// does not exist, which is `*` under its closest encloser: the nearest
+    /// name above it that does exist (RFC 4592 §3.3.1). There may not be
+    /// any records at it.
+    fn source_of_synthesis(&self, qname: &Name) -> Option<Name> {
+        let mut encloser = qname.parent()?;
+        while ! self.exists(&encloser) {
+            encloser = encloser.parent()?;
+        }
+
+        let wildcard = if encloser.is_root() { "*.".into() } else { format!("*.{}", encloser.to_fully_qualified()) };
+        wildcard.parse().ok()
+    }
+
+    /// Whether a name exists in the zone, by owning records or by having
+    /// names below it that do, which makes it an empty non-terminal.
+    fn exists(&self, qname: &Name) -> bool {
+        self.records_at(qname).next().is_some() || self.has_names_below(qname)
+    }
+
+    /// Whether any records in the zone are owned by names below this one,
+    /// which means the name exists even if it has no records of its own.
+    fn has_names_below(&self, qname: &Name) -> bool {
+        self.records.iter().any(|answer| {
+            matches!(answer, Answer::Standard { qname: owner, .. } if owner != qname && owner.is_below_or_at(qname))
+        })
+    }
+
+    /// Returns the name of the zone cut that the name is at or below, if
+    /// part of the zone has been delegated. A query for DS records at the
+    /// cut itself is answered by this side of it.
+    fn delegation(&self, qname: &Name, qtype: TypeInt) -> Option<Name> {
+        let mut name = qname.clone();
+
+        while name != self.origin {
+            let at_cut = name == *qname && qtype == DS::RR_TYPE;
+            if ! at_cut && self.records_at(&name).any(|a| is_type(a, NS::RR_TYPE)) {
+                return Some(name);
+            }
+
+            name = name.parent()?;
+        }
+
+        None
+    }
+
+    /// Returns the address records in the zone for any nameservers, mail
+    /// servers, or services that the given records point to.
+    fn additional_records(&self, answers: &[Answer]) -> Vec<Answer> {
+        let mut additionals = Vec::new();
+
+        for answer in answers {
+            let target = match answer {
+                Answer::Standard { record: Record::NS(ns), .. }    => &ns.nameserver,
+                Answer::Standard { record: Record::MX(mx), .. }    => &mx.exchange,
+                Answer::Standard { record: Record::SRV(srv), .. }  => &srv.target,
+                _                                                  => continue,
+            };
+
+            for address in self.records_at(target).filter(|a| is_type(a, A::RR_TYPE) || is_type(a, AAAA::RR_TYPE)) {
+                if ! additionals.contains(address) {
+                    additionals.push(address.clone());
+                }
+            }
+        }
+
+        additionals
+    }
+
+    /// Returns the SOA record to put in the authority section of a negative
+    /// response, with its TTL lowered to the SOA’s minimum field if that is
+    /// lower, as resolvers use it to cache the response (RFC 2308 §3).
+    fn negative_soa(&self) -> Answer {
+        Answer::Standard {
+            qname: self.origin.clone(),
+            qclass: self.qclass,
+            ttl: self.soa_ttl.min(self.soa.minimum_ttl),
+            record: Record::SOA(self.soa.clone()),
+        }
+    }
+
+    /// Turns the response into one that only carries an error code.
+    fn error(&self, mut response: Response, rcode: ErrorCode) -> Response {
+        response.answers.clear();
+        response.authorities.clear();
+        response.additionals.retain(|a| ! a.is_standard());
+        response.flags.authoritative = false;
+        response.flags.error_code = Some(rcode);
+        response
+    }
+}
+
+/// Whether the answer is a standard record of the given type.
+fn is_type(answer: &Answer, qtype: TypeInt) -> bool {
+    matches!(answer, Answer::Standard { record, .. } if record.type_number() == qtype)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zonefile::parse_zone;
+
+    fn zone() -> Zone {
+        let records = parse_zone("
+$TTL 1h
+@         IN  SOA    ns1 hostmaster ( 1 2h 1h 2w 5m )
+              NS     ns1
+              MX     10 mail
+ns1           A      127.0.0.1
+mail          A      127.0.0.2
+www           CNAME  web
+web           A      127.0.0.3
+away          CNAME  elsewhere.example.
+loop          CNAME  loop
+a.b           TXT    \"below an empty non-terminal\"
+*.wild        A      127.0.0.5
+*.wild        TXT    \"from a wildcard\"
+here.wild     TXT    \"not from a wildcard\"
+*.alias       CNAME  web
+sub           NS     ns.sub
+ns.sub        A      127.0.0.4
+", Some("lookup.dog.".parse().unwrap())).unwrap();
+
+        Zone::new(records).unwrap()
+    }
+
+    fn ask(qname: &str, qtype: TypeInt) -> Response {
+        let request = Request {
+            transaction_id: 0x1234,
+            flags: Flags::query(),
+            queries: vec![ Query { qname: qname.parse().unwrap(), qclass: QClass::IN, qtype } ],
+            additional: None,
+        };
+
+        zone().answer(&request)
+    }
+
+    fn summary(answers: &[Answer]) -> Vec<String> {
+        answers.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn origin() {
+        assert_eq!(zone().origin(), &"lookup.dog.".parse().unwrap());
+    }
+
+    #[test]
+    fn answered() {
+        let response = ask("ns1.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.transaction_id, 0x1234);
+        assert!(response.flags.authoritative);
+        assert_eq!(response.flags.error_code, None);
+        assert_eq!(summary(&response.answers), vec![ "ns1.lookup.dog. 3600 IN A 127.0.0.1" ]);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let response = ask("NS1.Lookup.Dog.", A::RR_TYPE);
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn apex_nameservers_with_glue() {
+        let response = ask("lookup.dog.", NS::RR_TYPE);
+        assert_eq!(summary(&response.answers), vec![ "lookup.dog. 3600 IN NS ns1.lookup.dog." ]);
+        assert_eq!(summary(&response.additionals), vec![ "ns1.lookup.dog. 3600 IN A 127.0.0.1" ]);
+    }
+
+    #[test]
+    fn any() {
+        let response = ask("lookup.dog.", ANY);
+        assert_eq!(response.answers.len(), 3);
+    }
+
+    #[test]
+    fn cname_followed() {
+        let response = ask("www.lookup.dog.", A::RR_TYPE);
+        assert_eq!(summary(&response.answers), vec![
+            "www.lookup.dog. 3600 IN CNAME web.lookup.dog.",
+            "web.lookup.dog. 3600 IN A 127.0.0.3",
+        ]);
+    }
+
+    #[test]
+    fn cname_out_of_zone() {
+        let response = ask("away.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, None);
+        assert_eq!(summary(&response.answers), vec![ "away.lookup.dog. 3600 IN CNAME elsewhere.example." ]);
+    }
+
+    #[test]
+    fn cname_loop() {
+        let response = ask("loop.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, Some(ErrorCode::ServerFailure));
+        assert!(response.answers.is_empty());
+    }
+
+    #[test]
+    fn no_data() {
+        let response = ask("web.lookup.dog.", AAAA::RR_TYPE);
+        assert_eq!(response.flags.error_code, None);
+        assert!(response.answers.is_empty());
+        assert_eq!(summary(&response.authorities), vec![ "lookup.dog. 300 IN SOA ns1.lookup.dog. hostmaster.lookup.dog. 1 7200 3600 1209600 300" ]);
+    }
+
+    #[test]
+    fn empty_non_terminal() {
+        let response = ask("b.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, None);
+        assert_eq!(response.authorities.len(), 1);
+    }
+
+    #[test]
+    fn nxdomain() {
+        let response = ask("missing.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, Some(ErrorCode::NXDomain));
+        assert!(response.flags.authoritative);
+        assert_eq!(response.authorities.len(), 1);
+    }
+
+    #[test]
+    fn wildcard() {
+        let response = ask("anything.wild.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, None);
+        assert_eq!(summary(&response.answers), vec![ "anything.wild.lookup.dog. 3600 IN A 127.0.0.5" ]);
+    }
+
+    #[test]
+    fn wildcard_several_labels_down() {
+        let response = ask("a.b.c.wild.lookup.dog.", A::RR_TYPE);
+        assert_eq!(summary(&response.answers), vec![ "a.b.c.wild.lookup.dog. 3600 IN A 127.0.0.5" ]);
+    }
+
+    #[test]
+    fn wildcard_no_data() {
+        let response = ask("anything.wild.lookup.dog.", AAAA::RR_TYPE);
+        assert_eq!(response.flags.error_code, None);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.authorities.len(), 1);
+    }
+
+    #[test]
+    fn wildcard_blocked_by_existing_name() {
+        // The name exists, so the wildcard does not answer for it, even
+        // though it has no A records of its own.
+        let response = ask("here.wild.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, None);
+        assert!(response.answers.is_empty());
+
+        // Names below it have it as their closest encloser, which has no
+        // wildcard of its own.
+        let response = ask("below.here.wild.lookup.dog.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, Some(ErrorCode::NXDomain));
+    }
+
+    #[test]
+    fn wildcard_cname() {
+        let response = ask("www.alias.lookup.dog.", A::RR_TYPE);
+        assert_eq!(summary(&response.answers), vec![
+            "www.alias.lookup.dog. 3600 IN CNAME web.lookup.dog.",
+            "web.lookup.dog. 3600 IN A 127.0.0.3",
+        ]);
+    }
+
+    #[test]
+    fn referral() {
+        let response = ask("www.sub.lookup.dog.", A::RR_TYPE);
+        assert!(! response.flags.authoritative);
+        assert!(response.answers.is_empty());
+        assert_eq!(summary(&response.authorities), vec![ "sub.lookup.dog. 3600 IN NS ns.sub.lookup.dog." ]);
+        assert_eq!(summary(&response.additionals), vec![ "ns.sub.lookup.dog. 3600 IN A 127.0.0.4" ]);
+    }
+
+    #[test]
+    fn ds_at_cut() {
+        let response = ask("sub.lookup.dog.", DS::RR_TYPE);
+        assert!(response.flags.authoritative);
+        assert_eq!(response.authorities.len(), 1);
+    }
+
+    #[test]
+    fn refused_outside() {
+        let response = ask("lookup.cat.", A::RR_TYPE);
+        assert_eq!(response.flags.error_code, Some(ErrorCode::QueryRefused));
+    }
+
+    #[test]
+    fn missing_soa() {
+        let records = parse_zone("lookup.dog. 60 A 127.0.0.1", None).unwrap();
+        assert_eq!(Zone::new(records), Err(ZoneContentsError::MissingSOA));
+    }
+
+    #[test]
+    fn record_outside() {
+        let records = parse_zone("
+lookup.dog. 60 SOA ns1.lookup.dog. hostmaster.lookup.dog. 1 2 3 4 5
+lookup.cat. 60 A 127.0.0.1
+", None).unwrap();
+
+        assert_eq!(Zone::new(records), Err(ZoneContentsError::OutOfZone("lookup.cat.".parse().unwrap())));
+    }
+}
@@ -0,0 +1,602 @@
+//! Parsing master files, also known as zone files, into records.
+//!
+//! A zone file is a list of resource records in presentation format, one
+//! per line, with a few conveniences on top:
+//!
+//! - `$ORIGIN` sets the name that relative names get appended to, and `@`
+//!   stands for the origin itself;
+//! - `$TTL` sets the TTL for records that do not give one;
+//! - `$INCLUDE` reads the records from another file;
+//! - records can span several lines by wrapping them in parentheses;
+//! - a record with no owner name, because its line starts with
+//!   whitespace, has the same owner as the one before it.
+//!
+//! # References
+//!
+//! - [RFC 1035 §5](https://tools.ietf.org/html/rfc1035#section-5) — Master files (November 1987)
+//! - [RFC 2308 §4](https://tools.ietf.org/html/rfc2308#section-4) — SOA Minimum Field (March 1998)
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::*;
+
+use crate::name::Name;
+use crate::presentation::{Fields, PresentationError, parse_ttl};
+use crate::record::Record;
+use crate::types::{Answer, QClass};
+use crate::wire::WireError;
+
+
+/// How many `$INCLUDE` directives can be nested inside one another before
+/// giving up, in case a file ends up including itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+
+/// Parses the contents of a zone file into a list of records, in the order
+/// they appear. If no origin is given, the file must set one with
+/// `$ORIGIN` before using any relative names.
+///
+/// Any `$INCLUDE` directives are read relative to the current directory.
+///
+/// # Examples
+///
+/// ```
+/// use dns::zonefile::parse_zone;
+///
+/// let zone = "
+/// $TTL 1h
+/// @     IN  SOA  ns1 hostmaster ( 1 2h 1h 2w 5m )
+///           NS   ns1
+/// ns1       A    127.0.0.1
+/// ";
+///
+/// let records = parse_zone(zone, Some("lookup.dog.".parse().unwrap())).unwrap();
+/// assert_eq!(records[2].to_string(), "ns1.lookup.dog. 3600 IN A 127.0.0.1");
+/// ```
+pub fn parse_zone(input: &str, origin: Option<Name>) -> Result<Vec<Answer>, ZoneError> {
+    let mut parser = Parser::new(origin);
+    parser.parse(input, None, 0)?;
+    Ok(parser.records)
+}
+
+/// Reads and parses a zone file from disk, with any `$INCLUDE` directives
+/// read relative to the directory the file is in.
+pub fn read_zone_file(path: &Path, origin: Option<Name>) -> Result<Vec<Answer>, ZoneError> {
+    let mut parser = Parser::new(origin);
+    parser.include(path, 0)?;
+    Ok(parser.records)
+}
+
+
+/// Something that can go wrong reading a zone file, along with where in
+/// the file it went wrong.
+#[derive(PartialEq, Debug)]
+pub struct ZoneError {
+
+    /// The file the error was in, if the zone was read from a file.
+    pub path: Option<PathBuf>,
+
+    /// The line number, starting from 1, of the start of the entry the
+    /// error was in.
+    pub line: usize,
+
+    /// What went wrong.
+    pub kind: ZoneErrorKind,
+}
+
+/// The kinds of thing that can go wrong reading a zone file.
+#[derive(PartialEq, Debug)]
+pub enum ZoneErrorKind {
+
+    /// A file could not be read.
+    IO(io::ErrorKind),
+
+    /// A record’s data could not be parsed.
+    Presentation(PresentationError),
+
+    /// A relative name was used before any origin was set.
+    MissingOrigin,
+
+    /// A record did not give an owner name, and there was no previous
+    /// record to take one from.
+    MissingOwner,
+
+    /// A record did not give a TTL, and there was neither a `$TTL`
+    /// directive nor a previous record to take one from.
+    MissingTTL,
+
+    /// A record did not say what type it was.
+    MissingType,
+
+    /// A directive that is not `$ORIGIN`, `$TTL`, or `$INCLUDE`.
+    UnknownDirective(String),
+
+    /// A directive was missing its argument, or its argument was invalid.
+    InvalidDirective(String),
+
+    /// Parentheses were opened without being closed, or the other way
+    /// around.
+    UnbalancedParentheses,
+
+    /// `$INCLUDE` directives were nested too deeply.
+    IncludeTooDeep,
+}
+
+impl From<PresentationError> for ZoneErrorKind {
+    fn from(error: PresentationError) -> Self {
+        Self::Presentation(error)
+    }
+}
+
+impl From<WireError> for ZoneErrorKind {
+    fn from(error: WireError) -> Self {
+        Self::Presentation(PresentationError::InvalidName(error))
+    }
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+
+        write!(f, "{}: {}", self.line, self.kind)
+    }
+}
+
+impl fmt::Display for ZoneErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(kind)                    => write!(f, "Could not read file: {:?}", kind),
+            Self::Presentation(e)             => write!(f, "{}", e),
+            Self::MissingOrigin               => write!(f, "Relative name used with no $ORIGIN"),
+            Self::MissingOwner                => write!(f, "Record has no owner name"),
+            Self::MissingTTL                  => write!(f, "Record has no TTL and there is no $TTL"),
+            Self::MissingType                 => write!(f, "Record has no type"),
+            Self::UnknownDirective(name)      => write!(f, "Unknown directive {:?}", name),
+            Self::InvalidDirective(name)      => write!(f, "Invalid {} directive", name),
+            Self::UnbalancedParentheses       => write!(f, "Unbalanced parentheses"),
+            Self::IncludeTooDeep              => write!(f, "$INCLUDE nested too deeply"),
+        }
+    }
+}
+
+
+/// The state that carries over from one entry in a zone file to the next.
+struct Parser {
+    origin: Option<Name>,
+    default_ttl: Option<u32>,
+    last_ttl: Option<u32>,
+    last_owner: Option<Name>,
+    last_class: QClass,
+    records: Vec<Answer>,
+}
+
+impl Parser {
+    fn new(origin: Option<Name>) -> Self {
+        let origin = origin.map(|o| {
+            if o.as_str().ends_with('.') { o } else { Name::unchecked(format!("{}.", o)) }
+        });
+
+        Self {
+            origin,
+            default_ttl: None,
+            last_ttl: None,
+            last_owner: None,
+            last_class: QClass::IN,
+            records: Vec::new(),
+        }
+    }
+
+    /// Reads and parses the file at the given path.
+    fn include(&mut self, path: &Path, depth: usize) -> Result<(), ZoneError> {
+        let contents = fs::read_to_string(path).map_err(|e| ZoneError {
+            path: Some(path.into()),
+            line: 0,
+            kind: ZoneErrorKind::IO(e.kind()),
+        })?;
+
+        self.parse(&contents, Some(path), depth)
+    }
+
+    fn parse(&mut self, input: &str, path: Option<&Path>, depth: usize) -> Result<(), ZoneError> {
+        let error = |line, kind| ZoneError { path: path.map(Into::into), line, kind };
+
+        for (line, entry) in entries(input).map_err(|(line, kind)| error(line, kind))? {
+            if let Some(directive) = entry.strip_prefix('$') {
+                self.directive(directive, path, line, depth)?;
+            }
+            else {
+                self.entry(&entry).map_err(|kind| error(line, kind))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles one of the dollar-sign directives.
+    fn directive(&mut self, input: &str, path: Option<&Path>, line: usize, depth: usize) -> Result<(), ZoneError> {
+        let error = |kind| ZoneError { path: path.map(Into::into), line, kind };
+
+        let mut fields = Fields::new(input).map_err(|e| error(e.into()))?;
+        let name = format!("${}", fields.next().unwrap_or_default().to_ascii_uppercase());
+        let invalid = || error(ZoneErrorKind::InvalidDirective(name.clone()));
+
+        match name.as_str() {
+            "$ORIGIN" => {
+                let origin = fields.next().ok_or_else(invalid)?;
+                self.origin = Some(self.qualify(origin).map_err(error)?);
+            }
+            "$TTL" => {
+                let ttl = fields.next().and_then(parse_ttl).ok_or_else(invalid)?;
+                self.default_ttl = Some(ttl);
+            }
+            "$INCLUDE" => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(error(ZoneErrorKind::IncludeTooDeep));
+                }
+
+                let file = fields.next().ok_or_else(invalid)?.trim_matches('"');
+                let file = match path.and_then(Path::parent) {
+                    Some(directory)  => directory.join(file),
+                    None             => PathBuf::from(file),
+                };
+
+                // The included file can have its own origin, but whatever
+                // it ends up as, the old one gets put back afterwards
+                // (RFC 1035 §5.1).
+                let origin = self.origin.clone();
+                if let Some(new_origin) = fields.next() {
+                    self.origin = Some(self.qualify(new_origin).map_err(error)?);
+                }
+
+                debug!("Including zone file {:?}", file);
+                let result = self.include(&file, depth + 1);
+                self.origin = origin;
+                result?;
+            }
+            _ => {
+                return Err(error(ZoneErrorKind::UnknownDirective(name)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses one record, which may have come from several lines.
+    fn entry(&mut self, input: &str) -> Result<(), ZoneErrorKind> {
+        let inherits_owner = input.starts_with(char::is_whitespace);
+        let mut fields = Fields::new(input)?.peekable();
+
+        let qname = if inherits_owner {
+            self.last_owner.clone().ok_or(ZoneErrorKind::MissingOwner)?
+        }
+        else {
+            let owner = fields.next().ok_or(ZoneErrorKind::MissingOwner)?;
+            self.qualify(owner)?
+        };
+
+        // The TTL and class can come in either order, and either or both
+        // can be left out.
+        let mut ttl = None;
+        let mut qclass = None;
+        while let Some(field) = fields.peek() {
+            if ttl.is_none() && field.starts_with(|c: char| c.is_ascii_digit()) {
+                ttl = Some(parse_ttl(field).ok_or_else(|| PresentationError::InvalidField { field: "TTL", value: (*field).into() })?);
+            }
+            else if qclass.is_none() && field.parse::<QClass>().is_ok() {
+                qclass = field.parse().ok();
+            }
+            else {
+                break;
+            }
+
+            fields.next();
+        }
+
+        let rtype = fields.next().ok_or(ZoneErrorKind::MissingType)?;
+        let data = fields.collect::<Vec<_>>().join(" ");
+        let mut record = Record::from_presentation(rtype, &data)?;
+        self.qualify_record(&mut record)?;
+
+        // Records without a TTL use the one from the $TTL directive, or,
+        // failing that, the one from the record before (RFC 2308 §4).
+        if ttl.is_some() {
+            self.last_ttl = ttl;
+        }
+        let ttl = ttl.or(self.default_ttl).or(self.last_ttl).ok_or(ZoneErrorKind::MissingTTL)?;
+
+        let qclass = qclass.unwrap_or(self.last_class);
+        self.last_class = qclass;
+        self.last_owner = Some(qname.clone());
+
+        self.records.push(Answer::Standard { qname, qclass, ttl, record });
+        Ok(())
+    }
+
+    /// Makes every domain name in the record’s data fully qualified.
+    fn qualify_record(&self, record: &mut Record) -> Result<(), ZoneErrorKind> {
+        let names = match record {
+            Record::CNAME(cname)  => vec![ &mut cname.domain ],
+            Record::MX(mx)        => vec![ &mut mx.exchange ],
+            Record::NS(ns)        => vec![ &mut ns.nameserver ],
+            Record::PTR(ptr)      => vec![ &mut ptr.cname ],
+            Record::RRSIG(rrsig)  => vec![ &mut rrsig.signer_name ],
+            Record::SOA(soa)      => vec![ &mut soa.mname, &mut soa.rname ],
+            Record::SRV(srv)      => vec![ &mut srv.target ],
+            _                     => vec![],
+        };
+
+        for name in names {
+            *name = self.qualify(name.as_str())?;
+        }
+
+        Ok(())
+    }
+
+    /// Turns a name as written in the zone file into a fully-qualified
+    /// name, replacing `@` with the origin, and appending the origin to
+    /// any name that does not end in a dot.
+    fn qualify(&self, input: &str) -> Result<Name, ZoneErrorKind> {
+        if input == "@" {
+            return self.origin.clone().ok_or(ZoneErrorKind::MissingOrigin);
+        }
+
+        let name = Fields::new(input)?.name("name")?;
+        if name.as_str().ends_with('.') {
+            return Ok(name);
+        }
+
+        let origin = self.origin.as_ref().ok_or(ZoneErrorKind::MissingOrigin)?;
+        if origin.is_root() {
+            Ok(format!("{}.", name).parse()?)
+        }
+        else {
+            Ok(format!("{}.{}", name, origin).parse()?)
+        }
+    }
+}
+
+
+/// Splits a zone file into its entries, joining together any lines inside
+/// parentheses and removing comments. Each entry comes with the line
+/// number it started on. Entries keep any whitespace at the start, as it
+/// means the owner name has been left out.
+fn entries(input: &str) -> Result<Vec<(usize, String)>, (usize, ZoneErrorKind)> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut line = 1;
+    let mut depth = 0_usize;
+    let mut quoted = false;
+    let mut comment = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+            comment = false;
+
+            if depth == 0 && ! quoted {
+                if ! current.trim().is_empty() {
+                    entries.push((start_line, current.trim_end().to_owned()));
+                }
+
+                current.clear();
+                start_line = line;
+                continue;
+            }
+
+            current.push(' ');
+            continue;
+        }
+
+        if comment {
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                continue;
+            }
+            '"'            => quoted = ! quoted,
+            ';' if ! quoted => { comment = true; continue; }
+            '(' if ! quoted => depth += 1,
+            ')' if ! quoted => depth = depth.checked_sub(1).ok_or((line, ZoneErrorKind::UnbalancedParentheses))?,
+            _ => {}
+        }
+
+        current.push(c);
+    }
+
+    if depth > 0 {
+        return Err((start_line, ZoneErrorKind::UnbalancedParentheses));
+    }
+
+    if ! current.trim().is_empty() {
+        entries.push((start_line, current.trim_end().to_owned()));
+    }
+
+    Ok(entries)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::record::*;
+
+    fn origin() -> Option<Name> {
+        Some("lookup.dog.".parse().unwrap())
+    }
+
+    fn lines(records: &[Answer]) -> Vec<String> {
+        records.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn simple_records() {
+        let zone = "\
+            lookup.dog. 300 IN A 127.0.0.1\n\
+            lookup.dog. 300 IN MX 10 mail.lookup.dog.\n";
+
+        assert_eq!(lines(&parse_zone(zone, None).unwrap()), vec![
+            "lookup.dog. 300 IN A 127.0.0.1",
+            "lookup.dog. 300 IN MX 10 mail.lookup.dog.",
+        ]);
+    }
+
+    #[test]
+    fn relative_names() {
+        let zone = "www 60 CNAME @\nmail 60 MX 10 mx1\n";
+
+        assert_eq!(lines(&parse_zone(zone, origin()).unwrap()), vec![
+            "www.lookup.dog. 60 IN CNAME lookup.dog.",
+            "mail.lookup.dog. 60 IN MX 10 mx1.lookup.dog.",
+        ]);
+    }
+
+    #[test]
+    fn origin_directive() {
+        let zone = "$ORIGIN dog.\nlookup 60 A 127.0.0.1\n$ORIGIN sub\nwww 60 A 127.0.0.2\n";
+
+        assert_eq!(lines(&parse_zone(zone, None).unwrap()), vec![
+            "lookup.dog. 60 IN A 127.0.0.1",
+            "www.sub.dog. 60 IN A 127.0.0.2",
+        ]);
+    }
+
+    #[test]
+    fn ttl_directive_and_inheritance() {
+        let zone = "$TTL 1h\na A 127.0.0.1\nb 5m A 127.0.0.2\n";
+        let records = parse_zone(zone, origin()).unwrap();
+
+        assert_eq!(lines(&records), vec![
+            "a.lookup.dog. 3600 IN A 127.0.0.1",
+            "b.lookup.dog. 300 IN A 127.0.0.2",
+        ]);
+    }
+
+    #[test]
+    fn previous_ttl_without_directive() {
+        let zone = "a 120 A 127.0.0.1\nb A 127.0.0.2\n";
+        let records = parse_zone(zone, origin()).unwrap();
+        assert_eq!(lines(&records)[1], "b.lookup.dog. 120 IN A 127.0.0.2");
+    }
+
+    #[test]
+    fn missing_ttl() {
+        let error = parse_zone("a A 127.0.0.1\n", origin()).unwrap_err();
+        assert_eq!(error, ZoneError { path: None, line: 1, kind: ZoneErrorKind::MissingTTL });
+    }
+
+    #[test]
+    fn class_and_ttl_either_way_round() {
+        let zone = "a IN 60 A 127.0.0.1\nb 60 IN A 127.0.0.2\nc CH 60 TXT woof\n";
+
+        assert_eq!(lines(&parse_zone(zone, origin()).unwrap()), vec![
+            "a.lookup.dog. 60 IN A 127.0.0.1",
+            "b.lookup.dog. 60 IN A 127.0.0.2",
+            "c.lookup.dog. 60 CH TXT \"woof\"",
+        ]);
+    }
+
+    #[test]
+    fn owner_inheritance() {
+        let zone = "a 60 A 127.0.0.1\n  60 AAAA ::1\n";
+        let records = parse_zone(zone, origin()).unwrap();
+        assert_eq!(lines(&records)[1], "a.lookup.dog. 60 IN AAAA ::1");
+    }
+
+    #[test]
+    fn missing_owner() {
+        let error = parse_zone("  60 A 127.0.0.1\n", origin()).unwrap_err();
+        assert_eq!(error.kind, ZoneErrorKind::MissingOwner);
+    }
+
+    #[test]
+    fn parentheses_and_comments() {
+        let zone = "\
+@ 3600 IN SOA ns1 hostmaster (  ; the SOA
+        2020010101  ; serial
+        2h 1h 2w    ; timers
+        5m )
+";
+
+        let records = parse_zone(zone, origin()).unwrap();
+        assert_eq!(records, vec![ Answer::Standard {
+            qname: "lookup.dog.".parse().unwrap(),
+            qclass: QClass::IN,
+            ttl: 3600,
+            record: Record::SOA(SOA {
+                mname: "ns1.lookup.dog.".parse().unwrap(),
+                rname: "hostmaster.lookup.dog.".parse().unwrap(),
+                serial: 2020010101,
+                refresh_interval: 7200,
+                retry_interval: 3600,
+                expire_limit: 1_209_600,
+                minimum_ttl: 300,
+            }),
+        }]);
+    }
+
+    #[test]
+    fn quoted_semicolons() {
+        let records = parse_zone("a 60 TXT \"v=DKIM1; k=rsa\" ; comment\n", origin()).unwrap();
+        assert_eq!(lines(&records), vec![ "a.lookup.dog. 60 IN TXT \"v=DKIM1; k=rsa\"" ]);
+    }
+
+    #[test]
+    fn unbalanced_parentheses() {
+        let error = parse_zone("a 60 TXT ( \"woof\"\n", origin()).unwrap_err();
+        assert_eq!(error.kind, ZoneErrorKind::UnbalancedParentheses);
+    }
+
+    #[test]
+    fn no_origin() {
+        let error = parse_zone("www 60 A 127.0.0.1\n", None).unwrap_err();
+        assert_eq!(error.kind, ZoneErrorKind::MissingOrigin);
+    }
+
+    #[test]
+    fn unknown_directive() {
+        let error = parse_zone("$GENERATE 1-10 a$ A 127.0.0.$\n", origin()).unwrap_err();
+        assert_eq!(error.kind, ZoneErrorKind::UnknownDirective("$GENERATE".into()));
+    }
+
+    #[test]
+    fn error_line_numbers() {
+        let error = parse_zone("a 60 A 127.0.0.1\n\n; comment\nb 60 A nope\n", origin()).unwrap_err();
+        assert_eq!(error.line, 4);
+    }
+
+    #[test]
+    fn includes() {
+        let directory = std::env::temp_dir().join(format!("dog-zonefile-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("main.zone"), "$TTL 60\n$INCLUDE hosts.zone hosts\nafter A 127.0.0.3\n").unwrap();
+        fs::write(directory.join("hosts.zone"), "one A 127.0.0.1\ntwo A 127.0.0.2\n").unwrap();
+
+        let records = read_zone_file(&directory.join("main.zone"), origin());
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(lines(&records.unwrap()), vec![
+            "one.hosts.lookup.dog. 60 IN A 127.0.0.1",
+            "two.hosts.lookup.dog. 60 IN A 127.0.0.2",
+            "after.lookup.dog. 60 IN A 127.0.0.3",
+        ]);
+    }
+
+    #[test]
+    fn missing_include() {
+        let error = parse_zone("$INCLUDE /nonexistent/dog.zone\n", origin()).unwrap_err();
+        assert_eq!(error.kind, ZoneErrorKind::IO(io::ErrorKind::NotFound));
+    }
+}
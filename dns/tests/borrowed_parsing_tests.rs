@@ -0,0 +1,139 @@
+use dns::{Response, QClass, ParseOptions, Wire, WireError};
+use dns::record::{Record, A};
+
+
+/// A response to an A query for `lookup.dog`, with a CNAME answer pointing
+/// at `www.lookup.dog` and an A answer for that, both using compression.
+fn cname_response() -> Vec<u8> {
+    vec![
+        0xbe, 0xef,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01,  // counts (1, 2, 0, 1)
+
+        // query:
+        0x06, 0x6c, 0x6f, 0x6f, 0x6b, 0x75, 0x70, 0x03, 0x64, 0x6f, 0x67, 0x00,  // name
+        0x00, 0x01,  // type A
+        0x00, 0x01,  // class IN
+
+        // answer 1:
+        0xc0, 0x0c,  // name (pointer to the query)
+        0x00, 0x05,  // type CNAME
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x00, 0x3c,  // TTL
+        0x00, 0x06,  // data length
+        0x03, 0x77, 0x77, 0x77, 0xc0, 0x0c,  // www + pointer
+
+        // answer 2:
+        0xc0, 0x28,  // name (pointer to the CNAME’s data)
+        0x00, 0x01,  // type A
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x00, 0x3c,  // TTL
+        0x00, 0x04,  // data length
+        0x7f, 0x00, 0x00, 0x01,  // address
+
+        // additional:
+        0x00,  // name
+        0x00, 0x29,  // type OPT
+        0x02, 0x00,  // UDP payload size
+        0x00, 0x00, 0x00, 0x00,  // higher bits, version, flags
+        0x00, 0x00,  // data length
+    ]
+}
+
+
+#[test]
+fn same_as_owned() {
+    let bytes = cname_response();
+    let response = Response::parse_ref(&bytes).unwrap();
+    assert_eq!(response.to_response(), Response::from_bytes(&bytes));
+}
+
+#[test]
+fn sections() {
+    let bytes = cname_response();
+    let response = Response::parse_ref(&bytes).unwrap();
+
+    assert_eq!(response.transaction_id, 0xbeef);
+    assert_eq!(response.queries().len(), 1);
+    assert_eq!(response.answers().len(), 2);
+    assert_eq!(response.authorities().len(), 0);
+    assert_eq!(response.additionals().len(), 1);
+    assert!(response.additionals()[0].is_pseudo());
+}
+
+#[test]
+fn compressed_names() {
+    let bytes = cname_response();
+    let response = Response::parse_ref(&bytes).unwrap();
+    let answer = response.answers()[1];
+
+    assert!(answer.qname.matches(&"WWW.Lookup.Dog".parse().unwrap()));
+    assert!(! answer.qname.matches(&"lookup.dog".parse().unwrap()));
+    assert_eq!(answer.qname.labels().collect::<Result<Vec<_>, _>>(),
+               Ok(vec![ &b"www"[..], &b"lookup"[..], &b"dog"[..] ]));
+    assert_eq!(answer.qname.to_name(), Ok("www.lookup.dog.".parse().unwrap()));
+}
+
+#[test]
+fn record_data() {
+    let bytes = cname_response();
+    let response = Response::parse_ref(&bytes).unwrap();
+    let answer = response.answers()[1];
+
+    assert_eq!(answer.qclass(), QClass::IN);
+    assert_eq!(answer.ttl(), 60);
+    assert_eq!(answer.rdata, &[ 0x7f, 0x00, 0x00, 0x01 ]);
+    assert_eq!(answer.record(), Ok(Record::A(A { address: "127.0.0.1".parse().unwrap() })));
+}
+
+#[test]
+fn truncated_record() {
+    let bytes = cname_response();
+    assert_eq!(Response::parse_ref(&bytes[.. bytes.len() - 1]), Err(WireError::IO));
+}
+
+#[test]
+fn bad_pointer_only_fails_when_read() {
+    let mut bytes = cname_response();
+    bytes[29] = 0x1c;  // the answer’s name now points at itself
+    let response = Response::parse_ref(&bytes).unwrap();
+
+    let answer = response.answers()[0];
+    assert!(matches!(answer.qname.to_name(), Err(WireError::TooMuchRecursion(_))));
+    assert!(matches!(answer.qname.labels().last(), Some(Err(WireError::TooMuchRecursion(_)))));
+    assert!(response.to_response().is_err());
+}
+
+#[test]
+fn limits_from_options() {
+    let bytes = cname_response();
+    let options = ParseOptions { max_pointer_jumps: 1, .. ParseOptions::default() };
+    let response = Response::parse_ref_with(&bytes, &options).unwrap();
+
+    // The second answer’s name points into the first’s data, which points
+    // back to the query, so it takes two jumps to read.
+    assert!(response.answers()[0].qname.to_name().is_ok());
+    assert_eq!(response.answers()[1].qname.to_name(),
+               Err(WireError::TooMuchRecursion(vec![ 0x28 ])));
+}
+
+#[test]
+fn trailing_bytes_with_options() {
+    let mut bytes = cname_response();
+    bytes.extend(&[ 0xde, 0xad ]);
+
+    assert!(Response::parse_ref(&bytes).is_ok());
+    assert_eq!(Response::parse_ref_with(&bytes, &ParseOptions::strict()),
+               Err(WireError::TrailingBytes(2)));
+}
+
+#[test]
+fn only_kept_records() {
+    let mut bytes = cname_response();
+    bytes[29] = 0x1c;  // the first answer can no longer be read
+    let response = Response::parse_ref(&bytes).unwrap();
+
+    let kept = response.to_response_where(|answer| answer.qtype == A::RR_TYPE).unwrap();
+    assert_eq!(kept.answers.len(), 1);
+    assert!(kept.additionals.is_empty());
+}
@@ -0,0 +1,123 @@
+use std::net::Ipv4Addr;
+
+use dns::{Answer, ErrorCode, QClass};
+use dns::record::*;
+
+
+fn answer(record: Record) -> Answer {
+    Answer::Standard { qname: "lookup.dog".parse().unwrap(), qclass: QClass::IN, ttl: 300, record }
+}
+
+
+#[test]
+fn standard_answer() {
+    let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) });
+    assert_eq!(answer(record).to_string(), "lookup.dog. 300 IN A 127.0.0.1");
+}
+
+#[test]
+fn unknown_class() {
+    let answer = Answer::Standard {
+        qname: "lookup.dog.".parse().unwrap(),
+        qclass: QClass::Other(254),
+        ttl: 0,
+        record: Record::TXT(TXT::from_text("woof")),
+    };
+
+    assert_eq!(answer.to_string(), "lookup.dog. 0 CLASS254 TXT \"woof\"");
+}
+
+#[test]
+fn unknown_type() {
+    let record = Record::Other { type_number: UnknownQtype::UnheardOf(731), bytes: vec![ 0xDE, 0xAD ] };
+    assert_eq!(answer(record).to_string(), "lookup.dog. 300 IN TYPE731 \\# 2 DEAD");
+}
+
+#[test]
+fn known_but_unparsed_type() {
+    let record = Record::Other { type_number: UnknownQtype::from(18), bytes: Vec::new() };
+    assert_eq!(answer(record).to_string(), "lookup.dog. 300 IN AFSDB \\# 0");
+}
+
+#[test]
+fn pseudo_answer() {
+    let answer = Answer::Pseudo {
+        qname: dns::Name::root(),
+        opt: OPT { udp_payload_size: 512, higher_bits: 0, edns0_version: 0, flags: 0, data: Vec::new() },
+    };
+
+    assert_eq!(answer.to_string(), "; . OPT 512 0 0 0");
+}
+
+
+#[test]
+fn parse_by_mnemonic() {
+    let record = Record::from_presentation("mx", "10 mail.lookup.dog.").unwrap();
+    assert_eq!(record, Record::MX(MX { preference: 10, exchange: "mail.lookup.dog.".parse().unwrap() }));
+}
+
+#[test]
+fn parse_generic_known_type() {
+    let record = Record::from_presentation("TYPE1", r"\# 4 7F000001").unwrap();
+    assert_eq!(record, Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) }));
+}
+
+#[test]
+fn parse_generic_unknown_type() {
+    let record = Record::from_presentation("TYPE731", r"\# 2 DEAD").unwrap();
+    assert_eq!(record, Record::Other { type_number: UnknownQtype::UnheardOf(731), bytes: vec![ 0xDE, 0xAD ] });
+    assert_eq!(record.to_string(), r"\# 2 DEAD");
+}
+
+#[test]
+fn parse_generic_wrong_length() {
+    assert!(Record::from_presentation("TYPE731", r"\# 3 DEAD").is_err());
+}
+
+#[test]
+fn parse_unknown_type() {
+    assert_eq!(Record::from_presentation("WOOF", "1"),
+               Err(dns::presentation::PresentationError::UnknownType("WOOF".into())));
+}
+
+#[test]
+fn parse_displayed_records() {
+    let records = vec![
+        Record::CAA(CAA { critical: true, tag: "iodef".into(), value: "mailto:\"dog\"@lookup.dog".into() }),
+        Record::TXT(TXT::from_text("a".repeat(300))),
+        Record::SRV(SRV { priority: 0, weight: 5, port: 443, target: "lookup.dog.".parse().unwrap() }),
+    ];
+
+    for record in records {
+        let parsed = Record::from_presentation(&record.type_mnemonic(), &record.to_string()).unwrap();
+        assert_eq!(parsed, record);
+    }
+}
+
+#[test]
+fn every_type_name() {
+    let names = dns::qtype_names();
+    assert_eq!(&names[.. 2], &[ "A", "AAAA" ]);
+    assert!(names.contains(&"ANY"));
+
+    for name in names {
+        let number = dns::find_qtype_number(name).or_else(|| find_other_qtype_number(name)).unwrap();
+        assert_eq!(dns::find_qtype_name(number), Some(name));
+    }
+}
+
+#[test]
+fn rcode_mnemonics() {
+    assert_eq!(ErrorCode::NXDomain.mnemonic(), Some("NXDOMAIN"));
+    assert_eq!(ErrorCode::Other(11).mnemonic(), None);
+    assert_eq!(ErrorCode::mnemonic_of(None), Some("NOERROR"));
+}
+
+#[test]
+fn parse_rcodes() {
+    assert_eq!("servfail".parse::<ErrorCode>(), Ok(ErrorCode::ServerFailure));
+    assert_eq!("16".parse::<ErrorCode>(), Ok(ErrorCode::BadVersion));
+    assert_eq!("NOERROR".parse::<ErrorCode>(), Err(()));
+    assert_eq!(ErrorCode::parse_optional("NOERROR"), Some(None));
+    assert_eq!(ErrorCode::parse_optional("4096"), None);
+}
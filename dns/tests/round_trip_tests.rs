@@ -0,0 +1,304 @@
+//! Property tests that write out randomly-generated messages and read them
+//! back in, checking that the read and write paths agree with each other.
+//!
+//! The strategies here only build values that can be written out, so any
+//! value that fails to encode is as much a failure as one that changes on
+//! the way through. When a case fails, proptest shrinks it down to the
+//! smallest message that still fails, and prints that.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use proptest::prelude::*;
+use proptest::collection::vec;
+use proptest::option;
+
+use dns::{Request, Response, Query, Answer, Flags, QClass, Name, ParseOptions, WriteOptions};
+use dns::record::*;
+
+
+// ---- names, classes, and flags ----
+
+/// Names of up to four labels, mixing cases to check that nothing along the
+/// way depends on it.
+fn names() -> impl Strategy<Value = Name> {
+    vec("[a-zA-Z0-9_-]{1,20}", 0 ..= 4).prop_map(|labels| {
+        if labels.is_empty() {
+            Name::root()
+        }
+        else {
+            format!("{}.", labels.join(".")).parse().expect("Labels are valid")
+        }
+    })
+}
+
+fn classes() -> impl Strategy<Value = QClass> {
+    prop_oneof![
+        Just(QClass::IN),
+        Just(QClass::CH),
+        Just(QClass::HS),
+        any::<u16>().prop_filter("Known class number", |n| ! matches!(n, 1 | 3 | 4)).prop_map(QClass::Other),
+    ]
+}
+
+fn flags() -> impl Strategy<Value = Flags> {
+    any::<u16>().prop_map(Flags::from_u16)
+}
+
+/// Some bytes, but not so many that a message could stop fitting in one.
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0 .. 64)
+}
+
+
+// ---- records ----
+
+fn a_records() -> impl Strategy<Value = Record> {
+    any::<u32>().prop_map(|ip| Record::A(A { address: Ipv4Addr::from(ip) }))
+}
+
+fn aaaa_records() -> impl Strategy<Value = Record> {
+    any::<u128>().prop_map(|ip| Record::AAAA(AAAA { address: Ipv6Addr::from(ip) }))
+}
+
+fn caa_records() -> impl Strategy<Value = Record> {
+    (any::<bool>(), "[a-z0-9]{1,15}", ".{0,40}")
+        .prop_map(|(critical, tag, value)| Record::CAA(CAA { critical, tag, value }))
+}
+
+fn cname_records() -> impl Strategy<Value = Record> {
+    names().prop_map(|domain| Record::CNAME(CNAME { domain }))
+}
+
+fn dnskey_records() -> impl Strategy<Value = Record> {
+    (any::<u16>(), any::<u8>(), any::<u8>(), bytes())
+        .prop_map(|(flags, protocol, algorithm, public_key)| Record::DNSKEY(DNSKEY { flags, protocol, algorithm, public_key }))
+}
+
+fn ds_records() -> impl Strategy<Value = Record> {
+    (any::<u16>(), any::<u8>(), any::<u8>(), bytes())
+        .prop_map(|(key_tag, algorithm, digest_type, digest)| Record::DS(DS { key_tag, algorithm, digest_type, digest }))
+}
+
+fn mx_records() -> impl Strategy<Value = Record> {
+    (any::<u16>(), names()).prop_map(|(preference, exchange)| Record::MX(MX { preference, exchange }))
+}
+
+fn ns_records() -> impl Strategy<Value = Record> {
+    names().prop_map(|nameserver| Record::NS(NS { nameserver }))
+}
+
+fn ptr_records() -> impl Strategy<Value = Record> {
+    names().prop_map(|cname| Record::PTR(PTR { cname }))
+}
+
+fn rrsig_records() -> impl Strategy<Value = Record> {
+    (any::<u16>(), any::<u8>(), any::<u8>(), any::<u32>(), any::<u32>(), any::<u32>(), any::<u16>(), names(), bytes())
+        .prop_map(|(type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature)| {
+            Record::RRSIG(RRSIG { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature })
+        })
+}
+
+fn soa_records() -> impl Strategy<Value = Record> {
+    (names(), names(), any::<u32>(), any::<u32>(), any::<u32>(), any::<u32>(), any::<u32>())
+        .prop_map(|(mname, rname, serial, refresh_interval, retry_interval, expire_limit, minimum_ttl)| {
+            Record::SOA(SOA { mname, rname, serial, refresh_interval, retry_interval, expire_limit, minimum_ttl })
+        })
+}
+
+fn srv_records() -> impl Strategy<Value = Record> {
+    (any::<u16>(), any::<u16>(), any::<u16>(), names())
+        .prop_map(|(priority, weight, port, target)| Record::SRV(SRV { priority, weight, port, target }))
+}
+
+/// There has to be at least one string, and none of them can be longer
+/// than a length byte can say.
+fn txt_records() -> impl Strategy<Value = Record> {
+    vec(vec(any::<u8>(), 0 ..= 255), 1 .. 4).prop_map(|strings| Record::TXT(TXT { strings }))
+}
+
+/// Unknown records must not have the type number of a record that gets
+/// parsed into its own variant.
+fn unknown_records() -> impl Strategy<Value = Record> {
+    const PARSED_TYPES: [u16; 14] = [ 1, 2, 5, 6, 12, 15, 16, 28, 33, 41, 43, 46, 48, 257 ];
+
+    (any::<u16>().prop_filter("Parsed type number", |n| ! PARSED_TYPES.contains(n)), bytes())
+        .prop_map(|(number, bytes)| Record::Other { type_number: UnknownQtype::from(number), bytes })
+}
+
+fn records() -> impl Strategy<Value = Record> {
+    prop_oneof![
+        a_records(), aaaa_records(), caa_records(), cname_records(), dnskey_records(),
+        ds_records(), mx_records(), ns_records(), ptr_records(), rrsig_records(),
+        soa_records(), srv_records(), txt_records(), unknown_records(),
+    ]
+}
+
+fn opts() -> impl Strategy<Value = OPT> {
+    (any::<u16>(), any::<u8>(), any::<u8>(), any::<u16>(), bytes())
+        .prop_map(|(udp_payload_size, higher_bits, edns0_version, flags, data)| OPT { udp_payload_size, higher_bits, edns0_version, flags, data })
+}
+
+
+// ---- messages ----
+
+fn queries() -> impl Strategy<Value = Query> {
+    (names(), classes(), any::<u16>()).prop_map(|(qname, qclass, qtype)| Query { qname, qclass, qtype })
+}
+
+/// A standard answer with a random name, class, and TTL, holding a record
+/// from the given strategy.
+fn standard_answers(records: impl Strategy<Value = Record>) -> impl Strategy<Value = Answer> {
+    (names(), classes(), any::<u32>(), records)
+        .prop_map(|(qname, qclass, ttl, record)| Answer::Standard { qname, qclass, ttl, record })
+}
+
+fn pseudo_answers() -> impl Strategy<Value = Answer> {
+    (names(), opts()).prop_map(|(qname, opt)| Answer::Pseudo { qname, opt })
+}
+
+fn answers() -> impl Strategy<Value = Answer> {
+    prop_oneof![
+        7 => standard_answers(records()),
+        1 => pseudo_answers(),
+    ]
+}
+
+fn requests() -> impl Strategy<Value = Request> {
+    (any::<u16>(), flags(), vec(queries(), 0 .. 4), option::of(opts()))
+        .prop_map(|(transaction_id, flags, queries, additional)| Request { transaction_id, flags, queries, additional })
+}
+
+fn responses() -> impl Strategy<Value = Response> {
+    (any::<u16>(), flags(), vec(queries(), 0 .. 4), vec(answers(), 0 .. 4), vec(answers(), 0 .. 4), vec(answers(), 0 .. 4))
+        .prop_map(|(transaction_id, flags, queries, answers, authorities, additionals)| {
+            Response { transaction_id, flags, queries, answers, authorities, additionals }
+        })
+}
+
+/// A response with nothing in it but one answer from the given strategy.
+fn responses_with(answers: impl Strategy<Value = Answer>) -> impl Strategy<Value = Response> {
+    answers.prop_map(|answer| {
+        let mut flags = Flags::query();
+        flags.response = true;
+
+        Response {
+            transaction_id: 0xABCD,
+            flags,
+            queries: Vec::new(),
+            answers: vec![ answer ],
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    })
+}
+
+
+/// Writes the response out both with and without name compression, and
+/// checks that reading each one back gives the same response.
+fn check_response(response: &Response) -> Result<(), TestCaseError> {
+    for options in [ WriteOptions { compress_names: true }, WriteOptions { compress_names: false } ] {
+        let bytes = response.to_bytes_with(options)
+                            .map_err(|e| TestCaseError::fail(format!("Failed to write out ({:?}): {:?}", options, e)))?;
+
+        let read = Response::from_bytes_with(&bytes, ParseOptions::strict())
+                            .map_err(|e| TestCaseError::fail(format!("Failed to read back ({:?}): {:?}", options, e)))?;
+
+        prop_assert_eq!(&read, response, "Response changed on the way through ({:?})", options);
+    }
+
+    Ok(())
+}
+
+
+proptest! {
+    #[test]
+    fn requests_round_trip(request in requests()) {
+        let bytes = request.to_bytes()
+                           .map_err(|e| TestCaseError::fail(format!("Failed to write out: {:?}", e)))?;
+
+        let read = Request::from_bytes(&bytes)
+                           .map_err(|e| TestCaseError::fail(format!("Failed to read back: {:?}", e)))?;
+
+        prop_assert_eq!(read, request);
+    }
+
+    #[test]
+    fn responses_round_trip(response in responses()) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn a_records_round_trip(response in responses_with(standard_answers(a_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn aaaa_records_round_trip(response in responses_with(standard_answers(aaaa_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn caa_records_round_trip(response in responses_with(standard_answers(caa_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn cname_records_round_trip(response in responses_with(standard_answers(cname_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn dnskey_records_round_trip(response in responses_with(standard_answers(dnskey_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn ds_records_round_trip(response in responses_with(standard_answers(ds_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn mx_records_round_trip(response in responses_with(standard_answers(mx_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn ns_records_round_trip(response in responses_with(standard_answers(ns_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn ptr_records_round_trip(response in responses_with(standard_answers(ptr_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn rrsig_records_round_trip(response in responses_with(standard_answers(rrsig_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn soa_records_round_trip(response in responses_with(standard_answers(soa_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn srv_records_round_trip(response in responses_with(standard_answers(srv_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn txt_records_round_trip(response in responses_with(standard_answers(txt_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn unknown_records_round_trip(response in responses_with(standard_answers(unknown_records()))) {
+        check_response(&response)?;
+    }
+
+    #[test]
+    fn opt_records_round_trip(response in responses_with(pseudo_answers())) {
+        check_response(&response)?;
+    }
+}
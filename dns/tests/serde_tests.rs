@@ -0,0 +1,98 @@
+use std::net::Ipv4Addr;
+
+use dns::{Response, Query, Answer, Flags, QClass, ErrorCode};
+use dns::record::*;
+use serde_json::json;
+
+
+fn round_trip<T>(value: &T) -> T
+where T: serde::Serialize + serde::de::DeserializeOwned
+{
+    let text = serde_json::to_string(value).unwrap();
+    serde_json::from_str(&text).unwrap()
+}
+
+fn answer(record: Record) -> Answer {
+    Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl: 300, record }
+}
+
+
+#[test]
+fn a_record() {
+    let answer = answer(Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) }));
+
+    assert_eq!(serde_json::to_value(&answer).unwrap(),
+               json!({ "Standard": { "name": "lookup.dog.", "class": "IN", "ttl": 300, "record": { "A": { "address": "127.0.0.1" } } } }));
+    assert_eq!(round_trip(&answer), answer);
+}
+
+#[test]
+fn whole_response() {
+    let response = Response {
+        transaction_id: 0xABCD,
+        flags: Flags { response: true, error_code: Some(ErrorCode::NXDomain), .. Flags::query() },
+        queries: vec![ Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::CH, qtype: 16 } ],
+        answers: vec![ answer(Record::MX(MX { preference: 10, exchange: "mail.lookup.dog.".parse().unwrap() })) ],
+        authorities: Vec::new(),
+        additionals: vec![ Answer::Pseudo { qname: ".".parse().unwrap(), opt: OPT { udp_payload_size: 1232, higher_bits: 0, edns0_version: 0, flags: 0x8000, data: vec![ 0, 10, 0, 0 ] } } ],
+    };
+
+    assert_eq!(round_trip(&response), response);
+}
+
+#[test]
+fn binary_fields() {
+    let dnskey = Record::DNSKEY(DNSKEY { flags: 257, protocol: 3, algorithm: 8, public_key: vec![ 1, 2, 3 ] });
+    let ds = Record::DS(DS { key_tag: 20326, algorithm: 8, digest_type: 2, digest: vec![ 0xDE, 0xAD ] });
+
+    assert_eq!(serde_json::to_value(&dnskey).unwrap()["DNSKEY"]["public_key"], json!("AQID"));
+    assert_eq!(serde_json::to_value(&ds).unwrap()["DS"]["digest"], json!("DEAD"));
+    assert_eq!(round_trip(&dnskey), dnskey);
+    assert_eq!(round_trip(&ds), ds);
+}
+
+#[test]
+fn txt_text() {
+    let record = Record::TXT(TXT { strings: vec![ b"v=spf1 ".to_vec(), b"-all".to_vec() ] });
+
+    assert_eq!(serde_json::to_value(&record).unwrap(),
+               json!({ "TXT": { "encoding": "text", "strings": [ "v=spf1 ", "-all" ] } }));
+    assert_eq!(round_trip(&record), record);
+}
+
+#[test]
+fn txt_not_utf8() {
+    let record = Record::TXT(TXT { strings: vec![ b"woof".to_vec(), vec![ 0xFF, 0xFE ] ] });
+
+    assert_eq!(serde_json::to_value(&record).unwrap(),
+               json!({ "TXT": { "encoding": "base64", "strings": [ "d29vZg==", "//4=" ] } }));
+    assert_eq!(round_trip(&record), record);
+}
+
+#[test]
+fn txt_bad_base64() {
+    let text = r#"{ "TXT": { "encoding": "base64", "strings": [ "!!!" ] } }"#;
+    assert!(serde_json::from_str::<Record>(text).is_err());
+}
+
+#[test]
+fn unknown_type() {
+    let record = Record::Other { type_number: UnknownQtype::UnheardOf(731), bytes: vec![ 1, 2 ] };
+
+    assert_eq!(serde_json::to_value(&record).unwrap(),
+               json!({ "Other": { "type_number": 731, "bytes": "AQI=" } }));
+    assert_eq!(round_trip(&record), record);
+}
+
+#[test]
+fn error_codes() {
+    assert_eq!(serde_json::to_value(ErrorCode::NXDomain).unwrap(), json!("NXDOMAIN"));
+    assert_eq!(serde_json::to_value(ErrorCode::Other(23)).unwrap(), json!("23"));
+    assert_eq!(round_trip(&ErrorCode::Other(23)), ErrorCode::Other(23));
+}
+
+#[test]
+fn invalid_name() {
+    let text = format!(r#"{{ "name": "{}.dog.", "class": "IN", "type": 1 }}"#, "a".repeat(64));
+    assert!(serde_json::from_str::<Query>(&text).is_err());
+}
@@ -0,0 +1,51 @@
+use std::io::Cursor;
+
+use dns::{read_tcp_message, write_tcp_message, frame_tcp_message, TcpMessageBuffer};
+
+
+#[test]
+fn write_then_read() {
+    let mut bytes = Vec::new();
+    write_tcp_message(&mut bytes, &[ 0xAB, 0xCD, 0xEF ]).unwrap();
+    write_tcp_message(&mut bytes, &[]).unwrap();
+    assert_eq!(bytes, vec![ 0x00, 0x03, 0xAB, 0xCD, 0xEF, 0x00, 0x00 ]);
+
+    let mut reader = Cursor::new(bytes);
+    assert_eq!(read_tcp_message(&mut reader).unwrap(), Some(vec![ 0xAB, 0xCD, 0xEF ]));
+    assert_eq!(read_tcp_message(&mut reader).unwrap(), Some(vec![]));
+    assert_eq!(read_tcp_message(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn read_cut_short() {
+    let mut reader = Cursor::new(vec![ 0x00, 0x05, 0xAB ]);
+    assert!(read_tcp_message(&mut reader).is_err());
+}
+
+#[test]
+fn read_cut_short_in_length() {
+    let mut reader = Cursor::new(vec![ 0x00 ]);
+    assert!(read_tcp_message(&mut reader).is_err());
+}
+
+#[test]
+fn too_long_to_frame() {
+    assert!(frame_tcp_message(&vec![ 0; 65_535 ]).is_ok());
+    assert!(frame_tcp_message(&vec![ 0; 65_536 ]).is_err());
+}
+
+#[test]
+fn buffer_split_across_reads() {
+    let mut buffer = TcpMessageBuffer::new();
+    buffer.extend(&[ 0x00 ]);
+    assert_eq!(buffer.next_message(), None);
+
+    buffer.extend(&[ 0x02, 0x12 ]);
+    assert_eq!(buffer.next_message(), None);
+
+    buffer.extend(&[ 0x34, 0x00, 0x01, 0x56 ]);
+    assert_eq!(buffer.next_message(), Some(vec![ 0x12, 0x34 ]));
+    assert_eq!(buffer.next_message(), Some(vec![ 0x56 ]));
+    assert_eq!(buffer.next_message(), None);
+    assert!(buffer.is_empty());
+}
@@ -0,0 +1,88 @@
+use std::net::Ipv4Addr;
+
+use dns::{Request, Response, Query, Answer, Flags, ErrorCode, QClass, WriteOptions, qtype};
+use dns::record::*;
+
+
+fn query() -> Query {
+    Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: qtype!(MX) }
+}
+
+fn answer(record: Record) -> Answer {
+    Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl: 300, record }
+}
+
+
+#[test]
+fn request_round_trip() {
+    let request = Request {
+        transaction_id: 0xceac,
+        flags: Flags::query(),
+        queries: vec![ query() ],
+        additional: Some(Request::additional_record()),
+    };
+
+    let bytes = request.to_bytes().unwrap();
+    assert_eq!(Request::from_bytes(&bytes), Ok(request));
+}
+
+#[test]
+fn response_round_trip() {
+    let mut flags = Flags::query();
+    flags.response = true;
+
+    let response = Response {
+        transaction_id: 0xceac,
+        flags,
+        queries: vec![ query() ],
+        answers: vec![
+            answer(Record::MX(MX { preference: 10, exchange: "mail.lookup.dog.".parse().unwrap() })),
+            answer(Record::TXT(TXT::from_text("x".repeat(300)))),
+        ],
+        authorities: vec![
+            answer(Record::SOA(SOA {
+                mname: "ns1.lookup.dog.".parse().unwrap(), rname: "hostmaster.lookup.dog.".parse().unwrap(),
+                serial: 2020, refresh_interval: 3600, retry_interval: 600,
+                expire_limit: 86400, minimum_ttl: 300,
+            })),
+        ],
+        additionals: vec![
+            answer(Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) })),
+            Answer::Pseudo { qname: "".parse().unwrap(), opt: Request::additional_record() },
+        ],
+    };
+
+    let bytes = response.to_bytes().unwrap();
+    assert_eq!(Response::from_bytes(&bytes), Ok(response.clone()));
+
+    let uncompressed = response.to_bytes_with(WriteOptions { compress_names: false }).unwrap();
+    assert!(uncompressed.len() > bytes.len());
+    assert_eq!(Response::from_bytes(&uncompressed), Ok(response));
+}
+
+#[test]
+fn header_bits_round_trip() {
+    let flags = Flags::from_u16(0b_1010_0001_1100_0000);
+    assert_eq!(flags.opcode, 4);
+    assert!(flags.reserved);
+    assert_eq!(flags.to_u16(), 0b_1010_0001_1100_0000);
+}
+
+#[test]
+fn error_code_round_trip() {
+    let mut flags = Flags::query();
+    flags.response = true;
+    flags.error_code = Some(ErrorCode::NXDomain);
+
+    let response = Response {
+        transaction_id: 0xceac,
+        flags,
+        queries: vec![ query() ],
+        answers: Vec::new(),
+        authorities: Vec::new(),
+        additionals: Vec::new(),
+    };
+
+    let bytes = response.to_bytes().unwrap();
+    assert_eq!(Response::from_bytes(&bytes), Ok(response));
+}
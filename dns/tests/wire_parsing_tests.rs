@@ -1,7 +1,240 @@
-use dns::Response;
+use dns::{Request, Response, ParseOptions, WireError, ErrorLocation, Section};
 
 
 #[test]
 fn parse_nothing() {
     assert!(Response::from_bytes(&[]).is_err());
 }
+
+
+/// A response with one MX answer, whose length field is given.
+fn mx_response(rdata_len: u8, trailing: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![
+        0x0d, 0xcd,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,  // counts (0, 1, 0, 0)
+
+        // answer:
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // name
+        0x00, 0x0f,  // type MX
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x03, 0xa5,  // TTL
+        0x00, rdata_len,  // data length
+        0x00, 0x0a,  // preference
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // exchange
+    ];
+
+    bytes.extend(trailing);
+    bytes
+}
+
+#[test]
+fn well_formed_strict() {
+    let bytes = mx_response(7, &[]);
+    assert!(Response::from_bytes_with(&bytes, ParseOptions::strict()).is_ok());
+}
+
+#[test]
+fn trailing_bytes() {
+    let bytes = mx_response(7, &[ 0xde, 0xad ]);
+    assert!(Response::from_bytes(&bytes).is_ok());
+    assert_eq!(Response::from_bytes_with(&bytes, ParseOptions::strict()),
+               Err(WireError::TrailingBytes(2)));
+}
+
+#[test]
+fn count_too_high() {
+    let mut bytes = mx_response(7, &[]);
+    bytes[7] = 0x02;  // two answers, but only one follows
+    assert_eq!(Response::from_bytes(&bytes),
+               Err(WireError::WrongCount { section: Section::Answer, declared: 2, found: 1 }));
+}
+
+#[test]
+fn count_too_low() {
+    let mut bytes = mx_response(7, &[]);
+    bytes[7] = 0x00;  // no answers, but one follows
+    assert!(Response::from_bytes(&bytes).is_ok());
+    assert_eq!(Response::from_bytes_with(&bytes, ParseOptions::strict()),
+               Err(WireError::WrongCount { section: Section::Additional, declared: 0, found: 1 }));
+}
+
+#[test]
+fn rdata_under_read() {
+    let bytes = mx_response(9, &[ 0x00, 0x00 ]);
+    assert_eq!(Response::from_bytes(&bytes).unwrap_err().cause(),
+               &WireError::WrongLength { expected: 9, got: 7 });
+}
+
+#[test]
+fn rdata_over_read() {
+    let bytes = mx_response(5, &[]);
+    assert_eq!(Response::from_bytes(&bytes).unwrap_err().cause(),
+               &WireError::WrongLength { expected: 5, got: 7 });
+}
+
+
+/// A response with one A answer whose name is `a.b.c.dog`, written as a
+/// chain of compression pointers that each add one label.
+fn chained_response() -> Vec<u8> {
+    vec![
+        0x0d, 0xcd,  // transaction ID
+        0x81, 0x80,  // flags (standard query, response, no error)
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,  // counts (0, 1, 0, 0)
+
+        // answer:
+        0x01, 0x61, 0xc0, 0x27,  // "a", then a pointer to "b"
+        0x00, 0x01,  // type A
+        0x00, 0x01,  // class IN
+        0x00, 0x00, 0x03, 0xa5,  // TTL
+        0x00, 0x04,  // data length
+        0x7f, 0x00, 0x00, 0x01,  // address
+
+        // (not part of any record, but pointed to)
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // "dog"
+        0x01, 0x63, 0xc0, 0x1e,  // "c", then a pointer to "dog"
+        0x01, 0x62, 0xc0, 0x23,  // "b", then a pointer to "c"
+    ]
+}
+
+#[test]
+fn pointer_chain_within_limit() {
+    let bytes = chained_response();
+    let response = Response::from_bytes(&bytes).unwrap();
+    assert_eq!(response.answers.len(), 1);
+}
+
+#[test]
+fn too_many_pointer_jumps() {
+    let bytes = chained_response();
+    let options = ParseOptions { max_pointer_jumps: 2, .. ParseOptions::default() };
+    assert_eq!(Response::from_bytes_with(&bytes, options).unwrap_err().cause(),
+               &WireError::TooMuchRecursion(vec![ 0x27, 0x23 ]));
+}
+
+#[test]
+fn decompressed_name_too_long() {
+    let bytes = chained_response();
+    let options = ParseOptions { max_name_length: 10, .. ParseOptions::default() };
+    assert_eq!(Response::from_bytes_with(&bytes, options).unwrap_err().cause(),
+               &WireError::NameTooLong(11));
+}
+
+#[test]
+fn name_length_cannot_be_raised() {
+    let mut bytes = vec![ 0x0d, 0xcd, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00 ];
+    for _ in 0 .. 5 {
+        bytes.push(63);
+        bytes.extend(&[ b'a'; 63 ]);
+    }
+    bytes.extend(&[ 0x00, 0x00, 0x01, 0x00, 0x01 ]);
+
+    let options = ParseOptions { max_name_length: 1000, .. ParseOptions::default() };
+    assert_eq!(Request::from_bytes_with(&bytes, options).unwrap_err().cause(),
+               &WireError::NameTooLong(257));
+}
+
+#[test]
+fn error_messages() {
+    assert_eq!(WireError::IO.to_string(),
+               "Malformed packet: insufficient data");
+    assert_eq!(WireError::WrongLength { expected: 9, got: 7 }.to_string(),
+               "Malformed packet: expected length 9, got 7");
+    assert_eq!(WireError::NameTooLong(257).to_string(),
+               "Domain name too long: 257 bytes (maximum 255)");
+}
+
+#[test]
+fn error_as_boxed_error() {
+    fn parse(bytes: &[u8]) -> Result<Response, Box<dyn std::error::Error>> {
+        Ok(Response::from_bytes(bytes)?)
+    }
+
+    let error = parse(&[ 0x0d ]).unwrap_err();
+    assert_eq!(error.to_string(), "Malformed packet: insufficient data in the header");
+}
+
+
+#[test]
+fn error_location_of_record() {
+    let bytes = mx_response(9, &[ 0x00, 0x00 ]);
+    let location = ErrorLocation { section: Section::Answer, index: 0, qtype: Some(15), offset: 12 };
+
+    assert_eq!(Response::from_bytes(&bytes), Err(WireError::At {
+        location,
+        error: Box::new(WireError::WrongLength { expected: 9, got: 7 }),
+    }));
+}
+
+#[test]
+fn error_location_of_name() {
+    let bytes = chained_response();
+    let options = ParseOptions { max_pointer_jumps: 2, .. ParseOptions::default() };
+    let error = Response::from_bytes_with(&bytes, options).unwrap_err();
+
+    assert_eq!(error.location(),
+               Some(ErrorLocation { section: Section::Answer, index: 0, qtype: None, offset: 12 }));
+}
+
+#[test]
+fn error_location_of_query() {
+    let bytes = vec![
+        0x0d, 0xcd, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x03, 0x64, 0x6f, 0x67, 0x00,  // name
+        0x00, 0x01,  // type A, but no class
+    ];
+
+    assert_eq!(Request::from_bytes(&bytes).unwrap_err().location(),
+               Some(ErrorLocation { section: Section::Query, index: 0, qtype: Some(1), offset: 12 }));
+}
+
+#[test]
+fn error_location_in_later_section() {
+    let mut bytes = mx_response(7, &[]);
+    bytes[7] = 0x00;  // no answers...
+    bytes[9] = 0x01;  // ...but one authority
+    bytes.extend(&mx_response(9, &[ 0x00, 0x00 ])[12 ..]);
+    bytes[11] = 0x01;  // and the broken one is an additional
+
+    let error = Response::from_bytes(&bytes).unwrap_err();
+    assert_eq!(error.location(),
+               Some(ErrorLocation { section: Section::Additional, index: 0, qtype: Some(15), offset: 34 }));
+    assert_eq!(error.cause(), &WireError::WrongLength { expected: 9, got: 7 });
+}
+
+#[test]
+fn unlocated_error() {
+    let bytes = mx_response(7, &[ 0xde, 0xad ]);
+    let error = Response::from_bytes_with(&bytes, ParseOptions::strict()).unwrap_err();
+    assert_eq!(error.location(), None);
+    assert_eq!(error.cause(), &WireError::TrailingBytes(2));
+}
+
+#[test]
+fn located_error_messages() {
+    let error = WireError::At {
+        location: ErrorLocation { section: Section::Answer, index: 2, qtype: Some(15), offset: 45 },
+        error: Box::new(WireError::WrongLength { expected: 9, got: 7 }),
+    };
+    assert_eq!(error.to_string(),
+               "Malformed packet: expected length 9, got 7 in answer #3 (MX) at byte 45");
+
+    let error = WireError::At {
+        location: ErrorLocation { section: Section::Authority, index: 0, qtype: Some(65280), offset: 12 },
+        error: Box::new(WireError::IO),
+    };
+    assert_eq!(error.to_string(),
+               "Malformed packet: insufficient data in authority #1 (type 65280) at byte 12");
+}
+
+#[test]
+fn located_error_cause() {
+    use std::error::Error;
+
+    let error = WireError::At {
+        location: ErrorLocation { section: Section::Answer, index: 0, qtype: Some(15), offset: 12 },
+        error: Box::new(WireError::IO),
+    };
+    assert!(error.source().is_none());
+    assert_eq!(error.cause(), &WireError::IO);
+}
@@ -0,0 +1,180 @@
+//! Finding a nameserver that is authoritative for the zone a name is in,
+//! so a query can be sent to it directly instead of to a resolver, and
+//! get answered from the zone itself rather than from anybody’s cache.
+
+use std::net::IpAddr;
+
+use dns::{qtype, Answer, Name, Response, TypeInt};
+use dns::record::{A, AAAA, NS, Record};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// A nameserver that is authoritative for a zone, and the address it was
+/// found at.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Authority {
+
+    /// The zone the queried name is in, which is the closest name at or
+    /// above it with NS records.
+    pub zone: Name,
+
+    /// The nameserver’s name, from one of the zone’s NS records.
+    pub nameserver: Name,
+
+    /// The nameserver’s address.
+    pub address: IpAddr,
+}
+
+/// Something that stopped an authoritative nameserver from being found.
+#[derive(Debug)]
+pub enum AuthorityError {
+
+    /// A query to the resolver for the NS records, or for a nameserver’s
+    /// addresses, failed, or the resolver responded with an error.
+    Lookup(LookupError),
+
+    /// No nameserver with an address could be found for the zone.
+    NoAddresses(Name),
+}
+
+impl From<LookupError> for AuthorityError {
+    fn from(error: LookupError) -> Self {
+        Self::Lookup(error)
+    }
+}
+
+
+/// Finds the zone the given name is in, by asking for NS records at it and
+/// then at each name above it until some are found, and then the address
+/// of the first of the zone’s nameservers that has one. The lookup function
+/// is called to send each query to the resolver.
+pub fn find(qname: &Name, mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>) -> Result<Authority, AuthorityError> {
+    let mut zone = qname.clone();
+
+    let nameservers = loop {
+        info!("Looking up NS records at {:?}", zone);
+        let response = lookup(&zone, qtype!(NS)).map_err(|e| LookupError::Transport(zone.clone(), e))?;
+        check_rcode(&zone, &response)?;
+
+        // A CNAME gets followed by the resolver, so any NS records have to
+        // be at the name itself to make it a zone.
+        let nameservers = response.answers.into_iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { qname, record: Record::NS(NS { nameserver }), .. } if qname == zone => Some(nameserver),
+                _                                                                                         => None,
+            }
+        }).collect::<Vec<_>>();
+
+        if ! nameservers.is_empty() {
+            break nameservers;
+        }
+
+        match zone.parent() {
+            Some(parent)  => zone = parent,
+            None          => return Err(AuthorityError::NoAddresses(zone)),
+        }
+    };
+
+    for nameserver in nameservers {
+        for qtype in [ qtype!(A), qtype!(AAAA) ] {
+            info!("Looking up addresses of {:?}", nameserver);
+            let response = lookup(&nameserver, qtype).map_err(|e| LookupError::Transport(nameserver.clone(), e))?;
+            check_rcode(&nameserver, &response)?;
+
+            if let Some(address) = response.answers.iter().find_map(address) {
+                debug!("Using {:?} ({}) as the authority for {:?}", nameserver, address, zone);
+                return Ok(Authority { zone, nameserver, address });
+            }
+        }
+    }
+
+    Err(AuthorityError::NoAddresses(zone))
+}
+
+/// Gets the address out of an A or AAAA record.
+fn address(answer: &Answer) -> Option<IpAddr> {
+    match answer {
+        Answer::Standard { record: Record::A(A { address }), .. }        => Some(IpAddr::V4(*address)),
+        Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => Some(IpAddr::V6(*address)),
+        _                                                                => None,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::ErrorCode;
+    use dns::record::CNAME;
+
+    /// Answers for the `lookup.dog` zone, where `www` is an alias for a
+    /// name in another zone, and `ns1` has no addresses.
+    fn lookup(qname: &Name, qtype: TypeInt) -> Result<Response, TransportError> {
+        let answers = match (qname.as_str(), qtype) {
+            ("lookup.dog.", t) if t == qtype!(NS)   => vec![
+                answer("lookup.dog.", Record::NS(NS { nameserver: name("ns1.lookup.dog.") })),
+                answer("lookup.dog.", Record::NS(NS { nameserver: name("ns2.lookup.dog.") })),
+            ],
+            ("www.lookup.dog.", t) if t == qtype!(NS)  => vec![
+                answer("www.lookup.dog.", Record::CNAME(CNAME { domain: name("elsewhere.dog.") })),
+                answer("elsewhere.dog.", Record::NS(NS { nameserver: name("ns.elsewhere.dog.") })),
+            ],
+            ("ns2.lookup.dog.", t) if t == qtype!(A)   => vec![ answer("ns2.lookup.dog.", Record::A(A { address: "192.0.2.2".parse().unwrap() })) ],
+            _ => Vec::new(),
+        };
+
+        let rcode = if qname.as_str().starts_with("missing.") { Some(ErrorCode::NXDomain) } else { None };
+        Ok(response(qname, qtype, rcode, answers))
+    }
+
+    #[test]
+    fn zone_apex() {
+        assert_eq!(find(&name("lookup.dog."), lookup).unwrap(), Authority {
+            zone: name("lookup.dog."),
+            nameserver: name("ns2.lookup.dog."),
+            address: "192.0.2.2".parse().unwrap(),
+        });
+    }
+
+    #[test]
+    fn below_the_apex() {
+        let authority = find(&name("a.b.lookup.dog."), lookup).unwrap();
+        assert_eq!(authority.zone, name("lookup.dog."));
+    }
+
+    #[test]
+    fn alias() {
+        let authority = find(&name("www.lookup.dog."), lookup).unwrap();
+        assert_eq!(authority.zone, name("lookup.dog."));
+    }
+
+    #[test]
+    fn name_that_does_not_exist() {
+        let authority = find(&name("missing.lookup.dog."), lookup).unwrap();
+        assert_eq!(authority.nameserver, name("ns2.lookup.dog."));
+    }
+
+    #[test]
+    fn no_addresses() {
+        let result = find(&name("lookup.dog."), |qname, qtype| {
+            if qtype == qtype!(NS) { lookup(qname, qtype) } else { Ok(response(qname, qtype, None, Vec::new())) }
+        });
+
+        assert!(matches!(result, Err(AuthorityError::NoAddresses(zone)) if zone == name("lookup.dog.")));
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = find(&name("lookup.dog."), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::ServerFailure), Vec::new()))
+        });
+
+        assert!(matches!(result, Err(AuthorityError::Lookup(LookupError::Rcode(_, ErrorCode::ServerFailure)))));
+    }
+}
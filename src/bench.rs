@@ -0,0 +1,254 @@
+//! Load-testing a nameserver by sending it queries at a steady rate, and
+//! summarising how quickly and how well it responded.
+
+use std::time::Duration;
+
+use futures_util::stream::{self, StreamExt};
+use log::*;
+use tokio::time::{delay_until, timeout, Instant};
+
+use dns::{Query, ErrorCode};
+use dns_transport::{Runtime, Transport};
+
+use crate::requests::RequestGenerator;
+
+
+/// How long to wait for each response before counting it as timed out.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How hard to load the nameserver.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Load {
+
+    /// How many queries to send each second.
+    pub rate: u32,
+
+    /// How long to keep sending them for.
+    pub duration: Duration,
+}
+
+/// What happened to one query that was sent.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Outcome {
+
+    /// A response arrived, with this error code, if it had one.
+    Response(Option<ErrorCode>),
+
+    /// No response arrived before the timeout.
+    Timeout,
+
+    /// Sending the query or receiving the response failed.
+    Error,
+}
+
+/// One query that was sent, and how long it took to find out what
+/// happened to it.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Sample {
+
+    /// What happened.
+    pub outcome: Outcome,
+
+    /// How long it took to happen.
+    pub latency: Duration,
+
+    /// How long after the start of the run it was sent.
+    pub offset: Duration,
+}
+
+/// A summary of a benchmark run.
+#[derive(PartialEq, Debug)]
+pub struct Report {
+
+    /// How many queries were sent.
+    pub sent: usize,
+
+    /// How long it took from sending the first query to receiving the last
+    /// response or timing out.
+    pub elapsed: Duration,
+
+    /// How many queries were actually sent each second, which falls short
+    /// of the target rate if they could not be sent fast enough.
+    pub rate: f64,
+
+    /// How many queries got a response.
+    pub responses: usize,
+
+    /// How many queries timed out.
+    pub timeouts: usize,
+
+    /// How many queries failed for another reason.
+    pub errors: usize,
+
+    /// How many responses had each error code, in the order each one was
+    /// first seen, with no code meaning `NOERROR`.
+    pub rcodes: Vec<(Option<ErrorCode>, usize)>,
+
+    /// How long the responses took to arrive, if there were any.
+    pub latencies: Option<Latencies>,
+}
+
+/// The latencies at the percentiles that get reported.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Latencies {
+
+    /// The fastest response.
+    pub min: Duration,
+
+    /// The median response.
+    pub p50: Duration,
+
+    /// The response that 90% of them were at least as fast as.
+    pub p90: Duration,
+
+    /// The response that 99% of them were at least as fast as.
+    pub p99: Duration,
+
+    /// The slowest response.
+    pub max: Duration,
+}
+
+
+/// Sends the queries to the transport at the given rate, going back to the
+/// first one after running out, until the duration is up. Each query is
+/// sent at a fixed time after the start, rather than after the previous
+/// one, so a slow response does not hold up any of the others.
+pub fn run(runtime: &mut Runtime, transport: &(impl Transport + ?Sized), requests: &RequestGenerator, queries: &[Query], load: Load) -> Report {
+    let total = (u128::from(load.rate) * load.duration.as_millis() / 1000) as usize;
+    let interval = Duration::from_secs(1) / load.rate;
+
+    // Queries only get made when there is room for them, which there will
+    // be unless every query sent in the last timeout period is unanswered.
+    let outstanding = (load.rate as usize) * (TIMEOUT.as_secs() as usize + 1);
+
+    runtime.block_on(async {
+        let start = Instant::now();
+
+        let sends = (0 .. total).map(|index| {
+            let query = &queries[index % queries.len()];
+            let request = requests.make_request(&query.qname, query.qtype, query.qclass);
+
+            async move {
+                delay_until(start + interval * index as u32).await;
+                let sent = Instant::now();
+
+                let outcome = match timeout(TIMEOUT, transport.exchange(&request)).await {
+                    Ok(Ok(response))  => Outcome::Response(response.flags.error_code),
+                    Ok(Err(e))        => { debug!("Query failed: {:?}", e); Outcome::Error }
+                    Err(_)            => Outcome::Timeout,
+                };
+
+                Sample { outcome, latency: sent.elapsed(), offset: sent - start }
+            }
+        });
+
+        let samples = stream::iter(sends).buffer_unordered(outstanding.max(1)).collect::<Vec<_>>().await;
+        Report::summarise(&samples, start.elapsed())
+    })
+}
+
+
+impl Report {
+
+    /// Summarises what happened to the queries that were sent.
+    pub fn summarise(samples: &[Sample], elapsed: Duration) -> Self {
+        let mut rcodes: Vec<(Option<ErrorCode>, usize)> = Vec::new();
+        let mut latencies = Vec::new();
+        let (mut timeouts, mut errors) = (0, 0);
+
+        for sample in samples {
+            match sample.outcome {
+                Outcome::Response(rcode) => {
+                    latencies.push(sample.latency);
+
+                    match rcodes.iter_mut().find(|(r, _)| *r == rcode) {
+                        Some((_, count))  => *count += 1,
+                        None              => rcodes.push((rcode, 1)),
+                    }
+                }
+                Outcome::Timeout  => timeouts += 1,
+                Outcome::Error    => errors += 1,
+            }
+        }
+
+        latencies.sort();
+        let responses = latencies.len();
+        let latencies = Latencies::of_sorted(&latencies);
+
+        // The rate is measured between the first and last queries being
+        // sent, so waiting for the last responses does not count.
+        let sending = samples.iter().map(|s| s.offset).max().unwrap_or_default();
+        let rate = if samples.len() > 1 && sending > Duration::default() { (samples.len() - 1) as f64 / sending.as_secs_f64() }
+                                                                     else { samples.len() as f64 };
+
+        Self { sent: samples.len(), elapsed, rate, responses, timeouts, errors, rcodes, latencies }
+    }
+}
+
+impl Latencies {
+
+    /// Picks the percentiles out of a sorted list of latencies, using the
+    /// nearest-rank method.
+    fn of_sorted(sorted: &[Duration]) -> Option<Self> {
+        if sorted.is_empty() {
+            return None;
+        }
+
+        let percentile = |p: usize| sorted[(sorted.len() * p).div_ceil(100).max(1) - 1];
+
+        Some(Self {
+            min: sorted[0],
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn response(millis: u64, rcode: Option<ErrorCode>) -> Sample {
+        Sample { outcome: Outcome::Response(rcode), latency: Duration::from_millis(millis), offset: Duration::default() }
+    }
+
+    #[test]
+    fn summary() {
+        let samples = [
+            response(30, None),
+            response(10, Some(ErrorCode::NXDomain)),
+            Sample { outcome: Outcome::Timeout, latency: TIMEOUT, offset: Duration::from_millis(250) },
+            response(20, None),
+            Sample { outcome: Outcome::Error, latency: Duration::from_millis(1), offset: Duration::from_millis(500) },
+        ];
+
+        let report = Report::summarise(&samples, Duration::from_secs(1));
+        assert_eq!(report.sent, 5);
+        assert_eq!(report.responses, 3);
+        assert_eq!(report.timeouts, 1);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.rate, 8.0);
+        assert_eq!(report.rcodes, vec![ (None, 2), (Some(ErrorCode::NXDomain), 1) ]);
+        assert_eq!(report.latencies.map(|l| (l.min, l.p50, l.max)),
+                   Some((Duration::from_millis(10), Duration::from_millis(20), Duration::from_millis(30))));
+    }
+
+    #[test]
+    fn percentiles() {
+        let sorted = (1 ..= 100).map(Duration::from_millis).collect::<Vec<_>>();
+        let latencies = Latencies::of_sorted(&sorted).unwrap();
+        assert_eq!(latencies.p50, Duration::from_millis(50));
+        assert_eq!(latencies.p90, Duration::from_millis(90));
+        assert_eq!(latencies.p99, Duration::from_millis(99));
+    }
+
+    #[test]
+    fn no_responses() {
+        let report = Report::summarise(&[ Sample { outcome: Outcome::Timeout, latency: TIMEOUT, offset: Duration::default() } ], TIMEOUT);
+        assert_eq!(report.latencies, None);
+    }
+}
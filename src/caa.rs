@@ -0,0 +1,241 @@
+//! Working out which certificate authorities may issue certificates for a
+//! domain, by finding the CAA records that apply to it: the ones at the
+//! domain itself, or failing that, at its closest ancestor that has any
+//! (RFC 8659 §3).
+
+use dns::{Answer, Name, Response};
+use dns::record::{CAA, Record};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// The CAA records that apply to a domain, and where they were found.
+#[derive(PartialEq, Debug)]
+pub struct CaaCheck {
+
+    /// The domain that certificates would be issued for.
+    pub domain: Name,
+
+    /// Every name that got queried, from the domain upwards, stopping at
+    /// the first one with any CAA records.
+    pub checked: Vec<Name>,
+
+    /// The name the relevant records were found at, if any were, which is
+    /// the last of the names checked.
+    pub found_at: Option<Name>,
+
+    /// The relevant records. If there are none, any CA may issue.
+    pub records: Vec<CAA>,
+}
+
+/// Which CAs may issue one kind of certificate.
+#[derive(PartialEq, Debug)]
+pub enum Permission {
+
+    /// Any CA may, as nothing restricts it.
+    AnyCA,
+
+    /// No CA may.
+    NoCA,
+
+    /// Only the CAs with these domain names may.
+    Only(Vec<String>),
+}
+
+
+/// Finds the CAA records that apply to the given domain, calling the
+/// lookup function to query for the CAA records at each name in turn.
+/// The root is never queried, as records there would apply to everything.
+pub fn check(domain: &Name, mut lookup: impl FnMut(&Name) -> Result<Response, TransportError>) -> Result<CaaCheck, LookupError> {
+    let mut checked = Vec::new();
+    let mut name = domain.clone();
+
+    while ! name.is_root() {
+        info!("Checking for CAA records at {:?}", name);
+        let response = lookup(&name).map_err(|e| LookupError::Transport(name.clone(), e))?;
+        checked.push(name.clone());
+
+        check_rcode(&name, &response)?;
+
+        // Any CNAME gets followed by the nameserver, so the records at the
+        // end of it are the ones in the answer section.
+        let records = response.answers.into_iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { record: Record::CAA(caa), .. }  => Some(caa),
+                _                                                   => None,
+            }
+        }).collect::<Vec<_>>();
+
+        if ! records.is_empty() {
+            return Ok(CaaCheck { domain: domain.clone(), checked, found_at: Some(name), records });
+        }
+
+        name = name.parent().unwrap_or_else(Name::root);
+    }
+
+    Ok(CaaCheck { domain: domain.clone(), checked, found_at: None, records: Vec::new() })
+}
+
+
+impl CaaCheck {
+
+    /// Which CAs may issue certificates for the domain itself.
+    pub fn issue(&self) -> Permission {
+        self.permission("issue")
+    }
+
+    /// Which CAs may issue wildcard certificates for the domain. If there
+    /// are no `issuewild` records, the `issue` ones apply to these too.
+    pub fn issuewild(&self) -> Permission {
+        if self.has_tag("issuewild") {
+            self.permission("issuewild")
+        }
+        else {
+            self.issue()
+        }
+    }
+
+    /// The URLs that CAs should report requests that break the policy to.
+    pub fn iodef(&self) -> Vec<&str> {
+        self.records.iter().filter(|r| r.tag.eq_ignore_ascii_case("iodef")).map(|r| r.value.as_str()).collect()
+    }
+
+    /// The records marked as critical with tags that CAs are not expected
+    /// to understand, which stop every CA from issuing anything.
+    pub fn unknown_critical(&self) -> Vec<&CAA> {
+        self.records.iter().filter(|r| r.critical && ! KNOWN_TAGS.iter().any(|t| r.tag.eq_ignore_ascii_case(t))).collect()
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.records.iter().any(|r| r.tag.eq_ignore_ascii_case(tag))
+    }
+
+    /// Works out who may issue from the records with the given tag. Each
+    /// one names a CA by its domain, before any parameters after a
+    /// semicolon; one with no domain, such as `";"`, names nobody.
+    fn permission(&self, tag: &str) -> Permission {
+        if ! self.unknown_critical().is_empty() {
+            return Permission::NoCA;
+        }
+
+        if ! self.has_tag(tag) {
+            return Permission::AnyCA;
+        }
+
+        let mut issuers = Vec::new();
+        for record in self.records.iter().filter(|r| r.tag.eq_ignore_ascii_case(tag)) {
+            let issuer = record.value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+            if ! issuer.is_empty() && ! issuers.contains(&issuer) {
+                issuers.push(issuer);
+            }
+        }
+
+        if issuers.is_empty() {
+            Permission::NoCA
+        }
+        else {
+            Permission::Only(issuers)
+        }
+    }
+}
+
+/// The tags defined in RFC 8659, which CAs have to understand.
+const KNOWN_TAGS: &[&str] = &[ "issue", "issuewild", "iodef" ];
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::{qtype, ErrorCode};
+
+    fn caa(critical: bool, tag: &str, value: &str) -> CAA {
+        CAA { critical, tag: tag.into(), value: value.into() }
+    }
+
+    fn response(qname: &Name, rcode: Option<ErrorCode>, records: Vec<CAA>) -> Response {
+        let answers = records.into_iter().map(|caa| answer(qname.as_str(), Record::CAA(caa))).collect();
+        crate::lookup::testing::response(qname, qtype!(CAA), rcode, answers)
+    }
+
+    fn records_at(at: &'static str, records: Vec<CAA>) -> impl FnMut(&Name) -> Result<Response, TransportError> {
+        move |qname| {
+            if *qname == name(at) { Ok(response(qname, None, records.clone())) }
+                             else { Ok(response(qname, Some(ErrorCode::NXDomain), Vec::new())) }
+        }
+    }
+
+    fn found(records: Vec<CAA>) -> CaaCheck {
+        CaaCheck { domain: name("lookup.dog."), checked: vec![ name("lookup.dog.") ], found_at: Some(name("lookup.dog.")), records }
+    }
+
+    #[test]
+    fn walks_up_the_tree() {
+        let records = vec![ caa(false, "issue", "letsencrypt.org") ];
+        let check = check(&name("www.lookup.dog."), records_at("lookup.dog.", records.clone())).unwrap();
+
+        assert_eq!(check.checked, vec![ name("www.lookup.dog."), name("lookup.dog.") ]);
+        assert_eq!(check.found_at, Some(name("lookup.dog.")));
+        assert_eq!(check.records, records);
+    }
+
+    #[test]
+    fn stops_before_the_root() {
+        let check = check(&name("www.lookup.dog."), records_at(".", vec![ caa(false, "issue", ";") ])).unwrap();
+
+        assert_eq!(check.checked, vec![ name("www.lookup.dog."), name("lookup.dog."), name("dog.") ]);
+        assert_eq!(check.found_at, None);
+        assert_eq!(check.issue(), Permission::AnyCA);
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = check(&name("lookup.dog."), |qname| Ok(response(qname, Some(ErrorCode::ServerFailure), Vec::new())));
+        assert!(matches!(result, Err(LookupError::Rcode(_, ErrorCode::ServerFailure))));
+    }
+
+    #[test]
+    fn issuers() {
+        let check = found(vec![
+            caa(false, "issue", "letsencrypt.org"),
+            caa(false, "issue", "DigiCert.com; cansignhttpexchanges=yes"),
+            caa(false, "iodef", "mailto:security@lookup.dog"),
+        ]);
+
+        assert_eq!(check.issue(), Permission::Only(vec![ "letsencrypt.org".into(), "digicert.com".into() ]));
+        assert_eq!(check.issuewild(), check.issue());
+        assert_eq!(check.iodef(), vec![ "mailto:security@lookup.dog" ]);
+    }
+
+    #[test]
+    fn wildcards_separately() {
+        let check = found(vec![ caa(false, "issue", "letsencrypt.org"), caa(false, "issuewild", ";") ]);
+        assert_eq!(check.issue(), Permission::Only(vec![ "letsencrypt.org".into() ]));
+        assert_eq!(check.issuewild(), Permission::NoCA);
+    }
+
+    #[test]
+    fn only_reports() {
+        let check = found(vec![ caa(false, "iodef", "mailto:security@lookup.dog") ]);
+        assert_eq!(check.issue(), Permission::AnyCA);
+    }
+
+    #[test]
+    fn unknown_critical_tag() {
+        let check = found(vec![ caa(false, "issue", "letsencrypt.org"), caa(true, "tbs", "woof") ]);
+        assert_eq!(check.unknown_critical().len(), 1);
+        assert_eq!(check.issue(), Permission::NoCA);
+        assert_eq!(check.issuewild(), Permission::NoCA);
+    }
+
+    #[test]
+    fn unknown_tag_not_critical() {
+        let check = found(vec![ caa(false, "issue", "letsencrypt.org"), caa(false, "contactemail", "dog@lookup.dog") ]);
+        assert!(check.unknown_critical().is_empty());
+        assert_eq!(check.issue(), Permission::Only(vec![ "letsencrypt.org".into() ]));
+    }
+}
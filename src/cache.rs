@@ -0,0 +1,167 @@
+//! Keeping a cache of responses for each nameserver, optionally saved to
+//! a directory so it lasts between runs.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+use log::*;
+
+use dns::Cache;
+
+use crate::resolve::Nameserver;
+
+
+/// The caches of responses from each nameserver, which get shared between
+/// every transport going to it.
+pub struct Caches {
+
+    /// The directory to load the caches from and save them to, if they
+    /// are kept on disk rather than only in memory.
+    directory: Option<PathBuf>,
+
+    /// The cache for each nameserver used so far.
+    caches: RefCell<Vec<(Nameserver, Arc<Mutex<Cache>>)>>,
+}
+
+impl Caches {
+
+    /// Creates a new set of caches, which start off empty if they are only
+    /// kept in memory, or get loaded from files in the directory as each
+    /// nameserver gets used.
+    pub fn new(directory: Option<PathBuf>) -> Self {
+        Self { directory, caches: RefCell::new(Vec::new()) }
+    }
+
+    /// Returns the cache for the given nameserver, creating or loading it
+    /// if this is the first time it has been used.
+    pub fn for_nameserver(&self, nameserver: &str, now: u64) -> Arc<Mutex<Cache>> {
+        let mut caches = self.caches.borrow_mut();
+        if let Some((_, cache)) = caches.iter().find(|(ns, _)| ns == nameserver) {
+            return Arc::clone(cache);
+        }
+
+        let cache = match self.path(nameserver) {
+            Some(path) => match File::open(&path) {
+                Ok(file) => {
+                    debug!("Loading cache from {:?}", path);
+                    Cache::load(BufReader::new(file), now).unwrap_or_else(|e| {
+                        warn!("Failed to read cache from {:?}: {}", path, e);
+                        Cache::new()
+                    })
+                }
+                Err(e) => {
+                    debug!("Starting with an empty cache, as {:?} could not be opened: {}", path, e);
+                    Cache::new()
+                }
+            },
+            None => Cache::new(),
+        };
+
+        let cache = Arc::new(Mutex::new(cache));
+        caches.push((nameserver.into(), Arc::clone(&cache)));
+        cache
+    }
+
+    /// Saves every cache that has been used to its file, if the caches are
+    /// being kept on disk. Each one gets written to a temporary file that
+    /// then replaces the old one, so another dog reading the cache at the
+    /// same time never sees a file that is only half written.
+    pub fn save(&self, now: u64) -> io::Result<()> {
+        let directory = match &self.directory {
+            Some(d)  => d,
+            None     => return Ok(()),
+        };
+
+        fs::create_dir_all(directory)?;
+        for (nameserver, cache) in self.caches.borrow().iter() {
+            let path = directory.join(file_name(nameserver));
+            let temporary = directory.join(format!(".{}.{}.tmp", file_name(nameserver), process::id()));
+            debug!("Saving cache to {:?}", path);
+
+            let written = File::create(&temporary).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                cache.lock().expect("Cache lock poisoned").save(&mut writer, now)?;
+                writer.flush()
+            });
+
+            if let Err(e) = written.and_then(|()| fs::rename(&temporary, &path)) {
+                let _ = fs::remove_file(&temporary);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, nameserver: &str) -> Option<PathBuf> {
+        self.directory.as_ref().map(|d| d.join(file_name(nameserver)))
+    }
+}
+
+/// Returns the name of the file a nameserver’s cache gets saved in, which
+/// is the nameserver with anything that could not go in a file name, such
+/// as the slashes in a DNS-over-HTTPS URL, replaced.
+fn file_name(nameserver: &str) -> String {
+    nameserver.chars()
+              .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+              .collect()
+}
+
+
+// Caches are compared by where they are kept rather than by what is in
+// them, so the options that set them up can still be compared.
+
+impl PartialEq for Caches {
+    fn eq(&self, other: &Self) -> bool {
+        self.directory == other.directory
+    }
+}
+
+impl fmt::Debug for Caches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Caches").field("directory", &self.directory).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("1.1.1.1"), "1.1.1.1");
+        assert_eq!(file_name("[::1]:53"), "___1__53");
+        assert_eq!(file_name("https://cloudflare-dns.com/dns-query"), "https___cloudflare-dns.com_dns-query");
+    }
+
+    #[test]
+    fn shared_between_transports() {
+        let caches = Caches::new(None);
+        let first = caches.for_nameserver("1.1.1.1", 0);
+        let second = caches.for_nameserver("1.1.1.1", 0);
+        let other = caches.for_nameserver("8.8.8.8", 0);
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(! Arc::ptr_eq(&first, &other));
+    }
+
+    #[test]
+    fn saved_in_place() {
+        let directory = std::env::temp_dir().join(format!("dog-cache-{}", process::id()));
+        let caches = Caches::new(Some(directory.clone()));
+        caches.save(0).unwrap();
+        caches.for_nameserver("1.1.1.1", 0);
+        caches.save(0).unwrap();
+
+        let files = fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(files, vec![ "1.1.1.1" ]);
+    }
+}
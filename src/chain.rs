@@ -0,0 +1,126 @@
+//! Following CNAME chains through the answer section of a response.
+
+use std::collections::HashMap;
+
+use dns::{Answer, Name};
+use dns::record::Record;
+
+
+/// A **chain** of aliases, starting at the name that was queried, moving
+/// through each CNAME record’s target, and ending at the records for the
+/// last name (if the response contained any).
+#[derive(PartialEq, Debug)]
+pub struct Chain {
+
+    /// Every name in the chain, starting with the queried name.
+    pub names: Vec<Name>,
+
+    /// The non-CNAME records belonging to the last name in the chain.
+    pub records: Vec<Record>,
+}
+
+impl Chain {
+
+    /// Builds the graph of aliases in the given answers, and walks it
+    /// starting from the queried name. Returns `None` if the queried name
+    /// is not an alias at all.
+    pub fn find(answers: &[Answer], qname: &Name) -> Option<Self> {
+        let mut aliases = HashMap::new();
+        for answer in answers {
+            if let Answer::Standard { qname, record: Record::CNAME(cname), .. } = answer {
+                aliases.insert(qname, &cname.domain);
+            }
+        }
+
+        let mut names = vec![ qname.clone() ];
+        while let Some(target) = aliases.get(names.last().unwrap()) {
+            if names.contains(target) {
+                break;  // the chain loops back on itself
+            }
+
+            names.push((*target).clone());
+        }
+
+        if names.len() == 1 {
+            return None;
+        }
+
+        let last_name = names.last().unwrap();
+        let records = answers.iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { record: Record::CNAME(_), .. }  => None,
+                Answer::Standard { qname, record, .. } if qname == last_name => Some(record.clone()),
+                _ => None,
+            }
+        }).collect();
+
+        Some(Self { names, records })
+    }
+
+    /// The name at the end of the chain.
+    pub fn last_name(&self) -> &Name {
+        self.names.last().unwrap()
+    }
+
+    /// Whether the chain reaches a name that has records. If not, its target
+    /// needs to be queried separately.
+    pub fn is_complete(&self) -> bool {
+        ! self.records.is_empty()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::QClass;
+    use dns::record::{A, CNAME};
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn cname(owner: &str, target: &str) -> Answer {
+        let record = Record::CNAME(CNAME { domain: name(target) });
+        Answer::Standard { qname: name(owner), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    fn a(owner: &str) -> Answer {
+        let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) });
+        Answer::Standard { qname: name(owner), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    #[test]
+    fn no_aliases() {
+        assert_eq!(Chain::find(&[ a("lookup.dog.") ], &name("lookup.dog")), None);
+    }
+
+    #[test]
+    fn complete_chain() {
+        let answers = [ a("c.dog."), cname("b.dog.", "c.dog."), cname("a.dog.", "b.dog.") ];
+
+        assert_eq!(Chain::find(&answers, &name("a.dog")), Some(Chain {
+            names: vec![ name("a.dog"), name("b.dog."), name("c.dog.") ],
+            records: vec![ Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) }) ],
+        }));
+    }
+
+    #[test]
+    fn incomplete_chain() {
+        let chain = Chain::find(&[ cname("a.dog.", "b.dog.") ], &name("a.dog")).unwrap();
+        assert_eq!(chain.last_name().as_str(), "b.dog.");
+        assert!(! chain.is_complete());
+    }
+
+    #[test]
+    fn looping_chain() {
+        let answers = [ cname("a.dog.", "b.dog."), cname("b.dog.", "a.dog.") ];
+
+        assert_eq!(Chain::find(&answers, &name("a.dog")), Some(Chain {
+            names: vec![ name("a.dog"), name("b.dog.") ],
+            records: vec![],
+        }));
+    }
+}
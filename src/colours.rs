@@ -13,14 +13,27 @@ pub struct Colours {
     pub authority: Style,
     pub additional: Style,
 
+    pub secure: Style,
+    pub insecure: Style,
+    pub bogus: Style,
+
+    pub only_in_file: Style,
+    pub only_on_server: Style,
+    pub ttl_changed: Style,
+
+    pub disagreement: Style,
+
     pub a: Style,
     pub aaaa: Style,
     pub caa: Style,
     pub cname: Style,
+    pub dnskey: Style,
+    pub ds: Style,
     pub mx: Style,
     pub ns: Style,
     pub opt: Style,
     pub ptr: Style,
+    pub rrsig: Style,
     pub soa: Style,
     pub srv: Style,
     pub txt: Style,
@@ -39,14 +52,27 @@ impl Colours {
             authority: Cyan.normal(),
             additional: Green.normal(),
 
+            secure: Green.normal(),
+            insecure: Yellow.normal(),
+            bogus: Red.bold(),
+
+            only_in_file: Red.normal(),
+            only_on_server: Green.normal(),
+            ttl_changed: Yellow.normal(),
+
+            disagreement: Red.bold(),
+
             a: Green.bold(),
             aaaa: Green.bold(),
             caa: Red.normal(),
             cname: Yellow.normal(),
+            dnskey: Purple.normal(),
+            ds: Purple.normal(),
             mx: Cyan.normal(),
             ns: Red.normal(),
             opt: Purple.normal(),
             ptr: Red.normal(),
+            rrsig: Purple.normal(),
             soa: Purple.normal(),
             srv: Cyan.normal(),
             txt: Yellow.normal(),
@@ -0,0 +1,459 @@
+//! Generating the scripts that let shells complete dog’s options and
+//! arguments. The options get read back out of their definitions, and the
+//! record types out of the `dns` crate’s list of the types it knows, so a
+//! script generated by a build of dog completes everything that build
+//! accepts, and nothing it does not.
+
+use std::fmt::Write;
+
+use crate::locale;
+use crate::options::{Options, COMMANDS};
+use crate::presets::PRESETS;
+
+
+/// A shell that a completion script can be generated for.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Every shell, with the name it gets picked by.
+const SHELLS: [(&str, Shell); 4] = [
+    ("bash",        Shell::Bash),
+    ("zsh",         Shell::Zsh),
+    ("fish",        Shell::Fish),
+    ("powershell",  Shell::Powershell),
+];
+
+/// The network classes that can be queried.
+const CLASSES: [&str; 3] = [ "IN", "CH", "HS" ];
+
+impl Shell {
+
+    /// Picks a shell by its name.
+    pub fn from_name(input: &str) -> Option<Self> {
+        SHELLS.iter().find(|(name, _)| *name == input).map(|(_, shell)| *shell)
+    }
+}
+
+
+/// One of the command-line options, as read back out of the usage text
+/// that `getopts` generates for it.
+#[derive(PartialEq, Debug)]
+struct Flag {
+
+    /// The single-character name, such as the `t` in `-t`.
+    short: Option<char>,
+
+    /// The long name, such as the `type` in `--type`.
+    long: Option<String>,
+
+    /// The hint for the option’s value, such as `TYPE`. This is missing
+    /// for options that do not take one, and for options where the value
+    /// is optional, as those have to be given with an `=` if at all.
+    hint: Option<String>,
+
+    /// What the option does.
+    description: String,
+}
+
+/// What can be completed as the value of an option.
+#[derive(PartialEq, Debug)]
+enum Values {
+
+    /// One of these words.
+    Words(Vec<String>),
+
+    /// The path to a file.
+    Files,
+
+    /// Anything at all, so nothing gets suggested.
+    Anything,
+}
+
+
+/// Returns the completion script for the given shell.
+pub fn script(shell: Shell) -> String {
+    let flags = flags();
+
+    match shell {
+        Shell::Bash        => bash(&flags),
+        Shell::Zsh         => zsh(&flags),
+        Shell::Fish        => fish(&flags),
+        Shell::Powershell  => powershell(&flags),
+    }
+}
+
+/// Reads every option back out of the definitions of the options.
+fn flags() -> Vec<Flag> {
+    let mut flags = Vec::new();
+    let _ = Options::definitions().usage_with_format(|rows| {
+        flags.extend(rows.filter_map(|row| parse_row(&row)));
+        String::new()
+    });
+
+    flags
+}
+
+/// Parses one row of `getopts` usage text, which has the short name, long
+/// name, and hint in columns, followed by the description, which can be
+/// wrapped onto more lines.
+fn parse_row(row: &str) -> Option<Flag> {
+    let mut rest = row.strip_prefix("    ")?;
+
+    let short = match rest.strip_prefix('-').filter(|after| ! after.starts_with('-')) {
+        Some(after) => {
+            let short = after.chars().next()?;
+            let after = &after[short.len_utf8() ..];
+            rest = after.strip_prefix(", ").or_else(|| after.strip_prefix(' '))?;
+            Some(short)
+        }
+        None => {
+            rest = rest.strip_prefix("    ").unwrap_or(rest);
+            None
+        }
+    };
+
+    let long = match rest.strip_prefix("--") {
+        Some(after) => {
+            let (long, after) = after.split_once(' ')?;
+            rest = after;
+            Some(long.to_owned())
+        }
+        None => None,
+    };
+
+    let (hint, description) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+    let hint = Some(hint).filter(|hint| ! hint.is_empty() && ! hint.starts_with('[')).map(str::to_owned);
+    let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if short.is_none() && long.is_none() {
+        return None;
+    }
+
+    Some(Flag { short, long, hint, description })
+}
+
+/// Works out what can be completed as the value of an option that takes
+/// one.
+fn values(flag: &Flag) -> Values {
+    let words = |words: &[&str]| Values::Words(words.iter().map(|w| (*w).to_owned()).collect());
+
+    match flag.long.as_deref() {
+        Some("type")                        => Values::Words(record_types()),
+        Some("class")                       => words(&CLASSES),
+        Some("nameserver") | Some("bootstrap") => Values::Words(PRESETS.iter().map(|p| p.name.to_owned()).collect()),
+        Some("locale")                      => Values::Words(locale::languages().map(str::to_owned).collect()),
+        Some("edns")                        => words(&[ "disable", "hide", "show" ]),
+        Some("color") | Some("colour")      => words(&[ "always", "automatic", "never" ]),
+        Some("ttl-format")                  => words(&[ "raw", "human", "expiry" ]),
+        _ => match flag.hint.as_deref() {
+            Some("FILE") | Some("PATH") | Some("DIR")  => Values::Files,
+            _                                          => Values::Anything,
+        },
+    }
+}
+
+/// The names of every record type that can be queried.
+fn record_types() -> Vec<String> {
+    dns::qtype_names().into_iter().map(str::to_owned).collect()
+}
+
+/// The commands that can be given as the first argument.
+fn commands() -> Vec<String> {
+    COMMANDS.iter().copied().chain([ "completions" ]).map(str::to_owned).collect()
+}
+
+/// The words that can be given as arguments anywhere: record types,
+/// classes, and the public resolvers that can be picked by name.
+fn arguments() -> Vec<String> {
+    let mut arguments = record_types();
+    arguments.extend(CLASSES.iter().map(|class| (*class).to_owned()));
+    arguments.extend(PRESETS.iter().map(|preset| format!("@{}", preset.name)));
+    arguments
+}
+
+/// The names of the shells, for completing the `completions` command.
+fn shell_names() -> Vec<String> {
+    SHELLS.iter().map(|(name, _)| (*name).to_owned()).collect()
+}
+
+/// Every way an option can be written, such as `-t` and `--type`.
+fn spellings(flag: &Flag) -> Vec<String> {
+    let short = flag.short.map(|short| format!("-{}", short));
+    let long = flag.long.as_ref().map(|long| format!("--{}", long));
+    short.into_iter().chain(long).collect()
+}
+
+
+/// Generates the script for Bash, which completes based on the word
+/// before the one being completed.
+fn bash(flags: &[Flag]) -> String {
+    let mut out = String::new();
+    let all_flags = flags.iter().flat_map(spellings).collect::<Vec<_>>();
+
+    writeln!(out, "# bash completion for dog, generated by `dog completions bash`").unwrap();
+    writeln!(out, "_dog() {{").unwrap();
+    writeln!(out, "    local cur prev").unwrap();
+    writeln!(out, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+    writeln!(out, "    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    case \"$prev\" in").unwrap();
+
+    for flag in flags.iter().filter(|flag| flag.hint.is_some()) {
+        writeln!(out, "        {})", spellings(flag).join("|")).unwrap();
+        match values(flag) {
+            Values::Words(words)  => writeln!(out, "            COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )", words.join(" ")).unwrap(),
+            Values::Files         => writeln!(out, "            COMPREPLY=( $(compgen -f -- \"$cur\") )").unwrap(),
+            Values::Anything      => {}
+        }
+        writeln!(out, "            return").unwrap();
+        writeln!(out, "            ;;").unwrap();
+    }
+
+    writeln!(out, "        completions)").unwrap();
+    writeln!(out, "            COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )", shell_names().join(" ")).unwrap();
+    writeln!(out, "            return").unwrap();
+    writeln!(out, "            ;;").unwrap();
+    writeln!(out, "    esac").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    if [[ \"$cur\" == -* ]]; then").unwrap();
+    writeln!(out, "        COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )", all_flags.join(" ")).unwrap();
+    writeln!(out, "    elif [[ $COMP_CWORD -eq 1 ]]; then").unwrap();
+    writeln!(out, "        COMPREPLY=( $(compgen -W \"{} {}\" -- \"$cur\") )", commands().join(" "), arguments().join(" ")).unwrap();
+    writeln!(out, "    else").unwrap();
+    writeln!(out, "        COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )", arguments().join(" ")).unwrap();
+    writeln!(out, "    fi").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "complete -o bashdefault -o default -F _dog dog").unwrap();
+
+    out
+}
+
+/// Generates the script for Zsh, which describes each option to the
+/// `_arguments` function.
+fn zsh(flags: &[Flag]) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "#compdef dog").unwrap();
+    writeln!(out, "# zsh completion for dog, generated by `dog completions zsh`").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "local curcontext=\"$curcontext\" state line").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "_arguments -s -S -C \\").unwrap();
+
+    for flag in flags {
+        let description = zsh_escape(&flag.description);
+        let action = match (&flag.hint, values(flag)) {
+            (None, _)                          => String::new(),
+            (Some(hint), Values::Words(words)) => format!(":{}:({})", hint.to_lowercase(), words.join(" ")),
+            (Some(hint), Values::Files)        => format!(":{}:_files", hint.to_lowercase()),
+            (Some(hint), Values::Anything)     => format!(":{}: ", hint.to_lowercase()),
+        };
+
+        if let Some(short) = flag.short {
+            let suffix = if flag.hint.is_some() { "+" } else { "" };
+            writeln!(out, "    '-{}{}[{}]{}' \\", short, suffix, description, action).unwrap();
+        }
+
+        if let Some(long) = &flag.long {
+            let suffix = if flag.hint.is_some() { "=" } else { "" };
+            writeln!(out, "    '--{}{}[{}]{}' \\", long, suffix, description, action).unwrap();
+        }
+    }
+
+    writeln!(out, "    '*:argument:->arguments'").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "if [[ $state == arguments ]]; then").unwrap();
+    writeln!(out, "    if [[ $words[2] == completions ]] && (( CURRENT == 3 )); then").unwrap();
+    writeln!(out, "        compadd -- {}", shell_names().join(" ")).unwrap();
+    writeln!(out, "    elif (( CURRENT == 2 )); then").unwrap();
+    writeln!(out, "        compadd -- {} {}", commands().join(" "), arguments().join(" ")).unwrap();
+    writeln!(out, "    else").unwrap();
+    writeln!(out, "        compadd -- {}", arguments().join(" ")).unwrap();
+    writeln!(out, "    fi").unwrap();
+    writeln!(out, "fi").unwrap();
+
+    out
+}
+
+/// Escapes text to go in the brackets of a single-quoted `_arguments`
+/// option description.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:")
+}
+
+/// Generates the script for fish, which has one `complete` command for
+/// each option.
+fn fish(flags: &[Flag]) -> String {
+    let mut out = String::new();
+    let commands = commands().join(" ");
+
+    writeln!(out, "# fish completion for dog, generated by `dog completions fish`").unwrap();
+    writeln!(out, "complete -c dog -n '__fish_use_subcommand' -a '{}'", commands).unwrap();
+    writeln!(out, "complete -c dog -n 'not __fish_seen_subcommand_from completions' -a '{}'", arguments().join(" ")).unwrap();
+    writeln!(out, "complete -c dog -n '__fish_seen_subcommand_from completions' -x -a '{}'", shell_names().join(" ")).unwrap();
+
+    for flag in flags {
+        let mut line = String::from("complete -c dog");
+
+        if let Some(short) = flag.short {
+            write!(line, " -s '{}'", short).unwrap();
+        }
+
+        if let Some(long) = &flag.long {
+            write!(line, " -l '{}'", long).unwrap();
+        }
+
+        write!(line, " -d '{}'", fish_escape(&flag.description)).unwrap();
+
+        if flag.hint.is_some() {
+            match values(flag) {
+                Values::Words(words)  => write!(line, " -x -a '{}'", words.join(" ")).unwrap(),
+                Values::Files         => write!(line, " -r -F").unwrap(),
+                Values::Anything      => write!(line, " -x").unwrap(),
+            }
+        }
+
+        writeln!(out, "{}", line).unwrap();
+    }
+
+    out
+}
+
+/// Escapes text to go in a single-quoted fish string.
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// Generates the script for PowerShell, which registers a block that
+/// returns the completions for the word before the one being completed.
+fn powershell(flags: &[Flag]) -> String {
+    let mut out = String::new();
+    let all_flags = flags.iter().flat_map(spellings).collect::<Vec<_>>();
+
+    writeln!(out, "# PowerShell completion for dog, generated by `dog completions powershell`").unwrap();
+    writeln!(out, "Register-ArgumentCompleter -Native -CommandName dog -ScriptBlock {{").unwrap();
+    writeln!(out, "    param($wordToComplete, $commandAst, $cursorPosition)").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})").unwrap();
+    writeln!(out, "    $previous = if ($wordToComplete) {{ $words[-2] }} else {{ $words[-1] }}").unwrap();
+    writeln!(out, "    $position = if ($wordToComplete) {{ $words.Count - 1 }} else {{ $words.Count }}").unwrap();
+    writeln!(out, "    $prefix = $wordToComplete.Trim(\"'\")").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    $candidates = switch -CaseSensitive ($previous) {{").unwrap();
+
+    for flag in flags.iter().filter(|flag| flag.hint.is_some()) {
+        let body = match values(flag) {
+            Values::Words(words)  => format!("{}; break", powershell_list(&words)),
+            _                     => String::from("break"),
+        };
+
+        writeln!(out, "        {{ $_ -cin {} }} {{ {} }}", powershell_list(&spellings(flag)), body).unwrap();
+    }
+
+    writeln!(out, "        'completions' {{ {}; break }}", powershell_list(&shell_names())).unwrap();
+    writeln!(out, "        default {{").unwrap();
+    writeln!(out, "            if ($prefix.StartsWith('-')) {{ {} }}", powershell_list(&all_flags)).unwrap();
+    writeln!(out, "            elseif ($position -eq 1) {{ {}; {} }}", powershell_list(&commands()), powershell_list(&arguments())).unwrap();
+    writeln!(out, "            else {{ {} }}", powershell_list(&arguments())).unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    # Arguments starting with @ have to be quoted, or PowerShell splats them.").unwrap();
+    writeln!(out, "    $candidates | Where-Object {{ $_.StartsWith($prefix, [StringComparison]::Ordinal) }} | ForEach-Object {{").unwrap();
+    writeln!(out, "        $text = if ($_.StartsWith('@')) {{ \"'$_'\" }} else {{ $_ }}").unwrap();
+    writeln!(out, "        [System.Management.Automation.CompletionResult]::new($text, $_, 'ParameterValue', $_)").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+/// Formats words as a list of single-quoted PowerShell strings.
+fn powershell_list(words: &[String]) -> String {
+    words.iter().map(|word| format!("'{}'", word.replace('\'', "''"))).collect::<Vec<_>>().join(", ")
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn shell_names() {
+        assert_eq!(Shell::from_name("powershell"), Some(Shell::Powershell));
+        assert_eq!(Shell::from_name("Bash"), None);
+    }
+
+    #[test]
+    fn row_with_both_names() {
+        assert_eq!(parse_row("    -t, --type TYPE         Type of the DNS record being queried (A, MX,\n                        NS...)"), Some(Flag {
+            short: Some('t'),
+            long: Some("type".into()),
+            hint: Some("TYPE".into()),
+            description: "Type of the DNS record being queried (A, MX, NS...)".into(),
+        }));
+    }
+
+    #[test]
+    fn row_with_short_name() {
+        assert_eq!(parse_row("    -Z TWEAKS           Uncommon protocol tweaks"), Some(Flag {
+            short: Some('Z'),
+            long: None,
+            hint: Some("TWEAKS".into()),
+            description: "Uncommon protocol tweaks".into(),
+        }));
+    }
+
+    #[test]
+    fn row_with_long_flag() {
+        assert_eq!(parse_row("        --authoritative \n                        Send the queries straight to an authoritative"), Some(Flag {
+            short: None,
+            long: Some("authoritative".into()),
+            hint: None,
+            description: "Send the queries straight to an authoritative".into(),
+        }));
+    }
+
+    #[test]
+    fn row_with_optional_value() {
+        let flag = parse_row("        --dns64-synthesize [PREFIX]\n                        Show A records").unwrap();
+        assert_eq!(flag.long, Some("dns64-synthesize".into()));
+        assert_eq!(flag.hint, None);
+    }
+
+    #[test]
+    fn every_option() {
+        let flags = flags();
+        assert!(flags.iter().any(|f| f.short == Some('?') && f.long.as_deref() == Some("help")));
+        assert!(flags.iter().any(|f| f.short == Some('Z') && f.hint.as_deref() == Some("TWEAKS")));
+        assert!(flags.iter().all(|f| ! f.description.is_empty()), "{:?}", flags);
+    }
+
+    #[test]
+    fn type_values() {
+        let flag = flags().into_iter().find(|f| f.short == Some('t')).unwrap();
+        assert!(matches!(values(&flag), Values::Words(words) if words.contains(&"SSHFP".to_owned()) && words.contains(&"OPENPGPKEY".to_owned())));
+    }
+
+    #[test]
+    fn scripts_have_record_types() {
+        for (_, shell) in SHELLS {
+            let script = script(shell);
+            assert!(script.contains("TLSA"), "{:?}", shell);
+            assert!(script.contains("@cloudflare"), "{:?}", shell);
+        }
+    }
+
+    #[test]
+    fn zsh_escaping() {
+        assert_eq!(zsh_escape("Don’t [ever]: do it"), "Don’t \\[ever\\]\\: do it");
+        assert_eq!(zsh_escape("it's"), "it'\\''s");
+    }
+}
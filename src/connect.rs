@@ -1,5 +1,8 @@
+use std::time::Duration;
+
 use dns_transport::*;
 
+use crate::presets;
 use crate::resolve::Nameserver;
 
 
@@ -29,14 +32,48 @@ pub enum TransportType {
 
 impl TransportType {
 
-    /// Creates a boxed `Transport` depending on the transport type.
-    pub fn make_transport(self, ns: Nameserver) -> Box<dyn Transport> {
+    /// Creates a boxed `Transport` depending on the transport type, with
+    /// the case of query names sent over UDP randomised if requested, and
+    /// listening for more UDP packets for the given window after one
+    /// arrives, and giving up on UDP responses after the given timeout.
+    /// Nameservers named after a preset are sent to that preset’s
+    /// endpoint for the transport type, and the host names of encrypted
+    /// resolvers get looked up using the given bootstrap, with their
+    /// certificates checked using the given settings. Unless the bootstrap
+    /// says otherwise, encrypted presets get connected to at their known
+    /// addresses, trying each in turn, rather than having their host
+    /// names looked up.
+    pub fn make_transport(self, ns: Nameserver, randomise_case: bool, receive_window: Duration, timeout: Duration, bootstrap: &Bootstrap, tls: &TlsSettings) -> Box<dyn Transport> {
+        let preset = presets::find(&ns);
+        let ns = preset.map_or(ns, |p| p.endpoint(self).into());
+        let bootstrap = match preset {
+            Some(preset) if *bootstrap == Bootstrap::System  => preset.bootstrap(),
+            _                                                => bootstrap.clone(),
+        };
+
         match self {
-            Self::Automatic  => Box::new(AutoTransport::new(ns)),
-            Self::UDP        => Box::new(UdpTransport::new(ns)),
+            Self::Automatic  => {
+                let mut transport = AutoTransport::new(ns);
+                transport.randomise_case = randomise_case;
+                transport.receive_window = receive_window;
+                transport.timeout = timeout;
+                Box::new(transport)
+            }
+            Self::UDP => {
+                let mut transport = UdpTransport::new(ns);
+                transport.randomise_case = randomise_case;
+                transport.receive_window = receive_window;
+                transport.timeout = timeout;
+                Box::new(transport)
+            }
             Self::TCP        => Box::new(TcpTransport::new(ns)),
-            Self::TLS        => Box::new(TlsTransport::new(ns)),
-            Self::HTTPS      => Box::new(HttpsTransport::new(ns)),
+            Self::TLS => {
+                let mut transport = TlsTransport::new(ns);
+                transport.bootstrap = bootstrap;
+                transport.tls = tls.clone();
+                Box::new(transport)
+            }
+            Self::HTTPS      => Box::new(HttpsTransport::with_settings(ns, bootstrap, tls)),
         }
     }
 }
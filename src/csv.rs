@@ -0,0 +1,34 @@
+//! Writing rows of comma-separated values.
+
+/// Joins the given fields into one line of CSV, quoting any field that
+/// contains a comma, a quote, or a line break, and doubling any quotes
+/// inside it (RFC 4180 §2).
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    fields.iter().map(|field| {
+        let field = field.as_ref();
+
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        }
+        else {
+            field.to_owned()
+        }
+    }).collect::<Vec<_>>().join(",")
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(csv_row(&[ "lookup.dog.", "A", "300", "127.0.0.1" ]), "lookup.dog.,A,300,127.0.0.1");
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(csv_row(&[ "\"woof\"", "a,b", "" ]), "\"\"\"woof\"\"\",\"a,b\",");
+    }
+}
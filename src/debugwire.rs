@@ -0,0 +1,64 @@
+//! Logging every message that gets sent or received as annotated
+//! hexadecimal, with each field of the message on its own line, for
+//! tracking down servers and middleboxes that send malformed packets.
+
+use async_trait::async_trait;
+use tracing::debug;
+
+use dns::Request;
+use dns_transport::{Error as TransportError, Reply, Transport};
+
+use crate::hexdump::annotated_dump;
+
+
+/// A transport that logs the bytes of every request it sends, and every
+/// response it receives, at the debug level. Responses that fail to parse
+/// get logged too, as those are the ones most worth looking at.
+pub struct DebugTransport<T = Box<dyn Transport>> {
+    inner: T,
+    server: String,
+}
+
+impl<T: Transport> DebugTransport<T> {
+
+    /// Creates a new debugging transport that sends requests down the given
+    /// transport, noting the server they went to.
+    pub fn new(inner: T, server: String) -> Self {
+        Self { inner, server }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for DebugTransport<T> {
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, TransportError> {
+        let result = self.inner.send_with_details(request).await;
+
+        // The request gets logged as the transport underneath sent it, if
+        // it says, as it may have changed it along the way.
+        let sent = match &result {
+            Ok(reply) if ! reply.sent.is_empty()  => reply.sent.clone(),
+            _                                     => request.to_bytes()?,
+        };
+        log_message(&self.server, "sent", &sent);
+
+        match &result {
+            Ok(reply)                                                  => log_message(&self.server, "received", &reply.bytes),
+            Err(TransportError::MalformedResponse { error, bytes })  => {
+                log_message(&self.server, "received", bytes);
+                debug!(server = %self.server, %error, "Response did not parse");
+            }
+            Err(_)                                                     => {/* nothing arrived to log */},
+        }
+
+        result
+    }
+}
+
+/// Logs the size of a message and which way it went, followed by the
+/// message split up into its fields.
+fn log_message(server: &str, direction: &'static str, bytes: &[u8]) {
+    debug!(%server, direction, length = bytes.len(), "Message");
+    for line in annotated_dump(bytes) {
+        debug!("  {}", line);
+    }
+}
@@ -0,0 +1,424 @@
+//! Checking that a zone is delegated properly: that the nameservers its
+//! parent zone refers to agree with the ones the zone lists itself, that
+//! they have the glue records they need, and that each of them actually
+//! answers for the zone.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use log::*;
+
+use dns::{Answer, ErrorCode, Name, QClass, Response, qtype};
+use dns::record::{NS, Record};
+use dns_transport::Error as TransportError;
+
+use crate::trace::{Nameserver, Referral, Step, TraceError, Tracer};
+
+
+/// What a zone’s parent and its own nameservers say about its delegation.
+#[derive(Debug)]
+pub struct Delegation {
+
+    /// The zone that was checked.
+    pub zone: Name,
+
+    /// The zone that delegates to it.
+    pub parent: Name,
+
+    /// The nameservers the parent refers to, with their glue addresses.
+    pub parent_nameservers: Vec<Nameserver>,
+
+    /// The nameservers the zone lists in its own NS records, according to
+    /// the first of its nameservers to answer.
+    pub child_nameservers: Vec<Name>,
+
+    /// What each of the parent’s nameservers said when asked about the
+    /// zone directly.
+    pub servers: Vec<ServerCheck>,
+}
+
+/// One of the nameservers the parent refers to, and whether it answers
+/// for the zone.
+#[derive(Debug)]
+pub struct ServerCheck {
+
+    /// The nameserver’s name.
+    pub name: Name,
+
+    /// The address it was asked on, from the glue or from looking it up.
+    pub address: Option<Ipv4Addr>,
+
+    /// The zone’s NS records, along with any addresses sent with them, if
+    /// it answered authoritatively, or why it is lame if it did not.
+    pub result: Result<ChildAnswer, Lameness>,
+}
+
+/// The authoritative answer a nameserver gave when asked for the zone’s
+/// NS records.
+#[derive(PartialEq, Debug)]
+pub struct ChildAnswer {
+
+    /// The nameservers in the zone’s NS records.
+    pub nameservers: Vec<Name>,
+
+    /// The addresses of any of them sent in the additional section.
+    pub addresses: Vec<(Name, Ipv4Addr)>,
+}
+
+/// Why a nameserver that the parent refers to does not answer for the
+/// zone. Resolvers sent to a lame nameserver get no answer, or a wrong
+/// one.
+#[derive(Debug)]
+pub enum Lameness {
+
+    /// No address could be found for the nameserver.
+    NoAddress,
+
+    /// The nameserver could not be reached.
+    Unreachable(TransportError),
+
+    /// The nameserver responded with an error.
+    Rcode(ErrorCode),
+
+    /// The nameserver responded, but not authoritatively for the zone.
+    NotAuthoritative,
+}
+
+/// A mistake in the delegation.
+#[derive(PartialEq, Debug)]
+pub enum Problem {
+
+    /// The parent refers to a nameserver that the zone does not list.
+    OnlyAtParent(Name),
+
+    /// The zone lists a nameserver that the parent does not refer to.
+    OnlyAtChild(Name),
+
+    /// A nameserver inside the zone has no glue at the parent, so
+    /// resolvers cannot find its address without already knowing it.
+    MissingGlue(Name),
+
+    /// A nameserver’s glue address at the parent is not one of the
+    /// addresses the zone gives it.
+    GlueMismatch(Name, Ipv4Addr),
+
+    /// A nameserver the parent refers to does not answer for the zone.
+    Lame(Name),
+}
+
+/// Something that stopped the delegation from being checked.
+#[derive(Debug)]
+pub enum DelegationError {
+
+    /// Following the referrals down from the root failed.
+    Trace(TraceError),
+
+    /// No nameserver referred to the zone. Either it is not a zone of its
+    /// own, or its parent’s nameservers serve it as well.
+    NotDelegated(Name),
+}
+
+
+/// Follows the referrals from the root down to the given zone, then asks
+/// each nameserver its parent refers to about it.
+pub fn check(tracer: &mut Tracer<'_>, zone: &Name, qclass: QClass) -> Result<Delegation, DelegationError> {
+    let trace = tracer.trace(zone, qtype!(NS), qclass);
+
+    let (parent, referral) = match find_referral(&trace.steps, zone) {
+        Some(found) => found,
+        None => {
+            return Err(match trace.error {
+                Some(e)  => DelegationError::Trace(e),
+                None     => DelegationError::NotDelegated(zone.clone()),
+            });
+        }
+    };
+
+    let mut servers = Vec::new();
+    for ns in &referral.nameservers {
+        let address = ns.address.or_else(|| tracer.look_up_address(&ns.name, 0));
+        let result = match address {
+            Some(address) => {
+                info!("Asking {:?} ({}) about zone {:?}", ns.name, address, zone);
+                tracer.query_server(address, zone, qtype!(NS), qclass)
+                      .map_err(Lameness::Unreachable)
+                      .and_then(|reply| child_answer(zone, &reply.response))
+            }
+            None => Err(Lameness::NoAddress),
+        };
+
+        servers.push(ServerCheck { name: ns.name.clone(), address, result });
+    }
+
+    let child_nameservers = servers.iter()
+        .find_map(|server| server.result.as_ref().ok())
+        .map(|answer| answer.nameservers.clone())
+        .unwrap_or_default();
+
+    Ok(Delegation { zone: zone.clone(), parent, parent_nameservers: referral.nameservers, child_nameservers, servers })
+}
+
+/// Finds the step where the zone got delegated to, returning the zone the
+/// nameserver was authoritative for and the referral it gave.
+fn find_referral(steps: &[Step], zone: &Name) -> Option<(Name, Referral)> {
+    steps.iter().find_map(|step| {
+        match Referral::from_response(&step.response) {
+            Some(referral) if same_name(&referral.zone, zone)  => Some((step.zone.clone(), referral)),
+            _                                                  => None,
+        }
+    })
+}
+
+/// Reads a nameserver’s answer to a query for the zone’s NS records,
+/// which has to be authoritative to count.
+fn child_answer(zone: &Name, response: &Response) -> Result<ChildAnswer, Lameness> {
+    if let Some(rcode) = response.flags.error_code {
+        return Err(Lameness::Rcode(rcode));
+    }
+
+    if ! response.flags.authoritative {
+        return Err(Lameness::NotAuthoritative);
+    }
+
+    let nameservers = response.answers.iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { qname, record: Record::NS(NS { nameserver }), .. } if same_name(qname, zone) => Some(nameserver.clone()),
+            _ => None,
+        }
+    }).collect::<Vec<_>>();
+
+    if nameservers.is_empty() {
+        return Err(Lameness::NotAuthoritative);
+    }
+
+    let addresses = response.additionals.iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { qname, record: Record::A(a), .. }  => Some((qname.clone(), a.address)),
+            _                                                     => None,
+        }
+    }).collect();
+
+    Ok(ChildAnswer { nameservers, addresses })
+}
+
+/// Whether two names are the same, ignoring case.
+fn same_name(a: &Name, b: &Name) -> bool {
+    a.as_str().eq_ignore_ascii_case(b.as_str())
+}
+
+
+impl Delegation {
+
+    /// Lists the mistakes in the delegation.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        for ns in &self.parent_nameservers {
+            if ! self.child_nameservers.is_empty() && ! self.child_nameservers.iter().any(|name| same_name(name, &ns.name)) {
+                problems.push(Problem::OnlyAtParent(ns.name.clone()));
+            }
+        }
+
+        for name in &self.child_nameservers {
+            if ! self.parent_nameservers.iter().any(|ns| same_name(&ns.name, name)) {
+                problems.push(Problem::OnlyAtChild(name.clone()));
+            }
+        }
+
+        for ns in &self.parent_nameservers {
+            match ns.address {
+                None if ns.name.is_below_or_at(&self.zone) => {
+                    problems.push(Problem::MissingGlue(ns.name.clone()));
+                }
+                Some(glue) => {
+                    let child_addresses = self.child_addresses(&ns.name);
+                    if ! child_addresses.is_empty() && ! child_addresses.contains(&glue) {
+                        problems.push(Problem::GlueMismatch(ns.name.clone(), glue));
+                    }
+                }
+                None => {}
+            }
+        }
+
+        for server in &self.servers {
+            if server.result.is_err() {
+                problems.push(Problem::Lame(server.name.clone()));
+            }
+        }
+
+        problems
+    }
+
+    /// The addresses the zone’s own nameservers gave for a nameserver.
+    fn child_addresses(&self, name: &Name) -> Vec<Ipv4Addr> {
+        let mut addresses = Vec::new();
+
+        for answer in self.servers.iter().filter_map(|server| server.result.as_ref().ok()) {
+            for (qname, address) in &answer.addresses {
+                if same_name(qname, name) && ! addresses.contains(address) {
+                    addresses.push(*address);
+                }
+            }
+        }
+
+        addresses
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OnlyAtParent(name)         => write!(f, "the parent lists {}, but the zone does not", name),
+            Self::OnlyAtChild(name)          => write!(f, "the zone lists {}, but the parent does not", name),
+            Self::MissingGlue(name)          => write!(f, "{} is inside the zone, but the parent has no glue for it", name),
+            Self::GlueMismatch(name, glue)   => write!(f, "the parent’s glue for {} is {}, which the zone does not give as its address", name, glue),
+            Self::Lame(name)                 => write!(f, "{} is lame, as it does not answer for the zone", name),
+        }
+    }
+}
+
+impl fmt::Display for Lameness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAddress         => write!(f, "no address"),
+            Self::Unreachable(e)    => write!(f, "unreachable ({:?})", e),
+            Self::Rcode(rcode)      => write!(f, "responded with {:?}", rcode),
+            Self::NotAuthoritative  => write!(f, "not authoritative"),
+        }
+    }
+}
+
+impl fmt::Display for DelegationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trace(e)            => write!(f, "{}", e),
+            Self::NotDelegated(zone)  => write!(f, "No nameserver referred to {}, so it is not a zone, or it shares its parent’s nameservers", zone),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::Flags;
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn parent(nameserver: &str, glue: Option<Ipv4Addr>) -> Nameserver {
+        Nameserver { name: name(nameserver), address: glue }
+    }
+
+    fn answered(nameserver: &str, nameservers: &[&str], addresses: &[(&str, Ipv4Addr)]) -> ServerCheck {
+        ServerCheck {
+            name: name(nameserver),
+            address: Some(Ipv4Addr::new(192, 0, 2, 1)),
+            result: Ok(ChildAnswer {
+                nameservers: nameservers.iter().map(|n| name(n)).collect(),
+                addresses: addresses.iter().map(|(n, a)| (name(n), *a)).collect(),
+            }),
+        }
+    }
+
+    fn delegation(parent_nameservers: Vec<Nameserver>, child_nameservers: &[&str], servers: Vec<ServerCheck>) -> Delegation {
+        Delegation {
+            zone: name("lookup.dog."),
+            parent: name("dog."),
+            parent_nameservers,
+            child_nameservers: child_nameservers.iter().map(|n| name(n)).collect(),
+            servers,
+        }
+    }
+
+    fn response(authoritative: bool, answers: Vec<Answer>) -> Response {
+        let mut flags = Flags::query();
+        flags.response = true;
+        flags.authoritative = authoritative;
+
+        Response { transaction_id: 0, flags, queries: Vec::new(), answers, authorities: Vec::new(), additionals: Vec::new() }
+    }
+
+    fn ns(zone: &str, nameserver: &str) -> Answer {
+        Answer::Standard { qname: name(zone), qclass: QClass::IN, ttl: 3600, record: Record::NS(NS { nameserver: name(nameserver) }) }
+    }
+
+    #[test]
+    fn consistent() {
+        let glue = Ipv4Addr::new(192, 0, 2, 1);
+        let d = delegation(
+            vec![ parent("ns1.lookup.dog.", Some(glue)), parent("ns.other.dog.", None) ],
+            &[ "ns1.lookup.dog.", "ns.other.dog." ],
+            vec![ answered("ns1.lookup.dog.", &[ "ns1.lookup.dog.", "ns.other.dog." ], &[ ("ns1.lookup.dog.", glue) ]) ],
+        );
+
+        assert_eq!(d.problems(), Vec::new());
+    }
+
+    #[test]
+    fn nameserver_sets_differ() {
+        let d = delegation(
+            vec![ parent("ns.other.dog.", None), parent("old.other.dog.", None) ],
+            &[ "ns.other.dog.", "new.other.dog." ],
+            vec![],
+        );
+
+        assert_eq!(d.problems(), vec![
+            Problem::OnlyAtParent(name("old.other.dog.")),
+            Problem::OnlyAtChild(name("new.other.dog.")),
+        ]);
+    }
+
+    #[test]
+    fn names_compared_without_case() {
+        let d = delegation(vec![ parent("NS.Other.Dog.", None) ], &[ "ns.other.dog." ], vec![]);
+        assert_eq!(d.problems(), Vec::new());
+    }
+
+    #[test]
+    fn missing_glue() {
+        let d = delegation(vec![ parent("ns1.lookup.dog.", None) ], &[ "ns1.lookup.dog." ], vec![]);
+        assert_eq!(d.problems(), vec![ Problem::MissingGlue(name("ns1.lookup.dog.")) ]);
+    }
+
+    #[test]
+    fn glue_mismatch() {
+        let glue = Ipv4Addr::new(192, 0, 2, 1);
+        let d = delegation(
+            vec![ parent("ns1.lookup.dog.", Some(glue)) ],
+            &[ "ns1.lookup.dog." ],
+            vec![ answered("ns1.lookup.dog.", &[ "ns1.lookup.dog." ], &[ ("ns1.lookup.dog.", Ipv4Addr::new(192, 0, 2, 99)) ]) ],
+        );
+
+        assert_eq!(d.problems(), vec![ Problem::GlueMismatch(name("ns1.lookup.dog."), glue) ]);
+    }
+
+    #[test]
+    fn lame_nameserver() {
+        let lame = ServerCheck { name: name("ns.other.dog."), address: None, result: Err(Lameness::NoAddress) };
+        let d = delegation(vec![ parent("ns.other.dog.", None) ], &[], vec![ lame ]);
+        assert_eq!(d.problems(), vec![ Problem::Lame(name("ns.other.dog.")) ]);
+    }
+
+    #[test]
+    fn authoritative_answer() {
+        let response = response(true, vec![ ns("lookup.dog.", "ns1.lookup.dog."), ns("lookup.dog.", "ns2.lookup.dog.") ]);
+        assert_eq!(child_answer(&name("lookup.dog."), &response).unwrap().nameservers,
+                   vec![ name("ns1.lookup.dog."), name("ns2.lookup.dog.") ]);
+    }
+
+    #[test]
+    fn non_authoritative_answer() {
+        let response = response(false, vec![ ns("lookup.dog.", "ns1.lookup.dog.") ]);
+        assert!(matches!(child_answer(&name("lookup.dog."), &response), Err(Lameness::NotAuthoritative)));
+    }
+
+    #[test]
+    fn referral_is_not_an_answer() {
+        let mut response = response(false, Vec::new());
+        response.authorities.push(ns("lookup.dog.", "ns1.lookup.dog."));
+        assert!(matches!(child_answer(&name("lookup.dog."), &response), Err(Lameness::NotAuthoritative)));
+    }
+}
@@ -0,0 +1,262 @@
+//! Comparing the records in a zone file against the ones a nameserver
+//! actually serves.
+//!
+//! There is no zone transfer support, so each set of records in the file
+//! gets queried for on its own. This means that records on the server at
+//! names or types that the file does not mention at all cannot be found.
+
+use std::collections::HashMap;
+
+use dns::{Answer, Name, QClass, Response, TypeInt};
+use dns::diff::{diff, Change, DiffOptions};
+
+
+/// A set of records from a zone file that share an owner name, type, and
+/// class, and so can all be fetched with a single query.
+#[derive(PartialEq, Debug)]
+pub struct RecordSet {
+
+    /// The owner name of every record in the set.
+    pub qname: Name,
+
+    /// The type of every record in the set.
+    pub qtype: TypeInt,
+
+    /// The class of every record in the set.
+    pub qclass: QClass,
+
+    /// The records themselves, in the order they appeared in the file.
+    pub answers: Vec<Answer>,
+}
+
+/// How one set of records in the zone file differs from the records the
+/// nameserver returned for the same name, type, and class.
+#[derive(PartialEq, Debug)]
+pub struct SetDiff {
+
+    /// The owner name that was queried.
+    pub qname: Name,
+
+    /// The type that was queried.
+    pub qtype: TypeInt,
+
+    /// The class that was queried.
+    pub qclass: QClass,
+
+    /// Records in the zone file that the nameserver did not return.
+    pub only_in_file: Vec<Answer>,
+
+    /// Records the nameserver returned that are not in the zone file.
+    pub only_on_server: Vec<Answer>,
+
+    /// Records that both have, but with different TTLs, paired with the
+    /// TTL that the nameserver gave.
+    pub ttl_changes: Vec<(Answer, u32)>,
+}
+
+
+/// Groups the records read from a zone file into sets, in the order that
+/// each set first appears.
+pub fn record_sets(answers: Vec<Answer>) -> Vec<RecordSet> {
+    let mut sets: Vec<RecordSet> = Vec::new();
+    let mut indices: HashMap<_, usize> = HashMap::new();
+
+    for answer in answers {
+        if let Answer::Standard { qname, qclass, record, .. } = &answer {
+            let key = (qname.clone(), record.type_number(), qclass.to_u16());
+
+            match indices.get(&key).copied() {
+                Some(index) => {
+                    sets[index].answers.push(answer);
+                }
+                None => {
+                    let set = RecordSet { qname: qname.clone(), qtype: key.1, qclass: *qclass, answers: vec![ answer ] };
+                    let _ = indices.insert(key, sets.len());
+                    sets.push(set);
+                }
+            }
+        }
+    }
+
+    sets
+}
+
+/// Compares a set of records from the zone file against the response the
+/// nameserver sent when queried for them.
+///
+/// Matching records are looked for in every section of the response, as
+/// queries for delegation NS records and their glue get answered with a
+/// referral rather than an answer.
+pub fn compare(set: &RecordSet, response: &Response) -> SetDiff {
+    let sections = response.answers.iter()
+        .chain(&response.authorities)
+        .chain(&response.additionals);
+
+    // Glue records can turn up in more than one section.
+    let mut server_answers: Vec<&Answer> = Vec::new();
+    for answer in sections.filter(|answer| set.contains_same_kind(answer)) {
+        if ! server_answers.iter().any(|s| record_data(s) == record_data(answer)) {
+            server_answers.push(answer);
+        }
+    }
+
+    let mut set_diff = SetDiff {
+        qname: set.qname.clone(),
+        qtype: set.qtype,
+        qclass: set.qclass,
+        only_in_file: Vec::new(),
+        only_on_server: Vec::new(),
+        ttl_changes: Vec::new(),
+    };
+
+    // The records get compared as the answers of two messages, with the
+    // server’s on the left, so a changed TTL comes out as the record in
+    // the file along with the TTL the server gave.
+    let message = |answers| Response { answers, authorities: Vec::new(), additionals: Vec::new(), .. response.clone() };
+    let server = message(server_answers.into_iter().cloned().collect());
+    let file = message(set.answers.clone());
+
+    for change in diff(&server, &file, DiffOptions::default()).changes {
+        match change {
+            Change::Removed(_, answer)   => set_diff.only_on_server.push(answer),
+            Change::Added(_, answer)     => set_diff.only_in_file.push(answer),
+            Change::TTL(_, answer, ttl)  => set_diff.ttl_changes.push((answer, ttl)),
+            _                            => {/* only the records differ */},
+        }
+    }
+
+    set_diff
+}
+
+
+impl RecordSet {
+
+    /// Whether the given answer has the same owner name, type, and class
+    /// as the records in this set.
+    fn contains_same_kind(&self, answer: &Answer) -> bool {
+        match answer {
+            Answer::Standard { qname, qclass, record, .. } => {
+                *qname == self.qname && *qclass == self.qclass && record.type_number() == self.qtype
+            }
+            Answer::Pseudo { .. } => {
+                false
+            }
+        }
+    }
+}
+
+impl SetDiff {
+
+    /// Whether the zone file and the nameserver agree on this set.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_file.is_empty() && self.only_on_server.is_empty() && self.ttl_changes.is_empty()
+    }
+}
+
+
+fn record_data(answer: &Answer) -> Option<&dns::record::Record> {
+    match answer {
+        Answer::Standard { record, .. }  => Some(record),
+        Answer::Pseudo { .. }            => None,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::Flags;
+    use dns::record::{Record, A, NS};
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn a(owner: &str, ttl: u32, last_octet: u8) -> Answer {
+        let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, last_octet) });
+        Answer::Standard { qname: name(owner), qclass: QClass::IN, ttl, record }
+    }
+
+    fn response(answers: Vec<Answer>, authorities: Vec<Answer>) -> Response {
+        Response {
+            transaction_id: 0x1234,
+            flags: Flags::from_u16(0x8400),
+            queries: Vec::new(),
+            answers,
+            authorities,
+            additionals: Vec::new(),
+        }
+    }
+
+    fn a_set(answers: Vec<Answer>) -> RecordSet {
+        RecordSet { qname: name("lookup.dog."), qtype: 1, qclass: QClass::IN, answers }
+    }
+
+    #[test]
+    fn grouping() {
+        let ns = Answer::Standard {
+            qname: name("lookup.dog."), qclass: QClass::IN, ttl: 300,
+            record: Record::NS(NS { nameserver: name("ns1.lookup.dog.") }),
+        };
+
+        let sets = record_sets(vec![ a("lookup.dog.", 300, 1), ns.clone(), a("LOOKUP.dog.", 300, 2), a("www.lookup.dog.", 300, 1) ]);
+        assert_eq!(sets.len(), 3);
+        assert_eq!(sets[0].answers, vec![ a("lookup.dog.", 300, 1), a("LOOKUP.dog.", 300, 2) ]);
+        assert_eq!(sets[1].answers, vec![ ns ]);
+        assert_eq!(sets[2].qname, name("www.lookup.dog."));
+    }
+
+    #[test]
+    fn identical() {
+        let set = a_set(vec![ a("lookup.dog.", 300, 1), a("lookup.dog.", 300, 2) ]);
+        let diff = compare(&set, &response(vec![ a("Lookup.Dog.", 300, 2), a("lookup.dog.", 300, 1) ], Vec::new()));
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn missing_from_server() {
+        let set = a_set(vec![ a("lookup.dog.", 300, 1) ]);
+        let diff = compare(&set, &response(Vec::new(), Vec::new()));
+        assert_eq!(diff.only_in_file, vec![ a("lookup.dog.", 300, 1) ]);
+        assert_eq!(diff.only_on_server, vec![]);
+    }
+
+    #[test]
+    fn different_data() {
+        let set = a_set(vec![ a("lookup.dog.", 300, 1) ]);
+        let diff = compare(&set, &response(vec![ a("lookup.dog.", 300, 2) ], Vec::new()));
+        assert_eq!(diff.only_in_file, vec![ a("lookup.dog.", 300, 1) ]);
+        assert_eq!(diff.only_on_server, vec![ a("lookup.dog.", 300, 2) ]);
+    }
+
+    #[test]
+    fn different_ttl() {
+        let set = a_set(vec![ a("lookup.dog.", 300, 1) ]);
+        let diff = compare(&set, &response(vec![ a("lookup.dog.", 600, 1) ], Vec::new()));
+        assert_eq!(diff.ttl_changes, vec![ (a("lookup.dog.", 300, 1), 600) ]);
+        assert!(diff.only_in_file.is_empty());
+    }
+
+    #[test]
+    fn ignores_other_records() {
+        let set = a_set(vec![ a("lookup.dog.", 300, 1) ]);
+        let diff = compare(&set, &response(vec![ a("lookup.dog.", 300, 1) ], vec![ a("ns1.lookup.dog.", 300, 9) ]));
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn referral() {
+        let set = RecordSet {
+            qname: name("sub.lookup.dog."), qtype: 2, qclass: QClass::IN,
+            answers: vec![ Answer::Standard {
+                qname: name("sub.lookup.dog."), qclass: QClass::IN, ttl: 300,
+                record: Record::NS(NS { nameserver: name("ns1.sub.lookup.dog.") }),
+            } ],
+        };
+
+        let diff = compare(&set, &response(Vec::new(), set.answers.clone()));
+        assert!(diff.is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+//! Output in the same layout as dig, for scripts and people that expect it.
+
+use std::net::SocketAddr;
+
+use dns::{Response, Answer, Query, ErrorCode, Flags};
+use dns::presentation::{hex, timestamp, type_mnemonic};
+use dns::record::OPT;
+
+use crate::output::{Exchange, Sections, opcode_mnemonic, protocol_name};
+
+
+/// Returns the lines dig would print for a response: the header and flags,
+/// the OPT pseudosection, each section in turn, and a footer with where
+/// the response came from and when.
+pub fn response_lines(response: &Response, exchange: Option<&Exchange>, now: u64) -> Vec<String> {
+    let edns = exchange.and_then(|e| e.edns.as_ref());
+    let standard = |answers: &[Answer]| answers.iter().filter(|a| a.is_standard()).cloned().collect::<Vec<_>>();
+    let (answers, authorities, additionals) = (standard(&response.answers), standard(&response.authorities), standard(&response.additionals));
+
+    let question = response.queries.iter()
+                           .map(|q| format!("{} {}", q.qname, type_mnemonic(q.qtype)))
+                           .collect::<Vec<_>>().join(" ");
+
+    let mut lines = vec![
+        String::new(),
+        format!("; <<>> dog {} <<>> {}", env!("CARGO_PKG_VERSION"), question),
+        ";; global options: +cmd".into(),
+        ";; Got answer:".into(),
+        format!(";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+                mnemonic_or_reserved(opcode_mnemonic(response.flags.opcode), response.flags.opcode.into()),
+                mnemonic_or_reserved(ErrorCode::mnemonic_of(response.flags.error_code), response.flags.error_code.map_or(0, ErrorCode::to_bits)),
+                response.transaction_id),
+        format!(";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+                flag_names(response.flags).join(" "), response.queries.len(), answers.len(), authorities.len(),
+                additionals.len() + usize::from(edns.is_some())),
+    ];
+
+    lines.push(String::new());
+    if let Some(opt) = edns {
+        lines.push(";; OPT PSEUDOSECTION:".into());
+        lines.extend(edns_lines(opt));
+        lines.push(String::new());
+    }
+
+    lines.push(";; QUESTION SECTION:".into());
+    lines.extend(response.queries.iter().map(question_line));
+
+    for (title, section) in &[ ("ANSWER", answers), ("AUTHORITY", authorities), ("ADDITIONAL", additionals) ] {
+        if ! section.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(";; {} SECTION:", title));
+            lines.extend(section.iter().filter_map(record_line));
+        }
+    }
+
+    lines.push(String::new());
+    if let Some(exchange) = exchange {
+        lines.push(format!(";; Query time: {} msec", exchange.round_trip.as_millis()));
+        lines.push(format!(";; SERVER: {}({}) ({})", server_address(exchange), exchange.destination.nameserver, protocol_name(exchange.protocol)));
+    }
+
+    lines.push(format!(";; WHEN: {}", when(now)));
+    if let Some(exchange) = exchange {
+        lines.push(format!(";; MSG SIZE  rcvd: {}", exchange.bytes.len()));
+    }
+
+    lines.push(String::new());
+    lines
+}
+
+/// Returns the lines dig would print for a response when it has been told
+/// which sections to print with `+noall`: the records in those sections,
+/// with no header, titles, or footer.
+pub fn section_lines(response: &Response, sections: Sections) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if sections.question {
+        lines.extend(response.queries.iter().map(question_line));
+    }
+
+    for (wanted, section) in &[ (sections.answer, &response.answers), (sections.authority, &response.authorities), (sections.additional, &response.additionals) ] {
+        if *wanted {
+            lines.extend(section.iter().filter_map(record_line));
+        }
+    }
+
+    lines
+}
+
+/// Formats a record the way dig does, with tabs between the fields and
+/// the name padded out to line up the rest.
+pub fn record_line(answer: &Answer) -> Option<String> {
+    match answer {
+        Answer::Standard { qname, qclass, ttl, record } => {
+            let name = fully_qualified(&qname.to_string());
+            Some(format!("{}{}{}\t{}\t{}\t{}", name, padding(name.len()), ttl, qclass, record.type_mnemonic(), record))
+        }
+        Answer::Pseudo { .. } => {
+            None
+        }
+    }
+}
+
+
+fn question_line(query: &Query) -> String {
+    let name = format!(";{}", fully_qualified(&query.qname.to_string()));
+    format!("{}{}\t{}\t{}", name, padding(name.len()), query.qclass, type_mnemonic(query.qtype))
+}
+
+fn fully_qualified(name: &str) -> String {
+    if name.ends_with('.') { name.into() } else { format!("{}.", name) }
+}
+
+/// The tabs that take a name up to the 24th column, where dig puts the
+/// TTL, with at least one tab in case the name is longer than that.
+fn padding(name_length: usize) -> String {
+    let tabs = if name_length < 24 { (24 - name_length).div_ceil(8) } else { 1 };
+    "\t".repeat(tabs)
+}
+
+fn flag_names(flags: Flags) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if flags.response            { names.push("qr"); }
+    if flags.authoritative       { names.push("aa"); }
+    if flags.truncated           { names.push("tc"); }
+    if flags.recursion_desired   { names.push("rd"); }
+    if flags.recursion_available { names.push("ra"); }
+    if flags.authentic_data      { names.push("ad"); }
+    if flags.checking_disabled   { names.push("cd"); }
+    if flags.reserved            { names.push("z"); }
+    names
+}
+
+/// Writes an opcode or rcode the way dig does, as its mnemonic, or as
+/// `RESERVED` followed by its number if it has not been assigned one.
+fn mnemonic_or_reserved(mnemonic: Option<&str>, number: u16) -> String {
+    match mnemonic {
+        Some(mnemonic)  => mnemonic.into(),
+        None            => format!("RESERVED{}", number),
+    }
+}
+
+/// Describes the EDNS version, flags, and payload size, followed by a line
+/// for each option, with the ones that have been assigned named.
+fn edns_lines(opt: &OPT) -> Vec<String> {
+    let flags = if opt.dnssec_ok() { " do" } else { "" };
+    let mut lines = vec![ format!("; EDNS: version: {}, flags:{}; udp: {}", opt.edns0_version, flags, opt.udp_payload_size) ];
+
+    for (code, value) in opt.options() {
+        let value = hex(value).to_lowercase();
+
+        match (code, OPT::option_name(code)) {
+            (11, _)           => lines.push(keepalive_line(opt)),
+            (_, Some(name))   => lines.push(format!("; {}: {}", name, value)),
+            (_, None)         => lines.push(format!("; OPT={}: {}", code, value)),
+        }
+    }
+
+    lines
+}
+
+/// Describes the edns-tcp-keepalive option, which has the idle timeout the
+/// server advertised in it, if it is a response.
+fn keepalive_line(opt: &OPT) -> String {
+    match opt.tcp_keepalive() {
+        Some(timeout)  => format!("; TCP-KEEPALIVE: {:.1} secs", timeout.as_secs_f64()),
+        None           => "; TCP-KEEPALIVE".into(),
+    }
+}
+
+/// Writes the nameserver the way dig does, as the address the query was
+/// sent to followed by its port. Responses that were not sent anywhere,
+/// such as cached ones, only have the nameserver as it was given.
+fn server_address(exchange: &Exchange) -> String {
+    let nameserver = &exchange.destination.nameserver;
+    match exchange.connection.address.or_else(|| nameserver.parse::<SocketAddr>().ok()) {
+        Some(address)  => format!("{}#{}", address.ip(), address.port()),
+        None           => nameserver.clone(),
+    }
+}
+
+/// Formats the current time as dig does, such as `Thu Oct 15 12:00:00 UTC
+/// 2026`, although always in UTC rather than the local time zone.
+fn when(now: u64) -> String {
+    const DAYS: [&str; 7] = [ "Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed" ];
+    const MONTHS: [&str; 12] = [ "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec" ];
+
+    let stamp = timestamp(now as u32);
+    let month = stamp[4 .. 6].parse::<usize>().unwrap_or(1);
+
+    format!("{} {} {} {}:{}:{} UTC {}",
+            DAYS[(now / 86400 % 7) as usize], MONTHS[month - 1], &stamp[6 .. 8],
+            &stamp[8 .. 10], &stamp[10 .. 12], &stamp[12 .. 14], &stamp[0 .. 4])
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+    use dns::QClass;
+    use dns::record::{Record, A};
+    use dns_transport::{Connection, Protocol};
+    use crate::connect::TransportType;
+    use crate::requests::Destination;
+
+    #[test]
+    fn full_response() {
+        let response = Response {
+            transaction_id: 4660,
+            flags: Flags::from_u16(0x8180),
+            queries: vec![ Query { qname: "lookup.dog".parse().unwrap(), qclass: QClass::IN, qtype: 1 } ],
+            answers: vec![ Answer::Standard {
+                qname: "lookup.dog.".parse().unwrap(),
+                qclass: QClass::IN,
+                ttl: 300,
+                record: Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) }),
+            } ],
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        };
+
+        let exchange = Exchange {
+            destination: Destination { nameserver: "1.1.1.1".into(), transport_type: TransportType::Automatic, authority: None },
+            round_trip: Duration::from_millis(12),
+            attempts: 1,
+            protocol: Protocol::UDP,
+            connection: Connection { address: Some("1.1.1.1:5353".parse().unwrap()), .. Connection::default() },
+            edns: Some(OPT { udp_payload_size: 1232, higher_bits: 0, edns0_version: 0, flags: 0, data: vec![ 0, 10, 0, 2, 0xAB, 0xCD ] }),
+            bytes: vec![ 0; 61 ],
+        };
+
+        let lines = response_lines(&response, Some(&exchange), 1_602_763_200);
+        assert_eq!(lines[1 ..].join("\n"), format!("\
+; <<>> dog {} <<>> lookup.dog A
+;; global options: +cmd
+;; Got answer:
+;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 4660
+;; flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1
+
+;; OPT PSEUDOSECTION:
+; EDNS: version: 0, flags:; udp: 1232
+; COOKIE: abcd
+
+;; QUESTION SECTION:
+;lookup.dog.\t\t\tIN\tA
+
+;; ANSWER SECTION:
+lookup.dog.\t\t300\tIN\tA\t127.0.0.1
+
+;; Query time: 12 msec
+;; SERVER: 1.1.1.1#5353(1.1.1.1) (UDP)
+;; WHEN: Thu Oct 15 12:00:00 UTC 2020
+;; MSG SIZE  rcvd: 61
+", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn selected_sections() {
+        let record = |qname: &str, address| Answer::Standard {
+            qname: qname.parse().unwrap(),
+            qclass: QClass::IN,
+            ttl: 300,
+            record: Record::A(A { address }),
+        };
+
+        let response = Response {
+            transaction_id: 4660,
+            flags: Flags::from_u16(0x8180),
+            queries: vec![ Query { qname: "lookup.dog".parse().unwrap(), qclass: QClass::IN, qtype: 1 } ],
+            answers: vec![ record("lookup.dog.", Ipv4Addr::new(127, 0, 0, 1)) ],
+            authorities: Vec::new(),
+            additionals: vec![ record("ns.lookup.dog.", Ipv4Addr::new(127, 0, 0, 2)) ],
+        };
+
+        let sections = Sections { question: false, answer: true, authority: false, additional: false };
+        assert_eq!(section_lines(&response, sections), vec![ "lookup.dog.\t\t300\tIN\tA\t127.0.0.1" ]);
+
+        let sections = Sections { question: true, answer: false, authority: true, additional: true };
+        assert_eq!(section_lines(&response, sections), vec![ ";lookup.dog.\t\t\tIN\tA", "ns.lookup.dog.\t\t300\tIN\tA\t127.0.0.2" ]);
+    }
+
+    #[test]
+    fn tcp_keepalive() {
+        let opt = OPT { udp_payload_size: 1232, higher_bits: 0, edns0_version: 0, flags: 0, data: vec![ 0, 11, 0, 2, 0x01, 0x2C ] };
+        assert_eq!(edns_lines(&opt), vec![ "; EDNS: version: 0, flags:; udp: 1232", "; TCP-KEEPALIVE: 30.0 secs" ]);
+    }
+
+    #[test]
+    fn long_names() {
+        assert_eq!(padding(23), "\t");
+        assert_eq!(padding(16), "\t");
+        assert_eq!(padding(15), "\t\t");
+        assert_eq!(padding(40), "\t");
+    }
+
+    #[test]
+    fn reserved_opcodes() {
+        assert_eq!(mnemonic_or_reserved(opcode_mnemonic(6), 6), "DSO");
+        assert_eq!(mnemonic_or_reserved(opcode_mnemonic(3), 3), "RESERVED3");
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(when(0), "Thu Jan 01 00:00:00 UTC 1970");
+    }
+}
@@ -0,0 +1,188 @@
+//! Checking responses against what the user expects them to contain, so
+//! dog can be used as a health check that fails when they do not match.
+
+use std::fmt;
+
+use dns::{Response, Answer, ErrorCode};
+
+use crate::locale::ENGLISH;
+use crate::output::{TextFormat, TtlFormat};
+
+
+/// What the user expects the responses to contain.
+#[derive(PartialEq, Debug, Default)]
+pub struct Expectations {
+
+    /// Values that have to be among the answers, written the same way as
+    /// in the short output mode, such as an address or a domain name.
+    pub values: Vec<String>,
+
+    /// The rcode, as a number, that every response has to have.
+    pub rcode: Option<u16>,
+
+    /// The lowest TTL that any answer is allowed to have.
+    pub ttl_min: Option<u32>,
+}
+
+/// One way the responses did not match what was expected.
+#[derive(PartialEq, Debug)]
+pub enum Failure {
+
+    /// None of the answers had this value.
+    MissingValue(String),
+
+    /// A response had a different rcode. Contains the expected rcode and
+    /// the one it had.
+    WrongRcode(u16, u16),
+
+    /// An answer had a TTL lower than the minimum. Contains the answer,
+    /// written as a zone file record, and the minimum.
+    LowTTL(String, u32),
+}
+
+
+impl Expectations {
+
+    /// Whether the user expects anything at all.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.rcode.is_none() && self.ttl_min.is_none()
+    }
+
+    /// Checks every response against the expectations, returning each way
+    /// they failed to match.
+    pub fn check(&self, responses: &[&Response]) -> Vec<Failure> {
+        let mut failures = Vec::new();
+        let answers = responses.iter().flat_map(|r| &r.answers).collect::<Vec<_>>();
+
+        // Values are compared without any trailing dot and regardless of
+        // case, so a domain name matches however it was written.
+        let format = TextFormat { format_durations: false, unicode_names: false, concat_txt: true, ttl_format: TtlFormat::Raw, catalog: &ENGLISH };
+        let found = answers.iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { record, .. }  => Some(normalise(&format.record_short_value(record))),
+                Answer::Pseudo { .. }            => None,
+            }
+        }).collect::<Vec<_>>();
+
+        for value in &self.values {
+            if ! found.contains(&normalise(value)) {
+                failures.push(Failure::MissingValue(value.clone()));
+            }
+        }
+
+        if let Some(expected) = self.rcode {
+            for response in responses {
+                let rcode = rcode_number(response.flags.error_code);
+                if rcode != expected {
+                    failures.push(Failure::WrongRcode(expected, rcode));
+                }
+            }
+        }
+
+        if let Some(minimum) = self.ttl_min {
+            for answer in answers {
+                if let Answer::Standard { ttl, .. } = answer {
+                    if *ttl < minimum {
+                        failures.push(Failure::LowTTL(answer.to_string(), minimum));
+                    }
+                }
+            }
+        }
+
+        failures
+    }
+}
+
+/// Parses an rcode given as its mnemonic, in any case, or as a number.
+pub fn parse_rcode(input: &str) -> Option<u16> {
+    ErrorCode::parse_optional(input).map(rcode_number)
+}
+
+fn rcode_number(rcode: Option<ErrorCode>) -> u16 {
+    rcode.map_or(0, ErrorCode::to_bits)
+}
+
+fn rcode_mnemonic(rcode: u16) -> String {
+    match ErrorCode::mnemonic_of(ErrorCode::from_bits(rcode)) {
+        Some(mnemonic)  => mnemonic.into(),
+        None            => rcode.to_string(),
+    }
+}
+
+fn normalise(value: &str) -> String {
+    value.trim_end_matches('.').to_lowercase()
+}
+
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingValue(value)          => write!(f, "Expected answer {:?} was not found", value),
+            Self::WrongRcode(expected, got)    => write!(f, "Expected status {} but got {}", rcode_mnemonic(*expected), rcode_mnemonic(*got)),
+            Self::LowTTL(answer, minimum)      => write!(f, "TTL of {} is below the minimum of {}", answer, minimum),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::{Flags, QClass};
+    use dns::record::{Record, A, CNAME};
+
+    fn response(ttl: u32, records: Vec<Record>, rcode: Option<ErrorCode>) -> Response {
+        let mut flags = Flags::from_u16(0x8180);
+        flags.error_code = rcode;
+
+        Response {
+            transaction_id: 1,
+            flags,
+            queries: Vec::new(),
+            answers: records.into_iter().map(|record| Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl, record }).collect(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    fn a(last_octet: u8) -> Record {
+        Record::A(A { address: Ipv4Addr::new(127, 0, 0, last_octet) })
+    }
+
+    #[test]
+    fn values_found() {
+        let cname = Record::CNAME(CNAME { domain: "Other.Lookup.Dog.".parse().unwrap() });
+        let expectations = Expectations { values: vec![ "127.0.0.1".into(), "other.lookup.dog".into() ], .. Expectations::default() };
+        assert_eq!(expectations.check(&[ &response(300, vec![ cname, a(1) ], None) ]), vec![]);
+    }
+
+    #[test]
+    fn value_missing() {
+        let expectations = Expectations { values: vec![ "127.0.0.2".into() ], .. Expectations::default() };
+        assert_eq!(expectations.check(&[ &response(300, vec![ a(1) ], None) ]),
+                   vec![ Failure::MissingValue("127.0.0.2".into()) ]);
+    }
+
+    #[test]
+    fn wrong_rcode() {
+        let expectations = Expectations { rcode: Some(0), .. Expectations::default() };
+        assert_eq!(expectations.check(&[ &response(300, vec![], Some(ErrorCode::NXDomain)) ]),
+                   vec![ Failure::WrongRcode(0, 3) ]);
+    }
+
+    #[test]
+    fn low_ttl() {
+        let expectations = Expectations { ttl_min: Some(300), .. Expectations::default() };
+        assert_eq!(expectations.check(&[ &response(60, vec![ a(1) ], None) ]).len(), 1);
+        assert_eq!(expectations.check(&[ &response(300, vec![ a(1) ], None) ]), vec![]);
+    }
+
+    #[test]
+    fn rcodes() {
+        assert_eq!(parse_rcode("nxdomain"), Some(3));
+        assert_eq!(parse_rcode("23"), Some(23));
+        assert_eq!(parse_rcode("WOOF"), None);
+    }
+}
@@ -0,0 +1,290 @@
+//! The kinds of ways a lookup can fail, which pick both the exit status
+//! and the `kind` in the JSON output.
+
+use dns::ErrorCode;
+use dns_transport::{Error as TransportError, NetworkFailure};
+
+use crate::exits;
+
+
+/// A **failure kind** is one of the ways dog can fail to look something
+/// up. Each one has a name that programs can match on in the JSON output,
+/// which stays the same between versions, and an exit status.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum FailureKind {
+
+    /// Nothing was listening on the nameserver’s UDP port.
+    PortUnreachable,
+
+    /// There was no route to the nameserver’s network or host.
+    NetworkUnreachable,
+
+    /// Connecting to the nameserver, or waiting for its response, took
+    /// too long.
+    Timeout,
+
+    /// The nameserver refused the TCP connection.
+    ConnectionRefused,
+
+    /// Any other network error.
+    Network,
+
+    /// The HTTP request for DNS-over-HTTPS failed.
+    Http,
+
+    /// The TLS connection could not be set up.
+    Tls,
+
+    /// The server’s certificate did not have a pinned public key.
+    PinMismatch,
+
+    /// The HTTPS server responded with an error status.
+    HttpStatus,
+
+    /// The response could not be parsed.
+    Parse,
+
+    /// The response had the wrong transaction ID.
+    TransactionIdMismatch,
+
+    /// The response was for a different question.
+    QuestionMismatch,
+
+    /// The response came from somewhere the query was not sent.
+    WrongSource,
+
+    /// The response’s question had its case changed.
+    CaseMismatch,
+
+    /// The nameserver responded with NXDOMAIN.
+    NXDomain,
+
+    /// The nameserver responded with any other error.
+    ServerError,
+
+    /// None of the nameservers for a zone had an address.
+    NoAddresses,
+
+    /// A zone has no NS records.
+    NoNameservers,
+
+    /// A nameserver is not authoritative for a zone it was meant to serve.
+    NotAuthoritative,
+
+    /// No nameserver referred to a zone.
+    NotDelegated,
+
+    /// A nameserver referred to a zone outside the one it serves.
+    BadReferral,
+
+    /// There were more referrals than dog is willing to follow.
+    TooManySteps,
+
+    /// A response did not match what was expected of it.
+    ExpectationFailed,
+}
+
+impl FailureKind {
+
+    /// Picks the kind of a query that failed, which is the way the
+    /// nameserver could not be reached if that is known.
+    pub fn of(error: &TransportError) -> Self {
+        match error.network_failure() {
+            Some(NetworkFailure::PortUnreachable)     => return Self::PortUnreachable,
+            Some(NetworkFailure::NetworkUnreachable)  => return Self::NetworkUnreachable,
+            Some(NetworkFailure::Timeout)             => return Self::Timeout,
+            Some(NetworkFailure::ConnectionRefused)   => return Self::ConnectionRefused,
+            None                                      => {}
+        }
+
+        match error {
+            TransportError::NetworkError(_)                => Self::Network,
+            TransportError::HttpError(_)                   => Self::Http,
+            TransportError::SslError(_)                    => Self::Tls,
+            TransportError::PinMismatch { .. }             => Self::PinMismatch,
+            TransportError::BadRequest                     => Self::HttpStatus,
+            TransportError::WireError(_)                   => Self::Parse,
+            TransportError::MalformedResponse { .. }       => Self::Parse,
+            TransportError::TransactionIdMismatch { .. }   => Self::TransactionIdMismatch,
+            TransportError::QuestionMismatch { .. }        => Self::QuestionMismatch,
+            TransportError::WrongSource { .. }             => Self::WrongSource,
+            TransportError::CaseMismatch { .. }            => Self::CaseMismatch,
+            TransportError::PortUnreachable(_)             => Self::PortUnreachable,
+        }
+    }
+
+    /// Picks the kind of an error code a nameserver responded with.
+    pub fn of_rcode(rcode: ErrorCode) -> Self {
+        match rcode {
+            ErrorCode::NXDomain  => Self::NXDomain,
+            _                    => Self::ServerError,
+        }
+    }
+
+    /// The exit status for this kind of failure.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::PortUnreachable     => exits::PORT_UNREACHABLE,
+            Self::NetworkUnreachable  => exits::NETWORK_UNREACHABLE,
+            Self::Timeout             => exits::TIMED_OUT,
+            Self::ConnectionRefused   => exits::CONNECTION_REFUSED,
+            Self::Parse               => exits::PARSE_ERROR,
+            Self::NXDomain            => exits::NXDOMAIN,
+            Self::ServerError         => exits::SERVER_ERROR,
+            Self::ExpectationFailed   => exits::EXPECTATION_FAILED,
+            _                         => exits::NETWORK_ERROR,
+        }
+    }
+
+    /// The name of this kind of failure in the JSON output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PortUnreachable        => "port-unreachable",
+            Self::NetworkUnreachable     => "network-unreachable",
+            Self::Timeout                => "timeout",
+            Self::ConnectionRefused      => "connection-refused",
+            Self::Network                => "network",
+            Self::Http                   => "http",
+            Self::Tls                    => "tls",
+            Self::PinMismatch            => "pin-mismatch",
+            Self::HttpStatus             => "http-status",
+            Self::Parse                  => "parse",
+            Self::TransactionIdMismatch  => "transaction-id-mismatch",
+            Self::QuestionMismatch       => "question-mismatch",
+            Self::WrongSource            => "wrong-source",
+            Self::CaseMismatch           => "case-mismatch",
+            Self::NXDomain               => "nxdomain",
+            Self::ServerError            => "server-error",
+            Self::NoAddresses            => "no-addresses",
+            Self::NoNameservers          => "no-nameservers",
+            Self::NotAuthoritative       => "not-authoritative",
+            Self::NotDelegated           => "not-delegated",
+            Self::BadReferral            => "bad-referral",
+            Self::TooManySteps           => "too-many-steps",
+            Self::ExpectationFailed      => "expectation-failed",
+        }
+    }
+}
+
+
+/// Something that stopped a command from finishing what it was doing,
+/// which has a kind that picks its exit status and its name in the JSON
+/// output.
+pub trait Failure {
+
+    /// The kind of this failure.
+    fn kind(&self) -> FailureKind;
+
+    /// The exit status for this failure.
+    fn exit_code(&self) -> i32 {
+        self.kind().exit_code()
+    }
+}
+
+impl Failure for TransportError {
+    fn kind(&self) -> FailureKind {
+        FailureKind::of(self)
+    }
+}
+
+impl Failure for crate::lookup::LookupError {
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::Transport(_, e)  => FailureKind::of(e),
+            Self::Rcode(_, rcode)  => FailureKind::of_rcode(*rcode),
+        }
+    }
+}
+
+impl Failure for crate::expect::Failure {
+    fn kind(&self) -> FailureKind {
+        FailureKind::ExpectationFailed
+    }
+}
+
+impl Failure for crate::authority::AuthorityError {
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::Lookup(e)        => e.kind(),
+            Self::NoAddresses(_)   => FailureKind::NoAddresses,
+        }
+    }
+}
+
+impl Failure for crate::soa::SoaError {
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::Lookup(e)          => e.kind(),
+            Self::NoNameservers(_)   => FailureKind::NoNameservers,
+        }
+    }
+}
+
+impl Failure for crate::soa::ServerError {
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::Unreachable(e)     => FailureKind::of(e),
+            Self::Rcode(rcode)       => FailureKind::of_rcode(*rcode),
+            Self::NoAddresses        => FailureKind::NoAddresses,
+            Self::NotAuthoritative   => FailureKind::NotAuthoritative,
+        }
+    }
+}
+
+impl Failure for crate::trace::TraceError {
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::Transport(e)    => FailureKind::of(e),
+            Self::NoAddresses(_)  => FailureKind::NoAddresses,
+            Self::BadReferral(_)  => FailureKind::BadReferral,
+            Self::TooManySteps    => FailureKind::TooManySteps,
+        }
+    }
+}
+
+impl Failure for crate::delegation::DelegationError {
+    fn kind(&self) -> FailureKind {
+        match self {
+            Self::Trace(e)          => e.kind(),
+            Self::NotDelegated(_)   => FailureKind::NotDelegated,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wire_errors_parse() {
+        let error = TransportError::WireError(dns::WireError::IO);
+        assert_eq!(FailureKind::of(&error), FailureKind::Parse);
+        assert_eq!(error.exit_code(), exits::PARSE_ERROR);
+    }
+
+    #[test]
+    fn port_unreachable() {
+        let error = TransportError::PortUnreachable("127.0.0.1:53".parse().unwrap());
+        assert_eq!(error.kind().as_str(), "port-unreachable");
+        assert_eq!(error.exit_code(), exits::PORT_UNREACHABLE);
+    }
+
+    #[test]
+    fn rcodes() {
+        assert_eq!(FailureKind::of_rcode(ErrorCode::NXDomain).exit_code(), exits::NXDOMAIN);
+        assert_eq!(FailureKind::of_rcode(ErrorCode::QueryRefused).as_str(), "server-error");
+    }
+
+    #[test]
+    fn checker_failures_are_network_errors() {
+        assert_eq!(FailureKind::NoNameservers.exit_code(), exits::NETWORK_ERROR);
+    }
+
+    #[test]
+    fn expectation_failures() {
+        let failure = crate::expect::Failure::WrongRcode(0, 3);
+        assert_eq!(failure.kind().as_str(), "expectation-failed");
+        assert_eq!(failure.exit_code(), exits::EXPECTATION_FAILED);
+    }
+}
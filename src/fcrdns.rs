@@ -0,0 +1,194 @@
+//! Checking that an address’s reverse DNS is confirmed by its forward DNS,
+//! known as forward-confirmed reverse DNS, by looking up its PTR records
+//! and then the addresses of each name they point to, and seeing whether
+//! the address is among them. Mail servers often refuse to accept mail
+//! from addresses that fail this check.
+
+use std::net::IpAddr;
+
+use dns::{qtype, Answer, Name, Response, TypeInt};
+use dns::record::{A, AAAA, PTR, Record};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// The names an address’s reverse DNS points to, and their addresses.
+#[derive(PartialEq, Debug)]
+pub struct FcrdnsCheck {
+
+    /// The address that was looked up.
+    pub address: IpAddr,
+
+    /// The names in the address’s PTR records, in the order they were
+    /// returned in.
+    pub names: Vec<ForwardName>,
+}
+
+/// One of the names an address’s PTR records point to.
+#[derive(PartialEq, Debug)]
+pub struct ForwardName {
+
+    /// The name, as given in the PTR record.
+    pub name: Name,
+
+    /// The name’s addresses of the same family as the one being checked,
+    /// which is empty if it has none.
+    pub addresses: Vec<IpAddr>,
+}
+
+
+/// Looks up the PTR records at the given reverse name, calling the lookup
+/// function to query for them, and then for the A or AAAA records of
+/// each name they point to, depending on the family of the address.
+///
+/// # Panics
+///
+/// Panics if the name is not a reverse name for a whole address, which
+/// the options should have checked for already.
+pub fn check(reverse_name: &Name, mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>) -> Result<FcrdnsCheck, LookupError> {
+    let address = PTR::reversed_address(reverse_name).expect("Not a reverse name");
+
+    info!("Looking up PTR records at {:?}", reverse_name);
+    let response = lookup(reverse_name, qtype!(PTR)).map_err(|e| LookupError::Transport(reverse_name.clone(), e))?;
+    check_rcode(reverse_name, &response)?;
+
+    let targets = response.answers.into_iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { record: Record::PTR(ptr), .. }  => Some(ptr.cname),
+            _                                                   => None,
+        }
+    }).collect::<Vec<Name>>();
+
+    let forward_qtype = if address.is_ipv4() { qtype!(A) } else { qtype!(AAAA) };
+
+    let mut names = Vec::new();
+    for name in targets {
+        info!("Looking up addresses of {:?}", name);
+        let response = lookup(&name, forward_qtype).map_err(|e| LookupError::Transport(name.clone(), e))?;
+        check_rcode(&name, &response)?;
+
+        // Any CNAME records get followed by the resolver, so the addresses
+        // can be under a different name from the one that was asked for.
+        let addresses = response.answers.iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { record: Record::A(A { address }), .. }        => Some(IpAddr::V4(*address)),
+                Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => Some(IpAddr::V6(*address)),
+                _                                                                => None,
+            }
+        }).collect();
+
+        names.push(ForwardName { name, addresses });
+    }
+
+    Ok(FcrdnsCheck { address, names })
+}
+
+
+impl FcrdnsCheck {
+
+    /// Whether any of the names the address points to points back to it,
+    /// which is what passing the check means.
+    pub fn passed(&self) -> bool {
+        self.names.iter().any(|name| self.confirms(name))
+    }
+
+    /// Whether the given name has the address being checked among its
+    /// addresses.
+    pub fn confirms(&self, name: &ForwardName) -> bool {
+        name.addresses.contains(&self.address)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::ErrorCode;
+    use dns::record::CNAME;
+
+    /// Answers PTR queries with the given names, and address queries for
+    /// the names under `lookup.dog`, one of which is an alias and one of
+    /// which points somewhere else.
+    fn lookup(targets: &'static [&'static str]) -> impl FnMut(&Name, TypeInt) -> Result<Response, TransportError> {
+        move |qname, qtype| {
+            if qtype == qtype!(PTR) {
+                let answers = targets.iter().map(|t| answer(qname.as_str(), Record::PTR(PTR { cname: name(t) }))).collect();
+                return Ok(response(qname, qtype, None, answers));
+            }
+
+            let answers = match (qname.as_str(), qtype) {
+                ("mail.lookup.dog.", t) if t == qtype!(A)     => vec![ answer("mail.lookup.dog.", Record::A(A { address: "192.0.2.25".parse().unwrap() })) ],
+                ("mail.lookup.dog.", t) if t == qtype!(AAAA)  => vec![ answer("mail.lookup.dog.", Record::AAAA(AAAA { address: "2001:db8::25".parse().unwrap() })) ],
+                ("alias.lookup.dog.", t) if t == qtype!(A)    => vec![
+                    answer("alias.lookup.dog.", Record::CNAME(CNAME { domain: name("mail.lookup.dog.") })),
+                    answer("mail.lookup.dog.", Record::A(A { address: "192.0.2.25".parse().unwrap() })),
+                ],
+                ("web.lookup.dog.", t) if t == qtype!(A)      => vec![ answer("web.lookup.dog.", Record::A(A { address: "192.0.2.80".parse().unwrap() })) ],
+                _ => Vec::new(),
+            };
+
+            Ok(response(qname, qtype, None, answers))
+        }
+    }
+
+    fn reverse(address: &str) -> Name {
+        PTR::reverse_name(address.parse().unwrap())
+    }
+
+    #[test]
+    fn confirmed() {
+        let check = check(&reverse("192.0.2.25"), lookup(&[ "mail.lookup.dog." ])).unwrap();
+
+        assert_eq!(check.names, vec![ ForwardName { name: name("mail.lookup.dog."), addresses: vec![ "192.0.2.25".parse().unwrap() ] } ]);
+        assert!(check.passed());
+    }
+
+    #[test]
+    fn confirmed_over_ipv6() {
+        let check = check(&reverse("2001:db8::25"), lookup(&[ "mail.lookup.dog." ])).unwrap();
+        assert!(check.passed());
+    }
+
+    #[test]
+    fn confirmed_through_alias() {
+        let check = check(&reverse("192.0.2.25"), lookup(&[ "alias.lookup.dog." ])).unwrap();
+        assert!(check.passed());
+    }
+
+    #[test]
+    fn points_elsewhere() {
+        let check = check(&reverse("192.0.2.25"), lookup(&[ "web.lookup.dog.", "gone.lookup.dog." ])).unwrap();
+
+        assert!(check.names[1].addresses.is_empty());
+        assert!(! check.passed());
+    }
+
+    #[test]
+    fn one_of_several() {
+        let check = check(&reverse("192.0.2.25"), lookup(&[ "web.lookup.dog.", "mail.lookup.dog." ])).unwrap();
+
+        assert!(! check.confirms(&check.names[0]));
+        assert!(check.confirms(&check.names[1]));
+        assert!(check.passed());
+    }
+
+    #[test]
+    fn no_ptr_records() {
+        let check = check(&reverse("192.0.2.25"), lookup(&[])).unwrap();
+        assert!(! check.passed());
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = check(&reverse("192.0.2.25"), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::ServerFailure), Vec::new()))
+        });
+
+        assert!(matches!(result, Err(LookupError::Rcode(_, ErrorCode::ServerFailure))));
+    }
+}
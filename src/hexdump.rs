@@ -0,0 +1,294 @@
+//! Dumping raw messages as blocks of hexadecimal and ASCII, or as
+//! hexadecimal split up into the fields of a DNS message.
+
+use dns::presentation::type_mnemonic;
+
+
+/// Formats the bytes the way `hexdump -C` does: sixteen bytes per line,
+/// with the offset at the start, the bytes split into two groups of
+/// eight, and the printable ASCII characters at the end.
+pub fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes.chunks(16).enumerate().map(|(index, chunk)| {
+        let mut line = format!("{:08x} ", index * 16);
+
+        for column in 0 .. 16 {
+            if column == 8 {
+                line.push(' ');
+            }
+
+            match chunk.get(column) {
+                Some(byte)  => line.push_str(&format!(" {:02x}", byte)),
+                None        => line.push_str("   "),
+            }
+        }
+
+        let ascii = chunk.iter()
+                         .map(|&b| if b.is_ascii_graphic() || b == b' ' { char::from(b) } else { '.' })
+                         .collect::<String>();
+
+        line.push_str(&format!("  |{}|", ascii));
+        line
+    }).collect()
+}
+
+
+/// Formats the bytes of a DNS message with each field on its own line: its
+/// offset, its bytes in hexadecimal, and what it is. Long fields take up
+/// more than one line, with sixteen bytes on each. A message that ends in
+/// the middle of a field, or has bytes left over at the end, says so, as
+/// those are what this is for finding.
+pub fn annotated_dump(bytes: &[u8]) -> Vec<String> {
+    let mut dump = Annotator { bytes, offset: 0, lines: Vec::new() };
+    let _ = dump.message();
+
+    if dump.offset < bytes.len() {
+        let rest = bytes.len() - dump.offset;
+        dump.field(rest, format!("{} trailing bytes after the message", rest));
+    }
+
+    dump.lines
+}
+
+/// Walks through a message, adding lines for each field as it goes.
+struct Annotator<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    lines: Vec<String>,
+}
+
+impl Annotator<'_> {
+
+    /// Adds the lines for the header and every record. This returns `None`
+    /// as soon as the message ends early, once that has been noted.
+    fn message(&mut self) -> Option<()> {
+        self.field(2, "Transaction ID".into())?;
+
+        let label = self.peek(2).map_or_else(|| "Flags".into(), |flags| flags_description(flags as u16));
+        self.field(2, label)?;
+
+        let mut counts = [0; 4];
+        for (count, section) in counts.iter_mut().zip(&[ "Questions", "Answers", "Authorities", "Additionals" ]) {
+            *count = self.number(2, section)?;
+        }
+
+        for index in 0 .. counts[0] {
+            self.name(&format!("Question {}", index + 1))?;
+            self.record_type()?;
+            self.record_class()?;
+        }
+
+        for (count, section) in counts[1 ..].iter().zip(&[ "Answer", "Authority", "Additional" ]) {
+            for index in 0 .. *count {
+                self.name(&format!("{} {}", section, index + 1))?;
+                self.record_type()?;
+                self.record_class()?;
+                self.number(4, "TTL")?;
+                let length = self.number(2, "Data length")?;
+                self.field(length as usize, "Data".into())?;
+            }
+        }
+
+        Some(())
+    }
+
+    /// Adds the lines for a name, which is one field up to and including
+    /// the zero byte at the end or a compression pointer. The name gets
+    /// written out in full, following any pointers.
+    fn name(&mut self, label: &str) -> Option<()> {
+        let start = self.offset;
+        let mut length = 0;
+        let mut pointer = None;
+
+        loop {
+            let byte = match self.bytes.get(start + length) {
+                Some(byte) => usize::from(*byte),
+                None => {
+                    let rest = self.bytes.len() - start;
+                    return self.field(rest + 1, format!("{} name", label)).map(|_| ());
+                }
+            };
+
+            if byte & 0xC0 == 0xC0 {
+                pointer = self.bytes.get(start + length + 1).map(|low| (byte & 0x3F) << 8 | usize::from(*low));
+                length += 2;
+                break;
+            }
+
+            length += 1 + byte;
+            if byte == 0 {
+                break;
+            }
+        }
+
+        let mut text = read_name(self.bytes, start).unwrap_or_else(|| "(invalid)".into());
+        if let Some(pointer) = pointer {
+            text.push_str(&format!(" (compressed, continuing at {:04x})", pointer));
+        }
+
+        self.field(length, format!("{} name: {}", label, text)).map(|_| ())
+    }
+
+    /// Adds the line for a record type.
+    fn record_type(&mut self) -> Option<()> {
+        let label = self.peek(2).map_or_else(|| "Type".into(), |number| format!("Type: {} ({})", type_mnemonic(number as u16), number));
+        self.field(2, label).map(|_| ())
+    }
+
+    /// Adds the line for a record class, which is the largest payload the
+    /// sender can receive in an OPT record instead.
+    fn record_class(&mut self) -> Option<()> {
+        let label = self.peek(2).map_or_else(|| "Class".into(), |number| {
+            match number {
+                1    => "Class: IN (1)".into(),
+                3    => "Class: CH (3)".into(),
+                4    => "Class: HS (4)".into(),
+                255  => "Class: ANY (255)".into(),
+                n    => format!("Class: {}, or the payload size for OPT", n),
+            }
+        });
+
+        self.field(2, label).map(|_| ())
+    }
+
+    /// Adds the line for a big-endian number of the given number of bytes,
+    /// returning the number.
+    fn number(&mut self, length: usize, label: &str) -> Option<u32> {
+        let number = self.peek(length);
+        self.field(length, number.map_or_else(|| label.into(), |n| format!("{}: {}", label, n)))?;
+        number
+    }
+
+    /// Reads the big-endian number of the given number of bytes at the
+    /// current offset without moving past it, if the message is long
+    /// enough to have it.
+    fn peek(&self, length: usize) -> Option<u32> {
+        let bytes = self.bytes.get(self.offset .. self.offset + length)?;
+        Some(bytes.iter().fold(0_u32, |n, b| n << 8 | u32::from(*b)))
+    }
+
+    /// Adds the lines for a field of the given length, labelling the first
+    /// one, and returns its bytes. If the message ends before the field
+    /// does, the lines get added for the bytes there are, and `None` gets
+    /// returned.
+    fn field(&mut self, length: usize, label: String) -> Option<&[u8]> {
+        let start = self.offset;
+        let end = (start + length).min(self.bytes.len());
+        let field = &self.bytes[start .. end];
+
+        for (index, chunk) in field.chunks(16).enumerate() {
+            let hex = chunk.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+            let label = if index == 0 { label.as_str() } else { "" };
+            self.lines.push(format!("{:04x}  {:47}  {}", start + index * 16, hex, label).trim_end().to_owned());
+        }
+
+        self.offset = end;
+        if end - start < length {
+            self.lines.push(format!("{:04x}  Message ends {} bytes early, in the middle of this field", end, length - (end - start)));
+            return None;
+        }
+
+        Some(field)
+    }
+}
+
+/// Reads the name starting at the given offset, following compression
+/// pointers, as long as there are not so many of them that they must be
+/// going round in a loop.
+fn read_name(bytes: &[u8], mut offset: usize) -> Option<String> {
+    let mut labels = Vec::new();
+
+    for _ in 0 .. 128 {
+        let length = usize::from(*bytes.get(offset)?);
+
+        if length & 0xC0 == 0xC0 {
+            offset = (length & 0x3F) << 8 | usize::from(*bytes.get(offset + 1)?);
+            continue;
+        }
+
+        if length == 0 {
+            return Some(if labels.is_empty() { ".".into() } else { labels.join(".") + "." });
+        }
+
+        let label = bytes.get(offset + 1 .. offset + 1 + length)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += 1 + length;
+    }
+
+    None
+}
+
+/// Describes the flags in the header, with the ones that are set named.
+fn flags_description(flags: u16) -> String {
+    let names = [ (15, "QR"), (10, "AA"), (9, "TC"), (8, "RD"), (7, "RA"), (6, "Z"), (5, "AD"), (4, "CD") ];
+    let set = names.iter().filter(|(bit, _)| flags & (1 << bit) != 0).map(|(_, name)| *name).collect::<Vec<_>>();
+
+    format!("Flags: {} opcode {} rcode {}", if set.is_empty() { "none".into() } else { set.join(" ") }, (flags >> 11) & 0xF, flags & 0xF)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn nothing() {
+        assert_eq!(hex_dump(&[]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn full_line() {
+        let bytes = b"\x12\x34\x81\x80\x00\x01\x00\x01\x00\x00\x00\x01\x06lookup";
+        assert_eq!(hex_dump(&bytes[.. 16]),
+                   vec![ "00000000  12 34 81 80 00 01 00 01  00 00 00 01 06 6c 6f 6f  |.4...........loo|" ]);
+    }
+
+    #[test]
+    fn annotated() {
+        let bytes = b"\x12\x34\x81\x80\x00\x01\x00\x01\x00\x00\x00\x00\x06lookup\x03dog\x00\x00\x01\x00\x01\
+                      \xc0\x0c\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\xc0\x00\x02\x01";
+
+        assert_eq!(annotated_dump(bytes), vec![
+            "0000  12 34                                            Transaction ID",
+            "0002  81 80                                            Flags: QR RD RA opcode 0 rcode 0",
+            "0004  00 01                                            Questions: 1",
+            "0006  00 01                                            Answers: 1",
+            "0008  00 00                                            Authorities: 0",
+            "000a  00 00                                            Additionals: 0",
+            "000c  06 6c 6f 6f 6b 75 70 03 64 6f 67 00              Question 1 name: lookup.dog.",
+            "0018  00 01                                            Type: A (1)",
+            "001a  00 01                                            Class: IN (1)",
+            "001c  c0 0c                                            Answer 1 name: lookup.dog. (compressed, continuing at 000c)",
+            "001e  00 01                                            Type: A (1)",
+            "0020  00 01                                            Class: IN (1)",
+            "0022  00 00 01 2c                                      TTL: 300",
+            "0026  00 04                                            Data length: 4",
+            "0028  c0 00 02 01                                      Data",
+        ]);
+    }
+
+    #[test]
+    fn annotated_truncated() {
+        let bytes = b"\x12\x34\x81\x80\x00\x01\x00\x00\x00\x00\x00\x00\x06look";
+
+        assert_eq!(&annotated_dump(bytes)[6 ..], &[
+            "000c  06 6c 6f 6f 6b                                   Question 1 name",
+            "0011  Message ends 1 bytes early, in the middle of this field",
+        ]);
+    }
+
+    #[test]
+    fn annotated_trailing_bytes() {
+        let bytes = b"\x12\x34\x81\x80\x00\x00\x00\x00\x00\x00\x00\x00\xff\xff";
+        assert_eq!(annotated_dump(bytes).last().unwrap(), "000c  ff ff                                            2 trailing bytes after the message");
+    }
+
+    #[test]
+    fn partial_line() {
+        let bytes = b"\x12\x34\x81\x80\x00\x01\x00\x01\x00\x00\x00\x01\x06lookup\x03dog";
+        assert_eq!(hex_dump(bytes), vec![
+            "00000000  12 34 81 80 00 01 00 01  00 00 00 01 06 6c 6f 6f  |.4...........loo|",
+            "00000010  6b 75 70 03 64 6f 67                              |kup.dog|",
+        ]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Answering queries from a hosts file, the way the operating system’s
+//! resolver does before it asks any nameserver, so dog can be made to
+//! agree with programs that look names up through `getaddrinfo`.
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::*;
+
+use dns::{qtype, Answer, Name, QClass, Query, Request, Response};
+use dns::record::{A, AAAA, PTR, Record};
+use dns_transport::{Connection, Error as TransportError, Protocol, Reply, Transport};
+
+
+/// Where the hosts file is, unless the user says otherwise.
+pub const DEFAULT_PATH: &str = "/etc/hosts";
+
+/// The names and addresses listed in a hosts file.
+#[derive(PartialEq, Debug)]
+pub struct HostsFile {
+
+    /// Where the file was read from.
+    pub path: PathBuf,
+
+    /// Each name in the file, and the address on its line, in the order
+    /// they were listed in. A name that is on more than one line appears
+    /// once for each of them.
+    entries: Vec<(Name, IpAddr)>,
+}
+
+impl HostsFile {
+
+    /// Reads and parses the hosts file at the given path.
+    pub fn read(path: PathBuf) -> io::Result<Self> {
+        let text = fs::read_to_string(&path)?;
+        Ok(Self::parse(path, &text))
+    }
+
+    /// Parses the contents of a hosts file, where each line has an address
+    /// followed by the names that have it, with anything after a `#` being
+    /// a comment. Lines that cannot be parsed get skipped, as the operating
+    /// system skips them too.
+    pub fn parse(path: PathBuf, text: &str) -> Self {
+        let mut entries = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+
+            let address = match fields.next() {
+                Some(field) => field,
+                None        => continue,
+            };
+
+            // Link-local addresses can have a zone after them, which there
+            // is nowhere to put in a AAAA record.
+            let address = match address.split('%').next().unwrap_or_default().parse::<IpAddr>() {
+                Ok(address) => address,
+                Err(_) => {
+                    debug!("Skipping line {} of {:?}, with invalid address {:?}", number + 1, path, address);
+                    continue;
+                }
+            };
+
+            for field in fields {
+                match Name::from_unicode(field) {
+                    Ok(name)  => entries.push((name, address)),
+                    Err(_)    => debug!("Skipping invalid name {:?} on line {} of {:?}", field, number + 1, path),
+                }
+            }
+        }
+
+        Self { path, entries }
+    }
+
+    /// Returns the records the hosts file has for a query: the addresses
+    /// of the name for an A or AAAA query, and the first name listed for
+    /// the address for a PTR query at a reverse name. This is empty if
+    /// the file has nothing of that type for the name.
+    pub fn records(&self, query: &Query) -> Vec<Record> {
+        if query.qclass != QClass::IN {
+            return Vec::new();
+        }
+
+        if query.qtype == qtype!(PTR) {
+            let address = match PTR::reversed_address(&query.qname) {
+                Some(address) => address,
+                None          => return Vec::new(),
+            };
+
+            return self.entries.iter()
+                       .find(|(_, a)| *a == address)
+                       .map(|(name, _)| Record::PTR(PTR { cname: name.clone() }))
+                       .into_iter().collect();
+        }
+
+        let mut records = Vec::new();
+        for (name, address) in &self.entries {
+            if *name != query.qname {
+                continue;
+            }
+
+            let record = match address {
+                IpAddr::V4(address) if query.qtype == qtype!(A)     => Record::A(A { address: *address }),
+                IpAddr::V6(address) if query.qtype == qtype!(AAAA)  => Record::AAAA(AAAA { address: *address }),
+                _                                                   => continue,
+            };
+
+            if ! records.contains(&record) {
+                records.push(record);
+            }
+        }
+
+        records
+    }
+
+    /// Makes up a response to a request from the hosts file, if it has
+    /// records for the request’s question. The records have a TTL of zero,
+    /// as the file could change at any time.
+    pub fn answer(&self, request: &Request) -> Option<Response> {
+        let query = match &request.queries[..] {
+            [ q ]  => q,
+            _      => return None,
+        };
+
+        let records = self.records(query);
+        if records.is_empty() {
+            return None;
+        }
+
+        let mut flags = request.flags;
+        flags.response = true;
+        flags.recursion_available = true;
+
+        let answers = records.into_iter().map(|record| {
+            Answer::Standard { qname: query.qname.clone(), qclass: query.qclass, ttl: 0, record }
+        }).collect();
+
+        Some(Response {
+            transaction_id: request.transaction_id,
+            flags,
+            queries: request.queries.clone(),
+            answers,
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        })
+    }
+}
+
+
+/// A transport that answers requests from a hosts file when it has records
+/// for their question, and sends them down another transport when it does
+/// not.
+pub struct HostsTransport<T = Box<dyn Transport>> {
+    inner: T,
+    hosts: Arc<HostsFile>,
+}
+
+impl<T: Transport> HostsTransport<T> {
+
+    /// Creates a new hosts transport that sends requests down the given
+    /// transport when the given hosts file cannot answer them.
+    pub fn new(inner: T, hosts: Arc<HostsFile>) -> Self {
+        Self { inner, hosts }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for HostsTransport<T> {
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, TransportError> {
+        match self.hosts.answer(request) {
+            Some(response) => {
+                debug!("Answering query for {} from {:?}", request.queries[0].qname, self.hosts.path);
+                let bytes = response.to_bytes()?;
+                Ok(Reply { response, bytes, sent: Vec::new(), round_trip: Duration::default(), attempts: 0, protocol: Protocol::Hosts, connection: Connection::default() })
+            }
+            None => {
+                self.inner.send_with_details(request).await
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::record::MX;
+
+    fn hosts() -> HostsFile {
+        HostsFile::parse("/etc/hosts".into(), "\
+            # The usual ones\n\
+            127.0.0.1    localhost\n\
+            ::1          localhost ip6-localhost\n\
+            \n\
+            192.0.2.1    Printer.lookup.dog printer  # the one upstairs\n\
+            192.0.2.2    printer.lookup.dog\n\
+            fe80::1%eth0 router.lookup.dog\n\
+            not-an-ip    broken.lookup.dog\n")
+    }
+
+    fn query(qname: &str, qtype: dns::TypeInt) -> Query {
+        Query { qname: qname.parse().unwrap(), qclass: QClass::IN, qtype }
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(hosts().records(&query("printer.lookup.dog.", qtype!(A))), vec![
+            Record::A(A { address: "192.0.2.1".parse().unwrap() }),
+            Record::A(A { address: "192.0.2.2".parse().unwrap() }),
+        ]);
+    }
+
+    #[test]
+    fn ipv6_addresses() {
+        assert_eq!(hosts().records(&query("localhost", qtype!(AAAA))), vec![ Record::AAAA(AAAA { address: "::1".parse().unwrap() }) ]);
+        assert_eq!(hosts().records(&query("router.lookup.dog", qtype!(AAAA))), vec![ Record::AAAA(AAAA { address: "fe80::1".parse().unwrap() }) ]);
+    }
+
+    #[test]
+    fn reverse() {
+        let qname = PTR::reverse_name("192.0.2.1".parse().unwrap());
+        assert_eq!(hosts().records(&Query { qname, qclass: QClass::IN, qtype: qtype!(PTR) }),
+                   vec![ Record::PTR(PTR { cname: "Printer.lookup.dog".parse().unwrap() }) ]);
+    }
+
+    #[test]
+    fn nothing_listed() {
+        assert_eq!(hosts().records(&query("printer.lookup.dog", qtype!(AAAA))), Vec::new());
+        assert_eq!(hosts().records(&query("broken.lookup.dog", qtype!(A))), Vec::new());
+        assert_eq!(hosts().records(&query("printer.lookup.dog", qtype!(MX))), Vec::new());
+    }
+
+    #[test]
+    fn answer() {
+        let request = dns::MessageBuilder::query("printer".parse().unwrap(), qtype!(A)).transaction_id(1234).build();
+        let response = hosts().answer(&request).unwrap();
+
+        assert!(response.flags.response);
+        assert_eq!(response.transaction_id, 1234);
+        assert_eq!(response.answers, vec![
+            Answer::Standard { qname: "printer".parse().unwrap(), qclass: QClass::IN, ttl: 0, record: Record::A(A { address: "192.0.2.1".parse().unwrap() }) },
+        ]);
+    }
+
+    #[test]
+    fn no_answer() {
+        let request = dns::MessageBuilder::query("lookup.dog".parse().unwrap(), qtype!(A)).build();
+        assert_eq!(hosts().answer(&request), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! Reading files that can also be given on standard input.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+
+/// Where to read a file’s contents from.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Input {
+
+    /// Read from the file at the given path.
+    File(PathBuf),
+
+    /// Read from standard input.
+    Stdin,
+}
+
+impl Input {
+
+    /// Treats a path of `-` as standard input, as is the convention.
+    pub fn from_path(path: PathBuf) -> Self {
+        if path.as_os_str() == "-" { Self::Stdin } else { Self::File(path) }
+    }
+
+    /// Reads every byte of the input.
+    pub fn read_bytes(&self) -> io::Result<Vec<u8>> {
+        match self {
+            Self::File(path) => {
+                fs::read(path)
+            }
+            Self::Stdin => {
+                let mut bytes = Vec::new();
+                io::stdin().read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Reads the whole input as text.
+    pub fn read_text(&self) -> io::Result<String> {
+        String::from_utf8(self.read_bytes()?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
@@ -0,0 +1,545 @@
+//! Interpreting the email policies published in TXT records — SPF, DKIM,
+//! and DMARC — and explaining what each part of them means.
+
+use dns::{Answer, Name, Response};
+use dns::presentation::decode_base64;
+use dns::record::{Record, TXT};
+
+
+/// The kinds of email policy that can be interpreted.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Kind {
+
+    /// A Sender Policy Framework record, listing the hosts allowed to send
+    /// mail for a domain (RFC 7208).
+    SPF,
+
+    /// A DomainKeys Identified Mail key record, holding the public key that
+    /// mail signatures are checked with (RFC 6376).
+    DKIM,
+
+    /// A DMARC policy record, saying what to do with mail that fails the
+    /// other two checks (RFC 7489).
+    DMARC,
+}
+
+impl Kind {
+
+    /// The name of this kind of policy, for the output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SPF    => "SPF",
+            Self::DKIM   => "DKIM",
+            Self::DMARC  => "DMARC",
+        }
+    }
+}
+
+
+/// A TXT record that was interpreted as an email policy.
+#[derive(PartialEq, Debug)]
+pub struct Interpretation {
+
+    /// The name the record was found at.
+    pub owner: Name,
+
+    /// What kind of policy it holds.
+    pub kind: Kind,
+
+    /// The record’s strings, joined together.
+    pub text: String,
+
+    /// Each part of the policy, in the order they were written.
+    pub terms: Vec<Term>,
+
+    /// The tags in a DKIM or DMARC record, by name. SPF records do not
+    /// have any.
+    pub tags: Vec<(String, String)>,
+
+    /// Anything wrong with the policy, which could make receivers ignore
+    /// or misread it.
+    pub problems: Vec<String>,
+}
+
+/// One part of a policy, such as an SPF mechanism or a DMARC tag.
+#[derive(PartialEq, Debug)]
+pub struct Term {
+
+    /// The part as it was written.
+    pub text: String,
+
+    /// What it means.
+    pub explanation: String,
+}
+
+
+/// Interprets every TXT record in the answer sections of the given
+/// responses that holds an email policy.
+pub fn interpret_responses(responses: &[&Response]) -> Vec<Interpretation> {
+    responses.iter().flat_map(|response| &response.answers).filter_map(|answer| {
+        match answer {
+            Answer::Standard { qname, record: Record::TXT(txt), .. }  => interpret(qname, txt),
+            _                                                         => None,
+        }
+    }).collect()
+}
+
+/// Interprets a TXT record found at the given name, if it holds an email
+/// policy. SPF records are recognised by their version, and DKIM and
+/// DMARC records by the name they are published at as well, so that a
+/// record there with a broken version still gets explained.
+pub fn interpret(owner: &Name, txt: &TXT) -> Option<Interpretation> {
+    let text = String::from_utf8_lossy(&txt.join()).trim().to_string();
+    let first_label = owner.labels().next().unwrap_or_default();
+
+    let kind = if has_version(&text, "v=spf1") {
+        Kind::SPF
+    }
+    else if has_version(&text, "v=DMARC1") || first_label.eq_ignore_ascii_case("_dmarc") {
+        Kind::DMARC
+    }
+    else if has_version(&text, "v=DKIM1") || owner.labels().any(|l| l.eq_ignore_ascii_case("_domainkey")) {
+        Kind::DKIM
+    }
+    else {
+        return None;
+    };
+
+    let mut interpretation = Interpretation { owner: owner.clone(), kind, text, terms: Vec::new(), tags: Vec::new(), problems: Vec::new() };
+    match kind {
+        Kind::SPF    => interpret_spf(&mut interpretation),
+        Kind::DKIM   => interpret_dkim(&mut interpretation),
+        Kind::DMARC  => interpret_dmarc(&mut interpretation),
+    }
+
+    Some(interpretation)
+}
+
+/// Whether the text starts with the given version tag, followed by the
+/// end of the text or a separator.
+fn has_version(text: &str, version: &str) -> bool {
+    let bytes = text.as_bytes();
+
+    bytes.get(.. version.len()).is_some_and(|start| start.eq_ignore_ascii_case(version.as_bytes()))
+        && matches!(bytes.get(version.len()), None | Some(b' ') | Some(b';'))
+}
+
+
+// ---- SPF ----
+
+/// The number of mechanisms and modifiers that cause DNS lookups that a
+/// receiver will follow before giving up (RFC 7208 §4.6.4).
+const SPF_LOOKUP_LIMIT: usize = 10;
+
+fn interpret_spf(interpretation: &mut Interpretation) {
+    let text = interpretation.text.clone();
+    let mut lookups = 0;
+    let mut seen_all = false;
+    let mut has_redirect = false;
+
+    for (index, term) in text.split_whitespace().enumerate() {
+        if index == 0 {
+            interpretation.terms.push(Term { text: term.into(), explanation: "SPF version 1".into() });
+            continue;
+        }
+
+        if seen_all {
+            interpretation.problems.push(format!("{} comes after all, so it is never reached", term));
+        }
+
+        // Modifiers have a name and an equals sign; mechanisms have an
+        // optional qualifier, a name, and maybe a colon or a slash.
+        if let Some((name, value)) = term.split_once('=').filter(|(name, _)| name.chars().all(|c| c.is_ascii_alphanumeric())) {
+            let explanation = match name.to_ascii_lowercase().as_str() {
+                "redirect" => {
+                    lookups += 1;
+                    has_redirect = true;
+                    format!("Use the policy at {} instead", value)
+                }
+                "exp" => {
+                    format!("Explain failures with the text at {}", value)
+                }
+                _ => {
+                    "Unknown modifier, which is ignored".into()
+                }
+            };
+
+            interpretation.terms.push(Term { text: term.into(), explanation });
+            continue;
+        }
+
+        let (result, mechanism) = match term.chars().next() {
+            Some('+')  => ("Pass", &term[1 ..]),
+            Some('-')  => ("Fail", &term[1 ..]),
+            Some('~')  => ("Soft fail", &term[1 ..]),
+            Some('?')  => ("Neutral", &term[1 ..]),
+            _          => ("Pass", term),
+        };
+
+        let (name, argument) = match mechanism.find([ ':', '/' ]) {
+            Some(index)  => (&mechanism[.. index], mechanism[index ..].strip_prefix(':').unwrap_or(&mechanism[index ..])),
+            None         => (mechanism, ""),
+        };
+
+        let domain = if argument.is_empty() || argument.starts_with('/') { format!("this domain{}", argument) } else { argument.to_string() };
+        let what = match name.to_ascii_lowercase().as_str() {
+            "all" => {
+                seen_all = true;
+                "every other host".to_string()
+            }
+            "include" => {
+                lookups += 1;
+                format!("hosts that pass the policy at {}", argument)
+            }
+            "a" => {
+                lookups += 1;
+                format!("the addresses of {}", domain)
+            }
+            "mx" => {
+                lookups += 1;
+                format!("the mail servers of {}", domain)
+            }
+            "ptr" => {
+                lookups += 1;
+                interpretation.problems.push("ptr is slow and unreliable, and should not be used (RFC 7208 §5.5)".into());
+                format!("hosts whose reverse DNS names are in {}", domain)
+            }
+            "exists" => {
+                lookups += 1;
+                format!("any host, if {} has an address", argument)
+            }
+            "ip4" | "ip6" if ! argument.is_empty() => {
+                format!("addresses in {}", argument)
+            }
+            _ => {
+                interpretation.problems.push(format!("{} is not a valid mechanism, so receivers treat the whole policy as an error", term));
+                interpretation.terms.push(Term { text: term.into(), explanation: "Invalid mechanism".into() });
+                continue;
+            }
+        };
+
+        interpretation.terms.push(Term { text: term.into(), explanation: format!("{} for {}", result, what) });
+    }
+
+    if lookups > SPF_LOOKUP_LIMIT {
+        interpretation.problems.push(format!("Needs {} DNS lookups, but receivers give up after {}", lookups, SPF_LOOKUP_LIMIT));
+    }
+
+    if ! seen_all && ! has_redirect {
+        interpretation.problems.push("Has no all mechanism, so hosts that match nothing are neutral".into());
+    }
+}
+
+
+// ---- DKIM ----
+
+fn interpret_dkim(interpretation: &mut Interpretation) {
+    read_tags(interpretation);
+
+    for (index, (tag, value)) in interpretation.tags.iter().enumerate() {
+        let explanation = match tag.as_str() {
+            "v" => {
+                if index != 0 || value != "DKIM1" {
+                    interpretation.problems.push("The version has to be the first tag, and be DKIM1".into());
+                }
+
+                "DKIM version 1".into()
+            }
+            "h" => {
+                format!("Only accept signatures hashed with {}", list(value, ':'))
+            }
+            "k" => {
+                format!("The key is an {} key", value)
+            }
+            "n" => {
+                format!("Notes: {}", value)
+            }
+            "p" if value.is_empty() => {
+                interpretation.problems.push("The key has been revoked, so signatures made with it fail".into());
+                "Revoked key".into()
+            }
+            "p" => {
+                let key = value.chars().filter(|c| ! c.is_whitespace()).collect::<String>();
+                match decode_base64(&key) {
+                    Some(bytes) => {
+                        format!("Public key, {} bytes long", bytes.len())
+                    }
+                    None => {
+                        interpretation.problems.push("The public key is not valid Base64".into());
+                        "Public key".into()
+                    }
+                }
+            }
+            "s" => {
+                format!("The key can be used for {}", if value == "*" { "any service".into() } else { list(value, ':') })
+            }
+            "t" => {
+                let flags = value.split(':').map(|flag| {
+                    match flag.trim() {
+                        "y"    => "the domain is testing DKIM, so failures should be treated like unsigned mail",
+                        "s"    => "signatures have to be from this exact domain, not a subdomain",
+                        _      => "an unknown flag",
+                    }
+                }).collect::<Vec<_>>();
+
+                format!("Flags: {}", flags.join("; "))
+            }
+            _ => {
+                "Unknown tag, which is ignored".into()
+            }
+        };
+
+        interpretation.terms.push(Term { text: format!("{}={}", tag, value), explanation });
+    }
+
+    if ! interpretation.tags.iter().any(|(tag, _)| tag == "p") {
+        interpretation.problems.push("Has no public key (p=)".into());
+    }
+}
+
+
+// ---- DMARC ----
+
+fn interpret_dmarc(interpretation: &mut Interpretation) {
+    read_tags(interpretation);
+
+    if ! matches!(interpretation.tags.first(), Some((tag, value)) if tag == "v" && value == "DMARC1") {
+        interpretation.problems.push("Does not start with v=DMARC1, so receivers ignore the record".into());
+    }
+
+    for (tag, value) in &interpretation.tags {
+        let explanation = match tag.as_str() {
+            "v" => {
+                "DMARC version 1".into()
+            }
+            "p" | "sp" => {
+                let action = match value.to_ascii_lowercase().as_str() {
+                    "none"        => "take no action on mail that fails, only report it",
+                    "quarantine"  => "treat mail that fails as suspicious, such as by putting it in spam",
+                    "reject"      => "reject mail that fails",
+                    _ => {
+                        interpretation.problems.push(format!("{} is not a valid policy (none, quarantine, or reject)", value));
+                        "invalid policy"
+                    }
+                };
+
+                if tag == "p" { capitalise(action) } else { format!("For subdomains, {}", action) }
+            }
+            "pct" => {
+                match value.parse::<u8>() {
+                    Ok(percent) if percent <= 100 => {
+                        format!("Apply the policy to {}% of mail that fails", percent)
+                    }
+                    _ => {
+                        interpretation.problems.push(format!("{} is not a valid percentage", value));
+                        "Invalid percentage".into()
+                    }
+                }
+            }
+            "rua" => {
+                format!("Send aggregate reports to {}", list(value, ','))
+            }
+            "ruf" => {
+                format!("Send failure reports to {}", list(value, ','))
+            }
+            "adkim" | "aspf" => {
+                let check = if tag == "adkim" { "DKIM" } else { "SPF" };
+                match value.as_str() {
+                    "r" => {
+                        format!("{} passes for subdomains of the sender’s domain", check)
+                    }
+                    "s" => {
+                        format!("{} only passes for exactly the sender’s domain", check)
+                    }
+                    _ => {
+                        interpretation.problems.push(format!("{} is not a valid alignment mode (r or s)", value));
+                        "Invalid alignment mode".into()
+                    }
+                }
+            }
+            "fo" => {
+                let options = value.split(':').map(|option| {
+                    match option.trim() {
+                        "0"  => "when every check fails",
+                        "1"  => "when any check fails",
+                        "d"  => "when DKIM fails",
+                        "s"  => "when SPF fails",
+                        _    => "at an unknown time",
+                    }
+                }).collect::<Vec<_>>();
+
+                format!("Send failure reports {}", options.join(", and "))
+            }
+            "rf" => {
+                format!("Send failure reports in {} format", value)
+            }
+            "ri" => {
+                format!("Send aggregate reports every {} seconds", value)
+            }
+            _ => {
+                "Unknown tag, which is ignored".into()
+            }
+        };
+
+        interpretation.terms.push(Term { text: format!("{}={}", tag, value), explanation });
+    }
+
+    if ! interpretation.tags.iter().any(|(tag, _)| tag == "p") {
+        interpretation.problems.push("Has no policy (p=), so receivers ignore the record".into());
+    }
+}
+
+
+// ---- helpers ----
+
+/// Splits a DKIM or DMARC record into its `tag=value` pairs, separated by
+/// semicolons, noting any parts that are not pairs as problems.
+fn read_tags(interpretation: &mut Interpretation) {
+    for part in interpretation.text.split(';').map(str::trim).filter(|p| ! p.is_empty()) {
+        match part.split_once('=') {
+            Some((tag, value)) => {
+                interpretation.tags.push((tag.trim().to_string(), value.trim().to_string()));
+            }
+            None => {
+                interpretation.problems.push(format!("{:?} is not a tag=value pair", part));
+            }
+        }
+    }
+}
+
+/// Tidies up a list of values with the given separator between them.
+fn list(value: &str, separator: char) -> String {
+    value.split(separator).map(str::trim).collect::<Vec<_>>().join(", ")
+}
+
+/// Capitalises the first letter of an explanation.
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first)  => first.to_uppercase().chain(chars).collect(),
+        None         => String::new(),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn interpret_text(owner: &str, text: &str) -> Option<Interpretation> {
+        interpret(&owner.parse().unwrap(), &TXT::from_text(text))
+    }
+
+    fn explanations(interpretation: &Interpretation) -> Vec<(&str, &str)> {
+        interpretation.terms.iter().map(|t| (t.text.as_str(), t.explanation.as_str())).collect()
+    }
+
+    #[test]
+    fn not_a_policy() {
+        assert_eq!(interpret_text("lookup.dog.", "google-site-verification=woof"), None);
+    }
+
+    #[test]
+    fn not_quite_a_version() {
+        assert_eq!(interpret_text("lookup.dog.", "v=spf10"), None);
+        assert_eq!(interpret_text("lookup.dog.", "v=spf\u{e9}"), None);
+    }
+
+    #[test]
+    fn spf() {
+        let spf = interpret_text("lookup.dog.", "v=spf1 ip4:192.0.2.0/24 mx include:_spf.example.net ~all").unwrap();
+        assert_eq!(spf.kind, Kind::SPF);
+        assert_eq!(explanations(&spf), vec![
+            ("v=spf1",                   "SPF version 1"),
+            ("ip4:192.0.2.0/24",         "Pass for addresses in 192.0.2.0/24"),
+            ("mx",                       "Pass for the mail servers of this domain"),
+            ("include:_spf.example.net", "Pass for hosts that pass the policy at _spf.example.net"),
+            ("~all",                     "Soft fail for every other host"),
+        ]);
+        assert!(spf.problems.is_empty());
+    }
+
+    #[test]
+    fn spf_split_across_strings() {
+        let txt = TXT { strings: vec![ b"v=spf1 ip4:192.0.2.1 ".to_vec(), b"-all".to_vec() ] };
+        let spf = interpret(&"lookup.dog.".parse().unwrap(), &txt).unwrap();
+        assert_eq!(spf.terms.last().unwrap().explanation, "Fail for every other host");
+    }
+
+    #[test]
+    fn spf_problems() {
+        let spf = interpret_text("lookup.dog.", "v=spf1 ptr a:mail.lookup.dog/24 woof").unwrap();
+        assert_eq!(spf.terms[2].explanation, "Pass for the addresses of mail.lookup.dog/24");
+        assert_eq!(spf.problems, vec![
+            "ptr is slow and unreliable, and should not be used (RFC 7208 §5.5)",
+            "woof is not a valid mechanism, so receivers treat the whole policy as an error",
+            "Has no all mechanism, so hosts that match nothing are neutral",
+        ]);
+    }
+
+    #[test]
+    fn spf_too_many_lookups() {
+        let includes = (0 .. 11).map(|n| format!("include:{}.lookup.dog", n)).collect::<Vec<_>>().join(" ");
+        let spf = interpret_text("lookup.dog.", &format!("v=spf1 {} -all", includes)).unwrap();
+        assert_eq!(spf.problems, vec![ "Needs 11 DNS lookups, but receivers give up after 10" ]);
+    }
+
+    #[test]
+    fn spf_redirect() {
+        let spf = interpret_text("lookup.dog.", "v=spf1 redirect=_spf.lookup.dog").unwrap();
+        assert_eq!(spf.terms[1].explanation, "Use the policy at _spf.lookup.dog instead");
+        assert!(spf.problems.is_empty());
+    }
+
+    #[test]
+    fn dkim() {
+        let dkim = interpret_text("selector._domainkey.lookup.dog.", "v=DKIM1; k=rsa; t=y; p=AQIDBA==").unwrap();
+        assert_eq!(dkim.kind, Kind::DKIM);
+        assert_eq!(explanations(&dkim), vec![
+            ("v=DKIM1",   "DKIM version 1"),
+            ("k=rsa",     "The key is an rsa key"),
+            ("t=y",       "Flags: the domain is testing DKIM, so failures should be treated like unsigned mail"),
+            ("p=AQIDBA==", "Public key, 4 bytes long"),
+        ]);
+        assert_eq!(dkim.tags[1], ("k".into(), "rsa".into()));
+        assert!(dkim.problems.is_empty());
+    }
+
+    #[test]
+    fn dkim_revoked() {
+        let dkim = interpret_text("selector._domainkey.lookup.dog.", "v=DKIM1; p=").unwrap();
+        assert_eq!(dkim.problems, vec![ "The key has been revoked, so signatures made with it fail" ]);
+    }
+
+    #[test]
+    fn dmarc() {
+        let dmarc = interpret_text("_dmarc.lookup.dog.", "v=DMARC1; p=quarantine; sp=reject; pct=50; rua=mailto:reports@lookup.dog; adkim=s").unwrap();
+        assert_eq!(dmarc.kind, Kind::DMARC);
+        assert_eq!(explanations(&dmarc), vec![
+            ("v=DMARC1",                       "DMARC version 1"),
+            ("p=quarantine",                   "Treat mail that fails as suspicious, such as by putting it in spam"),
+            ("sp=reject",                      "For subdomains, reject mail that fails"),
+            ("pct=50",                         "Apply the policy to 50% of mail that fails"),
+            ("rua=mailto:reports@lookup.dog",  "Send aggregate reports to mailto:reports@lookup.dog"),
+            ("adkim=s",                        "DKIM only passes for exactly the sender’s domain"),
+        ]);
+        assert!(dmarc.problems.is_empty());
+    }
+
+    #[test]
+    fn dmarc_problems() {
+        let dmarc = interpret_text("_dmarc.lookup.dog.", "p=bounce; pct=200").unwrap();
+        assert_eq!(dmarc.problems, vec![
+            "Does not start with v=DMARC1, so receivers ignore the record",
+            "bounce is not a valid policy (none, quarantine, or reject)",
+            "200 is not a valid percentage",
+        ]);
+    }
+
+    #[test]
+    fn dmarc_without_policy() {
+        let dmarc = interpret_text("_dmarc.lookup.dog.", "v=DMARC1").unwrap();
+        assert_eq!(dmarc.problems, vec![ "Has no policy (p=), so receivers ignore the record" ]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Converting responses and records into the JSON that the JSON and YAML
+//! output formats print.
+//!
+//! This is dog’s own schema, kept apart from the serde support in the dns
+//! crate, so that neither one can change the other. Records become objects
+//! with a `type` field holding the mnemonic of the record type, and one
+//! field for each part of the record data. Names, addresses, and classes
+//! become strings; public keys and signatures become base64, and digests
+//! become hex, as they are written in zone files.
+
+use dns::{Response, Query, Answer, Flags, Name, QClass, ErrorCode};
+use dns::presentation::{base64, hex, type_mnemonic};
+use dns::record::*;
+use serde_json::{json, Value as JsonValue};
+
+
+/// Something that has a place in dog’s JSON output.
+pub trait ToJson {
+
+    /// Converts this into a JSON value.
+    fn to_json(&self) -> JsonValue;
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Array(self.iter().map(T::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> JsonValue {
+        self.as_slice().to_json()
+    }
+}
+
+
+// ---- names, classes, and flags ----
+
+impl ToJson for Name {
+    fn to_json(&self) -> JsonValue {
+        json!(self.as_str())
+    }
+}
+
+impl ToJson for QClass {
+    fn to_json(&self) -> JsonValue {
+        json!(self.to_string())
+    }
+}
+
+/// Error codes use their mnemonics from the IANA registry, with numbers
+/// for the ones we do not know the name of.
+impl ToJson for ErrorCode {
+    fn to_json(&self) -> JsonValue {
+        match self.mnemonic() {
+            Some(mnemonic)  => json!(mnemonic),
+            None            => json!(self.to_bits()),
+        }
+    }
+}
+
+impl ToJson for Flags {
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "response": self.response,
+            "opcode": self.opcode,
+            "authoritative": self.authoritative,
+            "truncated": self.truncated,
+            "recursion_desired": self.recursion_desired,
+            "recursion_available": self.recursion_available,
+            "authentic_data": self.authentic_data,
+            "checking_disabled": self.checking_disabled,
+            "reserved": self.reserved,
+            "error_code": self.error_code.map(|e| e.to_json()),
+        })
+    }
+}
+
+
+// ---- messages ----
+
+impl ToJson for Query {
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "name": self.qname.to_json(),
+            "class": self.qclass.to_json(),
+            "type": self.qtype,
+        })
+    }
+}
+
+/// Standard answers are their record, with the name, class, and TTL added
+/// alongside the record data. Pseudo-records are told apart by their type
+/// of `OPT`.
+impl ToJson for Answer {
+    fn to_json(&self) -> JsonValue {
+        match self {
+            Self::Standard { qname, qclass, ttl, record } => {
+                let mut value = record.to_json();
+                let map = value.as_object_mut().expect("Records are objects");
+                map.insert("name".into(), qname.to_json());
+                map.insert("class".into(), qclass.to_json());
+                map.insert("ttl".into(), json!(ttl));
+                value
+            }
+            Self::Pseudo { qname, opt } => {
+                let mut value = opt.to_json();
+                let map = value.as_object_mut().expect("Records are objects");
+                map.insert("name".into(), qname.to_json());
+                map.insert("type".into(), json!("OPT"));
+                value
+            }
+        }
+    }
+}
+
+impl ToJson for Response {
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "transaction_id": self.transaction_id,
+            "flags": self.flags.to_json(),
+            "queries": self.queries.to_json(),
+            "answers": self.answers.to_json(),
+            "authorities": self.authorities.to_json(),
+            "additionals": self.additionals.to_json(),
+        })
+    }
+}
+
+
+// ---- records ----
+
+/// Records of types that cannot be parsed have an `unknown` field set, with
+/// their type as a mnemonic if there is one or a number if not, and their
+/// raw data as base64.
+impl ToJson for Record {
+    fn to_json(&self) -> JsonValue {
+        let (rtype, mut value) = match self {
+            Self::A(rec)       => ("A",      json!({ "address": rec.address.to_string() })),
+            Self::AAAA(rec)    => ("AAAA",   json!({ "address": rec.address.to_string() })),
+            Self::CAA(rec)     => ("CAA",    json!({ "critical": rec.critical, "tag": rec.tag, "value": rec.value })),
+            Self::CNAME(rec)   => ("CNAME",  json!({ "domain": rec.domain.to_json() })),
+            Self::DNSKEY(rec)  => ("DNSKEY", json!({ "flags": rec.flags, "protocol": rec.protocol, "algorithm": rec.algorithm, "public_key": base64(&rec.public_key) })),
+            Self::DS(rec)      => ("DS",     json!({ "key_tag": rec.key_tag, "algorithm": rec.algorithm, "digest_type": rec.digest_type, "digest": hex(&rec.digest) })),
+            Self::MX(rec)      => ("MX",     json!({ "preference": rec.preference, "exchange": rec.exchange.to_json() })),
+            Self::NS(rec)      => ("NS",     json!({ "nameserver": rec.nameserver.to_json() })),
+            Self::PTR(rec)     => ("PTR",    json!({ "cname": rec.cname.to_json() })),
+            Self::RRSIG(rec)   => ("RRSIG",  rrsig(rec)),
+            Self::SOA(rec)     => ("SOA",    soa(rec)),
+            Self::SRV(rec)     => ("SRV",    json!({ "priority": rec.priority, "weight": rec.weight, "port": rec.port, "target": rec.target.to_json() })),
+            Self::TXT(rec)     => ("TXT",    txt(rec)),
+            Self::Custom(custom) => {
+                return json!({ "unknown": true, "type": custom.type_number(), "rdata": base64(custom.bytes()) });
+            }
+            Self::Other { type_number, bytes } => {
+                let rtype = match type_number {
+                    UnknownQtype::HeardOf(name)  => json!(name),
+                    UnknownQtype::UnheardOf(num) => json!(num),
+                };
+
+                return json!({ "unknown": true, "type": rtype, "rdata": base64(bytes) });
+            }
+        };
+
+        value.as_object_mut().expect("Records are objects").insert("type".into(), json!(rtype));
+        value
+    }
+}
+
+impl ToJson for OPT {
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "udp_payload_size": self.udp_payload_size,
+            "higher_bits": self.higher_bits,
+            "version": self.edns0_version,
+            "flags": self.flags,
+            "data": self.data,
+        })
+    }
+}
+
+fn rrsig(rec: &RRSIG) -> JsonValue {
+    json!({
+        "type_covered": type_mnemonic(rec.type_covered),
+        "algorithm": rec.algorithm,
+        "labels": rec.labels,
+        "original_ttl": rec.original_ttl,
+        "expiration": rec.expiration,
+        "inception": rec.inception,
+        "key_tag": rec.key_tag,
+        "signer_name": rec.signer_name.to_json(),
+        "signature": base64(&rec.signature),
+    })
+}
+
+fn soa(rec: &SOA) -> JsonValue {
+    json!({
+        "mname": rec.mname.to_json(),
+        "rname": rec.rname.to_json(),
+        "email": rec.email(),
+        "serial": rec.serial,
+        "refresh_interval": rec.refresh_interval,
+        "retry_interval": rec.retry_interval,
+        "expire_limit": rec.expire_limit,
+        "minimum_ttl": rec.minimum_ttl,
+    })
+}
+
+/// The strings are kept apart, and the message they make up when joined
+/// is there as well, for anything that only wants the whole text.
+fn txt(rec: &TXT) -> JsonValue {
+    json!({
+        "strings": rec.as_utf8_lossy(),
+        "message": String::from_utf8_lossy(&rec.join()),
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn a_record() {
+        let answer = Answer::Standard {
+            qname: "lookup.dog.".parse().unwrap(),
+            qclass: QClass::IN,
+            ttl: 300,
+            record: Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) }),
+        };
+
+        assert_eq!(answer.to_json(),
+                   json!({ "name": "lookup.dog.", "class": "IN", "ttl": 300, "type": "A", "address": "127.0.0.1" }));
+    }
+
+    #[test]
+    fn unknown_record() {
+        let record = Record::Other { type_number: UnknownQtype::UnheardOf(731), bytes: vec![ 1, 2 ] };
+        assert_eq!(record.to_json(), json!({ "unknown": true, "type": 731, "rdata": "AQI=" }));
+    }
+
+    #[test]
+    fn txt_strings() {
+        let record = Record::TXT(TXT { strings: vec![ b"v=spf1 ".to_vec(), b"-all".to_vec() ] });
+        assert_eq!(record.to_json(),
+                   json!({ "type": "TXT", "strings": [ "v=spf1 ", "-all" ], "message": "v=spf1 -all" }));
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(ErrorCode::NXDomain.to_json(), json!("NXDOMAIN"));
+        assert_eq!(ErrorCode::Other(23).to_json(), json!(23));
+    }
+}
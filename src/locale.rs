@@ -0,0 +1,845 @@
+//! Translating the messages printed around the text output, and the units
+//! of humanised durations, into the user’s language. Each language has a
+//! catalog of messages, and any message a catalog is missing falls back to
+//! English, so a language can be added one message at a time.
+//!
+//! Catalogs for more languages, or with different wording, can be read
+//! from a file, which lists each message by its name:
+//!
+//! ```text
+//! # Messages in Dutch
+//! language = nl
+//! units = d u m s
+//! NoResults = Geen resultaten
+//! RanIn = Uitgevoerd in {}ms
+//! ```
+//!
+//! Only the text and short output modes get translated. The JSON, YAML,
+//! CSV, and dig output stay the same everywhere, as programs read them.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+
+/// The messages that get translated.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Message {
+
+    /// Printed when there were no answers to show.
+    NoResults,
+
+    /// How long everything took, in milliseconds.
+    RanIn,
+
+    /// How long one query took, who it was sent to, and how.
+    QueryTime,
+
+    /// For a query answered from the cache of the given nameserver.
+    QueryTimeFromCache,
+
+    /// For a query answered from the hosts file.
+    QueryTimeFromHosts,
+
+    /// Added to the query time when the query had to be sent again.
+    AfterRetrying,
+
+    /// The word that starts a line about a query that failed.
+    Error,
+
+    /// The line that says a response had an error code.
+    Status,
+
+    /// The names of the error codes.
+    FormatError,
+    ServerFailure,
+    NXDomain,
+    NotImplemented,
+    QueryRefused,
+    BadVersion,
+    OtherFailure,
+
+    /// Starts a line about something wrong that a checker found.
+    Problem,
+
+    /// A list of addresses that turned out to be empty.
+    NoAddresses,
+
+    /// The lines of the CAA check.
+    CaaRecordsAt,
+    NoCaaRecords,
+    Certificates,
+    WildcardCertificates,
+    IncidentReports,
+    AnyCA,
+    NoCA,
+    UnknownCriticalTag,
+
+    /// The lines and problems of the MX check.
+    NullMx,
+    ImplicitMx,
+    NullMxWithOthers,
+    ExchangeWithoutAddresses,
+    CnameExchange,
+
+    /// The lines of the forward-confirmed reverse DNS check.
+    NoPtrRecords,
+    ForwardConfirmed,
+    NotForwardConfirmed,
+
+    /// The lines of the wildcard probe.
+    DomainHasRecords,
+    DomainHasNoRecords,
+    DomainDoesNotExist,
+    RecordCount,
+    NoRecordsOfType,
+    NoWildcard,
+    WildcardWithoutRecords,
+    WildcardSynthesising,
+
+    /// The lines and table headings of the system resolver comparison.
+    SystemResolverFailed,
+    Address,
+    System,
+    Yes,
+    SystemAgrees,
+    DisagreesHostsFile,
+    DisagreesMulticastDns,
+    DisagreesUnknown,
+
+    /// The lines of the NAT64 prefix discovery.
+    NoDns64,
+    Nat64Prefix,
+    WellKnownNat64Prefix,
+
+    /// The lines of the SOA serial check.
+    SerialBehind,
+    InSync,
+    OutOfSync,
+    NoSerial,
+
+    /// The lines, lameness, and problems of the delegation check.
+    DelegatedFrom,
+    Glue,
+    ParentLists,
+    ZoneLists,
+    NoAnswer,
+    Authoritative,
+    Lame,
+    NoAddress,
+    Unreachable,
+    RespondedWith,
+    NotAuthoritative,
+    OnlyAtParent,
+    OnlyAtChild,
+    MissingGlue,
+    GlueMismatch,
+    LameNameserver,
+
+    /// The lines of the SRV resolution.
+    ServiceUnavailable,
+    NoSrvRecords,
+    SrvEndpoint,
+
+    /// The lines about who answered a query, and how it got there.
+    AnsweredBy,
+    AnsweredFromHosts,
+    Connection,
+    ConnectedTo,
+    TlsCipher,
+    HttpStatus,
+    FellBackToTcp,
+
+    /// The summaries of comparisons.
+    RecordSetsMatch,
+    RecordSetsDiffer,
+    MessagesSame,
+}
+
+impl Message {
+
+    /// Every message, which is how the names of the messages in a catalog
+    /// file get looked up.
+    pub const ALL: &'static [Self] = &[
+        Self::NoResults, Self::RanIn, Self::QueryTime, Self::QueryTimeFromCache,
+        Self::QueryTimeFromHosts, Self::AfterRetrying, Self::Error, Self::Status,
+        Self::FormatError, Self::ServerFailure, Self::NXDomain, Self::NotImplemented,
+        Self::QueryRefused, Self::BadVersion, Self::OtherFailure, Self::Problem,
+        Self::NoAddresses, Self::CaaRecordsAt, Self::NoCaaRecords, Self::Certificates,
+        Self::WildcardCertificates, Self::IncidentReports, Self::AnyCA, Self::NoCA,
+        Self::UnknownCriticalTag, Self::NullMx, Self::ImplicitMx, Self::NullMxWithOthers,
+        Self::ExchangeWithoutAddresses, Self::CnameExchange, Self::NoPtrRecords, Self::ForwardConfirmed,
+        Self::NotForwardConfirmed, Self::DomainHasRecords, Self::DomainHasNoRecords, Self::DomainDoesNotExist,
+        Self::RecordCount, Self::NoRecordsOfType, Self::NoWildcard, Self::WildcardWithoutRecords,
+        Self::WildcardSynthesising, Self::SystemResolverFailed, Self::Address, Self::System,
+        Self::Yes, Self::SystemAgrees, Self::DisagreesHostsFile, Self::DisagreesMulticastDns,
+        Self::DisagreesUnknown, Self::NoDns64, Self::Nat64Prefix, Self::WellKnownNat64Prefix,
+        Self::SerialBehind, Self::InSync, Self::OutOfSync, Self::NoSerial,
+        Self::DelegatedFrom, Self::Glue, Self::ParentLists, Self::ZoneLists,
+        Self::NoAnswer, Self::Authoritative, Self::Lame, Self::NoAddress,
+        Self::Unreachable, Self::RespondedWith, Self::NotAuthoritative, Self::OnlyAtParent,
+        Self::OnlyAtChild, Self::MissingGlue, Self::GlueMismatch, Self::LameNameserver,
+        Self::ServiceUnavailable, Self::NoSrvRecords, Self::SrvEndpoint, Self::AnsweredBy,
+        Self::AnsweredFromHosts, Self::Connection, Self::ConnectedTo, Self::TlsCipher,
+        Self::HttpStatus, Self::FellBackToTcp, Self::RecordSetsMatch, Self::RecordSetsDiffer,
+        Self::MessagesSame,
+    ];
+
+    /// Finds a message by the name a catalog file gives it, which is the
+    /// same as its name here, such as `NoResults`.
+    pub fn named(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|message| format!("{:?}", message) == name)
+    }
+}
+
+/// The messages and duration units for one language.
+#[derive(PartialEq, Debug)]
+pub struct Catalog {
+
+    /// The language’s code, such as `de`, which is what the start of the
+    /// locale gets matched against.
+    pub language: &'static str,
+
+    /// The units that durations are written in: days, hours, minutes, and
+    /// seconds.
+    pub units: [&'static str; 4],
+
+    /// The translated messages, where each `{}` gets replaced by one of
+    /// the values that go in it, in order.
+    messages: &'static [(Message, &'static str)],
+
+    /// The catalog to take any missing messages from before falling back
+    /// to English, which is the built-in one for the language of a catalog
+    /// read from a file.
+    base: Option<&'static Catalog>,
+}
+
+/// The messages that every other catalog falls back to.
+pub const ENGLISH: Catalog = Catalog {
+    language: "en",
+    units: [ "d", "h", "m", "s" ],
+    messages: &[
+        (Message::NoResults,                 "No results"),
+        (Message::RanIn,                     "Ran in {}ms"),
+        (Message::QueryTime,                 "Query time: {}ms from {} over {}"),
+        (Message::QueryTimeFromCache,        "Query time: 0ms from the cache of {}"),
+        (Message::QueryTimeFromHosts,        "Query time: 0ms from the hosts file"),
+        (Message::AfterRetrying,             ", after retrying"),
+        (Message::Error,                     "Error"),
+        (Message::Status,                    "Status: {}"),
+        (Message::FormatError,               "Format Error"),
+        (Message::ServerFailure,             "Server Failure"),
+        (Message::NXDomain,                  "NXDomain"),
+        (Message::NotImplemented,            "Not Implemented"),
+        (Message::QueryRefused,              "Query Refused"),
+        (Message::BadVersion,                "Bad Version"),
+        (Message::OtherFailure,              "Other Failure ({})"),
+        (Message::Problem,                   "Problem: {}"),
+        (Message::NoAddresses,               "(no addresses)"),
+        (Message::CaaRecordsAt,              "CAA records at {} (checked {})"),
+        (Message::NoCaaRecords,              "No CAA records (checked {})"),
+        (Message::Certificates,              "Certificates:"),
+        (Message::WildcardCertificates,      "Wildcard certificates:"),
+        (Message::IncidentReports,           "Incident reports:"),
+        (Message::AnyCA,                     "any CA"),
+        (Message::NoCA,                      "no CA"),
+        (Message::UnknownCriticalTag,        "the critical tag {} is not understood, so no CA may issue"),
+        (Message::NullMx,                    "Null MX record: this domain does not accept mail"),
+        (Message::ImplicitMx,                "No MX records, so mail is delivered to the domain’s own addresses"),
+        (Message::NullMxWithOthers,          "a null MX record must be the only MX record"),
+        (Message::ExchangeWithoutAddresses,  "the mail exchange {} has no addresses"),
+        (Message::CnameExchange,             "the mail exchange {} is a CNAME to {}, rather than the server’s own name"),
+        (Message::NoPtrRecords,              "No PTR records"),
+        (Message::ForwardConfirmed,          "Forward-confirmed: a name points back to this address"),
+        (Message::NotForwardConfirmed,       "Not forward-confirmed: no name points back to this address"),
+        (Message::DomainHasRecords,          "The domain has {} records"),
+        (Message::DomainHasNoRecords,        "The domain exists but has no {} records, so it may be an empty non-terminal"),
+        (Message::DomainDoesNotExist,        "The domain does not exist"),
+        (Message::RecordCount,               "{} records"),
+        (Message::NoRecordsOfType,           "no {} records"),
+        (Message::NoWildcard,                "No wildcard: none of the made-up names exist"),
+        (Message::WildcardWithoutRecords,    "Wildcard found, but it has no {} records"),
+        (Message::WildcardSynthesising,      "Wildcard found, synthesising:"),
+        (Message::SystemResolverFailed,      "The system resolver failed: {}"),
+        (Message::Address,                   "Address"),
+        (Message::System,                    "System"),
+        (Message::Yes,                       "yes"),
+        (Message::SystemAgrees,              "The system resolver agrees with DNS"),
+        (Message::DisagreesHostsFile,        "The system resolver disagrees with DNS, as the hosts file lists the name"),
+        (Message::DisagreesMulticastDns,     "The system resolver disagrees with DNS, as names under local are resolved with multicast DNS"),
+        (Message::DisagreesUnknown,          "The system resolver disagrees with DNS; check /etc/nsswitch.conf for where else it looks"),
+        (Message::NoDns64,                   "No DNS64: ipv4only.arpa has no synthesised AAAA records"),
+        (Message::Nat64Prefix,               "NAT64 prefix {}"),
+        (Message::WellKnownNat64Prefix,      "NAT64 prefix {} (the well-known prefix)"),
+        (Message::SerialBehind,              "{} ({} behind)"),
+        (Message::InSync,                    "In sync at serial {}"),
+        (Message::OutOfSync,                 "Out of sync; the newest serial is {}"),
+        (Message::NoSerial,                  "No nameserver responded with a serial"),
+        (Message::DelegatedFrom,             "(delegated from {})"),
+        (Message::Glue,                      "{} (glue {})"),
+        (Message::ParentLists,               "Parent lists:"),
+        (Message::ZoneLists,                 "Zone lists:"),
+        (Message::NoAnswer,                  "(no answer)"),
+        (Message::Authoritative,             "authoritative"),
+        (Message::Lame,                      "lame: {}"),
+        (Message::NoAddress,                 "no address"),
+        (Message::Unreachable,               "unreachable ({})"),
+        (Message::RespondedWith,             "responded with {}"),
+        (Message::NotAuthoritative,          "not authoritative"),
+        (Message::OnlyAtParent,              "the parent lists {}, but the zone does not"),
+        (Message::OnlyAtChild,               "the zone lists {}, but the parent does not"),
+        (Message::MissingGlue,               "{} is inside the zone, but the parent has no glue for it"),
+        (Message::GlueMismatch,              "the parent’s glue for {} is {}, which the zone does not give as its address"),
+        (Message::LameNameserver,            "{} is lame, as it does not answer for the zone"),
+        (Message::ServiceUnavailable,        "The service is not available at this domain"),
+        (Message::NoSrvRecords,              "No SRV records"),
+        (Message::SrvEndpoint,               "priority {} weight {} port {} {}"),
+        (Message::AnsweredBy,                "Answered by {} ({}), authoritative for {}"),
+        (Message::AnsweredFromHosts,         "Answered from the hosts file, not by {}"),
+        (Message::Connection,                "Connection: {}"),
+        (Message::ConnectedTo,               "connected to {}"),
+        (Message::TlsCipher,                 "{} with {}"),
+        (Message::HttpStatus,                "{} status {}"),
+        (Message::FellBackToTcp,             "fell back to TCP as the UDP response was truncated"),
+        (Message::RecordSetsMatch,           "All {} record sets match"),
+        (Message::RecordSetsDiffer,          "{} of {} record sets differ"),
+        (Message::MessagesSame,              "The messages are the same"),
+    ],
+    base: None,
+};
+
+const GERMAN: Catalog = Catalog {
+    language: "de",
+    units: [ "T", "h", "m", "s" ],
+    messages: &[
+        (Message::NoResults,                 "Keine Ergebnisse"),
+        (Message::RanIn,                     "Ausgeführt in {}ms"),
+        (Message::QueryTime,                 "Abfragezeit: {}ms von {} über {}"),
+        (Message::QueryTimeFromCache,        "Abfragezeit: 0ms aus dem Cache von {}"),
+        (Message::QueryTimeFromHosts,        "Abfragezeit: 0ms aus der Hosts-Datei"),
+        (Message::AfterRetrying,             ", nach erneutem Versuch"),
+        (Message::Error,                     "Fehler"),
+        (Message::Status,                    "Status: {}"),
+        (Message::FormatError,               "Formatfehler"),
+        (Message::ServerFailure,             "Serverfehler"),
+        (Message::NotImplemented,            "Nicht implementiert"),
+        (Message::QueryRefused,              "Anfrage abgelehnt"),
+        (Message::BadVersion,                "Falsche Version"),
+        (Message::OtherFailure,              "Anderer Fehler ({})"),
+        (Message::Problem,                   "Problem: {}"),
+        (Message::NoAddresses,               "(keine Adressen)"),
+        (Message::CaaRecordsAt,              "CAA-Einträge bei {} (geprüft: {})"),
+        (Message::NoCaaRecords,              "Keine CAA-Einträge (geprüft: {})"),
+        (Message::Certificates,              "Zertifikate:"),
+        (Message::WildcardCertificates,      "Wildcard-Zertifikate:"),
+        (Message::IncidentReports,           "Vorfallsberichte:"),
+        (Message::AnyCA,                     "jede CA"),
+        (Message::NoCA,                      "keine CA"),
+        (Message::UnknownCriticalTag,        "das kritische Tag {} ist unbekannt, daher darf keine CA ausstellen"),
+        (Message::NullMx,                    "Null-MX-Eintrag: diese Domain nimmt keine E-Mails an"),
+        (Message::ImplicitMx,                "Keine MX-Einträge, daher werden E-Mails an die Adressen der Domain selbst zugestellt"),
+        (Message::NullMxWithOthers,          "ein Null-MX-Eintrag muss der einzige MX-Eintrag sein"),
+        (Message::ExchangeWithoutAddresses,  "der Mailserver {} hat keine Adressen"),
+        (Message::CnameExchange,             "der Mailserver {} ist ein CNAME auf {} statt des eigenen Namens des Servers"),
+        (Message::NoPtrRecords,              "Keine PTR-Einträge"),
+        (Message::ForwardConfirmed,          "Vorwärts bestätigt: ein Name verweist zurück auf diese Adresse"),
+        (Message::NotForwardConfirmed,       "Nicht vorwärts bestätigt: kein Name verweist zurück auf diese Adresse"),
+        (Message::DomainHasRecords,          "Die Domain hat {}-Einträge"),
+        (Message::DomainHasNoRecords,        "Die Domain existiert, hat aber keine {}-Einträge, ist also vielleicht ein leerer Zwischenknoten"),
+        (Message::DomainDoesNotExist,        "Die Domain existiert nicht"),
+        (Message::RecordCount,               "{} Einträge"),
+        (Message::NoRecordsOfType,           "keine {}-Einträge"),
+        (Message::NoWildcard,                "Kein Wildcard: keiner der erfundenen Namen existiert"),
+        (Message::WildcardWithoutRecords,    "Wildcard gefunden, aber ohne {}-Einträge"),
+        (Message::WildcardSynthesising,      "Wildcard gefunden, erzeugt:"),
+        (Message::SystemResolverFailed,      "Der System-Resolver ist fehlgeschlagen: {}"),
+        (Message::Address,                   "Adresse"),
+        (Message::System,                    "System"),
+        (Message::Yes,                       "ja"),
+        (Message::SystemAgrees,              "Der System-Resolver stimmt mit DNS überein"),
+        (Message::DisagreesHostsFile,        "Der System-Resolver weicht von DNS ab, da die Hosts-Datei den Namen enthält"),
+        (Message::DisagreesMulticastDns,     "Der System-Resolver weicht von DNS ab, da Namen unter local per Multicast-DNS aufgelöst werden"),
+        (Message::DisagreesUnknown,          "Der System-Resolver weicht von DNS ab; in /etc/nsswitch.conf steht, wo er sonst nachsieht"),
+        (Message::NoDns64,                   "Kein DNS64: ipv4only.arpa hat keine erzeugten AAAA-Einträge"),
+        (Message::Nat64Prefix,               "NAT64-Präfix {}"),
+        (Message::WellKnownNat64Prefix,      "NAT64-Präfix {} (das bekannte Präfix)"),
+        (Message::SerialBehind,              "{} ({} zurück)"),
+        (Message::InSync,                    "Synchron bei Seriennummer {}"),
+        (Message::OutOfSync,                 "Nicht synchron; die neueste Seriennummer ist {}"),
+        (Message::NoSerial,                  "Kein Nameserver hat mit einer Seriennummer geantwortet"),
+        (Message::DelegatedFrom,             "(delegiert von {})"),
+        (Message::Glue,                      "{} (Glue {})"),
+        (Message::ParentLists,               "Übergeordnete Zone:"),
+        (Message::ZoneLists,                 "Zone selbst:"),
+        (Message::NoAnswer,                  "(keine Antwort)"),
+        (Message::Authoritative,             "autoritativ"),
+        (Message::Lame,                      "lahm: {}"),
+        (Message::NoAddress,                 "keine Adresse"),
+        (Message::Unreachable,               "nicht erreichbar ({})"),
+        (Message::RespondedWith,             "antwortete mit {}"),
+        (Message::NotAuthoritative,          "nicht autoritativ"),
+        (Message::OnlyAtParent,              "die übergeordnete Zone nennt {}, die Zone selbst aber nicht"),
+        (Message::OnlyAtChild,               "die Zone nennt {}, die übergeordnete Zone aber nicht"),
+        (Message::MissingGlue,               "{} liegt in der Zone, aber die übergeordnete Zone hat keinen Glue dafür"),
+        (Message::GlueMismatch,              "der Glue der übergeordneten Zone für {} ist {}, was die Zone nicht als Adresse angibt"),
+        (Message::LameNameserver,            "{} ist lahm, da er nicht für die Zone antwortet"),
+        (Message::ServiceUnavailable,        "Der Dienst ist unter dieser Domain nicht verfügbar"),
+        (Message::NoSrvRecords,              "Keine SRV-Einträge"),
+        (Message::SrvEndpoint,               "Priorität {} Gewicht {} Port {} {}"),
+        (Message::AnsweredBy,                "Beantwortet von {} ({}), autoritativ für {}"),
+        (Message::AnsweredFromHosts,         "Aus der Hosts-Datei beantwortet, nicht von {}"),
+        (Message::Connection,                "Verbindung: {}"),
+        (Message::ConnectedTo,               "verbunden mit {}"),
+        (Message::TlsCipher,                 "{} mit {}"),
+        (Message::HttpStatus,                "{} Status {}"),
+        (Message::FellBackToTcp,             "auf TCP ausgewichen, da die UDP-Antwort abgeschnitten war"),
+        (Message::RecordSetsMatch,           "Alle {} Datensatzgruppen stimmen überein"),
+        (Message::RecordSetsDiffer,          "{} von {} Datensatzgruppen unterscheiden sich"),
+        (Message::MessagesSame,              "Die Nachrichten sind gleich"),
+    ],
+    base: None,
+};
+
+const FRENCH: Catalog = Catalog {
+    language: "fr",
+    units: [ "j", "h", "m", "s" ],
+    messages: &[
+        (Message::NoResults,                 "Aucun résultat"),
+        (Message::RanIn,                     "Exécuté en {}ms"),
+        (Message::QueryTime,                 "Temps de requête : {}ms depuis {} via {}"),
+        (Message::QueryTimeFromCache,        "Temps de requête : 0ms depuis le cache de {}"),
+        (Message::QueryTimeFromHosts,        "Temps de requête : 0ms depuis le fichier hosts"),
+        (Message::AfterRetrying,             ", après une nouvelle tentative"),
+        (Message::Error,                     "Erreur"),
+        (Message::Status,                    "Statut : {}"),
+        (Message::FormatError,               "Erreur de format"),
+        (Message::ServerFailure,             "Échec du serveur"),
+        (Message::NotImplemented,            "Non implémenté"),
+        (Message::QueryRefused,              "Requête refusée"),
+        (Message::BadVersion,                "Version incorrecte"),
+        (Message::OtherFailure,              "Autre échec ({})"),
+        (Message::Problem,                   "Problème : {}"),
+        (Message::NoAddresses,               "(aucune adresse)"),
+        (Message::CaaRecordsAt,              "Enregistrements CAA à {} (vérifié : {})"),
+        (Message::NoCaaRecords,              "Aucun enregistrement CAA (vérifié : {})"),
+        (Message::Certificates,              "Certificats :"),
+        (Message::WildcardCertificates,      "Certificats wildcard :"),
+        (Message::IncidentReports,           "Rapports d’incident :"),
+        (Message::AnyCA,                     "toute AC"),
+        (Message::NoCA,                      "aucune AC"),
+        (Message::UnknownCriticalTag,        "l’étiquette critique {} n’est pas comprise, donc aucune AC ne peut émettre"),
+        (Message::NullMx,                    "Enregistrement MX nul : ce domaine n’accepte pas de courrier"),
+        (Message::ImplicitMx,                "Aucun enregistrement MX, le courrier est donc livré aux adresses du domaine lui-même"),
+        (Message::NullMxWithOthers,          "un enregistrement MX nul doit être le seul enregistrement MX"),
+        (Message::ExchangeWithoutAddresses,  "le serveur de courrier {} n’a aucune adresse"),
+        (Message::CnameExchange,             "le serveur de courrier {} est un CNAME vers {}, plutôt que le nom propre du serveur"),
+        (Message::NoPtrRecords,              "Aucun enregistrement PTR"),
+        (Message::ForwardConfirmed,          "Confirmé : un nom renvoie vers cette adresse"),
+        (Message::NotForwardConfirmed,       "Non confirmé : aucun nom ne renvoie vers cette adresse"),
+        (Message::DomainHasRecords,          "Le domaine a des enregistrements {}"),
+        (Message::DomainHasNoRecords,        "Le domaine existe mais n’a aucun enregistrement {}, il s’agit peut-être d’un nœud intermédiaire vide"),
+        (Message::DomainDoesNotExist,        "Le domaine n’existe pas"),
+        (Message::RecordCount,               "{} enregistrements"),
+        (Message::NoRecordsOfType,           "aucun enregistrement {}"),
+        (Message::NoWildcard,                "Pas de wildcard : aucun des noms inventés n’existe"),
+        (Message::WildcardWithoutRecords,    "Wildcard trouvé, mais sans enregistrement {}"),
+        (Message::WildcardSynthesising,      "Wildcard trouvé, qui synthétise :"),
+        (Message::SystemResolverFailed,      "Le résolveur du système a échoué : {}"),
+        (Message::Address,                   "Adresse"),
+        (Message::System,                    "Système"),
+        (Message::Yes,                       "oui"),
+        (Message::SystemAgrees,              "Le résolveur du système est d’accord avec le DNS"),
+        (Message::DisagreesHostsFile,        "Le résolveur du système diffère du DNS, car le fichier hosts contient le nom"),
+        (Message::DisagreesMulticastDns,     "Le résolveur du système diffère du DNS, car les noms sous local sont résolus par DNS multicast"),
+        (Message::DisagreesUnknown,          "Le résolveur du système diffère du DNS ; voir /etc/nsswitch.conf pour savoir où il cherche aussi"),
+        (Message::NoDns64,                   "Pas de DNS64 : ipv4only.arpa n’a aucun enregistrement AAAA synthétisé"),
+        (Message::Nat64Prefix,               "Préfixe NAT64 {}"),
+        (Message::WellKnownNat64Prefix,      "Préfixe NAT64 {} (le préfixe bien connu)"),
+        (Message::SerialBehind,              "{} ({} de retard)"),
+        (Message::InSync,                    "Synchronisé au numéro de série {}"),
+        (Message::OutOfSync,                 "Désynchronisé ; le numéro de série le plus récent est {}"),
+        (Message::NoSerial,                  "Aucun serveur de noms n’a répondu avec un numéro de série"),
+        (Message::DelegatedFrom,             "(délégué par {})"),
+        (Message::Glue,                      "{} (glue {})"),
+        (Message::ParentLists,               "Selon le parent :"),
+        (Message::ZoneLists,                 "Selon la zone :"),
+        (Message::NoAnswer,                  "(aucune réponse)"),
+        (Message::Authoritative,             "fait autorité"),
+        (Message::Lame,                      "boiteux : {}"),
+        (Message::NoAddress,                 "aucune adresse"),
+        (Message::Unreachable,               "injoignable ({})"),
+        (Message::RespondedWith,             "a répondu {}"),
+        (Message::NotAuthoritative,          "ne fait pas autorité"),
+        (Message::OnlyAtParent,              "le parent indique {}, mais pas la zone"),
+        (Message::OnlyAtChild,               "la zone indique {}, mais pas le parent"),
+        (Message::MissingGlue,               "{} est dans la zone, mais le parent n’a pas de glue pour lui"),
+        (Message::GlueMismatch,              "la glue du parent pour {} est {}, que la zone ne donne pas comme adresse"),
+        (Message::LameNameserver,            "{} est boiteux, car il ne répond pas pour la zone"),
+        (Message::ServiceUnavailable,        "Le service n’est pas disponible sur ce domaine"),
+        (Message::NoSrvRecords,              "Aucun enregistrement SRV"),
+        (Message::SrvEndpoint,               "priorité {} poids {} port {} {}"),
+        (Message::AnsweredBy,                "Répondu par {} ({}), qui fait autorité pour {}"),
+        (Message::AnsweredFromHosts,         "Répondu depuis le fichier hosts, pas par {}"),
+        (Message::Connection,                "Connexion : {}"),
+        (Message::ConnectedTo,               "connecté à {}"),
+        (Message::TlsCipher,                 "{} avec {}"),
+        (Message::HttpStatus,                "{} statut {}"),
+        (Message::FellBackToTcp,             "repli sur TCP car la réponse UDP était tronquée"),
+        (Message::RecordSetsMatch,           "Les {} ensembles d’enregistrements concordent"),
+        (Message::RecordSetsDiffer,          "{} ensembles d’enregistrements sur {} diffèrent"),
+        (Message::MessagesSame,              "Les messages sont identiques"),
+    ],
+    base: None,
+};
+
+const SPANISH: Catalog = Catalog {
+    language: "es",
+    units: [ "d", "h", "m", "s" ],
+    messages: &[
+        (Message::NoResults,                 "Sin resultados"),
+        (Message::RanIn,                     "Ejecutado en {}ms"),
+        (Message::QueryTime,                 "Tiempo de consulta: {}ms desde {} por {}"),
+        (Message::QueryTimeFromCache,        "Tiempo de consulta: 0ms desde la caché de {}"),
+        (Message::QueryTimeFromHosts,        "Tiempo de consulta: 0ms desde el archivo hosts"),
+        (Message::AfterRetrying,             ", tras reintentar"),
+        (Message::Error,                     "Error"),
+        (Message::Status,                    "Estado: {}"),
+        (Message::FormatError,               "Error de formato"),
+        (Message::ServerFailure,             "Fallo del servidor"),
+        (Message::NotImplemented,            "No implementado"),
+        (Message::QueryRefused,              "Consulta rechazada"),
+        (Message::BadVersion,                "Versión incorrecta"),
+        (Message::OtherFailure,              "Otro fallo ({})"),
+        (Message::Problem,                   "Problema: {}"),
+        (Message::NoAddresses,               "(sin direcciones)"),
+        (Message::CaaRecordsAt,              "Registros CAA en {} (comprobado: {})"),
+        (Message::NoCaaRecords,              "Sin registros CAA (comprobado: {})"),
+        (Message::Certificates,              "Certificados:"),
+        (Message::WildcardCertificates,      "Certificados comodín:"),
+        (Message::IncidentReports,           "Informes de incidentes:"),
+        (Message::AnyCA,                     "cualquier CA"),
+        (Message::NoCA,                      "ninguna CA"),
+        (Message::UnknownCriticalTag,        "la etiqueta crítica {} no se entiende, así que ninguna CA puede emitir"),
+        (Message::NullMx,                    "Registro MX nulo: este dominio no acepta correo"),
+        (Message::ImplicitMx,                "Sin registros MX, así que el correo se entrega a las direcciones del propio dominio"),
+        (Message::NullMxWithOthers,          "un registro MX nulo debe ser el único registro MX"),
+        (Message::ExchangeWithoutAddresses,  "el servidor de correo {} no tiene direcciones"),
+        (Message::CnameExchange,             "el servidor de correo {} es un CNAME a {}, en lugar del nombre propio del servidor"),
+        (Message::NoPtrRecords,              "Sin registros PTR"),
+        (Message::ForwardConfirmed,          "Confirmado: un nombre apunta de vuelta a esta dirección"),
+        (Message::NotForwardConfirmed,       "No confirmado: ningún nombre apunta de vuelta a esta dirección"),
+        (Message::DomainHasRecords,          "El dominio tiene registros {}"),
+        (Message::DomainHasNoRecords,        "El dominio existe pero no tiene registros {}, así que puede ser un nodo intermedio vacío"),
+        (Message::DomainDoesNotExist,        "El dominio no existe"),
+        (Message::RecordCount,               "{} registros"),
+        (Message::NoRecordsOfType,           "sin registros {}"),
+        (Message::NoWildcard,                "Sin comodín: ninguno de los nombres inventados existe"),
+        (Message::WildcardWithoutRecords,    "Comodín encontrado, pero sin registros {}"),
+        (Message::WildcardSynthesising,      "Comodín encontrado, que sintetiza:"),
+        (Message::SystemResolverFailed,      "El resolvedor del sistema falló: {}"),
+        (Message::Address,                   "Dirección"),
+        (Message::System,                    "Sistema"),
+        (Message::Yes,                       "sí"),
+        (Message::SystemAgrees,              "El resolvedor del sistema coincide con DNS"),
+        (Message::DisagreesHostsFile,        "El resolvedor del sistema no coincide con DNS, porque el archivo hosts contiene el nombre"),
+        (Message::DisagreesMulticastDns,     "El resolvedor del sistema no coincide con DNS, porque los nombres bajo local se resuelven con DNS multicast"),
+        (Message::DisagreesUnknown,          "El resolvedor del sistema no coincide con DNS; consulte /etc/nsswitch.conf para ver dónde más busca"),
+        (Message::NoDns64,                   "Sin DNS64: ipv4only.arpa no tiene registros AAAA sintetizados"),
+        (Message::Nat64Prefix,               "Prefijo NAT64 {}"),
+        (Message::WellKnownNat64Prefix,      "Prefijo NAT64 {} (el prefijo conocido)"),
+        (Message::SerialBehind,              "{} ({} por detrás)"),
+        (Message::InSync,                    "Sincronizado en el número de serie {}"),
+        (Message::OutOfSync,                 "Sin sincronizar; el número de serie más reciente es {}"),
+        (Message::NoSerial,                  "Ningún servidor de nombres respondió con un número de serie"),
+        (Message::DelegatedFrom,             "(delegado desde {})"),
+        (Message::Glue,                      "{} (glue {})"),
+        (Message::ParentLists,               "Según el padre:"),
+        (Message::ZoneLists,                 "Según la zona:"),
+        (Message::NoAnswer,                  "(sin respuesta)"),
+        (Message::Authoritative,             "autoritativo"),
+        (Message::Lame,                      "cojo: {}"),
+        (Message::NoAddress,                 "sin dirección"),
+        (Message::Unreachable,               "inalcanzable ({})"),
+        (Message::RespondedWith,             "respondió con {}"),
+        (Message::NotAuthoritative,          "no autoritativo"),
+        (Message::OnlyAtParent,              "el padre indica {}, pero la zona no"),
+        (Message::OnlyAtChild,               "la zona indica {}, pero el padre no"),
+        (Message::MissingGlue,               "{} está dentro de la zona, pero el padre no tiene glue para él"),
+        (Message::GlueMismatch,              "el glue del padre para {} es {}, que la zona no da como su dirección"),
+        (Message::LameNameserver,            "{} está cojo, porque no responde por la zona"),
+        (Message::ServiceUnavailable,        "El servicio no está disponible en este dominio"),
+        (Message::NoSrvRecords,              "Sin registros SRV"),
+        (Message::SrvEndpoint,               "prioridad {} peso {} puerto {} {}"),
+        (Message::AnsweredBy,                "Respondido por {} ({}), autoritativo para {}"),
+        (Message::AnsweredFromHosts,         "Respondido desde el archivo hosts, no por {}"),
+        (Message::Connection,                "Conexión: {}"),
+        (Message::ConnectedTo,               "conectado a {}"),
+        (Message::TlsCipher,                 "{} con {}"),
+        (Message::HttpStatus,                "{} estado {}"),
+        (Message::FellBackToTcp,             "se recurrió a TCP porque la respuesta UDP estaba truncada"),
+        (Message::RecordSetsMatch,           "Los {} conjuntos de registros coinciden"),
+        (Message::RecordSetsDiffer,          "{} de {} conjuntos de registros difieren"),
+        (Message::MessagesSame,              "Los mensajes son iguales"),
+    ],
+    base: None,
+};
+
+/// Every catalog there is.
+static CATALOGS: [&Catalog; 4] = [ &ENGLISH, &GERMAN, &FRENCH, &SPANISH ];
+
+/// The environment variables that say which language to use, in the order
+/// they take priority.
+const LOCALE_VARIABLES: [&str; 3] = [ "LC_ALL", "LC_MESSAGES", "LANG" ];
+
+
+/// Finds the catalog for a locale such as `de`, `de_AT.UTF-8`, or `de-AT`,
+/// going by the language at the start of it. The `C` and `POSIX` locales
+/// are English.
+pub fn find(locale: &str) -> Option<&'static Catalog> {
+    let language = locale.split([ '_', '-', '.', '@' ]).next().unwrap_or_default();
+
+    if language == "C" || language == "POSIX" {
+        return Some(&ENGLISH);
+    }
+
+    CATALOGS.iter().copied().find(|catalog| catalog.language.eq_ignore_ascii_case(language))
+}
+
+/// The codes of the languages there are catalogs for.
+pub fn languages() -> impl Iterator<Item=&'static str> {
+    CATALOGS.iter().map(|catalog| catalog.language)
+}
+
+/// Picks the catalog for the locale in the first of the locale environment
+/// variables that is set, using the given function to read them. This is
+/// English if none are set, or if there is no catalog for the language.
+pub fn from_environment(var: impl Fn(&str) -> Option<String>) -> &'static Catalog {
+    LOCALE_VARIABLES.iter()
+        .filter_map(|name| var(name).filter(|value| ! value.is_empty()))
+        .next()
+        .and_then(|locale| find(&locale))
+        .unwrap_or(&ENGLISH)
+}
+
+/// Reads a catalog from the file at the given path. Its messages are kept
+/// for as long as dog runs, the same as the built-in ones.
+pub fn read(path: &Path) -> Result<&'static Catalog, CatalogError> {
+    let text = fs::read_to_string(path).map_err(CatalogError::IO)?;
+    Ok(Box::leak(Box::new(parse(&text)?)))
+}
+
+/// Parses the text of a catalog file. Each line gives a setting or a
+/// message as `name = value`, and blank lines and lines starting with `#`
+/// are skipped. The settings are the `language`, which picks the built-in
+/// catalog to take missing messages from, and the four duration `units`.
+pub fn parse(text: &str) -> Result<Catalog, CatalogError> {
+    let mut language = None;
+    let mut units = None;
+    let mut messages = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = match line.split_once('=') {
+            Some((name, value))  => (name.trim(), leak(value.trim())),
+            None                 => return Err(CatalogError::MissingEquals(line_number)),
+        };
+
+        match name {
+            "language" => {
+                language = Some(value);
+            }
+            "units" => {
+                let parts = value.split_whitespace().map(leak).collect::<Vec<_>>();
+                units = Some(<[&'static str; 4]>::try_from(parts).map_err(|_| CatalogError::WrongUnits(line_number))?);
+            }
+            _ => {
+                let message = Message::named(name).ok_or_else(|| CatalogError::UnknownMessage(line_number, name.into()))?;
+                messages.push((message, value));
+            }
+        }
+    }
+
+    let base = language.and_then(find);
+    let units = units.unwrap_or(base.unwrap_or(&ENGLISH).units);
+    let language = language.unwrap_or(ENGLISH.language);
+    Ok(Catalog { language, units, messages: Box::leak(messages.into_boxed_slice()), base })
+}
+
+/// Keeps a string from a catalog file for as long as dog runs.
+fn leak(value: &str) -> &'static str {
+    Box::leak(value.to_owned().into_boxed_str())
+}
+
+/// Something wrong with a catalog file.
+#[derive(Debug)]
+pub enum CatalogError {
+
+    /// The file could not be read.
+    IO(io::Error),
+
+    /// A line has no `=` between the name and the value.
+    MissingEquals(usize),
+
+    /// A line names a message that does not exist.
+    UnknownMessage(usize, String),
+
+    /// The `units` line does not have exactly four units in it.
+    WrongUnits(usize),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(e)                        => write!(f, "{}", e),
+            Self::MissingEquals(line)          => write!(f, "line {} has no ‘=’ in it", line),
+            Self::UnknownMessage(line, name)   => write!(f, "line {} has an unknown message {:?}", line, name),
+            Self::WrongUnits(line)             => write!(f, "line {} should have four units", line),
+        }
+    }
+}
+
+
+impl Catalog {
+
+    /// Returns a message with the given values put in it.
+    pub fn format(&self, message: Message, values: &[&dyn fmt::Display]) -> String {
+        let template = self.template(message);
+        let mut values = values.iter();
+        let mut output = String::new();
+
+        for (index, piece) in template.split("{}").enumerate() {
+            if index > 0 {
+                if let Some(value) = values.next() {
+                    output.push_str(&value.to_string());
+                }
+            }
+
+            output.push_str(piece);
+        }
+
+        output
+    }
+
+    /// Returns a message that has no values to put in it.
+    pub fn message(&self, message: Message) -> &'static str {
+        self.template(message)
+    }
+
+    /// Returns the untranslated message, falling back to the base catalog
+    /// and then English if this catalog does not have it.
+    fn template(&self, message: Message) -> &'static str {
+        self.messages.iter()
+            .chain(self.base.map_or(&[][..], |base| base.messages))
+            .chain(ENGLISH.messages)
+            .find(|(m, _)| *m == message)
+            .map(|(_, template)| *template)
+            .expect("English catalog is missing a message")
+    }
+
+    /// Formats a number of seconds as days, hours, minutes, and seconds,
+    /// leaving out the larger units when they are zero.
+    pub fn duration(&self, seconds: u32) -> String {
+        let [ d, h, m, s ] = self.units;
+
+        if seconds < 60 {
+            format!("{}{}", seconds, s)
+        }
+        else if seconds < 60 * 60 {
+            format!("{}{}{:02}{}", seconds / 60, m, seconds % 60, s)
+        }
+        else if seconds < 60 * 60 * 24 {
+            format!("{}{}{:02}{}{:02}{}", seconds / 3600, h, (seconds % 3600) / 60, m, seconds % 60, s)
+        }
+        else {
+            format!("{}{}{}{}{:02}{}{:02}{}", seconds / 86400, d, (seconds % 86400) / 3600, h, (seconds % 3600) / 60, m, seconds % 60, s)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn locale_names() {
+        assert_eq!(find("de"), Some(&GERMAN));
+        assert_eq!(find("fr_CA.UTF-8"), Some(&FRENCH));
+        assert_eq!(find("es-MX"), Some(&SPANISH));
+        assert_eq!(find("C.UTF-8"), Some(&ENGLISH));
+        assert_eq!(find("tlh"), None);
+    }
+
+    #[test]
+    fn environment_priority() {
+        let catalog = from_environment(|name| match name {
+            "LC_ALL"  => Some(String::new()),
+            "LANG"    => Some("de_DE.UTF-8".into()),
+            _         => None,
+        });
+
+        assert_eq!(catalog.language, "de");
+        assert_eq!(from_environment(|_| None).language, "en");
+        assert_eq!(from_environment(|_| Some("tlh".into())).language, "en");
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(GERMAN.format(Message::QueryTime, &[ &12, &"192.0.2.53:53", &"UDP" ]), "Abfragezeit: 12ms von 192.0.2.53:53 über UDP");
+        assert_eq!(FRENCH.format(Message::OtherFailure, &[ &23 ]), "Autre échec (23)");
+    }
+
+    #[test]
+    fn english_fallback() {
+        assert_eq!(GERMAN.message(Message::NXDomain), "NXDomain");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(ENGLISH.duration(59), "59s");
+        assert_eq!(ENGLISH.duration(3601), "1h00m01s");
+        assert_eq!(GERMAN.duration(90061), "1T1h01m01s");
+        assert_eq!(FRENCH.duration(86400), "1j0h00m00s");
+    }
+
+    #[test]
+    fn every_english_message() {
+        for message in Message::ALL {
+            assert!(ENGLISH.messages.iter().any(|(m, _)| m == message), "{:?}", message);
+        }
+
+        assert_eq!(ENGLISH.messages.len(), Message::ALL.len());
+    }
+
+    #[test]
+    fn catalog_file() {
+        let catalog = parse("# Dutch\nlanguage = nl\nunits = d u m s\n\nNoResults = Geen resultaten\n").unwrap();
+        assert_eq!(catalog.language, "nl");
+        assert_eq!(catalog.message(Message::NoResults), "Geen resultaten");
+        assert_eq!(catalog.message(Message::Error), "Error");
+        assert_eq!(catalog.duration(3600), "1u00m00s");
+    }
+
+    #[test]
+    fn catalog_file_over_built_in() {
+        let catalog = parse("language = de\nRanIn = Fertig nach {}ms\n").unwrap();
+        assert_eq!(catalog.format(Message::RanIn, &[ &5 ]), "Fertig nach 5ms");
+        assert_eq!(catalog.message(Message::NoResults), "Keine Ergebnisse");
+        assert_eq!(catalog.duration(86400), "1T0h00m00s");
+    }
+
+    #[test]
+    fn catalog_file_mistakes() {
+        assert!(matches!(parse("NoResults"), Err(CatalogError::MissingEquals(1))));
+        assert!(matches!(parse("\nNoResultz = Nothing"), Err(CatalogError::UnknownMessage(2, _))));
+        assert!(matches!(parse("units = d h m"), Err(CatalogError::WrongUnits(1))));
+    }
+}
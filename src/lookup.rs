@@ -0,0 +1,92 @@
+//! Looking names up through the resolver, for the commands that check how
+//! a domain is set up, which all fail in the same ways.
+
+use dns::{ErrorCode, Name, Response};
+use dns_transport::Error as TransportError;
+
+
+/// Something that stopped a name from being looked up.
+#[derive(Debug)]
+pub enum LookupError {
+
+    /// The query for the name failed.
+    Transport(Name, TransportError),
+
+    /// The resolver responded with an error other than `NXDOMAIN`.
+    Rcode(Name, ErrorCode),
+}
+
+impl LookupError {
+
+    /// The name that was being looked up.
+    pub fn name(&self) -> &Name {
+        match self {
+            Self::Transport(name, _) | Self::Rcode(name, _)  => name,
+        }
+    }
+}
+
+/// Returns an error if the response has an error code. `NXDOMAIN` just
+/// means there are no records, so it counts as an empty answer.
+pub fn check_rcode(name: &Name, response: &Response) -> Result<(), LookupError> {
+    match response.flags.error_code {
+        None | Some(ErrorCode::NXDomain)  => Ok(()),
+        Some(rcode)                       => Err(LookupError::Rcode(name.clone(), rcode)),
+    }
+}
+
+
+/// Responses for the checkers’ tests to have their lookup functions
+/// answer with.
+#[cfg(test)]
+pub mod testing {
+    use dns::{Answer, ErrorCode, Flags, Name, QClass, Query, Request, Response, TypeInt};
+    use dns::record::Record;
+
+    pub fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    pub fn answer(qname: &str, record: Record) -> Answer {
+        Answer::Standard { qname: name(qname), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    /// A response to a query for the given name and type, with the given
+    /// error code and answers.
+    pub fn response(qname: &Name, qtype: TypeInt, rcode: Option<ErrorCode>, answers: Vec<Answer>) -> Response {
+        let request = Request {
+            transaction_id: 0,
+            flags: Flags::query(),
+            queries: vec![ Query { qname: qname.clone(), qclass: QClass::IN, qtype } ],
+            additional: None,
+        };
+
+        let mut response = dns::testing::response_to(&request);
+        response.flags.error_code = rcode;
+        response.answers = answers;
+        response
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::testing::*;
+    use dns::qtype;
+    use dns::record::A;
+
+    #[test]
+    fn nxdomain_is_empty() {
+        let response = response(&name("lookup.dog."), qtype!(A), Some(ErrorCode::NXDomain), Vec::new());
+        assert!(check_rcode(&name("lookup.dog."), &response).is_ok());
+    }
+
+    #[test]
+    fn servfail_is_an_error() {
+        let response = response(&name("lookup.dog."), qtype!(A), Some(ErrorCode::ServerFailure), Vec::new());
+        let error = check_rcode(&name("lookup.dog."), &response).unwrap_err();
+        assert!(matches!(error, LookupError::Rcode(_, ErrorCode::ServerFailure)));
+        assert_eq!(error.name(), &name("lookup.dog."));
+    }
+}
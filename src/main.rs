@@ -11,22 +11,71 @@
 #![warn(trivial_casts, trivial_numeric_casts)]
 #![warn(unused)]
 
+#![allow(clippy::upper_case_acronyms)]
+
 #![deny(unsafe_code)]
 
 
+use std::cell::RefCell;
 use std::env;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
 use std::process::exit;
-use std::time::Instant;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures_util::stream::{self, StreamExt};
 
 use log::*;
 
+mod authority;
+mod bench;
+mod caa;
+mod cache;
+mod chain;
 mod colours;
+mod completions;
 mod connect;
+mod csv;
+mod debugwire;
+mod delegation;
+mod diff;
+mod dig;
+mod expect;
+mod failure;
+mod fcrdns;
+mod hexdump;
+mod hosts;
+mod input;
+mod interpret;
+mod json;
+mod locale;
+mod lookup;
+mod mx;
+mod nat64;
 mod output;
+mod pcap;
+mod ping;
+mod presets;
+mod probe;
+mod propagate;
 mod requests;
 mod resolve;
+mod serve;
+mod soa;
+mod sort;
+mod srv;
+mod stats;
+mod syscmp;
 mod table;
+mod trace;
 mod txid;
+mod validate;
+mod watch;
+mod wildcard;
+mod wirelog;
+mod yaml;
 
 mod options;
 use self::options::*;
@@ -40,7 +89,7 @@ fn main() {
     match Options::getopts(env::args_os().skip(1)) {
         OptionsResult::Ok(options) => {
             info!("Running with options -> {:#?}", options);
-            let dog = Dog::init(options);
+            let dog = Dog::init(*options);
             exit(dog.run());
         }
 
@@ -71,6 +120,19 @@ fn main() {
             exit(exits::SUCCESS);
         }
 
+        OptionsResult::ListResolvers => {
+            for line in presets::list_lines() {
+                println!("{}", line);
+            }
+
+            exit(exits::SUCCESS);
+        }
+
+        OptionsResult::Completions(shell) => {
+            print!("{}", completions::script(shell));
+            exit(exits::SUCCESS);
+        }
+
         OptionsResult::InvalidOptionsFormat(oe) => {
             eprintln!("Invalid options: {:?}", oe);
             exit(exits::OPTIONS_ERROR);
@@ -85,24 +147,36 @@ fn main() {
 
 
 /// Checks the `DOG_DEBUG` environment variable, enabling debug logging if
-/// it’s non-empty.
+/// it’s non-empty. It can also be a list of filters, such as
+/// `dog::cache=trace,warn`, to pick which levels get logged for which
+/// modules. The messages from `--debug-wire` always get logged.
 fn configure_logger() {
-    let present = match env::var_os("DOG_DEBUG") {
-        Some(debug)  => debug.len() > 0,
-        None         => false,
-    };
+    let debug = env::var("DOG_DEBUG").unwrap_or_default();
 
     let mut logs = env_logger::Builder::new();
-    if present {
+    let _ = logs.format_timestamp_millis();
+
+    if is_log_filter(&debug) {
+        let _ = logs.parse_filters(&debug);
+    }
+    else if ! debug.is_empty() {
         let _ = logs.filter(None, log::LevelFilter::Debug);
     }
     else {
         let _ = logs.filter(None, log::LevelFilter::Off);
     }
 
+    let _ = logs.filter(Some("dog::debugwire"), log::LevelFilter::Debug);
     logs.init()
 }
 
+/// Whether the value of `DOG_DEBUG` is a list of log filters, rather than
+/// just something to turn debug logging on with, such as `1`.
+fn is_log_filter(debug: &str) -> bool {
+    let levels = [ "off", "error", "warn", "info", "debug", "trace" ];
+    debug.contains('=') || debug.split(',').any(|filter| levels.contains(&filter.to_ascii_lowercase().as_str()))
+}
+
 struct Dog {
     options: Options,
 }
@@ -113,39 +187,886 @@ impl Dog {
     }
 
     fn run(self) -> i32 {
-        let Options { requests, format, measure_time } = self.options;
-        let mut runtime = dns_transport::Runtime::new().expect("Failed to create runtime");
+        let caches = self.options.requests.cache.clone();
+
+        if let Some(log) = &self.options.requests.wire_log {
+            if let Err(e) = log.open() {
+                eprintln!("Failed to open wire log: {}", e);
+                return exits::OPTIONS_ERROR;
+            }
+        }
+
+        let exit_code = match self.options.command.clone() {
+            Some(Command::Diff(zone_file))           => self.run_diff(&zone_file),
+            Some(Command::Decode(source))            => self.run_decode(&source),
+            Some(Command::Pcap(capture_file))        => self.run_pcap(&capture_file),
+            Some(Command::Compare(left, right, opts)) => self.run_compare(&left, &right, opts),
+            Some(Command::Watch(interval))           => self.run_watch(interval),
+            Some(Command::Propagate)                 => self.run_propagate(),
+            Some(Command::CaaCheck)                  => self.run_caa_check(),
+            Some(Command::Srv(..))                   => self.run_srv(),
+            Some(Command::MxCheck)                   => self.run_mx_check(),
+            Some(Command::Fcrdns)                    => self.run_fcrdns(),
+            Some(Command::Wildcard)                  => self.run_wildcard(),
+            Some(Command::Syscmp)                    => self.run_syscmp(),
+            Some(Command::Nat64)                     => self.run_nat64(),
+            Some(Command::SoaCheck)                  => self.run_soa_check(),
+            Some(Command::Delegation)                => self.run_delegation(),
+            Some(Command::Bench(input, load))        => self.run_bench(&input, load),
+            Some(Command::Ping(count))               => self.run_ping(count),
+            Some(Command::Probe(address, interval))  => self.run_probe(address, interval),
+            Some(Command::Serve(listen, zone_file))  => self.run_serve(listen, zone_file.as_deref()),
+            None if self.options.trace               => self.run_trace(),
+            None | Some(Command::Batch(_))           => self.run_queries(),
+        };
+
+        // Caches kept on disk get saved once everything has finished, as
+        // the commands that keep running until interrupted never get here.
+        if let Some(caches) = caches {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+            if let Err(e) = caches.save(now) {
+                eprintln!("Failed to save cache: {}", e);
+            }
+        }
+
+        exit_code
+    }
+
+    fn run_queries(self) -> i32 {
+        let Options { requests, format, measure_time, stats, interpret, expectations, authoritative, dns64_prefix, chase, validate, trust_anchors, parallel, command, sections, sort, .. } = self.options;
+        let mut runtime = new_runtime();
         let should_show_opt = requests.edns.should_show();
 
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let mut anchors = Vec::new();
+        if validate {
+            match trust_anchors.load(now) {
+                Ok(a) => {
+                    anchors = a;
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return exits::OPTIONS_ERROR;
+                }
+            }
+        }
+
+        // Queries read from a file are grouped by the line they came from, so
+        // each group can be labelled in the output.
+        let mut labels = Vec::new();
+        let mut generated = Vec::new();
+        if let Some(Command::Batch(input)) = &command {
+            let lines = match read_batch(input) {
+                Ok(l) => l,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return exits::OPTIONS_ERROR;
+                }
+            };
+
+            for (label, inputs) in lines {
+                let types = if inputs.types.is_empty() { &requests.inputs.types } else { &inputs.types };
+                let classes = if inputs.classes.is_empty() { &requests.inputs.classes } else { &inputs.classes };
+
+                let group = labels.len();
+                generated.extend(requests.generate_for(&inputs.domains, types, classes).into_iter().map(|g| (group, g)));
+                labels.push(label);
+            }
+        }
+        else {
+            generated.extend(requests.generate().into_iter().map(|g| (0, g)));
+        }
+
+        let mut errored = false;
+        let mut error_exit = None;
+        let mut failed = 0;
+        let mut query_errors = Vec::new();
+
+        // Each query gets sent to an authoritative nameserver instead of the
+        // resolver, once the resolver has been asked where to find one, with
+        // recursion turned off as the nameserver has the answer itself.
+        if authoritative {
+            let mut redirected = Vec::new();
+            for (group, (mut request, transport, destination)) in generated {
+                let query = request.queries[0].clone();
+                let found = authority::find(&query.qname, |name, qtype| {
+                    let request = requests.make_request(name, qtype, query.qclass);
+                    runtime.block_on(async { transport.exchange(&request).await })
+                });
+
+                match found {
+                    Ok(authority) => {
+                        request.flags.recursion_desired = false;
+                        let nameserver = SocketAddr::new(authority.address, 53).to_string();
+                        let transport = Rc::from(requests.make_transport(destination.transport_type, nameserver.clone()));
+                        let destination = requests::Destination { nameserver, authority: Some(authority), .. destination };
+                        redirected.push((group, (request, transport, destination)));
+                    }
+                    Err(authority::AuthorityError::Lookup(lookup::LookupError::Transport(_, e))) => {
+                        error_exit = error_exit.or_else(|| Some(exits::query_error(&e)));
+                        query_errors.push(output::QueryError::Transport(e));
+                        errored = true;
+                        failed += 1;
+                    }
+                    Err(e) => {
+                        error_exit = error_exit.or_else(|| Some(failure::Failure::exit_code(&e)));
+                        query_errors.push(output::QueryError::Authority(e));
+                        errored = true;
+                        failed += 1;
+                    }
+                }
+            }
+
+            generated = redirected;
+        }
+
         let mut responses = Vec::new();
         let timer = if measure_time { Some(Instant::now()) } else { None };
 
-        let mut errored = false;
-        for (request, transport) in requests.generate() {
-            let result = runtime.block_on(async { transport.send(&request).await });
+        // Queries from the command line all get sent at once unless limited,
+        // but as a file of queries could be any length, those get sent one
+        // at a time instead.
+        let parallel = parallel.unwrap_or(if labels.is_empty() { generated.len().max(1) } else { 1 });
 
+        let results = send_all(&mut runtime, generated.iter().map(|(_, (request, transport, _))| (request, &**transport)).collect(), parallel);
+        for ((group, (_, transport, destination)), result) in generated.into_iter().zip(results) {
             match result {
-                Ok(mut response) => {
+                Ok(reply) => {
+                    let dns_transport::Reply { mut response, bytes, round_trip, attempts, protocol, connection, .. } = reply;
+                    if chase {
+                        if let Err(e) = chase_cnames(&mut runtime, &requests, &*transport, &mut response) {
+                            error_exit = error_exit.or_else(|| Some(exits::query_error(&e)));
+                            query_errors.push(output::QueryError::Transport(e));
+                            errored = true;
+                        }
+                    }
+
+                    // The OPT record is kept for the JSON output even when it
+                    // is not being shown as one of the additional records.
+                    let edns = response.additionals.iter().find_map(|answer| {
+                        match answer {
+                            dns::Answer::Pseudo { opt, .. }  => Some(opt.clone()),
+                            dns::Answer::Standard { .. }     => None,
+                        }
+                    });
+
                     if ! should_show_opt {
                         response.answers.retain(dns::Answer::is_standard);
                         response.authorities.retain(dns::Answer::is_standard);
                         response.additionals.retain(dns::Answer::is_standard);
                     }
 
-                    responses.push(response);
+                    if let Some(prefix) = dns64_prefix {
+                        nat64::synthesise(&mut response, prefix);
+                    }
+
+                    if sort {
+                        sort::sort_response(&mut response);
+                    }
+
+                    let exchange = output::Exchange { destination, round_trip, attempts, protocol, connection, edns, bytes };
+                    responses.push((group, response, transport, exchange));
                 }
                 Err(e) => {
-                    format.print_error(e);
+                    error_exit = error_exit.or_else(|| Some(exits::query_error(&e)));
+                    query_errors.push(output::QueryError::Transport(e));
                     errored = true;
+                    failed += 1;
                 }
             }
         }
 
+        // Validation happens after every response has arrived, so the time
+        // taken only covers the queries themselves.
         let duration = timer.map(|t| t.elapsed());
-        if format.print(responses, duration) {
-            if errored {
+        let mut validations = Vec::new();
+        if validate {
+            let mut validator = validate::Validator::new(&mut runtime, &requests, &anchors);
+            for (_, response, transport, _) in &responses {
+                validations.push(validator.validate(&**transport, response));
+            }
+
+            if let Some(root_keys) = validator.root_keys().filter(|_| trust_anchors.is_tracked()) {
+                if validate::track(&mut anchors, root_keys, now) {
+                    if let Err(e) = trust_anchors.save(&anchors) {
+                        eprintln!("Failed to save trust anchors: {}", e);
+                        errored = true;
+                    }
+                }
+            }
+        }
+
+        let stats = if stats {
+            let exchanges = responses.iter().map(|(_, _, _, exchange)| exchange).collect::<Vec<_>>();
+            Some(stats::Stats::summarise(&exchanges, failed))
+        }
+        else {
+            None
+        };
+
+        // Expectations that failed get printed along with the errors, so they
+        // end up in the same document in the JSON output.
+        let failures = expectations.check(&responses.iter().map(|(_, response, _, _)| response).collect::<Vec<_>>());
+        let expectation_failed = ! failures.is_empty();
+        query_errors.extend(failures.into_iter().map(output::QueryError::Expectation));
+        let error_code = responses.iter().find_map(|(_, response, _, _)| response.flags.error_code);
+
+        let interpretations = if interpret {
+            Some(interpret::interpret_responses(&responses.iter().map(|(_, response, _, _)| response).collect::<Vec<_>>()))
+        }
+        else {
+            None
+        };
+
+        let printed = if labels.is_empty() {
+            let (responses, exchanges) = responses.into_iter().map(|(_, response, _, exchange)| (response, exchange)).unzip();
+            format.print(output::Printable { responses, exchanges, validations, errors: query_errors, chase, sections }, duration)
+        }
+        else {
+            let mut groups = labels.into_iter().map(|label| {
+                output::BatchGroup { label, responses: Vec::new(), exchanges: Vec::new(), validations: Vec::new() }
+            }).collect::<Vec<_>>();
+
+            let mut validations = validations.into_iter();
+            for (group, response, _, exchange) in responses {
+                groups[group].responses.push(response);
+                groups[group].exchanges.push(exchange);
+                groups[group].validations.extend(validations.next());
+            }
+
+            format.print_batch(groups, duration, query_errors, chase, sections)
+        };
+
+        if let Some(stats) = stats {
+            format.print_stats(&stats);
+        }
+
+        if let Some(interpretations) = interpretations {
+            format.print_interpretations(&interpretations);
+        }
+
+        // An error code only sets the exit status when nothing was expected
+        // of the responses, as expecting one is how to check for it.
+        if errored {
+            error_exit.unwrap_or(exits::NETWORK_ERROR)
+        }
+        else if expectation_failed {
+            exits::EXPECTATION_FAILED
+        }
+        else if let Some(rcode) = error_code.filter(|_| expectations.is_empty()) {
+            exits::error_code(rcode)
+        }
+        else if ! printed {
+            exits::NO_SHORT_RESULTS
+        }
+        else {
+            exits::SUCCESS
+        }
+    }
+
+    fn run_trace(self) -> i32 {
+        let Options { requests, format, measure_time, .. } = self.options;
+        let mut runtime = new_runtime();
+        let should_show_opt = requests.edns.should_show();
+        let transport_type = requests.inputs.transport_types[0];
+
+        let mut traces = Vec::new();
+        let timer = if measure_time { Some(Instant::now()) } else { None };
+
+        let mut tracer = trace::Tracer::new(&mut runtime, &requests, transport_type);
+        for domain in &requests.inputs.domains {
+            for qtype in requests.inputs.types.iter().copied() {
+                for qclass in requests.inputs.classes.iter().copied() {
+                    let mut trace = tracer.trace(domain, qtype, qclass);
+
+                    if ! should_show_opt {
+                        for step in &mut trace.steps {
+                            step.response.answers.retain(dns::Answer::is_standard);
+                            step.response.authorities.retain(dns::Answer::is_standard);
+                            step.response.additionals.retain(dns::Answer::is_standard);
+                        }
+                    }
+
+                    traces.push(trace);
+                }
+            }
+        }
+
+        let error_exit = exits::first_failure(traces.iter().filter_map(|t| t.error.as_ref()));
+        let duration = timer.map(|t| t.elapsed());
+        if format.print_traces(traces, duration) {
+            if let Some(code) = error_exit {
+                code
+            }
+            else {
+                exits::SUCCESS
+            }
+        }
+        else {
+            exits::NO_SHORT_RESULTS
+        }
+    }
+
+    /// Sends the queries over and over until interrupted, printing how the
+    /// answers changed each round. A round where any query failed gets its
+    /// errors printed instead, and is not compared against, so a timeout
+    /// does not look like every record went away and came back again.
+    fn run_watch(self, interval: Duration) -> i32 {
+        let Options { requests, format, .. } = self.options;
+        let mut runtime = new_runtime();
+        let mut previous: Option<(Instant, Vec<dns::Answer>)> = None;
+
+        loop {
+            let started = Instant::now();
+            let generated = requests.generate();
+            let results = send_all(&mut runtime, generated.iter().map(|(request, transport, _)| (request, &**transport)).collect(), generated.len().max(1));
+
+            let mut answers = Vec::new();
+            let mut errored = false;
+            for result in results {
+                match result {
+                    Ok(reply) => {
+                        answers.extend(reply.response.answers.into_iter().filter(dns::Answer::is_standard));
+                    }
+                    Err(e) => {
+                        format.print_error(e);
+                        errored = true;
+                    }
+                }
+            }
+
+            if ! errored {
+                let elapsed = previous.as_ref().map_or(0, |(sent, _)| started.duration_since(*sent).as_secs());
+                let watched = watch::compare_rounds(previous.as_ref().map(|(_, a)| &a[..]), &answers, elapsed);
+
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+                format.print_watch_round(now, &watched);
+                previous = Some((started, answers));
+            }
+
+            std::thread::sleep(interval.saturating_sub(started.elapsed()));
+        }
+    }
+
+    fn run_probe(self, address: SocketAddr, interval: Duration) -> i32 {
+        let Options { requests, format, .. } = self.options;
+        let mut runtime = new_runtime();
+
+        let listener = match TcpListener::bind(address) {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("Failed to listen on {}: {}", address, e);
+                return exits::NETWORK_ERROR;
+            }
+        };
+
+        // The metrics get served from their own thread, so a scrape never
+        // has to wait for a round of queries to finish.
+        let metrics = Arc::new(Mutex::new(probe::Metrics::default()));
+        let served = Arc::clone(&metrics);
+        std::thread::spawn(move || probe::serve(listener, &served));
+        info!("Serving metrics on {}", address);
+
+        loop {
+            let started = Instant::now();
+            let generated = requests.generate();
+            let results = send_all(&mut runtime, generated.iter().map(|(request, transport, _)| (request, &**transport)).collect(), generated.len().max(1));
+
+            let mut metrics = metrics.lock().expect("Metrics lock poisoned");
+            for ((request, _, destination), result) in generated.iter().zip(results) {
+                let target = probe::Target::of(&request.queries[0], &destination.nameserver);
+
+                match result {
+                    Ok(reply) => {
+                        metrics.record_response(target, reply.round_trip, &reply.response);
+                    }
+                    Err(e) => {
+                        format.print_error(e);
+                        metrics.record_failure(target);
+                    }
+                }
+            }
+
+            metrics.rounds += 1;
+            drop(metrics);
+
+            std::thread::sleep(interval.saturating_sub(started.elapsed()));
+        }
+    }
+
+    fn run_serve(self, listen: SocketAddr, zone_file: Option<&Path>) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let source = match zone_file {
+            Some(zone_file) => {
+                let origin = requests.inputs.domains.first().cloned();
+                let zone = dns::zonefile::read_zone_file(zone_file, origin)
+                               .map_err(|e| e.to_string())
+                               .and_then(|answers| dns::Zone::new(answers).map_err(|e| format!("{}: {}", zone_file.display(), e)));
+
+                match zone {
+                    Ok(zone) => {
+                        info!("Loaded zone {}", zone.origin());
+                        serve::Source::Zone(zone)
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return exits::OPTIONS_ERROR;
+                    }
+                }
+            }
+            None => {
+                serve::Source::Upstream(requests.transport())
+            }
+        };
+
+        let mut runtime = new_runtime();
+
+        info!("Serving queries on {}", listen);
+        match serve::run(&mut runtime, listen, source, move |served| format.print_served(served)) {
+            Ok(()) => {
+                exits::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Failed to serve queries on {}: {}", listen, e);
                 exits::NETWORK_ERROR
             }
+        }
+    }
+
+    fn run_caa_check(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let checks = run_lookups(&requests, |query, lookup| caa::check(&query.qname, |name| lookup(name, query.qtype)));
+
+        let error_exit = exits::first_failure(checks.iter().filter_map(|result| result.as_ref().err()));
+        format.print_caa_checks(checks);
+        error_exit.unwrap_or(exits::SUCCESS)
+    }
+
+    fn run_mx_check(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let checks = run_lookups(&requests, |query, lookup| mx::check(&query.qname, lookup));
+
+        let error_exit = exits::first_failure(checks.iter().filter_map(|result| result.as_ref().err()));
+        let problems = checks.iter().flatten().any(|check| ! check.problems().is_empty());
+        format.print_mx_checks(checks);
+        error_exit.unwrap_or(if problems { exits::PROBLEMS_FOUND } else { exits::SUCCESS })
+    }
+
+    fn run_fcrdns(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let checks = run_lookups(&requests, |query, lookup| fcrdns::check(&query.qname, lookup));
+
+        let error_exit = exits::first_failure(checks.iter().filter_map(|result| result.as_ref().err()));
+        let failed = checks.iter().flatten().any(|check| ! check.passed());
+        format.print_fcrdns_checks(checks);
+        error_exit.unwrap_or(if failed { exits::PROBLEMS_FOUND } else { exits::SUCCESS })
+    }
+
+    fn run_wildcard(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let probes = run_lookups(&requests, |query, lookup| {
+            let labels = wildcard::random_labels(wildcard::PROBE_COUNT);
+            wildcard::probe(&query.qname, query.qtype, &labels, lookup)
+        });
+
+        let error_exit = exits::first_failure(probes.iter().filter_map(|result| result.as_ref().err()));
+        format.print_wildcard_probes(probes);
+        error_exit.unwrap_or(exits::SUCCESS)
+    }
+
+    fn run_syscmp(self) -> i32 {
+        let Options { mut requests, format, .. } = self.options;
+
+        // The hosts file only gets read to explain any differences, so it
+        // not being there is not a problem. It gets taken out of the
+        // requests, as the DNS side has to come from DNS, not the file.
+        let hosts = match requests.hosts.take() {
+            Some(hosts)  => Some(hosts),
+            None         => hosts::HostsFile::read(hosts::DEFAULT_PATH.into()).ok().map(Arc::new),
+        };
+
+        let comparisons = run_lookups(&requests, |query, lookup| syscmp::compare(&query.qname, hosts.as_deref(), syscmp::system_addresses, lookup));
+
+        let error_exit = exits::first_failure(comparisons.iter().filter_map(|result| result.as_ref().err()));
+        let differ = comparisons.iter().flatten().any(|comparison| ! comparison.agrees());
+        format.print_system_comparisons(comparisons);
+        error_exit.unwrap_or(if differ { exits::ZONES_DIFFER } else { exits::SUCCESS })
+    }
+
+    fn run_nat64(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+        let mut runtime = new_runtime();
+
+        // Each nameserver gets asked separately, as each one could be
+        // synthesising with a different prefix, or not at all.
+        let mut discoveries = Vec::new();
+        for (request, transport, destination) in requests.generate() {
+            let result = runtime.block_on(async { transport.exchange(&request).await });
+            discoveries.push((destination.nameserver, result.map(|response| nat64::discover(&response))));
+        }
+
+        let error_exit = exits::first_failure(discoveries.iter().filter_map(|(_, result)| result.as_ref().err()));
+        let printed = format.print_nat64_prefixes(discoveries);
+        error_exit.unwrap_or(if ! printed { exits::NO_SHORT_RESULTS } else { exits::SUCCESS })
+    }
+
+    fn run_soa_check(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+        let runtime = RefCell::new(new_runtime());
+        let transport_type = requests.inputs.transport_types[0];
+
+        // The nameservers and their addresses come from the resolver, but
+        // the SOA records have to come from each nameserver itself, without
+        // recursion, so that nothing in between answers for them.
+        let mut checks = Vec::new();
+        for (request, transport, _) in requests.generate() {
+            let query = request.queries[0].clone();
+            let lookup = |name: &dns::Name, qtype| {
+                let request = requests.make_request(name, qtype, query.qclass);
+                runtime.borrow_mut().block_on(async { transport.exchange(&request).await })
+            };
+
+            let ask = |address| {
+                let mut request = requests.make_request(&query.qname, dns::qtype!(dns::record::SOA), query.qclass);
+                request.flags.recursion_desired = false;
+
+                let nameserver = requests.make_transport(transport_type, SocketAddr::new(address, 53).to_string());
+                runtime.borrow_mut().block_on(async { nameserver.exchange(&request).await })
+            };
+
+            checks.push(soa::check(&query.qname, lookup, ask));
+        }
+
+        let error_exit = exits::first_failure(checks.iter().filter_map(|check| check.as_ref().err()))
+            .or_else(|| exits::first_failure(checks.iter().flatten().flat_map(soa::SoaCheck::failures)));
+        let out_of_sync = checks.iter().flatten().any(|check| ! check.in_sync());
+        format.print_soa_checks(checks);
+        error_exit.unwrap_or(if out_of_sync { exits::ZONES_DIFFER } else { exits::SUCCESS })
+    }
+
+    fn run_delegation(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+        let mut runtime = new_runtime();
+        let transport_type = requests.inputs.transport_types[0];
+
+        let mut delegations = Vec::new();
+        let mut tracer = trace::Tracer::new(&mut runtime, &requests, transport_type);
+        for domain in &requests.inputs.domains {
+            for qclass in requests.inputs.classes.iter().copied() {
+                delegations.push(delegation::check(&mut tracer, domain, qclass));
+            }
+        }
+
+        let error_exit = exits::first_failure(delegations.iter().filter_map(|result| result.as_ref().err()));
+        let problems = delegations.iter().flatten().any(|d| ! d.problems().is_empty());
+        format.print_delegations(delegations);
+        error_exit.unwrap_or(if problems { exits::PROBLEMS_FOUND } else { exits::SUCCESS })
+    }
+
+    fn run_srv(self) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        // The targets’ addresses get looked up over the same transport as
+        // the SRV records, after them, as the targets are not known before.
+        let resolutions = run_lookups(&requests, |query, lookup| srv::resolve(&query.qname, lookup));
+
+        let error_exit = exits::first_failure(resolutions.iter().filter_map(|result| result.as_ref().err()));
+        format.print_srv_resolutions(resolutions);
+        error_exit.unwrap_or(exits::SUCCESS)
+    }
+
+    fn run_propagate(self) -> i32 {
+        let Options { requests, format, parallel, measure_time, .. } = self.options;
+        let mut runtime = new_runtime();
+        let should_show_opt = requests.edns.should_show();
+
+        let generated = requests.generate();
+        let timer = if measure_time { Some(Instant::now()) } else { None };
+        let parallel = parallel.unwrap_or_else(|| generated.len().max(1));
+        let results = send_all(&mut runtime, generated.iter().map(|(request, transport, _)| (request, &**transport)).collect(), parallel);
+        let duration = timer.map(|t| t.elapsed());
+
+        // Every query gets sent to every resolver in turn, so the results
+        // come in runs of one per resolver.
+        let mut propagations: Vec<propagate::Propagation> = Vec::new();
+        let per_query = requests.inputs.resolvers.len() * requests.inputs.transport_types.len();
+        for (index, ((request, _, destination), result)) in generated.into_iter().zip(results).enumerate() {
+            if index % per_query == 0 {
+                propagations.push(propagate::Propagation { query: request.queries[0].clone(), lookups: Vec::new() });
+            }
+
+            let result = result.map(|reply| {
+                let mut response = reply.response;
+                if ! should_show_opt {
+                    response.answers.retain(dns::Answer::is_standard);
+                    response.authorities.retain(dns::Answer::is_standard);
+                    response.additionals.retain(dns::Answer::is_standard);
+                }
+
+                response
+            });
+
+            let lookup = propagate::Lookup { nameserver: destination.nameserver, result };
+            propagations.last_mut().unwrap().lookups.push(lookup);
+        }
+
+        let error_exit = exits::first_failure(propagations.iter().flat_map(|p| &p.lookups).filter_map(|l| l.result.as_ref().err()));
+        if format.print_propagations(propagations, duration) {
+            if let Some(code) = error_exit {
+                code
+            }
+            else {
+                exits::SUCCESS
+            }
+        }
+        else {
+            exits::NO_SHORT_RESULTS
+        }
+    }
+
+    fn run_bench(self, input: &input::Input, load: bench::Load) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let lines = match read_batch(input) {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("{}", e);
+                return exits::OPTIONS_ERROR;
+            }
+        };
+
+        let mut queries = Vec::new();
+        for (_, inputs) in lines {
+            let types = if inputs.types.is_empty() { &requests.inputs.types } else { &inputs.types };
+            let classes = if inputs.classes.is_empty() { &requests.inputs.classes } else { &inputs.classes };
+
+            for qname in &inputs.domains {
+                for qtype in types.iter().copied() {
+                    for qclass in classes.iter().copied() {
+                        queries.push(dns::Query { qname: qname.clone(), qtype, qclass });
+                    }
+                }
+            }
+        }
+
+        if queries.is_empty() {
+            eprintln!("No queries to send");
+            return exits::OPTIONS_ERROR;
+        }
+
+        let mut runtime = new_runtime();
+        let transport = requests.transport();
+        let report = bench::run(&mut runtime, &*transport, &requests, &queries, load);
+
+        // Only the number of failures gets kept, so a run where every
+        // failure was a timeout is the only one with a more specific code.
+        let error_exit = if report.errors > 0        { Some(exits::NETWORK_ERROR) }
+                    else if report.timeouts > 0  { Some(exits::TIMED_OUT) }
+                                            else { None };
+        format.print_bench(&report);
+
+        if let Some(code) = error_exit {
+            code
+        }
+        else {
+            exits::SUCCESS
+        }
+    }
+
+    fn run_ping(self, count: u32) -> i32 {
+        let Options { requests, format, .. } = self.options;
+        let mut runtime = new_runtime();
+        let generated = requests.generate();
+
+        let mut exchanges = Vec::new();
+        let mut failed = 0;
+        let mut error_exit = None;
+        for sequence in 1 ..= count {
+            let started = Instant::now();
+
+            // Each query gets a new transaction ID, the same as ping gives
+            // each echo request a new sequence number.
+            for (request, transport, destination) in &generated {
+                let query = &request.queries[0];
+                let request = requests.make_request(&query.qname, query.qtype, query.qclass);
+                let outcome = ping::send(&mut runtime, &**transport, &request);
+
+                match &outcome {
+                    ping::Outcome::Response(reply) => {
+                        let dns_transport::Reply { round_trip, attempts, protocol, connection, bytes, .. } = &**reply;
+                        exchanges.push(output::Exchange { destination: destination.clone(), round_trip: *round_trip, attempts: *attempts, protocol: *protocol, connection: connection.clone(), edns: None, bytes: bytes.clone() });
+                    }
+                    ping::Outcome::Timeout => {
+                        error_exit = error_exit.or(Some(exits::TIMED_OUT));
+                        failed += 1;
+                    }
+                    ping::Outcome::Error(e) => {
+                        error_exit = error_exit.or_else(|| Some(exits::query_error(e)));
+                        failed += 1;
+                    }
+                }
+
+                format.print_ping(sequence, destination, outcome);
+            }
+
+            if sequence < count {
+                std::thread::sleep(ping::INTERVAL.saturating_sub(started.elapsed()));
+            }
+        }
+
+        let stats = stats::Stats::summarise(&exchanges.iter().collect::<Vec<_>>(), failed);
+        format.print_stats(&stats);
+
+        if let Some(code) = error_exit {
+            code
+        }
+        else {
+            exits::SUCCESS
+        }
+    }
+
+    fn run_decode(self, source: &input::Input) -> i32 {
+        let Options { requests, format, sections, sort, .. } = self.options;
+
+        let bytes = match source.read_bytes() {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("Failed to read message: {}", e);
+                return exits::OPTIONS_ERROR;
+            }
+        };
+
+        // A wire log holds every response from a session, whereas anything
+        // else has to be a single message.
+        let messages = wirelog::read_responses(&bytes).unwrap_or_else(|| vec![ bytes ]);
+
+        let mut responses = Vec::new();
+        for message in messages {
+            let mut response = match dns::Response::from_bytes(&message) {
+                Ok(r) => r,
+                Err(e) => {
+                    let error = dns_transport::Error::WireError(e);
+                    let code = exits::query_error(&error);
+                    format.print_error(error);
+                    return code;
+                }
+            };
+
+            if ! requests.edns.should_show() {
+                response.answers.retain(dns::Answer::is_standard);
+                response.authorities.retain(dns::Answer::is_standard);
+                response.additionals.retain(dns::Answer::is_standard);
+            }
+
+            if sort {
+                sort::sort_response(&mut response);
+            }
+
+            responses.push(response);
+        }
+
+        if format.print(output::Printable { sections, .. output::Printable::responses(responses) }, None) {
+            exits::SUCCESS
+        }
+        else {
+            exits::NO_SHORT_RESULTS
+        }
+    }
+
+    fn run_compare(self, left_file: &Path, right_file: &Path, diff_options: dns::diff::DiffOptions) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let mut responses = Vec::new();
+        for path in [ left_file, right_file ] {
+            let bytes = match std::fs::read(path) {
+                Ok(b) => b,
+                Err(e) => {
+                    eprintln!("Failed to read message from {}: {}", path.display(), e);
+                    return exits::OPTIONS_ERROR;
+                }
+            };
+
+            let mut response = match dns::Response::from_bytes(&bytes) {
+                Ok(r) => r,
+                Err(e) => {
+                    let error = dns_transport::Error::WireError(e);
+                    let code = exits::query_error(&error);
+                    format.print_error(error);
+                    return code;
+                }
+            };
+
+            if ! requests.edns.should_show() {
+                response.answers.retain(dns::Answer::is_standard);
+                response.authorities.retain(dns::Answer::is_standard);
+                response.additionals.retain(dns::Answer::is_standard);
+            }
+
+            responses.push(response);
+        }
+
+        let diff = dns::diff::diff(&responses[0], &responses[1], diff_options);
+        let differ = ! diff.is_empty();
+        format.print_response_diff(&diff);
+
+        if differ {
+            exits::ZONES_DIFFER
+        }
+        else {
+            exits::SUCCESS
+        }
+    }
+
+    fn run_pcap(self, capture_file: &Path) -> i32 {
+        let Options { requests, format, .. } = self.options;
+
+        let messages = match std::fs::read(capture_file) {
+            Ok(bytes) => pcap::dns_messages(&bytes),
+            Err(e) => {
+                eprintln!("Failed to read capture: {}", e);
+                return exits::OPTIONS_ERROR;
+            }
+        };
+
+        let messages = match messages {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{}", e);
+                return exits::OPTIONS_ERROR;
+            }
+        };
+
+        // Messages that fail to parse get reported, but the rest of the
+        // capture still gets printed.
+        let mut errored = false;
+        let mut captured = Vec::new();
+        for message in messages {
+            // Captures can be big, so OPT records that are not going to be
+            // shown do not get decoded at all.
+            let show_edns = requests.edns.should_show();
+            let parsed = dns::Response::parse_ref(&message.bytes)
+                             .and_then(|r| r.to_response_where(|answer| show_edns || ! answer.is_pseudo()));
+
+            match parsed {
+                Ok(response) => {
+                    captured.push((message, response));
+                }
+                Err(e) => {
+                    format.print_error(dns_transport::Error::WireError(e));
+                    errored = true;
+                }
+            }
+        }
+
+        if format.print_captured(captured) {
+            if errored {
+                exits::PARSE_ERROR
+            }
             else {
                 exits::SUCCESS
             }
@@ -154,6 +1075,163 @@ impl Dog {
             exits::NO_SHORT_RESULTS
         }
     }
+
+    fn run_diff(self, zone_file: &Path) -> i32 {
+        let Options { requests, format, measure_time, .. } = self.options;
+        let origin = requests.inputs.domains.first().cloned();
+
+        let answers = match dns::zonefile::read_zone_file(zone_file, origin) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{}", e);
+                return exits::OPTIONS_ERROR;
+            }
+        };
+
+        let mut runtime = new_runtime();
+        let transport = requests.transport();
+
+        let mut diffs = Vec::new();
+        let timer = if measure_time { Some(Instant::now()) } else { None };
+
+        let mut error_exit = None;
+        for set in diff::record_sets(answers) {
+            let request = requests.make_request(&set.qname, set.qtype, set.qclass);
+            let result = runtime.block_on(async { transport.exchange(&request).await });
+
+            match result {
+                Ok(response) => {
+                    diffs.push(diff::compare(&set, &response));
+                }
+                Err(e) => {
+                    error_exit = error_exit.or_else(|| Some(exits::query_error(&e)));
+                    format.print_error(e);
+                }
+            }
+        }
+
+        let duration = timer.map(|t| t.elapsed());
+        let differs = diffs.iter().any(|d| ! d.is_empty());
+        format.print_diffs(diffs, duration);
+
+        if let Some(code) = error_exit {
+            code
+        }
+        else if differs {
+            exits::ZONES_DIFFER
+        }
+        else {
+            exits::SUCCESS
+        }
+    }
+}
+
+
+/// The most extra queries to send while following a CNAME chain.
+const MAX_CHASES: usize = 8;
+
+/// Creates the runtime that queries get sent on.
+fn new_runtime() -> dns_transport::Runtime {
+    dns_transport::Runtime::new().expect("Failed to create runtime")
+}
+
+/// Runs one of the checkers that look names up through the resolver once
+/// for each domain, giving it a function that looks names up over the
+/// same transport as the domain’s own query, and returns what it found for
+/// each domain. The names get looked up one at a time, as each lookup can
+/// depend on the ones before it.
+fn run_lookups<T, E>(requests: &requests::RequestGenerator, mut check: impl FnMut(&dns::Query, &mut dyn FnMut(&dns::Name, dns::TypeInt) -> Result<dns::Response, dns_transport::Error>) -> Result<T, E>) -> Vec<Result<T, E>> {
+    let mut runtime = new_runtime();
+
+    requests.generate().into_iter().map(|(request, transport, _)| {
+        let query = request.queries[0].clone();
+        let mut lookup = |name: &dns::Name, qtype| {
+            let request = requests.make_request(name, qtype, query.qclass);
+            runtime.block_on(async { transport.exchange(&request).await })
+        };
+
+        check(&query, &mut lookup)
+    }).collect()
+}
+
+/// Sends every request down its transport, with up to `parallel` of them
+/// waiting for a response at once, and returns each result in the same
+/// order as the requests.
+///
+/// Requests going down the same transport get handed to it in batches, so
+/// the TCP and TLS transports can send a whole batch down one connection.
+fn send_all(runtime: &mut dns_transport::Runtime, requests: Vec<(&dns::Request, &dyn dns_transport::Transport)>, parallel: usize) -> Vec<Result<dns_transport::Reply, dns_transport::Error>> {
+    let mut batches: Vec<(&dyn dns_transport::Transport, Vec<usize>)> = Vec::new();
+    for (index, (_, transport)) in requests.iter().enumerate() {
+        match batches.iter_mut().find(|(t, batch)| std::ptr::addr_eq(*t, *transport) && batch.len() < parallel) {
+            Some((_, batch))  => batch.push(index),
+            None              => batches.push((*transport, vec![ index ])),
+        }
+    }
+
+    let largest = batches.iter().map(|(_, batch)| batch.len()).max().unwrap_or(1);
+    let sends = batches.iter().map(|(transport, batch)| {
+        let batch = batch.iter().map(|index| requests[*index].0.clone()).collect::<Vec<_>>();
+        async move { transport.send_all(&batch).await }
+    });
+
+    let replies: Vec<_> = runtime.block_on(stream::iter(sends).buffered((parallel / largest).max(1)).collect());
+
+    let mut results = requests.iter().map(|_| None).collect::<Vec<_>>();
+    for ((_, batch), replies) in batches.iter().zip(replies) {
+        for (index, reply) in batch.iter().zip(replies) {
+            results[*index] = Some(reply);
+        }
+    }
+
+    results.into_iter().map(|result| result.expect("Request went unsent")).collect()
+}
+
+/// Reads a file of queries, returning the inputs on each line that has
+/// any, along with the line to label them with in the output.
+fn read_batch(input: &input::Input) -> Result<Vec<(String, requests::Inputs)>, String> {
+    let text = input.read_text().map_err(|e| format!("Failed to read queries: {}", e))?;
+
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        match requests::Inputs::from_batch_line(line) {
+            Ok(Some(inputs))  => lines.push((line.split_whitespace().collect::<Vec<_>>().join(" "), inputs)),
+            Ok(None)          => {}
+            Err(e)            => return Err(format!("Line {} of queries: {}", index + 1, e)),
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Follows the CNAME chain in the response’s answer section, sending
+/// further queries over the same transport for any target whose records
+/// were not included, and adding the answers to the response.
+fn chase_cnames(runtime: &mut dns_transport::Runtime, requests: &requests::RequestGenerator, transport: &dyn dns_transport::Transport, response: &mut dns::Response) -> Result<(), dns_transport::Error> {
+    let query = match response.queries.first() {
+        Some(q)  => q.clone(),
+        None     => return Ok(()),
+    };
+
+    for _ in 0 .. MAX_CHASES {
+        let target = match chain::Chain::find(&response.answers, &query.qname) {
+            Some(ref chain) if ! chain.is_complete() => chain.last_name().clone(),
+            _ => return Ok(()),
+        };
+
+        info!("Chasing CNAME target {:?}", target);
+        let request = requests.make_request(&target, query.qtype, query.qclass);
+        let extra = runtime.block_on(async { transport.exchange(&request).await })?;
+
+        if extra.answers.is_empty() {
+            return Ok(());
+        }
+
+        response.answers.extend(extra.answers);
+    }
+
+    warn!("Gave up chasing CNAME chain for {:?}", query.qname);
+    Ok(())
 }
 
 
@@ -167,9 +1245,62 @@ mod exits {
     pub const NETWORK_ERROR: i32 = 1;
 
     /// Exit code for when there is no result from the server when running in
-    /// short mode. This can be any received server error, not just NXDOMAIN.
+    /// short mode, even though it did not respond with an error.
     pub const NO_SHORT_RESULTS: i32 = 2;
 
     /// Exit code for when the command-line options are invalid.
     pub const OPTIONS_ERROR: i32 = 3;
+
+    /// Exit code for when things that were compared do not match: a zone
+    /// file and the records on the nameserver, the versions of a zone its
+    /// nameservers are serving, or two messages.
+    pub const ZONES_DIFFER: i32 = 4;
+
+    /// Exit code for when the responses did not match what the user
+    /// expected them to contain.
+    pub const EXPECTATION_FAILED: i32 = 5;
+
+    /// Exit code for when checking how a domain is set up, such as its
+    /// mail exchanges or its delegation, found problems with it.
+    pub const PROBLEMS_FOUND: i32 = 6;
+
+    /// Exit code for when nothing was listening on a nameserver’s UDP port.
+    pub const PORT_UNREACHABLE: i32 = 7;
+
+    /// Exit code for when there was no route to a nameserver.
+    pub const NETWORK_UNREACHABLE: i32 = 8;
+
+    /// Exit code for when a nameserver took too long to connect to.
+    pub const TIMED_OUT: i32 = 9;
+
+    /// Exit code for when a nameserver refused a TCP connection.
+    pub const CONNECTION_REFUSED: i32 = 10;
+
+    /// Exit code for when a nameserver responded with NXDOMAIN, saying the
+    /// domain does not exist.
+    pub const NXDOMAIN: i32 = 11;
+
+    /// Exit code for when a nameserver responded with any other error,
+    /// such as SERVFAIL or REFUSED.
+    pub const SERVER_ERROR: i32 = 12;
+
+    /// Exit code for when a response could not be parsed.
+    pub const PARSE_ERROR: i32 = 13;
+
+    /// Picks the exit code for a query that failed, from its kind.
+    pub fn query_error(error: &dns_transport::Error) -> i32 {
+        crate::failure::FailureKind::of(error).exit_code()
+    }
+
+    /// Picks the exit code for a response with an error code.
+    pub fn error_code(rcode: dns::ErrorCode) -> i32 {
+        crate::failure::FailureKind::of_rcode(rcode).exit_code()
+    }
+
+    /// Picks the exit code for the first of the failures, if there were
+    /// any, so a command that failed to check several things exits with
+    /// the code for whatever went wrong first.
+    pub fn first_failure<'a, F: crate::failure::Failure + 'a>(failures: impl IntoIterator<Item=&'a F>) -> Option<i32> {
+        failures.into_iter().next().map(crate::failure::Failure::exit_code)
+    }
 }
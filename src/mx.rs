@@ -0,0 +1,239 @@
+//! Checking that a domain can receive mail, by looking up its MX records
+//! and the addresses of each mail exchange, and pointing out the common
+//! mistakes in them.
+
+use std::fmt;
+use std::net::IpAddr;
+
+use dns::{qtype, Answer, Name, Response, TypeInt};
+use dns::record::{A, AAAA, CNAME, MX, Record};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// A domain’s mail exchanges, and the addresses they were found at.
+#[derive(PartialEq, Debug)]
+pub struct MxCheck {
+
+    /// The domain that mail would be sent to.
+    pub domain: Name,
+
+    /// The mail exchanges, sorted by preference, leaving out any null MX
+    /// record.
+    pub exchanges: Vec<Exchange>,
+
+    /// Whether the domain has a null MX record, saying that it does not
+    /// accept mail at all (RFC 7505).
+    pub null_mx: bool,
+}
+
+/// One of the servers that accepts mail for a domain.
+#[derive(PartialEq, Debug)]
+pub struct Exchange {
+
+    /// Exchanges with lower preferences get tried first.
+    pub preference: u16,
+
+    /// The name of the server, as given in the MX record.
+    pub name: Name,
+
+    /// The name the server’s name is an alias for, if it is a CNAME.
+    pub cname: Option<Name>,
+
+    /// The server’s IPv4 and IPv6 addresses, which is empty if it has none.
+    pub addresses: Vec<IpAddr>,
+}
+
+/// A mistake in a domain’s mail setup that stops mail from reaching it,
+/// or makes it less likely to.
+#[derive(PartialEq, Debug)]
+pub enum Problem {
+
+    /// The domain has a null MX record alongside other MX records, which
+    /// RFC 7505 forbids.
+    NullMxWithOthers,
+
+    /// A mail exchange has no A or AAAA records, so mail cannot be
+    /// delivered to it.
+    NoAddresses(Name),
+
+    /// A mail exchange’s name is an alias, which RFC 2181 §10.3 forbids.
+    CnameExchange(Name, Name),
+}
+
+
+/// Looks up the mail exchanges of the given domain, calling the lookup
+/// function to query for its MX records, and then for the A and AAAA
+/// records of each exchange.
+pub fn check(domain: &Name, mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>) -> Result<MxCheck, LookupError> {
+    info!("Looking up MX records at {:?}", domain);
+    let response = lookup(domain, qtype!(MX)).map_err(|e| LookupError::Transport(domain.clone(), e))?;
+    check_rcode(domain, &response)?;
+
+    let records = response.answers.into_iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { record: Record::MX(mx), .. }  => Some(mx),
+            _                                                 => None,
+        }
+    }).collect::<Vec<MX>>();
+
+    let null_mx = records.iter().any(|mx| mx.exchange.is_root());
+
+    let mut exchanges = Vec::new();
+    for mx in records.into_iter().filter(|mx| ! mx.exchange.is_root()) {
+        let mut cname = None;
+        let mut addresses = Vec::new();
+
+        for qtype in [ qtype!(A), qtype!(AAAA) ] {
+            info!("Looking up addresses of {:?}", mx.exchange);
+            let response = lookup(&mx.exchange, qtype).map_err(|e| LookupError::Transport(mx.exchange.clone(), e))?;
+            check_rcode(&mx.exchange, &response)?;
+
+            for answer in &response.answers {
+                match answer {
+                    Answer::Standard { record: Record::A(A { address }), .. }     => addresses.push(IpAddr::V4(*address)),
+                    Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => addresses.push(IpAddr::V6(*address)),
+                    Answer::Standard { qname, record: Record::CNAME(CNAME { domain }), .. } if *qname == mx.exchange => {
+                        cname = Some(domain.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        exchanges.push(Exchange { preference: mx.preference, name: mx.exchange, cname, addresses });
+    }
+
+    exchanges.sort_by_key(|exchange| exchange.preference);
+    Ok(MxCheck { domain: domain.clone(), exchanges, null_mx })
+}
+
+
+impl MxCheck {
+
+    /// Whether mail is delivered to the domain’s own addresses, as it has
+    /// no MX records at all (RFC 5321 §5.1).
+    pub fn is_implicit(&self) -> bool {
+        self.exchanges.is_empty() && ! self.null_mx
+    }
+
+    /// Lists the problems with the domain’s mail exchanges.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        if self.null_mx && ! self.exchanges.is_empty() {
+            problems.push(Problem::NullMxWithOthers);
+        }
+
+        for exchange in &self.exchanges {
+            if let Some(cname) = &exchange.cname {
+                problems.push(Problem::CnameExchange(exchange.name.clone(), cname.clone()));
+            }
+
+            if exchange.addresses.is_empty() {
+                problems.push(Problem::NoAddresses(exchange.name.clone()));
+            }
+        }
+
+        problems
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NullMxWithOthers         => write!(f, "a null MX record must be the only MX record"),
+            Self::NoAddresses(name)        => write!(f, "the mail exchange {} has no addresses", name),
+            Self::CnameExchange(name, to)  => write!(f, "the mail exchange {} is a CNAME to {}, rather than the server’s own name", name, to),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::ErrorCode;
+
+    fn mx(preference: u16, exchange: &str) -> Record {
+        Record::MX(MX { preference, exchange: name(exchange) })
+    }
+
+    /// Answers MX queries with the given records, and address queries
+    /// for the exchanges under `lookup.dog`, one of which is an alias.
+    fn lookup(records: Vec<Record>) -> impl FnMut(&Name, TypeInt) -> Result<Response, TransportError> {
+        move |qname, qtype| {
+            if qtype == qtype!(MX) {
+                let answers = records.iter().cloned().map(|r| answer(qname.as_str(), r)).collect();
+                return Ok(response(qname, qtype, None, answers));
+            }
+
+            let answers = match (qname.as_str(), qtype) {
+                ("mx1.lookup.dog.", t) if t == qtype!(A)     => vec![ answer("mx1.lookup.dog.", Record::A(A { address: "192.0.2.1".parse().unwrap() })) ],
+                ("mx1.lookup.dog.", t) if t == qtype!(AAAA)  => vec![ answer("mx1.lookup.dog.", Record::AAAA(AAAA { address: "2001:db8::1".parse().unwrap() })) ],
+                ("mail.lookup.dog.", t) if t == qtype!(A)    => vec![
+                    answer("mail.lookup.dog.", Record::CNAME(CNAME { domain: name("mx1.lookup.dog.") })),
+                    answer("mx1.lookup.dog.", Record::A(A { address: "192.0.2.1".parse().unwrap() })),
+                ],
+                _ => Vec::new(),
+            };
+
+            Ok(response(qname, qtype, None, answers))
+        }
+    }
+
+    #[test]
+    fn exchanges_and_addresses() {
+        let check = check(&name("lookup.dog."), lookup(vec![ mx(20, "mx2.lookup.dog."), mx(10, "mx1.lookup.dog.") ])).unwrap();
+
+        assert_eq!(check.exchanges[0].name, name("mx1.lookup.dog."));
+        assert_eq!(check.exchanges[0].addresses, vec![ "192.0.2.1".parse::<IpAddr>().unwrap(), "2001:db8::1".parse().unwrap() ]);
+        assert_eq!(check.exchanges[1].name, name("mx2.lookup.dog."));
+        assert_eq!(check.problems(), vec![ Problem::NoAddresses(name("mx2.lookup.dog.")) ]);
+    }
+
+    #[test]
+    fn null_mx() {
+        let check = check(&name("lookup.dog."), lookup(vec![ mx(0, ".") ])).unwrap();
+
+        assert!(check.null_mx);
+        assert!(! check.is_implicit());
+        assert_eq!(check.exchanges, Vec::new());
+        assert_eq!(check.problems(), Vec::new());
+    }
+
+    #[test]
+    fn null_mx_with_others() {
+        let check = check(&name("lookup.dog."), lookup(vec![ mx(0, "."), mx(10, "mx1.lookup.dog.") ])).unwrap();
+        assert_eq!(check.problems(), vec![ Problem::NullMxWithOthers ]);
+    }
+
+    #[test]
+    fn cname_exchange() {
+        let check = check(&name("lookup.dog."), lookup(vec![ mx(10, "mail.lookup.dog.") ])).unwrap();
+
+        assert_eq!(check.exchanges[0].cname, Some(name("mx1.lookup.dog.")));
+        assert_eq!(check.problems(), vec![ Problem::CnameExchange(name("mail.lookup.dog."), name("mx1.lookup.dog.")) ]);
+    }
+
+    #[test]
+    fn no_records() {
+        let check = check(&name("lookup.dog."), lookup(Vec::new())).unwrap();
+        assert!(check.is_implicit());
+        assert_eq!(check.problems(), Vec::new());
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = check(&name("lookup.dog."), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::ServerFailure), Vec::new()))
+        });
+
+        assert!(matches!(result, Err(LookupError::Rcode(_, ErrorCode::ServerFailure))));
+    }
+}
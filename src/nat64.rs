@@ -0,0 +1,240 @@
+//! Working with the IPv6 prefixes that NAT64 gateways translate to and
+//! from IPv4, which DNS64 resolvers embed IPv4 addresses in to give
+//! hosts on IPv6-only networks something to connect to (RFC 6147).
+//!
+//! A network’s prefix can be discovered by asking its resolver for the
+//! AAAA records of `ipv4only.arpa`, a name that only has A records, and
+//! finding where the well-known IPv4 addresses ended up in the synthesised
+//! addresses (RFC 7050).
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use dns::{Answer, Name, Response};
+use dns::record::{A, AAAA, Record};
+
+
+/// The name that gets asked for to discover the prefix.
+pub const IPV4ONLY_ARPA: &str = "ipv4only.arpa.";
+
+/// The addresses `ipv4only.arpa` has A records for (RFC 7050 §8.2).
+const WELL_KNOWN_ADDRESSES: [Ipv4Addr; 2] = [ Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171) ];
+
+/// The lengths a NAT64 prefix is allowed to have (RFC 6052 §2.2).
+const PREFIX_LENGTHS: [u8; 6] = [ 96, 64, 56, 48, 40, 32 ];
+
+
+/// An IPv6 prefix that IPv4 addresses get embedded in.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Prefix {
+
+    /// The prefix’s address, with everything after the prefix zeroed.
+    pub address: Ipv6Addr,
+
+    /// How many bits long the prefix is.
+    pub length: u8,
+}
+
+impl Prefix {
+
+    /// The well-known prefix, `64:ff9b::/96`, which is used unless the
+    /// network has a prefix of its own (RFC 6052 §2.1).
+    pub const WELL_KNOWN: Self = Self { address: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), length: 96 };
+
+    /// Embeds an IPv4 address in this prefix, skipping over bits 64 to 71,
+    /// which have to be zero (RFC 6052 §2.2).
+    pub fn embed(self, ipv4: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.address.octets();
+        let prefix_octets = usize::from(self.length / 8);
+
+        let positions = (prefix_octets ..).filter(|i| *i != 8);
+        for (position, octet) in positions.zip(ipv4.octets().iter()) {
+            octets[position] = *octet;
+        }
+
+        Ipv6Addr::from(octets)
+    }
+
+    /// Takes the IPv4 address back out of an IPv6 address that has one
+    /// embedded in a prefix of this length.
+    pub fn extract(self, ipv6: Ipv6Addr) -> Ipv4Addr {
+        let octets = ipv6.octets();
+        let prefix_octets = usize::from(self.length / 8);
+
+        let mut ipv4 = [0; 4];
+        let positions = (prefix_octets ..).filter(|i| *i != 8);
+        for (octet, position) in ipv4.iter_mut().zip(positions) {
+            *octet = octets[position];
+        }
+
+        Ipv4Addr::from(ipv4)
+    }
+
+    /// Returns the prefix the given address is in, with the given length,
+    /// by zeroing every bit after it.
+    fn of(ipv6: Ipv6Addr, length: u8) -> Self {
+        let mut octets = ipv6.octets();
+        for octet in &mut octets[usize::from(length / 8) ..] {
+            *octet = 0;
+        }
+
+        Self { address: Ipv6Addr::from(octets), length }
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.length)
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = ();
+
+    /// Parses a prefix such as `64:ff9b::/96`. Only the lengths that IPv4
+    /// addresses can be embedded after are accepted, and there must not be
+    /// anything set after the prefix.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (address, length) = input.split_once('/').ok_or(())?;
+        let address = address.parse::<Ipv6Addr>().map_err(|_| ())?;
+        let length = length.parse::<u8>().map_err(|_| ())?;
+
+        if ! PREFIX_LENGTHS.contains(&length) || Self::of(address, length).address != address {
+            return Err(());
+        }
+
+        Ok(Self { address, length })
+    }
+}
+
+
+/// Finds the NAT64 prefixes in a response to a query for the AAAA records
+/// of `ipv4only.arpa`, by looking for the well-known IPv4 addresses in
+/// each synthesised address, at each of the positions they could be
+/// embedded at. Prefixes are returned in the order they were found, with
+/// no duplicates.
+pub fn discover(response: &Response) -> Vec<Prefix> {
+    let mut prefixes = Vec::new();
+
+    for answer in &response.answers {
+        let ipv6 = match answer {
+            Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => *address,
+            _                                                                => continue,
+        };
+
+        let found = PREFIX_LENGTHS.iter()
+                                  .map(|length| Prefix::of(ipv6, *length))
+                                  .find(|prefix| WELL_KNOWN_ADDRESSES.contains(&prefix.extract(ipv6)));
+
+        if let Some(prefix) = found.filter(|p| ! prefixes.contains(p)) {
+            prefixes.push(prefix);
+        }
+    }
+
+    prefixes
+}
+
+/// Replaces every A record in the answer section of a response with the
+/// AAAA record that a DNS64 resolver using the given prefix would have
+/// synthesised from it, keeping its name and TTL.
+pub fn synthesise(response: &mut Response, prefix: Prefix) {
+    for answer in &mut response.answers {
+        if let Answer::Standard { record, .. } = answer {
+            if let Record::A(A { address }) = record {
+                *record = Record::AAAA(AAAA { address: prefix.embed(*address) });
+            }
+        }
+    }
+}
+
+/// The name to query to discover the prefix.
+pub fn ipv4only_arpa() -> Name {
+    IPV4ONLY_ARPA.parse().expect("Invalid ipv4only.arpa name")
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::{Flags, QClass};
+
+    fn prefix(input: &str) -> Prefix {
+        input.parse().unwrap()
+    }
+
+    fn response(records: Vec<Record>) -> Response {
+        Response {
+            transaction_id: 0,
+            flags: Flags::query(),
+            queries: Vec::new(),
+            answers: records.into_iter().map(|record| Answer::Standard { qname: ipv4only_arpa(), qclass: QClass::IN, ttl: 300, record }).collect(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    fn aaaa(input: &str) -> Record {
+        Record::AAAA(AAAA { address: input.parse().unwrap() })
+    }
+
+    // These are the examples from RFC 6052 §2.4.
+
+    #[test]
+    fn embed_every_length() {
+        let ipv4 = Ipv4Addr::new(192, 0, 2, 33);
+        assert_eq!(prefix("2001:db8::/32").embed(ipv4),          "2001:db8:c000:221::".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(prefix("2001:db8:100::/40").embed(ipv4),      "2001:db8:1c0:2:21::".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(prefix("2001:db8:122::/48").embed(ipv4),      "2001:db8:122:c000:2:2100::".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(prefix("2001:db8:122:300::/56").embed(ipv4),  "2001:db8:122:3c0:0:221::".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(prefix("2001:db8:122:344::/64").embed(ipv4),  "2001:db8:122:344:c0:2:2100:0".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(prefix("2001:db8:122:344::/96").embed(ipv4),  "2001:db8:122:344::192.0.2.33".parse::<Ipv6Addr>().unwrap());
+    }
+
+    #[test]
+    fn extract_round_trip() {
+        let ipv4 = Ipv4Addr::new(192, 0, 2, 33);
+        let prefix = prefix("2001:db8:122::/48");
+        assert_eq!(prefix.extract(prefix.embed(ipv4)), ipv4);
+    }
+
+    #[test]
+    fn well_known() {
+        assert_eq!(Prefix::WELL_KNOWN.embed(Ipv4Addr::new(192, 0, 2, 1)), "64:ff9b::c000:201".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(Prefix::WELL_KNOWN.to_string(), "64:ff9b::/96");
+    }
+
+    #[test]
+    fn invalid_prefixes() {
+        assert_eq!("64:ff9b::/80".parse::<Prefix>(), Err(()));
+        assert_eq!("64:ff9b::1/96".parse::<Prefix>(), Err(()));
+        assert_eq!("192.0.2.0/24".parse::<Prefix>(), Err(()));
+        assert_eq!("64:ff9b::".parse::<Prefix>(), Err(()));
+    }
+
+    #[test]
+    fn discover_well_known() {
+        let response = response(vec![ aaaa("64:ff9b::c000:aa"), aaaa("64:ff9b::c000:ab") ]);
+        assert_eq!(discover(&response), vec![ Prefix::WELL_KNOWN ]);
+    }
+
+    #[test]
+    fn discover_shorter_prefix() {
+        let response = response(vec![ aaaa("2001:db8:122:c000:0:aa00::") ]);
+        assert_eq!(discover(&response), vec![ prefix("2001:db8:122::/48") ]);
+    }
+
+    #[test]
+    fn discover_nothing() {
+        assert_eq!(discover(&response(Vec::new())), Vec::new());
+        assert_eq!(discover(&response(vec![ aaaa("2001:db8::1") ])), Vec::new());
+    }
+
+    #[test]
+    fn synthesised_records() {
+        let mut response = response(vec![ Record::A(A { address: Ipv4Addr::new(192, 0, 2, 1) }) ]);
+        synthesise(&mut response, Prefix::WELL_KNOWN);
+        assert!(matches!(&response.answers[0], Answer::Standard { record, ttl: 300, .. } if *record == aaaa("64:ff9b::c000:201")));
+    }
+}
@@ -1,16 +1,49 @@
+use std::env;
 use std::ffi::OsStr;
 use std::fmt;
+use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 use log::*;
 
 use dns::{QClass, find_qtype_number, qtype};
-use dns::record::{A, find_other_qtype_number};
+use dns::diff::DiffOptions;
+use dns::record::{A, AAAA, CAA, MX, NS, PTR, SRV, TXT, find_other_qtype_number};
+use dns_transport::{Bootstrap, TlsSettings, DEFAULT_TIMEOUT, ClientIdentity, IdentityError, parse_pin, certificates_from_pem};
 
+use crate::cache::Caches;
+use crate::completions::Shell;
 use crate::connect::TransportType;
-use crate::output::{OutputFormat, UseColours, TextFormat};
+use crate::expect::{Expectations, parse_rcode};
+use crate::hosts::{self, HostsFile};
+use crate::locale;
+use crate::output::{OutputFormat, Sections, UseColours, TextFormat, TtlFormat};
+use crate::ping;
+use crate::presets;
+use crate::probe::{self, parse_listen_address};
+use crate::propagate;
 use crate::requests::{RequestGenerator, Inputs, ProtocolTweaks, UseEDNS};
 use crate::resolve::Resolver;
-use crate::txid::TxidGenerator;
+use crate::serve;
+use crate::srv;
+use crate::txid::{Txids, TxidStrategy};
+use crate::bench::Load;
+use crate::input::Input;
+use crate::nat64::{self, Prefix};
+use crate::validate::TrustAnchors;
+use crate::watch::parse_interval;
+use crate::wirelog::WireLog;
+
+
+/// The commands that can be given as the first argument, in place of a
+/// domain to query.
+pub const COMMANDS: &[&str] = &[
+    "diff", "decode", "pcap", "compare", "propagate", "caa-check", "mx-check", "fcrdns", "wildcard",
+    "syscmp", "nat64", "soa-check", "delegation", "srv", "bench", "ping", "serve",
+];
 
 
 /// The command-line options used when running dog.
@@ -23,24 +56,212 @@ pub struct Options {
     /// Whether to display the time taken after every query.
     pub measure_time: bool,
 
+    /// Whether to print a summary of how long the queries took, and how
+    /// they were sent, after the responses.
+    pub stats: bool,
+
+    /// Whether to explain the SPF, DKIM, and DMARC policies in any TXT
+    /// records among the answers, after the responses.
+    pub interpret: bool,
+
+    /// What the responses have to contain for dog to exit successfully.
+    pub expectations: Expectations,
+
+    /// Whether to resolve queries iteratively from the root servers,
+    /// displaying each delegation step along the way.
+    pub trace: bool,
+
+    /// Whether to send the queries straight to an authoritative nameserver
+    /// for the zone each name is in, found by asking the resolver for the
+    /// zone’s NS records, rather than to the resolver itself.
+    pub authoritative: bool,
+
+    /// The NAT64 prefix to embed the addresses in any A records among the
+    /// answers in, to show the AAAA records a DNS64 resolver would turn
+    /// them into, if the user asked for that.
+    pub dns64_prefix: Option<Prefix>,
+
+    /// Whether to follow CNAME chains in the answer section, querying for
+    /// any targets that were not included, and display them as chains.
+    pub chase: bool,
+
+    /// Whether to validate the DNSSEC signatures over the answers, starting
+    /// from the root trust anchor.
+    pub validate: bool,
+
+    /// Where to load the DNSSEC trust anchors from when validating.
+    pub trust_anchors: TrustAnchors,
+
+    /// Something to do other than printing the responses to the queries,
+    /// if the first argument named a command.
+    pub command: Option<Command>,
+
+    /// How many queries to have waiting for a response at once, if the
+    /// user has limited it.
+    pub parallel: Option<usize>,
+
     /// How to format the output data.
     pub format: OutputFormat,
+
+    /// Which sections of the responses to print.
+    pub sections: Sections,
+
+    /// Whether to sort the records in each section of the responses, and
+    /// remove any duplicates, so the output is the same between runs.
+    pub sort: bool,
+}
+
+/// The commands that can be given as the first argument, each of which
+/// does something other than printing the responses to the queries on
+/// the command line.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Command {
+
+    /// Compare the zone file at the given path against the records the
+    /// nameserver serves.
+    Diff(PathBuf),
+
+    /// Decode and print a captured message, without sending any queries.
+    Decode(Input),
+
+    /// Find every DNS message in the packet capture file at the given path
+    /// and print them, without sending any queries.
+    Pcap(PathBuf),
+
+    /// Decode the captured messages at the two given paths and print how
+    /// the second differs from the first, without sending any queries.
+    Compare(PathBuf, PathBuf, DiffOptions),
+
+    /// Send the queries listed in a file, one per line, printing the
+    /// responses to each line’s queries under that line. This one is
+    /// chosen with the `--file` option rather than named.
+    Batch(Input),
+
+    /// Send the queries again and again, waiting the given interval
+    /// between rounds, and highlight how the answers change. This one is
+    /// chosen with the `--watch` option rather than named.
+    Watch(Duration),
+
+    /// Send the queries to a list of public resolvers, along with any
+    /// nameservers the user gave, and compare what each one answers with.
+    Propagate,
+
+    /// Find the CAA records that apply to each domain, walking up the name
+    /// tree from it, and report which CAs may issue certificates for it.
+    CaaCheck,
+
+    /// Look up the SRV records for the given service and protocol at each
+    /// domain, then the addresses of their targets, and list the places
+    /// the service can be reached at in the order to try them in.
+    Srv(String, String),
+
+    /// Look up the MX records of each domain and the addresses of their
+    /// mail exchanges, and report any mistakes in them.
+    MxCheck,
+
+    /// Look up the PTR records of each address, then the addresses of the
+    /// names they point to, and report whether any of them point back.
+    Fcrdns,
+
+    /// Ask for a few made-up names under each domain, and report whether
+    /// a wildcard synthesises records for them, and which.
+    Wildcard,
+
+    /// Look up each domain with the system resolver and with DNS, and
+    /// compare the addresses each of them gives.
+    Syscmp,
+
+    /// Ask each nameserver for the AAAA records of `ipv4only.arpa`, and
+    /// report the NAT64 prefix its DNS64 synthesis uses, if any.
+    Nat64,
+
+    /// Look up the nameservers for each zone, then ask every one of them
+    /// directly for the zone’s SOA record, and compare the serial numbers.
+    SoaCheck,
+
+    /// Follow the referrals from the root down to each zone, then check
+    /// that the nameservers its parent refers to agree with the ones it
+    /// lists itself, have glue where they need it, and answer for it.
+    Delegation,
+
+    /// Send the queries listed in a file to the nameserver over and over,
+    /// at the given rate, and summarise how quickly it responded.
+    Bench(Input, Load),
+
+    /// Send the same query to the nameserver the given number of times,
+    /// a second apart, printing how long each response took to arrive.
+    Ping(u32),
+
+    /// Send the queries again and again, waiting the given interval
+    /// between rounds, and serve metrics about the responses over HTTP at
+    /// the given address. This one is chosen with the `--probe` option
+    /// rather than named.
+    Probe(SocketAddr, Duration),
+
+    /// Listen for queries on the given address, forward them to the
+    /// nameserver, and send the responses back, printing each one. If a
+    /// zone file is given, the queries get answered from it instead.
+    Serve(SocketAddr, Option<PathBuf>),
+}
+
+impl Command {
+
+    /// What running this command is called in error messages, for the
+    /// commands that cannot trace, validate, or chase.
+    fn activity(&self) -> Option<&'static str> {
+        match self {
+            Self::Decode(_) | Self::Pcap(_) | Self::Compare(..)  => Some("decoding messages"),
+            Self::Watch(_)     => Some("watching"),
+            Self::Propagate    => Some("checking propagation"),
+            Self::CaaCheck     => Some("checking CAA records"),
+            Self::Srv(..)      => Some("resolving a service"),
+            Self::MxCheck      => Some("checking mail exchanges"),
+            Self::Fcrdns       => Some("checking reverse DNS"),
+            Self::Wildcard     => Some("looking for a wildcard"),
+            Self::Syscmp       => Some("comparing with the system resolver"),
+            Self::Nat64        => Some("discovering the NAT64 prefix"),
+            Self::SoaCheck     => Some("checking SOA serials"),
+            Self::Delegation   => Some("checking a delegation"),
+            Self::Bench(..)    => Some("benchmarking"),
+            Self::Ping(_)      => Some("pinging"),
+            Self::Probe(..)    => Some("probing"),
+            Self::Serve(..)    => Some("serving"),
+            Self::Diff(_) | Self::Batch(_)  => None,
+        }
+    }
+
+    /// Whether this command prints summaries of its own rather than the
+    /// responses, which only come in the text, short, JSON, and YAML
+    /// output.
+    fn prints_summaries(&self) -> bool {
+        matches!(self, Self::Watch(_) | Self::CaaCheck | Self::Srv(..) | Self::MxCheck | Self::Fcrdns | Self::Wildcard |
+                       Self::Syscmp | Self::Nat64 | Self::SoaCheck | Self::Delegation | Self::Ping(_) | Self::Serve(..))
+    }
+
+    /// What the domains given to this command are for, and how to give
+    /// them, for the commands that need at least one.
+    fn domain_usage(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Propagate    => Some(("check the propagation of", "dog propagate DOMAIN")),
+            Self::CaaCheck     => Some(("check the CAA records of", "dog caa-check DOMAIN")),
+            Self::Srv(..)      => Some(("look up the service at", "dog srv SERVICE PROTOCOL DOMAIN")),
+            Self::MxCheck      => Some(("check the mail exchanges of", "dog mx-check DOMAIN")),
+            Self::Wildcard     => Some(("look for a wildcard under", "dog wildcard DOMAIN")),
+            Self::Syscmp       => Some(("compare the system resolver’s answer for", "dog syscmp DOMAIN")),
+            Self::SoaCheck     => Some(("check the nameservers of", "dog soa-check DOMAIN")),
+            Self::Delegation   => Some(("check the delegation of", "dog delegation DOMAIN")),
+            Self::Probe(..)    => Some(("probe", "dog DOMAIN --probe ADDRESS")),
+            _                  => None,
+        }
+    }
 }
 
 impl Options {
 
-    /// Parses and interprets a set of options from the user’s command-line
-    /// arguments.
-    ///
-    /// This returns an `Ok` set of options if successful and running
-    /// normally, a `Help` or `Version` variant if one of those options is
-    /// specified, or an error variant if there’s an invalid option or
-    /// inconsistency within the options after they were parsed.
+    /// Defines every command-line option, which is where the shell
+    /// completion scripts get their list of options from too.
     #[allow(unused_results)]
-    pub fn getopts<C>(args: C) -> OptionsResult
-    where C: IntoIterator,
-          C::Item: AsRef<OsStr>,
-    {
+    pub fn definitions() -> getopts::Options {
         let mut opts = getopts::Options::new();
 
         // Query options
@@ -48,29 +269,110 @@ impl Options {
         opts.optmulti("t", "type",        "Type of the DNS record being queried (A, MX, NS...)", "TYPE");
         opts.optmulti("n", "nameserver",  "Address of the nameserver to send packets to", "ADDR");
         opts.optmulti("",  "class",       "Network class of the DNS record being queried (IN, CH, HS)", "CLASS");
+        opts.optmulti("x", "reverse",     "IP address to do a reverse lookup on", "ADDR");
+        opts.optflag("",  "stdin",        "Read the message to decode from standard input");
+        opts.optopt ("f", "file",         "Read queries from a file, one per line", "FILE");
 
         // Sending options
+        opts.optopt ("",  "parallel",     "Number of queries to send at once", "NUMBER");
+        opts.optopt ("",  "watch",        "Repeat the queries on an interval, highlighting changes", "INTERVAL");
+        opts.optopt ("",  "probe",        "Repeat the queries, serving Prometheus metrics on an address", "ADDRESS");
+        opts.optopt ("",  "listen",       "Address to listen for queries on when serving", "ADDRESS");
+        opts.optopt ("",  "zone",         "Zone file to answer queries from when serving", "FILE");
+        opts.optopt ("",  "qps",          "Number of queries to send each second when benchmarking", "NUMBER");
+        opts.optopt ("",  "duration",     "How long to keep sending queries when benchmarking", "INTERVAL");
+        opts.optopt ("",  "count",        "Number of queries to send when pinging", "NUMBER");
         opts.optopt ("",  "edns",         "Whether to OPT in to EDNS (disable, hide, show)", "SETTING");
-        opts.optopt ("",  "txid",         "Set the transaction ID to a specific value", "NUMBER");
+        opts.optopt ("",  "txid",         "Set the transaction ID to a specific value, counting up from there", "NUMBER");
+        opts.optopt ("",  "txid-seed",    "Pick random transaction IDs that are the same every run", "NUMBER");
         opts.optopt ("Z", "",             "Uncommon protocol tweaks", "TWEAKS");
+        opts.optflag("",  "dnssec",       "Set the DO bit, asking for DNSSEC records in responses");
+        opts.optflag("",  "cd",           "Set the CD flag, asking resolvers not to validate DNSSEC");
+        opts.optflag("",  "ad",           "Set the AD flag, asking resolvers whether they validated");
+        opts.optflag("",  "norecurse",    "Clear the RD flag, asking resolvers only for what they have cached");
+        opts.optflag("",  "multi-question", "Ask every question in one request, to test how servers cope with it");
+        opts.optflag("",  "cache",        "Answer repeated queries from a cache until their TTLs run out");
+        opts.optopt ("",  "cache-dir",    "Keep the cache in a directory, so it lasts between runs", "DIR");
+        opts.optflag("",  "hosts",        "Answer queries from /etc/hosts first, as the system resolver does");
+        opts.optopt ("",  "hostsfile",    "Answer queries from a different hosts file first", "PATH");
+        opts.optopt ("",  "log-wire",     "Append every request and response to a JSON-lines file", "FILE");
+        opts.optflag("",  "debug-wire",   "Log every request and response as annotated hexadecimal");
+        opts.optopt ("",  "udp-window",   "How long to keep listening for duplicate or late UDP responses", "INTERVAL");
+        opts.optopt ("",  "timeout",      "How long to wait for each UDP response (default 5s)", "INTERVAL");
+        opts.optflag("",  "no-0x20",      "Do not randomise the case of query names sent over UDP");
+        opts.optflag("",  "trace",        "Trace the delegation path down from the root servers");
+        opts.optflag("",  "authoritative", "Send the queries straight to an authoritative nameserver for their zone");
+        opts.optflagopt("", "dns64-synthesize", "Show A records as the AAAA records DNS64 would make from them", "PREFIX");
+        opts.optflag("",  "chase",        "Follow CNAME chains, querying for any missing targets");
+        opts.optflag("",  "validate",     "Validate DNSSEC signatures from the root trust anchor");
+        opts.optopt ("",  "trust-anchor", "Load the root trust anchors from a file", "FILE");
+        opts.optflag("",  "track-anchors", "Update the trust anchor file as root keys roll over");
 
         // Protocol options
         opts.optflag("U", "udp",          "Use the DNS protocol over UDP");
         opts.optflag("T", "tcp",          "Use the DNS protocol over TCP");
         opts.optflag("S", "tls",          "Use the DNS-over-TLS protocol");
         opts.optflag("H", "https",        "Use the DNS-over-HTTPS protocol");
+        opts.optopt ("",  "bootstrap",    "Nameserver to look up the host name of a TLS or HTTPS resolver with", "ADDR");
+        opts.optopt ("",  "resolver-ip",  "Connect to a TLS or HTTPS resolver at this address without looking it up", "ADDR");
+        opts.optflag("",  "insecure",     "Do not check the certificates of TLS or HTTPS resolvers");
+        opts.optopt ("",  "cafile",       "Trust the certificate authorities in a PEM file as well", "FILE");
+        opts.optmulti("", "pin",          "Only accept a TLS resolver whose certificate has this public key", "PIN");
+        opts.optopt ("",  "cert",         "Present the client certificate in a PEM file to TLS or HTTPS resolvers", "FILE");
+        opts.optopt ("",  "key",          "Read the private key for the client certificate from a PEM file", "FILE");
 
         // Output options
         opts.optopt ("",  "color",        "When to use terminal colors",  "WHEN");
         opts.optopt ("",  "colour",       "When to use terminal colours", "WHEN");
         opts.optflag("J", "json",         "Display the output as JSON");
+        opts.optflag("",  "yaml",         "Display the output as YAML");
+        opts.optflag("",  "csv",          "Display the answers as comma-separated values");
+        opts.optflag("",  "zone-format",  "Display the output as zone file records");
+        opts.optflag("",  "dig",          "Display the output in the same layout as dig");
+        opts.optflag("",  "hex",          "Display a hex dump of each response");
+        opts.optflag("",  "raw",          "Write the raw bytes of each response");
         opts.optflag("",  "seconds",      "Do not format durations, display them as seconds");
-        opts.optflag("1", "short",        "Short mode: display nothing but the first result");
+        opts.optopt ("",  "ttl-format",   "How to display TTLs (raw, human, expiry)", "FORMAT");
+        opts.optopt ("",  "locale",       "Language to print messages and durations in", "LANG");
+        opts.optopt ("",  "catalog",      "Read the messages to print from a catalog file", "PATH");
+        opts.optflag("",  "ascii",        "Do not decode internationalised domain names");
+        opts.optflag("",  "txt-concat",   "Join the strings in TXT records into one");
+        opts.optflag("",  "ignore-ttl",   "Ignore differences in TTLs when comparing messages");
+        opts.optflag("1", "short",        "Short mode: display nothing but the record data");
         opts.optflag("",  "time",         "Print how long the response took to arrive");
+        opts.optflag("",  "stats",        "Print a summary of query times, retries, and protocols");
+        opts.optflag("",  "interpret",    "Explain the SPF, DKIM, and DMARC policies in TXT records");
+        opts.optflag("",  "question",     "Print the question section");
+        opts.optflag("",  "answer-only",  "Print the answer section");
+        opts.optflag("",  "authority",    "Print the authority section");
+        opts.optflag("",  "additional",   "Print the additional section");
+        opts.optflag("",  "sort",         "Sort records by type and data, removing duplicates");
+
+        // Expectation options
+        opts.optmulti("",  "expect",      "Exit with an error unless this value is among the answers", "VALUE");
+        opts.optopt ("",  "expect-rcode", "Exit with an error unless every response has this status", "RCODE");
+        opts.optopt ("",  "expect-ttl-min", "Exit with an error if any answer has a lower TTL", "SECONDS");
 
         // Meta options
         opts.optflag("v", "version",      "Print version information");
         opts.optflag("?", "help",         "Print list of command-line options");
+        opts.optflag("",  "list-resolvers", "Print the public resolvers that can be picked by name");
+
+        opts
+    }
+
+    /// Parses and interprets a set of options from the user’s command-line
+    /// arguments.
+    ///
+    /// This returns an `Ok` set of options if successful and running
+    /// normally, a `Help` or `Version` variant if one of those options is
+    /// specified, or an error variant if there’s an invalid option or
+    /// inconsistency within the options after they were parsed.
+    pub fn getopts<C>(args: C) -> OptionsResult
+    where C: IntoIterator,
+          C::Item: AsRef<OsStr>,
+    {
+        let opts = Self::definitions();
 
         let matches = match opts.parse(args) {
             Ok(m)  => m,
@@ -85,14 +387,26 @@ impl Options {
         else if matches.opt_present("help") {
             OptionsResult::Help(HelpReason::Flag, uc)
         }
+        else if matches.opt_present("list-resolvers") {
+            OptionsResult::ListResolvers
+        }
+        else if matches.free.first().map(String::as_str) == Some("completions") {
+            match matches.free.get(1).map(String::as_str) {
+                Some(input) => match Shell::from_name(input) {
+                    Some(shell) => OptionsResult::Completions(shell),
+                    None        => OptionsResult::InvalidOptions(OptionsError::InvalidShell(input.into())),
+                },
+                None => OptionsResult::InvalidOptions(OptionsError::MissingShell),
+            }
+        }
         else {
             match Self::deduce(matches) {
                 Ok(opts) => {
-                    if opts.requests.inputs.domains.is_empty() {
+                    if opts.requests.inputs.domains.is_empty() && opts.command.is_none() {
                         OptionsResult::Help(HelpReason::NoDomains, uc)
                     }
                     else {
-                        OptionsResult::Ok(opts)
+                        OptionsResult::Ok(Box::new(opts))
                     }
                 }
                 Err(e) => {
@@ -102,548 +416,2643 @@ impl Options {
         }
     }
 
-    fn deduce(matches: getopts::Matches) -> Result<Self, OptionsError> {
+    fn deduce(mut matches: getopts::Matches) -> Result<Self, OptionsError> {
+        let command = Self::deduce_command(&mut matches)?;
+        let parallel = Self::deduce_parallel(&matches)?;
         let measure_time = matches.opt_present("time");
-        let format = OutputFormat::deduce(&matches);
-        let requests = RequestGenerator::deduce(matches)?;
+        let stats = matches.opt_present("stats");
+        let interpret = matches.opt_present("interpret");
+        let expectations = Expectations::deduce(&matches)?;
+        let trace = matches.opt_present("trace");
+        let authoritative = matches.opt_present("authoritative");
+        let dns64_prefix = Self::deduce_dns64_prefix(&matches)?;
+        let chase = matches.opt_present("chase");
+        let validate = matches.opt_present("validate");
+        let trust_anchors = TrustAnchors::deduce(&matches)?;
+        let format = OutputFormat::deduce(&matches)?;
+        let sections = Self::deduce_sections(&matches);
+        let sort = matches.opt_present("sort");
+        let mut requests = RequestGenerator::deduce(matches)?;
+
+        if trace {
+            if requests.inputs.transport_types.iter().any(|tt| *tt == TransportType::TLS || *tt == TransportType::HTTPS) {
+                return Err(OptionsError::TraceOverEncryption);
+            }
 
-        Ok(Self { requests, measure_time, format })
-    }
-}
+            if requests.inputs.resolvers.iter().any(|r| *r != Resolver::SystemDefault) {
+                return Err(OptionsError::TraceWithNameserver);
+            }
+        }
 
+        if let Some(command) = &command {
+            if let Some(activity) = command.activity() {
+                if trace || validate || chase {
+                    return Err(OptionsError::CommandWhileTracing(activity));
+                }
 
-impl RequestGenerator {
-    fn deduce(matches: getopts::Matches) -> Result<Self, OptionsError> {
-        let edns = UseEDNS::deduce(&matches)?;
-        let txid_generator = TxidGenerator::deduce(&matches)?;
-        let protocol_tweaks = ProtocolTweaks::deduce(&matches)?;
-        let inputs = Inputs::deduce(matches)?;
+                if command.prints_summaries() && ! matches!(format, OutputFormat::Text(..) | OutputFormat::Short(_) | OutputFormat::JSON | OutputFormat::YAML) {
+                    return Err(OptionsError::CommandFormat(activity));
+                }
+            }
 
-        Ok(Self { inputs, txid_generator, edns, protocol_tweaks })
-    }
-}
+            if let Some((purpose, usage)) = command.domain_usage() {
+                if requests.inputs.domains.is_empty() {
+                    return Err(OptionsError::MissingDomain(purpose, usage));
+                }
+            }
+        }
 
+        match command {
+            Some(Command::Batch(_)) if trace => {
+                return Err(OptionsError::BatchWhileTracing);
+            }
+            Some(Command::Batch(_)) if ! requests.inputs.domains.is_empty() => {
+                return Err(OptionsError::BatchWithDomains);
+            }
+            Some(Command::Diff(_)) if trace || validate => {
+                return Err(OptionsError::DiffWhileTracing);
+            }
+            Some(Command::Bench(..)) if ! requests.inputs.domains.is_empty() => {
+                return Err(OptionsError::BatchWithDomains);
+            }
+            Some(Command::Propagate) if ! matches!(format, OutputFormat::Text(..) | OutputFormat::Short(_) | OutputFormat::JSON | OutputFormat::YAML | OutputFormat::CSV) => {
+                return Err(OptionsError::PropagateFormat);
+            }
+            Some(Command::Propagate) => {
+                requests.inputs.resolvers = propagate::resolvers(&requests.inputs.resolvers);
+            }
+            Some(Command::CaaCheck) => {
+                requests.inputs.types = vec![ qtype!(CAA) ];
+            }
+            Some(Command::Srv(ref service, ref protocol)) => {
+                let mut names = Vec::new();
+                for domain in &requests.inputs.domains {
+                    let input = srv::service_name(service, protocol, domain);
+                    names.push(dns::Name::from_unicode(&input).map_err(|_| OptionsError::InvalidDomain(input))?);
+                }
 
-impl Inputs {
-    fn deduce(matches: getopts::Matches) -> Result<Self, OptionsError> {
-        let mut inputs = Self::default();
-        inputs.load_transport_types(&matches);
-        inputs.load_named_args(&matches)?;
-        inputs.load_free_args(matches)?;
-        inputs.load_fallbacks();
-        Ok(inputs)
-    }
+                requests.inputs.domains = names;
+                requests.inputs.types = vec![ qtype!(SRV) ];
+            }
+            Some(Command::MxCheck) => {
+                requests.inputs.types = vec![ qtype!(MX) ];
+            }
+            Some(Command::Fcrdns) if requests.inputs.domains.is_empty() => {
+                return Err(OptionsError::MissingFcrdnsAddress);
+            }
+            Some(Command::Fcrdns) => {
+                let mut names = Vec::new();
+                for domain in &requests.inputs.domains {
+                    let input = domain.as_str();
+                    match input.parse() {
+                        Ok(address)                                          => names.push(PTR::reverse_name(address)),
+                        Err(_) if PTR::reversed_address(domain).is_some()    => names.push(domain.clone()),
+                        Err(_)                                               => return Err(OptionsError::InvalidFcrdnsAddress(input.into())),
+                    }
+                }
 
-    fn load_transport_types(&mut self, matches: &getopts::Matches) {
-        if matches.opt_present("https") {
-            self.transport_types.push(TransportType::HTTPS);
+                requests.inputs.domains = names;
+                requests.inputs.types = vec![ qtype!(PTR) ];
+            }
+            Some(Command::Syscmp) => {
+                requests.inputs.types = vec![ qtype!(A) ];
+            }
+            Some(Command::Nat64) if ! requests.inputs.domains.is_empty() => {
+                return Err(OptionsError::Nat64WithDomains);
+            }
+            Some(Command::Nat64) => {
+                requests.inputs.domains = vec![ nat64::ipv4only_arpa() ];
+                requests.inputs.types = vec![ qtype!(AAAA) ];
+                requests.inputs.classes = vec![ QClass::IN ];
+            }
+            Some(Command::SoaCheck) if requests.inputs.transport_types.iter().any(|tt| *tt == TransportType::TLS || *tt == TransportType::HTTPS) => {
+                return Err(OptionsError::SoaCheckOverEncryption);
+            }
+            Some(Command::SoaCheck) => {
+                requests.inputs.types = vec![ qtype!(NS) ];
+            }
+            Some(Command::Delegation) if requests.inputs.transport_types.iter().any(|tt| *tt == TransportType::TLS || *tt == TransportType::HTTPS) => {
+                return Err(OptionsError::DelegationOverEncryption);
+            }
+            Some(Command::Delegation) if requests.inputs.resolvers.iter().any(|r| *r != Resolver::SystemDefault) => {
+                return Err(OptionsError::DelegationWithNameserver);
+            }
+            Some(Command::Delegation) => {
+                requests.inputs.types = vec![ qtype!(NS) ];
+            }
+            Some(Command::Ping(_)) if requests.inputs.domains.is_empty() => {
+                requests.inputs.domains = vec![ dns::Name::root() ];
+                requests.inputs.types = vec![ qtype!(NS) ];
+            }
+            Some(Command::Serve(_, None)) if ! requests.inputs.domains.is_empty() => {
+                return Err(OptionsError::ServeWithDomains);
+            }
+            Some(Command::Batch(_)) | Some(Command::Watch(_)) | Some(Command::Probe(..)) | Some(Command::Serve(..)) => {}
+            Some(_) if format == OutputFormat::Hex || format == OutputFormat::Raw => {
+                return Err(OptionsError::BytesWithoutQueries);
+            }
+            _ => {}
         }
 
-        if matches.opt_present("tls") {
-            self.transport_types.push(TransportType::TLS);
+        if stats && (trace || ! matches!(command, None | Some(Command::Batch(_)))) {
+            return Err(OptionsError::StatsWithoutQueries);
         }
 
-        if matches.opt_present("tcp") {
-            self.transport_types.push(TransportType::TCP);
+        if authoritative {
+            if trace || ! matches!(command, None | Some(Command::Batch(_))) {
+                return Err(OptionsError::AuthoritativeWithoutQueries);
+            }
+
+            if requests.inputs.transport_types.iter().any(|tt| *tt == TransportType::TLS || *tt == TransportType::HTTPS) {
+                return Err(OptionsError::AuthoritativeOverEncryption);
+            }
         }
 
-        if matches.opt_present("udp") {
-            self.transport_types.push(TransportType::UDP);
+        if dns64_prefix.is_some() && (trace || validate || ! matches!(command, None | Some(Command::Batch(_)))) {
+            return Err(OptionsError::Dns64WithoutQueries);
         }
-    }
 
-    fn load_named_args(&mut self, matches: &getopts::Matches) -> Result<(), OptionsError> {
-        for domain in matches.opt_strs("query") {
-            self.domains.push(domain);
+        if interpret && (trace || ! matches!(command, None | Some(Command::Batch(_)))) {
+            return Err(OptionsError::InterpretWithoutQueries);
         }
 
-        for qtype in matches.opt_strs("type") {
-            self.add_type(&qtype)?;
+        if sections != Sections::all() {
+            if trace || ! matches!(command, None | Some(Command::Batch(_)) | Some(Command::Decode(_))) {
+                return Err(OptionsError::SectionsWithoutQueries);
+            }
+
+            if matches!(format, OutputFormat::Short(_) | OutputFormat::CSV | OutputFormat::Hex | OutputFormat::Raw) {
+                return Err(OptionsError::SectionsFormat);
+            }
         }
 
-        for ns in matches.opt_strs("nameserver") {
-            self.add_nameserver(&ns)?;
+        if sort && (trace || ! matches!(command, None | Some(Command::Batch(_)) | Some(Command::Decode(_)))) {
+            return Err(OptionsError::SortWithoutQueries);
         }
 
-        for qclass in matches.opt_strs("class") {
-            self.add_class(&qclass)?;
+        if requests.cache.is_some() && matches!(command, Some(Command::Decode(_)) | Some(Command::Pcap(_)) | Some(Command::Compare(..)) | Some(Command::Bench(..)) | Some(Command::Ping(_))) {
+            return Err(OptionsError::CacheWithoutQueries);
         }
 
-        Ok(())
-    }
+        if requests.hosts.is_some() && (trace || validate || matches!(command, Some(Command::Decode(_)) | Some(Command::Pcap(_)) | Some(Command::Compare(..)) | Some(Command::Bench(..)) | Some(Command::Ping(_)))) {
+            return Err(OptionsError::HostsWithoutQueries);
+        }
 
-    fn add_type(&mut self, input: &str) -> Result<(), OptionsError> {
-        if input == "OPT" {
-            return Err(OptionsError::QueryTypeOPT);
+        if (requests.wire_log.is_some() || requests.debug_wire) && matches!(command, Some(Command::Decode(_)) | Some(Command::Pcap(_)) | Some(Command::Compare(..))) {
+            return Err(OptionsError::WireLogWithoutQueries);
         }
 
-        let type_number = find_qtype_number(input)
-            .or_else(|| find_other_qtype_number(input))
-            .or_else(|| input.parse().ok());
+        if ! expectations.is_empty() && (trace || ! matches!(command, None | Some(Command::Batch(_)))) {
+            return Err(OptionsError::ExpectWithoutQueries);
+        }
 
-        match type_number {
-            Some(qtype)  => Ok(self.types.push(qtype)),
-            None         => Err(OptionsError::InvalidQueryType(input.into())),
+        if trust_anchors != TrustAnchors::BuiltIn && ! validate {
+            return Err(OptionsError::AnchorsWithoutValidate);
         }
-    }
 
-    fn add_nameserver(&mut self, input: &str) -> Result<(), OptionsError> {
-        self.resolvers.push(Resolver::Specified(input.into()));
-        Ok(())
-    }
+        if validate {
+            if trace {
+                return Err(OptionsError::ValidateWhileTracing);
+            }
 
-    fn parse_class_name(&self, input: &str) -> Option<QClass> {
-        match input {
-            "IN"  => Some(QClass::IN),
-            "CH"  => Some(QClass::CH),
-            "HS"  => Some(QClass::HS),
-            _     => None,
+            if requests.edns == UseEDNS::Disable {
+                return Err(OptionsError::ValidateWithoutEDNS);
+            }
+
+            requests.protocol_tweaks.set_dnssec_ok = true;
         }
-    }
 
-    fn add_class(&mut self, input: &str) -> Result<(), OptionsError> {
-        let qclass = self.parse_class_name(input)
-            .or_else(|| input.parse().ok().map(QClass::Other));
+        if requests.protocol_tweaks.set_dnssec_ok && requests.edns == UseEDNS::Disable {
+            return Err(OptionsError::DnssecWithoutEDNS);
+        }
 
-        match qclass {
-            Some(class)  => Ok(self.classes.push(class)),
-            None         => Err(OptionsError::InvalidQueryClass(input.into())),
+        if requests.protocol_tweaks.combine_questions && (trace || validate || chase || command.is_some()) {
+            return Err(OptionsError::MultiQuestionWithoutQueries);
         }
+
+        Ok(Self { requests, measure_time, stats, interpret, expectations, trace, authoritative, dns64_prefix, chase, validate, trust_anchors, command, parallel, format, sections, sort })
     }
 
-    fn load_free_args(&mut self, matches: getopts::Matches) -> Result<(), OptionsError> {
-        for a in matches.free {
-            if a.starts_with('@') {
-                trace!("Got nameserver -> {:?}", &a[1..]);
-                self.add_nameserver(&a[1..])?;
-            }
-            else if a.chars().all(char::is_uppercase) {
-                if let Some(class) = self.parse_class_name(&a) {
-                    trace!("Got qclass -> {:?}", &a);
-                    self.classes.push(class);
-                }
-                else {
-                    trace!("Got qtype -> {:?}", &a);
-                    self.add_type(&a)?;
-                }
-            }
-            else {
-                trace!("Got domain -> {:?}", &a);
-                self.domains.push(a);
-            }
+    /// Checks for a command, which has to come before any other free
+    /// arguments. Apart from `propagate`, `caa-check`, `mx-check`,
+    /// `wildcard`, `syscmp`, `soa-check`, `delegation`, and `ping`, which take the
+    /// usual domains,
+    /// `fcrdns`, which takes addresses in their place, `nat64`, which
+    /// takes none, `srv`, which takes
+    /// a service and a protocol before them, `compare`,
+    /// which takes the paths to two messages, and `serve`, which takes the
+    /// address to listen on and any zone file as options, it has to be
+    /// followed by the path to the file it reads. For `diff`, and `serve`
+    /// with a zone file, any domain given after that is used as the zone’s
+    /// origin, and `decode` can read from standard input instead of a file.
+    /// Arguments starting with `@` are nameservers rather than paths, so
+    /// they can come before the path. If there is no command, a file of
+    /// queries, an interval to watch the queries on, or an address to serve
+    /// probe metrics on may have been given instead, with the watch
+    /// interval also setting how often the probe sends its queries.
+    fn deduce_command(matches: &mut getopts::Matches) -> Result<Option<Command>, OptionsError> {
+        let stdin = matches.opt_present("stdin");
+        let file = matches.opt_str("file");
+        let watch = match matches.opt_str("watch") {
+            Some(input) => Some(parse_interval(&input).ok_or(OptionsError::InvalidWatchInterval(input))?),
+            None        => None,
+        };
+
+        let probe = match matches.opt_str("probe") {
+            Some(input) => Some(parse_listen_address(&input).ok_or(OptionsError::InvalidProbeAddress(input))?),
+            None        => None,
+        };
+
+        let listen = match matches.opt_str("listen") {
+            Some(input) => Some(parse_listen_address(&input).ok_or(OptionsError::InvalidListenAddress(input))?),
+            None        => None,
+        };
+
+        if listen.is_some() && matches.free.first().map(String::as_str) != Some("serve") {
+            return Err(OptionsError::ListenWithoutServe);
         }
 
-        Ok(())
-    }
+        let zone = matches.opt_str("zone").map(PathBuf::from);
+        if zone.is_some() && matches.free.first().map(String::as_str) != Some("serve") {
+            return Err(OptionsError::ZoneWithoutServe);
+        }
 
-    fn load_fallbacks(&mut self) {
-        if self.types.is_empty() {
-            self.types.push(qtype!(A));
+        let ignore_ttl = matches.opt_present("ignore-ttl");
+        if ignore_ttl && matches.free.first().map(String::as_str) != Some("compare") {
+            return Err(OptionsError::IgnoreTtlWithoutCompare);
         }
 
-        if self.classes.is_empty() {
-            self.classes.push(QClass::IN);
+        let load_given = matches.opt_present("qps") || matches.opt_present("duration");
+        if load_given && matches.free.first().map(String::as_str) != Some("bench") {
+            return Err(OptionsError::LoadWithoutBench);
         }
 
-        if self.resolvers.is_empty() {
-            self.resolvers.push(Resolver::SystemDefault);
+        if matches.opt_present("count") && matches.free.first().map(String::as_str) != Some("ping") {
+            return Err(OptionsError::CountWithoutPing);
         }
 
-        if self.transport_types.is_empty() {
-            self.transport_types.push(TransportType::Automatic);
+        let name = match matches.free.first().map(String::as_str) {
+            Some(name) if COMMANDS.contains(&name) => name.to_owned(),
+            _ if stdin => return Err(OptionsError::StdinWithoutDecode),
+            _ => return match (file, watch, probe) {
+                (Some(_), _, Some(_))        => Err(OptionsError::ProbeWithCommand),
+                (Some(_), Some(_), None)     => Err(OptionsError::WatchWithCommand),
+                (Some(path), None, None)     => Ok(Some(Command::Batch(Input::from_path(path.into())))),
+                (None, wait, Some(address))  => Ok(Some(Command::Probe(address, wait.unwrap_or(probe::DEFAULT_INTERVAL)))),
+                (None, Some(wait), None)     => Ok(Some(Command::Watch(wait))),
+                (None, None, None)           => Ok(None),
+            },
+        };
+
+        if probe.is_some() {
+            return Err(OptionsError::ProbeWithCommand);
         }
-    }
-}
 
+        if file.is_some() {
+            return Err(OptionsError::BatchWithCommand);
+        }
 
-impl TxidGenerator {
-    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
-        if let Some(starting_txid) = matches.opt_str("txid") {
-            if let Ok(start) = starting_txid.parse() {
-                Ok(Self::Sequence(start))
-            }
-            else {
-                Err(OptionsError::InvalidTxid(starting_txid))
-            }
+        if watch.is_some() {
+            return Err(OptionsError::WatchWithCommand);
         }
-        else {
-            Ok(Self::Random)
+
+        let _ = matches.free.remove(0);
+        if name == "propagate" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Propagate)) };
         }
-    }
-}
 
+        if name == "caa-check" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::CaaCheck)) };
+        }
 
-impl OutputFormat {
-    fn deduce(matches: &getopts::Matches) -> Self {
-        if matches.opt_present("short") {
-            let summary_format = TextFormat::deduce(matches);
-            Self::Short(summary_format)
+        if name == "mx-check" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::MxCheck)) };
         }
-        else if matches.opt_present("json") {
-            Self::JSON
+
+        if name == "fcrdns" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Fcrdns)) };
         }
-        else {
-            let use_colours = UseColours::deduce(matches);
-            let summary_format = TextFormat::deduce(matches);
-            Self::Text(use_colours, summary_format)
+
+        if name == "wildcard" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Wildcard)) };
         }
-    }
-}
 
+        if name == "syscmp" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Syscmp)) };
+        }
 
-impl UseColours {
-    fn deduce(matches: &getopts::Matches) -> Self {
-        match matches.opt_str("color").or_else(|| matches.opt_str("colour")).unwrap_or_default().as_str() {
-            "automatic" | "auto" | ""  => Self::Automatic,
-            "always"    | "yes"        => Self::Always,
-            "never"     | "no"         => Self::Never,
-            otherwise => {
-                warn!("Unknown colour setting {:?}", otherwise);
-                Self::Automatic
-            },
+        if name == "nat64" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Nat64)) };
         }
-    }
-}
 
+        if name == "soa-check" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::SoaCheck)) };
+        }
 
-impl TextFormat {
-    fn deduce(matches: &getopts::Matches) -> Self {
-        let format_durations = ! matches.opt_present("seconds");
-        Self { format_durations }
-    }
-}
+        if name == "delegation" {
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Delegation)) };
+        }
 
+        if name == "ping" {
+            let count = match matches.opt_str("count") {
+                Some(input) => match input.parse() {
+                    Ok(0) | Err(_)  => return Err(OptionsError::InvalidCount(input)),
+                    Ok(number)      => number,
+                },
+                None => ping::DEFAULT_COUNT,
+            };
 
-impl UseEDNS {
-    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
-        if let Some(edns) = matches.opt_str("edns") {
-            match edns.as_str() {
-                "disable" | "off"  => Ok(Self::Disable),
-                "hide"             => Ok(Self::SendAndHide),
-                "show"             => Ok(Self::SendAndShow),
-                oh                 => Err(OptionsError::InvalidEDNS(oh.into())),
-            }
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Ping(count))) };
+        }
+
+        if name == "srv" {
+            if stdin {
+                return Err(OptionsError::StdinWithoutDecode);
+            }
+
+            let mut parts = Vec::new();
+            while parts.len() < 2 {
+                match matches.free.iter().position(|arg| ! arg.starts_with('@')) {
+                    Some(index)  => parts.push(matches.free.remove(index)),
+                    None         => return Err(OptionsError::MissingSrvService),
+                }
+            }
+
+            let protocol = parts.pop().unwrap();
+            let service = parts.pop().unwrap();
+            return Ok(Some(Command::Srv(service, protocol)));
+        }
+
+        if name == "compare" {
+            if stdin {
+                return Err(OptionsError::StdinWithoutDecode);
+            }
+
+            let mut paths = Vec::new();
+            while paths.len() < 2 {
+                match matches.free.iter().position(|arg| ! arg.starts_with('@')) {
+                    Some(index)  => paths.push(PathBuf::from(matches.free.remove(index))),
+                    None         => return Err(OptionsError::MissingCompareFiles),
+                }
+            }
+
+            let options = DiffOptions { ignore_ttl, .. DiffOptions::default() };
+            let right = paths.pop().unwrap();
+            let left = paths.pop().unwrap();
+            return Ok(Some(Command::Compare(left, right, options)));
+        }
+
+        if name == "serve" {
+            let listen = listen.unwrap_or_else(|| SocketAddr::from((Ipv4Addr::LOCALHOST, serve::DEFAULT_PORT)));
+            return if stdin { Err(OptionsError::StdinWithoutDecode) } else { Ok(Some(Command::Serve(listen, zone))) };
+        }
+
+        let path = matches.free.iter().position(|arg| ! arg.starts_with('@')).map(|index| PathBuf::from(matches.free.remove(index)));
+
+        match (&*name, path) {
+            ("decode", _) if stdin  => Ok(Some(Command::Decode(Input::Stdin))),
+            (_, _) if stdin         => Err(OptionsError::StdinWithoutDecode),
+            ("diff", Some(path))    => Ok(Some(Command::Diff(path))),
+            ("diff", None)          => Err(OptionsError::MissingZoneFile),
+            ("decode", Some(path))  => Ok(Some(Command::Decode(Input::File(path)))),
+            ("decode", None)        => Err(OptionsError::MissingMessageFile),
+            ("bench", Some(path))   => Ok(Some(Command::Bench(Input::from_path(path), Self::deduce_load(matches)?))),
+            ("bench", None)         => Err(OptionsError::MissingQueryFile),
+            (_, Some(path))         => Ok(Some(Command::Pcap(path))),
+            (_, None)               => Err(OptionsError::MissingCaptureFile),
+        }
+    }
+
+    /// Works out how hard to load the nameserver when benchmarking, which
+    /// is ten queries a second for ten seconds unless the user says so.
+    fn deduce_load(matches: &getopts::Matches) -> Result<Load, OptionsError> {
+        let rate = match matches.opt_str("qps") {
+            Some(input) => match input.parse() {
+                Ok(0) | Err(_)  => return Err(OptionsError::InvalidRate(input)),
+                Ok(number)      => number,
+            },
+            None => 10,
+        };
+
+        let duration = match matches.opt_str("duration") {
+            Some(input) => parse_interval(&input).ok_or(OptionsError::InvalidBenchDuration(input))?,
+            None        => Duration::from_secs(10),
+        };
+
+        Ok(Load { rate, duration })
+    }
+
+    /// Works out which NAT64 prefix to synthesise AAAA records with, which
+    /// is the well-known one if the option is given without a prefix.
+    fn deduce_dns64_prefix(matches: &getopts::Matches) -> Result<Option<Prefix>, OptionsError> {
+        if ! matches.opt_present("dns64-synthesize") {
+            return Ok(None);
+        }
+
+        match matches.opt_str("dns64-synthesize") {
+            Some(input) => input.parse().map(Some).map_err(|_| OptionsError::InvalidDns64Prefix(input)),
+            None        => Ok(Some(Prefix::WELL_KNOWN)),
+        }
+    }
+
+    /// Works out which sections to print, which is every one of them
+    /// unless any are picked, in which case it is only those.
+    fn deduce_sections(matches: &getopts::Matches) -> Sections {
+        let sections = Sections {
+            question:   matches.opt_present("question"),
+            answer:     matches.opt_present("answer-only"),
+            authority:  matches.opt_present("authority"),
+            additional: matches.opt_present("additional"),
+        };
+
+        if sections == (Sections { question: false, answer: false, authority: false, additional: false }) {
+            Sections::all()
         }
         else {
-            Ok(Self::SendAndHide)
+            sections
+        }
+    }
+
+    fn deduce_parallel(matches: &getopts::Matches) -> Result<Option<usize>, OptionsError> {
+        match matches.opt_str("parallel") {
+            Some(input) => match input.parse() {
+                Ok(0) | Err(_)  => Err(OptionsError::InvalidParallel(input)),
+                Ok(number)      => Ok(Some(number)),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+
+impl RequestGenerator {
+    fn deduce(matches: getopts::Matches) -> Result<Self, OptionsError> {
+        let edns = UseEDNS::deduce(&matches)?;
+        let txids = Txids::deduce(&matches)?;
+        let protocol_tweaks = ProtocolTweaks::deduce(&matches)?;
+        let randomise_case = ! matches.opt_present("no-0x20");
+        let receive_window = match matches.opt_str("udp-window") {
+            Some(input)  => parse_interval(&input).ok_or(OptionsError::InvalidUdpWindow(input))?,
+            None         => Duration::ZERO,
+        };
+        let timeout = match matches.opt_str("timeout") {
+            Some(input)  => parse_interval(&input).filter(|t| ! t.is_zero()).ok_or(OptionsError::InvalidTimeout(input))?,
+            None         => DEFAULT_TIMEOUT,
+        };
+        let cache = match matches.opt_str("cache-dir") {
+            Some(directory)                       => Some(Rc::new(Caches::new(Some(directory.into())))),
+            None if matches.opt_present("cache")  => Some(Rc::new(Caches::new(None))),
+            None                                  => None,
+        };
+        let wire_log = matches.opt_str("log-wire").map(|path| Arc::new(WireLog::new(path.into())));
+        let debug_wire = matches.opt_present("debug-wire");
+        let hosts = match matches.opt_str("hostsfile") {
+            Some(path)                            => Some(path),
+            None if matches.opt_present("hosts")  => Some(hosts::DEFAULT_PATH.into()),
+            None                                  => None,
+        };
+        let hosts = match hosts {
+            Some(path)  => Some(Arc::new(HostsFile::read(path.clone().into()).map_err(|_| OptionsError::InvalidHostsFile(path))?)),
+            None        => None,
+        };
+        let bootstrap = Self::deduce_bootstrap(&matches)?;
+        let tls = Self::deduce_tls(&matches)?;
+        let inputs = Inputs::deduce(matches)?;
+
+        let encrypted = inputs.transport_types.iter().any(|tt| *tt == TransportType::TLS || *tt == TransportType::HTTPS);
+        if bootstrap != Bootstrap::System && ! encrypted {
+            return Err(OptionsError::BootstrapWithoutEncryption);
+        }
+
+        if tls != TlsSettings::default() && ! encrypted {
+            return Err(OptionsError::CertificatesWithoutEncryption);
+        }
+
+        Ok(Self { inputs, txids, edns, protocol_tweaks, randomise_case, receive_window, timeout, bootstrap, tls, cache, hosts, wire_log, debug_wire })
+    }
+
+    /// Works out how to check the certificates of encrypted resolvers,
+    /// reading in the extra certificate authorities to trust, if any.
+    fn deduce_tls(matches: &getopts::Matches) -> Result<TlsSettings, OptionsError> {
+        let insecure = matches.opt_present("insecure");
+
+        let ca_certificates = match matches.opt_str("cafile") {
+            Some(_) if insecure  => return Err(OptionsError::InsecureWithCaFile),
+            Some(path)           => std::fs::read(&path).ok().and_then(|pem| certificates_from_pem(&pem))
+                                        .ok_or(OptionsError::InvalidCaFile(path))?,
+            None                 => Vec::new(),
+        };
+
+        let pins = matches.opt_strs("pin").into_iter()
+                          .map(|input| parse_pin(&input).ok_or(OptionsError::InvalidPin(input)))
+                          .collect::<Result<_, _>>()?;
+
+        let identity = match (matches.opt_str("cert"), matches.opt_str("key")) {
+            (Some(cert), Some(key)) => {
+                let cert_pem = std::fs::read(&cert).map_err(|_| OptionsError::InvalidClientCertificate(cert.clone()))?;
+                let key_pem = std::fs::read(&key).map_err(|_| OptionsError::InvalidClientKey(key.clone()))?;
+
+                match ClientIdentity::from_pem(&cert_pem, &key_pem) {
+                    Ok(identity)                            => Some(identity),
+                    Err(IdentityError::InvalidCertificate)  => return Err(OptionsError::InvalidClientCertificate(cert)),
+                    Err(IdentityError::InvalidKey)          => return Err(OptionsError::InvalidClientKey(key)),
+                    Err(IdentityError::KeyMismatch)         => return Err(OptionsError::ClientKeyMismatch),
+                }
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(OptionsError::CertificateWithoutKey);
+            }
+            (None, None) => {
+                None
+            }
+        };
+
+        Ok(TlsSettings { insecure, ca_certificates, pins, identity })
+    }
+
+    /// Works out how to look up the host names of encrypted resolvers: by
+    /// asking a nameserver, which can be one of the presets, or not at all
+    /// when their address is given.
+    fn deduce_bootstrap(matches: &getopts::Matches) -> Result<Bootstrap, OptionsError> {
+        match (matches.opt_str("bootstrap"), matches.opt_str("resolver-ip")) {
+            (Some(_), Some(_)) => {
+                Err(OptionsError::BootstrapWithResolverIp)
+            }
+            (Some(nameserver), None) => {
+                let nameserver = presets::find(&nameserver).map_or(nameserver, |p| p.addresses[0].into());
+                Ok(Bootstrap::Nameserver(nameserver))
+            }
+            (None, Some(input)) => {
+                let address = input.parse::<IpAddr>().map_err(|_| OptionsError::InvalidResolverIp(input))?;
+                Ok(Bootstrap::Addresses(vec![ address ]))
+            }
+            (None, None) => {
+                Ok(Bootstrap::System)
+            }
+        }
+    }
+}
+
+
+impl Inputs {
+
+    /// Parses one line of a file of queries, which has a domain followed
+    /// by an optional type and class, written the same way as they would be
+    /// on the command line but in any case. Blank lines and lines starting
+    /// with `#` have no queries, so they return nothing.
+    pub fn from_batch_line(line: &str) -> Result<Option<Self>, OptionsError> {
+        let mut words = line.split_whitespace();
+        let mut inputs = Self::default();
+
+        match words.next() {
+            Some(word) if ! word.starts_with('#') => inputs.add_domain(word)?,
+            _                                     => return Ok(None),
+        }
+
+        for word in words.map(str::to_uppercase) {
+            if let Some(class) = inputs.parse_class_name(&word) {
+                inputs.classes.push(class);
+            }
+            else {
+                inputs.add_type(&word)?;
+            }
+        }
+
+        Ok(Some(inputs))
+    }
+
+    fn deduce(mut matches: getopts::Matches) -> Result<Self, OptionsError> {
+        let mut inputs = Self::default();
+        inputs.load_transport_types(&matches);
+        inputs.load_named_args(&matches)?;
+        let reversing = matches.opt_present("reverse");
+        let shortcut = if inputs.domains.is_empty() { Self::take_shortcut(&mut matches) } else { None };
+        inputs.load_free_args(matches)?;
+
+        // Reverse lookups are for PTR records unless told otherwise.
+        if reversing && inputs.types.is_empty() {
+            inputs.types.push(qtype!(PTR));
+        }
+
+        // Shortcuts are for CH TXT records unless told otherwise.
+        if let Some(qname) = shortcut {
+            inputs.domains.insert(0, dns::Name::from_unicode(qname).unwrap());
+
+            if inputs.types.is_empty() {
+                inputs.types.push(qtype!(TXT));
+            }
+
+            if inputs.classes.is_empty() {
+                inputs.classes.push(QClass::CH);
+            }
+        }
+
+        inputs.load_fallbacks();
+        Ok(inputs)
+    }
+
+    /// Checks for a shortcut for one of the queries that nameservers answer
+    /// with the software they run or the server that answered, returning
+    /// the name it stands for. It has to be the only free argument apart
+    /// from nameservers, so a query such as `dog id NS` is still for the
+    /// domain it looks like.
+    fn take_shortcut(matches: &mut getopts::Matches) -> Option<&'static str> {
+        if ! matches.free.iter().skip(1).all(|arg| arg.starts_with('@')) {
+            return None;
+        }
+
+        let qname = match matches.free.first().map(String::as_str) {
+            Some("version")   => "version.bind",
+            Some("hostname")  => "hostname.bind",
+            Some("id")        => "id.server",
+            _                 => return None,
+        };
+
+        let _ = matches.free.remove(0);
+        Some(qname)
+    }
+
+    fn load_transport_types(&mut self, matches: &getopts::Matches) {
+        if matches.opt_present("https") {
+            self.transport_types.push(TransportType::HTTPS);
+        }
+
+        if matches.opt_present("tls") {
+            self.transport_types.push(TransportType::TLS);
+        }
+
+        if matches.opt_present("tcp") {
+            self.transport_types.push(TransportType::TCP);
+        }
+
+        if matches.opt_present("udp") {
+            self.transport_types.push(TransportType::UDP);
+        }
+    }
+
+    fn load_named_args(&mut self, matches: &getopts::Matches) -> Result<(), OptionsError> {
+        for domain in matches.opt_strs("query") {
+            self.add_domain(&domain)?;
+        }
+
+        for address in matches.opt_strs("reverse") {
+            self.add_reverse(&address)?;
+        }
+
+        for qtype in matches.opt_strs("type") {
+            self.add_type(&qtype)?;
+        }
+
+        for ns in matches.opt_strs("nameserver") {
+            self.add_nameserver(&ns)?;
+        }
+
+        for qclass in matches.opt_strs("class") {
+            self.add_class(&qclass)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_type(&mut self, input: &str) -> Result<(), OptionsError> {
+        if input == "OPT" {
+            return Err(OptionsError::QueryTypeOPT);
+        }
+
+        let type_number = find_qtype_number(input)
+            .or_else(|| find_other_qtype_number(input))
+            .or_else(|| input.parse().ok());
+
+        match type_number {
+            Some(qtype)  => {
+                self.types.push(qtype);
+                Ok(())
+            }
+            None         => Err(OptionsError::InvalidQueryType(input.into())),
+        }
+    }
+
+    fn add_domain(&mut self, input: &str) -> Result<(), OptionsError> {
+        match dns::Name::from_unicode(input) {
+            Ok(domain) => {
+                self.domains.push(domain);
+                Ok(())
+            }
+            Err(_) => {
+                Err(OptionsError::InvalidDomain(input.into()))
+            }
+        }
+    }
+
+    fn add_reverse(&mut self, input: &str) -> Result<(), OptionsError> {
+        match input.parse() {
+            Ok(address) => {
+                self.domains.push(PTR::reverse_name(address));
+                Ok(())
+            }
+            Err(_) => {
+                Err(OptionsError::InvalidReverseAddress(input.into()))
+            }
+        }
+    }
+
+    fn add_nameserver(&mut self, input: &str) -> Result<(), OptionsError> {
+        self.resolvers.push(Resolver::Specified(input.into()));
+        Ok(())
+    }
+
+    fn parse_class_name(&self, input: &str) -> Option<QClass> {
+        match input {
+            "IN"  => Some(QClass::IN),
+            "CH"  => Some(QClass::CH),
+            "HS"  => Some(QClass::HS),
+            _     => None,
+        }
+    }
+
+    fn add_class(&mut self, input: &str) -> Result<(), OptionsError> {
+        let qclass = self.parse_class_name(input)
+            .or_else(|| input.parse().ok().map(QClass::Other));
+
+        match qclass {
+            Some(class)  => {
+                self.classes.push(class);
+                Ok(())
+            }
+            None         => Err(OptionsError::InvalidQueryClass(input.into())),
+        }
+    }
+
+    fn load_free_args(&mut self, matches: getopts::Matches) -> Result<(), OptionsError> {
+        for a in matches.free {
+            if let Some(nameserver) = a.strip_prefix('@') {
+                trace!("Got nameserver -> {:?}", nameserver);
+                self.add_nameserver(nameserver)?;
+            }
+            else if a.chars().all(char::is_uppercase) {
+                if let Some(class) = self.parse_class_name(&a) {
+                    trace!("Got qclass -> {:?}", &a);
+                    self.classes.push(class);
+                }
+                else {
+                    trace!("Got qtype -> {:?}", &a);
+                    self.add_type(&a)?;
+                }
+            }
+            else {
+                trace!("Got domain -> {:?}", &a);
+                self.add_domain(&a)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_fallbacks(&mut self) {
+        if self.types.is_empty() {
+            self.types.push(qtype!(A));
+        }
+
+        if self.classes.is_empty() {
+            self.classes.push(QClass::IN);
+        }
+
+        if self.resolvers.is_empty() {
+            self.resolvers.push(Resolver::SystemDefault);
+        }
+
+        if self.transport_types.is_empty() {
+            self.transport_types.push(TransportType::Automatic);
+        }
+    }
+}
+
+
+impl Txids {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        match (matches.opt_str("txid"), matches.opt_str("txid-seed")) {
+            (Some(_), Some(_)) => {
+                Err(OptionsError::TxidWithSeed)
+            }
+            (Some(starting_txid), None) => {
+                match starting_txid.parse() {
+                    Ok(start)  => Ok(Self::new(TxidStrategy::Sequence(start))),
+                    Err(_)     => Err(OptionsError::InvalidTxid(starting_txid)),
+                }
+            }
+            (None, Some(seed)) => {
+                match seed.parse() {
+                    Ok(seed)  => Ok(Self::new(TxidStrategy::Seeded(seed))),
+                    Err(_)    => Err(OptionsError::InvalidTxidSeed(seed)),
+                }
+            }
+            (None, None) => {
+                Ok(Self::new(TxidStrategy::Random))
+            }
         }
     }
-}
+}
+
+
+impl TrustAnchors {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        match matches.opt_str("trust-anchor") {
+            Some(path) if matches.opt_present("track-anchors")  => Ok(Self::Tracked(path.into())),
+            Some(path)                                          => Ok(Self::File(path.into())),
+            None if matches.opt_present("track-anchors")        => Err(OptionsError::TrackingWithoutAnchorFile),
+            None                                                => Ok(Self::BuiltIn),
+        }
+    }
+}
+
+
+impl Expectations {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        let values = matches.opt_strs("expect");
+
+        let rcode = match matches.opt_str("expect-rcode") {
+            Some(input) => Some(parse_rcode(&input).ok_or(OptionsError::InvalidExpectedRcode(input))?),
+            None        => None,
+        };
+
+        let ttl_min = match matches.opt_str("expect-ttl-min") {
+            Some(input) => Some(input.parse().map_err(|_| OptionsError::InvalidExpectedTTL(input))?),
+            None        => None,
+        };
+
+        Ok(Self { values, rcode, ttl_min })
+    }
+}
+
+
+impl OutputFormat {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        if matches.opt_present("short") {
+            let summary_format = TextFormat::deduce(matches)?;
+            Ok(Self::Short(summary_format))
+        }
+        else if matches.opt_present("json") {
+            Ok(Self::JSON)
+        }
+        else if matches.opt_present("yaml") {
+            Ok(Self::YAML)
+        }
+        else if matches.opt_present("csv") {
+            Ok(Self::CSV)
+        }
+        else if matches.opt_present("dig") {
+            Ok(Self::Dig)
+        }
+        else if matches.opt_present("hex") {
+            Ok(Self::Hex)
+        }
+        else if matches.opt_present("raw") {
+            Ok(Self::Raw)
+        }
+        else if matches.opt_present("zone-format") {
+            Ok(Self::Zone)
+        }
+        else {
+            let use_colours = UseColours::deduce(matches);
+            let summary_format = TextFormat::deduce(matches)?;
+            Ok(Self::Text(use_colours, summary_format))
+        }
+    }
+}
+
+
+impl UseColours {
+    fn deduce(matches: &getopts::Matches) -> Self {
+        match matches.opt_str("color").or_else(|| matches.opt_str("colour")).unwrap_or_default().as_str() {
+            "automatic" | "auto" | ""  => Self::Automatic,
+            "always"    | "yes"        => Self::Always,
+            "never"     | "no"         => Self::Never,
+            otherwise => {
+                warn!("Unknown colour setting {:?}", otherwise);
+                Self::Automatic
+            },
+        }
+    }
+}
+
+
+impl TextFormat {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        let format_durations = ! matches.opt_present("seconds");
+        let unicode_names = ! matches.opt_present("ascii");
+        let concat_txt = matches.opt_present("txt-concat");
+        let ttl_format = TtlFormat::deduce(matches, format_durations)?;
+        let catalog = Self::deduce_catalog(matches)?;
+        Ok(Self { format_durations, unicode_names, concat_txt, ttl_format, catalog })
+    }
+
+    /// Picks the language to print in, which comes from a catalog file
+    /// if the user gives one, or else from the locale environment
+    /// variables unless the user gives a locale.
+    fn deduce_catalog(matches: &getopts::Matches) -> Result<&'static locale::Catalog, OptionsError> {
+        if let Some(path) = matches.opt_str("catalog") {
+            return locale::read(path.as_ref()).map_err(|e| OptionsError::InvalidCatalog(path, e.to_string()));
+        }
+
+        match matches.opt_str("locale") {
+            Some(lang)  => locale::find(&lang).ok_or(OptionsError::InvalidLocale(lang)),
+            None        => Ok(locale::from_environment(|name| env::var(name).ok())),
+        }
+    }
+}
+
+
+impl TtlFormat {
+
+    /// Works out how to display TTLs, which follows whether durations are
+    /// being formatted unless the user says otherwise.
+    fn deduce(matches: &getopts::Matches, format_durations: bool) -> Result<Self, OptionsError> {
+        match matches.opt_str("ttl-format").as_deref() {
+            Some("raw")               => Ok(Self::Raw),
+            Some("human")             => Ok(Self::Human),
+            Some("expiry")            => Ok(Self::Expiry),
+            Some(otherwise)           => Err(OptionsError::InvalidTtlFormat(otherwise.into())),
+            None if format_durations  => Ok(Self::Human),
+            None                      => Ok(Self::Raw),
+        }
+    }
+}
+
+
+impl UseEDNS {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        if let Some(edns) = matches.opt_str("edns") {
+            match edns.as_str() {
+                "disable" | "off"  => Ok(Self::Disable),
+                "hide"             => Ok(Self::SendAndHide),
+                "show"             => Ok(Self::SendAndShow),
+                oh                 => Err(OptionsError::InvalidEDNS(oh.into())),
+            }
+        }
+        else {
+            Ok(Self::SendAndHide)
+        }
+    }
+}
+
+
+impl ProtocolTweaks {
+    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
+        let mut tweaks = Self::default();
+
+        if let Some(tweak_strs) = matches.opt_str("Z") {
+            for tweak_str in tweak_strs.split(',') {
+                match tweak_str {
+                    "authentic"  => { tweaks.set_authentic_flag = true; },
+                    otherwise    => return Err(OptionsError::InvalidTweak(otherwise.into())),
+                }
+            }
+        }
+
+        if matches.opt_present("ad") {
+            tweaks.set_authentic_flag = true;
+        }
+
+        if matches.opt_present("cd") {
+            tweaks.set_checking_disabled = true;
+        }
+
+        if matches.opt_present("dnssec") {
+            tweaks.set_dnssec_ok = true;
+        }
+
+        if matches.opt_present("norecurse") {
+            tweaks.clear_recursion_desired = true;
+        }
+
+        if matches.opt_present("multi-question") {
+            tweaks.combine_questions = true;
+        }
+
+        Ok(tweaks)
+    }
+}
+
+
+/// The result of the `Options::getopts` function.
+#[derive(PartialEq, Debug)]
+pub enum OptionsResult {
+
+    /// The options were parsed successfully.
+    Ok(Box<Options>),
+
+    /// There was an error (from `getopts`) parsing the arguments.
+    InvalidOptionsFormat(getopts::Fail),
+
+    /// There was an error with the combination of options the user selected.
+    InvalidOptions(OptionsError),
+
+    /// Can’t run any checks because there’s help to display!
+    Help(HelpReason, UseColours),
+
+    /// One of the arguments was `--version`, to display the version number.
+    Version(UseColours),
+
+    /// One of the arguments was `--list-resolvers`, to display the public
+    /// resolvers that can be picked by name.
+    ListResolvers,
+
+    /// The command was `completions`, to print the completion script for
+    /// the given shell.
+    Completions(Shell),
+}
+
+/// The reason that help is being displayed. If it’s for the `--help` flag,
+/// then we shouldn’t return an error exit status.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum HelpReason {
+
+    /// Help was requested with the `--help` flag.
+    Flag,
+
+    /// There were no domains being queried, so display help instead.
+    /// Unlike `dig`, we don’t implicitly search for the root domain.
+    NoDomains,
+}
+
+/// Something wrong with the combination of options the user has picked.
+#[derive(PartialEq, Debug)]
+pub enum OptionsError {
+    InvalidDomain(String),
+    InvalidEDNS(String),
+    InvalidTtlFormat(String),
+    InvalidLocale(String),
+    InvalidCatalog(String, String),
+    MissingShell,
+    InvalidShell(String),
+    InvalidQueryType(String),
+    InvalidQueryClass(String),
+    InvalidReverseAddress(String),
+    InvalidTxid(String),
+    InvalidTxidSeed(String),
+    TxidWithSeed,
+    InvalidTweak(String),
+    QueryTypeOPT,
+    TraceOverEncryption,
+    TraceWithNameserver,
+    CommandWhileTracing(&'static str),
+    CommandFormat(&'static str),
+    MissingDomain(&'static str, &'static str),
+    ValidateWhileTracing,
+    ValidateWithoutEDNS,
+    DnssecWithoutEDNS,
+    MultiQuestionWithoutQueries,
+    InvalidUdpWindow(String),
+    InvalidTimeout(String),
+    InvalidResolverIp(String),
+    BootstrapWithResolverIp,
+    BootstrapWithoutEncryption,
+    InvalidCaFile(String),
+    InsecureWithCaFile,
+    InvalidPin(String),
+    CertificatesWithoutEncryption,
+    InvalidClientCertificate(String),
+    InvalidClientKey(String),
+    ClientKeyMismatch,
+    CertificateWithoutKey,
+    AnchorsWithoutValidate,
+    TrackingWithoutAnchorFile,
+    MissingZoneFile,
+    DiffWhileTracing,
+    BytesWithoutQueries,
+    MissingMessageFile,
+    StdinWithoutDecode,
+    MissingCaptureFile,
+    MissingCompareFiles,
+    IgnoreTtlWithoutCompare,
+    InvalidParallel(String),
+    BatchWithCommand,
+    BatchWithDomains,
+    BatchWhileTracing,
+    InvalidWatchInterval(String),
+    WatchWithCommand,
+    PropagateFormat,
+    MissingSrvService,
+    MissingFcrdnsAddress,
+    InvalidFcrdnsAddress(String),
+    Nat64WithDomains,
+    InvalidDns64Prefix(String),
+    Dns64WithoutQueries,
+    SoaCheckOverEncryption,
+    DelegationOverEncryption,
+    DelegationWithNameserver,
+    StatsWithoutQueries,
+    AuthoritativeWithoutQueries,
+    AuthoritativeOverEncryption,
+    InterpretWithoutQueries,
+    SectionsWithoutQueries,
+    SectionsFormat,
+    SortWithoutQueries,
+    InvalidExpectedRcode(String),
+    InvalidExpectedTTL(String),
+    ExpectWithoutQueries,
+    MissingQueryFile,
+    InvalidRate(String),
+    InvalidBenchDuration(String),
+    LoadWithoutBench,
+    InvalidCount(String),
+    CountWithoutPing,
+    CacheWithoutQueries,
+    WireLogWithoutQueries,
+    InvalidHostsFile(String),
+    HostsWithoutQueries,
+    InvalidProbeAddress(String),
+    ProbeWithCommand,
+    InvalidListenAddress(String),
+    ListenWithoutServe,
+    ServeWithDomains,
+    ZoneWithoutServe,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDomain(domain)  => write!(f, "Invalid domain name {:?}", domain),
+            Self::InvalidEDNS(edns)      => write!(f, "Invalid EDNS setting {:?}", edns),
+            Self::InvalidTtlFormat(tf)   => write!(f, "Invalid TTL format {:?}", tf),
+            Self::InvalidLocale(lang)    => write!(f, "No translation for locale {:?}", lang),
+            Self::InvalidCatalog(path, e) => write!(f, "Could not read the message catalog {:?}: {}", path, e),
+            Self::MissingShell           => write!(f, "Completions need a shell (bash, zsh, fish, powershell)"),
+            Self::InvalidShell(shell)    => write!(f, "No completions for shell {:?} (bash, zsh, fish, powershell)", shell),
+            Self::InvalidQueryType(qt)   => write!(f, "Invalid query type {:?}", qt),
+            Self::InvalidQueryClass(qc)  => write!(f, "Invalid query class {:?}", qc),
+            Self::InvalidReverseAddress(a) => write!(f, "Invalid IP address to reverse {:?}", a),
+            Self::InvalidTxid(txid)      => write!(f, "Invalid transaction ID {:?}", txid),
+            Self::InvalidTxidSeed(seed)  => write!(f, "Invalid transaction ID seed {:?}", seed),
+            Self::TxidWithSeed           => write!(f, "Cannot both set the transaction ID and seed it"),
+            Self::InvalidTweak(tweak)    => write!(f, "Invalid protocol tweak {:?}", tweak),
+            Self::QueryTypeOPT           => write!(f, "OPT request is sent by default (see -Z flag)"),
+            Self::TraceOverEncryption    => write!(f, "Cannot trace over TLS or HTTPS, as root servers only speak plain DNS"),
+            Self::TraceWithNameserver    => write!(f, "Cannot specify a nameserver when tracing, as traces start from the root servers"),
+            Self::CommandWhileTracing(activity) => write!(f, "Cannot trace, validate, or chase when {}", activity),
+            Self::CommandFormat(activity) => write!(f, "Can only print text, short, JSON, or YAML output when {}", activity),
+            Self::MissingDomain(purpose, usage) => write!(f, "Missing domain to {} ({})", purpose, usage),
+            Self::ValidateWhileTracing   => write!(f, "Cannot validate DNSSEC signatures while tracing"),
+            Self::ValidateWithoutEDNS    => write!(f, "Cannot validate DNSSEC signatures with EDNS disabled"),
+            Self::DnssecWithoutEDNS      => write!(f, "Cannot set the DO bit with EDNS disabled, as it goes in the OPT record"),
+            Self::MultiQuestionWithoutQueries => write!(f, "Can only ask several questions at once when looking up domains, without tracing, validating, or chasing"),
+            Self::InvalidUdpWindow(input) => write!(f, "Invalid time to listen for UDP responses {:?} (such as 200ms)", input),
+            Self::InvalidTimeout(input)   => write!(f, "Invalid time to wait for a response {:?} (such as 2s)", input),
+            Self::InvalidResolverIp(input) => write!(f, "Invalid resolver IP address {:?}", input),
+            Self::BootstrapWithResolverIp => write!(f, "Cannot both look up the resolver with a bootstrap nameserver and give its address"),
+            Self::BootstrapWithoutEncryption => write!(f, "Bootstrapping only applies to TLS or HTTPS resolvers (see -S and -H)"),
+            Self::InvalidCaFile(path)    => write!(f, "Could not read any certificates from {:?}", path),
+            Self::InsecureWithCaFile     => write!(f, "Cannot both trust extra certificate authorities and skip checking certificates"),
+            Self::InvalidPin(input)      => write!(f, "Invalid certificate pin {:?} (such as sha256/BASE64)", input),
+            Self::CertificatesWithoutEncryption => write!(f, "Certificate options only apply to TLS or HTTPS resolvers (see -S and -H)"),
+            Self::InvalidClientCertificate(path) => write!(f, "Could not read a client certificate from {:?}", path),
+            Self::InvalidClientKey(path) => write!(f, "Could not read a private key from {:?}", path),
+            Self::ClientKeyMismatch      => write!(f, "The private key does not belong to the client certificate"),
+            Self::CertificateWithoutKey  => write!(f, "Client certificates need both a certificate and a key (see --cert and --key)"),
+            Self::AnchorsWithoutValidate => write!(f, "Trust anchors are only used when validating (see --validate)"),
+            Self::TrackingWithoutAnchorFile => write!(f, "Cannot track trust anchors without a file to keep them in (see --trust-anchor)"),
+            Self::MissingZoneFile        => write!(f, "Missing zone file to compare against (dog diff FILE @SERVER)"),
+            Self::DiffWhileTracing       => write!(f, "Cannot compare a zone file while tracing or validating"),
+            Self::BytesWithoutQueries    => write!(f, "Can only display the bytes of responses to queries"),
+            Self::MissingMessageFile     => write!(f, "Missing file containing the message to decode (dog decode FILE)"),
+            Self::StdinWithoutDecode     => write!(f, "Can only read from standard input when decoding (dog decode --stdin)"),
+            Self::MissingCompareFiles    => write!(f, "Missing files containing the messages to compare (dog compare FILE FILE)"),
+            Self::IgnoreTtlWithoutCompare => write!(f, "Can only ignore TTLs when comparing messages"),
+            Self::MissingCaptureFile     => write!(f, "Missing packet capture file to read (dog pcap FILE)"),
+            Self::InvalidParallel(num)   => write!(f, "Invalid number of queries to send at once {:?}", num),
+            Self::BatchWithCommand       => write!(f, "Cannot read queries from a file while running another command"),
+            Self::BatchWithDomains       => write!(f, "Cannot give domains on the command line when reading queries from a file"),
+            Self::BatchWhileTracing      => write!(f, "Cannot trace queries read from a file"),
+            Self::InvalidWatchInterval(i) => write!(f, "Invalid interval to watch on {:?} (such as 30s, 5m, or 1h)", i),
+            Self::WatchWithCommand       => write!(f, "Cannot watch while running another command or reading queries from a file"),
+            Self::PropagateFormat        => write!(f, "Can only check propagation with text, short, JSON, YAML, or CSV output"),
+            Self::MissingSrvService      => write!(f, "Missing service and protocol to look up (dog srv SERVICE PROTOCOL DOMAIN)"),
+            Self::MissingFcrdnsAddress   => write!(f, "Missing address to check the reverse DNS of (dog fcrdns ADDRESS)"),
+            Self::InvalidFcrdnsAddress(a) => write!(f, "Invalid IP address to check the reverse DNS of {:?}", a),
+            Self::Nat64WithDomains       => write!(f, "Cannot give domains when discovering the NAT64 prefix, as it always asks for ipv4only.arpa"),
+            Self::InvalidDns64Prefix(p)  => write!(f, "Invalid NAT64 prefix {:?} (such as 64:ff9b::/96)", p),
+            Self::Dns64WithoutQueries    => write!(f, "Can only synthesise DNS64 answers to queries, not when tracing, validating, or running a command"),
+            Self::SoaCheckOverEncryption => write!(f, "Cannot check SOA serials over TLS or HTTPS, as authoritative nameservers only speak plain DNS"),
+            Self::DelegationOverEncryption => write!(f, "Cannot check a delegation over TLS or HTTPS, as root servers only speak plain DNS"),
+            Self::DelegationWithNameserver => write!(f, "Cannot check a delegation with a nameserver, as it starts from the root servers"),
+            Self::AuthoritativeWithoutQueries => write!(f, "Can only send queries to an authoritative nameserver, not when tracing or running a command"),
+            Self::AuthoritativeOverEncryption => write!(f, "Cannot send queries to an authoritative nameserver over TLS or HTTPS, as they only speak plain DNS"),
+            Self::StatsWithoutQueries    => write!(f, "Can only print statistics for queries, not when tracing or running a command"),
+            Self::InterpretWithoutQueries => write!(f, "Can only interpret the answers to queries, not when tracing or running a command"),
+            Self::SectionsWithoutQueries => write!(f, "Can only pick which sections of responses to print, not when tracing or running a command"),
+            Self::SectionsFormat         => write!(f, "Can only pick which sections to print in the text, JSON, YAML, zone, and dig output"),
+            Self::SortWithoutQueries     => write!(f, "Can only sort the records in responses to queries, not when tracing or running a command"),
+            Self::InvalidExpectedRcode(r) => write!(f, "Invalid status to expect {:?} (such as NOERROR or NXDOMAIN)", r),
+            Self::InvalidExpectedTTL(ttl) => write!(f, "Invalid minimum TTL to expect {:?}", ttl),
+            Self::ExpectWithoutQueries   => write!(f, "Can only check the responses to queries, not when tracing or running a command"),
+            Self::MissingQueryFile       => write!(f, "Missing file of queries to send (dog bench FILE @SERVER)"),
+            Self::InvalidRate(rate)      => write!(f, "Invalid number of queries to send each second {:?}", rate),
+            Self::InvalidBenchDuration(d) => write!(f, "Invalid duration to benchmark for {:?} (such as 30s, 5m, or 1h)", d),
+            Self::LoadWithoutBench       => write!(f, "The query rate and duration are only used when benchmarking (dog bench FILE)"),
+            Self::InvalidCount(count)    => write!(f, "Invalid number of queries to send {:?}", count),
+            Self::CountWithoutPing       => write!(f, "The number of queries is only used when pinging (dog ping)"),
+            Self::CacheWithoutQueries    => write!(f, "Can only cache the responses to queries, not when decoding, comparing, benchmarking, or pinging"),
+            Self::WireLogWithoutQueries  => write!(f, "Can only log messages that get sent, not when decoding or comparing"),
+            Self::InvalidHostsFile(path) => write!(f, "Could not read the hosts file {:?}", path),
+            Self::HostsWithoutQueries    => write!(f, "Can only answer queries from a hosts file, not when tracing, validating, decoding, comparing, benchmarking, or pinging"),
+            Self::InvalidProbeAddress(a) => write!(f, "Invalid address to serve metrics on {:?} (such as 9153 or 127.0.0.1:9153)", a),
+            Self::ProbeWithCommand       => write!(f, "Cannot probe while running another command or reading queries from a file"),
+            Self::InvalidListenAddress(a) => write!(f, "Invalid address to listen on {:?} (such as 5300 or 127.0.0.1:5300)", a),
+            Self::ListenWithoutServe     => write!(f, "The address to listen on is only used when serving (dog serve --listen ADDRESS)"),
+            Self::ServeWithDomains       => write!(f, "Cannot give domains on the command line when forwarding, as the queries come from clients"),
+            Self::ZoneWithoutServe       => write!(f, "The zone file is only used when serving (dog serve --zone FILE)"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::record::*;
+
+    impl Inputs {
+        fn fallbacks() -> Self {
+            Inputs {
+                domains:         vec![ /* No domains by default */ ],
+                types:           vec![ qtype!(A) ],
+                classes:         vec![ QClass::IN ],
+                resolvers:       vec![ Resolver::SystemDefault ],
+                transport_types: vec![ TransportType::Automatic ],
+            }
+        }
+    }
+
+    impl OptionsResult {
+        fn unwrap(self) -> Options {
+            match self {
+                Self::Ok(o)  => *o,
+                _            => panic!("{:?}", self),
+            }
+        }
+    }
+
+    // help tests
+
+    #[test]
+    fn help() {
+        assert_eq!(Options::getopts(&[ "--help" ]),
+                   OptionsResult::Help(HelpReason::Flag, UseColours::Automatic));
+    }
+
+    #[test]
+    fn help_no_colour() {
+        assert_eq!(Options::getopts(&[ "--help", "--colour=never" ]),
+                   OptionsResult::Help(HelpReason::Flag, UseColours::Never));
+    }
+
+    #[test]
+    fn version() {
+        assert_eq!(Options::getopts(&[ "--version" ]),
+                   OptionsResult::Version(UseColours::Automatic));
+    }
+
+    #[test]
+    fn version_yes_color() {
+        assert_eq!(Options::getopts(&[ "--version", "--color", "always" ]),
+                   OptionsResult::Version(UseColours::Always));
+    }
+
+    #[test]
+    fn list_resolvers() {
+        assert_eq!(Options::getopts(&[ "--list-resolvers" ]),
+                   OptionsResult::ListResolvers);
+    }
+
+    #[test]
+    fn completions() {
+        assert_eq!(Options::getopts(&[ "completions", "zsh" ]),
+                   OptionsResult::Completions(Shell::Zsh));
+    }
+
+    #[test]
+    fn completions_without_shell() {
+        assert_eq!(Options::getopts(&[ "completions" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingShell));
+    }
+
+    #[test]
+    fn completions_for_invalid_shell() {
+        assert_eq!(Options::getopts(&[ "completions", "tcsh" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidShell("tcsh".into())));
+    }
+
+    #[test]
+    fn preset_nameserver() {
+        let options = Options::getopts(&[ "lookup.dog", "@cloudflare", "--https" ]).unwrap();
+        assert_eq!(options.requests.inputs.resolvers, vec![ Resolver::Specified("cloudflare".into()) ]);
+    }
+
+    #[test]
+    fn fail() {
+        assert_eq!(Options::getopts(&[ "--pear" ]),
+                   OptionsResult::InvalidOptionsFormat(getopts::Fail::UnrecognizedOption("pear".into())));
+    }
+
+    #[test]
+    fn empty() {
+        let nothing: Vec<&str> = vec![];
+        assert_eq!(Options::getopts(nothing),
+                   OptionsResult::Help(HelpReason::NoDomains, UseColours::Automatic));
+    }
+
+    #[test]
+    fn an_unrelated_argument() {
+        assert_eq!(Options::getopts(&[ "--time" ]),
+                   OptionsResult::Help(HelpReason::NoDomains, UseColours::Automatic));
+    }
+
+    // query tests
+
+    #[test]
+    fn just_domain() {
+        let options = Options::getopts(&[ "lookup.dog" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn unicode_domain() {
+        let options = Options::getopts(&[ "bücher.example" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "xn--bcher-kva.example".parse().unwrap() ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn just_named_domain() {
+        let options = Options::getopts(&[ "-q", "lookup.dog" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn domain_and_type() {
+        let options = Options::getopts(&[ "lookup.dog", "SOA" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            types:      vec![ qtype!(SOA) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn domain_and_nameserver() {
+        let options = Options::getopts(&[ "lookup.dog", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn domain_and_class() {
+        let options = Options::getopts(&[ "lookup.dog", "CH" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            classes:    vec![ QClass::CH ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn version_shortcut() {
+        let options = Options::getopts(&[ "version", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "version.bind".parse().unwrap() ],
+            types:      vec![ qtype!(TXT) ],
+            classes:    vec![ QClass::CH ],
+            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn hostname_shortcut() {
+        let options = Options::getopts(&[ "hostname", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "hostname.bind".parse().unwrap() ]);
+        assert_eq!(options.requests.inputs.classes, vec![ QClass::CH ]);
+    }
+
+    #[test]
+    fn id_shortcut_with_class() {
+        let options = Options::getopts(&[ "id", "@1.1.1.1", "--class", "IN" ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "id.server".parse().unwrap() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(TXT) ]);
+        assert_eq!(options.requests.inputs.classes, vec![ QClass::IN ]);
+    }
+
+    #[test]
+    fn shortcut_only_first() {
+        let options = Options::getopts(&[ "lookup.dog", "version" ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "lookup.dog".parse().unwrap(), "version".parse().unwrap() ]);
+    }
+
+    #[test]
+    fn shortcut_name_with_type() {
+        let options = Options::getopts(&[ "id", "NS", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "id".parse().unwrap() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(NS) ]);
+        assert_eq!(options.requests.inputs.classes, vec![ QClass::IN ]);
+    }
+
+    #[test]
+    fn shortcut_name_with_domain() {
+        let options = Options::getopts(&[ "hostname", "lookup.dog" ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "hostname".parse().unwrap(), "lookup.dog".parse().unwrap() ]);
+    }
+
+    #[test]
+    fn shortcut_name_with_query() {
+        let options = Options::getopts(&[ "-q", "lookup.dog", "version" ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "lookup.dog".parse().unwrap(), "version".parse().unwrap() ]);
+        assert_eq!(options.requests.inputs.classes, vec![ QClass::IN ]);
+    }
+
+    #[test]
+    fn all_free() {
+        let options = Options::getopts(&[ "lookup.dog", "CH", "NS", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            classes:    vec![ QClass::CH ],
+            types:      vec![ qtype!(NS) ],
+            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn all_parameters() {
+        let options = Options::getopts(&[ "-q", "lookup.dog", "--class", "CH", "--type", "SOA", "--nameserver", "1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            classes:    vec![ QClass::CH ],
+            types:      vec![ qtype!(SOA) ],
+            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn two_types() {
+        let options = Options::getopts(&[ "-q", "lookup.dog", "--type", "SRV", "--type", "AAAA" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            types:      vec![ qtype!(SRV), qtype!(AAAA) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn two_classes() {
+        let options = Options::getopts(&[ "-q", "lookup.dog", "--class", "IN", "--class", "CH" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            classes:    vec![ QClass::IN, QClass::CH ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn all_mixed_1() {
+        let options = Options::getopts(&[ "lookup.dog", "--class", "CH", "SOA", "--nameserver", "1.1.1.1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            classes:    vec![ QClass::CH ],
+            types:      vec![ qtype!(SOA) ],
+            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn all_mixed_2() {
+        let options = Options::getopts(&[ "CH", "SOA", "MX", "IN", "-q", "lookup.dog", "--class", "HS" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            classes:    vec![ QClass::HS, QClass::CH, QClass::IN ],
+            types:      vec![ qtype!(SOA), qtype!(MX) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn all_mixed_3() {
+        let options = Options::getopts(&[ "lookup.dog", "--nameserver", "1.1.1.1", "--nameserver", "1.0.0.1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "lookup.dog".parse().unwrap() ],
+            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()),
+                              Resolver::Specified("1.0.0.1".into()), ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn explicit_numerics() {
+        let options = Options::getopts(&[ "11", "--class", "22", "--type", "33" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "11".parse().unwrap() ],
+            classes:    vec![ QClass::Other(22) ],
+            types:      vec![ 33 ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn reverse_ipv4() {
+        let options = Options::getopts(&[ "-x", "8.8.8.8" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "8.8.8.8.in-addr.arpa".parse().unwrap() ],
+            types:      vec![ qtype!(PTR) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn reverse_ipv6() {
+        let options = Options::getopts(&[ "--reverse", "2001:db8::1" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa".parse().unwrap() ],
+            types:      vec![ qtype!(PTR) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    #[test]
+    fn reverse_with_type() {
+        let options = Options::getopts(&[ "-x", "127.0.0.1", "TXT" ]).unwrap();
+        assert_eq!(options.requests.inputs, Inputs {
+            domains:    vec![ "1.0.0.127.in-addr.arpa".parse().unwrap() ],
+            types:      vec![ qtype!(TXT) ],
+            .. Inputs::fallbacks()
+        });
+    }
+
+    // output format tests
+
+    #[test]
+    fn zone_format() {
+        let options = Options::getopts(&[ "lookup.dog", "--zone-format" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::Zone);
+    }
+
+    #[test]
+    fn json_beats_zone_format() {
+        let options = Options::getopts(&[ "lookup.dog", "--zone-format", "--json" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::JSON);
+    }
+
+    #[test]
+    fn dig() {
+        let options = Options::getopts(&[ "lookup.dog", "--dig" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::Dig);
+    }
+
+    #[test]
+    fn hex() {
+        let options = Options::getopts(&[ "lookup.dog", "--hex" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::Hex);
+    }
+
+    #[test]
+    fn raw() {
+        let options = Options::getopts(&[ "lookup.dog", "--raw" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::Raw);
+    }
+
+    #[test]
+    fn yaml() {
+        let options = Options::getopts(&[ "lookup.dog", "--yaml" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::YAML);
+    }
+
+    #[test]
+    fn csv() {
+        let options = Options::getopts(&[ "lookup.dog", "--csv" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::CSV);
+    }
+
+    #[test]
+    fn json_beats_yaml_and_csv() {
+        let options = Options::getopts(&[ "lookup.dog", "--csv", "--yaml", "--json" ]).unwrap();
+        assert_eq!(options.format, OutputFormat::JSON);
+    }
+
+    #[test]
+    fn ttl_format() {
+        let options = Options::getopts(&[ "lookup.dog", "--ttl-format", "expiry" ]).unwrap();
+        assert!(matches!(options.format, OutputFormat::Text(_, TextFormat { format_durations: true, ttl_format: TtlFormat::Expiry, .. })));
+    }
+
+    #[test]
+    fn seconds_means_raw_ttls() {
+        let options = Options::getopts(&[ "lookup.dog", "--seconds" ]).unwrap();
+        assert!(matches!(options.format, OutputFormat::Text(_, TextFormat { format_durations: false, ttl_format: TtlFormat::Raw, .. })));
+    }
+
+    #[test]
+    fn human_ttls_with_seconds() {
+        let options = Options::getopts(&[ "lookup.dog", "--seconds", "--ttl-format", "human" ]).unwrap();
+        assert!(matches!(options.format, OutputFormat::Text(_, TextFormat { format_durations: false, ttl_format: TtlFormat::Human, .. })));
+    }
+
+    // invalid options tests
+
+    #[test]
+    fn invalid_reverse() {
+        assert_eq!(Options::getopts(&[ "-x", "lookup.dog" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidReverseAddress("lookup.dog".into())));
+    }
+
+    #[test]
+    fn invalid_domain() {
+        let domain = format!("{}.dog", "a".repeat(64));
+        assert_eq!(Options::getopts(&[ &domain ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidDomain(domain)));
+    }
+
+    #[test]
+    fn invalid_ttl_format() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--ttl-format", "dog-years" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidTtlFormat("dog-years".into())));
+    }
+
+    #[test]
+    fn locale() {
+        let options = Options::getopts(&[ "lookup.dog", "--locale", "de_DE.UTF-8" ]).unwrap();
+        assert!(matches!(options.format, OutputFormat::Text(_, TextFormat { catalog, .. }) if catalog.language == "de"));
+    }
+
+    #[test]
+    fn missing_catalog() {
+        let result = Options::getopts(&[ "lookup.dog", "--catalog", "/nonexistent/dog.catalog" ]);
+        assert!(matches!(result, OptionsResult::InvalidOptions(OptionsError::InvalidCatalog(path, _)) if path == "/nonexistent/dog.catalog"));
+    }
+
+    #[test]
+    fn invalid_locale() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--locale", "tlh" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidLocale("tlh".into())));
+    }
+
+    #[test]
+    fn invalid_named_class() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--class", "tubes" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidQueryClass("tubes".into())));
+    }
+
+    #[test]
+    fn invalid_named_type() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--type", "tubes" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidQueryType("tubes".into())));
+    }
+
+    #[test]
+    fn invalid_capsword() {
+        assert_eq!(Options::getopts(&[ "SMH", "lookup.dog" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidQueryType("SMH".into())));
+    }
+
+    #[test]
+    fn invalid_txid() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--txid=0x1234" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidTxid("0x1234".into())));
+    }
+
+    #[test]
+    fn txid() {
+        let options = Options::getopts(&[ "lookup.dog", "--txid=1234" ]).unwrap();
+        assert_eq!(options.requests.txids.strategy, TxidStrategy::Sequence(1234));
+    }
+
+    #[test]
+    fn txid_seed() {
+        let options = Options::getopts(&[ "lookup.dog", "--txid-seed=42" ]).unwrap();
+        assert_eq!(options.requests.txids.strategy, TxidStrategy::Seeded(42));
+    }
+
+    #[test]
+    fn invalid_txid_seed() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--txid-seed=-1" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidTxidSeed("-1".into())));
+    }
+
+    #[test]
+    fn txid_with_seed() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--txid=1", "--txid-seed=2" ]),
+                   OptionsResult::InvalidOptions(OptionsError::TxidWithSeed));
+    }
+
+    #[test]
+    fn trace() {
+        let options = Options::getopts(&[ "lookup.dog", "--trace" ]).unwrap();
+        assert!(options.trace);
+    }
+
+    #[test]
+    fn trace_over_tls() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--trace", "--tls" ]),
+                   OptionsResult::InvalidOptions(OptionsError::TraceOverEncryption));
+    }
+
+    #[test]
+    fn trace_with_nameserver() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--trace", "@1.1.1.1" ]),
+                   OptionsResult::InvalidOptions(OptionsError::TraceWithNameserver));
+    }
+
+    #[test]
+    fn authoritative() {
+        let options = Options::getopts(&[ "lookup.dog", "--authoritative" ]).unwrap();
+        assert!(options.authoritative);
+    }
+
+    #[test]
+    fn authoritative_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--authoritative", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::AuthoritativeWithoutQueries));
+    }
+
+    #[test]
+    fn authoritative_over_https() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--authoritative", "-H", "@https://dns.lookup.dog/dns-query" ]),
+                   OptionsResult::InvalidOptions(OptionsError::AuthoritativeOverEncryption));
+    }
+
+    #[test]
+    fn chase() {
+        let options = Options::getopts(&[ "lookup.dog", "--chase" ]).unwrap();
+        assert!(options.chase);
+        assert!(! options.trace);
+    }
+
+    #[test]
+    fn validate() {
+        let options = Options::getopts(&[ "lookup.dog", "--validate" ]).unwrap();
+        assert!(options.validate);
+        assert!(options.requests.protocol_tweaks.set_dnssec_ok);
+    }
+
+    #[test]
+    fn validate_without_edns() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--validate", "--edns=disable" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ValidateWithoutEDNS));
+    }
+
+    #[test]
+    fn dnssec_flags() {
+        let options = Options::getopts(&[ "lookup.dog", "--dnssec", "--cd", "--ad" ]).unwrap();
+        assert_eq!(options.requests.protocol_tweaks, ProtocolTweaks {
+            set_authentic_flag: true,
+            set_dnssec_ok: true,
+            set_checking_disabled: true,
+            clear_recursion_desired: false,
+            combine_questions: false,
+        });
+    }
+
+    #[test]
+    fn norecurse() {
+        let options = Options::getopts(&[ "lookup.dog", "--norecurse" ]).unwrap();
+        assert!(options.requests.protocol_tweaks.clear_recursion_desired);
+    }
+
+    #[test]
+    fn no_dnssec_flags() {
+        let options = Options::getopts(&[ "lookup.dog" ]).unwrap();
+        assert_eq!(options.requests.protocol_tweaks, ProtocolTweaks::default());
+    }
+
+    #[test]
+    fn dnssec_without_edns() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--dnssec", "--edns=disable" ]),
+                   OptionsResult::InvalidOptions(OptionsError::DnssecWithoutEDNS));
+    }
+
+    #[test]
+    fn udp_window() {
+        let options = Options::getopts(&[ "lookup.dog", "--udp-window", "200ms" ]).unwrap();
+        assert_eq!(options.requests.receive_window, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn invalid_udp_window() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--udp-window", "soon" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidUdpWindow("soon".into())));
+    }
+
+    #[test]
+    fn timeout() {
+        let options = Options::getopts(&[ "lookup.dog", "--timeout", "2s" ]).unwrap();
+        assert_eq!(options.requests.timeout, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn default_timeout() {
+        let options = Options::getopts(&[ "lookup.dog" ]).unwrap();
+        assert_eq!(options.requests.timeout, DEFAULT_TIMEOUT);
+    }
+
+    #[test]
+    fn zero_timeout() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--timeout", "0s" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidTimeout("0s".into())));
+    }
+
+    #[test]
+    fn bootstrap() {
+        let options = Options::getopts(&[ "lookup.dog", "@dns.quad9.net", "--tls", "--bootstrap", "192.0.2.53" ]).unwrap();
+        assert_eq!(options.requests.bootstrap, Bootstrap::Nameserver("192.0.2.53".into()));
+    }
+
+    #[test]
+    fn bootstrap_preset() {
+        let options = Options::getopts(&[ "lookup.dog", "@dns.quad9.net", "--https", "--bootstrap", "google" ]).unwrap();
+        assert_eq!(options.requests.bootstrap, Bootstrap::Nameserver("8.8.8.8".into()));
+    }
+
+    #[test]
+    fn resolver_ip() {
+        let options = Options::getopts(&[ "lookup.dog", "@dns.quad9.net", "--tls", "--resolver-ip", "9.9.9.9" ]).unwrap();
+        assert_eq!(options.requests.bootstrap, Bootstrap::Addresses(vec![ IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)) ]));
+    }
+
+    #[test]
+    fn invalid_resolver_ip() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--resolver-ip", "dns.quad9.net" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidResolverIp("dns.quad9.net".into())));
+    }
+
+    #[test]
+    fn bootstrap_with_resolver_ip() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--bootstrap", "192.0.2.53", "--resolver-ip", "9.9.9.9" ]),
+                   OptionsResult::InvalidOptions(OptionsError::BootstrapWithResolverIp));
+    }
+
+    #[test]
+    fn bootstrap_without_encryption() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--bootstrap", "192.0.2.53" ]),
+                   OptionsResult::InvalidOptions(OptionsError::BootstrapWithoutEncryption));
+    }
+
+    #[test]
+    fn insecure() {
+        let options = Options::getopts(&[ "lookup.dog", "@192.0.2.53", "--tls", "--insecure" ]).unwrap();
+        assert!(options.requests.tls.insecure);
+    }
+
+    #[test]
+    fn pin() {
+        let options = Options::getopts(&[ "lookup.dog", "--tls", "--pin", "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=" ]).unwrap();
+        assert_eq!(options.requests.tls.pins.len(), 1);
+        assert_eq!(&options.requests.tls.pins[0][.. 4], &[ 0xE3, 0xB0, 0xC4, 0x42 ]);
+    }
+
+    #[test]
+    fn invalid_pin() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--pin", "md5/AAAA" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidPin("md5/AAAA".into())));
+    }
+
+    #[test]
+    fn pin_over_https() {
+        let options = Options::getopts(&[ "lookup.dog", "--https", "--pin", "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=" ]).unwrap();
+        assert_eq!(options.requests.tls.pins.len(), 1);
+    }
+
+    #[test]
+    fn missing_cafile() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--cafile", "/nonexistent/ca.pem" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidCaFile("/nonexistent/ca.pem".into())));
+    }
+
+    #[test]
+    fn insecure_with_cafile() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--insecure", "--cafile", "ca.pem" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InsecureWithCaFile));
+    }
+
+    #[test]
+    fn certificates_without_encryption() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--insecure" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CertificatesWithoutEncryption));
+    }
+
+    #[test]
+    fn certificate_without_key() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--cert", "client.pem" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CertificateWithoutKey));
+    }
+
+    #[test]
+    fn missing_client_certificate() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--tls", "--cert", "/nonexistent/client.pem", "--key", "/nonexistent/client.key" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidClientCertificate("/nonexistent/client.pem".into())));
+    }
+
+    #[test]
+    fn multi_question() {
+        let options = Options::getopts(&[ "lookup.dog", "A", "AAAA", "--multi-question" ]).unwrap();
+        assert!(options.requests.protocol_tweaks.combine_questions);
+    }
+
+    #[test]
+    fn multi_question_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--multi-question", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MultiQuestionWithoutQueries));
+    }
+
+    #[test]
+    fn validate_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--validate", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ValidateWhileTracing));
+    }
+
+    #[test]
+    fn trust_anchor_file() {
+        let options = Options::getopts(&[ "lookup.dog", "--validate", "--trust-anchor", "root.xml" ]).unwrap();
+        assert_eq!(options.trust_anchors, TrustAnchors::File("root.xml".into()));
+    }
+
+    #[test]
+    fn tracked_anchors() {
+        let options = Options::getopts(&[ "lookup.dog", "--validate", "--trust-anchor", "root.key", "--track-anchors" ]).unwrap();
+        assert_eq!(options.trust_anchors, TrustAnchors::Tracked("root.key".into()));
+    }
+
+    #[test]
+    fn anchors_without_validate() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--trust-anchor", "root.xml" ]),
+                   OptionsResult::InvalidOptions(OptionsError::AnchorsWithoutValidate));
+    }
+
+    #[test]
+    fn tracking_without_file() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--validate", "--track-anchors" ]),
+                   OptionsResult::InvalidOptions(OptionsError::TrackingWithoutAnchorFile));
+    }
+
+    #[test]
+    fn case_randomised_by_default() {
+        let options = Options::getopts(&[ "lookup.dog" ]).unwrap();
+        assert!(options.requests.randomise_case);
+    }
+
+    #[test]
+    fn no_0x20() {
+        let options = Options::getopts(&[ "lookup.dog", "--no-0x20" ]).unwrap();
+        assert!(! options.requests.randomise_case);
+    }
+
+    #[test]
+    fn diff() {
+        let options = Options::getopts(&[ "diff", "db.lookup.dog", "@127.0.0.1" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Diff(PathBuf::from("db.lookup.dog"))));
+        assert_eq!(options.requests.inputs.domains, vec![]);
+        assert_eq!(options.requests.inputs.resolvers, vec![ Resolver::Specified("127.0.0.1".into()) ]);
+    }
+
+    #[test]
+    fn diff_with_origin() {
+        let options = Options::getopts(&[ "diff", "db.lookup.dog", "lookup.dog", "@127.0.0.1" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Diff(PathBuf::from("db.lookup.dog"))));
+        assert_eq!(options.requests.inputs.domains, vec![ "lookup.dog".parse().unwrap() ]);
+    }
+
+    #[test]
+    fn diff_without_file() {
+        assert_eq!(Options::getopts(&[ "diff" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingZoneFile));
+    }
+
+    #[test]
+    fn diff_while_tracing() {
+        assert_eq!(Options::getopts(&[ "diff", "db.lookup.dog", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::DiffWhileTracing));
+    }
+
+    #[test]
+    fn diff_as_bytes() {
+        assert_eq!(Options::getopts(&[ "diff", "db.lookup.dog", "--raw" ]),
+                   OptionsResult::InvalidOptions(OptionsError::BytesWithoutQueries));
+    }
+
+    #[test]
+    fn decode_as_bytes() {
+        assert_eq!(Options::getopts(&[ "decode", "response.bin", "--hex" ]),
+                   OptionsResult::InvalidOptions(OptionsError::BytesWithoutQueries));
+    }
+
+    #[test]
+    fn decode() {
+        let options = Options::getopts(&[ "decode", "response.bin" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Decode(Input::File(PathBuf::from("response.bin")))));
+    }
+
+    #[test]
+    fn decode_stdin() {
+        let options = Options::getopts(&[ "decode", "--stdin" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Decode(Input::Stdin)));
+    }
+
+    #[test]
+    fn decode_without_file() {
+        assert_eq!(Options::getopts(&[ "decode" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingMessageFile));
+    }
+
+    #[test]
+    fn stdin_without_decode() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--stdin" ]),
+                   OptionsResult::InvalidOptions(OptionsError::StdinWithoutDecode));
+    }
+
+    #[test]
+    fn decode_while_tracing() {
+        assert_eq!(Options::getopts(&[ "decode", "response.bin", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CommandWhileTracing("decoding messages")));
+    }
+
+    #[test]
+    fn log_wire() {
+        let options = Options::getopts(&[ "lookup.dog", "--log-wire", "session.jsonl" ]).unwrap();
+        assert_eq!(options.requests.wire_log, Some(Arc::new(WireLog::new(PathBuf::from("session.jsonl")))));
+    }
+
+    #[test]
+    fn debug_wire() {
+        let options = Options::getopts(&[ "lookup.dog", "--debug-wire" ]).unwrap();
+        assert!(options.requests.debug_wire);
+    }
+
+    #[test]
+    fn debug_wire_when_comparing() {
+        assert_eq!(Options::getopts(&[ "compare", "one.bin", "two.bin", "--debug-wire" ]),
+                   OptionsResult::InvalidOptions(OptionsError::WireLogWithoutQueries));
+    }
+
+    #[test]
+    fn missing_hosts_file() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--hostsfile", "/nonexistent/hosts" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidHostsFile("/nonexistent/hosts".into())));
+    }
+
+    #[test]
+    fn log_wire_when_decoding() {
+        assert_eq!(Options::getopts(&[ "decode", "session.jsonl", "--log-wire", "again.jsonl" ]),
+                   OptionsResult::InvalidOptions(OptionsError::WireLogWithoutQueries));
+    }
+
+    #[test]
+    fn compare() {
+        let options = Options::getopts(&[ "compare", "before.bin", "after.bin", "--ignore-ttl" ]).unwrap();
+        let diff_options = DiffOptions { ignore_ttl: true, .. DiffOptions::default() };
+        assert_eq!(options.command, Some(Command::Compare(PathBuf::from("before.bin"), PathBuf::from("after.bin"), diff_options)));
+    }
+
+    #[test]
+    fn compare_one_file() {
+        assert_eq!(Options::getopts(&[ "compare", "before.bin" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingCompareFiles));
+    }
+
+    #[test]
+    fn ignore_ttl_without_compare() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--ignore-ttl" ]),
+                   OptionsResult::InvalidOptions(OptionsError::IgnoreTtlWithoutCompare));
+    }
+
+    #[test]
+    fn pcap() {
+        let options = Options::getopts(&[ "pcap", "capture.pcap" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Pcap(PathBuf::from("capture.pcap"))));
+    }
+
+    #[test]
+    fn pcap_without_file() {
+        assert_eq!(Options::getopts(&[ "pcap" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingCaptureFile));
+    }
+
+    #[test]
+    fn batch() {
+        let options = Options::getopts(&[ "-f", "queries.txt", "--parallel", "8" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Batch(Input::File(PathBuf::from("queries.txt")))));
+        assert_eq!(options.parallel, Some(8));
+    }
+
+    #[test]
+    fn batch_stdin() {
+        let options = Options::getopts(&[ "--file", "-" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Batch(Input::Stdin)));
+        assert_eq!(options.parallel, None);
+    }
+
+    #[test]
+    fn batch_with_domains() {
+        assert_eq!(Options::getopts(&[ "-f", "queries.txt", "lookup.dog" ]),
+                   OptionsResult::InvalidOptions(OptionsError::BatchWithDomains));
+    }
+
+    #[test]
+    fn batch_with_command() {
+        assert_eq!(Options::getopts(&[ "pcap", "capture.pcap", "-f", "queries.txt" ]),
+                   OptionsResult::InvalidOptions(OptionsError::BatchWithCommand));
+    }
+
+    #[test]
+    fn invalid_parallel() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--parallel", "0" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidParallel("0".into())));
+    }
+
+    #[test]
+    fn propagate() {
+        let options = Options::getopts(&[ "propagate", "lookup.dog", "MX", "@192.0.2.53" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Propagate));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(MX) ]);
+        assert_eq!(options.requests.inputs.resolvers.last(), Some(&Resolver::Specified("192.0.2.53".into())));
+    }
+
+    #[test]
+    fn caa_check() {
+        let options = Options::getopts(&[ "caa-check", "www.lookup.dog", "@192.0.2.53" ]).unwrap();
+        assert_eq!(options.command, Some(Command::CaaCheck));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(CAA) ]);
+    }
+
+    #[test]
+    fn caa_check_without_domain() {
+        assert_eq!(Options::getopts(&[ "caa-check" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("check the CAA records of", "dog caa-check DOMAIN")));
+    }
+
+    #[test]
+    fn caa_check_format() {
+        assert_eq!(Options::getopts(&[ "caa-check", "lookup.dog", "--csv" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CommandFormat("checking CAA records")));
+    }
+
+    #[test]
+    fn srv() {
+        let options = Options::getopts(&[ "srv", "xmpp-client", "tcp", "lookup.dog", "@192.0.2.53" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Srv("xmpp-client".into(), "tcp".into())));
+        assert_eq!(options.requests.inputs.domains, vec![ dns::Name::from_unicode("_xmpp-client._tcp.lookup.dog").unwrap() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(SRV) ]);
+    }
+
+    #[test]
+    fn srv_without_service() {
+        assert_eq!(Options::getopts(&[ "srv", "xmpp-client" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingSrvService));
+    }
+
+    #[test]
+    fn srv_without_domain() {
+        assert_eq!(Options::getopts(&[ "srv", "xmpp-client", "tcp" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("look up the service at", "dog srv SERVICE PROTOCOL DOMAIN")));
+    }
+
+    #[test]
+    fn mx_check() {
+        let options = Options::getopts(&[ "mx-check", "lookup.dog" ]).unwrap();
+        assert_eq!(options.command, Some(Command::MxCheck));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(MX) ]);
+    }
+
+    #[test]
+    fn mx_check_without_domain() {
+        assert_eq!(Options::getopts(&[ "mx-check" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("check the mail exchanges of", "dog mx-check DOMAIN")));
+    }
+
+    #[test]
+    fn fcrdns() {
+        let options = Options::getopts(&[ "fcrdns", "192.0.2.25" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Fcrdns));
+        assert_eq!(options.requests.inputs.domains, vec![ "25.2.0.192.in-addr.arpa".parse().unwrap() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(PTR) ]);
+    }
+
+    #[test]
+    fn fcrdns_reverse_name() {
+        let options = Options::getopts(&[ "fcrdns", "25.2.0.192.in-addr.arpa." ]).unwrap();
+        assert_eq!(options.requests.inputs.domains, vec![ "25.2.0.192.in-addr.arpa".parse().unwrap() ]);
+    }
+
+    #[test]
+    fn fcrdns_invalid_address() {
+        assert_eq!(Options::getopts(&[ "fcrdns", "lookup.dog" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidFcrdnsAddress("lookup.dog".into())));
+    }
+
+    #[test]
+    fn fcrdns_without_address() {
+        assert_eq!(Options::getopts(&[ "fcrdns" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingFcrdnsAddress));
+    }
+
+    #[test]
+    fn wildcard() {
+        let options = Options::getopts(&[ "wildcard", "lookup.dog", "MX" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Wildcard));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(MX) ]);
+    }
+
+    #[test]
+    fn wildcard_without_domain() {
+        assert_eq!(Options::getopts(&[ "wildcard" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("look for a wildcard under", "dog wildcard DOMAIN")));
+    }
+
+    #[test]
+    fn syscmp() {
+        let options = Options::getopts(&[ "syscmp", "lookup.dog" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Syscmp));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(A) ]);
+    }
+
+    #[test]
+    fn syscmp_without_domain() {
+        assert_eq!(Options::getopts(&[ "syscmp" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("compare the system resolver’s answer for", "dog syscmp DOMAIN")));
+    }
+
+    #[test]
+    fn nat64() {
+        let options = Options::getopts(&[ "nat64", "@192.0.2.53" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Nat64));
+        assert_eq!(options.requests.inputs.domains, vec![ nat64::ipv4only_arpa() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(AAAA) ]);
+    }
+
+    #[test]
+    fn nat64_with_domains() {
+        assert_eq!(Options::getopts(&[ "nat64", "lookup.dog" ]),
+                   OptionsResult::InvalidOptions(OptionsError::Nat64WithDomains));
+    }
+
+    #[test]
+    fn dns64_synthesize() {
+        let options = Options::getopts(&[ "lookup.dog", "--dns64-synthesize" ]).unwrap();
+        assert_eq!(options.dns64_prefix, Some(Prefix::WELL_KNOWN));
+    }
+
+    #[test]
+    fn dns64_synthesize_with_prefix() {
+        let options = Options::getopts(&[ "lookup.dog", "--dns64-synthesize=2001:db8:122::/48" ]).unwrap();
+        assert_eq!(options.dns64_prefix, Some("2001:db8:122::/48".parse().unwrap()));
+    }
+
+    #[test]
+    fn dns64_synthesize_invalid_prefix() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--dns64-synthesize=64:ff9b::/80" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidDns64Prefix("64:ff9b::/80".into())));
+    }
+
+    #[test]
+    fn dns64_synthesize_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--dns64-synthesize", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::Dns64WithoutQueries));
+    }
+
+    #[test]
+    fn soa_check() {
+        let options = Options::getopts(&[ "soa-check", "lookup.dog", "@192.0.2.53" ]).unwrap();
+        assert_eq!(options.command, Some(Command::SoaCheck));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(NS) ]);
+    }
 
+    #[test]
+    fn soa_check_over_tls() {
+        assert_eq!(Options::getopts(&[ "soa-check", "lookup.dog", "--tls" ]),
+                   OptionsResult::InvalidOptions(OptionsError::SoaCheckOverEncryption));
+    }
 
-impl ProtocolTweaks {
-    fn deduce(matches: &getopts::Matches) -> Result<Self, OptionsError> {
-        let mut tweaks = Self::default();
+    #[test]
+    fn soa_check_without_domain() {
+        assert_eq!(Options::getopts(&[ "soa-check" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("check the nameservers of", "dog soa-check DOMAIN")));
+    }
 
-        if let Some(tweak_strs) = matches.opt_str("Z") {
-            for tweak_str in tweak_strs.split(',') {
-                match &*tweak_str {
-                    "authentic"  => { tweaks.set_authentic_flag = true; },
-                    otherwise    => return Err(OptionsError::InvalidTweak(otherwise.into())),
-                }
-            }
-        }
+    #[test]
+    fn delegation() {
+        let options = Options::getopts(&[ "delegation", "lookup.dog" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Delegation));
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(NS) ]);
+    }
 
-        Ok(tweaks)
+    #[test]
+    fn delegation_with_nameserver() {
+        assert_eq!(Options::getopts(&[ "delegation", "lookup.dog", "@192.0.2.53" ]),
+                   OptionsResult::InvalidOptions(OptionsError::DelegationWithNameserver));
     }
-}
 
+    #[test]
+    fn propagate_without_domain() {
+        assert_eq!(Options::getopts(&[ "propagate" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("check the propagation of", "dog propagate DOMAIN")));
+    }
 
-/// The result of the `Options::getopts` function.
-#[derive(PartialEq, Debug)]
-pub enum OptionsResult {
+    #[test]
+    fn propagate_while_tracing() {
+        assert_eq!(Options::getopts(&[ "propagate", "lookup.dog", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CommandWhileTracing("checking propagation")));
+    }
 
-    /// The options were parsed successfully.
-    Ok(Options),
+    #[test]
+    fn bench() {
+        let options = Options::getopts(&[ "bench", "@192.0.2.53", "--qps", "100", "--duration", "30s", "names.txt" ]).unwrap();
+        let load = Load { rate: 100, duration: Duration::from_secs(30) };
+        assert_eq!(options.command, Some(Command::Bench(Input::File(PathBuf::from("names.txt")), load)));
+        assert_eq!(options.requests.inputs.resolvers, vec![ Resolver::Specified("192.0.2.53".into()) ]);
+    }
 
-    /// There was an error (from `getopts`) parsing the arguments.
-    InvalidOptionsFormat(getopts::Fail),
+    #[test]
+    fn bench_without_file() {
+        assert_eq!(Options::getopts(&[ "bench", "@192.0.2.53" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingQueryFile));
+    }
 
-    /// There was an error with the combination of options the user selected.
-    InvalidOptions(OptionsError),
+    #[test]
+    fn invalid_rate() {
+        assert_eq!(Options::getopts(&[ "bench", "names.txt", "--qps", "lots" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidRate("lots".into())));
+    }
 
-    /// Can’t run any checks because there’s help to display!
-    Help(HelpReason, UseColours),
+    #[test]
+    fn ping() {
+        let options = Options::getopts(&[ "ping", "@192.0.2.53", "--count", "10" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Ping(10)));
+        assert_eq!(options.requests.inputs.domains, vec![ dns::Name::root() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(NS) ]);
+    }
 
-    /// One of the arguments was `--version`, to display the version number.
-    Version(UseColours),
-}
+    #[test]
+    fn ping_name() {
+        let options = Options::getopts(&[ "ping", "lookup.dog", "@192.0.2.53" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Ping(ping::DEFAULT_COUNT)));
+        assert_eq!(options.requests.inputs.domains, vec![ "lookup.dog".parse().unwrap() ]);
+        assert_eq!(options.requests.inputs.types, vec![ qtype!(A) ]);
+    }
 
-/// The reason that help is being displayed. If it’s for the `--help` flag,
-/// then we shouldn’t return an error exit status.
-#[derive(PartialEq, Debug, Copy, Clone)]
-pub enum HelpReason {
+    #[test]
+    fn invalid_count() {
+        assert_eq!(Options::getopts(&[ "ping", "--count", "0" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidCount("0".into())));
+    }
 
-    /// Help was requested with the `--help` flag.
-    Flag,
+    #[test]
+    fn count_without_ping() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--count", "3" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CountWithoutPing));
+    }
 
-    /// There were no domains being queried, so display help instead.
-    /// Unlike `dig`, we don’t implicitly search for the root domain.
-    NoDomains,
-}
+    #[test]
+    fn ping_dig() {
+        assert_eq!(Options::getopts(&[ "ping", "--dig" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CommandFormat("pinging")));
+    }
 
-/// Something wrong with the combination of options the user has picked.
-#[derive(PartialEq, Debug)]
-pub enum OptionsError {
-    TooManyProtocols,
-    InvalidEDNS(String),
-    InvalidQueryType(String),
-    InvalidQueryClass(String),
-    InvalidTxid(String),
-    InvalidTweak(String),
-    QueryTypeOPT,
-}
+    #[test]
+    fn rate_without_bench() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--qps", "100" ]),
+                   OptionsResult::InvalidOptions(OptionsError::LoadWithoutBench));
+    }
 
-impl fmt::Display for OptionsError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::TooManyProtocols       => write!(f, "Too many protocols"),
-            Self::InvalidEDNS(edns)      => write!(f, "Invalid EDNS setting {:?}", edns),
-            Self::InvalidQueryType(qt)   => write!(f, "Invalid query type {:?}", qt),
-            Self::InvalidQueryClass(qc)  => write!(f, "Invalid query class {:?}", qc),
-            Self::InvalidTxid(txid)      => write!(f, "Invalid transaction ID {:?}", txid),
-            Self::InvalidTweak(tweak)    => write!(f, "Invalid protocol tweak {:?}", tweak),
-            Self::QueryTypeOPT           => write!(f, "OPT request is sent by default (see -Z flag)"),
-        }
+    #[test]
+    fn stats() {
+        let options = Options::getopts(&[ "lookup.dog", "A", "AAAA", "--stats" ]).unwrap();
+        assert!(options.stats);
     }
-}
 
+    #[test]
+    fn stats_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--stats", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::StatsWithoutQueries));
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use pretty_assertions::assert_eq;
-    use dns::record::*;
+    #[test]
+    fn interpret() {
+        let options = Options::getopts(&[ "_dmarc.lookup.dog", "TXT", "--interpret" ]).unwrap();
+        assert!(options.interpret);
+    }
 
-    impl Inputs {
-        fn fallbacks() -> Self {
-            Inputs {
-                domains:         vec![ /* No domains by default */ ],
-                types:           vec![ qtype!(A) ],
-                classes:         vec![ QClass::IN ],
-                resolvers:       vec![ Resolver::SystemDefault ],
-                transport_types: vec![ TransportType::Automatic ],
-            }
-        }
+    #[test]
+    fn interpret_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "TXT", "--interpret", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InterpretWithoutQueries));
     }
 
-    impl OptionsResult {
-        fn unwrap(self) -> Options {
-            match self {
-                Self::Ok(o)  => o,
-                _            => panic!("{:?}", self),
-            }
-        }
+    #[test]
+    fn all_sections() {
+        let options = Options::getopts(&[ "lookup.dog" ]).unwrap();
+        assert_eq!(options.sections, Sections::all());
     }
 
-    // help tests
+    #[test]
+    fn answer_section_only() {
+        let options = Options::getopts(&[ "lookup.dog", "--answer-only" ]).unwrap();
+        assert_eq!(options.sections, Sections { question: false, answer: true, authority: false, additional: false });
+    }
 
     #[test]
-    fn help() {
-        assert_eq!(Options::getopts(&[ "--help" ]),
-                   OptionsResult::Help(HelpReason::Flag, UseColours::Automatic));
+    fn some_sections() {
+        let options = Options::getopts(&[ "lookup.dog", "NS", "--question", "--authority", "--dig" ]).unwrap();
+        assert_eq!(options.sections, Sections { question: true, answer: false, authority: true, additional: false });
     }
 
     #[test]
-    fn help_no_colour() {
-        assert_eq!(Options::getopts(&[ "--help", "--colour=never" ]),
-                   OptionsResult::Help(HelpReason::Flag, UseColours::Never));
+    fn sections_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--authority", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::SectionsWithoutQueries));
     }
 
     #[test]
-    fn version() {
-        assert_eq!(Options::getopts(&[ "--version" ]),
-                   OptionsResult::Version(UseColours::Automatic));
+    fn sections_short() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--additional", "--short" ]),
+                   OptionsResult::InvalidOptions(OptionsError::SectionsFormat));
     }
 
     #[test]
-    fn version_yes_color() {
-        assert_eq!(Options::getopts(&[ "--version", "--color", "always" ]),
-                   OptionsResult::Version(UseColours::Always));
+    fn sort() {
+        let options = Options::getopts(&[ "lookup.dog", "--sort" ]).unwrap();
+        assert!(options.sort);
     }
 
     #[test]
-    fn fail() {
-        assert_eq!(Options::getopts(&[ "--pear" ]),
-                   OptionsResult::InvalidOptionsFormat(getopts::Fail::UnrecognizedOption("pear".into())));
+    fn sort_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--sort", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::SortWithoutQueries));
     }
 
     #[test]
-    fn empty() {
-        let nothing: Vec<&str> = vec![];
-        assert_eq!(Options::getopts(nothing),
-                   OptionsResult::Help(HelpReason::NoDomains, UseColours::Automatic));
+    fn expectations() {
+        let options = Options::getopts(&[ "lookup.dog", "--expect", "127.0.0.1", "--expect", "127.0.0.2", "--expect-rcode", "noerror", "--expect-ttl-min", "300" ]).unwrap();
+        assert_eq!(options.expectations, Expectations {
+            values: vec![ "127.0.0.1".into(), "127.0.0.2".into() ],
+            rcode: Some(0),
+            ttl_min: Some(300),
+        });
     }
 
     #[test]
-    fn an_unrelated_argument() {
-        assert_eq!(Options::getopts(&[ "--time" ]),
-                   OptionsResult::Help(HelpReason::NoDomains, UseColours::Automatic));
+    fn invalid_expected_rcode() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--expect-rcode", "WOOF" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidExpectedRcode("WOOF".into())));
     }
 
-    // query tests
+    #[test]
+    fn invalid_expected_ttl() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--expect-ttl-min", "-1" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidExpectedTTL("-1".into())));
+    }
 
     #[test]
-    fn just_domain() {
-        let options = Options::getopts(&[ "lookup.dog" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            .. Inputs::fallbacks()
-        });
+    fn expect_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--expect", "127.0.0.1", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ExpectWithoutQueries));
     }
 
     #[test]
-    fn just_named_domain() {
-        let options = Options::getopts(&[ "-q", "lookup.dog" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            .. Inputs::fallbacks()
-        });
+    fn watch() {
+        let options = Options::getopts(&[ "lookup.dog", "--watch", "5s" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Watch(Duration::from_secs(5))));
     }
 
     #[test]
-    fn domain_and_type() {
-        let options = Options::getopts(&[ "lookup.dog", "SOA" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            types:      vec![ qtype!(SOA) ],
-            .. Inputs::fallbacks()
-        });
+    fn invalid_watch_interval() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--watch", "soon" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidWatchInterval("soon".into())));
     }
 
     #[test]
-    fn domain_and_nameserver() {
-        let options = Options::getopts(&[ "lookup.dog", "@1.1.1.1" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
-            .. Inputs::fallbacks()
-        });
+    fn overflowing_watch_interval() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--watch", "18446744073709551615h" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidWatchInterval("18446744073709551615h".into())));
     }
 
     #[test]
-    fn domain_and_class() {
-        let options = Options::getopts(&[ "lookup.dog", "CH" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            classes:    vec![ QClass::CH ],
-            .. Inputs::fallbacks()
-        });
+    fn watch_with_file() {
+        assert_eq!(Options::getopts(&[ "-f", "queries.txt", "--watch", "5s" ]),
+                   OptionsResult::InvalidOptions(OptionsError::WatchWithCommand));
     }
 
     #[test]
-    fn all_free() {
-        let options = Options::getopts(&[ "lookup.dog", "CH", "NS", "@1.1.1.1" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            classes:    vec![ QClass::CH ],
-            types:      vec![ qtype!(NS) ],
-            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
-            .. Inputs::fallbacks()
-        });
+    fn probe() {
+        let options = Options::getopts(&[ "lookup.dog", "--probe", "127.0.0.1:9153" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Probe("127.0.0.1:9153".parse().unwrap(), probe::DEFAULT_INTERVAL)));
     }
 
     #[test]
-    fn all_parameters() {
-        let options = Options::getopts(&[ "-q", "lookup.dog", "--class", "CH", "--type", "SOA", "--nameserver", "1.1.1.1" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            classes:    vec![ QClass::CH ],
-            types:      vec![ qtype!(SOA) ],
-            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
-            .. Inputs::fallbacks()
-        });
+    fn probe_on_interval() {
+        let options = Options::getopts(&[ "lookup.dog", "--probe", "9153", "--watch", "1m" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Probe("0.0.0.0:9153".parse().unwrap(), Duration::from_secs(60))));
     }
 
     #[test]
-    fn two_types() {
-        let options = Options::getopts(&[ "-q", "lookup.dog", "--type", "SRV", "--type", "AAAA" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            types:      vec![ qtype!(SRV), qtype!(AAAA) ],
-            .. Inputs::fallbacks()
-        });
+    fn invalid_probe_address() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--probe", "everywhere" ]),
+                   OptionsResult::InvalidOptions(OptionsError::InvalidProbeAddress("everywhere".into())));
     }
 
     #[test]
-    fn two_classes() {
-        let options = Options::getopts(&[ "-q", "lookup.dog", "--class", "IN", "--class", "CH" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            classes:    vec![ QClass::IN, QClass::CH ],
-            .. Inputs::fallbacks()
-        });
+    fn probe_with_file() {
+        assert_eq!(Options::getopts(&[ "-f", "queries.txt", "--probe", "9153" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ProbeWithCommand));
     }
 
     #[test]
-    fn all_mixed_1() {
-        let options = Options::getopts(&[ "lookup.dog", "--class", "CH", "SOA", "--nameserver", "1.1.1.1" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            classes:    vec![ QClass::CH ],
-            types:      vec![ qtype!(SOA) ],
-            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()) ],
-            .. Inputs::fallbacks()
-        });
+    fn probe_without_domains() {
+        assert_eq!(Options::getopts(&[ "--probe", "9153" ]),
+                   OptionsResult::InvalidOptions(OptionsError::MissingDomain("probe", "dog DOMAIN --probe ADDRESS")));
     }
 
     #[test]
-    fn all_mixed_2() {
-        let options = Options::getopts(&[ "CH", "SOA", "MX", "IN", "-q", "lookup.dog", "--class", "HS" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            classes:    vec![ QClass::HS, QClass::CH, QClass::IN ],
-            types:      vec![ qtype!(SOA), qtype!(MX) ],
-            .. Inputs::fallbacks()
-        });
+    fn serve() {
+        let options = Options::getopts(&[ "serve", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Serve("127.0.0.1:5300".parse().unwrap(), None)));
     }
 
     #[test]
-    fn all_mixed_3() {
-        let options = Options::getopts(&[ "lookup.dog", "--nameserver", "1.1.1.1", "--nameserver", "1.0.0.1" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("lookup.dog") ],
-            resolvers:  vec![ Resolver::Specified("1.1.1.1".into()),
-                              Resolver::Specified("1.0.0.1".into()), ],
-            .. Inputs::fallbacks()
-        });
+    fn serve_on_address() {
+        let options = Options::getopts(&[ "serve", "--listen", "5353", "@1.1.1.1" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Serve("0.0.0.0:5353".parse().unwrap(), None)));
     }
 
     #[test]
-    fn explicit_numerics() {
-        let options = Options::getopts(&[ "11", "--class", "22", "--type", "33" ]).unwrap();
-        assert_eq!(options.requests.inputs, Inputs {
-            domains:    vec![ String::from("11") ],
-            classes:    vec![ QClass::Other(22) ],
-            types:      vec![ 33 ],
-            .. Inputs::fallbacks()
-        });
+    fn listen_without_serve() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--listen", "5353" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ListenWithoutServe));
     }
 
-    // invalid options tests
+    #[test]
+    fn serve_zone() {
+        let options = Options::getopts(&[ "serve", "--zone", "db.lookup.dog", "lookup.dog" ]).unwrap();
+        assert_eq!(options.command, Some(Command::Serve("127.0.0.1:5300".parse().unwrap(), Some(PathBuf::from("db.lookup.dog")))));
+    }
 
     #[test]
-    fn invalid_named_class() {
-        assert_eq!(Options::getopts(&[ "lookup.dog", "--class", "tubes" ]),
-                   OptionsResult::InvalidOptions(OptionsError::InvalidQueryClass("tubes".into())));
+    fn zone_without_serve() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--zone", "db.lookup.dog" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ZoneWithoutServe));
     }
 
     #[test]
-    fn invalid_named_type() {
-        assert_eq!(Options::getopts(&[ "lookup.dog", "--type", "tubes" ]),
-                   OptionsResult::InvalidOptions(OptionsError::InvalidQueryType("tubes".into())));
+    fn serve_with_domains() {
+        assert_eq!(Options::getopts(&[ "serve", "lookup.dog", "@1.1.1.1" ]),
+                   OptionsResult::InvalidOptions(OptionsError::ServeWithDomains));
     }
 
     #[test]
-    fn invalid_capsword() {
-        assert_eq!(Options::getopts(&[ "SMH", "lookup.dog" ]),
-                   OptionsResult::InvalidOptions(OptionsError::InvalidQueryType("SMH".into())));
+    fn watch_while_tracing() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--watch", "5s", "--trace" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CommandWhileTracing("watching")));
     }
 
     #[test]
-    fn invalid_txid() {
-        assert_eq!(Options::getopts(&[ "lookup.dog", "--txid=0x1234" ]),
-                   OptionsResult::InvalidOptions(OptionsError::InvalidTxid("0x1234".into())));
+    fn watch_as_csv() {
+        assert_eq!(Options::getopts(&[ "lookup.dog", "--watch", "5s", "--csv" ]),
+                   OptionsResult::InvalidOptions(OptionsError::CommandFormat("watching")));
+    }
+
+    #[test]
+    fn batch_lines() {
+        let inputs = Inputs::from_batch_line("  lookup.dog  mx ch ").unwrap().unwrap();
+        assert_eq!(inputs.domains, vec![ "lookup.dog".parse().unwrap() ]);
+        assert_eq!(inputs.types, vec![ qtype!(MX) ]);
+        assert_eq!(inputs.classes, vec![ QClass::CH ]);
+    }
+
+    #[test]
+    fn batch_comments() {
+        assert_eq!(Inputs::from_batch_line("# lookup.dog"), Ok(None));
+        assert_eq!(Inputs::from_batch_line("   "), Ok(None));
+    }
+
+    #[test]
+    fn batch_invalid_type() {
+        assert_eq!(Inputs::from_batch_line("lookup.dog other.dog"),
+                   Err(OptionsError::InvalidQueryType("OTHER.DOG".into())));
+    }
+
+    #[test]
+    fn diff_as_domain() {
+        let options = Options::getopts(&[ "lookup.dog", "diff" ]).unwrap();
+        assert_eq!(options.command, None);
+        assert_eq!(options.requests.inputs.domains.len(), 2);
     }
 
     #[test]
@@ -1,14 +1,51 @@
-//! Text and JSON output.
+//! Text, JSON, YAML, CSV, and raw output.
 
-use std::time::Duration;
+use std::io::{self, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use dns::{Response, Query, Answer, ErrorCode, WireError};
-use dns::record::{Record, OPT, UnknownQtype};
-use dns_transport::Error as TransportError;
+use dns::{Response, Query, Answer, ErrorCode, Name};
+use dns::diff::{self, ResponseDiff};
+use dns::presentation::{base64, hex, timestamp, type_mnemonic};
+use dns::record::{Record, OPT};
+use dns_transport::{Error as TransportError, Connection, Protocol};
 use serde_json::{json, Value as JsonValue};
 
+use crate::bench::Report;
+use crate::caa::{CaaCheck, Permission};
+use crate::chain::Chain;
 use crate::colours::Colours;
+use crate::connect::TransportType;
+use crate::csv::csv_row;
+use crate::delegation::{self, Delegation, DelegationError, Lameness};
+use crate::dig;
+use crate::expect;
+use crate::diff::SetDiff;
+use crate::failure::{Failure, FailureKind};
+use crate::hexdump::hex_dump;
+use crate::interpret::Interpretation;
+use crate::json::ToJson;
+use crate::locale::{Catalog, Message, ENGLISH};
+use crate::lookup::LookupError;
+use crate::mx::{self, MxCheck};
+use crate::nat64::Prefix;
+use crate::authority::{Authority, AuthorityError};
+use crate::fcrdns::FcrdnsCheck;
+use crate::wildcard::{WildcardProbe, Presence};
+use crate::pcap::CapturedMessage;
+use crate::ping;
+use crate::propagate::{Propagation, AnswerSet, resolver_name};
+use crate::requests::Destination;
+use crate::serve::{Served, Outcome};
+use crate::soa::{SoaCheck, SoaError, ServerError};
+use crate::srv::{Endpoint, Resolution};
+use crate::stats::Stats;
+use crate::syscmp::{SystemComparison, Cause};
 use crate::table::{Table, Section};
+use crate::trace::{Trace, TraceError};
+use crate::validate::Status;
+use crate::watch::{WatchedAnswer, Change};
+use crate::yaml::to_yaml;
 
 
 /// How to format the output data.
@@ -23,6 +60,135 @@ pub enum OutputFormat {
 
     /// Format the entries as JSON.
     JSON,
+
+    /// Format the entries as YAML, with the same fields as the JSON.
+    YAML,
+
+    /// Format the answers as comma-separated values, one row per answer,
+    /// with a header row first.
+    CSV,
+
+    /// Format the records as they would be written in a zone file, one
+    /// record per line.
+    Zone,
+
+    /// Format the output the same way dig does.
+    Dig,
+
+    /// Dump the bytes of each response in hexadecimal, alongside their
+    /// ASCII characters.
+    Hex,
+
+    /// Write the bytes of each response to standard output exactly as they
+    /// were received, one after another.
+    Raw,
+}
+
+
+/// The version of the JSON output’s schema, which gets included at the
+/// top level of every JSON document. It only changes when fields are
+/// renamed, removed, or change meaning; adding new fields does not count.
+pub const JSON_SCHEMA_VERSION: u32 = 2;
+
+/// Details of how one response was received, which get included in the
+/// JSON output.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Exchange {
+
+    /// Where the request was sent.
+    pub destination: Destination,
+
+    /// How long it took between sending the request and receiving the
+    /// response, not counting any queries made to chase CNAME chains.
+    pub round_trip: Duration,
+
+    /// How many times the request had to be sent before a response that
+    /// was not truncated arrived, which is only ever more than one when a
+    /// truncated response over UDP led to it being sent again over TCP.
+    pub attempts: u32,
+
+    /// The protocol the response arrived over, which is only different
+    /// from the transport type when that was automatic.
+    pub protocol: Protocol,
+
+    /// The details of the connection the response arrived over.
+    pub connection: Connection,
+
+    /// The OPT record in the response, if it had one, which is kept here
+    /// even when it is being hidden from the additional records.
+    pub edns: Option<OPT>,
+
+    /// The response exactly as it was received, before any of it was
+    /// parsed or hidden.
+    pub bytes: Vec<u8>,
+}
+
+
+/// Everything there is to print about a set of queries, along with how to
+/// print it.
+#[derive(Debug)]
+pub struct Printable {
+
+    /// The responses that arrived.
+    pub responses: Vec<Response>,
+
+    /// How each of the responses was received, if known.
+    pub exchanges: Vec<Exchange>,
+
+    /// The DNSSEC status of each answer in each response, if validating.
+    pub validations: Vec<Vec<Option<Status>>>,
+
+    /// The queries that failed, and the responses that were not what
+    /// was expected.
+    pub errors: Vec<QueryError>,
+
+    /// Whether to show the CNAME chains in the responses.
+    pub chase: bool,
+
+    /// Which sections of each response to print.
+    pub sections: Sections,
+}
+
+impl Printable {
+
+    /// Just the given responses, with nothing known about how they were
+    /// received, printed in full.
+    pub fn responses(responses: Vec<Response>) -> Self {
+        Self { responses, exchanges: Vec::new(), validations: Vec::new(), errors: Vec::new(), chase: false, sections: Sections::all() }
+    }
+}
+
+
+/// Something that went wrong with one of the queries.
+#[derive(Debug)]
+pub enum QueryError {
+
+    /// The query was sent, but no response to it arrived.
+    Transport(TransportError),
+
+    /// No authoritative nameserver could be found to send the query to.
+    Authority(AuthorityError),
+
+    /// The response did not match what was expected of it.
+    Expectation(expect::Failure),
+}
+
+
+/// The responses to the queries from one line of a file of queries.
+#[derive(PartialEq, Debug)]
+pub struct BatchGroup {
+
+    /// The line the queries came from, with its spacing tidied up.
+    pub label: String,
+
+    /// The responses that arrived.
+    pub responses: Vec<Response>,
+
+    /// How each of the responses was received.
+    pub exchanges: Vec<Exchange>,
+
+    /// The DNSSEC status of each answer in each response, if validating.
+    pub validations: Vec<Vec<Option<Status>>>,
 }
 
 
@@ -46,6 +212,81 @@ pub struct TextFormat {
 
     /// Whether to format TTLs as hours, minutes, and seconds.
     pub format_durations: bool,
+
+    /// Whether to decode internationalised domain names from Punycode
+    /// back into Unicode.
+    pub unicode_names: bool,
+
+    /// Whether to join the strings in TXT records into one, instead of
+    /// showing where the record splits them up.
+    pub concat_txt: bool,
+
+    /// How to display the TTLs of records.
+    pub ttl_format: TtlFormat,
+
+    /// The language to print messages and durations in.
+    pub catalog: &'static Catalog,
+}
+
+/// How to display the TTL of a record in the text output.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum TtlFormat {
+
+    /// Display it as a number of seconds.
+    Raw,
+
+    /// Format it as days, hours, minutes, and seconds.
+    Human,
+
+    /// Display the time the record expires, counting from when it is
+    /// printed.
+    Expiry,
+}
+
+/// Which sections of each response to print, which is all of them unless
+/// the user has picked some.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Sections {
+
+    /// Whether to print the question section, which only the JSON, YAML,
+    /// and dig output include.
+    pub question: bool,
+
+    /// Whether to print the answer section.
+    pub answer: bool,
+
+    /// Whether to print the authority section.
+    pub authority: bool,
+
+    /// Whether to print the additional section.
+    pub additional: bool,
+}
+
+impl Sections {
+
+    /// Every section, which is what gets printed by default.
+    pub fn all() -> Self {
+        Self { question: true, answer: true, authority: true, additional: true }
+    }
+
+    /// Empties the sections of a response that are not being printed.
+    fn select(self, response: &mut Response) {
+        if ! self.question {
+            response.queries.clear();
+        }
+
+        if ! self.answer {
+            response.answers.clear();
+        }
+
+        if ! self.authority {
+            response.authorities.clear();
+        }
+
+        if ! self.additional {
+            response.additionals.clear();
+        }
+    }
 }
 
 impl UseColours {
@@ -71,61 +312,118 @@ impl UseColours {
 
 
 impl OutputFormat {
-    pub fn print(self, responses: Vec<Response>, duration: Option<Duration>) -> bool {
+    pub fn print(self, printable: Printable, duration: Option<Duration>) -> bool {
+        let Printable { mut responses, exchanges, validations, errors, chase, sections } = printable;
+
+        // The dig output counts the records in every section in its header,
+        // so it leaves them out itself.
+        if self != Self::Dig {
+            for response in &mut responses {
+                sections.select(response);
+            }
+        }
+
+        // Queries that failed get put in the same document as the ones that
+        // did not in the JSON output, and are printed before anything else
+        // in every other mode.
+        let errors = self.print_errors_unless_structured(errors);
+
         match self {
             Self::Short(tf) => {
-                let all_answers = responses.into_iter().flat_map(|r| r.answers).collect::<Vec<_>>();
-
-                if all_answers.is_empty() {
-                    eprintln!("No results");
+                if responses.iter().all(|r| r.answers.is_empty()) {
+                    eprintln!("{}", tf.catalog.message(Message::NoResults));
                     return false;
                 }
 
-                for answer in all_answers {
-                    match answer {
-                        Answer::Standard { record, .. } => {
-                            println!("{}", tf.record_payload_summary(&record))
-                        }
-                        Answer::Pseudo { opt, .. } => {
-                            println!("{}", tf.pseudo_record_payload_summary(&opt))
-                        }
+                for response in responses {
+                    for value in tf.short_values(&response, chase) {
+                        println!("{}", value);
                     }
-
                 }
             }
-            Self::JSON => {
+            Self::JSON | Self::YAML => {
                 let mut rs = Vec::new();
 
-                for response in responses {
-                    let json = json!({
-                        "queries": self.json_queries(&response.queries),
-                        "answers": self.json_answers(&response.answers),
-                        "authorities": self.json_answers(&response.authorities),
-                        "additionals": self.json_answers(&response.additionals),
-                    });
+                for (index, response) in responses.into_iter().enumerate() {
+                    let mut object = json_response(&response);
+                    let omut = object.as_object_mut().unwrap();
+
+                    if let Some(exchange) = exchanges.get(index) {
+                        omut.insert("server".into(), exchange.destination.nameserver.as_str().into());
+                        omut.insert("transport".into(), transport_name(exchange.destination.transport_type).into());
+                        omut.insert("authority".into(), exchange.destination.authority.as_ref().map_or(JsonValue::Null, json_authority));
+                        omut.insert("round_trip_ms".into(), json!(exchange.round_trip.as_millis() as u64));
+                        omut.insert("protocol".into(), protocol_name(exchange.protocol).to_lowercase().into());
+                        omut.insert("attempts".into(), exchange.attempts.into());
+                        omut.insert("connection".into(), json_connection(exchange));
+                        omut.insert("edns".into(), exchange.edns.as_ref().map_or(JsonValue::Null, json_edns));
+                    }
+
+                    if let Some(chain) = response_chain(&response).filter(|_| chase) {
+                        object.as_object_mut().unwrap().insert("chain".into(), json!(chain.names.iter().map(Name::to_string).collect::<Vec<_>>()));
+                    }
+
+                    if let Some(statuses) = validations.get(index) {
+                        let answers = object["answers"].as_array_mut().unwrap();
+                        for (answer, status) in answers.iter_mut().zip(statuses) {
+                            if let Some(status) = status {
+                                answer.as_object_mut().unwrap().insert("dnssec".into(), json_status(*status));
+                            }
+                        }
+                    }
 
-                    rs.push(json);
+                    rs.push(object);
                 }
 
+                let mut object = json!({ "schema_version": JSON_SCHEMA_VERSION, "responses": rs });
+                let omut = object.as_object_mut().unwrap();
+
                 if let Some(duration) = duration {
-                    let object = json!({ "responses": rs, "duration": duration });
-                    println!("{}", object);
+                    omut.insert("duration".into(), json!(duration));
                 }
-                else {
-                    let object = json!({ "responses": rs });
-                    println!("{}", object);
+
+                if ! errors.is_empty() {
+                    omut.insert("errors".into(), errors.into_iter().map(json_query_error).collect());
                 }
+
+                self.print_document(object);
             }
             Self::Text(uc, tf) => {
                 let mut table = Table::new(uc.palette(), tf);
+                let mut chain_lines = Vec::new();
+
+                for (index, response) in responses.into_iter().enumerate() {
+                    if let Some(authority) = exchanges.get(index).and_then(|e| e.destination.authority.as_ref()) {
+                        println!("{}", authority_line(authority, tf));
+                    }
+
+                    if let Some(exchange) = exchanges.get(index).filter(|e| e.protocol == Protocol::Hosts) {
+                        println!("{}", tf.catalog.format(Message::AnsweredFromHosts, &[ &exchange.destination.nameserver ]));
+                    }
 
-                for response in responses {
                     if let Some(rcode) = response.flags.error_code {
-                        print_error_code(rcode);
+                        print_error_code(rcode, tf.catalog);
+                    }
+
+                    if response.flags.reserved {
+                        print_reserved_bit();
                     }
 
-                    for a in response.answers {
-                        table.add_row(a, Section::Answer);
+                    // The chain takes the place of the answer rows, as it
+                    // contains every CNAME record and the records they point
+                    // to. It gets printed after the table, which is buffered.
+                    let chain = response_chain(&response).filter(|_| chase);
+                    if let Some(chain) = chain {
+                        chain_lines.extend(tf.chain_lines(&chain, &uc.palette()));
+                    }
+                    else {
+                        let statuses = validations.get(index);
+                        for (answer_index, a) in response.answers.into_iter().enumerate() {
+                            match statuses.and_then(|s| s.get(answer_index)).copied().flatten() {
+                                Some(status)  => table.add_validated_row(a, Section::Answer, status),
+                                None          => table.add_row(a, Section::Answer),
+                            }
+                        }
                     }
 
                     for a in response.authorities {
@@ -137,209 +435,2995 @@ impl OutputFormat {
                     }
                 }
 
-                table.print(duration);
+                if chain_lines.is_empty() || ! table.is_empty() {
+                    table.print(None);
+                }
+
+                for line in chain_lines {
+                    println!("{}", line);
+                }
+
+                if let Some(dur) = duration {
+                    for exchange in &exchanges {
+                        println!("{}", query_time(exchange, tf.catalog));
+
+                        if let Some(line) = connection_details(exchange, tf.catalog) {
+                            println!("{}", line);
+                        }
+                    }
+
+                    println!("{}", tf.catalog.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
+            Self::Dig => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+
+                for (index, response) in responses.iter().enumerate() {
+                    let lines = if sections == Sections::all() {
+                        dig::response_lines(response, exchanges.get(index), now)
+                    }
+                    else {
+                        dig::section_lines(response, sections)
+                    };
+
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+            }
+            Self::CSV => {
+                println!("{}", csv_row(&CSV_HEADER));
+
+                for response in responses {
+                    print_csv_rows(&response.answers);
+                }
+            }
+            Self::Hex => {
+                for exchange in &exchanges {
+                    println!(";; {} bytes from {} ({})", exchange.bytes.len(), exchange.destination.nameserver, transport_name(exchange.destination.transport_type));
+                    print_hex_dump(&exchange.bytes);
+                }
+            }
+            Self::Raw => {
+                print_raw(exchanges.iter().map(|e| &e.bytes[..]).collect());
+            }
+            Self::Zone => {
+                for response in responses {
+                    if let Some(rcode) = response.flags.error_code {
+                        println!("; Status: {}", rcode_name(Some(rcode)));
+                    }
+
+                    print_zone_lines(response);
+                }
+
+                if let Some(dur) = duration {
+                    println!("; {}", ENGLISH.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
             }
         }
 
         true
     }
 
-    pub fn print_error(&self, error: TransportError) {
-    	match self {
-    		Self::Short(..) | Self::Text(..) => {
-    			eprintln!("Error [{}]: {}", erroneous_phase(&error), error_message(error));
-    		}
-
-    		Self::JSON => {
-    			let object = json!({
-    				"error": true,
-    				"error_phase": erroneous_phase(&error),
-    				"error_message": error_message(error),
-    			});
+    pub fn print_traces(self, traces: Vec<Trace>, duration: Option<Duration>) -> bool {
+        match self {
+            Self::Short(tf) => {
+                let final_answers = traces.into_iter()
+                                          .filter_map(|t| t.steps.into_iter().last())
+                                          .flat_map(|s| s.response.answers)
+                                          .collect::<Vec<_>>();
 
-    			eprintln!("{}", object);
-    		}
-    	}
-    }
-}
+                if final_answers.is_empty() {
+                    eprintln!("{}", tf.catalog.message(Message::NoResults));
+                    return false;
+                }
 
-fn erroneous_phase(error: &TransportError) -> &'static str {
-	match error {
-		TransportError::NetworkError(_)  => "network",
-		TransportError::HttpError(_)     => "http",
-		TransportError::TlsError(_)      => "tls",
-		TransportError::BadRequest       => "http-status",
-		TransportError::WireError(_)     => "protocol",
-	}
-}
+                for answer in final_answers {
+                    if let Answer::Standard { record, .. } = answer {
+                        println!("{}", tf.record_short_value(&record));
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let mut ts = Vec::new();
 
-fn error_message(error: TransportError) -> String {
-	match error {
-		TransportError::NetworkError(e)  => e.to_string(),
-		TransportError::HttpError(e)     => e.to_string(),
-		TransportError::TlsError(e)      => e.to_string(),
-		TransportError::BadRequest       => "Nameserver returned HTTP 400 Bad Request".into(),
-		TransportError::WireError(e)     => {
-			match e {
-				WireError::IO                             => "Malformed packet: insufficient data".into(),
-				WireError::WrongLength { expected, got }  => format!("Malformed packet: expected length {}, got {}", expected, got),
-				WireError::TooMuchRecursion(indices)      => format!("Malformed packet: too much recursion: {:?}", indices),
-				WireError::OutOfBounds(index)             => format!("Malformed packet: out of bounds ({})", index),
-			}
-		}
-	}
-}
+                for trace in traces {
+                    let steps = trace.steps.iter().map(|step| {
+                        let mut object = step.response.to_json();
+                        let omut = object.as_object_mut().unwrap();
+                        omut.insert("zone".into(), step.zone.as_str().into());
+                        omut.insert("nameserver".into(), step.nameserver.as_str().into());
+                        omut.insert("address".into(), step.address.to_string().into());
+                        object
+                    }).collect::<Vec<_>>();
 
+                    let error = trace.error.map(trace_error_message);
+                    ts.push(json!({ "steps": steps, "error": error }));
+                }
 
-impl TextFormat {
-    pub fn record_payload_summary(self, record: &Record) -> String {
-        match *record {
-            Record::A(ref a) => {
-                format!("{}", a.address)
-            }
-            Record::AAAA(ref aaaa) => {
-                format!("{}", aaaa.address)
-            }
-            Record::CAA(ref caa) => {
-                if caa.critical {
-                    format!("{:?} {:?} (critical)", caa.tag, caa.value)
+                if let Some(duration) = duration {
+                    let object = json!({ "schema_version": JSON_SCHEMA_VERSION, "traces": ts, "duration": duration });
+                    self.print_document(object);
                 }
                 else {
-                    format!("{:?} {:?} (non-critical)", caa.tag, caa.value)
+                    let object = json!({ "schema_version": JSON_SCHEMA_VERSION, "traces": ts });
+                    self.print_document(object);
                 }
             }
-            Record::CNAME(ref cname) => {
-                format!("{:?}", cname.domain)
-            }
-            Record::MX(ref mx) => {
-                format!("{} {:?}", mx.preference, mx.exchange)
-            }
-            Record::NS(ref ns) => {
-                format!("{:?}", ns.nameserver)
-            }
-            Record::PTR(ref ptr) => {
-                format!("{:?}", ptr.cname)
+            Self::Text(uc, tf) => {
+                let colours = uc.palette();
+
+                for trace in traces {
+                    for step in trace.steps {
+                        println!("Zone {} from {} ({})",
+                            colours.qname.paint(tf.format_name(&step.zone)),
+                            colours.qname.paint(tf.format_name(&step.nameserver)),
+                            step.address);
+
+                        if let Some(rcode) = step.response.flags.error_code {
+                            print_error_code(rcode, tf.catalog);
+                        }
+
+                        let mut table = Table::new(uc.palette(), tf);
+
+                        for a in step.response.answers {
+                            table.add_row(a, Section::Answer);
+                        }
+
+                        for a in step.response.authorities {
+                            table.add_row(a, Section::Authority);
+                        }
+
+                        for a in step.response.additionals {
+                            table.add_row(a, Section::Additional);
+                        }
+
+                        table.print(None);
+                        println!();
+                    }
+
+                    if let Some(error) = trace.error {
+                        eprintln!("Error [trace]: {}", trace_error_message(error));
+                    }
+                }
+
+                if let Some(dur) = duration {
+                    println!("{}", tf.catalog.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
             }
-            Record::SOA(ref soa) => {
-                format!("{:?} {:?} {} {} {} {} {}",
-                    soa.mname, soa.rname, soa.serial,
-                    self.format_duration(soa.refresh_interval),
-                    self.format_duration(soa.retry_interval),
-                    self.format_duration(soa.expire_limit),
-                    self.format_duration(soa.minimum_ttl),
-                )
+            Self::Dig => {
+                for trace in traces {
+                    for step in trace.steps {
+                        let sections = step.response.answers.iter()
+                                           .chain(&step.response.authorities)
+                                           .chain(&step.response.additionals);
+
+                        for line in sections.filter_map(dig::record_line) {
+                            println!("{}", line);
+                        }
+
+                        println!(";; Received response from {}#53({}) for zone {}", step.address, step.nameserver, step.zone);
+                        println!();
+                    }
+
+                    if let Some(error) = trace.error {
+                        eprintln!(";; Error: {}", trace_error_message(error));
+                    }
+                }
             }
-            Record::SRV(ref srv) => {
-                format!("{} {} {:?}:{}", srv.priority, srv.weight, srv.target, srv.port)
+            Self::CSV => {
+                println!("{}", csv_row(&CSV_HEADER));
+
+                for trace in traces {
+                    if let Some(step) = trace.steps.last() {
+                        print_csv_rows(&step.response.answers);
+                    }
+                }
             }
-            Record::TXT(ref txt) => {
-                format!("{:?}", txt.message)
+            Self::Hex => {
+                for trace in traces {
+                    for step in trace.steps {
+                        println!(";; {} bytes from {} ({}) for zone {}", step.bytes.len(), step.nameserver, step.address, step.zone);
+                        print_hex_dump(&step.bytes);
+                    }
+
+                    if let Some(error) = trace.error {
+                        eprintln!("Error [trace]: {}", trace_error_message(error));
+                    }
+                }
             }
-            Record::Other { ref bytes, .. } => {
-                format!("{:?}", bytes)
+            Self::Raw => {
+                for trace in &traces {
+                    print_raw(trace.steps.iter().map(|s| &s.bytes[..]).collect());
+                }
+
+                for error in traces.into_iter().filter_map(|t| t.error) {
+                    eprintln!("Error [trace]: {}", trace_error_message(error));
+                }
             }
-        }
-    }
+            Self::Zone => {
+                for trace in traces {
+                    for step in trace.steps {
+                        println!("; Zone {} from {} ({})", step.zone, step.nameserver, step.address);
 
-    pub fn pseudo_record_payload_summary(self, opt: &OPT) -> String {
-        format!("{} {} {} {} {:?}",
-            opt.udp_payload_size,
-            opt.higher_bits,
-            opt.edns0_version,
-            opt.flags,
-            opt.data)
-    }
+                        if let Some(rcode) = step.response.flags.error_code {
+                            println!("; Status: {}", rcode_name(Some(rcode)));
+                        }
 
-    pub fn format_duration(self, seconds: u32) -> String {
-        if ! self.format_durations {
-            format!("{}", seconds)
-        }
-        else if seconds < 60 {
-            format!("{}s", seconds)
-        }
-        else if seconds < 60 * 60 {
-            format!("{}m{:02}s", seconds / 60, seconds % 60)
-        }
-        else if seconds < 60 * 60 * 24{
-            format!("{}h{:02}m{:02}s", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
-        }
-        else {
-            format!("{}d{}h{:02}m{:02}s", seconds / 86400, (seconds % 86400) / 3600, (seconds % 3600) / 60, seconds % 60)
+                        print_zone_lines(step.response);
+                        println!();
+                    }
+
+                    if let Some(error) = trace.error {
+                        eprintln!("Error [trace]: {}", trace_error_message(error));
+                    }
+                }
+
+                if let Some(dur) = duration {
+                    println!("; {}", ENGLISH.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
         }
+
+        true
     }
-}
 
-impl OutputFormat {
-    fn json_queries(&self, queries: &[Query]) -> JsonValue {
-        let queries = queries.iter().map(|q| {
-            json!({
-                "name": q.qname,
-                "class": format!("{:?}", q.qclass),
-                "type": q.qtype,
-            })
-        }).collect::<Vec<_>>();
-
-        json!(queries)
-    }
-
-    fn json_answers(&self, answers: &[Answer]) -> JsonValue {
-        let answers = answers.iter().map(|a| {
-            match a {
-                Answer::Standard { qname, qclass, ttl, record } => {
-                    let mut object = self.json_record(record);
-                    let omut = object.as_object_mut().unwrap();
-                    omut.insert("name".into(), qname.as_str().into());
-                    omut.insert("class".into(), format!("{:?}", qclass).into());
-                    omut.insert("ttl".into(), (*ttl).into());
-                    json!(object)
-                }
-                Answer::Pseudo { qname, opt } => {
-                    let object = json!({
-                        "name": qname,
-                        "type": "OPT",
-                        "version": opt.edns0_version,
-                        "data": opt.data,
-                    });
+    pub fn print_diffs(self, diffs: Vec<SetDiff>, duration: Option<Duration>) {
+        let total = diffs.len();
+        let diffs = diffs.into_iter().filter(|d| ! d.is_empty()).collect::<Vec<_>>();
 
-                    object
+        match self {
+            Self::Short(..) => {
+                for diff in &diffs {
+                    for line in diff_lines(diff, &Colours::plain()) {
+                        println!("{}", line);
+                    }
                 }
             }
-        }).collect::<Vec<_>>();
+            Self::JSON | Self::YAML => {
+                let ds = diffs.iter().map(|diff| {
+                    let ttl_changes = diff.ttl_changes.iter().map(|(answer, server_ttl)| {
+                        let mut object = answer.to_json();
+                        object.as_object_mut().unwrap().insert("server_ttl".into(), (*server_ttl).into());
+                        object
+                    }).collect::<Vec<_>>();
 
-        json!(answers)
-    }
+                    json!({
+                        "name": diff.qname.to_json(),
+                        "class": diff.qclass.to_json(),
+                        "type": diff.qtype,
+                        "only_in_file": diff.only_in_file.to_json(),
+                        "only_on_server": diff.only_on_server.to_json(),
+                        "ttl_changes": ttl_changes,
+                    })
+                }).collect::<Vec<_>>();
 
-    fn json_record(&self, record: &Record) -> JsonValue {
-        match record {
-            Record::A(rec)      => json!({ "type": "A",     "address": rec.address.to_string() }),
-            Record::AAAA(rec)   => json!({ "type": "AAAA",  "address": rec.address.to_string() }),
-            Record::CAA(rec)    => json!({ "type": "CAA",   "critical": rec.critical, "tag": rec.tag, "value": rec.value }),
-            Record::CNAME(rec)  => json!({ "type": "CNAME", "domain": rec.domain.to_string() }),
-            Record::MX(rec)     => json!({ "type": "MX",    "preference": rec.preference, "exchange": rec.exchange }),
-            Record::NS(rec)     => json!({ "type": "NS",    "nameserver": rec.nameserver }),
-            Record::PTR(rec)    => json!({ "type": "PTR",   "cname": rec.cname }),
-            Record::SOA(rec)    => json!({ "type": "SOA",   "mname": rec.mname }),
-            Record::SRV(rec)    => json!({ "type": "SRV",   "priority": rec.priority, "weight": rec.weight, "port": rec.port, "target": rec.target, }),
-            Record::TXT(rec)    => json!({ "type": "TXT",   "message": rec.message }),
-            Record::Other { type_number, bytes } => {
-                let type_name = match type_number {
-                    UnknownQtype::HeardOf(name) => json!(name),
-                    UnknownQtype::UnheardOf(num) => json!(num),
-                };
-                json!({ "unknown": true, "type": type_name, "bytes": bytes })
+                if let Some(duration) = duration {
+                    let object = json!({ "schema_version": JSON_SCHEMA_VERSION, "differences": ds, "duration": duration });
+                    self.print_document(object);
+                }
+                else {
+                    let object = json!({ "schema_version": JSON_SCHEMA_VERSION, "differences": ds });
+                    self.print_document(object);
+                }
             }
-        }
-    }
-}
+            Self::Text(uc, tf) => {
+                let colours = uc.palette();
+
+                for diff in &diffs {
+                    for line in diff_lines(diff, &colours) {
+                        println!("{}", line);
+                    }
+                }
+
+                if diffs.is_empty() {
+                    println!("{}", tf.catalog.format(Message::RecordSetsMatch, &[ &total ]));
+                }
+                else {
+                    println!("{}", tf.catalog.format(Message::RecordSetsDiffer, &[ &diffs.len(), &total ]));
+                }
+
+                if let Some(dur) = duration {
+                    println!("{}", tf.catalog.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
+            Self::CSV => {
+                println!("{}", csv_row(&[ "change", "name", "type", "ttl", "rdata" ]));
+
+                for diff in &diffs {
+                    for answer in &diff.only_in_file {
+                        print_csv_diff_row("-", answer, None);
+                    }
+
+                    for answer in &diff.only_on_server {
+                        print_csv_diff_row("+", answer, None);
+                    }
+
+                    // TTL changes get a row for each side, like a change in
+                    // the data would.
+                    for (answer, server_ttl) in &diff.ttl_changes {
+                        print_csv_diff_row("-", answer, None);
+                        print_csv_diff_row("+", answer, Some(*server_ttl));
+                    }
+                }
+            }
+            Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                for diff in &diffs {
+                    for line in diff_lines(diff, &Colours::plain()) {
+                        println!("{}", line);
+                    }
+                }
+
+                println!("; {}", ENGLISH.format(Message::RecordSetsDiffer, &[ &diffs.len(), &total ]));
+
+                if let Some(dur) = duration {
+                    println!("; {}", ENGLISH.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
+        }
+    }
+
+    /// Prints the responses to the queries read from a file, labelling
+    /// each group of them with the line their queries came from. The
+    /// formats meant for other programs label each line in the short
+    /// output, and print everything else as one document, as the responses
+    /// already contain their queries.
+    pub fn print_batch(self, groups: Vec<BatchGroup>, duration: Option<Duration>, errors: Vec<QueryError>, chase: bool, sections: Sections) -> bool {
+        let errors = self.print_errors_unless_structured(errors);
+
+        match self {
+            Self::Short(tf) => {
+                let mut printed = false;
+
+                for group in &groups {
+                    for value in group.responses.iter().flat_map(|r| tf.short_values(r, chase)) {
+                        println!("{}\t{}", group.label, value);
+                        printed = true;
+                    }
+                }
+
+                if ! printed {
+                    eprintln!("{}", tf.catalog.message(Message::NoResults));
+                    return false;
+                }
+            }
+            Self::JSON | Self::YAML | Self::CSV | Self::Raw => {
+                let (mut responses, mut exchanges, mut validations) = (Vec::new(), Vec::new(), Vec::new());
+                for group in groups {
+                    responses.extend(group.responses);
+                    exchanges.extend(group.exchanges);
+                    validations.extend(group.validations);
+                }
+
+                return self.print(Printable { responses, exchanges, validations, errors, chase, sections }, duration);
+            }
+            Self::Text(_, tf) => {
+                for group in groups {
+                    println!("{}", group.label);
+                    let _ = self.print(Printable { responses: group.responses, exchanges: group.exchanges, validations: group.validations, errors: Vec::new(), chase, sections }, None);
+                    println!();
+                }
+
+                if let Some(dur) = duration {
+                    println!("{}", tf.catalog.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
+            Self::Zone | Self::Dig | Self::Hex => {
+                for group in groups {
+                    println!("; {}", group.label);
+                    let _ = self.print(Printable { responses: group.responses, exchanges: group.exchanges, validations: group.validations, errors: Vec::new(), chase, sections }, None);
+                    println!();
+                }
+
+                if let Some(dur) = duration {
+                    println!("; {}", ENGLISH.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints the messages found in a packet capture, each with the
+    /// addresses it was sent between. Returns false if this is the short
+    /// output mode and none of them had any answers.
+    pub fn print_captured(self, captured: Vec<(CapturedMessage, Response)>) -> bool {
+        match self {
+            Self::Short(..) | Self::CSV => {
+                let responses = captured.into_iter().map(|(_, response)| response).collect();
+                return self.print(Printable::responses(responses), None);
+            }
+            Self::JSON | Self::YAML => {
+                let ms = captured.iter().map(|(message, response)| {
+                    let mut object = json_response(response);
+                    let omut = object.as_object_mut().unwrap();
+                    omut.insert("source".into(), message.source.to_string().into());
+                    omut.insert("destination".into(), message.destination.to_string().into());
+                    omut.insert("transport".into(), transport_name(message.transport_type).into());
+                    object
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "messages": ms }));
+            }
+            Self::Text(..) => {
+                for (message, response) in captured {
+                    println!("{}", captured_summary(&message, &response));
+
+                    // Queries have nothing to put in a table, so printing
+                    // one would only say there were no results.
+                    let has_records = ! (response.answers.is_empty() && response.authorities.is_empty() && response.additionals.is_empty());
+                    if has_records || response.flags.error_code.is_some() {
+                        let _ = self.print(Printable::responses(vec![ response ]), None);
+                    }
+
+                    println!();
+                }
+            }
+            Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                for (message, response) in captured {
+                    println!("; {}", captured_summary(&message, &response));
+                    let _ = self.print(Printable::responses(vec![ response ]), None);
+                    println!();
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints what happened to one query received while serving: who sent
+    /// it, and the response it got from the upstream nameserver or the
+    /// zone, or the error that made it get a `SERVFAIL` instead.
+    pub fn print_served(self, served: Served) {
+        let summary = served_summary(&served);
+
+        match self {
+            Self::Short(tf) => {
+                let response = match &served.outcome {
+                    Outcome::Forwarded(reply)         => Some(&reply.response),
+                    Outcome::Authoritative(response)  => Some(response),
+                    Outcome::Failed(_)                => None,
+                };
+
+                if let Some(response) = response {
+                    for value in tf.short_values(response, false) {
+                        println!("{}\t{}", served.client, value);
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let mut object = json!({
+                    "schema_version": JSON_SCHEMA_VERSION,
+                    "client": served.client.to_string(),
+                    "transport": transport_name(served.transport_type),
+                });
+
+                let omut = object.as_object_mut().unwrap();
+                match served.outcome {
+                    Outcome::Forwarded(reply) => {
+                        omut.insert("round_trip_ms".into(), json!(reply.round_trip.as_millis() as u64));
+                        omut.insert("protocol".into(), protocol_name(reply.protocol).to_lowercase().into());
+                        omut.insert("response".into(), json_response(&reply.response));
+                    }
+                    Outcome::Authoritative(response) => {
+                        omut.insert("response".into(), json_response(&response));
+                    }
+                    Outcome::Failed(e) => {
+                        omut.insert("error".into(), true.into());
+                        omut.insert("error_kind".into(), FailureKind::of(&e).as_str().into());
+                        omut.insert("error_phase".into(), erroneous_phase(&e).into());
+                        omut.insert("error_message".into(), error_message(e).into());
+                    }
+                }
+
+                self.print_document(object);
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                println!("{}", summary);
+
+                let response = match served.outcome {
+                    Outcome::Forwarded(reply)         => Ok(reply.response),
+                    Outcome::Authoritative(response)  => Ok(response),
+                    Outcome::Failed(e)                => Err(e),
+                };
+
+                match response {
+                    Ok(response) => {
+                        let has_records = ! (response.answers.is_empty() && response.authorities.is_empty() && response.additionals.is_empty());
+                        if has_records || response.flags.error_code.is_some() {
+                            let _ = self.print(Printable::responses(vec![ response ]), None);
+                        }
+                    }
+                    Err(e) => {
+                        self.print_error(e);
+                    }
+                }
+
+                println!();
+            }
+        }
+    }
+
+    /// Prints each resolver’s answer to each query in a propagation check,
+    /// flagging the resolvers that disagree with the answer most of them
+    /// gave. Returns false if this is the short output mode and none of
+    /// them had any answers.
+    pub fn print_propagations(self, propagations: Vec<Propagation>, duration: Option<Duration>) -> bool {
+        match self {
+            Self::Short(tf) => {
+                let mut printed = false;
+
+                for propagation in propagations {
+                    for lookup in propagation.lookups {
+                        match lookup.result {
+                            Ok(response) => {
+                                for value in tf.short_values(&response, false) {
+                                    println!("{}\t{}", lookup.nameserver, value);
+                                    printed = true;
+                                }
+                            }
+                            Err(e) => {
+                                self.print_error(e);
+                            }
+                        }
+                    }
+                }
+
+                if ! printed {
+                    eprintln!("{}", tf.catalog.message(Message::NoResults));
+                    return false;
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let ps = propagations.into_iter().map(|propagation| {
+                    let agreement = propagation.agreement();
+                    let failed = propagation.lookups.iter().filter(|lookup| lookup.result.is_err()).count();
+
+                    let rs = propagation.lookups.into_iter().zip(&agreement).map(|(lookup, agrees)| {
+                        let mut object = json!({
+                            "name": resolver_name(&lookup.nameserver),
+                            "nameserver": lookup.nameserver,
+                            "agrees": agrees,
+                        });
+
+                        let omut = object.as_object_mut().unwrap();
+                        match lookup.result {
+                            Ok(response) => {
+                                omut.insert("response".into(), json_response(&response));
+                            }
+                            Err(e) => {
+                                omut.insert("error_kind".into(), FailureKind::of(&e).as_str().into());
+                                omut.insert("error_phase".into(), erroneous_phase(&e).into());
+                                omut.insert("error_message".into(), error_message(e).into());
+                            }
+                        }
+
+                        object
+                    }).collect::<Vec<_>>();
+
+                    json!({
+                        "query": propagation.query.to_json(),
+                        "agreeing": agreement.iter().filter(|agrees| **agrees).count(),
+                        "failed": failed,
+                        "resolvers": rs,
+                    })
+                }).collect::<Vec<_>>();
+
+                if let Some(duration) = duration {
+                    let object = json!({ "schema_version": JSON_SCHEMA_VERSION, "propagations": ps, "duration": duration });
+                    self.print_document(object);
+                }
+                else {
+                    let object = json!({ "schema_version": JSON_SCHEMA_VERSION, "propagations": ps });
+                    self.print_document(object);
+                }
+            }
+            Self::Text(uc, tf) => {
+                let colours = uc.palette();
+
+                for propagation in propagations {
+                    let agreement = propagation.agreement();
+                    let agreeing = agreement.iter().filter(|agrees| **agrees).count();
+                    let failed = propagation.lookups.iter().filter(|lookup| lookup.result.is_err()).count();
+
+                    let query = &propagation.query;
+                    println!("{} {}", colours.qname.paint(tf.format_name(&query.qname)), type_name(query.qtype));
+
+                    let rows = propagation.lookups.into_iter().zip(&agreement).map(|(lookup, agrees)| {
+                        let name = resolver_name(&lookup.nameserver).unwrap_or_default();
+                        let summary = match lookup.result {
+                            Ok(response)  => tf.answer_set_summary(&AnswerSet::of(&response)),
+                            Err(e)        => format!("Error [{}]: {}", erroneous_phase(&e), error_message(e)),
+                        };
+
+                        (*agrees, name, lookup.nameserver, summary)
+                    }).collect::<Vec<_>>();
+
+                    let name_width = rows.iter().map(|r| r.1.chars().count()).max().unwrap_or(0);
+                    let nameserver_width = rows.iter().map(|r| r.2.chars().count()).max().unwrap_or(0);
+
+                    for (agrees, name, nameserver, summary) in rows {
+                        let line = format!("{:<nw$}  {:<sw$}  {}", name, nameserver, summary, nw = name_width, sw = nameserver_width);
+                        if agrees {
+                            println!("  {}", line);
+                        }
+                        else {
+                            println!("{}", colours.disagreement.paint(format!("✗ {}", line)));
+                        }
+                    }
+
+                    if agreeing == agreement.len() {
+                        println!("All {} resolvers agree", agreement.len());
+                    }
+                    else if failed == 0 {
+                        println!("{} of {} resolvers agree", agreeing, agreement.len());
+                    }
+                    else {
+                        println!("{} of {} resolvers agree, {} failed", agreeing, agreement.len(), failed);
+                    }
+
+                    println!();
+                }
+
+                if let Some(dur) = duration {
+                    println!("{}", tf.catalog.format(Message::RanIn, &[ &dur.as_millis() ]));
+                }
+            }
+            Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                println!("{}", csv_row(&[ "name", "type", "nameserver", "agrees", "status", "rdata" ]));
+
+                for propagation in propagations {
+                    let sets = propagation.answer_sets();
+                    let agreement = propagation.agreement();
+                    let query = &propagation.query;
+
+                    for ((lookup, set), agrees) in propagation.lookups.iter().zip(&sets).zip(&agreement) {
+                        let prefix = [ query.qname.to_string(), type_name(query.qtype), lookup.nameserver.clone(), agrees.to_string() ];
+
+                        match set {
+                            Some(set) if ! set.records.is_empty() => {
+                                for record in &set.records {
+                                    println!("{}", csv_row(&[ &prefix[..], &[ rcode_name(set.rcode), record.to_string() ] ].concat()));
+                                }
+                            }
+                            Some(set) => {
+                                println!("{}", csv_row(&[ &prefix[..], &[ rcode_name(set.rcode), String::new() ] ].concat()));
+                            }
+                            None => {
+                                println!("{}", csv_row(&[ &prefix[..], &[ "ERROR".into(), String::new() ] ].concat()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints one round of a watch, headed by the time it was sent at. The
+    /// text output marks answers that were added with `+`, ones that went
+    /// away with `-`, and ones with a different TTL with `~`, while the
+    /// JSON and YAML output prints a document for each round, and the short
+    /// output prints each current value next to the time.
+    pub fn print_watch_round(self, now: u64, answers: &[WatchedAnswer]) {
+        match self {
+            Self::Short(tf) => {
+                for watched in answers.iter().filter(|w| w.change != Change::Removed) {
+                    if let Answer::Standard { record, .. } = &watched.answer {
+                        println!("{}\t{}", watch_time(now), tf.record_short_value(record));
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let ws = answers.iter().map(|watched| {
+                    let mut object = watched.answer.to_json();
+                    let omut = object.as_object_mut().unwrap();
+                    match watched.change {
+                        Change::Unchanged  => { omut.insert("change".into(), "unchanged".into()); }
+                        Change::Added      => { omut.insert("change".into(), "added".into()); }
+                        Change::Removed    => { omut.insert("change".into(), "removed".into()); }
+                        Change::TTL(ttl)   => { omut.insert("change".into(), "ttl".into()); omut.insert("previous_ttl".into(), ttl.into()); }
+                    }
+                    object
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "time": now, "answers": ws }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+                println!("{}", watch_time(now));
+
+                for watched in answers {
+                    let line = match watched.change {
+                        Change::Unchanged  => format!("  {}", watched.answer),
+                        Change::Added      => colours.only_on_server.paint(format!("+ {}", watched.answer)).to_string(),
+                        Change::Removed    => colours.only_in_file.paint(format!("- {}", watched.answer)).to_string(),
+                        Change::TTL(ttl)   => colours.ttl_changed.paint(format!("~ {} ; TTL was {}", watched.answer, ttl)).to_string(),
+                    };
+
+                    println!("{}", line);
+                }
+
+                if answers.is_empty() {
+                    println!("  {}", tf.catalog.message(Message::NoResults));
+                }
+
+                println!();
+            }
+        }
+    }
+
+    /// Prints a summary of how long the queries took to get a response,
+    /// how many were retried, and which protocols the responses came over.
+    /// The formats meant for other programs print it to standard error,
+    /// apart from JSON and YAML, which print it as a document of its own.
+    pub fn print_stats(self, stats: &Stats) {
+        let protocols = stats.protocols.iter()
+                             .map(|(protocol, count)| format!("{} {}", protocol_name(*protocol), count))
+                             .collect::<Vec<_>>().join(", ");
+
+        let lines = [
+            format!("{} {}, {} failed, {} retried", stats.queries, if stats.queries == 1 { "query" } else { "queries" }, stats.failed, stats.retries),
+            format!("Round trip: min {}ms, avg {}ms, max {}ms", stats.min.as_millis(), stats.avg.as_millis(), stats.max.as_millis()),
+            format!("Protocols: {}", if protocols.is_empty() { "none".into() } else { protocols }),
+        ];
+
+        match self {
+            Self::JSON | Self::YAML => {
+                let ps = stats.protocols.iter()
+                              .map(|(protocol, count)| (protocol_name(*protocol).to_lowercase(), json!(count)))
+                              .collect::<serde_json::Map<_, _>>();
+
+                self.print_document(json!({
+                    "schema_version": JSON_SCHEMA_VERSION,
+                    "stats": {
+                        "queries": stats.queries,
+                        "failed": stats.failed,
+                        "retries": stats.retries,
+                        "min_round_trip_ms": stats.min.as_millis() as u64,
+                        "avg_round_trip_ms": stats.avg.as_millis() as u64,
+                        "max_round_trip_ms": stats.max.as_millis() as u64,
+                        "protocols": ps,
+                    },
+                }));
+            }
+            Self::Text(..) => {
+                for line in &lines {
+                    println!("{}", line);
+                }
+            }
+            Self::Zone | Self::Dig => {
+                for line in &lines {
+                    println!(";; {}", line);
+                }
+            }
+            Self::Short(..) | Self::CSV | Self::Hex | Self::Raw => {
+                for line in &lines {
+                    eprintln!("{}", line);
+                }
+            }
+        }
+    }
+
+    /// Prints how the second of two messages differs from the first, one
+    /// change per line, with records only in the first marked `-`, records
+    /// only in the second marked `+`, and everything else marked `~`.
+    pub fn print_response_diff(self, diff: &ResponseDiff) {
+        match self {
+            Self::JSON | Self::YAML => {
+                let changes = diff.changes.iter().map(json_change).collect::<Vec<_>>();
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "changes": changes }));
+            }
+            Self::Text(uc, tf) => {
+                let colours = uc.palette();
+
+                for change in &diff.changes {
+                    let line = change.to_string();
+                    let style = match change {
+                        diff::Change::Removed(..) | diff::Change::QueryRemoved(_)  => colours.only_in_file,
+                        diff::Change::Added(..) | diff::Change::QueryAdded(_)      => colours.only_on_server,
+                        _                                                          => colours.ttl_changed,
+                    };
+
+                    println!("{}", style.paint(line));
+                }
+
+                if diff.is_empty() {
+                    println!("{}", tf.catalog.message(Message::MessagesSame));
+                }
+            }
+            Self::Short(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                print!("{}", diff);
+            }
+        }
+    }
+
+    /// Prints which CAs may issue certificates for each domain, along with
+    /// where the CAA records that decide it were found.
+    pub fn print_caa_checks(self, checks: Vec<Result<CaaCheck, LookupError>>) {
+        match self {
+            Self::Short(tf) => {
+                for check in checks {
+                    match check {
+                        Ok(check)  => println!("{}\t{}", check.domain, permission_summary(&check.issue(), tf.catalog)),
+                        Err(e)     => eprintln!("{}", caa_error_message(e)),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let cs = checks.into_iter().map(|check| {
+                    match check {
+                        Ok(check) => {
+                            let records = check.records.iter().map(|caa| {
+                                json!({ "critical": caa.critical, "tag": caa.tag, "value": caa.value })
+                            }).collect::<Vec<_>>();
+
+                            json!({
+                                "domain": check.domain.to_string(),
+                                "checked": check.checked.iter().map(Name::to_string).collect::<Vec<_>>(),
+                                "found_at": check.found_at.as_ref().map(Name::to_string),
+                                "records": records,
+                                "issue": json_permission(&check.issue()),
+                                "issuewild": json_permission(&check.issuewild()),
+                                "iodef": check.iodef(),
+                            })
+                        }
+                        Err(e) => {
+                            json_lookup_error(e)
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "caa_checks": cs }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+                let width = label_width(tf.catalog, &[ Message::Certificates, Message::WildcardCertificates, Message::IncidentReports ]);
+
+                for check in checks {
+                    let check = match check {
+                        Ok(check) => check,
+                        Err(e) => {
+                            eprintln!("{}", caa_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(tf.format_name(&check.domain)));
+
+                    let checked = check.checked.iter().map(|name| tf.format_name(name)).collect::<Vec<_>>().join(", ");
+                    match &check.found_at {
+                        Some(found_at) => {
+                            println!("  {}", tf.catalog.format(Message::CaaRecordsAt, &[ &tf.format_name(found_at), &checked ]));
+                            for caa in &check.records {
+                                println!("    {}", caa);
+                            }
+                        }
+                        None => {
+                            println!("  {}", tf.catalog.format(Message::NoCaaRecords, &[ &checked ]));
+                        }
+                    }
+
+                    println!("  {:width$}  {}", tf.catalog.message(Message::Certificates), permission_summary(&check.issue(), tf.catalog), width = width);
+                    println!("  {:width$}  {}", tf.catalog.message(Message::WildcardCertificates), permission_summary(&check.issuewild(), tf.catalog), width = width);
+
+                    for url in check.iodef() {
+                        println!("  {:width$}  {}", tf.catalog.message(Message::IncidentReports), url, width = width);
+                    }
+
+                    for caa in check.unknown_critical() {
+                        let problem = tf.catalog.format(Message::UnknownCriticalTag, &[ &format!("{:?}", caa.tag) ]);
+                        println!("  {}", tf.catalog.format(Message::Problem, &[ &problem ]));
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints the mail exchanges of each domain and their addresses, along
+    /// with any problems found with them.
+    pub fn print_mx_checks(self, checks: Vec<Result<MxCheck, LookupError>>) {
+        match self {
+            Self::Short(..) => {
+                for check in checks {
+                    match check {
+                        Ok(check) => {
+                            if check.null_mx {
+                                println!("0 .");
+                            }
+
+                            for exchange in &check.exchanges {
+                                println!("{} {}", exchange.preference, exchange.name);
+                            }
+
+                            for problem in check.problems() {
+                                eprintln!("{}: {}", check.domain, problem);
+                            }
+                        }
+                        Err(e) => eprintln!("{}", lookup_error_message(e)),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let cs = checks.into_iter().map(|check| {
+                    match check {
+                        Ok(check) => {
+                            let exchanges = check.exchanges.iter().map(|exchange| {
+                                json!({
+                                    "preference": exchange.preference,
+                                    "exchange": exchange.name.to_string(),
+                                    "cname": exchange.cname.as_ref().map(Name::to_string),
+                                    "addresses": exchange.addresses.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                                })
+                            }).collect::<Vec<_>>();
+
+                            json!({
+                                "domain": check.domain.to_string(),
+                                "null_mx": check.null_mx,
+                                "implicit": check.is_implicit(),
+                                "exchanges": exchanges,
+                                "problems": check.problems().iter().map(ToString::to_string).collect::<Vec<_>>(),
+                            })
+                        }
+                        Err(e) => {
+                            json_lookup_error(e)
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "mx_checks": cs }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for check in checks {
+                    let check = match check {
+                        Ok(check) => check,
+                        Err(e) => {
+                            eprintln!("{}", lookup_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(tf.format_name(&check.domain)));
+
+                    if check.null_mx {
+                        println!("  {}", tf.catalog.message(Message::NullMx));
+                    }
+                    else if check.is_implicit() {
+                        println!("  {}", tf.catalog.message(Message::ImplicitMx));
+                    }
+
+                    for exchange in &check.exchanges {
+                        let addresses = if exchange.addresses.is_empty() {
+                            tf.catalog.message(Message::NoAddresses).into()
+                        }
+                        else {
+                            exchange.addresses.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+                        };
+
+                        println!("  {:>5} {}  {}", exchange.preference, tf.format_name(&exchange.name), addresses);
+                    }
+
+                    for problem in check.problems() {
+                        println!("  {}", tf.catalog.format(Message::Problem, &[ &mx_problem_message(&problem, tf.catalog) ]));
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints the names each address’s reverse DNS points to and their
+    /// addresses, and whether any of them point back to it.
+    pub fn print_fcrdns_checks(self, checks: Vec<Result<FcrdnsCheck, LookupError>>) {
+        match self {
+            Self::Short(..) => {
+                for check in checks {
+                    match check {
+                        Ok(check) => {
+                            let result = if check.passed() { "pass" } else { "fail" };
+                            let mut fields = vec![ check.address.to_string(), result.into() ];
+                            fields.extend(check.names.iter().filter(|name| check.confirms(name)).map(|name| name.name.to_string()));
+                            println!("{}", fields.join("\t"));
+                        }
+                        Err(e) => eprintln!("{}", lookup_error_message(e)),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let cs = checks.into_iter().map(|check| {
+                    match check {
+                        Ok(check) => {
+                            let names = check.names.iter().map(|name| {
+                                json!({
+                                    "name": name.name.to_string(),
+                                    "addresses": name.addresses.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                                    "confirmed": check.confirms(name),
+                                })
+                            }).collect::<Vec<_>>();
+
+                            json!({
+                                "address": check.address.to_string(),
+                                "names": names,
+                                "passed": check.passed(),
+                            })
+                        }
+                        Err(e) => {
+                            json_lookup_error(e)
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "fcrdns_checks": cs }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for check in checks {
+                    let check = match check {
+                        Ok(check) => check,
+                        Err(e) => {
+                            eprintln!("{}", lookup_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(check.address.to_string()));
+
+                    if check.names.is_empty() {
+                        println!("  {}", tf.catalog.message(Message::NoPtrRecords));
+                    }
+
+                    for name in &check.names {
+                        let addresses = if name.addresses.is_empty() {
+                            tf.catalog.message(Message::NoAddresses).into()
+                        }
+                        else {
+                            name.addresses.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+                        };
+
+                        let mark = if check.confirms(name) { "✓" } else { "✗" };
+                        println!("  {} {}  {}", mark, tf.format_name(&name.name), addresses);
+                    }
+
+                    if check.passed() {
+                        println!("  {}", tf.catalog.message(Message::ForwardConfirmed));
+                    }
+                    else {
+                        println!("  {}", tf.catalog.message(Message::NotForwardConfirmed));
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints what was found at each made-up name under a domain, and the
+    /// records a wildcard synthesises for them, if there is one.
+    pub fn print_wildcard_probes(self, probes: Vec<Result<WildcardProbe, LookupError>>) {
+        match self {
+            Self::Short(tf) => {
+                for probe in probes {
+                    match probe {
+                        Ok(probe) => {
+                            for record in probe.synthesised() {
+                                println!("{}", tf.record_short_value(record));
+                            }
+                        }
+                        Err(e) => eprintln!("{}", lookup_error_message(e)),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let ps = probes.into_iter().map(|probe| {
+                    match probe {
+                        Ok(probe) => {
+                            let names = probe.probes.iter().map(|p| {
+                                json!({
+                                    "name": p.name.to_string(),
+                                    "status": presence_name(p.presence),
+                                    "answers": p.answers.to_json(),
+                                })
+                            }).collect::<Vec<_>>();
+
+                            json!({
+                                "domain": probe.domain.to_string(),
+                                "type": type_name(probe.qtype),
+                                "domain_status": presence_name(probe.domain_presence),
+                                "wildcard": probe.has_wildcard(),
+                                "probes": names,
+                            })
+                        }
+                        Err(e) => {
+                            json_lookup_error(e)
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "wildcard_probes": ps }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for probe in probes {
+                    let probe = match probe {
+                        Ok(probe) => probe,
+                        Err(e) => {
+                            eprintln!("{}", lookup_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    let qtype = type_name(probe.qtype);
+                    println!("{} {}", colours.qname.paint(tf.format_name(&probe.domain)), qtype);
+
+                    match probe.domain_presence {
+                        Presence::Records   => println!("  {}", tf.catalog.format(Message::DomainHasRecords, &[ &qtype ])),
+                        Presence::NoData    => println!("  {}", tf.catalog.format(Message::DomainHasNoRecords, &[ &qtype ])),
+                        Presence::NxDomain  => println!("  {}", tf.catalog.message(Message::DomainDoesNotExist)),
+                    }
+
+                    for p in &probe.probes {
+                        let status = match p.presence {
+                            Presence::Records   => tf.catalog.format(Message::RecordCount, &[ &p.answers.len() ]),
+                            Presence::NoData    => tf.catalog.format(Message::NoRecordsOfType, &[ &qtype ]),
+                            Presence::NxDomain  => "NXDOMAIN".into(),
+                        };
+
+                        println!("  {}  {}", tf.format_name(&p.name), status);
+                    }
+
+                    if ! probe.has_wildcard() {
+                        println!("  {}", tf.catalog.message(Message::NoWildcard));
+                    }
+                    else if probe.synthesised().is_empty() {
+                        println!("  {}", tf.catalog.format(Message::WildcardWithoutRecords, &[ &qtype ]));
+                    }
+                    else {
+                        println!("  {}", tf.catalog.message(Message::WildcardSynthesising));
+                        for record in probe.synthesised() {
+                            println!("    {} {}", type_name(record.type_number()), tf.record_payload_summary(record));
+                        }
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints the addresses the system resolver and DNS gave for each
+    /// domain side by side, highlighting the ones only one of them gave.
+    pub fn print_system_comparisons(self, comparisons: Vec<Result<SystemComparison, LookupError>>) {
+        match self {
+            Self::Short(..) => {
+                for comparison in comparisons {
+                    match comparison {
+                        Ok(comparison) => {
+                            for address in comparison.addresses() {
+                                println!("{} {}", address, address_source(&comparison, address));
+                            }
+                        }
+                        Err(e) => eprintln!("{}", lookup_error_message(e)),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let cs = comparisons.into_iter().map(|comparison| {
+                    match comparison {
+                        Ok(comparison) => {
+                            let ips = |addresses: &[std::net::IpAddr]| addresses.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+                            json!({
+                                "domain": comparison.domain.to_string(),
+                                "system": ips(comparison.system_addresses()),
+                                "system_error": comparison.system.as_ref().err(),
+                                "dns": ips(&comparison.dns),
+                                "hosts_file": ips(&comparison.hosts_file),
+                                "agrees": comparison.agrees(),
+                                "cause": comparison.cause().map(cause_name),
+                            })
+                        }
+                        Err(e) => {
+                            json_lookup_error(e)
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "system_comparisons": cs }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for comparison in comparisons {
+                    let comparison = match comparison {
+                        Ok(comparison) => comparison,
+                        Err(e) => {
+                            eprintln!("{}", lookup_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(tf.format_name(&comparison.domain)));
+
+                    if let Err(e) = &comparison.system {
+                        println!("  {}", tf.catalog.format(Message::SystemResolverFailed, &[ e ]));
+                    }
+
+                    let (address_heading, system_heading, yes) = (tf.catalog.message(Message::Address), tf.catalog.message(Message::System), tf.catalog.message(Message::Yes));
+                    let addresses = comparison.addresses();
+                    let width = addresses.iter().map(|a| a.to_string().len()).max().unwrap_or_default().max(address_heading.chars().count());
+                    let system_width = system_heading.chars().count().max(yes.chars().count());
+                    if ! addresses.is_empty() {
+                        println!("  {:width$}  {:system_width$}  DNS", address_heading, system_heading, width = width, system_width = system_width);
+                    }
+
+                    for address in addresses {
+                        let system = if comparison.system_addresses().contains(&address) { yes } else { "-" };
+                        let dns = if comparison.dns.contains(&address) { yes } else { "-" };
+                        let line = format!("{:width$}  {:system_width$}  {}", address.to_string(), system, dns, width = width, system_width = system_width);
+
+                        if system == dns {
+                            println!("  {}", line);
+                        }
+                        else {
+                            println!("  {}", colours.disagreement.paint(line));
+                        }
+                    }
+
+                    match comparison.cause() {
+                        None                       => println!("  {}", tf.catalog.message(Message::SystemAgrees)),
+                        Some(Cause::HostsFile)     => println!("  {}", tf.catalog.message(Message::DisagreesHostsFile)),
+                        Some(Cause::MulticastDns)  => println!("  {}", tf.catalog.message(Message::DisagreesMulticastDns)),
+                        Some(Cause::Unknown)       => println!("  {}", tf.catalog.message(Message::DisagreesUnknown)),
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints the NAT64 prefixes each nameserver synthesises AAAA records
+    /// with, returning whether anything was printed in short mode.
+    pub fn print_nat64_prefixes(self, discoveries: Vec<(String, Result<Vec<Prefix>, TransportError>)>) -> bool {
+        match self {
+            Self::Short(tf) => {
+                let mut printed = false;
+                for (_, result) in discoveries {
+                    match result {
+                        Ok(prefixes) => {
+                            for prefix in prefixes {
+                                println!("{}", prefix);
+                                printed = true;
+                            }
+                        }
+                        Err(e) => self.print_error(e),
+                    }
+                }
+
+                if ! printed {
+                    eprintln!("{}", tf.catalog.message(Message::NoResults));
+                }
+
+                return printed;
+            }
+            Self::JSON | Self::YAML => {
+                let ds = discoveries.into_iter().map(|(nameserver, result)| {
+                    match result {
+                        Ok(prefixes) => {
+                            let ps = prefixes.iter().map(|prefix| {
+                                json!({
+                                    "prefix": prefix.to_string(),
+                                    "well_known": *prefix == Prefix::WELL_KNOWN,
+                                })
+                            }).collect::<Vec<_>>();
+
+                            json!({ "nameserver": nameserver, "dns64": ! prefixes.is_empty(), "prefixes": ps })
+                        }
+                        Err(e) => {
+                            json!({ "nameserver": nameserver, "error_kind": FailureKind::of(&e).as_str(), "error_phase": erroneous_phase(&e), "error_message": error_message(e) })
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "nat64_prefixes": ds }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for (nameserver, result) in discoveries {
+                    let prefixes = match result {
+                        Ok(prefixes) => prefixes,
+                        Err(e) => {
+                            eprintln!("Error [{}] asking {}: {}", erroneous_phase(&e), nameserver, error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(&nameserver));
+
+                    if prefixes.is_empty() {
+                        println!("  {}", tf.catalog.message(Message::NoDns64));
+                    }
+
+                    for prefix in prefixes {
+                        if prefix == Prefix::WELL_KNOWN {
+                            println!("  {}", tf.catalog.format(Message::WellKnownNat64Prefix, &[ &prefix ]));
+                        }
+                        else {
+                            println!("  {}", tf.catalog.format(Message::Nat64Prefix, &[ &prefix ]));
+                        }
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Prints the serial number each of a zone’s nameservers is serving,
+    /// and how far behind the newest one each of them is.
+    pub fn print_soa_checks(self, checks: Vec<Result<SoaCheck, SoaError>>) {
+        match self {
+            Self::Short(..) => {
+                for check in checks {
+                    let check = match check {
+                        Ok(check) => check,
+                        Err(e) => {
+                            eprintln!("{}", soa_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    for server in check.servers {
+                        match (server.address, server.result) {
+                            (Some(address), Ok(serial))  => println!("{}\t{}", address, serial),
+                            (_, Err(e))                  => eprintln!("{}: {}", server.name, server_error_message(e)),
+                            (None, Ok(_))                => {}
+                        }
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let cs = checks.into_iter().map(|check| {
+                    let check = match check {
+                        Ok(check) => check,
+                        Err(SoaError::Lookup(e)) => {
+                            return json_lookup_error(e);
+                        }
+                        Err(e) => {
+                            return json!({ "name": soa_error_name(&e).to_string(), "error_kind": e.kind().as_str(), "error_phase": "response", "error_message": soa_error_message(e) });
+                        }
+                    };
+
+                    let newest_serial = check.newest_serial();
+                    let in_sync = check.in_sync();
+                    let zone = check.zone.to_string();
+                    let skews = check.servers.iter().map(|server| server.result.as_ref().map(|serial| check.skew(*serial)).unwrap_or_default()).collect::<Vec<_>>();
+                    let servers = check.servers.into_iter().zip(skews).map(|(server, skew)| {
+                        let mut object = json!({
+                            "name": server.name.to_string(),
+                            "address": server.address.map(|a| a.to_string()),
+                        });
+
+                        let omut = object.as_object_mut().unwrap();
+                        match server.result {
+                            Ok(serial) => {
+                                omut.insert("serial".into(), serial.into());
+                                omut.insert("skew".into(), skew.into());
+                            }
+                            Err(e) => {
+                                omut.insert("error_kind".into(), e.kind().as_str().into());
+                                omut.insert("error".into(), server_error_message(e).into());
+                            }
+                        }
+
+                        object
+                    }).collect::<Vec<_>>();
+
+                    json!({ "zone": zone, "newest_serial": newest_serial, "in_sync": in_sync, "servers": servers })
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "soa_checks": cs }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for check in checks {
+                    let check = match check {
+                        Ok(check) => check,
+                        Err(e) => {
+                            eprintln!("{}", soa_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(tf.format_name(&check.zone)));
+
+                    let newest_serial = check.newest_serial();
+                    let in_sync = check.in_sync();
+                    let names = check.servers.iter().map(|server| tf.format_name(&server.name)).collect::<Vec<_>>();
+                    let addresses = check.servers.iter().map(|server| server.address.map(|a| a.to_string()).unwrap_or_default()).collect::<Vec<_>>();
+                    let name_width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+                    let address_width = addresses.iter().map(String::len).max().unwrap_or(0);
+                    let skews = check.servers.iter().map(|server| server.result.as_ref().map(|serial| check.skew(*serial)).unwrap_or_default()).collect::<Vec<_>>();
+
+                    for (((server, name), address), skew) in check.servers.into_iter().zip(names).zip(addresses).zip(skews) {
+                        let status = match server.result {
+                            Ok(serial) if skew == 0  => serial.to_string(),
+                            Ok(serial)               => colours.disagreement.paint(tf.catalog.format(Message::SerialBehind, &[ &serial, &skew ])).to_string(),
+                            Err(e) => colours.disagreement.paint(server_error_message(e)).to_string(),
+                        };
+
+                        println!("  {:nw$}  {:aw$}  {}", name, address, status, nw = name_width, aw = address_width);
+                    }
+
+                    match newest_serial {
+                        Some(serial) if in_sync  => println!("  {}", tf.catalog.format(Message::InSync, &[ &serial ])),
+                        Some(serial)             => println!("  {}", tf.catalog.format(Message::OutOfSync, &[ &serial ])),
+                        None                     => println!("  {}", tf.catalog.message(Message::NoSerial)),
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints the nameservers each zone is delegated to, according to its
+    /// parent and to itself, what each of them said when asked about it,
+    /// and any problems with the delegation.
+    pub fn print_delegations(self, delegations: Vec<Result<Delegation, DelegationError>>) {
+        match self {
+            Self::Short(..) => {
+                for delegation in delegations {
+                    match delegation {
+                        Ok(delegation) => {
+                            for problem in delegation.problems() {
+                                println!("{}\t{}", delegation.zone, problem);
+                            }
+                        }
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let ds = delegations.into_iter().map(|delegation| {
+                    let delegation = match delegation {
+                        Ok(delegation) => delegation,
+                        Err(DelegationError::Trace(TraceError::Transport(e))) => {
+                            return json!({ "error_kind": FailureKind::of(&e).as_str(), "error_phase": erroneous_phase(&e), "error_message": error_message(e) });
+                        }
+                        Err(e) => {
+                            return json!({ "error_kind": e.kind().as_str(), "error_phase": "trace", "error_message": e.to_string() });
+                        }
+                    };
+
+                    let problems = delegation.problems().iter().map(ToString::to_string).collect::<Vec<_>>();
+
+                    let parent_nameservers = delegation.parent_nameservers.iter().map(|ns| {
+                        json!({ "name": ns.name.to_string(), "glue": ns.address.map(|a| a.to_string()) })
+                    }).collect::<Vec<_>>();
+
+                    let servers = delegation.servers.into_iter().map(|server| {
+                        let mut object = json!({
+                            "name": server.name.to_string(),
+                            "address": server.address.map(|a| a.to_string()),
+                            "authoritative": server.result.is_ok(),
+                        });
+
+                        if let Err(lameness) = server.result {
+                            object.as_object_mut().unwrap().insert("error".into(), lameness.to_string().into());
+                        }
+
+                        object
+                    }).collect::<Vec<_>>();
+
+                    json!({
+                        "zone": delegation.zone.to_string(),
+                        "parent": delegation.parent.to_string(),
+                        "parent_nameservers": parent_nameservers,
+                        "child_nameservers": delegation.child_nameservers.iter().map(Name::to_string).collect::<Vec<_>>(),
+                        "servers": servers,
+                        "problems": problems,
+                    })
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "delegations": ds }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+                let width = label_width(tf.catalog, &[ Message::ParentLists, Message::ZoneLists ]);
+
+                for delegation in delegations {
+                    let delegation = match delegation {
+                        Ok(delegation) => delegation,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            continue;
+                        }
+                    };
+
+                    println!("{} {}", colours.qname.paint(tf.format_name(&delegation.zone)), tf.catalog.format(Message::DelegatedFrom, &[ &tf.format_name(&delegation.parent) ]));
+
+                    let parent = delegation.parent_nameservers.iter().map(|ns| {
+                        match ns.address {
+                            Some(glue)  => tf.catalog.format(Message::Glue, &[ &tf.format_name(&ns.name), &glue ]),
+                            None        => tf.format_name(&ns.name),
+                        }
+                    }).collect::<Vec<_>>();
+                    println!("  {:width$}  {}", tf.catalog.message(Message::ParentLists), parent.join(", "), width = width);
+
+                    let child = delegation.child_nameservers.iter().map(|name| tf.format_name(name)).collect::<Vec<_>>();
+                    if child.is_empty() {
+                        println!("  {:width$}  {}", tf.catalog.message(Message::ZoneLists), tf.catalog.message(Message::NoAnswer), width = width);
+                    }
+                    else {
+                        println!("  {:width$}  {}", tf.catalog.message(Message::ZoneLists), child.join(", "), width = width);
+                    }
+
+                    for server in &delegation.servers {
+                        let address = server.address.map(|a| a.to_string()).unwrap_or_default();
+                        let status = match &server.result {
+                            Ok(_)          => tf.catalog.message(Message::Authoritative).to_string(),
+                            Err(lameness)  => colours.disagreement.paint(tf.catalog.format(Message::Lame, &[ &lameness_message(lameness, tf.catalog) ])).to_string(),
+                        };
+
+                        println!("  {} {}  {}", tf.format_name(&server.name), address, status);
+                    }
+
+                    for problem in delegation.problems() {
+                        println!("  {}", tf.catalog.format(Message::Problem, &[ &delegation_problem_message(&problem, tf.catalog) ]));
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints the places each service can be reached at, in the order to
+    /// try them in. The short output prints just the address and port of
+    /// each, one per line, ready to connect to.
+    pub fn print_srv_resolutions(self, resolutions: Vec<Result<Resolution, LookupError>>) {
+        match self {
+            Self::Short(..) => {
+                for resolution in resolutions {
+                    match resolution {
+                        Ok(resolution) => {
+                            for address in resolution.endpoints.iter().flat_map(Endpoint::socket_addrs) {
+                                println!("{}", address);
+                            }
+                        }
+                        Err(e) => eprintln!("{}", lookup_error_message(e)),
+                    }
+                }
+            }
+            Self::JSON | Self::YAML => {
+                let rs = resolutions.into_iter().map(|resolution| {
+                    match resolution {
+                        Ok(resolution) => {
+                            let endpoints = resolution.endpoints.iter().map(|endpoint| {
+                                json!({
+                                    "priority": endpoint.priority,
+                                    "weight": endpoint.weight,
+                                    "port": endpoint.port,
+                                    "target": endpoint.target.to_string(),
+                                    "addresses": endpoint.addresses.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                                })
+                            }).collect::<Vec<_>>();
+
+                            json!({ "name": resolution.name.to_string(), "unavailable": resolution.unavailable, "endpoints": endpoints })
+                        }
+                        Err(e) => {
+                            json_lookup_error(e)
+                        }
+                    }
+                }).collect::<Vec<_>>();
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "srv_resolutions": rs }));
+            }
+            Self::Text(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let (colours, tf) = self.text_settings();
+
+                for resolution in resolutions {
+                    let resolution = match resolution {
+                        Ok(resolution) => resolution,
+                        Err(e) => {
+                            eprintln!("{}", lookup_error_message(e));
+                            continue;
+                        }
+                    };
+
+                    println!("{}", colours.qname.paint(tf.format_name(&resolution.name)));
+
+                    if resolution.unavailable {
+                        println!("  {}", tf.catalog.message(Message::ServiceUnavailable));
+                    }
+                    else if resolution.endpoints.is_empty() {
+                        println!("  {}", tf.catalog.message(Message::NoSrvRecords));
+                    }
+
+                    for endpoint in &resolution.endpoints {
+                        println!("  {}", tf.catalog.format(Message::SrvEndpoint, &[ &endpoint.priority, &endpoint.weight, &endpoint.port, &tf.format_name(&endpoint.target) ]));
+
+                        if endpoint.addresses.is_empty() {
+                            println!("    {}", tf.catalog.message(Message::NoAddresses));
+                        }
+
+                        for address in endpoint.socket_addrs() {
+                            println!("    {}", address);
+                        }
+                    }
+
+                    println!();
+                }
+            }
+        }
+    }
+
+    /// Prints an explanation of each email policy found among the answers,
+    /// after the responses. Like the statistics, the JSON and YAML output
+    /// prints them as a second document, and the short and CSV output
+    /// prints them to standard error.
+    pub fn print_interpretations(self, interpretations: &[Interpretation]) {
+        match self {
+            Self::JSON | Self::YAML => {
+                let is = interpretations.iter().map(json_interpretation).collect::<Vec<_>>();
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "interpretations": is }));
+            }
+            Self::Text(..) => {
+                for line in interpretations.iter().flat_map(interpretation_lines) {
+                    println!("{}", line);
+                }
+            }
+            Self::Zone | Self::Dig => {
+                for line in interpretations.iter().flat_map(interpretation_lines) {
+                    println!(";; {}", line);
+                }
+            }
+            Self::Short(..) | Self::CSV | Self::Hex | Self::Raw => {
+                for line in interpretations.iter().flat_map(interpretation_lines) {
+                    eprintln!("{}", line);
+                }
+            }
+        }
+    }
+
+    /// Prints the summary of a benchmark run: how many queries were sent
+    /// and how fast, what happened to them, and how long they took.
+    pub fn print_bench(self, report: &Report) {
+        match self {
+            Self::JSON | Self::YAML => {
+                let rcodes = report.rcodes.iter()
+                                   .map(|(rcode, count)| (rcode_name(*rcode), json!(count)))
+                                   .collect::<serde_json::Map<_, _>>();
+
+                let latencies = report.latencies.map_or(JsonValue::Null, |l| json!({
+                    "min": l.min, "p50": l.p50, "p90": l.p90, "p99": l.p99, "max": l.max,
+                }));
+
+                self.print_document(json!({
+                    "schema_version": JSON_SCHEMA_VERSION,
+                    "bench": {
+                        "sent": report.sent,
+                        "duration": report.elapsed,
+                        "rate": report.rate,
+                        "responses": report.responses,
+                        "timeouts": report.timeouts,
+                        "errors": report.errors,
+                        "rcodes": rcodes,
+                        "latencies": latencies,
+                    },
+                }));
+            }
+            Self::Text(..) | Self::Short(..) | Self::CSV | Self::Zone | Self::Dig | Self::Hex | Self::Raw => {
+                let percent = |count: usize| if report.sent == 0 { 0.0 } else { count as f64 * 100.0 / report.sent as f64 };
+
+                println!("Sent {} queries at {:.1} per second, finishing in {:.1}s", report.sent, report.rate, report.elapsed.as_secs_f64());
+                println!("Responses: {} ({:.1}%), timeouts: {} ({:.1}%), errors: {} ({:.1}%)",
+                         report.responses, percent(report.responses), report.timeouts, percent(report.timeouts), report.errors, percent(report.errors));
+
+                if ! report.rcodes.is_empty() {
+                    let rcodes = report.rcodes.iter().map(|(rcode, count)| format!("{} {}", rcode_name(*rcode), count)).collect::<Vec<_>>();
+                    println!("Status: {}", rcodes.join(", "));
+                }
+
+                if let Some(l) = report.latencies {
+                    println!("Latency: min {}ms, p50 {}ms, p90 {}ms, p99 {}ms, max {}ms",
+                             l.min.as_millis(), l.p50.as_millis(), l.p90.as_millis(), l.p99.as_millis(), l.max.as_millis());
+                }
+            }
+        }
+    }
+
+    /// Prints what happened to one query sent while pinging a nameserver:
+    /// how big the response was and how long it took to arrive, or why it
+    /// never did.
+    pub fn print_ping(self, sequence: u32, destination: &Destination, outcome: ping::Outcome) {
+        let server = &destination.nameserver;
+
+        match (self, outcome) {
+            (Self::JSON | Self::YAML, outcome) => {
+                let mut object = json!({
+                    "sequence": sequence,
+                    "server": server,
+                    "transport": transport_name(destination.transport_type),
+                });
+
+                let omut = object.as_object_mut().unwrap();
+                match outcome {
+                    ping::Outcome::Response(reply) => {
+                        omut.insert("status".into(), rcode_name(reply.response.flags.error_code).into());
+                        omut.insert("round_trip_ms".into(), json!(reply.round_trip.as_millis() as u64));
+                        omut.insert("protocol".into(), protocol_name(reply.protocol).to_lowercase().into());
+                        omut.insert("size".into(), reply.bytes.len().into());
+                    }
+                    ping::Outcome::Timeout => {
+                        omut.insert("error_kind".into(), FailureKind::Timeout.as_str().into());
+                        omut.insert("error_phase".into(), "timeout".into());
+                        omut.insert("error_message".into(), format!("No response within {}s", ping::TIMEOUT.as_secs()).into());
+                    }
+                    ping::Outcome::Error(e) => {
+                        omut.insert("error_kind".into(), FailureKind::of(&e).as_str().into());
+                        omut.insert("error_phase".into(), erroneous_phase(&e).into());
+                        omut.insert("error_message".into(), error_message(e).into());
+                    }
+                }
+
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "ping": object }));
+            }
+            (Self::Short(..), ping::Outcome::Response(reply)) => {
+                println!("{:.1}", reply.round_trip.as_secs_f64() * 1000.0);
+            }
+            (_, ping::Outcome::Response(reply)) => {
+                println!("{} bytes from {} ({}): seq={} status={} time={:.1}ms",
+                         reply.bytes.len(), server, protocol_name(reply.protocol), sequence,
+                         rcode_name(reply.response.flags.error_code), reply.round_trip.as_secs_f64() * 1000.0);
+            }
+            (Self::Short(..), ping::Outcome::Timeout) => {
+                eprintln!("No response from {} within {}s: seq={}", server, ping::TIMEOUT.as_secs(), sequence);
+            }
+            (_, ping::Outcome::Timeout) => {
+                println!("No response from {} within {}s: seq={}", server, ping::TIMEOUT.as_secs(), sequence);
+            }
+            (Self::Short(..), ping::Outcome::Error(e)) => {
+                eprintln!("Error [{}] from {}: seq={}: {}", erroneous_phase(&e), server, sequence, error_message(e));
+            }
+            (_, ping::Outcome::Error(e)) => {
+                println!("Error [{}] from {}: seq={}: {}", erroneous_phase(&e), server, sequence, error_message(e));
+            }
+        }
+    }
+
+    /// Prints a JSON document, or the YAML version of it.
+    fn print_document(self, document: JsonValue) {
+        if self == Self::YAML {
+            print!("{}", to_yaml(&document));
+        }
+        else {
+            println!("{}", document);
+        }
+    }
+
+    /// The colours and text settings to print a command’s own summary
+    /// with, which the formats other than text print without any colours
+    /// or formatting.
+    fn text_settings(self) -> (Colours, TextFormat) {
+        match self {
+            Self::Text(uc, tf)  => (uc.palette(), tf),
+            _                   => (Colours::plain(), TextFormat { format_durations: false, unicode_names: false, concat_txt: false, ttl_format: TtlFormat::Raw, catalog: &ENGLISH }),
+        }
+    }
+
+    /// Prints the errors for queries that failed straight away, unless
+    /// this is the JSON or YAML output, in which case they get returned to
+    /// be put in the document with everything else.
+    fn print_errors_unless_structured(self, errors: Vec<QueryError>) -> Vec<QueryError> {
+        if matches!(self, Self::JSON | Self::YAML) {
+            return errors;
+        }
+
+        for error in errors {
+            match error {
+                QueryError::Transport(e)    => self.print_error(e),
+                QueryError::Authority(e)    => eprintln!("{}", authority_error_message(e)),
+                QueryError::Expectation(f)  => eprintln!("Expectation failed: {}", f),
+            }
+        }
+
+        Vec::new()
+    }
+
+    pub fn print_error(&self, error: TransportError) {
+        match self {
+            Self::Short(tf) | Self::Text(_, tf) => {
+                eprintln!("{} [{}]: {}", tf.catalog.message(Message::Error), erroneous_phase(&error), error_message(error));
+            }
+
+            Self::Zone | Self::Dig | Self::CSV | Self::Hex | Self::Raw => {
+                eprintln!("Error [{}]: {}", erroneous_phase(&error), error_message(error));
+            }
+
+            Self::JSON | Self::YAML => {
+                self.print_document(json!({ "schema_version": JSON_SCHEMA_VERSION, "error": json_error(error) }));
+            }
+        }
+    }
+}
+
+/// Describes a failed query as an object with a `kind` that programs can
+/// match on, which stays the same between versions, along with the phase
+/// it failed in, a message for people, and any details that go with it.
+/// Something that went wrong with a query, in the same form as an error
+/// from the transport, so they can share the `errors` array.
+fn json_query_error(error: QueryError) -> JsonValue {
+    match error {
+        QueryError::Transport(e) => {
+            json_error(e)
+        }
+        QueryError::Authority(e) => {
+            json!({ "kind": e.kind().as_str(), "phase": "authority", "message": authority_error_message(e) })
+        }
+        QueryError::Expectation(f) => {
+            json!({ "kind": f.kind().as_str(), "phase": "expectation", "message": f.to_string() })
+        }
+    }
+}
+
+fn json_error(error: TransportError) -> JsonValue {
+    let mut object = json!({
+        "kind": FailureKind::of(&error).as_str(),
+        "phase": erroneous_phase(&error),
+    });
+
+    let details = match &error {
+        TransportError::PortUnreachable(address)                 => json!({ "address": address.to_string() }),
+        TransportError::TransactionIdMismatch { sent, received }  => json!({ "sent": sent, "received": received }),
+        TransportError::QuestionMismatch { sent, received }       => json!({ "sent": question_list(sent), "received": question_list(received) }),
+        TransportError::WrongSource { expected, received }        => json!({ "expected": expected.to_string(), "received": received.to_string() }),
+        TransportError::CaseMismatch { sent, received }           => json!({ "sent": sent.to_string(), "received": received.to_string() }),
+        TransportError::PinMismatch { received }                  => json!({ "received": base64(received) }),
+        _                                                         => json!({}),
+    };
+
+    let omut = object.as_object_mut().unwrap();
+    omut.insert("message".into(), error_message(error).into());
+    omut.extend(details.as_object().unwrap().clone());
+    object
+}
+
+/// Describes the error code a nameserver responded with as an object in
+/// the same form as a failed query, so both can be checked the same way.
+fn json_rcode_error(rcode: ErrorCode) -> JsonValue {
+    let message = match rcode {
+        ErrorCode::FormatError     => "Nameserver could not understand the query".into(),
+        ErrorCode::ServerFailure   => "Nameserver failed to answer the query".into(),
+        ErrorCode::NXDomain        => "Domain does not exist".into(),
+        ErrorCode::NotImplemented  => "Nameserver does not support this kind of query".into(),
+        ErrorCode::QueryRefused    => "Nameserver refused to answer the query".into(),
+        ErrorCode::BadVersion      => "Nameserver does not support this EDNS version".into(),
+        ErrorCode::Other(num)      => format!("Nameserver responded with error code {}", num),
+    };
+
+    json!({ "kind": FailureKind::of_rcode(rcode).as_str(), "phase": "response", "message": message, "rcode": rcode_name(Some(rcode)) })
+}
+
+fn erroneous_phase(error: &TransportError) -> &'static str {
+    match error {
+        TransportError::NetworkError(_)                => "network",
+        TransportError::PortUnreachable(_)             => "network",
+        TransportError::HttpError(_)                   => "http",
+        TransportError::BadRequest                     => "http-status",
+        TransportError::SslError(_)                    => "tls",
+        TransportError::PinMismatch { .. }             => "tls",
+        TransportError::WireError(_)                   |
+        TransportError::MalformedResponse { .. }       |
+        TransportError::TransactionIdMismatch { .. }   |
+        TransportError::QuestionMismatch { .. }        |
+        TransportError::WrongSource { .. }             |
+        TransportError::CaseMismatch { .. }            => "protocol",
+    }
+}
+
+fn trace_error_message(error: TraceError) -> String {
+    match error {
+        TraceError::Transport(e)  => format!("No nameservers could be reached: {}", error_message(e)),
+        otherwise                 => otherwise.to_string(),
+    }
+}
+
+fn error_message(error: TransportError) -> String {
+    match error {
+        TransportError::NetworkError(e)  => e.to_string(),
+        TransportError::HttpError(e)     => e.to_string(),
+        TransportError::SslError(e)      => e.to_string(),
+        TransportError::PinMismatch { received } if received.is_empty()  => "Server sent no certificate to check the pinned keys against".into(),
+        TransportError::PinMismatch { received }                  => format!("Certificate has public key sha256/{}, which is not pinned", base64(&received)),
+        TransportError::BadRequest       => "Nameserver returned HTTP 400 Bad Request".into(),
+        TransportError::TransactionIdMismatch { sent, received }  => format!("Response had transaction ID {} instead of {}", received, sent),
+        TransportError::QuestionMismatch { sent, received }       => format!("Response was for {} instead of {}", question_list(&received), question_list(&sent)),
+        TransportError::WrongSource { expected, received }        => format!("Response came from {} instead of {}", received, expected),
+        TransportError::CaseMismatch { sent, received }           => format!("Response was for {:?} instead of {:?} (try --no-0x20)", received, sent),
+        TransportError::PortUnreachable(address)                 => format!("Nothing is listening on port {} of {} (ICMP port unreachable)", address.port(), address.ip()),
+        TransportError::WireError(e)     => e.to_string(),
+        TransportError::MalformedResponse { error, .. }           => error.to_string(),
+    }
+}
+
+
+impl TextFormat {
+    pub fn record_payload_summary(self, record: &Record) -> String {
+        match *record {
+            Record::A(ref a) => {
+                format!("{}", a.address)
+            }
+            Record::AAAA(ref aaaa) => {
+                format!("{}", aaaa.address)
+            }
+            Record::CAA(ref caa) => {
+                if caa.critical {
+                    format!("{:?} {:?} (critical)", caa.tag, caa.value)
+                }
+                else {
+                    format!("{:?} {:?} (non-critical)", caa.tag, caa.value)
+                }
+            }
+            Record::CNAME(ref cname) => {
+                format!("{:?}", self.format_name(&cname.domain))
+            }
+            Record::DNSKEY(ref dnskey) => {
+                format!("{} {} {} {}", dnskey.flags, dnskey.protocol, dnskey.algorithm, base64(&dnskey.public_key))
+            }
+            Record::DS(ref ds) => {
+                format!("{} {} {} {}", ds.key_tag, ds.algorithm, ds.digest_type, hex(&ds.digest))
+            }
+            Record::MX(ref mx) => {
+                format!("{} {:?}", mx.preference, self.format_name(&mx.exchange))
+            }
+            Record::NS(ref ns) => {
+                format!("{:?}", self.format_name(&ns.nameserver))
+            }
+            Record::PTR(ref ptr) => {
+                format!("{:?}", self.format_name(&ptr.cname))
+            }
+            Record::RRSIG(ref rrsig) => {
+                format!("{} {} {} {} {} {} {} {:?} {}",
+                    type_name(rrsig.type_covered), rrsig.algorithm, rrsig.labels,
+                    self.format_duration(rrsig.original_ttl), rrsig.expiration, rrsig.inception,
+                    rrsig.key_tag, self.format_name(&rrsig.signer_name), base64(&rrsig.signature))
+            }
+            Record::SOA(ref soa) => {
+                let email = soa.email().unwrap_or_else(|| self.format_name(&soa.rname));
+                format!("mname {:?} email {:?} serial {} refresh {} retry {} expire {} minimum {}",
+                    self.format_name(&soa.mname), email, soa.serial,
+                    self.format_duration(soa.refresh_interval),
+                    self.format_duration(soa.retry_interval),
+                    self.format_duration(soa.expire_limit),
+                    self.format_duration(soa.minimum_ttl),
+                )
+            }
+            Record::SRV(ref srv) => {
+                format!("{} {} {:?}:{}", srv.priority, srv.weight, self.format_name(&srv.target), srv.port)
+            }
+            Record::TXT(ref txt) if self.concat_txt => {
+                format!("{:?}", String::from_utf8_lossy(&txt.join()))
+            }
+            Record::TXT(ref txt) => {
+                txt.as_utf8_lossy().iter().map(|string| format!("{:?}", string)).collect::<Vec<_>>().join(" ")
+            }
+            Record::Custom(ref custom) => {
+                custom.to_string()
+            }
+            Record::Other { ref bytes, .. } => {
+                format!("{:?}", bytes)
+            }
+        }
+    }
+
+    /// Describes the answers a resolver gave in a propagation check, as
+    /// either the short value of each record or the error code.
+    pub fn answer_set_summary(self, set: &AnswerSet) -> String {
+        if set.rcode.is_some() {
+            rcode_name(set.rcode)
+        }
+        else if set.records.is_empty() {
+            "No records".into()
+        }
+        else {
+            set.records.iter().map(|record| self.record_short_value(record)).collect::<Vec<_>>().join(", ")
+        }
+    }
+
+    /// Returns the short value of each answer in a response. When chasing,
+    /// the chain comes out in order, as dig’s `+short` does: each name the
+    /// aliases lead through, followed by the records at the end.
+    pub fn short_values(self, response: &Response, chase: bool) -> Vec<String> {
+        if let Some(chain) = response_chain(response).filter(|_| chase) {
+            let targets = chain.names.iter().skip(1).map(|name| self.format_name(name));
+            let records = chain.records.iter().map(|record| self.record_short_value(record));
+            return targets.chain(records).collect();
+        }
+
+        response.answers.iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { record, .. }  => Some(self.record_short_value(record)),
+                Answer::Pseudo { .. }            => None,
+            }
+        }).collect()
+    }
+
+    /// Returns just the part of a record that scripts usually want, for
+    /// the short output mode: the address of an A or AAAA record, the name
+    /// that a CNAME, MX, NS, or PTR record points to, the target and port
+    /// of an SRV record, or the text of a TXT or CAA record, without any
+    /// quoting. The strings in a TXT record are always joined together.
+    /// Records without one main value are written out in full.
+    pub fn record_short_value(self, record: &Record) -> String {
+        match record {
+            Record::A(a)          => a.address.to_string(),
+            Record::AAAA(aaaa)    => aaaa.address.to_string(),
+            Record::CAA(caa)      => caa.value.clone(),
+            Record::CNAME(cname)  => self.format_name(&cname.domain),
+            Record::MX(mx)        => self.format_name(&mx.exchange),
+            Record::NS(ns)        => self.format_name(&ns.nameserver),
+            Record::PTR(ptr)      => self.format_name(&ptr.cname),
+            Record::SRV(srv)      => format!("{}:{}", self.format_name(&srv.target), srv.port),
+            Record::TXT(txt)      => String::from_utf8_lossy(&txt.join()).into_owned(),
+            otherwise             => otherwise.to_string(),
+        }
+    }
+
+    /// Renders a CNAME chain as `a → b → c → record`, with one line for each
+    /// record at the end of the chain.
+    pub fn chain_lines(self, chain: &Chain, colours: &Colours) -> Vec<String> {
+        let names = chain.names.iter()
+                               .map(|name| colours.qname.paint(self.format_name(name)).to_string())
+                               .collect::<Vec<_>>()
+                               .join(" → ");
+
+        if chain.records.is_empty() {
+            return vec![ names ];
+        }
+
+        chain.records.iter()
+             .map(|record| format!("{} → {}", names, self.record_payload_summary(record)))
+             .collect()
+    }
+
+    /// Formats a domain name for display, decoding any internationalised
+    /// labels unless the user asked to see them as they were sent.
+    pub fn format_name(self, name: &Name) -> String {
+        if self.unicode_names {
+            name.to_unicode()
+        }
+        else {
+            name.to_string()
+        }
+    }
+
+    /// Describes the EDNS details in an OPT record the way dig’s OPT
+    /// pseudosection does: the version, flags, and payload size, followed
+    /// by a line for each option, by name, with its value decoded where
+    /// possible.
+    pub fn edns_lines(self, opt: &OPT) -> Vec<String> {
+        let flags = if opt.dnssec_ok() { " do" } else { "" };
+        let mut lines = vec![ format!("version {}, flags:{}, udp: {}", opt.edns0_version, flags, opt.udp_payload_size) ];
+
+        for (code, value) in opt.options() {
+            let name = OPT::option_name(code).map_or_else(|| format!("OPT={}", code), String::from);
+            lines.push(format!("{}: {}", name, self.edns_option_value(opt, code, value)));
+        }
+
+        lines
+    }
+
+    fn edns_option_value(self, opt: &OPT, code: u16, value: &[u8]) -> String {
+        match (code, value) {
+            (_, []) => {
+                "(empty)".into()
+            }
+            (3, nsid) => {
+                if nsid.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                    format!("{} ({:?})", hex(nsid).to_lowercase(), String::from_utf8_lossy(nsid))
+                }
+                else {
+                    hex(nsid).to_lowercase()
+                }
+            }
+            (8, [ f1, f2, source, scope, address @ .. ]) => {
+                format!("{}/{}/{}", subnet_address(u16::from_be_bytes([ *f1, *f2 ]), address), source, scope)
+            }
+            (9, [ a, b, c, d ]) => {
+                self.format_duration(u32::from_be_bytes([ *a, *b, *c, *d ]))
+            }
+            (11, _) => {
+                match opt.tcp_keepalive() {
+                    Some(timeout)  => format!("{:.1} secs", timeout.as_secs_f64()),
+                    None           => hex(value).to_lowercase(),
+                }
+            }
+            (12, padding) => {
+                format!("{} bytes", padding.len())
+            }
+            (15, [ a, b, text @ .. ]) => {
+                let info_code = u16::from_be_bytes([ *a, *b ]);
+                let description = match extended_error_name(info_code) {
+                    Some(name)  => format!("{} ({})", info_code, name),
+                    None        => info_code.to_string(),
+                };
+
+                if text.is_empty() {
+                    description
+                }
+                else {
+                    format!("{}: {:?}", description, String::from_utf8_lossy(text))
+                }
+            }
+            (_, value) => {
+                hex(value).to_lowercase()
+            }
+        }
+    }
+
+    pub fn format_duration(self, seconds: u32) -> String {
+        if self.format_durations {
+            self.catalog.duration(seconds)
+        }
+        else {
+            format!("{}", seconds)
+        }
+    }
+
+    /// Formats the TTL of a record, which can be shown as a duration or as
+    /// the time the record expires.
+    pub fn format_ttl(self, ttl: u32) -> String {
+        match self.ttl_format {
+            TtlFormat::Raw     => format!("{}", ttl),
+            TtlFormat::Human   => self.catalog.duration(ttl),
+            TtlFormat::Expiry  => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+                expiry_time(now, ttl)
+            }
+        }
+    }
+}
+
+/// Formats the time a record with the given TTL expires, as an ISO 8601
+/// timestamp in UTC, such as `2026-10-15T13:00:00Z`.
+fn expiry_time(now: u64, ttl: u32) -> String {
+    iso_timestamp(now + u64::from(ttl))
+}
+
+/// Formats a number of seconds since the Unix epoch as an ISO 8601
+/// timestamp in UTC.
+pub fn iso_timestamp(seconds: u64) -> String {
+    let stamp = timestamp(seconds as u32);
+    format!("{}-{}-{}T{}:{}:{}Z", &stamp[0 .. 4], &stamp[4 .. 6], &stamp[6 .. 8], &stamp[8 .. 10], &stamp[10 .. 12], &stamp[12 .. 14])
+}
+
+/// Formats the address in a client subnet option, which has any bytes
+/// after the prefix left off (RFC 7871 §6).
+fn subnet_address(family: u16, bytes: &[u8]) -> String {
+    match family {
+        1 => {
+            let mut octets = [0; 4];
+            octets.iter_mut().zip(bytes).for_each(|(octet, byte)| *octet = *byte);
+            Ipv4Addr::from(octets).to_string()
+        }
+        2 => {
+            let mut octets = [0; 16];
+            octets.iter_mut().zip(bytes).for_each(|(octet, byte)| *octet = *byte);
+            Ipv6Addr::from(octets).to_string()
+        }
+        _ => {
+            hex(bytes).to_lowercase()
+        }
+    }
+}
+
+/// Returns the meaning of an extended DNS error’s info code (RFC 8914 §4).
+fn extended_error_name(info_code: u16) -> Option<&'static str> {
+    Some(match info_code {
+         0 => "Other Error",
+         1 => "Unsupported DNSKEY Algorithm",
+         2 => "Unsupported DS Digest Type",
+         3 => "Stale Answer",
+         4 => "Forged Answer",
+         5 => "DNSSEC Indeterminate",
+         6 => "DNSSEC Bogus",
+         7 => "Signature Expired",
+         8 => "Signature Not Yet Valid",
+         9 => "DNSKEY Missing",
+        10 => "RRSIGs Missing",
+        11 => "No Zone Key Bit Set",
+        12 => "NSEC Missing",
+        13 => "Cached Error",
+        14 => "Not Ready",
+        15 => "Blocked",
+        16 => "Censored",
+        17 => "Filtered",
+        18 => "Prohibited",
+        19 => "Stale NXDOMAIN Answer",
+        20 => "Not Authoritative",
+        21 => "Not Supported",
+        22 => "No Reachable Authority",
+        23 => "Network Error",
+        24 => "Invalid Data",
+         _ => return None,
+    })
+}
+
+/// Returns the name of a record type, or its number if it has no name.
+fn type_name(type_number: u16) -> String {
+    dns::find_qtype_name(type_number).map_or_else(|| type_number.to_string(), String::from)
+}
+
+/// Prints every record in a response in zone file format, with the
+/// answers first, followed by the authority and additional records.
+fn print_zone_lines(response: Response) {
+    let sections = response.answers.into_iter()
+                           .chain(response.authorities)
+                           .chain(response.additionals);
+
+    for answer in sections {
+        println!("{}", answer);
+    }
+}
+
+/// Describes the differences in one set of records, one record per line,
+/// marked with `-` if it is only in the zone file, `+` if it is only on
+/// the nameserver, and `~` if only its TTL differs.
+fn diff_lines(diff: &SetDiff, colours: &Colours) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for answer in &diff.only_in_file {
+        lines.push(colours.only_in_file.paint(format!("- {}", answer)).to_string());
+    }
+
+    for answer in &diff.only_on_server {
+        lines.push(colours.only_on_server.paint(format!("+ {}", answer)).to_string());
+    }
+
+    for (answer, server_ttl) in &diff.ttl_changes {
+        lines.push(colours.ttl_changed.paint(format!("~ {} ; TTL {} on server", answer, server_ttl)).to_string());
+    }
+
+    lines
+}
+
+/// The header row of the CSV output.
+const CSV_HEADER: [&str; 4] = [ "name", "type", "ttl", "rdata" ];
+
+/// Prints a row of CSV for each standard answer, with the record data in
+/// presentation format.
+fn print_csv_rows(answers: &[Answer]) {
+    for answer in answers {
+        if let Answer::Standard { qname, ttl, record, .. } = answer {
+            println!("{}", csv_row(&[ qname.to_string(), record.type_mnemonic(), ttl.to_string(), record.to_string() ]));
+        }
+    }
+}
+
+/// Prints a row of CSV for one record in a zone file comparison, with the
+/// TTL replaced by the one from the nameserver if it is given.
+fn print_csv_diff_row(change: &str, answer: &Answer, ttl: Option<u32>) {
+    if let Answer::Standard { qname, ttl: file_ttl, record, .. } = answer {
+        let ttl = ttl.unwrap_or(*file_ttl);
+        println!("{}", csv_row(&[ change.into(), qname.to_string(), record.type_mnemonic(), ttl.to_string(), record.to_string() ]));
+    }
+}
+
+/// Describes the queries in a question section, for error messages.
+fn question_list(queries: &[Query]) -> String {
+    if queries.is_empty() {
+        return "no question".into();
+    }
+
+    queries.iter().map(|q| {
+        match dns::find_qtype_name(q.qtype) {
+            Some(name)  => format!("{} {}", q.qname, name),
+            None        => format!("{} {}", q.qname, q.qtype),
+        }
+    }).collect::<Vec<_>>().join(", ")
+}
+
+/// Finds the CNAME chain starting at the first query of the response.
+fn response_chain(response: &Response) -> Option<Chain> {
+    let query = response.queries.first()?;
+    Chain::find(&response.answers, &query.qname)
+}
+
+/// Describes a captured message by whether it is a query or a response,
+/// what it asked, and where it went.
+fn captured_summary(message: &CapturedMessage, response: &Response) -> String {
+    let kind = if response.flags.response { "Response" } else { "Query" };
+    let question = response.queries.iter()
+                           .map(|q| format!(" {} {}", q.qname, type_mnemonic(q.qtype)))
+                           .collect::<String>();
+
+    format!("{}{} from {} to {} over {:?}", kind, question, message.source, message.destination, message.transport_type)
+}
+
+/// Describes a query received while serving, and how long the upstream
+/// nameserver took to answer it, if it was forwarded.
+fn served_summary(served: &Served) -> String {
+    let question = served.request.queries.iter()
+                         .map(|q| format!(" {} {}", q.qname, type_mnemonic(q.qtype)))
+                         .collect::<String>();
+
+    let outcome = match &served.outcome {
+        Outcome::Forwarded(reply) if reply.protocol == Protocol::Cache  => "answered from the cache".into(),
+        Outcome::Forwarded(reply) if reply.protocol == Protocol::Hosts  => "answered from the hosts file".into(),
+        Outcome::Forwarded(reply)                                        => format!("answered in {}ms", reply.round_trip.as_millis()),
+        Outcome::Authoritative(_)                                        => "answered from the zone".into(),
+        Outcome::Failed(_)                                               => "failed".into(),
+    };
+
+    format!("Query{} from {} over {:?}, {}", question, served.client, served.transport_type, outcome)
+}
+
+/// Formats a time as `2026-10-15 12:00:00 UTC`, for the start of each
+/// round of a watch.
+fn watch_time(now: u64) -> String {
+    let stamp = timestamp(now as u32);
+    format!("{}-{}-{} {}:{}:{} UTC", &stamp[0 .. 4], &stamp[4 .. 6], &stamp[6 .. 8], &stamp[8 .. 10], &stamp[10 .. 12], &stamp[12 .. 14])
+}
+
+/// Converts a response to JSON, along with its opcode and rcode, which
+/// are not part of its serialised form.
+fn json_response(response: &Response) -> JsonValue {
+    let mut object = response.to_json();
+    let omut = object.as_object_mut().unwrap();
+    omut.insert("opcode".into(), opcode_name(response.flags.opcode));
+    omut.insert("rcode".into(), response.flags.error_code.map_or_else(|| json!(rcode_name(None)), |rcode| rcode.to_json()));
+    if let Some(rcode) = response.flags.error_code {
+        omut.insert("error".into(), json_rcode_error(rcode));
+    }
+    object
+}
+
+/// Returns the mnemonic for an rcode, which is `NOERROR` if there is no
+/// error, or its number if it does not have one.
+pub fn rcode_name(rcode: Option<ErrorCode>) -> String {
+    match ErrorCode::mnemonic_of(rcode) {
+        Some(mnemonic)  => mnemonic.into(),
+        None            => rcode.map_or(0, ErrorCode::to_bits).to_string(),
+    }
+}
+
+/// Returns the mnemonic for an opcode from the IANA registry, or its
+/// number if it does not have one.
+fn opcode_name(opcode: u8) -> JsonValue {
+    match opcode_mnemonic(opcode) {
+        Some(mnemonic)  => json!(mnemonic),
+        None            => json!(opcode),
+    }
+}
+
+/// Returns the mnemonic for an opcode from the IANA registry, if it has
+/// been assigned one.
+pub fn opcode_mnemonic(opcode: u8) -> Option<&'static str> {
+    match opcode {
+        0  => Some("QUERY"),
+        1  => Some("IQUERY"),
+        2  => Some("STATUS"),
+        4  => Some("NOTIFY"),
+        5  => Some("UPDATE"),
+        6  => Some("DSO"),
+        _  => None,
+    }
+}
+
+/// Describes how long one query took, where it went, and how.
+fn query_time(exchange: &Exchange, catalog: &Catalog) -> String {
+    if exchange.protocol == Protocol::Cache {
+        return catalog.format(Message::QueryTimeFromCache, &[ &exchange.destination.nameserver ]);
+    }
+
+    if exchange.protocol == Protocol::Hosts {
+        return catalog.message(Message::QueryTimeFromHosts).into();
+    }
+
+    let retried = if exchange.attempts > 1 { catalog.message(Message::AfterRetrying) } else { "" };
+    let time = catalog.format(Message::QueryTime, &[ &exchange.round_trip.as_millis(), &exchange.destination.nameserver, &protocol_name(exchange.protocol) ]);
+    format!("{}{}", time, retried)
+}
+
+/// Says which authoritative nameserver a response came from, and the zone
+/// it is authoritative for.
+fn authority_line(authority: &Authority, tf: TextFormat) -> String {
+    tf.catalog.format(Message::AnsweredBy, &[ &tf.format_name(&authority.nameserver), &authority.address, &tf.format_name(&authority.zone) ])
+}
+
+/// Describes the connection a response arrived over, if the transport
+/// knew anything about it.
+fn connection_details(exchange: &Exchange, catalog: &Catalog) -> Option<String> {
+    let Connection { address, tls_version, tls_cipher, alpn, http_version, http_status } = &exchange.connection;
+    let mut details = Vec::new();
+
+    if let Some(address) = address {
+        details.push(catalog.format(Message::ConnectedTo, &[ address ]));
+    }
+
+    if let Some(version) = tls_version {
+        match tls_cipher {
+            Some(cipher)  => details.push(catalog.format(Message::TlsCipher, &[ version, cipher ])),
+            None          => details.push(version.clone()),
+        }
+    }
+
+    if let Some(alpn) = alpn {
+        details.push(format!("ALPN {}", alpn));
+    }
+
+    if let (Some(version), Some(status)) = (http_version, http_status) {
+        details.push(catalog.format(Message::HttpStatus, &[ version, status ]));
+    }
+
+    if fell_back_to_tcp(exchange) {
+        details.push(catalog.message(Message::FellBackToTcp).into());
+    }
+
+    if details.is_empty() {
+        return None;
+    }
+
+    Some(catalog.format(Message::Connection, &[ &details.join(", ") ]))
+}
+
+/// Whether the request had to be sent again over TCP, which only happens
+/// when the transport was picked automatically.
+fn fell_back_to_tcp(exchange: &Exchange) -> bool {
+    exchange.destination.transport_type == TransportType::Automatic && exchange.protocol == Protocol::TCP
+}
+
+/// The authoritative nameserver a response came from, for the JSON output.
+fn json_authority(authority: &Authority) -> JsonValue {
+    json!({
+        "zone": authority.zone.to_string(),
+        "nameserver": authority.nameserver.to_string(),
+        "address": authority.address.to_string(),
+    })
+}
+
+/// The details of the connection a response arrived over, for the JSON
+/// output, with the ones the transport did not know left as null.
+fn json_connection(exchange: &Exchange) -> JsonValue {
+    let connection = &exchange.connection;
+
+    json!({
+        "address": connection.address.map(|a| a.to_string()),
+        "tls_version": connection.tls_version,
+        "tls_cipher": connection.tls_cipher,
+        "alpn": connection.alpn,
+        "http_version": connection.http_version,
+        "http_status": connection.http_status,
+        "tcp_fallback": fell_back_to_tcp(exchange),
+    })
+}
+
+/// Returns the name of the protocol a response arrived over.
+pub fn protocol_name(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::UDP    => "UDP",
+        Protocol::TCP    => "TCP",
+        Protocol::TLS    => "TLS",
+        Protocol::HTTPS  => "HTTPS",
+        Protocol::Cache  => "Cache",
+        Protocol::Hosts  => "Hosts",
+    }
+}
+
+/// Returns the name of a transport type as it appears in the JSON output.
+pub fn transport_name(transport_type: TransportType) -> &'static str {
+    match transport_type {
+        TransportType::Automatic  => "automatic",
+        TransportType::UDP        => "udp",
+        TransportType::TCP        => "tcp",
+        TransportType::TLS        => "tls",
+        TransportType::HTTPS      => "https",
+    }
+}
+
+/// Converts the OPT record from a response to JSON, picking the fields
+/// apart into the EDNS details they stand for (RFC 6891 §6.1.3).
+fn json_edns(opt: &OPT) -> JsonValue {
+    json!({
+        "udp_payload_size": opt.udp_payload_size,
+        "version": opt.edns0_version,
+        "extended_rcode": opt.higher_bits,
+        "dnssec_ok": opt.dnssec_ok(),
+        "options": base64(&opt.data),
+        "tcp_keepalive": opt.tcp_keepalive(),
+    })
+}
+
+/// Converts a DNSSEC validation status to JSON, including the reason an
+/// answer was considered bogus.
+fn json_status(status: Status) -> JsonValue {
+    match status {
+        Status::Bogus(problem)  => json!({ "status": status.to_string(), "problem": problem.to_string() }),
+        _                       => json!({ "status": status.to_string() }),
+    }
+}
+
+/// The width to pad labels that start lines out to, such as
+/// `Certificates:`, so the values after them line up whatever language
+/// they are in.
+fn label_width(catalog: &Catalog, labels: &[Message]) -> usize {
+    labels.iter().map(|label| catalog.message(*label).chars().count()).max().unwrap_or_default()
+}
+
+/// Describes a problem the MX check found, in the user’s language.
+fn mx_problem_message(problem: &mx::Problem, catalog: &Catalog) -> String {
+    match problem {
+        mx::Problem::NullMxWithOthers         => catalog.message(Message::NullMxWithOthers).into(),
+        mx::Problem::NoAddresses(name)        => catalog.format(Message::ExchangeWithoutAddresses, &[ name ]),
+        mx::Problem::CnameExchange(name, to)  => catalog.format(Message::CnameExchange, &[ name, to ]),
+    }
+}
+
+/// Describes a problem the delegation check found, in the user’s
+/// language.
+fn delegation_problem_message(problem: &delegation::Problem, catalog: &Catalog) -> String {
+    match problem {
+        delegation::Problem::OnlyAtParent(name)        => catalog.format(Message::OnlyAtParent, &[ name ]),
+        delegation::Problem::OnlyAtChild(name)         => catalog.format(Message::OnlyAtChild, &[ name ]),
+        delegation::Problem::MissingGlue(name)         => catalog.format(Message::MissingGlue, &[ name ]),
+        delegation::Problem::GlueMismatch(name, glue)  => catalog.format(Message::GlueMismatch, &[ name, glue ]),
+        delegation::Problem::Lame(name)                => catalog.format(Message::LameNameserver, &[ name ]),
+    }
+}
+
+/// Describes why a nameserver is lame, in the user’s language.
+fn lameness_message(lameness: &Lameness, catalog: &Catalog) -> String {
+    match lameness {
+        Lameness::NoAddress         => catalog.message(Message::NoAddress).into(),
+        Lameness::Unreachable(e)    => catalog.format(Message::Unreachable, &[ &format!("{:?}", e) ]),
+        Lameness::Rcode(rcode)      => catalog.format(Message::RespondedWith, &[ &format!("{:?}", rcode) ]),
+        Lameness::NotAuthoritative  => catalog.message(Message::NotAuthoritative).into(),
+    }
+}
+
+/// Describes which CAs may issue one kind of certificate.
+fn permission_summary(permission: &Permission, catalog: &Catalog) -> String {
+    match permission {
+        Permission::AnyCA           => catalog.message(Message::AnyCA).into(),
+        Permission::NoCA            => catalog.message(Message::NoCA).into(),
+        Permission::Only(issuers)   => issuers.join(", "),
+    }
+}
+
+/// Converts which CAs may issue one kind of certificate to JSON.
+fn json_permission(permission: &Permission) -> JsonValue {
+    match permission {
+        Permission::AnyCA           => json!({ "restricted": false, "issuers": [] }),
+        Permission::NoCA            => json!({ "restricted": true, "issuers": [] }),
+        Permission::Only(issuers)   => json!({ "restricted": true, "issuers": issuers }),
+    }
+}
+
+/// Converts one difference between two messages to JSON. Header fields
+/// that differ have both values, and records have the section they are in.
+fn json_change(change: &diff::Change) -> JsonValue {
+    match change {
+        diff::Change::TransactionId(l, r)        => json!({ "change": "header", "field": "transaction_id", "left": l, "right": r }),
+        diff::Change::Flag(name, l, r)           => json!({ "change": "header", "field": name, "left": l, "right": r }),
+        diff::Change::Opcode(l, r)               => json!({ "change": "header", "field": "opcode", "left": l, "right": r }),
+        diff::Change::Rcode(l, r)                => json!({ "change": "header", "field": "rcode", "left": rcode_name(*l), "right": rcode_name(*r) }),
+        diff::Change::Count(section, l, r)       => json!({ "change": "count", "section": section.to_string(), "left": l, "right": r }),
+        diff::Change::QueryRemoved(query)        => json!({ "change": "removed", "section": "query", "query": query.to_json() }),
+        diff::Change::QueryAdded(query)          => json!({ "change": "added", "section": "query", "query": query.to_json() }),
+        diff::Change::Removed(section, answer)   => json!({ "change": "removed", "section": section.to_string(), "record": answer.to_json() }),
+        diff::Change::Added(section, answer)     => json!({ "change": "added", "section": section.to_string(), "record": answer.to_json() }),
+        diff::Change::TTL(section, answer, ttl)  => json!({ "change": "ttl", "section": section.to_string(), "record": answer.to_json(), "previous_ttl": ttl }),
+    }
+}
+
+/// Describes why the CAA records for a domain could not be found.
+fn caa_error_message(error: LookupError) -> String {
+    match error {
+        LookupError::Transport(name, e)  => format!("Error [{}] checking {}: {}", erroneous_phase(&e), name, error_message(e)),
+        LookupError::Rcode(name, rcode)  => format!("Error checking {}: the response was {}, so no CA may issue", name, rcode_name(Some(rcode))),
+    }
+}
+
+/// Describes why a name could not be looked up for one of the checkers.
+fn lookup_error_message(error: LookupError) -> String {
+    match error {
+        LookupError::Transport(name, e)  => format!("Error [{}] looking up {}: {}", erroneous_phase(&e), name, error_message(e)),
+        LookupError::Rcode(name, rcode)  => format!("Error looking up {}: the response was {}", name, rcode_name(Some(rcode))),
+    }
+}
+
+/// Describes why a name could not be looked up for one of the checkers
+/// in their JSON output, along with the name.
+fn json_lookup_error(error: LookupError) -> JsonValue {
+    let kind = error.kind().as_str();
+
+    match error {
+        LookupError::Transport(name, e)  => json!({ "name": name.to_string(), "error_kind": kind, "error_phase": erroneous_phase(&e), "error_message": error_message(e) }),
+        LookupError::Rcode(name, rcode)  => json!({ "name": name.to_string(), "error_kind": kind, "error_phase": "response", "error_message": rcode_name(Some(rcode)) }),
+    }
+}
+
+/// Says which ways of looking a name up gave an address, for the short
+/// output.
+fn address_source(comparison: &SystemComparison, address: std::net::IpAddr) -> &'static str {
+    match (comparison.system_addresses().contains(&address), comparison.dns.contains(&address)) {
+        (true, true)   => "both",
+        (true, false)  => "system",
+        _              => "dns",
+    }
+}
+
+/// The name of why the system resolver disagrees with DNS, for the JSON
+/// output.
+fn cause_name(cause: Cause) -> &'static str {
+    match cause {
+        Cause::HostsFile     => "hosts_file",
+        Cause::MulticastDns  => "mdns",
+        Cause::Unknown       => "unknown",
+    }
+}
+
+/// Describes why an authoritative nameserver could not be found to send a
+/// query to.
+pub fn authority_error_message(error: AuthorityError) -> String {
+    match error {
+        AuthorityError::Lookup(e)            => lookup_error_message(e),
+        AuthorityError::NoAddresses(zone)    => format!("Error finding an authoritative nameserver for {}: none of its nameservers have addresses", zone),
+    }
+}
+
+/// The name of whether a name exists, for the JSON output.
+fn presence_name(presence: Presence) -> &'static str {
+    match presence {
+        Presence::Records   => "records",
+        Presence::NoData    => "nodata",
+        Presence::NxDomain  => "nxdomain",
+    }
+}
+
+/// Describes why the nameservers of a zone could not be found.
+fn soa_error_message(error: SoaError) -> String {
+    match error {
+        SoaError::Lookup(e)             => lookup_error_message(e),
+        SoaError::NoNameservers(name)   => format!("Error looking up {}: there are no NS records", name),
+    }
+}
+
+/// The name that was being looked up when an error happened.
+fn soa_error_name(error: &SoaError) -> &Name {
+    match error {
+        SoaError::Lookup(e)             => e.name(),
+        SoaError::NoNameservers(name)   => name,
+    }
+}
+
+/// Describes why a nameserver’s serial number could not be found.
+fn server_error_message(error: ServerError) -> String {
+    match error {
+        ServerError::NoAddresses       => "no addresses".into(),
+        ServerError::Unreachable(e)    => format!("unreachable: {}", error_message(e)),
+        ServerError::Rcode(rcode)      => format!("responded with {}", rcode_name(Some(rcode))),
+        ServerError::NotAuthoritative  => "not authoritative for the zone".into(),
+    }
+}
+
+/// Converts an interpreted email policy to JSON, with the tags of DKIM and
+/// DMARC records as an object, so scripts can look them up by name.
+fn json_interpretation(interpretation: &Interpretation) -> JsonValue {
+    let terms = interpretation.terms.iter().map(|term| {
+        json!({ "term": term.text, "explanation": term.explanation })
+    }).collect::<Vec<_>>();
+
+    let mut object = json!({
+        "name": interpretation.owner.to_string(),
+        "kind": interpretation.kind.name().to_lowercase(),
+        "text": interpretation.text,
+        "terms": terms,
+        "problems": interpretation.problems,
+    });
+
+    if ! interpretation.tags.is_empty() {
+        let tags = interpretation.tags.iter().map(|(tag, value)| (tag.clone(), json!(value))).collect::<serde_json::Map<_, _>>();
+        object.as_object_mut().unwrap().insert("tags".into(), tags.into());
+    }
+
+    object
+}
+
+/// The longest term shown in full in the text output. Longer ones, such
+/// as DKIM keys, get cut short so the explanations still line up.
+const TERM_WIDTH: usize = 32;
+
+/// Describes an interpreted email policy: a heading, then each term next
+/// to its explanation, then any problems with it.
+fn interpretation_lines(interpretation: &Interpretation) -> Vec<String> {
+    let terms = interpretation.terms.iter().map(|term| {
+        if term.text.chars().count() > TERM_WIDTH {
+            (format!("{}…", term.text.chars().take(TERM_WIDTH - 1).collect::<String>()), &term.explanation)
+        }
+        else {
+            (term.text.clone(), &term.explanation)
+        }
+    }).collect::<Vec<_>>();
+
+    let width = terms.iter().map(|(text, _)| text.chars().count()).max().unwrap_or(0);
+
+    let mut lines = vec![ format!("{} policy at {}", interpretation.kind.name(), interpretation.owner) ];
+    lines.extend(terms.into_iter().map(|(text, explanation)| format!("  {:<w$}  {}", text, explanation, w = width)));
+    lines.extend(interpretation.problems.iter().map(|problem| format!("  Problem: {}", problem)));
+    lines
+}
+
+/// Prints a hex dump of a message, followed by a blank line.
+fn print_hex_dump(bytes: &[u8]) {
+    for line in hex_dump(bytes) {
+        println!("{}", line);
+    }
+
+    println!();
+}
+
+/// Writes the bytes of each message to standard output without any
+/// formatting. A closed pipe is not worth reporting, so errors are ignored.
+fn print_raw(messages: Vec<&[u8]>) {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    for bytes in messages {
+        if handle.write_all(bytes).is_err() {
+            return;
+        }
+    }
+
+    let _ = handle.flush();
+}
+
+pub fn print_error_code(rcode: ErrorCode, catalog: &Catalog) {
+    let status = match rcode {
+        ErrorCode::FormatError     => catalog.message(Message::FormatError).into(),
+        ErrorCode::ServerFailure   => catalog.message(Message::ServerFailure).into(),
+        ErrorCode::NXDomain        => catalog.message(Message::NXDomain).into(),
+        ErrorCode::NotImplemented  => catalog.message(Message::NotImplemented).into(),
+        ErrorCode::QueryRefused    => catalog.message(Message::QueryRefused).into(),
+        ErrorCode::BadVersion      => catalog.message(Message::BadVersion).into(),
+        ErrorCode::Other(num)      => catalog.format(Message::OtherFailure, &[ &num ]),
+    };
+
+    println!("{}", catalog.format(Message::Status, &[ &status ]));
+}
+
+/// Prints a warning that the reserved Z bit in the header of a response
+/// was set, which no server should do, so something in between may have
+/// changed the message.
+fn print_reserved_bit() {
+    println!("Header: reserved Z bit is set");
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::record::*;
+
+    const FORMAT: TextFormat = TextFormat { format_durations: true, unicode_names: true, concat_txt: false, ttl_format: TtlFormat::Human, catalog: &ENGLISH };
+
+    #[test]
+    fn short_addresses() {
+        let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) });
+        assert_eq!(FORMAT.record_short_value(&record), "127.0.0.1");
+    }
+
+    #[test]
+    fn short_names() {
+        let record = Record::MX(MX { preference: 10, exchange: "xn--bcher-kva.lookup.dog.".parse().unwrap() });
+        assert_eq!(FORMAT.record_short_value(&record), "bücher.lookup.dog.");
+    }
+
+    #[test]
+    fn short_chain() {
+        use crate::lookup::testing::{answer, name, response};
+
+        let response = response(&name("www.lookup.dog."), dns::qtype!(A), None, vec![
+            answer("cdn.lookup.dog.", Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) })),
+            answer("www.lookup.dog.", Record::CNAME(CNAME { domain: name("cdn.lookup.dog.") })),
+        ]);
+
+        assert_eq!(FORMAT.short_values(&response, true), vec![ "cdn.lookup.dog.", "127.0.0.1" ]);
+    }
+
+    #[test]
+    fn translated_problems() {
+        let german = crate::locale::find("de").unwrap();
+        let problem = delegation::Problem::OnlyAtChild("ns1.lookup.dog.".parse().unwrap());
+        assert_eq!(delegation_problem_message(&problem, german), "die Zone nennt ns1.lookup.dog., die übergeordnete Zone aber nicht");
+        assert_eq!(mx_problem_message(&mx::Problem::NullMxWithOthers, &ENGLISH), mx::Problem::NullMxWithOthers.to_string());
+    }
+
+    #[test]
+    fn short_text() {
+        let record = Record::TXT(TXT { strings: vec![ b"v=spf1 \"woof\" ".to_vec(), b"-all".to_vec() ] });
+        assert_eq!(FORMAT.record_short_value(&record), "v=spf1 \"woof\" -all");
+    }
+
+    #[test]
+    fn text_segments() {
+        let record = Record::TXT(TXT { strings: vec![ b"v=spf1 ".to_vec(), b"-all".to_vec() ] });
+        assert_eq!(FORMAT.record_payload_summary(&record), "\"v=spf1 \" \"-all\"");
+    }
+
+    #[test]
+    fn text_concatenated() {
+        let format = TextFormat { concat_txt: true, .. FORMAT };
+        let record = Record::TXT(TXT { strings: vec![ b"v=spf1 ".to_vec(), b"-all".to_vec() ] });
+        assert_eq!(format.record_payload_summary(&record), "\"v=spf1 -all\"");
+    }
+
+    #[test]
+    fn labelled_soa() {
+        let record = Record::SOA(SOA {
+            mname: "ns1.lookup.dog.".parse().unwrap(), rname: "hostmaster.lookup.dog.".parse().unwrap(),
+            serial: 2020010101, refresh_interval: 7200, retry_interval: 900, expire_limit: 1209600, minimum_ttl: 300,
+        });
+
+        assert_eq!(FORMAT.record_payload_summary(&record),
+                   "mname \"ns1.lookup.dog.\" email \"hostmaster@lookup.dog\" serial 2020010101 refresh 2h00m00s retry 15m00s expire 14d0h00m00s minimum 5m00s");
+    }
+
+    #[test]
+    fn short_services() {
+        let record = Record::SRV(SRV { priority: 1, weight: 2, port: 443, target: "lookup.dog.".parse().unwrap() });
+        assert_eq!(FORMAT.record_short_value(&record), "lookup.dog.:443");
+    }
+
+    #[test]
+    fn ttl_formats() {
+        assert_eq!(FORMAT.format_ttl(3600), "1h00m00s");
+        assert_eq!(TextFormat { ttl_format: TtlFormat::Raw, .. FORMAT }.format_ttl(3600), "3600");
+        assert_eq!(expiry_time(1_602_763_200, 3600), "2020-10-15T13:00:00Z");
+    }
+
+    #[test]
+    fn edns_pseudosection() {
+        let data = vec![
+            0x00, 0x03, 0x00, 0x04, b'd', b'o', b'g', b'1',
+            0x00, 0x08, 0x00, 0x07, 0x00, 0x01, 0x18, 0x00, 0xC0, 0x00, 0x02,
+            0x00, 0x0F, 0x00, 0x06, 0x00, 0x0F, b'n', b'o', b'p', b'e',
+            0x00, 0x0C, 0x00, 0x03, 0x00, 0x00, 0x00,
+            0xFD, 0xE9, 0x00, 0x00,
+        ];
+
+        let opt = OPT { udp_payload_size: 1232, higher_bits: 0, edns0_version: 0, flags: 0x8000, data };
+        assert_eq!(FORMAT.edns_lines(&opt), vec![
+            "version 0, flags: do, udp: 1232",
+            "NSID: 646f6731 (\"dog1\")",
+            "CLIENT-SUBNET: 192.0.2.0/24/0",
+            "EDE: 15 (Blocked): \"nope\"",
+            "PADDING: 3 bytes",
+            "OPT=65001: (empty)",
+        ]);
+    }
+
+    #[test]
+    fn short_everything_else() {
+        let record = Record::DS(DS { key_tag: 1, algorithm: 8, digest_type: 2, digest: vec![ 0xAB ] });
+        assert_eq!(FORMAT.record_short_value(&record), "1 8 2 AB");
+    }
+
+    #[test]
+    fn interpretation_text() {
+        let txt = TXT::from_text(format!("v=DKIM1; p={}", "A".repeat(40)));
+        let interpretation = crate::interpret::interpret(&"s._domainkey.lookup.dog.".parse().unwrap(), &txt).unwrap();
+
+        assert_eq!(interpretation_lines(&interpretation), vec![
+            "DKIM policy at s._domainkey.lookup.dog.".to_string(),
+            "  v=DKIM1                           DKIM version 1".to_string(),
+            format!("  p={}…  Public key, 30 bytes long", "A".repeat(29)),
+        ]);
+    }
+
+    #[test]
+    fn watch_times() {
+        assert_eq!(watch_time(1_602_763_200), "2020-10-15 12:00:00 UTC");
+    }
+
+    fn exchange(transport_type: TransportType, protocol: Protocol, connection: Connection) -> Exchange {
+        Exchange {
+            destination: crate::requests::Destination { nameserver: "dns.lookup.dog".into(), transport_type, authority: None },
+            round_trip: Duration::from_millis(12),
+            attempts: 1,
+            protocol,
+            connection,
+            edns: None,
+            bytes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn tls_connection_details() {
+        let connection = Connection {
+            address: Some("192.0.2.53:853".parse().unwrap()),
+            tls_version: Some("TLSv1.3".into()),
+            tls_cipher: Some("TLS_AES_256_GCM_SHA384".into()),
+            alpn: Some("dot".into()),
+            .. Connection::default()
+        };
+
+        assert_eq!(connection_details(&exchange(TransportType::TLS, Protocol::TLS, connection), &ENGLISH),
+                   Some("Connection: connected to 192.0.2.53:853, TLSv1.3 with TLS_AES_256_GCM_SHA384, ALPN dot".into()));
+    }
+
+    #[test]
+    fn tcp_fallback_details() {
+        let connection = Connection { address: Some("192.0.2.53:53".parse().unwrap()), .. Connection::default() };
+        assert_eq!(connection_details(&exchange(TransportType::Automatic, Protocol::TCP, connection), &ENGLISH),
+                   Some("Connection: connected to 192.0.2.53:53, fell back to TCP as the UDP response was truncated".into()));
+    }
+
+    #[test]
+    fn translated_connection_details() {
+        let german = crate::locale::find("de").unwrap();
+        let connection = Connection { address: Some("192.0.2.53:53".parse().unwrap()), .. Connection::default() };
+        assert_eq!(connection_details(&exchange(TransportType::Automatic, Protocol::TCP, connection), german),
+                   Some("Verbindung: verbunden mit 192.0.2.53:53, auf TCP ausgewichen, da die UDP-Antwort abgeschnitten war".into()));
+    }
+
+    #[test]
+    fn no_connection_details() {
+        assert_eq!(connection_details(&exchange(TransportType::Automatic, Protocol::Cache, Connection::default()), &ENGLISH), None);
+    }
+
+    #[test]
+    fn timeout_error() {
+        let error = TransportError::NetworkError(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        assert_eq!(json_error(error), json!({ "kind": "timeout", "phase": "network", "message": "timed out" }));
+    }
+
+    #[test]
+    fn authority_and_expectation_errors() {
+        let error = QueryError::Authority(AuthorityError::NoAddresses("lookup.dog.".parse().unwrap()));
+        assert_eq!(json_query_error(error), json!({
+            "kind": "no-addresses",
+            "phase": "authority",
+            "message": "Error finding an authoritative nameserver for lookup.dog.: none of its nameservers have addresses",
+        }));
+
+        let error = QueryError::Expectation(expect::Failure::MissingValue("192.0.2.1".into()));
+        assert_eq!(json_query_error(error), json!({
+            "kind": "expectation-failed",
+            "phase": "expectation",
+            "message": "Expected answer \"192.0.2.1\" was not found",
+        }));
+    }
+
+    #[test]
+    fn mismatch_error_details() {
+        let error = TransportError::TransactionIdMismatch { sent: 1234, received: 4321 };
+        assert_eq!(json_error(error), json!({
+            "kind": "transaction-id-mismatch",
+            "phase": "protocol",
+            "message": "Response had transaction ID 4321 instead of 1234",
+            "sent": 1234,
+            "received": 4321,
+        }));
+    }
 
-pub fn print_error_code(rcode: ErrorCode) {
-    match rcode {
-        ErrorCode::FormatError     => println!("Status: Format Error"),
-        ErrorCode::ServerFailure   => println!("Status: Server Failure"),
-        ErrorCode::NXDomain        => println!("Status: NXDomain"),
-        ErrorCode::NotImplemented  => println!("Status: Not Implemented"),
-        ErrorCode::QueryRefused    => println!("Status: Query Refused"),
-        ErrorCode::BadVersion      => println!("Status: Bad Version"),
-        ErrorCode::Other(num)      => println!("Status: Other Failure ({})", num),
+    #[test]
+    fn nxdomain_error() {
+        assert_eq!(json_rcode_error(ErrorCode::NXDomain),
+                   json!({ "kind": "nxdomain", "phase": "response", "message": "Domain does not exist", "rcode": "NXDOMAIN" }));
     }
 }
@@ -0,0 +1,580 @@
+//! Finding DNS messages in packet capture files.
+//!
+//! Both the original pcap format and pcapng are understood, as long as the
+//! packets were captured on Ethernet, loopback, Linux “cooked”, or raw IP
+//! interfaces. Any UDP or TCP packet to or from port 53 is taken to contain
+//! DNS, with TCP streams being put back together and split up at the two-
+//! byte length that precedes every message sent over TCP.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use log::*;
+
+use dns::TcpMessageBuffer;
+
+use crate::connect::TransportType;
+
+
+/// One DNS message found in a capture, along with where it was going.
+#[derive(PartialEq, Debug, Clone)]
+pub struct CapturedMessage {
+
+    /// The address and port the message was sent from.
+    pub source: SocketAddr,
+
+    /// The address and port the message was sent to.
+    pub destination: SocketAddr,
+
+    /// Whether the message was sent over UDP or TCP.
+    pub transport_type: TransportType,
+
+    /// The bytes of the message, without any TCP length prefix.
+    pub bytes: Vec<u8>,
+}
+
+/// Something that can go wrong reading a capture file.
+#[derive(PartialEq, Debug)]
+pub enum PcapError {
+
+    /// The file did not start with the magic number of either format.
+    UnknownFormat,
+
+    /// The file ended in the middle of a header or a packet.
+    Truncated,
+
+    /// The original pcap format was used with a link type that is not
+    /// understood, so none of the packets could be read.
+    UnsupportedLinkType(u32),
+}
+
+impl fmt::Display for PcapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat           => write!(f, "Not a pcap or pcapng file"),
+            Self::Truncated               => write!(f, "Capture file is truncated"),
+            Self::UnsupportedLinkType(lt) => write!(f, "Unsupported link type {} in capture file", lt),
+        }
+    }
+}
+
+
+/// Reads every packet in a capture file, returning the DNS messages in
+/// the order they were sent, or in the order their last TCP segment was
+/// captured for messages split over more than one segment.
+pub fn dns_messages(bytes: &[u8]) -> Result<Vec<CapturedMessage>, PcapError> {
+    let mut streams = Streams::default();
+
+    match bytes.get(0 .. 4) {
+        Some([ 0x0A, 0x0D, 0x0D, 0x0A ]) => read_pcapng(bytes, &mut streams)?,
+        Some(_)                          => read_pcap(bytes, &mut streams)?,
+        None                             => return Err(PcapError::UnknownFormat),
+    }
+
+    Ok(streams.messages)
+}
+
+
+/// The link types of the interfaces packets can be captured on, which
+/// determine which header comes before the IP packet.
+/// (http://www.tcpdump.org/linktypes.html)
+mod link_type {
+    pub const NULL:       u32 = 0;
+    pub const ETHERNET:   u32 = 1;
+    pub const RAW:        u32 = 101;
+    pub const LOOP:       u32 = 108;
+    pub const LINUX_SLL:  u32 = 113;
+    pub const IPV4:       u32 = 228;
+    pub const IPV6:       u32 = 229;
+    pub const LINUX_SLL2: u32 = 276;
+}
+
+fn is_supported(link_type: u32) -> bool {
+    [ link_type::NULL, link_type::ETHERNET, link_type::RAW, link_type::LINUX_SLL,
+      link_type::LOOP, link_type::IPV4, link_type::IPV6, link_type::LINUX_SLL2 ].contains(&link_type)
+}
+
+
+/// Reads a file in the original pcap format, which is a header followed
+/// by each packet with a header of its own. The magic number can be in
+/// either byte order, with timestamps in micro- or nanoseconds.
+fn read_pcap(bytes: &[u8], streams: &mut Streams) -> Result<(), PcapError> {
+    let endian = match bytes.get(0 .. 4) {
+        Some([ 0xA1, 0xB2, 0xC3, 0xD4 ]) | Some([ 0xA1, 0xB2, 0x3C, 0x4D ])  => Endian::Big,
+        Some([ 0xD4, 0xC3, 0xB2, 0xA1 ]) | Some([ 0x4D, 0x3C, 0xB2, 0xA1 ])  => Endian::Little,
+        _                                                                  => return Err(PcapError::UnknownFormat),
+    };
+
+    let link_type = endian.u32(bytes, 20).ok_or(PcapError::Truncated)? & 0x0FFF_FFFF;
+    if ! is_supported(link_type) {
+        return Err(PcapError::UnsupportedLinkType(link_type));
+    }
+
+    let mut offset = 24;
+    while offset < bytes.len() {
+        let captured_length = endian.u32(bytes, offset + 8).ok_or(PcapError::Truncated)? as usize;
+        let data = bytes.get(offset + 16 .. offset + 16 + captured_length).ok_or(PcapError::Truncated)?;
+        read_frame(link_type, data, streams);
+        offset += 16 + captured_length;
+    }
+
+    Ok(())
+}
+
+/// Reads a file in the pcapng format, which is a series of blocks. Each
+/// section starts with a header block giving its byte order, followed by
+/// a block describing each interface, then the packets captured on them.
+fn read_pcapng(bytes: &[u8], streams: &mut Streams) -> Result<(), PcapError> {
+    let mut endian = Endian::Little;
+    let mut interfaces = Vec::new();
+
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let block_type = endian.u32(bytes, offset).ok_or(PcapError::Truncated)?;
+
+        // The byte order of a section is only known after reading the
+        // magic number in its header, which comes after the length.
+        if block_type == 0x0A0D_0D0A {
+            endian = match bytes.get(offset + 8 .. offset + 12) {
+                Some([ 0x1A, 0x2B, 0x3C, 0x4D ])  => Endian::Big,
+                Some([ 0x4D, 0x3C, 0x2B, 0x1A ])  => Endian::Little,
+                Some(_)                           => return Err(PcapError::UnknownFormat),
+                None                              => return Err(PcapError::Truncated),
+            };
+
+            interfaces.clear();
+        }
+
+        let block_length = endian.u32(bytes, offset + 4).ok_or(PcapError::Truncated)? as usize;
+        if block_length < 12 {
+            return Err(PcapError::UnknownFormat);
+        }
+
+        let block = bytes.get(offset .. offset + block_length).ok_or(PcapError::Truncated)?;
+        let body_end = block_length - 4;
+
+        match block_type {
+            // Interface description block
+            1 => {
+                let link_type = endian.u16(block, 8).ok_or(PcapError::Truncated)?;
+                interfaces.push(u32::from(link_type));
+            }
+
+            // Enhanced packet block
+            6 => {
+                let interface = endian.u32(block, 8).ok_or(PcapError::Truncated)? as usize;
+                let captured_length = endian.u32(block, 20).ok_or(PcapError::Truncated)? as usize;
+                let data = block.get(28 .. (28 + captured_length).min(body_end)).ok_or(PcapError::Truncated)?;
+
+                match interfaces.get(interface) {
+                    Some(&link_type) if is_supported(link_type) => read_frame(link_type, data, streams),
+                    _ => debug!("Skipping packet on unsupported interface {}", interface),
+                }
+            }
+
+            // Simple packet block, which is always from the first interface
+            3 => {
+                let original_length = endian.u32(block, 8).ok_or(PcapError::Truncated)? as usize;
+                let data = block.get(12 .. (12 + original_length).min(body_end)).ok_or(PcapError::Truncated)?;
+
+                match interfaces.first() {
+                    Some(&link_type) if is_supported(link_type) => read_frame(link_type, data, streams),
+                    _ => debug!("Skipping packet on unsupported interface 0"),
+                }
+            }
+
+            _ => {}
+        }
+
+        offset += block_length;
+    }
+
+    Ok(())
+}
+
+
+/// Strips the link-layer header from a captured frame, then reads the IP
+/// packet inside it.
+fn read_frame(link_type: u32, frame: &[u8], streams: &mut Streams) {
+    let packet = match link_type {
+        link_type::ETHERNET => {
+            // Skip over any 802.1Q VLAN tags to get to the EtherType.
+            let mut offset = 12;
+            while frame.get(offset .. offset + 2) == Some(&[ 0x81, 0x00 ]) {
+                offset += 4;
+            }
+
+            frame.get(offset + 2 ..)
+        }
+        link_type::NULL | link_type::LOOP  => frame.get(4 ..),
+        link_type::LINUX_SLL               => frame.get(16 ..),
+        link_type::LINUX_SLL2              => frame.get(20 ..),
+        _                                  => Some(frame),
+    };
+
+    if let Some(packet) = packet {
+        read_ip_packet(packet, streams);
+    }
+}
+
+/// Reads an IPv4 or IPv6 packet, telling them apart by their version
+/// number, and passes any UDP or TCP segment on. Fragmented packets are
+/// skipped, as DNS messages rarely get fragmented.
+fn read_ip_packet(packet: &[u8], streams: &mut Streams) {
+    let (source, destination, mut protocol, mut payload) = match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            let header_length = usize::from(packet[0] & 0x0F) * 4;
+            let total_length = usize::from(u16::from_be_bytes([ packet[2], packet[3] ]));
+            let fragment = u16::from_be_bytes([ packet[6], packet[7] ]);
+            if fragment & 0x3FFF != 0 {
+                debug!("Skipping fragmented IPv4 packet");
+                return;
+            }
+
+            let source = IpAddr::from(Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]));
+            let destination = IpAddr::from(Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]));
+            let end = total_length.clamp(header_length, packet.len());
+            match packet.get(header_length .. end) {
+                Some(payload)  => (source, destination, packet[9], payload),
+                None           => return,
+            }
+        }
+        Some(6) if packet.len() >= 40 => {
+            let payload_length = usize::from(u16::from_be_bytes([ packet[4], packet[5] ]));
+            let source = IpAddr::from(<[u8; 16]>::try_from(&packet[8 .. 24]).map(Ipv6Addr::from).unwrap());
+            let destination = IpAddr::from(<[u8; 16]>::try_from(&packet[24 .. 40]).map(Ipv6Addr::from).unwrap());
+            let end = (40 + payload_length).min(packet.len());
+            (source, destination, packet[6], &packet[40 .. end])
+        }
+        _ => {
+            return;
+        }
+    };
+
+    // Skip over any IPv6 extension headers that may come before the UDP or
+    // TCP header, giving up on fragments.
+    while let 0 | 43 | 60 = protocol {
+        if payload.len() < 8 {
+            return;
+        }
+
+        let length = (usize::from(payload[1]) + 1) * 8;
+        protocol = payload[0];
+        payload = match payload.get(length ..) {
+            Some(rest)  => rest,
+            None        => return,
+        };
+    }
+
+    match protocol {
+        17 if payload.len() >= 8 => {
+            let source_port = u16::from_be_bytes([ payload[0], payload[1] ]);
+            let destination_port = u16::from_be_bytes([ payload[2], payload[3] ]);
+            let length = usize::from(u16::from_be_bytes([ payload[4], payload[5] ])).clamp(8, payload.len());
+
+            if source_port == 53 || destination_port == 53 {
+                streams.messages.push(CapturedMessage {
+                    source: SocketAddr::new(source, source_port),
+                    destination: SocketAddr::new(destination, destination_port),
+                    transport_type: TransportType::UDP,
+                    bytes: payload[8 .. length].to_vec(),
+                });
+            }
+        }
+        6 if payload.len() >= 20 => {
+            let source_port = u16::from_be_bytes([ payload[0], payload[1] ]);
+            let destination_port = u16::from_be_bytes([ payload[2], payload[3] ]);
+
+            if source_port == 53 || destination_port == 53 {
+                let segment = Segment {
+                    sequence: u32::from_be_bytes([ payload[4], payload[5], payload[6], payload[7] ]),
+                    syn: payload[13] & 0x02 != 0,
+                    data: payload.get(usize::from(payload[12] >> 4) * 4 ..).unwrap_or_default(),
+                };
+
+                streams.add_segment(SocketAddr::new(source, source_port), SocketAddr::new(destination, destination_port), segment);
+            }
+        }
+        _ => {}
+    }
+}
+
+
+/// The bits of a TCP segment needed to put a stream back together.
+struct Segment<'a> {
+    sequence: u32,
+    syn: bool,
+    data: &'a [u8],
+}
+
+/// The messages found so far, along with the TCP streams in each
+/// direction that are still being put back together.
+#[derive(Default)]
+struct Streams {
+    messages: Vec<CapturedMessage>,
+    tcp: HashMap<(SocketAddr, SocketAddr), Stream>,
+}
+
+/// One direction of a TCP connection.
+#[derive(Default)]
+struct Stream {
+
+    /// The sequence number of the next byte expected, which is unknown
+    /// until the first segment has been seen.
+    next_sequence: Option<u32>,
+
+    /// Segments that have not been put in order yet, along with the
+    /// sequence numbers they start at.
+    pending: Vec<(u32, Vec<u8>)>,
+
+    /// Bytes that have been put in order, but do not yet make up a whole
+    /// message.
+    buffer: TcpMessageBuffer,
+}
+
+impl Streams {
+    fn add_segment(&mut self, source: SocketAddr, destination: SocketAddr, segment: Segment<'_>) {
+        let stream = self.tcp.entry((source, destination)).or_default();
+
+        // A SYN starts the stream again, and takes up one sequence number.
+        if segment.syn {
+            *stream = Stream::default();
+            stream.next_sequence = Some(segment.sequence.wrapping_add(1));
+        }
+
+        if segment.data.is_empty() {
+            return;
+        }
+
+        // If the capture started part of the way through a connection, the
+        // first segment seen is taken to be the start of a message.
+        let _ = stream.next_sequence.get_or_insert(segment.sequence);
+        stream.pending.push((segment.sequence, segment.data.to_vec()));
+        stream.take_pending();
+
+        while let Some(bytes) = stream.buffer.next_message() {
+            self.messages.push(CapturedMessage { source, destination, transport_type: TransportType::TCP, bytes });
+        }
+    }
+}
+
+impl Stream {
+
+    /// Moves any pending segments that start at or before the next
+    /// expected byte into the buffer, skipping over any bytes in them that
+    /// have already been seen, until only segments in the future are left.
+    fn take_pending(&mut self) {
+        let Self { next_sequence, pending, buffer } = self;
+        let mut next = next_sequence.unwrap_or_default();
+
+        loop {
+            let mut progressed = false;
+
+            pending.retain(|(start, data)| {
+                let seen = next.wrapping_sub(*start);
+                if (seen as i32) < 0 {
+                    return true;
+                }
+
+                if (seen as usize) < data.len() {
+                    buffer.extend(&data[seen as usize ..]);
+                    next = start.wrapping_add(data.len() as u32);
+                    progressed = true;
+                }
+
+                false
+            });
+
+            if ! progressed {
+                break;
+            }
+        }
+
+        *next_sequence = Some(next);
+    }
+}
+
+
+/// The byte order used in a capture file’s headers, which depends on the
+/// machine that wrote it.
+#[derive(Copy, Clone)]
+enum Endian {
+    Big,
+    Little,
+}
+
+impl Endian {
+    fn u16(self, bytes: &[u8], offset: usize) -> Option<u16> {
+        let array = <[u8; 2]>::try_from(bytes.get(offset .. offset + 2)?).ok()?;
+        Some(match self {
+            Self::Big     => u16::from_be_bytes(array),
+            Self::Little  => u16::from_le_bytes(array),
+        })
+    }
+
+    fn u32(self, bytes: &[u8], offset: usize) -> Option<u32> {
+        let array = <[u8; 4]>::try_from(bytes.get(offset .. offset + 4)?).ok()?;
+        Some(match self {
+            Self::Big     => u32::from_be_bytes(array),
+            Self::Little  => u32::from_le_bytes(array),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::frame_tcp_message;
+
+    const CLIENT: [u8; 4] = [ 192, 0, 2, 1 ];
+    const SERVER: [u8; 4] = [ 192, 0, 2, 53 ];
+
+    fn ipv4(protocol: u8, source: [u8; 4], destination: [u8; 4], payload: Vec<u8>) -> Vec<u8> {
+        let mut packet = vec![ 0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0 ];
+        packet[2 .. 4].copy_from_slice(&((20 + payload.len()) as u16).to_be_bytes());
+        packet.extend(&source);
+        packet.extend(&destination);
+        packet.extend(payload);
+        packet
+    }
+
+    fn udp(source_port: u16, destination_port: u16, data: &[u8]) -> Vec<u8> {
+        let mut segment = Vec::new();
+        segment.extend(&source_port.to_be_bytes());
+        segment.extend(&destination_port.to_be_bytes());
+        segment.extend(&((8 + data.len()) as u16).to_be_bytes());
+        segment.extend(&[ 0, 0 ]);
+        segment.extend(data);
+        segment
+    }
+
+    fn tcp(sequence: u32, syn: bool, data: &[u8]) -> Vec<u8> {
+        let mut segment = Vec::new();
+        segment.extend(&5353_u16.to_be_bytes());
+        segment.extend(&53_u16.to_be_bytes());
+        segment.extend(&sequence.to_be_bytes());
+        segment.extend(&[ 0, 0, 0, 0, 0x50, if syn { 0x02 } else { 0x18 }, 0xFF, 0xFF, 0, 0, 0, 0 ]);
+        segment.extend(data);
+        segment
+    }
+
+    fn ethernet(packet: Vec<u8>) -> Vec<u8> {
+        let mut frame = vec![ 0; 12 ];
+        frame.extend(&[ 0x08, 0x00 ]);
+        frame.extend(packet);
+        frame
+    }
+
+    fn pcap(link_type: u32, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut file = vec![ 0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0 ];
+        file.extend(&link_type.to_le_bytes());
+
+        for frame in frames {
+            file.extend(&[ 0; 8 ]);
+            file.extend(&(frame.len() as u32).to_le_bytes());
+            file.extend(&(frame.len() as u32).to_le_bytes());
+            file.extend(frame);
+        }
+
+        file
+    }
+
+    fn pcapng(link_type: u16, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut file = vec![ 0x0A, 0x0D, 0x0D, 0x0A, 28, 0, 0, 0, 0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0 ];
+        file.extend(&[ 0xFF; 8 ]);
+        file.extend(&28_u32.to_le_bytes());
+
+        file.extend(&[ 1, 0, 0, 0, 20, 0, 0, 0 ]);
+        file.extend(&link_type.to_le_bytes());
+        file.extend(&[ 0, 0, 0, 0, 0, 0 ]);
+        file.extend(&20_u32.to_le_bytes());
+
+        for frame in frames {
+            let padding = (4 - frame.len() % 4) % 4;
+            let length = (32 + frame.len() + padding) as u32;
+            file.extend(&6_u32.to_le_bytes());
+            file.extend(&length.to_le_bytes());
+            file.extend(&[ 0; 12 ]);
+            file.extend(&(frame.len() as u32).to_le_bytes());
+            file.extend(&(frame.len() as u32).to_le_bytes());
+            file.extend(frame);
+            file.extend(vec![ 0; padding ]);
+            file.extend(&length.to_le_bytes());
+        }
+
+        file
+    }
+
+    #[test]
+    fn udp_over_ethernet() {
+        let file = pcap(1, &[
+            ethernet(ipv4(17, CLIENT, SERVER, udp(5353, 53, b"query"))),
+            ethernet(ipv4(17, SERVER, CLIENT, udp(53, 5353, b"response"))),
+            ethernet(ipv4(17, CLIENT, SERVER, udp(5353, 123, b"not dns"))),
+        ]);
+
+        assert_eq!(dns_messages(&file), Ok(vec![
+            CapturedMessage {
+                source: "192.0.2.1:5353".parse().unwrap(),
+                destination: "192.0.2.53:53".parse().unwrap(),
+                transport_type: TransportType::UDP,
+                bytes: b"query".to_vec(),
+            },
+            CapturedMessage {
+                source: "192.0.2.53:53".parse().unwrap(),
+                destination: "192.0.2.1:5353".parse().unwrap(),
+                transport_type: TransportType::UDP,
+                bytes: b"response".to_vec(),
+            },
+        ]));
+    }
+
+    #[test]
+    fn tcp_reassembly() {
+        let stream = frame_tcp_message(b"split across segments").unwrap();
+
+        // The second half arrives first, and the first half gets sent twice.
+        let file = pcapng(101, &[
+            ipv4(6, CLIENT, SERVER, tcp(1000, true, &[])),
+            ipv4(6, CLIENT, SERVER, tcp(1011, false, &stream[10 ..])),
+            ipv4(6, CLIENT, SERVER, tcp(1001, false, &stream[.. 10])),
+            ipv4(6, CLIENT, SERVER, tcp(1001, false, &stream[.. 10])),
+        ]);
+
+        let messages = dns_messages(&file).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].transport_type, TransportType::TCP);
+        assert_eq!(messages[0].bytes, b"split across segments".to_vec());
+    }
+
+    #[test]
+    fn tcp_messages_together() {
+        let mut stream = frame_tcp_message(b"one").unwrap();
+        stream.extend(frame_tcp_message(b"two").unwrap());
+
+        let file = pcapng(101, &[ ipv4(6, CLIENT, SERVER, tcp(1, false, &stream)) ]);
+        let messages = dns_messages(&file).unwrap().into_iter().map(|m| m.bytes).collect::<Vec<_>>();
+        assert_eq!(messages, vec![ b"one".to_vec(), b"two".to_vec() ]);
+    }
+
+    #[test]
+    fn not_a_capture() {
+        assert_eq!(dns_messages(b"hello, world!"), Err(PcapError::UnknownFormat));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut file = pcap(1, &[ ethernet(ipv4(17, CLIENT, SERVER, udp(5353, 53, b"query"))) ]);
+        file.truncate(file.len() - 1);
+        assert_eq!(dns_messages(&file), Err(PcapError::Truncated));
+    }
+
+    #[test]
+    fn unsupported_link_type() {
+        assert_eq!(dns_messages(&pcap(105, &[])), Err(PcapError::UnsupportedLinkType(105)));
+    }
+}
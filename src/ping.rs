@@ -0,0 +1,50 @@
+//! Checking that a nameserver is up, and how quickly it responds, by
+//! sending it the same small query again and again, the way ping does
+//! with ICMP echo requests.
+
+use std::time::Duration;
+
+use log::*;
+use tokio::time::timeout;
+
+use dns::Request;
+use dns_transport::{Error as TransportError, Reply, Runtime, Transport};
+
+
+/// How many queries to send if the user does not say.
+pub const DEFAULT_COUNT: u32 = 4;
+
+/// How long to wait between sending each query.
+pub const INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for each response before counting it as lost.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What happened to one query that was sent.
+#[derive(Debug)]
+pub enum Outcome {
+
+    /// A response arrived.
+    Response(Box<Reply>),
+
+    /// No response arrived before the timeout.
+    Timeout,
+
+    /// Sending the query or receiving the response failed.
+    Error(TransportError),
+}
+
+
+/// Sends one query down the transport, waiting up to the timeout for the
+/// response to arrive.
+pub fn send(runtime: &mut Runtime, transport: &(impl Transport + ?Sized), request: &Request) -> Outcome {
+    let result = runtime.block_on(async {
+        timeout(TIMEOUT, transport.send_with_details(request)).await
+    });
+
+    match result {
+        Ok(Ok(reply))  => Outcome::Response(Box::new(reply)),
+        Ok(Err(e))     => { debug!("Query failed: {:?}", e); Outcome::Error(e) }
+        Err(_)         => Outcome::Timeout,
+    }
+}
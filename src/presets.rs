@@ -0,0 +1,160 @@
+//! Built-in settings for popular public resolvers, so they can be picked by
+//! name, such as `@cloudflare`, without having to remember their addresses
+//! or the URLs of their DNS-over-HTTPS endpoints.
+
+use std::net::IpAddr;
+
+use dns_transport::Bootstrap;
+
+use crate::connect::TransportType;
+
+
+/// A public resolver that can be used as a nameserver by giving its name.
+#[derive(PartialEq, Debug)]
+pub struct Preset {
+
+    /// The name to pick it by.
+    pub name: &'static str,
+
+    /// The company or project that runs it.
+    pub provider: &'static str,
+
+    /// The addresses of its nameservers, which requests get sent to
+    /// directly, so nothing has to be looked up before the first query
+    /// can be sent.
+    pub addresses: &'static [&'static str],
+
+    /// The host name on its TLS certificate, which gets sent in SNI and
+    /// checked against the certificate for DNS-over-TLS.
+    pub tls_name: &'static str,
+
+    /// The URL of its DNS-over-HTTPS endpoint.
+    pub https_url: &'static str,
+}
+
+/// Every resolver that can be picked by name.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "cloudflare",
+        provider: "Cloudflare",
+        addresses: &[ "1.1.1.1", "1.0.0.1" ],
+        tls_name: "cloudflare-dns.com",
+        https_url: "https://cloudflare-dns.com/dns-query",
+    },
+    Preset {
+        name: "google",
+        provider: "Google Public DNS",
+        addresses: &[ "8.8.8.8", "8.8.4.4" ],
+        tls_name: "dns.google",
+        https_url: "https://dns.google/dns-query",
+    },
+    Preset {
+        name: "quad9",
+        provider: "Quad9",
+        addresses: &[ "9.9.9.9", "149.112.112.112" ],
+        tls_name: "dns.quad9.net",
+        https_url: "https://dns.quad9.net/dns-query",
+    },
+    Preset {
+        name: "opendns",
+        provider: "OpenDNS",
+        addresses: &[ "208.67.222.222", "208.67.220.220" ],
+        tls_name: "dns.opendns.com",
+        https_url: "https://doh.opendns.com/dns-query",
+    },
+    Preset {
+        name: "adguard",
+        provider: "AdGuard DNS",
+        addresses: &[ "94.140.14.14", "94.140.15.15" ],
+        tls_name: "dns.adguard-dns.com",
+        https_url: "https://dns.adguard-dns.com/dns-query",
+    },
+];
+
+
+/// Finds the preset with the given name, in any case.
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+impl Preset {
+
+    /// Where requests over the given transport type should be sent: the
+    /// URL for DNS-over-HTTPS, the host name on the certificate for
+    /// DNS-over-TLS, and the first address for everything else.
+    pub fn endpoint(&self, transport_type: TransportType) -> &'static str {
+        match transport_type {
+            TransportType::HTTPS  => self.https_url,
+            TransportType::TLS    => self.tls_name,
+            _                     => self.addresses[0],
+        }
+    }
+
+    /// Connects to the resolver’s known addresses for DNS-over-TLS and
+    /// DNS-over-HTTPS, instead of looking up the host name in its
+    /// endpoint.
+    pub fn bootstrap(&self) -> Bootstrap {
+        Bootstrap::Addresses(self.addresses.iter().map(|a| a.parse::<IpAddr>().expect("Preset address is invalid")).collect())
+    }
+}
+
+
+/// Lists every preset, one per line, with its columns lined up, for
+/// `--list-resolvers`.
+pub fn list_lines() -> Vec<String> {
+    let rows = PRESETS.iter().map(|preset| {
+        [ preset.name.to_string(), preset.provider.to_string(), preset.addresses.join(", "), preset.tls_name.to_string(), preset.https_url.to_string() ]
+    }).collect::<Vec<_>>();
+
+    let widths = (0 .. 4).map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or_default()).collect::<Vec<_>>();
+
+    rows.iter().map(|row| {
+        let padded = row[.. 4].iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect::<Vec<_>>();
+        format!("{}  {}", padded.join("  "), row[4])
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn find_in_any_case() {
+        assert_eq!(find("Quad9").map(|p| p.addresses[0]), Some("9.9.9.9"));
+    }
+
+    #[test]
+    fn find_nothing() {
+        assert_eq!(find("1.1.1.1"), None);
+    }
+
+    #[test]
+    fn endpoints() {
+        let google = find("google").unwrap();
+        assert_eq!(google.endpoint(TransportType::UDP), "8.8.8.8");
+        assert_eq!(google.endpoint(TransportType::TLS), "dns.google");
+        assert_eq!(google.endpoint(TransportType::HTTPS), "https://dns.google/dns-query");
+    }
+
+    #[test]
+    fn bootstrap_every_address() {
+        let google = find("google").unwrap();
+        assert_eq!(google.bootstrap(), Bootstrap::Addresses(vec![ "8.8.8.8".parse().unwrap(), "8.8.4.4".parse().unwrap() ]));
+    }
+
+    #[test]
+    fn addresses_valid() {
+        for preset in PRESETS {
+            let _ = preset.bootstrap();
+        }
+    }
+
+    #[test]
+    fn lines_line_up() {
+        let lines = list_lines();
+        assert_eq!(lines.len(), PRESETS.len());
+        assert!(lines[0].starts_with("cloudflare  Cloudflare         1.1.1.1, 1.0.0.1  "));
+    }
+}
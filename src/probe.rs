@@ -0,0 +1,318 @@
+//! Querying on an interval in the background, and exposing what happened
+//! as Prometheus metrics over HTTP, so dog can act as a blackbox exporter.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::*;
+
+use dns::{Answer, Query, Response};
+use dns::presentation::type_mnemonic;
+
+use crate::output::rcode_name;
+use crate::watch::{compare_rounds, Change};
+
+
+/// How long to wait between rounds of queries if the user does not say.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The path the metrics are served from.
+const METRICS_PATH: &str = "/metrics";
+
+
+/// The labels that tell apart the queries being probed.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Target {
+
+    /// The domain name being queried.
+    pub name: String,
+
+    /// The mnemonic of the record type being queried.
+    pub qtype: String,
+
+    /// The nameserver the query gets sent to.
+    pub nameserver: String,
+}
+
+impl Target {
+
+    /// Returns the target for a query sent to a nameserver.
+    pub fn of(query: &Query, nameserver: &str) -> Self {
+        Self {
+            name: query.qname.to_string(),
+            qtype: type_mnemonic(query.qtype),
+            nameserver: nameserver.into(),
+        }
+    }
+}
+
+/// Everything that has happened to the queries for one target.
+#[derive(PartialEq, Debug, Default)]
+struct Series {
+
+    /// How many queries have been sent.
+    queries: u64,
+
+    /// How many queries failed to get a response.
+    failures: u64,
+
+    /// Whether the most recent query got a response.
+    up: bool,
+
+    /// How long the most recent response took to arrive.
+    duration: Duration,
+
+    /// How many answers the most recent response had.
+    answers: usize,
+
+    /// How many times the answers have differed from the previous
+    /// response’s, regardless of their TTLs.
+    changes: u64,
+
+    /// How many responses had each rcode, in the order each one was first
+    /// seen.
+    rcodes: Vec<(String, u64)>,
+
+    /// The answers from the most recent response, to compare the next
+    /// one against.
+    previous: Option<Vec<Answer>>,
+}
+
+/// The metrics for every target, which get updated after each round of
+/// queries and rendered whenever they are scraped.
+#[derive(PartialEq, Debug, Default)]
+pub struct Metrics {
+
+    /// How many rounds of queries have been sent.
+    pub rounds: u64,
+
+    /// The series for each target, in the order they were first queried.
+    series: Vec<(Target, Series)>,
+}
+
+
+impl Metrics {
+
+    /// Records a response that arrived for a target’s query.
+    pub fn record_response(&mut self, target: Target, round_trip: Duration, response: &Response) {
+        let series = self.series_for(target);
+        let answers = response.answers.iter().filter(|a| a.is_standard()).cloned().collect::<Vec<_>>();
+
+        series.queries += 1;
+        series.up = true;
+        series.duration = round_trip;
+        series.answers = answers.len();
+
+        let changed = compare_rounds(series.previous.as_deref(), &answers, 0).iter().any(|w| matches!(w.change, Change::Added | Change::Removed));
+        if changed {
+            series.changes += 1;
+        }
+
+        let rcode = rcode_name(response.flags.error_code);
+        match series.rcodes.iter_mut().find(|(r, _)| *r == rcode) {
+            Some((_, count))  => *count += 1,
+            None              => series.rcodes.push((rcode, 1)),
+        }
+
+        series.previous = Some(answers);
+    }
+
+    /// Records a target’s query failing to get a response.
+    pub fn record_failure(&mut self, target: Target) {
+        let series = self.series_for(target);
+        series.queries += 1;
+        series.failures += 1;
+        series.up = false;
+    }
+
+    fn series_for(&mut self, target: Target) -> &mut Series {
+        let index = match self.series.iter().position(|(t, _)| *t == target) {
+            Some(index) => index,
+            None => {
+                self.series.push((target, Series::default()));
+                self.series.len() - 1
+            }
+        };
+
+        &mut self.series[index].1
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        family(&mut out, "dog_probe_rounds_total", "counter", "Rounds of queries sent since dog started.");
+        writeln!(out, "dog_probe_rounds_total {}", self.rounds).unwrap();
+
+        family(&mut out, "dog_probe_up", "gauge", "Whether the most recent query got a response.");
+        for (target, series) in &self.series {
+            writeln!(out, "dog_probe_up{{{}}} {}", labels(target), u8::from(series.up)).unwrap();
+        }
+
+        family(&mut out, "dog_probe_duration_seconds", "gauge", "How long the most recent response took to arrive.");
+        for (target, series) in &self.series {
+            writeln!(out, "dog_probe_duration_seconds{{{}}} {}", labels(target), series.duration.as_secs_f64()).unwrap();
+        }
+
+        family(&mut out, "dog_probe_answers", "gauge", "How many answers the most recent response had.");
+        for (target, series) in &self.series {
+            writeln!(out, "dog_probe_answers{{{}}} {}", labels(target), series.answers).unwrap();
+        }
+
+        family(&mut out, "dog_probe_queries_total", "counter", "Queries sent since dog started.");
+        for (target, series) in &self.series {
+            writeln!(out, "dog_probe_queries_total{{{}}} {}", labels(target), series.queries).unwrap();
+        }
+
+        family(&mut out, "dog_probe_failures_total", "counter", "Queries that failed to get a response.");
+        for (target, series) in &self.series {
+            writeln!(out, "dog_probe_failures_total{{{}}} {}", labels(target), series.failures).unwrap();
+        }
+
+        family(&mut out, "dog_probe_responses_total", "counter", "Responses received, by their status.");
+        for (target, series) in &self.series {
+            for (rcode, count) in &series.rcodes {
+                writeln!(out, "dog_probe_responses_total{{{},rcode=\"{}\"}} {}", labels(target), escape(rcode), count).unwrap();
+            }
+        }
+
+        family(&mut out, "dog_probe_answer_changes_total", "counter", "Times the answers differed from the previous response's.");
+        for (target, series) in &self.series {
+            writeln!(out, "dog_probe_answer_changes_total{{{}}} {}", labels(target), series.changes).unwrap();
+        }
+
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn labels(target: &Target) -> String {
+    format!("name=\"{}\",type=\"{}\",nameserver=\"{}\"", escape(&target.name), escape(&target.qtype), escape(&target.nameserver))
+}
+
+/// Escapes a label value, which can contain anything but has to have its
+/// backslashes, quotes, and newlines escaped.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+
+/// Parses the address to serve the metrics on, which is either an IP
+/// address and port, or a port on its own to listen on every interface.
+pub fn parse_listen_address(input: &str) -> Option<SocketAddr> {
+    match input.parse::<u16>() {
+        Ok(port)  => Some(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))),
+        Err(_)    => input.parse().ok(),
+    }
+}
+
+/// Answers HTTP requests for the metrics, one connection at a time, until
+/// the process exits.
+pub fn serve(listener: TcpListener, metrics: &Arc<Mutex<Metrics>>) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| respond(stream, metrics));
+        if let Err(e) = result {
+            warn!("Failed to serve metrics: {}", e);
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &Mutex<Metrics>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    // Only the request line matters, as every request gets the same
+    // response, so the headers do not need to be read.
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    debug!("Metrics request -> {:?}", request_line.trim_end());
+
+    let (status, body) = match request_path(&request_line) {
+        Some(METRICS_PATH)  => ("200 OK", metrics.lock().expect("Metrics lock poisoned").render()),
+        Some(_)             => ("404 Not Found", format!("Metrics are served at {}\n", METRICS_PATH)),
+        None                => ("400 Bad Request", String::new()),
+    };
+
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)?;
+    stream.flush()
+}
+
+/// Picks the path out of a `GET` request line, ignoring any query string.
+fn request_path(request_line: &str) -> Option<&str> {
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target))  => target.split('?').next(),
+        _                            => None,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::{Flags, QClass, ErrorCode};
+    use dns::record::{Record, A};
+
+    fn target() -> Target {
+        Target { name: "lookup.dog.".into(), qtype: "A".into(), nameserver: "127.0.0.1".into() }
+    }
+
+    fn response(last_octet: u8, rcode: Option<ErrorCode>) -> Response {
+        let mut flags = Flags::from_u16(0x8180);
+        flags.error_code = rcode;
+        let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, last_octet) });
+
+        Response {
+            transaction_id: 1,
+            flags,
+            queries: Vec::new(),
+            answers: vec![ Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl: 300, record } ],
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn counting() {
+        let mut metrics = Metrics::default();
+        metrics.record_response(target(), Duration::from_millis(20), &response(1, None));
+        metrics.record_response(target(), Duration::from_millis(30), &response(1, None));
+        metrics.record_response(target(), Duration::from_millis(25), &response(2, Some(ErrorCode::ServerFailure)));
+        metrics.record_failure(target());
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("dog_probe_up{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\"} 0\n"));
+        assert!(rendered.contains("dog_probe_duration_seconds{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\"} 0.025\n"));
+        assert!(rendered.contains("dog_probe_queries_total{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\"} 4\n"));
+        assert!(rendered.contains("dog_probe_failures_total{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\"} 1\n"));
+        assert!(rendered.contains("dog_probe_responses_total{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\",rcode=\"NOERROR\"} 2\n"));
+        assert!(rendered.contains("dog_probe_responses_total{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\",rcode=\"SERVFAIL\"} 1\n"));
+        assert!(rendered.contains("dog_probe_answer_changes_total{name=\"lookup.dog.\",type=\"A\",nameserver=\"127.0.0.1\"} 1\n"));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn listen_addresses() {
+        assert_eq!(parse_listen_address("9153"), Some("0.0.0.0:9153".parse().unwrap()));
+        assert_eq!(parse_listen_address("127.0.0.1:9153"), Some("127.0.0.1:9153".parse().unwrap()));
+        assert_eq!(parse_listen_address("localhost"), None);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(request_path("GET /metrics HTTP/1.1\r\n"), Some("/metrics"));
+        assert_eq!(request_path("GET /metrics?x=1 HTTP/1.1\r\n"), Some("/metrics"));
+        assert_eq!(request_path("POST /metrics HTTP/1.1\r\n"), None);
+    }
+}
@@ -0,0 +1,239 @@
+//! Checking whether a change has reached the public resolvers, by sending
+//! the same query to each of them and comparing what they answer with.
+
+use dns::{Response, Query, ErrorCode, Section};
+use dns::diff::{diff, Change, DiffOptions};
+use dns::record::Record;
+use dns_transport::Error as TransportError;
+
+use crate::resolve::{Resolver, Nameserver};
+
+
+/// A well-known public resolver, which gets sent the query when checking
+/// propagation.
+#[derive(PartialEq, Debug)]
+pub struct PublicResolver {
+
+    /// The name of the company or project that runs it, and the country
+    /// it is in, for the regional ones.
+    pub name: &'static str,
+
+    /// The address of one of its nameservers.
+    pub address: &'static str,
+}
+
+/// The resolvers a propagation check queries by default, starting with
+/// the big anycast ones, followed by ones that serve a particular region
+/// and so may have their own view of the world.
+pub const PUBLIC_RESOLVERS: &[PublicResolver] = &[
+    PublicResolver { name: "Google",                address: "8.8.8.8" },
+    PublicResolver { name: "Google",                address: "8.8.4.4" },
+    PublicResolver { name: "Cloudflare",            address: "1.1.1.1" },
+    PublicResolver { name: "Cloudflare",            address: "1.0.0.1" },
+    PublicResolver { name: "Quad9",                 address: "9.9.9.9" },
+    PublicResolver { name: "Quad9",                 address: "149.112.112.112" },
+    PublicResolver { name: "OpenDNS",               address: "208.67.222.222" },
+    PublicResolver { name: "OpenDNS",               address: "208.67.220.220" },
+    PublicResolver { name: "AdGuard",               address: "94.140.14.140" },
+    PublicResolver { name: "Control D",             address: "76.76.2.0" },
+    PublicResolver { name: "DNS.SB",                address: "185.222.222.222" },
+    PublicResolver { name: "DNS.WATCH (Germany)",   address: "84.200.69.80" },
+    PublicResolver { name: "CIRA (Canada)",         address: "149.112.121.10" },
+    PublicResolver { name: "Yandex (Russia)",       address: "77.88.8.8" },
+    PublicResolver { name: "114DNS (China)",        address: "114.114.114.114" },
+    PublicResolver { name: "AliDNS (China)",        address: "223.5.5.5" },
+];
+
+
+/// Returns the resolvers to query: the built-in public ones, followed by
+/// any the user gave on the command line.
+pub fn resolvers(specified: &[Resolver]) -> Vec<Resolver> {
+    let mut resolvers = PUBLIC_RESOLVERS.iter()
+                                        .map(|pr| Resolver::Specified(pr.address.into()))
+                                        .collect::<Vec<_>>();
+
+    for resolver in specified {
+        if *resolver != Resolver::SystemDefault && ! resolvers.contains(resolver) {
+            resolvers.push(resolver.clone());
+        }
+    }
+
+    resolvers
+}
+
+/// Returns the name of the public resolver at the given address, if it
+/// is one of them.
+pub fn resolver_name(nameserver: &str) -> Option<&'static str> {
+    PUBLIC_RESOLVERS.iter().find(|pr| pr.address == nameserver).map(|pr| pr.name)
+}
+
+
+/// Every resolver’s response to one query.
+#[derive(Debug)]
+pub struct Propagation {
+
+    /// The query that got sent to each resolver.
+    pub query: Query,
+
+    /// What each resolver responded with, in the order they were listed.
+    pub lookups: Vec<Lookup>,
+}
+
+/// One resolver’s response to a query, or the error it failed with.
+#[derive(Debug)]
+pub struct Lookup {
+
+    /// The nameserver the query was sent to.
+    pub nameserver: Nameserver,
+
+    /// The response, or why there was not one.
+    pub result: Result<Response, TransportError>,
+}
+
+/// The parts of a response that get shown for each resolver: its rcode,
+/// and the records in its answer section.
+#[derive(PartialEq, Debug, Clone)]
+pub struct AnswerSet {
+
+    /// The error code, if the resolver returned one.
+    pub rcode: Option<ErrorCode>,
+
+    /// The answer records, sorted by their type and data.
+    pub records: Vec<Record>,
+}
+
+impl AnswerSet {
+
+    /// Picks the answer set out of a response.
+    pub fn of(response: &Response) -> Self {
+        let mut records = response.answers.iter().filter_map(|answer| {
+            match answer {
+                dns::Answer::Standard { record, .. }  => Some(record.clone()),
+                dns::Answer::Pseudo { .. }            => None,
+            }
+        }).collect::<Vec<_>>();
+
+        records.sort_by_cached_key(|record| format!("{} {}", record.type_mnemonic(), record));
+        Self { rcode: response.flags.error_code, records }
+    }
+}
+
+impl Propagation {
+
+    /// Returns the answer set of each lookup that got a response.
+    pub fn answer_sets(&self) -> Vec<Option<AnswerSet>> {
+        self.lookups.iter().map(|lookup| lookup.result.as_ref().ok().map(AnswerSet::of)).collect()
+    }
+
+    /// Returns whether each lookup got the same answer as the consensus.
+    /// Lookups that failed never agree.
+    pub fn agreement(&self) -> Vec<bool> {
+        let responses = self.lookups.iter().map(|lookup| lookup.result.as_ref().ok()).collect::<Vec<_>>();
+        let consensus = consensus(&responses).and_then(|index| responses[index]);
+
+        responses.iter().map(|response| {
+            matches!((response, consensus), (Some(response), Some(consensus)) if same_answer(response, consensus))
+        }).collect()
+    }
+}
+
+
+/// Whether two resolvers gave the same answer: the same rcode, and the
+/// same records in the answer section, regardless of their TTLs and order.
+/// The header flags and the other sections are left out, as resolvers
+/// fill those in differently.
+pub fn same_answer(left: &Response, right: &Response) -> bool {
+    let options = DiffOptions { ignore_ttl: true, ignore_transaction_id: true };
+
+    ! diff(left, right, options).changes.iter().any(|change| {
+        matches!(change, Change::Rcode(..)                    |
+                         Change::Count(Section::Answer, ..)   |
+                         Change::Removed(Section::Answer, _)  |
+                         Change::Added(Section::Answer, _))
+    })
+}
+
+/// Finds the response that the most resolvers gave the same answer as,
+/// which the others are compared against, and returns its index. If there
+/// is a tie, the one from the resolver earliest in the list wins.
+pub fn consensus(responses: &[Option<&Response>]) -> Option<usize> {
+    let mut best: Option<(usize, usize)> = None;
+
+    for (index, response) in responses.iter().enumerate() {
+        if let Some(response) = response {
+            let count = responses.iter().flatten().filter(|r| same_answer(r, response)).count();
+            if best.is_none_or(|(_, most)| count > most) {
+                best = Some((index, count));
+            }
+        }
+    }
+
+    best.map(|(index, _)| index)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::{Answer, Flags, QClass};
+    use dns::record::A;
+
+    fn response(ttl: u32, last_octets: &[u8]) -> Response {
+        let answers = last_octets.iter().map(|o| {
+            let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, *o) });
+            Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl, record }
+        }).collect();
+
+        Response { transaction_id: 0x1234, flags: Flags::from_u16(0x8180), queries: Vec::new(), answers, authorities: Vec::new(), additionals: Vec::new() }
+    }
+
+    #[test]
+    fn majority() {
+        let (one, two) = (response(300, &[ 1 ]), response(300, &[ 2 ]));
+        assert_eq!(consensus(&[ Some(&two), Some(&one), None, Some(&one) ]),
+                   Some(1));
+    }
+
+    #[test]
+    fn tie() {
+        let (one, two) = (response(300, &[ 1 ]), response(300, &[ 2 ]));
+        assert_eq!(consensus(&[ Some(&two), Some(&one) ]),
+                   Some(0));
+    }
+
+    #[test]
+    fn all_failed() {
+        assert_eq!(consensus(&[ None, None ]),
+                   None);
+    }
+
+    #[test]
+    fn ttls_and_order_ignored() {
+        let mut other = response(60, &[ 2, 1 ]);
+        other.transaction_id = 0x4321;
+        other.flags.authoritative = true;
+        assert!(same_answer(&response(300, &[ 1, 2 ]), &other));
+    }
+
+    #[test]
+    fn rcode_differs() {
+        let mut failure = response(300, &[]);
+        failure.flags.error_code = Some(ErrorCode::NXDomain);
+        assert!(! same_answer(&response(300, &[]), &failure));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(resolver_name("1.1.1.1"), Some("Cloudflare"));
+        assert_eq!(resolver_name("192.0.2.53"), None);
+    }
+
+    #[test]
+    fn user_resolvers_come_last() {
+        let resolvers = resolvers(&[ Resolver::SystemDefault, Resolver::Specified("192.0.2.53".into()), Resolver::Specified("1.1.1.1".into()) ]);
+        assert_eq!(resolvers.len(), PUBLIC_RESOLVERS.len() + 1);
+        assert_eq!(resolvers.last(), Some(&Resolver::Specified("192.0.2.53".into())));
+    }
+}
@@ -1,6 +1,18 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dns_transport::{Bootstrap, TlsSettings};
+
+use crate::authority::Authority;
+use crate::cache::Caches;
 use crate::connect::TransportType;
-use crate::resolve::Resolver;
-use crate::txid::TxidGenerator;
+use crate::debugwire::DebugTransport;
+use crate::hosts::{HostsFile, HostsTransport};
+use crate::output::transport_name;
+use crate::resolve::{Resolver, Nameserver};
+use crate::txid::Txids;
+use crate::wirelog::{WireLog, LoggingTransport};
 
 
 /// All the information necessary to generate requests for one or more
@@ -12,13 +24,47 @@ pub struct RequestGenerator {
     pub inputs: Inputs,
 
     /// How to generate transaction IDs.
-    pub txid_generator: TxidGenerator,
+    pub txids: Txids,
 
     /// Whether to OPT in to DNS extensions.
     pub edns: UseEDNS,
 
     /// Other weird protocol options.
     pub protocol_tweaks: ProtocolTweaks,
+
+    /// Whether to randomise the case of query names sent over UDP, and
+    /// check that responses echo it back.
+    pub randomise_case: bool,
+
+    /// How long to keep listening for duplicate or late UDP responses
+    /// after the first one arrives.
+    pub receive_window: Duration,
+
+    /// How long to wait for each UDP response before giving up on it.
+    pub timeout: Duration,
+
+    /// How to look up the host names of DNS-over-TLS and DNS-over-HTTPS
+    /// servers.
+    pub bootstrap: Bootstrap,
+
+    /// How to check the certificates of DNS-over-TLS and DNS-over-HTTPS
+    /// servers.
+    pub tls: TlsSettings,
+
+    /// The caches to answer queries from, instead of sending them, if
+    /// responses are being cached.
+    pub cache: Option<Rc<Caches>>,
+
+    /// The hosts file to answer queries from, before sending them, if the
+    /// user asked for it to be consulted.
+    pub hosts: Option<Arc<HostsFile>>,
+
+    /// The file to log every request and response to, if they are being
+    /// logged.
+    pub wire_log: Option<Arc<WireLog>>,
+
+    /// Whether to log every request and response as annotated hexadecimal.
+    pub debug_wire: bool,
 }
 
 /// Which things the user has specified they want queried.
@@ -26,7 +72,7 @@ pub struct RequestGenerator {
 pub struct Inputs {
 
     /// The list of domain names to query.
-    pub domains: Vec<String>,
+    pub domains: Vec<dns::Name>,
 
     /// The list of DNS record types to query for.
     pub types: Vec<u16>,
@@ -47,6 +93,40 @@ pub struct ProtocolTweaks {
 
     /// Set the `AD` flag (Authentic Data) in the header of each request.
     pub set_authentic_flag: bool,
+
+    /// Set the `DO` bit (DNSSEC OK) in the OPT record of each request,
+    /// asking for the signatures over the records to be sent back too.
+    pub set_dnssec_ok: bool,
+
+    /// Set the `CD` flag (Checking Disabled) in the header of each request,
+    /// asking a validating resolver to return records even if they fail
+    /// validation.
+    pub set_checking_disabled: bool,
+
+    /// Clear the `RD` flag (Recursion Desired) in the header of each
+    /// request, asking a resolver to answer only from what it already has
+    /// cached rather than going off to look the answer up.
+    pub clear_recursion_desired: bool,
+
+    /// Ask every question in one request, rather than sending a request
+    /// for each, to see how servers cope with more than one question.
+    pub combine_questions: bool,
+}
+
+/// Where a generated request is going to be sent, which gets displayed
+/// alongside its response in the JSON output.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Destination {
+
+    /// The nameserver the request is sent to.
+    pub nameserver: Nameserver,
+
+    /// The transport type used to reach the nameserver.
+    pub transport_type: TransportType,
+
+    /// The authoritative nameserver the nameserver is, if the request is
+    /// being sent straight to one rather than to a resolver.
+    pub authority: Option<Authority>,
 }
 
 /// Whether to send or display OPT packets.
@@ -68,40 +148,53 @@ pub enum UseEDNS {
 
 impl RequestGenerator {
 
-    /// Iterate through the inputs matrix, returning pairs of DNS requests and
-    /// the details of the transport to send them down.
-    pub fn generate(self) -> Vec<(dns::Request, Box<dyn dns_transport::Transport>)> {
-        let nameservers = self.inputs.resolvers.into_iter()
-                              .map(|e| e.lookup().expect("Failed to get nameserver").expect("No nameserver found"))
-                              .collect::<Vec<_>>();
+    /// Iterate through the inputs matrix, returning DNS requests along with
+    /// the transport to send them down and where they are going.
+    pub fn generate(&self) -> Vec<(dns::Request, Rc<dyn dns_transport::Transport>, Destination)> {
+        self.generate_for(&self.inputs.domains, &self.inputs.types, &self.inputs.classes)
+    }
+
+    /// Iterate through the inputs matrix in the same way, but with the
+    /// given domains, types, and classes in place of the ones in the inputs.
+    ///
+    /// Each destination gets one transport, which is shared between every
+    /// request going there, so any connections it keeps open get re-used.
+    pub fn generate_for(&self, domains: &[dns::Name], types: &[dns::TypeInt], classes: &[dns::QClass]) -> Vec<(dns::Request, Rc<dyn dns_transport::Transport>, Destination)> {
+        let mut destinations = Vec::new();
+        for resolver in self.inputs.resolvers.iter().cloned() {
+            let nameserver = resolver.lookup().expect("Failed to get nameserver").expect("No nameserver found");
+
+            for transport_type in &self.inputs.transport_types {
+                let transport = Rc::from(self.make_transport(*transport_type, nameserver.clone()));
+                let destination = Destination { nameserver: nameserver.clone(), transport_type: *transport_type, authority: None };
+                destinations.push((transport, destination));
+            }
+        }
+
+        if self.protocol_tweaks.combine_questions {
+            let mut queries = Vec::new();
+            for domain in domains {
+                for qtype in types.iter().copied() {
+                    for qclass in classes.iter().copied() {
+                        queries.push(dns::Query { qname: domain.clone(), qtype, qclass });
+                    }
+                }
+            }
+
+            return destinations.into_iter()
+                               .map(|(transport, destination)| (self.make_combined_request(queries.clone()), transport, destination))
+                               .collect();
+        }
 
+        // The types go on the outside, so the results for each type come out
+        // together when there are several domains.
         let mut requests = Vec::new();
-        for domain in &self.inputs.domains {
-            for qtype in self.inputs.types.iter().copied() {
-                for qclass in self.inputs.classes.iter().copied() {
-                    for nameserver in &nameservers {
-                        for transport_type in &self.inputs.transport_types {
-
-                            let transaction_id = self.txid_generator.generate();
-                            let mut flags = dns::Flags::query();
-                            if self.protocol_tweaks.set_authentic_flag {
-                                flags.authentic_data = true;
-                            }
-
-                            let mut additional = None;
-                            if self.edns.should_send() {
-                                additional = Some(dns::Request::additional_record());
-                            }
-
-                            let queries = vec![
-                                dns::Query { qname: domain.clone(), qtype, qclass },
-                            ];
-
-                            let request = dns::Request { transaction_id, flags, queries, additional };
-
-                            let transport = transport_type.make_transport(nameserver.clone());
-                            requests.push((request, transport));
-                        }
+        for qtype in types.iter().copied() {
+            for domain in domains {
+                for qclass in classes.iter().copied() {
+                    for (transport, destination) in &destinations {
+                        let request = self.make_request(domain, qtype, qclass);
+                        requests.push((request, Rc::clone(transport), destination.clone()));
                     }
                 }
             }
@@ -109,6 +202,81 @@ impl RequestGenerator {
 
         requests
     }
+
+    /// Creates a transport to the given nameserver over the given transport
+    /// type, which answers from the hosts file if it is being consulted,
+    /// then from that nameserver’s cache if responses are being cached, and
+    /// logs the messages that actually get sent if they are being logged or
+    /// debugged.
+    pub fn make_transport(&self, transport_type: TransportType, nameserver: Nameserver) -> Box<dyn dns_transport::Transport> {
+        let cache = self.cache.as_ref().map(|caches| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+            caches.for_nameserver(&nameserver, now)
+        });
+
+        let mut transport = transport_type.make_transport(nameserver.clone(), self.randomise_case, self.receive_window, self.timeout, &self.bootstrap, &self.tls);
+        if self.debug_wire {
+            transport = Box::new(DebugTransport::new(transport, nameserver.clone()));
+        }
+
+        if let Some(log) = &self.wire_log {
+            transport = Box::new(LoggingTransport::new(transport, Arc::clone(log), nameserver, transport_name(transport_type)));
+        }
+
+        if let Some(cache) = cache {
+            transport = Box::new(dns_transport::CachingTransport::new(transport, cache));
+        }
+
+        match &self.hosts {
+            Some(hosts)  => Box::new(HostsTransport::new(transport, Arc::clone(hosts))),
+            None         => transport,
+        }
+    }
+
+    /// Creates a transport to the first nameserver over the first transport
+    /// type, for sending one query after another rather than going
+    /// through the whole inputs matrix.
+    pub fn transport(&self) -> Box<dyn dns_transport::Transport> {
+        let nameserver = self.inputs.resolvers[0].clone().lookup()
+                             .expect("Failed to get nameserver").expect("No nameserver found");
+
+        self.make_transport(self.inputs.transport_types[0], nameserver)
+    }
+
+    /// Creates a single request for the given domain, type, and class,
+    /// using the transaction ID, EDNS, and protocol settings from the user.
+    pub fn make_request(&self, domain: &dns::Name, qtype: dns::TypeInt, qclass: dns::QClass) -> dns::Request {
+        self.make_combined_request(vec![ dns::Query { qname: domain.clone(), qtype, qclass } ])
+    }
+
+    /// Creates a single request asking every one of the given questions,
+    /// in order, with the same settings as `make_request`.
+    pub fn make_combined_request(&self, queries: Vec<dns::Query>) -> dns::Request {
+        let mut queries = queries.into_iter();
+        let first = queries.next().expect("No questions to ask");
+
+        let mut builder = dns::MessageBuilder::query(first.qname, first.qtype)
+            .class(first.qclass)
+            .transaction_id(self.txids.generate())
+            .authentic_data(self.protocol_tweaks.set_authentic_flag)
+            .checking_disabled(self.protocol_tweaks.set_checking_disabled)
+            .recursion(! self.protocol_tweaks.clear_recursion_desired);
+
+        for query in queries {
+            builder = builder.also_query(query);
+        }
+
+        if self.edns.should_send() {
+            let mut opt = dns::Request::additional_record();
+            if self.protocol_tweaks.set_dnssec_ok {
+                opt.flags |= 0b_1000_0000_0000_0000;
+            }
+
+            builder = builder.edns(opt);
+        }
+
+        builder.build()
+    }
 }
 
 impl UseEDNS {
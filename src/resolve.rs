@@ -5,7 +5,7 @@ use log::*;
 
 /// A **resolver** is used to obtain the IP address of the server we should
 /// send DNS requests to.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Resolver {
 
     /// Read the list of nameservers from the system, and use that.
@@ -36,8 +36,7 @@ impl Resolver {
                 for line in reader.lines() {
                     let line = line?;
 
-                    if line.starts_with("nameserver ") {
-                        let line = &line[11..];
+                    if let Some(line) = line.strip_prefix("nameserver ") {
                         let ip: Result<std::net::Ipv4Addr, _> = line.parse();
 
                         match ip {
@@ -0,0 +1,419 @@
+//! Running a small DNS server that forwards the queries it receives to an
+//! upstream nameserver, over whichever transport the user picked, or
+//! answers them itself from a zone file, and sends the responses back to
+//! the client.
+
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+use log::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UdpSocket, TcpListener, TcpStream};
+use tokio::task::{spawn_local, LocalSet};
+
+use dns::{Request, Response, ErrorCode, Zone, TcpMessageBuffer, frame_tcp_message};
+use dns_transport::{Runtime, Transport, Reply, Error as TransportError};
+
+use crate::connect::TransportType;
+
+
+/// The port to listen on if the user does not give an address, which is
+/// not the usual DNS port, as that would need special privileges.
+pub const DEFAULT_PORT: u16 = 5300;
+
+/// The largest UDP response that can be sent to a client that did not say
+/// it could accept anything bigger with an OPT record.
+const DEFAULT_UDP_SIZE: usize = 512;
+
+/// Where the server gets the responses to queries from.
+pub enum Source {
+
+    /// Forward each query to an upstream nameserver.
+    Upstream(Box<dyn Transport>),
+
+    /// Answer each query from the records in a zone, as the authoritative
+    /// nameserver for it.
+    Zone(Zone),
+}
+
+/// One query that a client sent to the server, and how it was answered.
+#[derive(Debug)]
+pub struct Served {
+
+    /// The address and port of the client that sent the query.
+    pub client: SocketAddr,
+
+    /// Whether the client sent it over UDP or TCP.
+    pub transport_type: TransportType,
+
+    /// The request as it was received from the client.
+    pub request: Request,
+
+    /// What the client got sent back.
+    pub outcome: Outcome,
+}
+
+/// How the server answered a query.
+#[derive(Debug)]
+pub enum Outcome {
+
+    /// The upstream nameserver replied, and its response was sent back to
+    /// the client.
+    Forwarded(Box<Reply>),
+
+    /// Forwarding the query failed with an error, so the client got a
+    /// `SERVFAIL` response instead.
+    Failed(TransportError),
+
+    /// The response was worked out from the zone.
+    Authoritative(Response),
+}
+
+
+/// Listens for queries over both UDP and TCP on the given address, and
+/// answers each one from the source, calling the function with what
+/// happened to it. Each query gets dealt with as soon as it arrives,
+/// without waiting for the responses to any earlier ones. This only
+/// returns if something goes wrong with the sockets.
+pub fn run(runtime: &mut Runtime, listen: SocketAddr, source: Source, log: impl Fn(Served) + 'static) -> io::Result<()> {
+    let source = Rc::new(source);
+    let log: Rc<dyn Fn(Served)> = Rc::new(log);
+
+    // The sockets get bound here, rather than inside the runtime, so that
+    // an address that cannot be used gets reported straight away.
+    let udp_socket = std::net::UdpSocket::bind(listen)?;
+    udp_socket.set_nonblocking(true)?;
+    let tcp_listener = std::net::TcpListener::bind(listen)?;
+    tcp_listener.set_nonblocking(true)?;
+
+    let local = LocalSet::new();
+    runtime.block_on(local.run_until(async move {
+        let tcp_listener = TcpListener::from_std(tcp_listener)?;
+        spawn_local(serve_tcp(tcp_listener, Rc::clone(&source), Rc::clone(&log)));
+        serve_udp(udp_socket, source, log).await
+    }))
+}
+
+async fn serve_udp(socket: std::net::UdpSocket, source: Rc<Source>, log: Rc<dyn Fn(Served)>) -> io::Result<()> {
+    // Responses get sent from a clone of the socket, so sending one does
+    // not have to wait for the next query to be received.
+    let sender = Rc::new(socket.try_clone()?);
+    let mut receiver = UdpSocket::from_std(socket)?;
+    let mut buf = vec![0; 4096];
+
+    loop {
+        let (len, client) = receiver.recv_from(&mut buf).await?;
+        let message = buf[.. len].to_vec();
+        let (source, log, sender) = (Rc::clone(&source), Rc::clone(&log), Rc::clone(&sender));
+
+        spawn_local(async move {
+            if let Some(bytes) = answer(&source, &*log, &message, client, TransportType::UDP).await {
+                if let Err(e) = sender.send_to(&bytes, client) {
+                    warn!("Failed to send response to {}: {}", client, e);
+                }
+            }
+        });
+    }
+}
+
+async fn serve_tcp(mut listener: TcpListener, source: Rc<Source>, log: Rc<dyn Fn(Served)>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, client)) => {
+                let (source, log) = (Rc::clone(&source), Rc::clone(&log));
+                spawn_local(async move {
+                    if let Err(e) = serve_tcp_connection(stream, client, &source, &*log).await {
+                        debug!("Connection from {} ended: {}", client, e);
+                    }
+                });
+            }
+            Err(e) => {
+                warn!("Failed to accept connection: {}", e);
+            }
+        }
+    }
+}
+
+/// Answers every length-prefixed message the client sends down the
+/// connection, until it closes it.
+async fn serve_tcp_connection(mut stream: TcpStream, client: SocketAddr, source: &Source, log: &dyn Fn(Served)) -> io::Result<()> {
+    let mut buffer = TcpMessageBuffer::new();
+    let mut chunk = vec![0; 4096];
+
+    loop {
+        while let Some(message) = buffer.next_message() {
+            if let Some(bytes) = answer(source, log, &message, client, TransportType::TCP).await {
+                stream.write_all(&frame_tcp_message(&bytes)?).await?;
+            }
+        }
+
+        let len = stream.read(&mut chunk).await?;
+        if len == 0 {
+            if buffer.is_empty() {
+                return Ok(());
+            }
+
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed part of the way through a message"));
+        }
+
+        buffer.extend(&chunk[.. len]);
+    }
+}
+
+
+/// Works out the bytes to send back to a client in response to a message,
+/// by getting a response from the source if it is a valid request, or
+/// responding with `FORMERR` if it is not. Messages too short to even
+/// have a header get no response at all.
+async fn answer(source: &Source, log: &dyn Fn(Served), message: &[u8], client: SocketAddr, transport_type: TransportType) -> Option<Vec<u8>> {
+    let request = match Request::from_bytes(message) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Received malformed message from {}: {:?}", client, e);
+            return format_error(message);
+        }
+    };
+
+    let limit = if transport_type == TransportType::UDP { udp_limit(&request) } else { usize::from(u16::MAX) };
+
+    let (bytes, outcome) = match source {
+        Source::Upstream(upstream) => {
+            match upstream.send_with_details(&request).await {
+                Ok(mut reply) => {
+                    // The upstream transport may have changed the case of
+                    // the query names, so the client gets back the ones it
+                    // sent.
+                    reply.response.transaction_id = request.transaction_id;
+                    reply.response.queries = request.queries.clone();
+                    (encode(&reply.response, limit), Outcome::Forwarded(Box::new(reply)))
+                }
+                Err(e) => {
+                    (encode(&failure(&request, ErrorCode::ServerFailure), limit), Outcome::Failed(e))
+                }
+            }
+        }
+        Source::Zone(zone) => {
+            let response = zone.answer(&request);
+            (encode(&response, limit), Outcome::Authoritative(response))
+        }
+    };
+
+    log(Served { client, transport_type, request, outcome });
+    Some(bytes)
+}
+
+/// Returns the largest UDP response the client can accept, which is the
+/// payload size in the request’s OPT record if it has one.
+fn udp_limit(request: &Request) -> usize {
+    match &request.additional {
+        Some(opt)  => usize::from(opt.udp_payload_size).max(DEFAULT_UDP_SIZE),
+        None       => DEFAULT_UDP_SIZE,
+    }
+}
+
+/// Converts a response to bytes, replacing it with an empty response with
+/// the truncated flag set if it would go over the limit, so the client
+/// knows to try again over TCP.
+fn encode(response: &Response, limit: usize) -> Vec<u8> {
+    let bytes = match response.to_bytes() {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to encode response: {:?}", e);
+            return Vec::new();
+        }
+    };
+
+    if bytes.len() <= limit {
+        return bytes;
+    }
+
+    debug!("Truncating response of {} bytes to fit in {}", bytes.len(), limit);
+    let mut truncated = Response { answers: Vec::new(), authorities: Vec::new(), additionals: Vec::new(), .. response.clone() };
+    truncated.flags.truncated = true;
+    truncated.to_bytes().unwrap_or_default()
+}
+
+/// Creates a response to the request that only contains an error code.
+fn failure(request: &Request, rcode: ErrorCode) -> Response {
+    let mut flags = request.flags;
+    flags.response = true;
+    flags.authoritative = false;
+    flags.truncated = false;
+    flags.recursion_available = true;
+    flags.error_code = Some(rcode);
+
+    Response {
+        transaction_id: request.transaction_id,
+        flags,
+        queries: request.queries.clone(),
+        answers: Vec::new(),
+        authorities: Vec::new(),
+        additionals: Vec::new(),
+    }
+}
+
+/// Creates a `FORMERR` response to a message that could not be read as a
+/// request, as long as it had a header to take the transaction ID from.
+/// The question gets sent back too if it can be read, without decoding
+/// the rest of the message.
+fn format_error(message: &[u8]) -> Option<Vec<u8>> {
+    if message.len() < 12 {
+        return None;
+    }
+
+    let queries = Response::parse_ref(message).ok()
+                           .and_then(|r| r.queries().iter().map(|q| q.to_query()).collect::<Result<_, _>>().ok())
+                           .unwrap_or_default();
+
+    let request = Request {
+        transaction_id: u16::from_be_bytes([ message[0], message[1] ]),
+        flags: dns::Flags::query(),
+        queries,
+        additional: None,
+    };
+
+    failure(&request, ErrorCode::FormatError).to_bytes().ok()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::cell::RefCell;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use dns::{Flags, Query, QClass, Answer, record::{Record, A, OPT}};
+    use dns::testing::{MockServer, response_to};
+    use dns_transport::Protocol;
+
+    struct Upstream {
+        answers: usize,
+    }
+
+    #[async_trait]
+    impl Transport for Upstream {
+        async fn send_with_details(&self, request: &Request) -> Result<Reply, TransportError> {
+            let mut response = failure(request, ErrorCode::ServerFailure);
+            response.flags.error_code = None;
+            response.queries[0].qname = "LoOkUp.DoG.".parse().unwrap();
+
+            for index in 0 .. self.answers {
+                let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, index as u8) });
+                response.answers.push(Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl: 300, record });
+            }
+
+            Ok(Reply { response, bytes: Vec::new(), sent: Vec::new(), round_trip: Duration::from_millis(1), attempts: 1, protocol: Protocol::UDP, connection: Default::default() })
+        }
+    }
+
+    fn upstream(answers: usize) -> Source {
+        Source::Upstream(Box::new(Upstream { answers }))
+    }
+
+    fn request(additional: Option<OPT>) -> Request {
+        Request {
+            transaction_id: 0xABCD,
+            flags: Flags::query(),
+            queries: vec![ Query { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, qtype: 1 } ],
+            additional,
+        }
+    }
+
+    fn answer_with(source: &Source, request: &Request, transport_type: TransportType) -> (Response, Vec<Served>) {
+        let served = RefCell::new(Vec::new());
+        let log = |s| served.borrow_mut().push(s);
+        let client = "127.0.0.1:5300".parse().unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        let bytes = runtime.block_on(answer(source, &log, &request.to_bytes().unwrap(), client, transport_type)).unwrap();
+        (Response::from_bytes(&bytes).unwrap(), served.into_inner())
+    }
+
+    #[test]
+    fn forwarded() {
+        let (response, served) = answer_with(&upstream(1), &request(None), TransportType::UDP);
+        assert_eq!(response.transaction_id, 0xABCD);
+        assert_eq!(response.queries, request(None).queries);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(served.len(), 1);
+    }
+
+    #[test]
+    fn truncated_over_udp() {
+        let (response, _) = answer_with(&upstream(40), &request(None), TransportType::UDP);
+        assert!(response.flags.truncated);
+        assert_eq!(response.answers.len(), 0);
+    }
+
+    #[test]
+    fn bigger_with_edns() {
+        let opt = OPT { udp_payload_size: 4096, .. Request::additional_record() };
+        let (response, _) = answer_with(&upstream(40), &request(Some(opt)), TransportType::UDP);
+        assert!(! response.flags.truncated);
+        assert_eq!(response.answers.len(), 40);
+    }
+
+    #[test]
+    fn not_truncated_over_tcp() {
+        let (response, _) = answer_with(&upstream(40), &request(None), TransportType::TCP);
+        assert_eq!(response.answers.len(), 40);
+    }
+
+    #[test]
+    fn forwarded_over_the_network() {
+        let server = MockServer::reply_with(response_to).unwrap();
+        let transport = dns_transport::UdpTransport::new(server.address().to_string());
+
+        let (response, served) = answer_with(&Source::Upstream(Box::new(transport)), &request(None), TransportType::UDP);
+        assert_eq!(response.queries, request(None).queries);
+        assert_eq!(server.requests().len(), 1);
+        assert!(matches!(served[0].outcome, Outcome::Forwarded(_)));
+    }
+
+    #[test]
+    fn authoritative() {
+        let records = dns::zonefile::parse_zone("@ 60 SOA ns1 hostmaster 1 2 3 4 5", Some("lookup.dog.".parse().unwrap())).unwrap();
+        let zone = Source::Zone(Zone::new(records).unwrap());
+
+        let (response, served) = answer_with(&zone, &request(None), TransportType::UDP);
+        assert!(response.flags.authoritative);
+        assert_eq!(response.authorities.len(), 1);
+        assert!(matches!(served[0].outcome, Outcome::Authoritative(_)));
+    }
+
+    #[test]
+    fn authoritative_wildcard() {
+        let records = dns::zonefile::parse_zone("@ 60 SOA ns1 hostmaster 1 2 3 4 5\n*.dog. 60 A 127.0.0.1", Some("dog.".parse().unwrap())).unwrap();
+        let zone = Source::Zone(Zone::new(records).unwrap());
+
+        let (response, _) = answer_with(&zone, &request(None), TransportType::UDP);
+        assert_eq!(response.flags.error_code, None);
+        assert_eq!(response.answers.iter().map(ToString::to_string).collect::<Vec<_>>(), vec![ "lookup.dog. 60 IN A 127.0.0.1" ]);
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = format_error(&[ 0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xFF ]).unwrap();
+        let response = Response::from_bytes(&bytes).unwrap();
+        assert_eq!(response.transaction_id, 0x1234);
+        assert_eq!(response.flags.error_code, Some(ErrorCode::FormatError));
+        assert_eq!(format_error(&[ 0x12 ]), None);
+    }
+
+    #[test]
+    fn malformed_with_question() {
+        let message = [
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+            0x03, 0x64, 0x6f, 0x67, 0x00, 0x00, 0x01, 0x00, 0x01,  // dog. A IN
+            0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x03, 0x7f, 0x00, 0x00,  // A record that is too short
+        ];
+
+        assert!(Request::from_bytes(&message).is_err());
+        let response = Response::from_bytes(&format_error(&message).unwrap()).unwrap();
+        assert_eq!(response.flags.error_code, Some(ErrorCode::FormatError));
+        assert_eq!(response.queries, vec![ Query { qname: "dog.".parse().unwrap(), qtype: 1, qclass: QClass::IN } ]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Checking whether every authoritative nameserver for a zone is serving
+//! the same version of it, by asking each of them directly for the zone’s
+//! SOA record and comparing the serial numbers.
+
+use std::cmp::Ordering;
+use std::net::IpAddr;
+
+use dns::{qtype, Answer, ErrorCode, Name, Response, TypeInt};
+use dns::record::{A, AAAA, NS, Record, SOA, compare_serials};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// The serial numbers served by each of a zone’s nameservers.
+#[derive(Debug)]
+pub struct SoaCheck {
+
+    /// The zone whose nameservers were checked.
+    pub zone: Name,
+
+    /// Every address of every nameserver in the zone’s NS records, in the
+    /// order they were returned.
+    pub servers: Vec<Server>,
+}
+
+/// One address of one of a zone’s nameservers, and what it said.
+#[derive(Debug)]
+pub struct Server {
+
+    /// The nameserver’s name, from the NS record.
+    pub name: Name,
+
+    /// The address that got queried, or nothing if the name has none.
+    pub address: Option<IpAddr>,
+
+    /// The serial number in the SOA record the server responded with.
+    pub result: Result<u32, ServerError>,
+}
+
+/// Something that stopped a nameserver’s serial number from being found.
+#[derive(Debug)]
+pub enum ServerError {
+
+    /// The nameserver’s name has no A or AAAA records.
+    NoAddresses,
+
+    /// The query to the nameserver failed, most likely because it could
+    /// not be reached.
+    Unreachable(TransportError),
+
+    /// The nameserver responded with an error.
+    Rcode(ErrorCode),
+
+    /// The nameserver responded without an authoritative SOA record for
+    /// the zone, so it is not serving it.
+    NotAuthoritative,
+}
+
+/// Something that stopped the zone’s nameservers from being found.
+#[derive(Debug)]
+pub enum SoaError {
+
+    /// A query to the resolver for the NS records, or for a nameserver’s
+    /// addresses, failed, or the resolver responded with an error.
+    Lookup(LookupError),
+
+    /// The zone has no NS records.
+    NoNameservers(Name),
+}
+
+impl From<LookupError> for SoaError {
+    fn from(error: LookupError) -> Self {
+        Self::Lookup(error)
+    }
+}
+
+
+/// Finds the nameservers for the given zone and checks the serial number
+/// at each of their addresses. The lookup function is called to query a
+/// resolver for the NS records and the nameservers’ addresses, and the
+/// query function to ask each nameserver for the SOA record directly.
+pub fn check(zone: &Name,
+             mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>,
+             mut query: impl FnMut(IpAddr) -> Result<Response, TransportError>) -> Result<SoaCheck, SoaError> {
+
+    info!("Looking up NS records at {:?}", zone);
+    let response = lookup(zone, qtype!(NS)).map_err(|e| LookupError::Transport(zone.clone(), e))?;
+    check_rcode(zone, &response)?;
+
+    let nameservers = response.answers.into_iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { record: Record::NS(ns), .. }  => Some(ns.nameserver),
+            _                                                 => None,
+        }
+    }).collect::<Vec<_>>();
+
+    if nameservers.is_empty() {
+        return Err(SoaError::NoNameservers(zone.clone()));
+    }
+
+    let mut servers = Vec::new();
+    for name in nameservers {
+        let mut addresses = Vec::new();
+        for qtype in [ qtype!(A), qtype!(AAAA) ] {
+            info!("Looking up addresses of {:?}", name);
+            let response = lookup(&name, qtype).map_err(|e| LookupError::Transport(name.clone(), e))?;
+            check_rcode(&name, &response)?;
+            addresses.extend(response.answers.iter().filter_map(address));
+        }
+
+        if addresses.is_empty() {
+            servers.push(Server { name, address: None, result: Err(ServerError::NoAddresses) });
+            continue;
+        }
+
+        for address in addresses {
+            info!("Querying {:?} ({}) for the SOA record", name, address);
+            let result = query(address).map_err(ServerError::Unreachable).and_then(|response| serial(zone, &response));
+            servers.push(Server { name: name.clone(), address: Some(address), result });
+        }
+    }
+
+    Ok(SoaCheck { zone: zone.clone(), servers })
+}
+
+/// Gets the address out of an A or AAAA record.
+fn address(answer: &Answer) -> Option<IpAddr> {
+    match answer {
+        Answer::Standard { record: Record::A(A { address }), .. }     => Some(IpAddr::V4(*address)),
+        Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => Some(IpAddr::V6(*address)),
+        _                                                                => None,
+    }
+}
+
+/// Gets the serial number out of a nameserver’s response, which has to be
+/// authoritative for the zone.
+fn serial(zone: &Name, response: &Response) -> Result<u32, ServerError> {
+    if let Some(rcode) = response.flags.error_code {
+        return Err(ServerError::Rcode(rcode));
+    }
+
+    if ! response.flags.authoritative {
+        return Err(ServerError::NotAuthoritative);
+    }
+
+    response.answers.iter().find_map(|answer| {
+        match answer {
+            Answer::Standard { qname, record: Record::SOA(SOA { serial, .. }), .. } if qname == zone => Some(*serial),
+            _                                                                                          => None,
+        }
+    }).ok_or(ServerError::NotAuthoritative)
+}
+
+
+impl SoaCheck {
+
+    /// The newest serial number any server responded with, comparing them
+    /// with serial number arithmetic so that ones that have wrapped around
+    /// count as newer (RFC 1982).
+    pub fn newest_serial(&self) -> Option<u32> {
+        self.serials().fold(None, |newest, serial| {
+            match newest {
+                Some(newest) if compare_serials(serial, newest) != Some(Ordering::Greater)  => Some(newest),
+                _                                                                           => Some(serial),
+            }
+        })
+    }
+
+    /// How far behind the newest serial number a server’s serial is.
+    pub fn skew(&self, serial: u32) -> u32 {
+        self.newest_serial().map_or(0, |newest| newest.wrapping_sub(serial))
+    }
+
+    /// Whether every server responded with the same serial number.
+    pub fn in_sync(&self) -> bool {
+        let mut serials = self.serials();
+        match serials.next() {
+            Some(first)  => serials.all(|serial| serial == first),
+            None         => true,
+        }
+    }
+
+    /// Why each server that could not be asked for its serial number
+    /// could not be.
+    pub fn failures(&self) -> impl Iterator<Item=&ServerError> + '_ {
+        self.servers.iter().filter_map(|server| server.result.as_ref().err())
+    }
+
+    fn serials(&self) -> impl Iterator<Item=u32> + '_ {
+        self.servers.iter().filter_map(|server| server.result.as_ref().ok().copied())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+
+    fn response(qname: &Name, qtype: TypeInt, authoritative: bool, records: Vec<Record>) -> Response {
+        let answers = records.into_iter().map(|record| answer(qname.as_str(), record)).collect();
+        let mut response = crate::lookup::testing::response(qname, qtype, None, answers);
+        response.flags.authoritative = authoritative;
+        response
+    }
+
+    fn soa(serial: u32) -> Record {
+        Record::SOA(SOA {
+            mname: name("ns1.lookup.dog."), rname: name("hostmaster.lookup.dog."), serial,
+            refresh_interval: 3600, retry_interval: 600, expire_limit: 86400, minimum_ttl: 300,
+        })
+    }
+
+    /// The resolver knows of two nameservers for `lookup.dog`, the second
+    /// of which has two addresses.
+    fn lookup(qname: &Name, qtype: TypeInt) -> Result<Response, TransportError> {
+        let records = match (qname.as_str(), qtype) {
+            ("lookup.dog.", t) if t == qtype!(NS)        => vec![ Record::NS(NS { nameserver: name("ns1.lookup.dog.") }), Record::NS(NS { nameserver: name("ns2.lookup.dog.") }) ],
+            ("ns1.lookup.dog.", t) if t == qtype!(A)     => vec![ Record::A(A { address: "192.0.2.1".parse().unwrap() }) ],
+            ("ns2.lookup.dog.", t) if t == qtype!(A)     => vec![ Record::A(A { address: "192.0.2.2".parse().unwrap() }) ],
+            ("ns2.lookup.dog.", t) if t == qtype!(AAAA)  => vec![ Record::AAAA(AAAA { address: "2001:db8::2".parse().unwrap() }) ],
+            _                                            => Vec::new(),
+        };
+
+        Ok(response(qname, qtype, false, records))
+    }
+
+    /// Each address serves the SOA record with the given serial number.
+    fn query(serials: [u32; 3]) -> impl FnMut(IpAddr) -> Result<Response, TransportError> {
+        move |address| {
+            let serial = match address.to_string().as_str() {
+                "192.0.2.1"  => serials[0],
+                "192.0.2.2"  => serials[1],
+                _            => serials[2],
+            };
+
+            Ok(response(&name("lookup.dog."), qtype!(SOA), true, vec![ soa(serial) ]))
+        }
+    }
+
+    #[test]
+    fn every_address_checked() {
+        let check = check(&name("lookup.dog."), lookup, query([ 7, 7, 7 ])).unwrap();
+
+        let servers = check.servers.iter().map(|s| (s.name.as_str(), s.address.unwrap().to_string())).collect::<Vec<_>>();
+        assert_eq!(servers, vec![
+            ("ns1.lookup.dog.", "192.0.2.1".into()),
+            ("ns2.lookup.dog.", "192.0.2.2".into()),
+            ("ns2.lookup.dog.", "2001:db8::2".into()),
+        ]);
+        assert!(check.in_sync());
+        assert_eq!(check.failures().count(), 0);
+    }
+
+    #[test]
+    fn serial_skew() {
+        let check = check(&name("lookup.dog."), lookup, query([ 2024010102, 2024010101, 2024010102 ])).unwrap();
+
+        assert!(! check.in_sync());
+        assert_eq!(check.newest_serial(), Some(2024010102));
+        assert_eq!(check.skew(2024010101), 1);
+    }
+
+    #[test]
+    fn serial_wrapped_around() {
+        let check = check(&name("lookup.dog."), lookup, query([ 5, u32::MAX, 5 ])).unwrap();
+        assert_eq!(check.newest_serial(), Some(5));
+        assert_eq!(check.skew(u32::MAX), 6);
+    }
+
+    #[test]
+    fn unreachable_server() {
+        let check = check(&name("lookup.dog."), lookup, |address| {
+            if address.is_ipv6() { Err(TransportError::NetworkError(std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"))) }
+                            else { Ok(response(&name("lookup.dog."), qtype!(SOA), true, vec![ soa(7) ])) }
+        }).unwrap();
+
+        assert!(check.in_sync());
+        assert_eq!(check.failures().count(), 1);
+        assert!(matches!(check.servers[2].result, Err(ServerError::Unreachable(_))));
+    }
+
+    #[test]
+    fn not_authoritative() {
+        let check = check(&name("lookup.dog."), lookup, |_| Ok(response(&name("lookup.dog."), qtype!(SOA), false, vec![ soa(7) ]))).unwrap();
+        assert!(matches!(check.servers[0].result, Err(ServerError::NotAuthoritative)));
+    }
+
+    #[test]
+    fn no_nameservers() {
+        let result = check(&name("www.lookup.dog."), lookup, query([ 1, 1, 1 ]));
+        assert!(matches!(result, Err(SoaError::NoNameservers(_))));
+    }
+}
@@ -0,0 +1,85 @@
+//! Sorting the records in responses, so the output stays the same from one
+//! run to the next even when nameservers rotate the order of their records.
+
+use dns::{Answer, Response};
+
+
+/// Sorts the records in each section of a response by their type and then
+/// their data, removing any that are exact duplicates of another. The OPT
+/// pseudo-record, if it is being shown, goes last.
+pub fn sort_response(response: &mut Response) {
+    for section in [ &mut response.answers, &mut response.authorities, &mut response.additionals ] {
+        section.sort_by_cached_key(sort_key);
+        section.dedup();
+    }
+}
+
+/// The key records get sorted by, which also includes their owner name
+/// and TTL, so exact duplicates end up next to each other.
+fn sort_key(answer: &Answer) -> (bool, String, String, String, u32) {
+    match answer {
+        Answer::Standard { qname, ttl, record, .. } => {
+            (false, record.type_mnemonic(), record.to_string(), qname.to_string().to_lowercase(), *ttl)
+        }
+        Answer::Pseudo { qname, .. } => {
+            (true, String::new(), String::new(), qname.to_string(), 0)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::{Flags, QClass};
+    use dns::record::{A, MX, Record};
+
+    fn answer(record: Record) -> Answer {
+        Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    fn a(last: u8) -> Answer {
+        answer(Record::A(A { address: Ipv4Addr::new(192, 0, 2, last) }))
+    }
+
+    fn response(answers: Vec<Answer>) -> Response {
+        Response {
+            transaction_id: 0,
+            flags: Flags::query(),
+            queries: Vec::new(),
+            answers,
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rotated_records() {
+        let mut one = response(vec![ a(2), a(3), a(1) ]);
+        let mut two = response(vec![ a(3), a(1), a(2) ]);
+        sort_response(&mut one);
+        sort_response(&mut two);
+
+        assert_eq!(one.answers, vec![ a(1), a(2), a(3) ]);
+        assert_eq!(one, two);
+    }
+
+    #[test]
+    fn by_type_first() {
+        let mx = answer(Record::MX(MX { preference: 10, exchange: "mx.lookup.dog.".parse().unwrap() }));
+        let mut response = response(vec![ mx.clone(), a(1) ]);
+        sort_response(&mut response);
+
+        assert_eq!(response.answers, vec![ a(1), mx ]);
+    }
+
+    #[test]
+    fn duplicates_removed() {
+        let mut response = response(vec![ a(1), a(2), a(1) ]);
+        sort_response(&mut response);
+
+        assert_eq!(response.answers, vec![ a(1), a(2) ]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Resolving a service to the addresses and ports to connect to, by
+//! looking up its SRV records and then the addresses of their targets
+//! (RFC 2782).
+
+use std::net::{IpAddr, SocketAddr};
+
+use dns::{qtype, Answer, Name, Response, TypeInt};
+use dns::record::{A, AAAA, Record, SRV};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// The places a service can be reached at, in the order to try them in.
+#[derive(PartialEq, Debug)]
+pub struct Resolution {
+
+    /// The name the SRV records were looked up at, such as
+    /// `_xmpp-client._tcp.example.com`.
+    pub name: Name,
+
+    /// Where the service runs, sorted by priority and then weight.
+    pub endpoints: Vec<Endpoint>,
+
+    /// Whether the domain says outright that the service is not there,
+    /// with a single SRV record whose target is the root.
+    pub unavailable: bool,
+}
+
+/// One of the machines a service runs on.
+#[derive(PartialEq, Debug)]
+pub struct Endpoint {
+
+    /// Endpoints with lower priorities get tried first.
+    pub priority: u16,
+
+    /// Among endpoints with the same priority, ones with higher weights
+    /// should be chosen more often.
+    pub weight: u16,
+
+    /// The port the service listens on.
+    pub port: u16,
+
+    /// The name of the machine.
+    pub target: Name,
+
+    /// The machine’s IPv4 and IPv6 addresses, which is empty if it has
+    /// none.
+    pub addresses: Vec<IpAddr>,
+}
+
+
+/// Works out the name to look a service up at from the service, the
+/// protocol, and the domain, adding the underscores if they are missing.
+pub fn service_name(service: &str, protocol: &str, domain: &Name) -> String {
+    format!("_{}._{}.{}", service.trim_start_matches('_'), protocol.trim_start_matches('_'), domain)
+}
+
+/// Resolves the service at the given name, calling the lookup function to
+/// query for its SRV records, and then for the A and AAAA records of any
+/// targets whose addresses were not sent along with them.
+pub fn resolve(name: &Name, mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>) -> Result<Resolution, LookupError> {
+    info!("Looking up SRV records at {:?}", name);
+    let response = lookup(name, qtype!(SRV)).map_err(|e| LookupError::Transport(name.clone(), e))?;
+    check_rcode(name, &response)?;
+
+    let records = response.answers.iter().filter_map(|answer| {
+        match answer {
+            Answer::Standard { record: Record::SRV(srv), .. }  => Some(srv.clone()),
+            _                                                   => None,
+        }
+    }).collect::<Vec<SRV>>();
+
+    if let [ only ] = &records[..] {
+        if only.target.is_root() {
+            return Ok(Resolution { name: name.clone(), endpoints: Vec::new(), unavailable: true });
+        }
+    }
+
+    let mut endpoints = Vec::new();
+    for srv in records {
+        let mut addresses = additional_addresses(&response, &srv.target);
+
+        if addresses.is_empty() {
+            for qtype in [ qtype!(A), qtype!(AAAA) ] {
+                info!("Looking up addresses of {:?}", srv.target);
+                let response = lookup(&srv.target, qtype).map_err(|e| LookupError::Transport(srv.target.clone(), e))?;
+                check_rcode(&srv.target, &response)?;
+                addresses.extend(response.answers.iter().filter_map(address));
+            }
+        }
+
+        endpoints.push(Endpoint { priority: srv.priority, weight: srv.weight, port: srv.port, target: srv.target, addresses });
+    }
+
+    endpoints.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)));
+    Ok(Resolution { name: name.clone(), endpoints, unavailable: false })
+}
+
+/// Finds the addresses of a target that the nameserver sent in the
+/// additional section of the response to the SRV query, which many do.
+fn additional_addresses(response: &Response, target: &Name) -> Vec<IpAddr> {
+    response.additionals.iter().filter(|answer| {
+        matches!(answer, Answer::Standard { qname, .. } if qname == target)
+    }).filter_map(address).collect()
+}
+
+/// Gets the address out of an A or AAAA record.
+fn address(answer: &Answer) -> Option<IpAddr> {
+    match answer {
+        Answer::Standard { record: Record::A(A { address }), .. }     => Some(IpAddr::V4(*address)),
+        Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => Some(IpAddr::V6(*address)),
+        _                                                                => None,
+    }
+}
+
+
+impl Endpoint {
+
+    /// The addresses and port to connect to.
+    pub fn socket_addrs(&self) -> impl Iterator<Item=SocketAddr> + '_ {
+        self.addresses.iter().map(move |address| SocketAddr::new(*address, self.port))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::{QClass, ErrorCode};
+
+    fn srv(priority: u16, weight: u16, target: &str) -> Record {
+        Record::SRV(SRV { priority, weight, port: 5222, target: name(target) })
+    }
+
+    fn response(qname: &Name, qtype: TypeInt, rcode: Option<ErrorCode>, records: Vec<Record>) -> Response {
+        let answers = records.into_iter().map(|record| answer(qname.as_str(), record)).collect();
+        crate::lookup::testing::response(qname, qtype, rcode, answers)
+    }
+
+    /// Answers SRV queries with the given records, and address queries
+    /// with one address per target, counting how many of those there were.
+    fn lookup(records: Vec<Record>, address_queries: &mut usize) -> impl FnMut(&Name, TypeInt) -> Result<Response, TransportError> + '_ {
+        move |qname, qtype| {
+            if qtype == qtype!(SRV) {
+                return Ok(response(qname, qtype, None, records.clone()));
+            }
+
+            *address_queries += 1;
+            let record = match (qname.as_str(), qtype) {
+                ("one.lookup.dog.", t) if t == qtype!(A)     => Record::A(A { address: "192.0.2.1".parse().unwrap() }),
+                ("two.lookup.dog.", t) if t == qtype!(A)     => Record::A(A { address: "192.0.2.2".parse().unwrap() }),
+                ("two.lookup.dog.", t) if t == qtype!(AAAA)  => Record::AAAA(AAAA { address: "2001:db8::2".parse().unwrap() }),
+                _ => return Ok(response(qname, qtype, None, Vec::new())),
+            };
+
+            Ok(response(qname, qtype, None, vec![ record ]))
+        }
+    }
+
+    #[test]
+    fn name_from_parts() {
+        assert_eq!(service_name("xmpp-client", "tcp", &name("lookup.dog.")), "_xmpp-client._tcp.lookup.dog.");
+        assert_eq!(service_name("_sip", "_udp", &name("lookup.dog.")), "_sip._udp.lookup.dog.");
+    }
+
+    #[test]
+    fn sorted_by_priority_then_weight() {
+        let mut queries = 0;
+        let records = vec![ srv(20, 0, "one.lookup.dog."), srv(10, 5, "one.lookup.dog."), srv(10, 50, "two.lookup.dog.") ];
+        let resolution = resolve(&name("_xmpp-client._tcp.lookup.dog."), lookup(records, &mut queries)).unwrap();
+
+        let order = resolution.endpoints.iter().map(|e| (e.priority, e.weight)).collect::<Vec<_>>();
+        assert_eq!(order, vec![ (10, 50), (10, 5), (20, 0) ]);
+        assert!(! resolution.unavailable);
+    }
+
+    #[test]
+    fn addresses_resolved() {
+        let mut queries = 0;
+        let resolution = resolve(&name("_xmpp-client._tcp.lookup.dog."), lookup(vec![ srv(10, 0, "two.lookup.dog.") ], &mut queries)).unwrap();
+
+        let addrs = resolution.endpoints[0].socket_addrs().collect::<Vec<_>>();
+        assert_eq!(addrs, vec![ "192.0.2.2:5222".parse().unwrap(), "[2001:db8::2]:5222".parse().unwrap() ]);
+        assert_eq!(queries, 2);
+    }
+
+    #[test]
+    fn addresses_from_additional_section() {
+        let mut queries = 0;
+        let srv_name = name("_xmpp-client._tcp.lookup.dog.");
+        let resolution = resolve(&srv_name, |qname, qtype| {
+            queries += 1;
+            let mut response = response(qname, qtype, None, vec![ srv(10, 0, "one.lookup.dog.") ]);
+            response.additionals.push(Answer::Standard {
+                qname: name("one.lookup.dog."), qclass: QClass::IN, ttl: 300,
+                record: Record::A(A { address: "192.0.2.9".parse().unwrap() }),
+            });
+            Ok(response)
+        }).unwrap();
+
+        assert_eq!(resolution.endpoints[0].addresses, vec![ "192.0.2.9".parse::<IpAddr>().unwrap() ]);
+        assert_eq!(queries, 1);
+    }
+
+    #[test]
+    fn service_not_available() {
+        let mut queries = 0;
+        let resolution = resolve(&name("_xmpp-client._tcp.lookup.dog."), lookup(vec![ srv(0, 0, ".") ], &mut queries)).unwrap();
+
+        assert!(resolution.unavailable);
+        assert_eq!(resolution.endpoints, Vec::new());
+        assert_eq!(queries, 0);
+    }
+
+    #[test]
+    fn no_records() {
+        let resolution = resolve(&name("_xmpp-client._tcp.lookup.dog."), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::NXDomain), Vec::new()))
+        }).unwrap();
+
+        assert_eq!(resolution.endpoints, Vec::new());
+        assert!(! resolution.unavailable);
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = resolve(&name("_xmpp-client._tcp.lookup.dog."), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::ServerFailure), Vec::new()))
+        });
+
+        assert!(matches!(result, Err(LookupError::Rcode(_, ErrorCode::ServerFailure))));
+    }
+}
@@ -0,0 +1,106 @@
+//! Summarising how long a set of queries took, and how they got there.
+
+use std::time::Duration;
+
+use dns_transport::Protocol;
+
+use crate::output::Exchange;
+
+
+/// A summary of the exchanges made for a set of queries.
+#[derive(PartialEq, Debug)]
+pub struct Stats {
+
+    /// How many queries were sent, including the ones that failed.
+    pub queries: usize,
+
+    /// How many queries failed to get a response.
+    pub failed: usize,
+
+    /// The shortest round trip of the queries that got a response.
+    pub min: Duration,
+
+    /// The mean round trip of the queries that got a response.
+    pub avg: Duration,
+
+    /// The longest round trip of the queries that got a response.
+    pub max: Duration,
+
+    /// How many requests had to be sent again over TCP after a truncated
+    /// response arrived over UDP, which is the only reason a request gets
+    /// sent more than once.
+    pub retries: u32,
+
+    /// How many responses arrived over each protocol, in the order each
+    /// protocol was first seen.
+    pub protocols: Vec<(Protocol, usize)>,
+}
+
+impl Stats {
+
+    /// Summarises the exchanges that got a response, along with the
+    /// number of queries that did not.
+    pub fn summarise(exchanges: &[&Exchange], failed: usize) -> Self {
+        let round_trips = exchanges.iter().map(|e| e.round_trip);
+        let min = round_trips.clone().min().unwrap_or_default();
+        let max = round_trips.clone().max().unwrap_or_default();
+        let avg = if exchanges.is_empty() { Duration::default() }
+                                     else { round_trips.sum::<Duration>() / exchanges.len() as u32 };
+
+        let mut protocols: Vec<(Protocol, usize)> = Vec::new();
+        for exchange in exchanges {
+            match protocols.iter_mut().find(|(p, _)| *p == exchange.protocol) {
+                Some((_, count))  => *count += 1,
+                None              => protocols.push((exchange.protocol, 1)),
+            }
+        }
+
+        let retries = exchanges.iter().map(|e| e.attempts.saturating_sub(1)).sum();
+        Self { queries: exchanges.len() + failed, failed, min, avg, max, retries, protocols }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::connect::TransportType;
+    use crate::requests::Destination;
+    use dns_transport::Connection;
+
+    fn exchange(millis: u64, attempts: u32, protocol: Protocol) -> Exchange {
+        Exchange {
+            destination: Destination { nameserver: "1.1.1.1".into(), transport_type: TransportType::Automatic, authority: None },
+            round_trip: Duration::from_millis(millis),
+            attempts,
+            protocol,
+            connection: Connection::default(),
+            edns: None,
+            bytes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let exchanges = [ exchange(10, 1, Protocol::UDP), exchange(30, 2, Protocol::TCP), exchange(20, 1, Protocol::UDP) ];
+
+        assert_eq!(Stats::summarise(&exchanges.iter().collect::<Vec<_>>(), 1), Stats {
+            queries: 4,
+            failed: 1,
+            min: Duration::from_millis(10),
+            avg: Duration::from_millis(20),
+            max: Duration::from_millis(30),
+            retries: 1,
+            protocols: vec![ (Protocol::UDP, 2), (Protocol::TCP, 1) ],
+        });
+    }
+
+    #[test]
+    fn everything_failed() {
+        let stats = Stats::summarise(&[], 2);
+        assert_eq!(stats.queries, 2);
+        assert_eq!(stats.avg, Duration::default());
+        assert_eq!(stats.protocols, vec![]);
+    }
+}
@@ -0,0 +1,232 @@
+//! Comparing the addresses the operating system’s resolver gives for a
+//! name, through `getaddrinfo`, with the ones DNS gives for it. The two
+//! can differ when the system checks somewhere other than DNS first, as
+//! configured in `/etc/nsswitch.conf`: a hosts file, or multicast DNS
+//! for names under `local`.
+
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+
+use dns::{qtype, Answer, Name, Response, TypeInt};
+use dns::record::{A, AAAA, Record};
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::hosts::HostsFile;
+use crate::lookup::{check_rcode, LookupError};
+
+
+/// The addresses each way of looking a name up gave for it.
+#[derive(PartialEq, Debug)]
+pub struct SystemComparison {
+
+    /// The name that was looked up.
+    pub domain: Name,
+
+    /// The addresses the system resolver gave, in the order it gave them,
+    /// or the reason it gave none.
+    pub system: Result<Vec<IpAddr>, String>,
+
+    /// The addresses in the A and AAAA records DNS gave.
+    pub dns: Vec<IpAddr>,
+
+    /// The addresses the hosts file lists for the name, which is where
+    /// the system resolver usually looks first.
+    pub hosts_file: Vec<IpAddr>,
+}
+
+/// Why the system resolver disagrees with DNS about a name, going by
+/// where else it could have got its answer from.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Cause {
+
+    /// The hosts file lists the name, and the system used its addresses.
+    HostsFile,
+
+    /// The name is under `local`, which gets resolved with multicast DNS
+    /// rather than by asking a nameserver (RFC 6762).
+    MulticastDns,
+
+    /// Neither of those, so it is something else in `nsswitch.conf`, or a
+    /// different resolver configuration.
+    Unknown,
+}
+
+
+/// Looks the name up with the given system lookup function, and then asks
+/// for its A and AAAA records with the DNS lookup function, noting any
+/// addresses the hosts file has for it too.
+pub fn compare(domain: &Name, hosts: Option<&HostsFile>, system_lookup: impl FnOnce(&str) -> io::Result<Vec<IpAddr>>, mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>) -> Result<SystemComparison, LookupError> {
+    info!("Looking up {:?} with the system resolver", domain);
+    let system = system_lookup(domain.as_str().trim_end_matches('.')).map_err(|e| e.to_string());
+
+    let mut dns = Vec::new();
+    for qtype in [ qtype!(A), qtype!(AAAA) ] {
+        info!("Looking up {:?} with DNS", domain);
+        let response = lookup(domain, qtype).map_err(|e| LookupError::Transport(domain.clone(), e))?;
+
+        check_rcode(domain, &response)?;
+
+        // Any CNAME records get followed by the resolver, so the addresses
+        // can be under a different name from the one that was asked for.
+        dns.extend(response.answers.iter().filter_map(|answer| {
+            match answer {
+                Answer::Standard { record: Record::A(A { address }), .. }        => Some(IpAddr::V4(*address)),
+                Answer::Standard { record: Record::AAAA(AAAA { address }), .. }  => Some(IpAddr::V6(*address)),
+                _                                                                => None,
+            }
+        }));
+    }
+
+    let hosts_file = hosts.map(|hosts| {
+        [ qtype!(A), qtype!(AAAA) ].iter().flat_map(|qtype| {
+            let query = dns::Query { qname: domain.clone(), qclass: dns::QClass::IN, qtype: *qtype };
+            hosts.records(&query).into_iter().filter_map(|record| match record {
+                Record::A(A { address })        => Some(IpAddr::V4(address)),
+                Record::AAAA(AAAA { address })  => Some(IpAddr::V6(address)),
+                _                               => None,
+            })
+        }).collect()
+    }).unwrap_or_default();
+
+    Ok(SystemComparison { domain: domain.clone(), system, dns, hosts_file })
+}
+
+/// Looks a name up with `getaddrinfo`, through the standard library,
+/// returning each address once in the order they came back in.
+pub fn system_addresses(name: &str) -> io::Result<Vec<IpAddr>> {
+    let mut addresses = Vec::new();
+    for socket in (name, 0).to_socket_addrs()? {
+        if ! addresses.contains(&socket.ip()) {
+            addresses.push(socket.ip());
+        }
+    }
+
+    Ok(addresses)
+}
+
+
+impl SystemComparison {
+
+    /// Every address either way of looking the name up gave, with the
+    /// system resolver’s ones first.
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        let mut addresses = self.system_addresses().to_vec();
+        for address in &self.dns {
+            if ! addresses.contains(address) {
+                addresses.push(*address);
+            }
+        }
+
+        addresses
+    }
+
+    /// The addresses the system resolver gave, which is none if it failed.
+    pub fn system_addresses(&self) -> &[IpAddr] {
+        self.system.as_deref().unwrap_or_default()
+    }
+
+    /// Whether both ways of looking the name up gave the same addresses,
+    /// in any order.
+    pub fn agrees(&self) -> bool {
+        let system = self.system_addresses();
+        system.iter().all(|a| self.dns.contains(a)) && self.dns.iter().all(|a| system.contains(a))
+    }
+
+    /// Works out why the two ways of looking the name up disagree, if
+    /// they do.
+    pub fn cause(&self) -> Option<Cause> {
+        if self.agrees() {
+            None
+        }
+        else if ! self.hosts_file.is_empty() && self.system_addresses().iter().all(|a| self.hosts_file.contains(a)) {
+            Some(Cause::HostsFile)
+        }
+        else if self.domain.labels().last().is_some_and(|tld| tld.eq_ignore_ascii_case("local")) {
+            Some(Cause::MulticastDns)
+        }
+        else {
+            Some(Cause::Unknown)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::{QClass, ErrorCode};
+
+    fn ip(input: &str) -> IpAddr {
+        input.parse().unwrap()
+    }
+
+    /// Answers with one IPv4 and one IPv6 address for every name.
+    fn lookup(qname: &Name, qtype: TypeInt) -> Result<Response, TransportError> {
+        let record = if qtype == qtype!(A) { Record::A(A { address: "192.0.2.1".parse().unwrap() }) }
+                                      else { Record::AAAA(AAAA { address: "2001:db8::1".parse().unwrap() }) };
+
+        Ok(response(qname, qtype, None, vec![ Answer::Standard { qname: qname.clone(), qclass: QClass::IN, ttl: 300, record } ]))
+    }
+
+    fn hosts() -> HostsFile {
+        HostsFile::parse("/etc/hosts".into(), "192.0.2.99 printer.lookup.dog\n")
+    }
+
+    #[test]
+    fn agreement() {
+        let comparison = compare(&name("lookup.dog"), None, |_| Ok(vec![ ip("2001:db8::1"), ip("192.0.2.1") ]), lookup).unwrap();
+
+        assert_eq!(comparison.dns, vec![ ip("192.0.2.1"), ip("2001:db8::1") ]);
+        assert!(comparison.agrees());
+        assert_eq!(comparison.cause(), None);
+    }
+
+    #[test]
+    fn system_without_ipv6() {
+        let comparison = compare(&name("lookup.dog"), None, |_| Ok(vec![ ip("192.0.2.1") ]), lookup).unwrap();
+
+        assert!(! comparison.agrees());
+        assert_eq!(comparison.addresses(), vec![ ip("192.0.2.1"), ip("2001:db8::1") ]);
+        assert_eq!(comparison.cause(), Some(Cause::Unknown));
+    }
+
+    #[test]
+    fn from_the_hosts_file() {
+        let comparison = compare(&name("printer.lookup.dog"), Some(&hosts()), |_| Ok(vec![ ip("192.0.2.99") ]), lookup).unwrap();
+
+        assert_eq!(comparison.hosts_file, vec![ ip("192.0.2.99") ]);
+        assert_eq!(comparison.cause(), Some(Cause::HostsFile));
+    }
+
+    #[test]
+    fn multicast_dns() {
+        let comparison = compare(&name("printer.local"), None, |_| Ok(vec![ ip("192.0.2.99") ]), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::NXDomain), Vec::new()))
+        }).unwrap();
+
+        assert!(comparison.dns.is_empty());
+        assert_eq!(comparison.cause(), Some(Cause::MulticastDns));
+    }
+
+    #[test]
+    fn system_failure() {
+        let comparison = compare(&name("lookup.dog"), None, |_| Err(io::Error::other("no such name")), lookup).unwrap();
+
+        assert_eq!(comparison.system, Err("no such name".into()));
+        assert!(comparison.system_addresses().is_empty());
+        assert!(! comparison.agrees());
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = compare(&name("lookup.dog"), None, |_| Ok(Vec::new()), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::ServerFailure), Vec::new()))
+        });
+
+        assert!(matches!(result, Err(LookupError::Rcode(_, ErrorCode::ServerFailure))));
+    }
+}
@@ -5,10 +5,12 @@ use std::time::Duration;
 use ansi_term::ANSIString;
 
 use dns::Answer;
-use dns::record::Record;
+use dns::record::{OPT, Record};
 
 use crate::colours::Colours;
+use crate::locale::Message;
 use crate::output::TextFormat;
+use crate::validate::Status;
 
 
 /// A **table** is built up from all the response records present in a DNS
@@ -18,6 +20,7 @@ pub struct Table {
     colours: Colours,
     text_format: TextFormat,
     rows: Vec<Row>,
+    pseudosections: Vec<OPT>,
 }
 
 /// A row of the table. This contains all the fields
@@ -27,6 +30,7 @@ pub struct Row {
     qname: String,
     ttl: Option<String>,
     section: Section,
+    status: Option<Status>,
     summary: String,
 }
 
@@ -49,33 +53,58 @@ impl Table {
 
     /// Create a new table with no rows.
     pub fn new(colours: Colours, text_format: TextFormat) -> Self {
-        Self { colours, text_format, rows: Vec::new() }
+        Self { colours, text_format, rows: Vec::new(), pseudosections: Vec::new() }
     }
 
     /// Adds a row to the table, containing the data in the given answer in
-    /// the right section.
+    /// the right section. OPT records do not get a row, as they get shown
+    /// in a pseudosection of their own above the table instead.
     pub fn add_row(&mut self, answer: Answer, section: Section) {
         match answer {
             Answer::Standard { record, qname, ttl, .. } => {
                 let qtype = self.coloured_record_type(&record);
                 let summary = self.text_format.record_payload_summary(&record);
-                let ttl = Some(self.text_format.format_duration(ttl));
-                self.rows.push(Row { qtype, qname, ttl, summary, section });
+                let ttl = Some(self.text_format.format_ttl(ttl));
+                self.rows.push(Row { qtype, qname: self.text_format.format_name(&qname), ttl, summary, section, status: None });
             }
-            Answer::Pseudo { qname, opt } => {
-                let qtype = self.colours.opt.paint("OPT");
-                let summary = self.text_format.pseudo_record_payload_summary(&opt);
-                self.rows.push(Row { qtype, qname, ttl: None, summary, section });
+            Answer::Pseudo { opt, .. } => {
+                self.pseudosections.push(opt);
             }
         }
     }
 
+    /// Adds a row to the table like `add_row`, also showing the DNSSEC
+    /// validation status of the answer.
+    pub fn add_validated_row(&mut self, answer: Answer, section: Section, status: Status) {
+        self.add_row(answer, section);
+        if let Some(row) = self.rows.last_mut() {
+            row.status = Some(status);
+        }
+    }
+
+    /// Whether no rows or pseudosections have been added to the table.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.pseudosections.is_empty()
+    }
+
     /// Prints the formatted table to stdout.
     pub fn print(self, duration: Option<Duration>) {
+        for opt in &self.pseudosections {
+            let mut lines = self.text_format.edns_lines(opt).into_iter();
+            if let Some(header) = lines.next() {
+                println!("{} {}", self.colours.opt.paint("EDNS:"), header);
+            }
+
+            for line in lines {
+                println!("  {}", line);
+            }
+        }
+
         if ! self.rows.is_empty() {
             let qtype_len = self.max_qtype_len();
             let qname_len = self.max_qname_len();
             let ttl_len   = self.max_ttl_len();
+            let status_len = self.max_status_len();
 
             for r in &self.rows {
                 for _ in 0 .. qtype_len - r.qtype.len() {
@@ -84,7 +113,7 @@ impl Table {
 
                 print!("{} {} ", r.qtype, self.colours.qname.paint(&r.qname));
 
-                for _ in 0 .. qname_len - r.qname.len() {
+                for _ in 0 .. qname_len - r.qname.chars().count() {
                     print!(" ");
                 }
 
@@ -101,15 +130,26 @@ impl Table {
                     }
                 }
 
-                println!(" {} {}", self.format_section(r.section), r.summary);
+                print!(" {} ", self.format_section(r.section));
+
+                if status_len > 0 {
+                    let status = r.status.map(status_text).unwrap_or_default();
+                    print!("{} ", self.format_status(r.status, &status));
+
+                    for _ in 0 .. status_len - status.len() {
+                        print!(" ");
+                    }
+                }
+
+                println!("{}", r.summary);
             }
         }
         else {
-            println!("No results");
+            println!("{}", self.text_format.catalog.message(Message::NoResults));
         }
 
         if let Some(dur) = duration {
-            println!("Ran in {}ms", dur.as_millis());
+            println!("{}", self.text_format.catalog.format(Message::RanIn, &[ &dur.as_millis() ]));
         }
     }
 
@@ -119,13 +159,17 @@ impl Table {
             Record::AAAA(_)   => self.colours.aaaa.paint("AAAA"),
             Record::CAA(_)    => self.colours.caa.paint("CAA"),
             Record::CNAME(_)  => self.colours.cname.paint("CNAME"),
+            Record::DNSKEY(_) => self.colours.dnskey.paint("DNSKEY"),
+            Record::DS(_)     => self.colours.ds.paint("DS"),
             Record::MX(_)     => self.colours.mx.paint("MX"),
             Record::NS(_)     => self.colours.ns.paint("NS"),
             Record::PTR(_)    => self.colours.ptr.paint("PTR"),
+            Record::RRSIG(_)  => self.colours.rrsig.paint("RRSIG"),
             Record::SOA(_)    => self.colours.soa.paint("SOA"),
             Record::SRV(_)    => self.colours.srv.paint("SRV"),
             Record::TXT(_)    => self.colours.txt.paint("TXT"),
 
+            Record::Custom(ref custom)             => self.colours.unknown.paint(custom.type_name()),
             Record::Other { ref type_number, .. } => self.colours.unknown.paint(type_number.to_string()),
         }
     }
@@ -135,13 +179,26 @@ impl Table {
     }
 
     fn max_qname_len(&self) -> usize {
-        self.rows.iter().map(|r| r.qname.len()).max().unwrap()
+        self.rows.iter().map(|r| r.qname.chars().count()).max().unwrap()
     }
 
     fn max_ttl_len(&self) -> usize {
         self.rows.iter().map(|r| r.ttl.as_ref().map_or(0, |e| e.len())).max().unwrap()
     }
 
+    fn max_status_len(&self) -> usize {
+        self.rows.iter().map(|r| r.status.map_or(0, |e| status_text(e).len())).max().unwrap()
+    }
+
+    fn format_status(&self, status: Option<Status>, text: &str) -> ANSIString<'static> {
+        match status {
+            Some(Status::Secure)     => self.colours.secure.paint(text.to_string()),
+            Some(Status::Insecure)   => self.colours.insecure.paint(text.to_string()),
+            Some(Status::Bogus(_))   => self.colours.bogus.paint(text.to_string()),
+            None                     => ANSIString::from(text.to_string()),
+        }
+    }
+
     fn format_section(&self, section: Section) -> ANSIString<'static> {
         match section {
             Section::Answer      => self.colours.answer.paint(" "),
@@ -150,3 +207,13 @@ impl Table {
         }
     }
 }
+
+
+/// The text displayed for a validation status, including the reason an
+/// answer was considered bogus.
+fn status_text(status: Status) -> String {
+    match status {
+        Status::Bogus(problem)  => format!("{} ({})", status, problem),
+        _                       => status.to_string(),
+    }
+}
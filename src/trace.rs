@@ -0,0 +1,363 @@
+//! Iterative resolution, following referrals down from the root servers.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use log::*;
+
+use dns::{Response, Answer, Name, QClass, TypeInt, qtype};
+use dns::record::{Record, A};
+use dns_transport::{Runtime, Error as TransportError};
+
+use crate::connect::TransportType;
+use crate::requests::RequestGenerator;
+
+
+/// A **tracer** resolves queries iteratively, the way a recursive resolver
+/// would: it asks a root server first, then follows each referral down the
+/// delegation tree until it reaches a server that gives an actual answer.
+pub struct Tracer<'rt> {
+    runtime: &'rt mut Runtime,
+    requests: &'rt RequestGenerator,
+    transport_type: TransportType,
+}
+
+/// The complete path taken to resolve one query.
+#[derive(Debug)]
+pub struct Trace {
+
+    /// Every response received, in order, starting from the root.
+    pub steps: Vec<Step>,
+
+    /// The reason the trace stopped before reaching an answer, if it did.
+    pub error: Option<TraceError>,
+}
+
+/// One step down the delegation tree.
+#[derive(Debug)]
+pub struct Step {
+
+    /// The zone that the queried nameserver is meant to be authoritative for.
+    pub zone: Name,
+
+    /// The host name of the nameserver that was queried.
+    pub nameserver: Name,
+
+    /// The address the nameserver was contacted on.
+    pub address: Ipv4Addr,
+
+    /// The response the nameserver sent back, which is either a referral to
+    /// the next zone down, or the final answer.
+    pub response: Response,
+
+    /// The response exactly as it was received, for the hex and raw output.
+    pub bytes: Vec<u8>,
+}
+
+/// Something that can go wrong while following referrals.
+#[derive(Debug)]
+pub enum TraceError {
+
+    /// None of the nameservers for a zone could be reached. Contains the
+    /// error from the last nameserver tried.
+    Transport(TransportError),
+
+    /// None of the nameservers for the given zone had an address that
+    /// could be found.
+    NoAddresses(Name),
+
+    /// A nameserver referred us to a zone that is not below the one it was
+    /// meant to be authoritative for, or that does not contain the domain.
+    BadReferral(Name),
+
+    /// The delegation chain was longer than we are willing to follow.
+    TooManySteps,
+}
+
+/// The most referrals to follow before assuming we are going round in
+/// circles.
+const MAX_STEPS: usize = 32;
+
+/// How many nameserver host names without glue records to look up, one
+/// within the other, before giving up.
+const MAX_GLUELESS_DEPTH: usize = 4;
+
+
+impl<'rt> Tracer<'rt> {
+
+    /// Creates a new tracer that sends its requests over the given runtime
+    /// and transport type, using the request settings from the user.
+    pub fn new(runtime: &'rt mut Runtime, requests: &'rt RequestGenerator, transport_type: TransportType) -> Self {
+        Self { runtime, requests, transport_type }
+    }
+
+    /// Follows the delegation path for the given query, starting from the
+    /// root servers, returning every step that was taken.
+    pub fn trace(&mut self, domain: &Name, qtype: TypeInt, qclass: QClass) -> Trace {
+        self.trace_at_depth(domain, qtype, qclass, 0)
+    }
+
+    fn trace_at_depth(&mut self, domain: &Name, qtype: TypeInt, qclass: QClass, depth: usize) -> Trace {
+        let mut steps = Vec::new();
+        let mut zone = Name::root();
+        let mut nameservers = root_hints();
+
+        for _ in 0 .. MAX_STEPS {
+            let step = match self.query_zone(&zone, &nameservers, domain, qtype, qclass, depth) {
+                Ok(step)  => step,
+                Err(e)    => return Trace { steps, error: Some(e) },
+            };
+
+            let referral = Referral::from_response(&step.response);
+            steps.push(step);
+
+            let referral = match referral {
+                Some(r)  => r,
+                None     => return Trace { steps, error: None },
+            };
+
+            if ! is_strictly_below(&referral.zone, &zone) || ! domain.is_below_or_at(&referral.zone) {
+                warn!("Zone {:?} referred us to {:?}", zone, referral.zone);
+                return Trace { steps, error: Some(TraceError::BadReferral(referral.zone)) };
+            }
+
+            debug!("Following referral from {:?} to {:?}", zone, referral.zone);
+            zone = referral.zone;
+            nameservers = referral.nameservers;
+        }
+
+        Trace { steps, error: Some(TraceError::TooManySteps) }
+    }
+
+    /// Sends the query to each of the nameservers for a zone in turn,
+    /// returning the first response that arrives.
+    fn query_zone(&mut self, zone: &Name, nameservers: &[Nameserver], domain: &Name, qtype: TypeInt, qclass: QClass, depth: usize) -> Result<Step, TraceError> {
+        let mut last_error = None;
+
+        for ns in nameservers {
+            let address = match ns.address {
+                Some(address)  => address,
+                None           => match self.look_up_address(&ns.name, depth + 1) {
+                    Some(address)  => address,
+                    None           => continue,
+                },
+            };
+
+            info!("Querying {:?} ({}) for zone {:?}", ns.name, address, zone);
+            match self.query_server(address, domain, qtype, qclass) {
+                Ok(reply) => {
+                    let dns_transport::Reply { response, bytes, .. } = reply;
+                    let zone = zone.clone();
+                    let nameserver = ns.name.clone();
+                    return Ok(Step { zone, nameserver, address, response, bytes });
+                }
+                Err(e) => {
+                    warn!("Nameserver {:?} ({}) failed: {:?}", ns.name, address, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            Some(e)  => Err(TraceError::Transport(e)),
+            None     => Err(TraceError::NoAddresses(zone.clone())),
+        }
+    }
+
+    /// Sends a single query, without recursion, to the nameserver at the
+    /// given address.
+    pub fn query_server(&mut self, address: Ipv4Addr, domain: &Name, qtype: TypeInt, qclass: QClass) -> Result<dns_transport::Reply, TransportError> {
+        let mut request = self.requests.make_request(domain, qtype, qclass);
+        request.flags.recursion_desired = false;
+
+        let transport = self.requests.make_transport(self.transport_type, address.to_string());
+        self.runtime.block_on(async { transport.send_with_details(&request).await })
+    }
+
+    /// Finds the address of a nameserver that was referred to without any
+    /// glue, by tracing it separately from the root.
+    pub fn look_up_address(&mut self, name: &Name, depth: usize) -> Option<Ipv4Addr> {
+        if depth > MAX_GLUELESS_DEPTH {
+            warn!("Too many glueless nameservers to look up {:?}", name);
+            return None;
+        }
+
+        debug!("Looking up address of glueless nameserver {:?}", name);
+        let trace = self.trace_at_depth(name, qtype!(A), QClass::IN, depth);
+        let last_step = trace.steps.last()?;
+
+        last_step.response.answers.iter().find_map(|answer| {
+            match answer {
+                Answer::Standard { record: Record::A(a), .. }  => Some(a.address),
+                _                                               => None,
+            }
+        })
+    }
+}
+
+
+/// A nameserver that a zone has been delegated to, and its address, if we
+/// were given one as glue.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Nameserver {
+    pub name: Name,
+    pub address: Option<Ipv4Addr>,
+}
+
+/// The delegation to another zone contained in a response.
+#[derive(PartialEq, Debug)]
+pub struct Referral {
+    pub zone: Name,
+    pub nameservers: Vec<Nameserver>,
+}
+
+impl Referral {
+
+    /// Extracts a referral from the authority and additional sections of a
+    /// response, if it is one. Responses with answers or errors are final.
+    pub fn from_response(response: &Response) -> Option<Self> {
+        if ! response.answers.is_empty() || response.flags.error_code.is_some() {
+            return None;
+        }
+
+        let mut zone = None;
+        let mut nameservers = Vec::new();
+        for answer in &response.authorities {
+            if let Answer::Standard { qname, record: Record::NS(ns), .. } = answer {
+                zone.get_or_insert_with(|| qname.clone());
+                nameservers.push(Nameserver { name: ns.nameserver.clone(), address: None });
+            }
+        }
+
+        for ns in &mut nameservers {
+            ns.address = response.additionals.iter().find_map(|answer| {
+                match answer {
+                    Answer::Standard { qname, record: Record::A(a), .. } if *qname == ns.name => Some(a.address),
+                    _ => None,
+                }
+            });
+        }
+
+        // Nameservers with glue can be queried straight away, so try those
+        // before any that need to be looked up separately.
+        nameservers.sort_by_key(|ns| ns.address.is_none());
+
+        let zone = zone?;
+        Some(Self { zone, nameservers })
+    }
+}
+
+
+/// Returns the built-in list of root servers, which every trace starts from.
+fn root_hints() -> Vec<Nameserver> {
+    ROOT_HINTS.iter()
+        .map(|&(name, address)| Nameserver { name: name.parse().unwrap(), address: Some(address) })
+        .collect()
+}
+
+/// The names and IPv4 addresses of the thirteen root servers.
+///
+/// See <https://www.iana.org/domains/root/servers>
+static ROOT_HINTS: &[(&str, Ipv4Addr)] = &[
+    ("a.root-servers.net.", Ipv4Addr::new(198,  41,   0,   4)),
+    ("b.root-servers.net.", Ipv4Addr::new(170, 247, 170,   2)),
+    ("c.root-servers.net.", Ipv4Addr::new(192,  33,   4,  12)),
+    ("d.root-servers.net.", Ipv4Addr::new(199,   7,  91,  13)),
+    ("e.root-servers.net.", Ipv4Addr::new(192, 203, 230,  10)),
+    ("f.root-servers.net.", Ipv4Addr::new(192,   5,   5, 241)),
+    ("g.root-servers.net.", Ipv4Addr::new(192, 112,  36,   4)),
+    ("h.root-servers.net.", Ipv4Addr::new(198,  97, 190,  53)),
+    ("i.root-servers.net.", Ipv4Addr::new(192,  36, 148,  17)),
+    ("j.root-servers.net.", Ipv4Addr::new(192,  58, 128,  30)),
+    ("k.root-servers.net.", Ipv4Addr::new(193,   0,  14, 129)),
+    ("l.root-servers.net.", Ipv4Addr::new(199,   7,  83,  42)),
+    ("m.root-servers.net.", Ipv4Addr::new(202,  12,  27,  33)),
+];
+
+
+/// Whether the first domain is a subdomain of the second, but not the same.
+fn is_strictly_below(domain: &Name, zone: &Name) -> bool {
+    domain.is_below_or_at(zone) && domain != zone
+}
+
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e)       => write!(f, "No nameservers could be reached (last error: {:?})", e),
+            Self::NoAddresses(zone)  => write!(f, "No addresses found for any nameserver of zone {:?}", zone),
+            Self::BadReferral(zone)  => write!(f, "Nameserver gave a referral to unrelated zone {:?}", zone),
+            Self::TooManySteps       => write!(f, "Gave up after following {} referrals", MAX_STEPS),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::Flags;
+    use dns::record::NS;
+
+    fn referral_response(authorities: Vec<Answer>, additionals: Vec<Answer>) -> Response {
+        Response {
+            transaction_id: 0x1234,
+            flags: Flags::from_u16(0x8000),
+            queries: Vec::new(),
+            answers: Vec::new(),
+            authorities,
+            additionals,
+        }
+    }
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn ns(zone: &str, nameserver: &str) -> Answer {
+        let record = Record::NS(NS { nameserver: name(nameserver) });
+        Answer::Standard { qname: name(zone), qclass: QClass::IN, ttl: 172800, record }
+    }
+
+    fn glue(host: &str, address: Ipv4Addr) -> Answer {
+        let record = Record::A(A { address });
+        Answer::Standard { qname: name(host), qclass: QClass::IN, ttl: 172800, record }
+    }
+
+    #[test]
+    fn referral_with_glue() {
+        let response = referral_response(
+            vec![ ns("dog.", "ns1.nic.dog."), ns("dog.", "ns2.nic.dog.") ],
+            vec![ glue("ns2.nic.dog.", Ipv4Addr::new(10, 0, 0, 2)) ],
+        );
+
+        assert_eq!(Referral::from_response(&response), Some(Referral {
+            zone: name("dog."),
+            nameservers: vec![
+                Nameserver { name: name("ns2.nic.dog."), address: Some(Ipv4Addr::new(10, 0, 0, 2)) },
+                Nameserver { name: name("ns1.nic.dog."), address: None },
+            ],
+        }));
+    }
+
+    #[test]
+    fn answer_is_not_referral() {
+        let mut response = referral_response(vec![ ns("dog.", "ns1.nic.dog.") ], vec![]);
+        response.answers.push(glue("lookup.dog.", Ipv4Addr::new(10, 0, 0, 1)));
+
+        assert_eq!(Referral::from_response(&response), None);
+    }
+
+    #[test]
+    fn no_nameservers_is_not_referral() {
+        let response = referral_response(vec![], vec![]);
+        assert_eq!(Referral::from_response(&response), None);
+    }
+
+    #[test]
+    fn subdomains() {
+        assert!(is_strictly_below(&name("dog."), &Name::root()));
+        assert!(! is_strictly_below(&name("dog."), &name("dog")));
+    }
+}
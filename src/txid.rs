@@ -1,7 +1,24 @@
-/// A **transaction ID generator** is used to create unique ID numbers to
-/// identify each packet, as part of the DNS protocol.
+use std::fmt;
+
+use dns::{RandomTxids, SeededTxids, SequentialTxids};
+
+
+/// The **transaction IDs** for the requests dog sends, which are used to
+/// tell the responses to different packets apart, as part of the DNS
+/// protocol. This pairs the way the user picked to generate them with the
+/// `dns::TxidGenerator` that does so.
+pub struct Txids {
+
+    /// How the transaction IDs get picked.
+    pub strategy: TxidStrategy,
+
+    /// Where the transaction IDs come from.
+    source: Box<dyn dns::TxidGenerator>,
+}
+
+/// The ways transaction IDs can be picked.
 #[derive(PartialEq, Debug, Copy, Clone)]
-pub enum TxidGenerator {
+pub enum TxidStrategy {
 
     /// Generate random transaction IDs each time.
     Random,
@@ -9,13 +26,66 @@ pub enum TxidGenerator {
     /// Generate transaction IDs in a sequence, starting from the given value,
     /// wrapping around.
     Sequence(u16),
+
+    /// Generate random transaction IDs from a generator seeded with the
+    /// given value, so they are the same every time.
+    Seeded(u64),
 }
 
-impl TxidGenerator {
-    pub fn generate(self) -> u16 {
-        match self {
-            Self::Random           => rand::random(),
-            Self::Sequence(start)  => start,   // todo
-        }
+impl Txids {
+
+    /// Creates a new transaction ID generator that picks IDs in the given
+    /// way.
+    pub fn new(strategy: TxidStrategy) -> Self {
+        let source: Box<dyn dns::TxidGenerator> = match strategy {
+            TxidStrategy::Random          => Box::new(RandomTxids),
+            TxidStrategy::Sequence(start) => Box::new(SequentialTxids::new(start)),
+            TxidStrategy::Seeded(seed)    => Box::new(SeededTxids::new(seed)),
+        };
+
+        Self { strategy, source }
+    }
+
+    /// Returns the transaction ID for the next request.
+    pub fn generate(&self) -> u16 {
+        self.source.next_txid()
+    }
+}
+
+
+// Generators are compared by how they pick IDs rather than by which ones
+// they will pick next, so the options that set them up can still be
+// compared.
+
+impl PartialEq for Txids {
+    fn eq(&self, other: &Self) -> bool {
+        self.strategy == other.strategy
+    }
+}
+
+impl fmt::Debug for Txids {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Txids").field("strategy", &self.strategy).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn sequence() {
+        let txids = Txids::new(TxidStrategy::Sequence(0xFFFF));
+        assert_eq!(txids.generate(), 0xFFFF);
+        assert_eq!(txids.generate(), 0x0000);
+    }
+
+    #[test]
+    fn seeded() {
+        let first = Txids::new(TxidStrategy::Seeded(7));
+        let second = Txids::new(TxidStrategy::Seeded(7));
+        assert_eq!((first.generate(), first.generate()), (second.generate(), second.generate()));
     }
 }
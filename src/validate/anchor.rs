@@ -0,0 +1,527 @@
+//! The trust anchors that DNSSEC validation starts from, and keeping them
+//! up to date as the root zone rolls over its keys.
+//!
+//! Anchors can be loaded from a file in either of the formats IANA
+//! publishes them in: the `root-anchors.xml` document, or DS records in
+//! presentation format, one per line. When tracking is turned on, the file
+//! gets rewritten as DS records, with the state of each anchor in a comment
+//! after it.
+//!
+//! # References
+//!
+//! - [RFC 5011](https://tools.ietf.org/html/rfc5011) — Automated Updates of DNS Security (DNSSEC) Trust Anchors (September 2007)
+//! - [RFC 7958](https://tools.ietf.org/html/rfc7958) — DNSSEC Trust Anchor Publication for the Root Zone (August 2016)
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use log::*;
+
+use dns::presentation::decode_hex;
+use dns::record::{DNSKEY, DS};
+
+use super::crypto;
+
+
+/// Where to get the root zone’s trust anchors from.
+#[derive(PartialEq, Debug, Clone)]
+pub enum TrustAnchors {
+
+    /// Use the anchors built in to dog.
+    BuiltIn,
+
+    /// Load the anchors from the file at the given path.
+    File(PathBuf),
+
+    /// Load the anchors from the file at the given path, and write them
+    /// back after following any key rollovers according to RFC 5011.
+    Tracked(PathBuf),
+}
+
+/// One root zone trust anchor, and how far through the RFC 5011 process
+/// it has got.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Anchor {
+
+    /// The DS record for the key.
+    pub ds: DS,
+
+    /// Whether the key is currently trusted.
+    pub state: AnchorState,
+}
+
+/// The states an anchor can be in.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum AnchorState {
+
+    /// The key is trusted.
+    Valid,
+
+    /// The key has appeared in the root zone’s key set, but has not been
+    /// there for long enough to be trusted yet. Contains the time it was
+    /// first seen, in seconds since the Unix epoch.
+    AddPending(u64),
+
+    /// The key has been revoked by the root zone, so it is no longer
+    /// trusted.
+    Revoked,
+}
+
+/// Something that can go wrong loading trust anchors from a file.
+#[derive(Debug)]
+pub enum AnchorError {
+
+    /// The file could not be read.
+    IO(io::Error),
+
+    /// The anchor with the given number in the file, counting from one,
+    /// could not be understood.
+    Malformed(usize),
+
+    /// The file contained an anchor for a zone other than the root.
+    NotRoot(String),
+
+    /// The file did not contain any anchors that are currently trusted.
+    NoAnchors,
+}
+
+/// How long a new key has to be seen for before it becomes trusted.
+///
+/// See [RFC 5011 §2.4.1](https://tools.ietf.org/html/rfc5011#section-2.4.1).
+const ADD_HOLD_DOWN: u64 = 30 * 24 * 60 * 60;
+
+
+impl TrustAnchors {
+
+    /// Loads the anchors, either from the file or from the built-in list,
+    /// leaving out any that IANA has said are not valid at the given time.
+    pub fn load(&self, now: u64) -> Result<Vec<Anchor>, AnchorError> {
+        let anchors = match self {
+            Self::BuiltIn => {
+                built_in()
+            }
+            Self::File(path) | Self::Tracked(path) => {
+                let contents = fs::read_to_string(path).map_err(AnchorError::IO)?;
+                parse(&contents, now)?
+            }
+        };
+
+        if anchors.iter().any(|a| a.state == AnchorState::Valid) {
+            Ok(anchors)
+        }
+        else {
+            Err(AnchorError::NoAnchors)
+        }
+    }
+
+    /// Writes the anchors back to the file as DS records, if they are
+    /// being tracked.
+    pub fn save(&self, anchors: &[Anchor]) -> io::Result<()> {
+        if let Self::Tracked(path) = self {
+            let mut contents = String::from("; Root zone trust anchors, tracked according to RFC 5011\n");
+            for anchor in anchors {
+                contents.push_str(&anchor.to_string());
+                contents.push('\n');
+            }
+
+            fs::write(path, contents)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether anchors should be tracked and written back.
+    pub fn is_tracked(&self) -> bool {
+        matches!(self, Self::Tracked(_))
+    }
+}
+
+
+/// Updates the anchors based on the root zone’s keys, which have already
+/// been validated using them, along with whether each key has signed the
+/// key set itself. Returns whether anything changed.
+///
+/// New key-signing keys start out pending, and become trusted once they
+/// have been seen for the hold-down time. Keys that are revoked, and that
+/// sign the key set to prove it, stop being trusted. Pending keys that
+/// disappear before the hold-down time is up are forgotten.
+pub fn track(anchors: &mut Vec<Anchor>, keys: &[(DNSKEY, bool)], now: u64) -> bool {
+    let mut changed = false;
+
+    for (key, self_signed) in keys.iter().filter(|(k, _)| k.is_secure_entry_point()) {
+        if key.is_revoked() {
+            if ! self_signed {
+                continue;
+            }
+
+            let mut unrevoked = key.clone();
+            unrevoked.flags &= ! 0b_0000_0000_1000_0000;
+
+            for anchor in anchors.iter_mut() {
+                if anchor.state != AnchorState::Revoked && crypto::ds_matches(&anchor.ds, "", &unrevoked) {
+                    info!("Trust anchor {} has been revoked", anchor.ds.key_tag);
+                    anchor.state = AnchorState::Revoked;
+                    changed = true;
+                }
+            }
+        }
+        else if ! anchors.iter().any(|a| crypto::ds_matches(&a.ds, "", key)) {
+            if let Some(ds) = crypto::make_ds("", key) {
+                info!("Found new root key {}, pending trust", ds.key_tag);
+                anchors.push(Anchor { ds, state: AnchorState::AddPending(now) });
+                changed = true;
+            }
+        }
+    }
+
+    for anchor in anchors.iter_mut() {
+        if let AnchorState::AddPending(first_seen) = anchor.state {
+            if now.saturating_sub(first_seen) >= ADD_HOLD_DOWN {
+                info!("Root key {} is now trusted", anchor.ds.key_tag);
+                anchor.state = AnchorState::Valid;
+                changed = true;
+            }
+        }
+    }
+
+    let count = anchors.len();
+    anchors.retain(|anchor| {
+        ! matches!(anchor.state, AnchorState::AddPending(_))
+            || keys.iter().any(|(k, _)| ! k.is_revoked() && crypto::ds_matches(&anchor.ds, "", k))
+    });
+
+    changed || anchors.len() != count
+}
+
+
+/// The DS records for the root zone’s key-signing keys, as published by
+/// IANA, for when no file is given.
+///
+/// # References
+///
+/// - [IANA root anchors](https://data.iana.org/root-anchors/root-anchors.xml)
+fn built_in() -> Vec<Anchor> {
+    ROOT_ANCHORS.iter().map(|&(key_tag, algorithm, digest_type, digest)| {
+        let digest = decode_hex(digest).expect("Invalid built-in anchor");
+        let ds = DS { key_tag, algorithm, digest_type, digest };
+        Anchor { ds, state: AnchorState::Valid }
+    }).collect()
+}
+
+/// Key tag, algorithm, digest type, and digest of each root key.
+static ROOT_ANCHORS: &[(u16, u8, u8, &str)] = &[
+    (20326, 8, 2, "E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D"),
+    (38696, 8, 2, "683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16"),
+];
+
+
+/// Parses the contents of an anchor file, working out which format it is
+/// in from the first character.
+fn parse(contents: &str, now: u64) -> Result<Vec<Anchor>, AnchorError> {
+    if contents.trim_start().starts_with('<') {
+        parse_xml(contents, now)
+    }
+    else {
+        parse_ds_records(contents)
+    }
+}
+
+/// Parses IANA’s XML format, skipping any keys whose validity period does
+/// not include the given time. This only picks out the few elements it
+/// needs, rather than being a full XML parser.
+fn parse_xml(contents: &str, now: u64) -> Result<Vec<Anchor>, AnchorError> {
+    if let Some(zone) = element(contents, "Zone") {
+        if zone != "." {
+            return Err(AnchorError::NotRoot(zone.into()));
+        }
+    }
+
+    let mut anchors = Vec::new();
+    for (index, block) in contents.split("<KeyDigest").skip(1).enumerate() {
+        let number = index + 1;
+        let block = block.split("</KeyDigest>").next().unwrap_or_default();
+
+        let valid_from = attribute(block, "validFrom").map(parse_timestamp);
+        let valid_until = attribute(block, "validUntil").map(parse_timestamp);
+        if valid_from == Some(None) || valid_until == Some(None) {
+            return Err(AnchorError::Malformed(number));
+        }
+
+        if valid_from.flatten().is_some_and(|from| from > now) || valid_until.flatten().is_some_and(|until| until <= now) {
+            debug!("Skipping anchor {} as it is not currently valid", number);
+            continue;
+        }
+
+        let key_tag     = element(block, "KeyTag").and_then(|e| e.parse().ok());
+        let algorithm   = element(block, "Algorithm").and_then(|e| e.parse().ok());
+        let digest_type = element(block, "DigestType").and_then(|e| e.parse().ok());
+        let digest      = element(block, "Digest").and_then(decode_hex);
+
+        match (key_tag, algorithm, digest_type, digest) {
+            (Some(key_tag), Some(algorithm), Some(digest_type), Some(digest)) => {
+                let ds = DS { key_tag, algorithm, digest_type, digest };
+                anchors.push(Anchor { ds, state: AnchorState::Valid });
+            }
+            _ => {
+                return Err(AnchorError::Malformed(number));
+            }
+        }
+    }
+
+    Ok(anchors)
+}
+
+/// Parses DS records in presentation format, one per line, with an
+/// optional state comment after each one.
+fn parse_ds_records(contents: &str) -> Result<Vec<Anchor>, AnchorError> {
+    let mut anchors = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let (record, comment) = match line.split_once(';') {
+            Some((record, comment))  => (record, comment),
+            None                     => (line, ""),
+        };
+
+        let fields = record.split_whitespace().collect::<Vec<_>>();
+        let owner = match fields.first() {
+            Some(owner)  => *owner,
+            None         => continue,
+        };
+
+        if owner != "." {
+            return Err(AnchorError::NotRoot(owner.into()));
+        }
+
+        let ds_index = fields.iter().position(|f| f.eq_ignore_ascii_case("DS")).ok_or(AnchorError::Malformed(number))?;
+        let data = &fields[ds_index + 1 ..];
+        if data.len() < 4 {
+            return Err(AnchorError::Malformed(number));
+        }
+
+        let key_tag     = data[0].parse().map_err(|_| AnchorError::Malformed(number))?;
+        let algorithm   = data[1].parse().map_err(|_| AnchorError::Malformed(number))?;
+        let digest_type = data[2].parse().map_err(|_| AnchorError::Malformed(number))?;
+        let digest      = decode_hex(&data[3 ..].concat()).ok_or(AnchorError::Malformed(number))?;
+
+        let ds = DS { key_tag, algorithm, digest_type, digest };
+        let state = parse_state(comment).ok_or(AnchorError::Malformed(number))?;
+        anchors.push(Anchor { ds, state });
+    }
+
+    Ok(anchors)
+}
+
+/// Parses the state comment written after an anchor, such as
+/// `state=addpend first_seen=1600000000`. Anchors without a state are
+/// trusted.
+fn parse_state(comment: &str) -> Option<AnchorState> {
+    let mut state = "valid";
+    let mut first_seen = None;
+
+    for field in comment.split_whitespace() {
+        match field.split_once('=') {
+            Some(("state", value))       => state = value,
+            Some(("first_seen", value))  => first_seen = Some(value.parse().ok()?),
+            _                            => {},
+        }
+    }
+
+    match state {
+        "valid"    => Some(AnchorState::Valid),
+        "addpend"  => Some(AnchorState::AddPending(first_seen?)),
+        "revoked"  => Some(AnchorState::Revoked),
+        _          => None,
+    }
+}
+
+/// Finds the trimmed text inside the first element with the given name.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = xml[start ..].find(&format!("</{}>", name))? + start;
+    Some(xml[start .. end].trim())
+}
+
+/// Finds the value of an attribute in the opening tag at the start of
+/// the given XML.
+fn attribute<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let tag = &xml[.. xml.find('>')?];
+    let start = tag.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = tag[start ..].find('"')? + start;
+    Some(&tag[start .. end])
+}
+
+/// Parses an XML timestamp such as `2017-02-02T00:00:00+00:00` into seconds
+/// since the Unix epoch. IANA always uses UTC, so the offset is ignored.
+fn parse_timestamp(input: &str) -> Option<u64> {
+    let date = input.get(0 .. 10)?;
+    let time = input.get(11 .. 19).unwrap_or("00:00:00");
+
+    let mut date_parts = date.split('-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+
+    let mut time_parts = time.split(':').map(|p| p.parse::<i64>().ok());
+    let (hour, minute, second) = (time_parts.next()??, time_parts.next()??, time_parts.next()??);
+
+    // Days since the epoch from a civil date, after Howard Hinnant’s
+    // algorithm, which treats March as the start of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+
+impl fmt::Display for Anchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digest = self.ds.digest.iter().map(|b| format!("{:02X}", b)).collect::<String>();
+        write!(f, ". IN DS {} {} {} {} ; ", self.ds.key_tag, self.ds.algorithm, self.ds.digest_type, digest)?;
+
+        match self.state {
+            AnchorState::Valid                 => write!(f, "state=valid"),
+            AnchorState::AddPending(seen)      => write!(f, "state=addpend first_seen={}", seen),
+            AnchorState::Revoked               => write!(f, "state=revoked"),
+        }
+    }
+}
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(e)          => write!(f, "Could not read trust anchors: {}", e),
+            Self::Malformed(n)   => write!(f, "Invalid trust anchor #{}", n),
+            Self::NotRoot(zone)  => write!(f, "Trust anchor for {:?} is not for the root zone", zone),
+            Self::NoAnchors      => write!(f, "No trusted anchors were found"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrustAnchor id="0" source="http://data.iana.org/root-anchors/root-anchors.xml">
+<Zone>.</Zone>
+<KeyDigest id="Kjqmt7v" validFrom="2010-07-15T00:00:00+00:00" validUntil="2019-01-11T00:00:00+00:00">
+<KeyTag>19036</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5</Digest>
+</KeyDigest>
+<KeyDigest id="Klajeyz" validFrom="2017-02-02T00:00:00+00:00">
+<KeyTag>20326</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D</Digest>
+</KeyDigest>
+</TrustAnchor>"#;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn key(flags: u16) -> DNSKEY {
+        DNSKEY { flags, protocol: 3, algorithm: 8, public_key: vec![ 3, 1, 0, 1, 0xAB, 0xCD ] }
+    }
+
+    #[test]
+    fn built_in_anchors() {
+        let anchors = built_in();
+        assert_eq!(anchors.len(), 2);
+        assert_eq!(&anchors[0].ds.digest[.. 2], &[ 0xE0, 0x6D ]);
+    }
+
+    #[test]
+    fn xml_skips_expired() {
+        let anchors = parse(XML, NOW).unwrap();
+        assert_eq!(anchors.len(), 1);
+        assert_eq!(anchors[0].ds.key_tag, 20326);
+        assert_eq!(anchors[0].state, AnchorState::Valid);
+    }
+
+    #[test]
+    fn xml_before_expiry() {
+        let anchors = parse(XML, 1_400_000_000).unwrap();
+        assert_eq!(anchors.len(), 1);
+        assert_eq!(anchors[0].ds.key_tag, 19036);
+    }
+
+    #[test]
+    fn ds_records() {
+        let contents = "; comment\n. 172800 IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D084 58E880409BBC683457104237C7F8EC8D\n\n";
+        let anchors = parse(contents, NOW).unwrap();
+        assert_eq!(anchors, built_in()[.. 1].to_vec());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut anchors = built_in();
+        anchors[1].state = AnchorState::AddPending(NOW);
+        let contents = anchors.iter().map(|a| format!("{}\n", a)).collect::<String>();
+        assert_eq!(parse(&contents, NOW).unwrap(), anchors);
+    }
+
+    #[test]
+    fn not_the_root() {
+        assert!(matches!(parse("lookup.dog. IN DS 1 8 2 AB", NOW), Err(AnchorError::NotRoot(_))));
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(matches!(parse(". IN DS 1 8 2 XYZ", NOW), Err(AnchorError::Malformed(1))));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00+00:00"), Some(0));
+        assert_eq!(parse_timestamp("2017-02-02T00:00:00+00:00"), Some(1_485_993_600));
+        assert_eq!(parse_timestamp("nonsense"), None);
+    }
+
+    #[test]
+    fn new_key_is_pending() {
+        let mut anchors = built_in();
+        assert!(track(&mut anchors, &[ (key(257), true) ], NOW));
+        assert_eq!(anchors.len(), 3);
+        assert_eq!(anchors[2].state, AnchorState::AddPending(NOW));
+        assert!(! track(&mut anchors, &[ (key(257), true) ], NOW + 60));
+    }
+
+    #[test]
+    fn pending_key_becomes_trusted() {
+        let mut anchors = built_in();
+        track(&mut anchors, &[ (key(257), true) ], NOW);
+        assert!(track(&mut anchors, &[ (key(257), true) ], NOW + ADD_HOLD_DOWN));
+        assert_eq!(anchors[2].state, AnchorState::Valid);
+    }
+
+    #[test]
+    fn pending_key_disappears() {
+        let mut anchors = built_in();
+        track(&mut anchors, &[ (key(257), true) ], NOW);
+        assert!(track(&mut anchors, &[], NOW + 60));
+        assert_eq!(anchors, built_in());
+    }
+
+    #[test]
+    fn zone_signing_keys_ignored() {
+        let mut anchors = built_in();
+        assert!(! track(&mut anchors, &[ (key(256), true) ], NOW));
+    }
+
+    #[test]
+    fn revoked_key() {
+        let mut anchors = vec![ Anchor { ds: crypto::make_ds("", &key(257)).unwrap(), state: AnchorState::Valid } ];
+        assert!(! track(&mut anchors, &[ (key(257 | 128), false) ], NOW));
+        assert!(track(&mut anchors, &[ (key(257 | 128), true) ], NOW));
+        assert_eq!(anchors[0].state, AnchorState::Revoked);
+    }
+}
@@ -0,0 +1,179 @@
+//! Encoding records in the canonical form that DNSSEC signatures are
+//! calculated over.
+//!
+//! # References
+//!
+//! - [RFC 4034 §6](https://tools.ietf.org/html/rfc4034#section-6) — Canonical Form and Order of Resource Records (March 2005)
+
+use dns::QClass;
+use dns::record::{Record, RRSIG};
+
+
+/// Appends a domain name in its canonical form: uncompressed, with every
+/// letter in lowercase.
+pub fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|l| ! l.is_empty()) {
+        bytes.push(label.len() as u8);
+        bytes.extend(label.to_ascii_lowercase().as_bytes());
+    }
+
+    bytes.push(0);
+}
+
+/// Counts the labels in a domain name, not including the root or a leading
+/// wildcard label, as compared with the labels field of an RRSIG record.
+pub fn label_count(name: &str) -> u8 {
+    name.split('.')
+        .filter(|l| ! l.is_empty())
+        .enumerate()
+        .filter(|(index, label)| ! (*index == 0 && *label == "*"))
+        .count() as u8
+}
+
+/// Serialises a record’s data in its canonical form, with the domain names
+/// of those types listed in RFC 4034 in lowercase.
+pub fn rdata(record: &Record) -> Vec<u8> {
+    let mut record = record.clone();
+
+    match &mut record {
+        Record::CNAME(cname)  => cname.domain.make_ascii_lowercase(),
+        Record::MX(mx)        => mx.exchange.make_ascii_lowercase(),
+        Record::NS(ns)        => ns.nameserver.make_ascii_lowercase(),
+        Record::PTR(ptr)      => ptr.cname.make_ascii_lowercase(),
+        Record::RRSIG(rrsig)  => rrsig.signer_name.make_ascii_lowercase(),
+        Record::SRV(srv)      => srv.target.make_ascii_lowercase(),
+        Record::SOA(soa)      => {
+            soa.mname.make_ascii_lowercase();
+            soa.rname.make_ascii_lowercase();
+        }
+        _ => {}
+    }
+
+    // Records with names too long to be written could not have been read
+    // in the first place, so there is nothing to verify for them.
+    let mut bytes = Vec::new();
+    if record.write(&mut bytes).is_err() {
+        bytes.clear();
+    }
+
+    bytes
+}
+
+/// Serialises the fields of an RRSIG record that come before the signature,
+/// which form the start of the data that the signature covers.
+pub fn rrsig_header(rrsig: &RRSIG) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(&rrsig.type_covered.to_be_bytes());
+    bytes.push(rrsig.algorithm);
+    bytes.push(rrsig.labels);
+    bytes.extend(&rrsig.original_ttl.to_be_bytes());
+    bytes.extend(&rrsig.expiration.to_be_bytes());
+    bytes.extend(&rrsig.inception.to_be_bytes());
+    bytes.extend(&rrsig.key_tag.to_be_bytes());
+    write_name(&mut bytes, rrsig.signer_name.as_str());
+    bytes
+}
+
+/// Builds the data that a signature over a set of records is calculated
+/// from: the RRSIG header, followed by each record in canonical order.
+///
+/// If the signature covers fewer labels than the owner name has, the
+/// records were synthesised from a wildcard, so the owner name gets
+/// replaced with the wildcard name it was expanded from.
+pub fn signed_data(rrsig: &RRSIG, owner: &str, qclass: QClass, records: &[&Record]) -> Vec<u8> {
+    let mut owner_bytes = Vec::new();
+    let labels = owner.split('.').filter(|l| ! l.is_empty()).collect::<Vec<_>>();
+    if usize::from(rrsig.labels) < labels.len() {
+        let closest = labels[labels.len() - usize::from(rrsig.labels) ..].join(".");
+        write_name(&mut owner_bytes, &format!("*.{}", closest));
+    }
+    else {
+        write_name(&mut owner_bytes, owner);
+    }
+
+    let mut rdatas = records.iter().map(|r| rdata(r)).collect::<Vec<_>>();
+    rdatas.sort();
+    rdatas.dedup();
+
+    let mut bytes = rrsig_header(rrsig);
+    for rdata in rdatas {
+        bytes.extend(&owner_bytes);
+        bytes.extend(&rrsig.type_covered.to_be_bytes());
+        bytes.extend(&qclass.to_u16().to_be_bytes());
+        bytes.extend(&rrsig.original_ttl.to_be_bytes());
+        bytes.extend(&(rdata.len() as u16).to_be_bytes());
+        bytes.extend(rdata);
+    }
+
+    bytes
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::record::{A, NS};
+
+    fn rrsig(labels: u8) -> RRSIG {
+        RRSIG {
+            type_covered: 1,
+            algorithm: 13,
+            labels,
+            original_ttl: 300,
+            expiration: 2,
+            inception: 1,
+            key_tag: 0x1234,
+            signer_name: "Lookup.Dog.".parse().unwrap(),
+            signature: vec![ 0xFF ],
+        }
+    }
+
+    #[test]
+    fn names_are_lowercased() {
+        let mut bytes = Vec::new();
+        write_name(&mut bytes, "DNS.Lookup.dog.");
+        assert_eq!(bytes, b"\x03dns\x06lookup\x03dog\x00".to_vec());
+    }
+
+    #[test]
+    fn root_name() {
+        let mut bytes = Vec::new();
+        write_name(&mut bytes, ".");
+        assert_eq!(bytes, vec![ 0 ]);
+    }
+
+    #[test]
+    fn counting_labels() {
+        assert_eq!(label_count("."), 0);
+        assert_eq!(label_count("lookup.dog."), 2);
+        assert_eq!(label_count("*.lookup.dog."), 2);
+    }
+
+    #[test]
+    fn name_rdata_is_lowercased() {
+        let record = Record::NS(NS { nameserver: "NS1.Lookup.Dog.".parse().unwrap() });
+        assert_eq!(rdata(&record), b"\x03ns1\x06lookup\x03dog\x00".to_vec());
+    }
+
+    #[test]
+    fn records_are_sorted_and_deduplicated() {
+        let one = Record::A(A { address: Ipv4Addr::new(10, 0, 0, 2) });
+        let two = Record::A(A { address: Ipv4Addr::new(10, 0, 0, 1) });
+        let data = signed_data(&rrsig(2), "lookup.dog.", QClass::IN, &[ &one, &two, &one ]);
+
+        let header_len = 18 + 12;
+        let record_len = 12 + 10 + 4;
+        assert_eq!(data.len(), header_len + record_len * 2);
+        assert_eq!(&data[header_len + 22 .. header_len + 26], &[ 10, 0, 0, 1 ]);
+        assert_eq!(&data[header_len + record_len + 22 ..], &[ 10, 0, 0, 2 ]);
+    }
+
+    #[test]
+    fn wildcard_owner() {
+        let record = Record::A(A { address: Ipv4Addr::new(10, 0, 0, 1) });
+        let data = signed_data(&rrsig(2), "anything.lookup.dog.", QClass::IN, &[ &record ]);
+        assert_eq!(&data[30 .. 44], b"\x01*\x06lookup\x03dog\x00");
+    }
+}
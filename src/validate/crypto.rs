@@ -0,0 +1,279 @@
+//! Verifying DNSSEC signatures and DS digests with OpenSSL.
+//!
+//! # References
+//!
+//! - [RFC 3110](https://tools.ietf.org/html/rfc3110) — RSA/SHA-1 SIGs and RSA KEYs in the DNS (May 2001)
+//! - [RFC 5702](https://tools.ietf.org/html/rfc5702) — Use of SHA-2 Algorithms with RSA in DNSKEY and RRSIG Resource Records (October 2009)
+//! - [RFC 6605](https://tools.ietf.org/html/rfc6605) — Elliptic Curve Digital Signature Algorithm (DSA) for DNSSEC (April 2012)
+//! - [RFC 8080](https://tools.ietf.org/html/rfc8080) — Edwards-Curve Digital Security Algorithm (EdDSA) for DNSSEC (February 2017)
+
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+
+use dns::record::{DNSKEY, DS};
+
+use super::canonical;
+
+
+/// Whether signatures made with the given DNSSEC algorithm number can be
+/// verified. Records signed with any other algorithm are treated as
+/// though they were unsigned.
+pub fn supports_algorithm(algorithm: u8) -> bool {
+    matches!(algorithm, 5 | 7 | 8 | 10 | 13 | 14 | 15)
+}
+
+/// Whether DS records with the given digest type number can be checked.
+pub fn supports_digest(digest_type: u8) -> bool {
+    matches!(digest_type, 1 | 2 | 4)
+}
+
+/// Checks whether the signature over the given data was made by the
+/// private half of the given key. Malformed keys and signatures count as
+/// failed verifications.
+pub fn verify_signature(key: &DNSKEY, data: &[u8], signature: &[u8]) -> bool {
+    let result = match key.algorithm {
+        5 | 7  => verify_rsa(&key.public_key, MessageDigest::sha1(), data, signature),
+        8      => verify_rsa(&key.public_key, MessageDigest::sha256(), data, signature),
+        10     => verify_rsa(&key.public_key, MessageDigest::sha512(), data, signature),
+        13     => verify_ecdsa(&key.public_key, Nid::X9_62_PRIME256V1, MessageDigest::sha256(), data, signature),
+        14     => verify_ecdsa(&key.public_key, Nid::SECP384R1, MessageDigest::sha384(), data, signature),
+        15     => verify_ed25519(&key.public_key, data, signature),
+        _      => Ok(false),
+    };
+
+    result.unwrap_or(false)
+}
+
+/// Checks whether a DS record refers to the given key, by hashing the
+/// key’s owner name and data and comparing it with the DS digest.
+pub fn ds_matches(ds: &DS, owner: &str, key: &DNSKEY) -> bool {
+    if ds.key_tag != key.key_tag() || ds.algorithm != key.algorithm {
+        return false;
+    }
+
+    match key_digest(owner, key, ds.digest_type) {
+        Some(digest)  => digest == ds.digest,
+        None          => false,
+    }
+}
+
+/// Creates the SHA-256 DS record that a parent zone would publish for the
+/// given key.
+pub fn make_ds(owner: &str, key: &DNSKEY) -> Option<DS> {
+    let digest = key_digest(owner, key, 2)?;
+    Some(DS { key_tag: key.key_tag(), algorithm: key.algorithm, digest_type: 2, digest })
+}
+
+/// Hashes a name the way NSEC3 records do, with SHA-1 applied over the
+/// name and salt, then over each previous hash and the salt again for the
+/// given number of extra iterations (RFC 5155 §5).
+pub fn nsec3_hash(name: &str, salt: &[u8], iterations: u16) -> Option<Vec<u8>> {
+    let mut hashed = Vec::new();
+    canonical::write_name(&mut hashed, name);
+
+    for _ in 0 ..= iterations {
+        hashed.extend(salt);
+        hashed = hash(MessageDigest::sha1(), &hashed).ok()?.to_vec();
+    }
+
+    Some(hashed)
+}
+
+/// Hashes a key’s owner name and data with the given DS digest type.
+fn key_digest(owner: &str, key: &DNSKEY, digest_type: u8) -> Option<Vec<u8>> {
+    let digest = match digest_type {
+        1  => MessageDigest::sha1(),
+        2  => MessageDigest::sha256(),
+        4  => MessageDigest::sha384(),
+        _  => return None,
+    };
+
+    let mut data = Vec::new();
+    canonical::write_name(&mut data, owner);
+    data.extend(key.rdata());
+
+    hash(digest, &data).ok().map(|hashed| hashed.to_vec())
+}
+
+
+/// RSA public keys are stored as the exponent’s length, the exponent,
+/// and then the modulus. The length takes up one byte, unless that byte is
+/// zero, in which case it takes up the next two.
+fn verify_rsa(public_key: &[u8], digest: MessageDigest, data: &[u8], signature: &[u8]) -> Result<bool, ErrorStack> {
+    let (exponent_len, rest) = match public_key {
+        [0, one, two, rest @ ..]  => (usize::from(u16::from_be_bytes([*one, *two])), rest),
+        [len, rest @ ..]          => (usize::from(*len), rest),
+        []                        => return Ok(false),
+    };
+
+    if exponent_len == 0 || rest.len() <= exponent_len {
+        return Ok(false);
+    }
+
+    let exponent = BigNum::from_slice(&rest[.. exponent_len])?;
+    let modulus  = BigNum::from_slice(&rest[exponent_len ..])?;
+    let pkey = PKey::from_rsa(Rsa::from_public_components(modulus, exponent)?)?;
+
+    let mut verifier = Verifier::new(digest, &pkey)?;
+    verifier.update(data)?;
+    verifier.verify(signature)
+}
+
+/// ECDSA public keys are stored as the two coordinates of the point, one
+/// after the other, and signatures as the two integers R and S. OpenSSL
+/// wants the signature DER-encoded instead.
+fn verify_ecdsa(public_key: &[u8], curve: Nid, digest: MessageDigest, data: &[u8], signature: &[u8]) -> Result<bool, ErrorStack> {
+    let half = public_key.len() / 2;
+    if public_key.is_empty() || ! public_key.len().is_multiple_of(2) || signature.len() != public_key.len() {
+        return Ok(false);
+    }
+
+    let group = EcGroup::from_curve_name(curve)?;
+    let x = BigNum::from_slice(&public_key[.. half])?;
+    let y = BigNum::from_slice(&public_key[half ..])?;
+    let ec_key = EcKey::<Public>::from_public_key_affine_coordinates(&group, &x, &y)?;
+    ec_key.check_key()?;
+
+    let r = BigNum::from_slice(&signature[.. half])?;
+    let s = BigNum::from_slice(&signature[half ..])?;
+    let der = EcdsaSig::from_private_components(r, s)?.to_der()?;
+
+    let pkey = PKey::from_ec_key(ec_key)?;
+    let mut verifier = Verifier::new(digest, &pkey)?;
+    verifier.update(data)?;
+    verifier.verify(&der)
+}
+
+/// Ed25519 public keys are the raw 32 bytes of the key, which get wrapped
+/// in a fixed DER prefix so OpenSSL can load them.
+fn verify_ed25519(public_key: &[u8], data: &[u8], signature: &[u8]) -> Result<bool, ErrorStack> {
+    const SPKI_PREFIX: [u8; 12] = [ 0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00 ];
+
+    if public_key.len() != 32 || signature.len() != 64 {
+        return Ok(false);
+    }
+
+    let mut der = SPKI_PREFIX.to_vec();
+    der.extend(public_key);
+    let pkey = PKey::public_key_from_der(&der)?;
+
+    let mut verifier = Verifier::new_without_digest(&pkey)?;
+    verifier.verify_oneshot(signature, data)
+}
+
+
+#[cfg(test)]
+pub(super) mod test {
+    use super::*;
+    use openssl::bn::{BigNumContext, BigNumRef};
+    use openssl::pkey::Private;
+    use openssl::sign::Signer;
+
+    fn padded(number: &BigNumRef) -> Vec<u8> {
+        let bytes = number.to_vec();
+        let mut padded = vec![ 0; 32 - bytes.len() ];
+        padded.extend(bytes);
+        padded
+    }
+
+    pub(in crate::validate) fn ecdsa_key() -> (EcKey<Private>, DNSKEY) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let private = EcKey::generate(&group).unwrap();
+
+        let mut ctx = BigNumContext::new().unwrap();
+        let mut x = BigNum::new().unwrap();
+        let mut y = BigNum::new().unwrap();
+        private.public_key().affine_coordinates_gfp(&group, &mut x, &mut y, &mut ctx).unwrap();
+
+        let mut public_key = padded(&x);
+        public_key.extend(padded(&y));
+
+        let dnskey = DNSKEY { flags: 257, protocol: 3, algorithm: 13, public_key };
+        (private, dnskey)
+    }
+
+    pub(in crate::validate) fn ecdsa_sign(private: &EcKey<Private>, data: &[u8]) -> Vec<u8> {
+        let pkey = PKey::from_ec_key(private.clone()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey).unwrap();
+        signer.update(data).unwrap();
+
+        let der = signer.sign_to_vec().unwrap();
+        let sig = EcdsaSig::from_der(&der).unwrap();
+        let mut signature = padded(sig.r());
+        signature.extend(padded(sig.s()));
+        signature
+    }
+
+    #[test]
+    fn ecdsa_valid() {
+        let (private, dnskey) = ecdsa_key();
+        let signature = ecdsa_sign(&private, b"dog");
+        assert!(verify_signature(&dnskey, b"dog", &signature));
+    }
+
+    #[test]
+    fn ecdsa_tampered() {
+        let (private, dnskey) = ecdsa_key();
+        let signature = ecdsa_sign(&private, b"dog");
+        assert!(! verify_signature(&dnskey, b"cat", &signature));
+    }
+
+    #[test]
+    fn rsa_valid() {
+        let private = Rsa::generate(1024).unwrap();
+        let mut public_key = vec![ private.e().num_bytes() as u8 ];
+        public_key.extend(private.e().to_vec());
+        public_key.extend(private.n().to_vec());
+        let dnskey = DNSKEY { flags: 256, protocol: 3, algorithm: 8, public_key };
+
+        let pkey = PKey::from_rsa(private).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey).unwrap();
+        signer.update(b"dog").unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+
+        assert!(verify_signature(&dnskey, b"dog", &signature));
+        assert!(! verify_signature(&dnskey, b"cat", &signature));
+    }
+
+    #[test]
+    fn malformed_key() {
+        let dnskey = DNSKEY { flags: 256, protocol: 3, algorithm: 8, public_key: vec![ 0, 0 ] };
+        assert!(! verify_signature(&dnskey, b"dog", &[ 1, 2, 3 ]));
+    }
+
+    #[test]
+    fn unsupported_algorithm() {
+        let dnskey = DNSKEY { flags: 256, protocol: 3, algorithm: 3, public_key: vec![ 1, 2, 3 ] };
+        assert!(! supports_algorithm(3));
+        assert!(! verify_signature(&dnskey, b"dog", &[ 1, 2, 3 ]));
+    }
+
+    #[test]
+    fn ds_digest() {
+        let (_, dnskey) = ecdsa_key();
+        let mut data = Vec::new();
+        canonical::write_name(&mut data, "lookup.dog.");
+        data.extend(dnskey.rdata());
+
+        let digest = hash(MessageDigest::sha256(), &data).unwrap().to_vec();
+        let ds = DS { key_tag: dnskey.key_tag(), algorithm: 13, digest_type: 2, digest };
+        assert!(ds_matches(&ds, "Lookup.Dog.", &dnskey));
+        assert!(! ds_matches(&ds, "other.dog.", &dnskey));
+        assert_eq!(make_ds("lookup.dog.", &dnskey), Some(ds));
+    }
+
+    #[test]
+    fn nsec3_example() {
+        // The hash of ‘example’ from RFC 5155 Appendix A.
+        let hashed = nsec3_hash("example.", &[ 0xAA, 0xBB, 0xCC, 0xDD ], 12).unwrap();
+        assert_eq!(hashed, vec![ 0x06, 0x53, 0x68, 0xab, 0xee, 0xd7, 0xec, 0x6e, 0x9f, 0xeb,
+                                 0xa9, 0x6b, 0x8c, 0x8b, 0xc3, 0xe8, 0xb7, 0x91, 0xf7, 0x16 ]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Reading the NSEC and NSEC3 records that prove there are no DS records
+//! for a delegation, which is what tells an unsigned child zone apart
+//! from one whose DS records have been stripped out of a response.
+//!
+//! dog does not decode either record type, so their data gets read here
+//! from the bytes of the unknown record.
+//!
+//! # References
+//!
+//! - [RFC 4035 §5.2](https://tools.ietf.org/html/rfc4035#section-5.2) — Authenticating Referrals (March 2005)
+//! - [RFC 5155](https://tools.ietf.org/html/rfc5155) — DNS Security (DNSSEC) Hashed Authenticated Denial of Existence (March 2008)
+
+use dns::{Answer, Name, TypeInt, qtype};
+use dns::record::{Record, CNAME, DS, NS, SOA};
+
+use super::crypto;
+
+
+/// The type number of NSEC records.
+pub const NSEC: TypeInt = 47;
+
+/// The type number of NSEC3 records.
+pub const NSEC3: TypeInt = 50;
+
+/// The only NSEC3 hash algorithm there is, SHA-1.
+const SHA1: u8 = 1;
+
+/// The NSEC3 flag that says unsigned delegations may have been skipped.
+const OPT_OUT: u8 = 0b_0000_0001;
+
+
+/// Searches the authority section of a response to a DS query for the
+/// records that prove the given name is a delegation from the given zone
+/// without any DS records. Returns the owner and type of each set of
+/// records the proof relies on, all of which have to be verified with the
+/// zone’s keys, or `None` if there is no proof.
+pub fn no_ds_proof(authorities: &[Answer], name: &Name, zone: &Name) -> Option<Vec<(Name, TypeInt)>> {
+    let nsec = other_records(authorities, NSEC)
+        .filter(|(owner, _)| *owner == name)
+        .filter_map(|(owner, bytes)| Some((owner, nsec_types(bytes)?)))
+        .find(|(_, types)| is_unsigned_delegation(types));
+
+    if let Some((owner, _)) = nsec {
+        return Some(vec![ (owner.clone(), NSEC) ]);
+    }
+
+    let nsec3s = other_records(authorities, NSEC3)
+        .filter(|(owner, _)| owner.parent().as_ref() == Some(zone))
+        .filter_map(|(owner, bytes)| Some((owner, Nsec3::read(bytes)?, base32hex(owner.labels().next()?)?)))
+        .collect::<Vec<_>>();
+
+    let (_, first, _) = nsec3s.first()?;
+    let (salt, iterations) = (first.salt.clone(), first.iterations);
+    let hash = |name: &Name| crypto::nsec3_hash(name.as_str(), &salt, iterations);
+    let nsec3s = nsec3s.into_iter().filter(|(_, nsec3, _)| nsec3.salt == salt && nsec3.iterations == iterations).collect::<Vec<_>>();
+
+    let hashed = hash(name)?;
+    if let Some((owner, _, _)) = nsec3s.iter().find(|(_, nsec3, owner_hash)| *owner_hash == hashed && is_unsigned_delegation(&nsec3.types)) {
+        return Some(vec![ ((*owner).clone(), NSEC3) ]);
+    }
+
+    // Without a record for the name itself, an opt-out record has to
+    // cover the next closer name, with another record matching the
+    // closest encloser to show where that is (RFC 5155 §8.6).
+    let mut next_closer = name.clone();
+    while let Some(encloser) = next_closer.parent().filter(|e| e.is_below_or_at(zone)) {
+        let encloser_hash = hash(&encloser)?;
+        if let Some((encloser_owner, _, _)) = nsec3s.iter().find(|(_, _, owner_hash)| *owner_hash == encloser_hash) {
+            let next_hash = hash(&next_closer)?;
+            let (covering_owner, _, _) = nsec3s.iter().find(|(_, nsec3, owner_hash)| {
+                nsec3.flags & OPT_OUT != 0 && covers(owner_hash, &nsec3.next_hash, &next_hash)
+            })?;
+
+            return Some(vec![ ((*encloser_owner).clone(), NSEC3), ((*covering_owner).clone(), NSEC3) ]);
+        }
+
+        next_closer = encloser;
+    }
+
+    None
+}
+
+/// Whether the type bitmap of a record at a name is what a parent zone
+/// has for an unsigned delegation: nameservers, but no DS records, and
+/// nothing that would only be there if the name were not a zone cut.
+fn is_unsigned_delegation(types: &[u8]) -> bool {
+    has_type(types, qtype!(NS)) && ! has_type(types, qtype!(DS)) && ! has_type(types, qtype!(SOA)) && ! has_type(types, qtype!(CNAME))
+}
+
+/// The owners and data of the records of the given unknown type.
+fn other_records<'a>(answers: &'a [Answer], rtype: TypeInt) -> impl Iterator<Item=(&'a Name, &'a [u8])> + 'a {
+    answers.iter().filter_map(move |answer| match answer {
+        Answer::Standard { qname, record: record @ Record::Other { bytes, .. }, .. } if record.type_number() == rtype => Some((qname, &bytes[..])),
+        _ => None,
+    })
+}
+
+
+/// The fields of an NSEC3 record.
+#[derive(PartialEq, Debug)]
+struct Nsec3 {
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+    next_hash: Vec<u8>,
+    types: Vec<u8>,
+}
+
+impl Nsec3 {
+
+    /// Reads the fields out of an NSEC3 record’s data, returning `None` if
+    /// it is too short or uses a hash algorithm other than SHA-1.
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let (&algorithm, bytes) = bytes.split_first()?;
+        let (&flags, bytes) = bytes.split_first()?;
+        let iterations = u16::from_be_bytes([ *bytes.first()?, *bytes.get(1)? ]);
+        let (salt, bytes) = length_prefixed(bytes.get(2 ..)?)?;
+        let (next_hash, types) = length_prefixed(bytes)?;
+
+        if algorithm != SHA1 {
+            return None;
+        }
+
+        Some(Self { flags, iterations, salt: salt.into(), next_hash: next_hash.into(), types: types.into() })
+    }
+}
+
+/// Splits off the type bitmap from an NSEC record’s data, skipping over
+/// the uncompressed next name that comes before it.
+fn nsec_types(bytes: &[u8]) -> Option<&[u8]> {
+    let mut position = 0;
+    loop {
+        let length = usize::from(*bytes.get(position)?);
+        position += 1 + length;
+
+        if length == 0 {
+            return bytes.get(position ..);
+        }
+    }
+}
+
+/// Splits a field with a one-byte length before it off the front of some
+/// record data.
+fn length_prefixed(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&length, bytes) = bytes.split_first()?;
+    let length = usize::from(length);
+    Some((bytes.get(.. length)?, bytes.get(length ..)?))
+}
+
+/// Whether the type bitmap from an NSEC or NSEC3 record has the given
+/// type in it. The bitmap is split into windows of 256 types, each one a
+/// window number, a length, and then up to 32 bytes of bits (RFC 4034
+/// §4.1.2).
+fn has_type(mut types: &[u8], rtype: TypeInt) -> bool {
+    let [ window, bit ] = rtype.to_be_bytes();
+
+    while let [ number, length, rest @ .. ] = types {
+        let length = usize::from(*length);
+        let bitmap = match rest.get(.. length) {
+            Some(bitmap)  => bitmap,
+            None          => return false,
+        };
+
+        if *number == window {
+            return bitmap.get(usize::from(bit / 8)).is_some_and(|byte| byte & (0b_1000_0000 >> (bit % 8)) != 0);
+        }
+
+        types = &rest[length ..];
+    }
+
+    false
+}
+
+/// Whether a hash comes after an NSEC3 record’s owner hash and before its
+/// next hash, going round to the start again after the last record.
+fn covers(owner_hash: &[u8], next_hash: &[u8], hash: &[u8]) -> bool {
+    if owner_hash < next_hash {
+        owner_hash < hash && hash < next_hash
+    }
+    else {
+        owner_hash < hash || hash < next_hash
+    }
+}
+
+/// Decodes the Base32 encoding with the extended hex alphabet that NSEC3
+/// owner names are written in, ignoring case, without any padding.
+fn base32hex(label: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0_u32, 0);
+
+    for c in label.chars() {
+        buffer = (buffer << 5) | c.to_digit(32)?;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use dns::QClass;
+    use dns::record::UnknownQtype;
+
+    fn other(owner: &str, rtype: TypeInt, bytes: Vec<u8>) -> Answer {
+        let record = Record::Other { type_number: UnknownQtype::from(rtype), bytes };
+        Answer::Standard { qname: owner.parse().unwrap(), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    /// A bitmap with NS, RRSIG, and NSEC in it, as for a delegation.
+    const DELEGATION: [u8; 8] = [ 0, 6, 0b_0010_0000, 0, 0, 0, 0, 0b_0000_0011 ];
+
+    /// A bitmap with NS, DS, RRSIG, and NSEC in it, as for a signed one.
+    const SIGNED_DELEGATION: [u8; 8] = [ 0, 6, 0b_0010_0000, 0, 0, 0, 0, 0b_0001_0011 ];
+
+    fn nsec(owner: &str, types: &[u8]) -> Answer {
+        let mut bytes = b"\x04next\x03dog\x00".to_vec();
+        bytes.extend(types);
+        other(owner, NSEC, bytes)
+    }
+
+    fn nsec3(name: &str, flags: u8, next_hash: &[u8], types: &[u8]) -> (Answer, Name) {
+        nsec3_at(&crypto::nsec3_hash(name, &[ 0xAB ], 1).unwrap(), flags, next_hash, types)
+    }
+
+    fn nsec3_at(owner_hash: &[u8], flags: u8, next_hash: &[u8], types: &[u8]) -> (Answer, Name) {
+        let owner = format!("{}.dog.", base32hex_encode(owner_hash));
+
+        let mut bytes = vec![ SHA1, flags, 0, 1, 1, 0xAB, next_hash.len() as u8 ];
+        bytes.extend(next_hash);
+        bytes.extend(types);
+        (other(&owner, NSEC3, bytes), owner.parse().unwrap())
+    }
+
+    fn base32hex_encode(bytes: &[u8]) -> String {
+        let bits = bytes.iter().map(|b| format!("{:08b}", b)).collect::<String>();
+        bits.as_bytes().chunks(5)
+            .map(|chunk| format!("{:0<5}", String::from_utf8_lossy(chunk)))
+            .map(|digit| std::char::from_digit(u32::from_str_radix(&digit, 2).unwrap(), 32).unwrap())
+            .collect()
+    }
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn bitmap_types() {
+        assert!(has_type(&DELEGATION, qtype!(NS)));
+        assert!(has_type(&DELEGATION, NSEC));
+        assert!(! has_type(&DELEGATION, qtype!(DS)));
+        assert!(! has_type(&DELEGATION, 256 + 2));
+    }
+
+    #[test]
+    fn base32hex_round_trip() {
+        let bytes = vec![ 0x06, 0x53, 0x68, 0xab, 0xee ];
+        assert_eq!(base32hex(&base32hex_encode(&bytes)), Some(bytes));
+        assert_eq!(base32hex("0P9M"), base32hex("0p9m"));
+        assert_eq!(base32hex("wxyz"), None);
+    }
+
+    #[test]
+    fn nsec_proof() {
+        let authorities = [ nsec("lookup.dog.", &DELEGATION) ];
+        assert_eq!(no_ds_proof(&authorities, &name("lookup.dog."), &name("dog.")),
+                   Some(vec![ (name("lookup.dog."), NSEC) ]));
+    }
+
+    #[test]
+    fn nsec_with_ds() {
+        let authorities = [ nsec("lookup.dog.", &SIGNED_DELEGATION) ];
+        assert_eq!(no_ds_proof(&authorities, &name("lookup.dog."), &name("dog.")), None);
+    }
+
+    #[test]
+    fn nsec_for_another_name() {
+        let authorities = [ nsec("other.dog.", &DELEGATION) ];
+        assert_eq!(no_ds_proof(&authorities, &name("lookup.dog."), &name("dog.")), None);
+    }
+
+    #[test]
+    fn no_records() {
+        assert_eq!(no_ds_proof(&[], &name("lookup.dog."), &name("dog.")), None);
+    }
+
+    #[test]
+    fn nsec3_match() {
+        let (record, owner) = nsec3("lookup.dog.", 0, &[ 0; 20 ], &DELEGATION);
+        assert_eq!(no_ds_proof(&[ record ], &name("lookup.dog."), &name("dog.")),
+                   Some(vec![ (owner, NSEC3) ]));
+    }
+
+    #[test]
+    fn nsec3_match_with_ds() {
+        let (record, _) = nsec3("lookup.dog.", 0, &[ 0; 20 ], &SIGNED_DELEGATION);
+        assert_eq!(no_ds_proof(&[ record ], &name("lookup.dog."), &name("dog.")), None);
+    }
+
+    #[test]
+    fn nsec3_opt_out() {
+        let (encloser, encloser_owner) = nsec3("dog.", 0, &[ 0; 20 ], &DELEGATION);
+        let (covering, covering_owner) = nsec3_at(&[ 0; 20 ], OPT_OUT, &[ 0xFF; 20 ], &DELEGATION);
+        assert_eq!(no_ds_proof(&[ encloser, covering ], &name("lookup.dog."), &name("dog.")),
+                   Some(vec![ (encloser_owner, NSEC3), (covering_owner, NSEC3) ]));
+    }
+
+    #[test]
+    fn nsec3_covering_without_opt_out() {
+        let (encloser, _) = nsec3("dog.", 0, &[ 0; 20 ], &DELEGATION);
+        let (covering, _) = nsec3_at(&[ 0; 20 ], 0, &[ 0xFF; 20 ], &DELEGATION);
+        assert_eq!(no_ds_proof(&[ encloser, covering ], &name("lookup.dog."), &name("dog.")), None);
+    }
+
+    #[test]
+    fn truncated_nsec3() {
+        assert_eq!(Nsec3::read(&[ SHA1, 0, 0, 1, 4, 0xAB ]), None);
+    }
+}
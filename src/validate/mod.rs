@@ -0,0 +1,575 @@
+//! DNSSEC validation, following the chain of trust down from the root
+//! zone’s keys to the records in a response.
+//!
+//! Starting from the built-in root trust anchor, the DS and DNSKEY records
+//! of every zone between the root and an answer’s owner name are fetched
+//! and checked in turn, and then the signatures over the answer itself are
+//! verified with the keys of the zone that signed it.
+//!
+//! Unsigned delegations are detected by a zone having no DS records, which
+//! has to be proven by NSEC or NSEC3 records signed by the parent zone;
+//! otherwise, anyone who could strip the DS records out of a response
+//! could make a signed zone look unsigned.
+//!
+//! # References
+//!
+//! - [RFC 4033](https://tools.ietf.org/html/rfc4033) — DNS Security Introduction and Requirements (March 2005)
+//! - [RFC 4035 §5](https://tools.ietf.org/html/rfc4035#section-5) — Authenticating DNS Responses (March 2005)
+//! - [RFC 5155 §8](https://tools.ietf.org/html/rfc5155#section-8) — Validator Considerations (March 2008)
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::*;
+
+use dns::{Response, Answer, Name, QClass, TypeInt, ErrorCode, qtype};
+use dns::record::{Record, DNSKEY, DS, RRSIG, SOA};
+use dns_transport::{Runtime, Transport};
+
+use crate::requests::RequestGenerator;
+
+mod anchor;
+pub use self::anchor::{TrustAnchors, Anchor, AnchorState, track};
+
+mod canonical;
+mod crypto;
+mod denial;
+
+
+/// A **validator** checks the DNSSEC signatures over the answers in a
+/// response, sending further queries for the keys it needs and
+/// remembering which zones it has already checked.
+pub struct Validator<'rt> {
+    runtime: &'rt mut Runtime,
+    requests: &'rt RequestGenerator,
+    anchors: Vec<DS>,
+    now: u32,
+    zones: HashMap<Name, Result<Zone, Problem>>,
+    root_keys: Option<Vec<(DNSKEY, bool)>>,
+}
+
+/// How much an answer can be trusted, after validation.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Status {
+
+    /// The answer’s signature was made by a key that chains all the way
+    /// up to the root trust anchor.
+    Secure,
+
+    /// The answer is in a zone that is not signed, or that is signed with
+    /// algorithms that cannot be checked.
+    Insecure,
+
+    /// The answer is in a signed zone, but its signatures could not be
+    /// verified.
+    Bogus(Problem),
+}
+
+/// The reason an answer was considered bogus.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Problem {
+
+    /// There was no signature over records in a signed zone.
+    MissingSignature,
+
+    /// The signature did not match the records.
+    InvalidSignature,
+
+    /// The current time is outside the signature’s validity period.
+    ExpiredSignature,
+
+    /// None of the zone’s keys were the one the signature was made with.
+    UnknownKey,
+
+    /// None of a zone’s keys matched the DS records in its parent zone.
+    UntrustedKeys,
+
+    /// The signature was made by a zone that the records are not in.
+    WrongSigner,
+
+    /// A signed zone delegates to a zone without any DS records, but
+    /// there were no NSEC or NSEC3 records to prove that there are none.
+    MissingDenial,
+
+    /// The records needed to validate the answer could not be fetched.
+    LookupFailed,
+}
+
+/// What the validator knows about one zone.
+#[derive(Debug, Clone)]
+enum Zone {
+
+    /// The zone with the given name has keys that chain up to the root.
+    Secure { name: Name, keys: Vec<DNSKEY> },
+
+    /// The zone, or one of its parents, is not signed.
+    Insecure,
+}
+
+
+impl<'rt> Validator<'rt> {
+
+    /// Creates a new validator that sends its requests over the given
+    /// runtime, using the request settings from the user, and trusting the
+    /// valid anchors out of those given.
+    pub fn new(runtime: &'rt mut Runtime, requests: &'rt RequestGenerator, anchors: &[Anchor]) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as u32).unwrap_or_default();
+        let anchors = anchors.iter()
+                             .filter(|a| a.state == AnchorState::Valid)
+                             .map(|a| a.ds.clone())
+                             .collect();
+
+        Self { runtime, requests, anchors, now, zones: HashMap::new(), root_keys: None }
+    }
+
+    /// The root zone’s keys, along with whether each one has signed the key
+    /// set, if they were fetched and validated against the anchors.
+    pub fn root_keys(&self) -> Option<&[(DNSKEY, bool)]> {
+        self.root_keys.as_deref()
+    }
+
+    /// Validates every answer in the response, sending any further queries
+    /// over the given transport. Returns one status for each answer, in
+    /// order, with `None` for pseudo-records.
+    pub fn validate(&mut self, transport: &(impl Transport + ?Sized), response: &Response) -> Vec<Option<Status>> {
+        let mut rrsets = HashMap::new();
+
+        response.answers.iter().map(|answer| {
+            let (owner, record) = match answer {
+                Answer::Standard { qname, record, .. }  => (qname.clone(), record),
+                Answer::Pseudo { .. }                   => return None,
+            };
+
+            // Signatures share the status of the records they cover.
+            let rtype = match record {
+                Record::RRSIG(rrsig)  => rrsig.type_covered,
+                otherwise             => otherwise.type_number(),
+            };
+
+            let key = (owner, rtype);
+            if let Some(status) = rrsets.get(&key) {
+                return Some(*status);
+            }
+
+            let status = self.rrset_status(transport, &response.answers, &key.0, rtype);
+            debug!("Records {:?} are {:?}", key, status);
+            rrsets.insert(key, status);
+            Some(status)
+        }).collect()
+    }
+
+    /// Works out the status of the set of records with the given owner and
+    /// type, using the keys of whichever zone signed them.
+    fn rrset_status(&mut self, transport: &(impl Transport + ?Sized), answers: &[Answer], owner: &Name, rtype: TypeInt) -> Status {
+        let mut signers = Vec::<Name>::new();
+        for rrsig in signatures(answers, owner, rtype) {
+            if ! signers.contains(&rrsig.signer_name) {
+                signers.push(rrsig.signer_name.clone());
+            }
+        }
+
+        if signers.is_empty() {
+            return match self.zone_for(transport, owner) {
+                Ok(Zone::Insecure)       => Status::Insecure,
+                Ok(Zone::Secure { .. })  => Status::Bogus(Problem::MissingSignature),
+                Err(problem)             => Status::Bogus(problem),
+            };
+        }
+
+        let mut problem = Problem::MissingSignature;
+        for signer in signers {
+            if ! owner.is_below_or_at(&signer) {
+                warn!("Records for {:?} were signed by {:?}", owner, signer);
+                problem = Problem::WrongSigner;
+                continue;
+            }
+
+            match self.zone_for(transport, &signer) {
+                Ok(Zone::Insecure) => {
+                    return Status::Insecure;
+                }
+                Ok(Zone::Secure { name, keys }) if name == signer => {
+                    match self.verify_rrset(answers, owner, rtype, &name, &keys) {
+                        Ok(())  => return Status::Secure,
+                        Err(p)  => problem = p,
+                    }
+                }
+                Ok(Zone::Secure { .. }) => {
+                    problem = Problem::WrongSigner;
+                }
+                Err(p) => {
+                    problem = p;
+                }
+            }
+        }
+
+        Status::Bogus(problem)
+    }
+
+    /// Finds the zone that the given name is in, checking every zone cut
+    /// between it and the root on the way down.
+    fn zone_for(&mut self, transport: &(impl Transport + ?Sized), name: &Name) -> Result<Zone, Problem> {
+        if let Some(zone) = self.zones.get(name) {
+            return zone.clone();
+        }
+
+        let result = match name.parent() {
+            None => {
+                let anchors = self.anchors.clone();
+                self.zone_keys(transport, name, &anchors)
+            }
+            Some(parent) => {
+                match self.zone_for(transport, &parent)? {
+                    Zone::Secure { name: parent_zone, keys } => self.check_cut(transport, name, &parent_zone, &keys),
+                    Zone::Insecure                           => Ok(Zone::Insecure),
+                }
+            }
+        };
+
+        self.zones.insert(name.clone(), result.clone());
+        result
+    }
+
+    /// Checks whether there is a zone cut at the given name, which is in a
+    /// signed zone. If there are DS records for it, they have to be signed
+    /// by the parent zone, and one of them has to match the child’s keys;
+    /// if there are none, the parent zone has to have signed a denial.
+    fn check_cut(&mut self, transport: &(impl Transport + ?Sized), name: &Name, parent_zone: &Name, parent_keys: &[DNSKEY]) -> Result<Zone, Problem> {
+        let ds_response = self.query(transport, name, qtype!(DS))?;
+        let ds_records = ds_response.answers.iter().filter_map(|answer| match answer {
+            Answer::Standard { qname, record: Record::DS(ds), .. } if qname == name => Some(ds.clone()),
+            _ => None,
+        }).collect::<Vec<_>>();
+
+        if ! ds_records.is_empty() {
+            self.verify_rrset(&ds_response.answers, name, qtype!(DS), parent_zone, parent_keys)?;
+            return self.zone_keys(transport, name, &ds_records);
+        }
+
+        // Without any DS records, the name is either inside the parent zone
+        // or the apex of a zone that is not signed, which only an SOA
+        // record can tell apart.
+        let response = self.query(transport, name, qtype!(SOA))?;
+        let is_apex = response.answers.iter().any(|answer| {
+            matches!(answer, Answer::Standard { qname, record: Record::SOA(_), .. } if qname == name)
+        });
+
+        if ! is_apex {
+            return Ok(Zone::Secure { name: parent_zone.clone(), keys: parent_keys.to_vec() });
+        }
+
+        let proof = match denial::no_ds_proof(&ds_response.authorities, name, parent_zone) {
+            Some(proof)  => proof,
+            None         => {
+                warn!("Zone {:?} has no DS records, and nothing to prove it", name);
+                return Err(Problem::MissingDenial);
+            }
+        };
+
+        for (owner, rtype) in proof {
+            self.verify_rrset(&ds_response.authorities, &owner, rtype, parent_zone, parent_keys)?;
+        }
+
+        debug!("Zone {:?} has no DS records, so it is insecure", name);
+        Ok(Zone::Insecure)
+    }
+
+    /// Fetches the keys for the zone with the given name, and checks that
+    /// the set of keys is signed by a key that matches one of its DS
+    /// records.
+    fn zone_keys(&mut self, transport: &(impl Transport + ?Sized), name: &Name, ds_records: &[DS]) -> Result<Zone, Problem> {
+        let usable = ds_records.iter()
+                               .filter(|ds| crypto::supports_algorithm(ds.algorithm) && crypto::supports_digest(ds.digest_type))
+                               .collect::<Vec<_>>();
+
+        if usable.is_empty() {
+            debug!("Zone {:?} uses no supported algorithms, so it is insecure", name);
+            return Ok(Zone::Insecure);
+        }
+
+        let response = self.query(transport, name, qtype!(DNSKEY))?;
+        let keys = response.answers.iter().filter_map(|answer| match answer {
+            Answer::Standard { qname, record: Record::DNSKEY(key), .. } if qname == name => Some(key.clone()),
+            _ => None,
+        }).collect::<Vec<_>>();
+
+        let trusted = keys.iter()
+                          .filter(|key| usable.iter().any(|ds| crypto::ds_matches(ds, name.as_str(), key)))
+                          .cloned()
+                          .collect::<Vec<_>>();
+
+        if trusted.is_empty() {
+            warn!("No keys for zone {:?} match its DS records", name);
+            return Err(Problem::UntrustedKeys);
+        }
+
+        self.verify_rrset(&response.answers, name, qtype!(DNSKEY), name, &trusted)?;
+
+        if name.is_root() {
+            let root_keys = keys.iter().map(|key| (key.clone(), self.is_self_signed(&response.answers, key))).collect();
+            self.root_keys = Some(root_keys);
+        }
+
+        Ok(Zone::Secure { name: name.clone(), keys })
+    }
+
+    /// Checks that the records with the given owner and type are signed by
+    /// one of the given keys belonging to the given zone.
+    fn verify_rrset(&self, answers: &[Answer], owner: &Name, rtype: TypeInt, zone: &Name, keys: &[DNSKEY]) -> Result<(), Problem> {
+        let (qclass, records) = records(answers, owner, rtype);
+        let mut problem = Problem::MissingSignature;
+
+        for rrsig in signatures(answers, owner, rtype).filter(|s| s.signer_name == *zone) {
+            if usize::from(rrsig.labels) > usize::from(canonical::label_count(owner.as_str())) {
+                problem = Problem::InvalidSignature;
+                continue;
+            }
+
+            if ! self.is_current(rrsig) {
+                problem = Problem::ExpiredSignature;
+                continue;
+            }
+
+            let data = canonical::signed_data(rrsig, owner.as_str(), qclass, &records);
+            let mut candidates = keys.iter().filter(|key| {
+                key.is_zone_key() && ! key.is_revoked() && key.protocol == 3 && key.algorithm == rrsig.algorithm && key.key_tag() == rrsig.key_tag
+            }).peekable();
+
+            if candidates.peek().is_none() {
+                problem = Problem::UnknownKey;
+                continue;
+            }
+
+            if candidates.any(|key| crypto::verify_signature(key, &data, &rrsig.signature)) {
+                return Ok(());
+            }
+
+            problem = Problem::InvalidSignature;
+        }
+
+        Err(problem)
+    }
+
+    /// Whether the root zone’s key set has been signed by the given key,
+    /// which is how a revoked key proves that it really has been revoked.
+    fn is_self_signed(&self, answers: &[Answer], key: &DNSKEY) -> bool {
+        let root = Name::root();
+        let (qclass, records) = records(answers, &root, qtype!(DNSKEY));
+
+        let signed = signatures(answers, &root, qtype!(DNSKEY))
+            .filter(|rrsig| rrsig.key_tag == key.key_tag() && rrsig.algorithm == key.algorithm && self.is_current(rrsig))
+            .any(|rrsig| crypto::verify_signature(key, &canonical::signed_data(rrsig, root.as_str(), qclass, &records), &rrsig.signature));
+
+        signed
+    }
+
+    /// Whether the current time is within the signature’s validity period,
+    /// using serial number arithmetic so the timestamps can wrap around.
+    fn is_current(&self, rrsig: &RRSIG) -> bool {
+        self.now.wrapping_sub(rrsig.inception) as i32 >= 0 && rrsig.expiration.wrapping_sub(self.now) as i32 >= 0
+    }
+
+    /// Sends a query for the records needed during validation, asking the
+    /// resolver not to do any checking of its own so bogus records still
+    /// come back.
+    fn query(&mut self, transport: &(impl Transport + ?Sized), name: &Name, qtype: TypeInt) -> Result<Response, Problem> {
+        let mut request = self.requests.make_request(name, qtype, QClass::IN);
+        request.flags.checking_disabled = true;
+
+        match self.runtime.block_on(async { transport.exchange(&request).await }) {
+            Ok(response) => {
+                match response.flags.error_code {
+                    None | Some(ErrorCode::NXDomain) => Ok(response),
+                    Some(rcode) => {
+                        warn!("Query for {:?} during validation returned {:?}", name, rcode);
+                        Err(Problem::LookupFailed)
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Query for {:?} during validation failed: {:?}", name, e);
+                Err(Problem::LookupFailed)
+            }
+        }
+    }
+}
+
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Secure        => write!(f, "secure"),
+            Self::Insecure      => write!(f, "insecure"),
+            Self::Bogus(_)      => write!(f, "bogus"),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSignature  => write!(f, "missing signature"),
+            Self::InvalidSignature  => write!(f, "invalid signature"),
+            Self::ExpiredSignature  => write!(f, "expired signature"),
+            Self::UnknownKey        => write!(f, "unknown key"),
+            Self::UntrustedKeys     => write!(f, "untrusted keys"),
+            Self::WrongSigner       => write!(f, "wrong signer"),
+            Self::MissingDenial     => write!(f, "missing denial of DS"),
+            Self::LookupFailed      => write!(f, "lookup failed"),
+        }
+    }
+}
+
+
+/// The class and records of the set with the given owner and type.
+fn records<'a>(answers: &'a [Answer], owner: &Name, rtype: TypeInt) -> (QClass, Vec<&'a Record>) {
+    let mut qclass = QClass::IN;
+    let mut records = Vec::new();
+
+    for answer in answers {
+        if let Answer::Standard { qname, qclass: class, record, .. } = answer {
+            if qname == owner && record.type_number() == rtype && ! matches!(record, Record::RRSIG(_)) {
+                qclass = *class;
+                records.push(record);
+            }
+        }
+    }
+
+    (qclass, records)
+}
+
+/// The signatures covering the set with the given owner and type.
+fn signatures<'a>(answers: &'a [Answer], owner: &'a Name, rtype: TypeInt) -> impl Iterator<Item=&'a RRSIG> + 'a {
+    answers.iter().filter_map(move |answer| match answer {
+        Answer::Standard { qname, record: Record::RRSIG(rrsig), .. } if rrsig.type_covered == rtype && qname == owner => Some(rrsig),
+        _ => None,
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use openssl::ec::EcKey;
+    use openssl::pkey::Private;
+    use dns::Request;
+    use dns::record::{A, UnknownQtype};
+    use dns::testing::response_to;
+    use dns_transport::{Reply, Protocol, Error as TransportError};
+    use crate::options::{Options, OptionsResult};
+
+    /// A resolver that answers from a table of answer and authority
+    /// sections, and sends back empty responses for anything else.
+    struct Sections {
+        sections: HashMap<(Name, TypeInt), (Vec<Answer>, Vec<Answer>)>,
+    }
+
+    #[async_trait]
+    impl Transport for Sections {
+        async fn send_with_details(&self, request: &Request) -> Result<Reply, TransportError> {
+            let query = &request.queries[0];
+            let mut response = response_to(request);
+            if let Some((answers, authorities)) = self.sections.get(&(query.qname.clone(), query.qtype)) {
+                response.answers = answers.clone();
+                response.authorities = authorities.clone();
+            }
+
+            Ok(Reply { response, bytes: Vec::new(), sent: Vec::new(), round_trip: Duration::from_millis(1), attempts: 1, protocol: Protocol::UDP, connection: Default::default() })
+        }
+    }
+
+    fn name(input: &str) -> Name {
+        input.parse().unwrap()
+    }
+
+    fn answer(owner: &str, record: Record) -> Answer {
+        Answer::Standard { qname: name(owner), qclass: QClass::IN, ttl: 300, record }
+    }
+
+    fn requests() -> RequestGenerator {
+        match Options::getopts(&[ "lookup.dog" ]) {
+            OptionsResult::Ok(options)  => options.requests,
+            otherwise                   => panic!("{:?}", otherwise),
+        }
+    }
+
+    /// Signs a set of records with the root zone’s key, returning them
+    /// followed by the signature.
+    fn signed(private: &EcKey<Private>, key: &DNSKEY, owner: &str, records: Vec<Record>) -> Vec<Answer> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        let mut rrsig = RRSIG {
+            type_covered: records[0].type_number(),
+            algorithm: 13,
+            labels: canonical::label_count(owner),
+            original_ttl: 300,
+            expiration: now + 3600,
+            inception: now - 3600,
+            key_tag: key.key_tag(),
+            signer_name: Name::root(),
+            signature: Vec::new(),
+        };
+
+        let data = canonical::signed_data(&rrsig, owner, QClass::IN, &records.iter().collect::<Vec<_>>());
+        rrsig.signature = crypto::test::ecdsa_sign(private, &data);
+
+        let mut answers = records.into_iter().map(|record| answer(owner, record)).collect::<Vec<_>>();
+        answers.push(answer(owner, Record::RRSIG(rrsig)));
+        answers
+    }
+
+    /// An NSEC record for `dog.` with NS, RRSIG, and NSEC in its bitmap,
+    /// as the root zone would have for an unsigned delegation.
+    fn delegation_nsec() -> Record {
+        let bytes = vec![ 0, 0, 6, 0b_0010_0000, 0, 0, 0, 0, 0b_0000_0011 ];
+        Record::Other { type_number: UnknownQtype::from(denial::NSEC), bytes }
+    }
+
+    /// Validates an unsigned answer in `dog.`, which is delegated to from
+    /// a signed root zone, whose response to the DS query for `dog.` has
+    /// the given authority section.
+    fn validate_with(authorities: impl FnOnce(&EcKey<Private>, &DNSKEY) -> Vec<Answer>) -> Vec<Option<Status>> {
+        let (private, key) = crypto::test::ecdsa_key();
+        let soa = SOA {
+            mname: name("ns1.dog."), rname: name("hostmaster.dog."), serial: 1,
+            refresh_interval: 3600, retry_interval: 600, expire_limit: 86400, minimum_ttl: 300,
+        };
+
+        let mut sections = HashMap::new();
+        sections.insert((Name::root(), qtype!(DNSKEY)), (signed(&private, &key, ".", vec![ Record::DNSKEY(key.clone()) ]), Vec::new()));
+        sections.insert((name("dog."), qtype!(DS)), (Vec::new(), authorities(&private, &key)));
+        sections.insert((name("dog."), qtype!(SOA)), (vec![ answer("dog.", Record::SOA(soa)) ], Vec::new()));
+        let transport = Sections { sections };
+
+        let anchors = [ Anchor { ds: crypto::make_ds(".", &key).unwrap(), state: AnchorState::Valid } ];
+        let requests = requests();
+        let mut runtime = Runtime::new().unwrap();
+        let mut validator = Validator::new(&mut runtime, &requests, &anchors);
+
+        let mut response = response_to(&requests.make_request(&name("lookup.dog."), qtype!(A), QClass::IN));
+        response.answers.push(answer("lookup.dog.", Record::A(A { address: Ipv4Addr::new(127, 0, 0, 1) })));
+        validator.validate(&transport, &response)
+    }
+
+    #[test]
+    fn signed_denial_of_ds() {
+        let statuses = validate_with(|private, key| signed(private, key, "dog.", vec![ delegation_nsec() ]));
+        assert_eq!(statuses, vec![ Some(Status::Insecure) ]);
+    }
+
+    #[test]
+    fn stripped_ds() {
+        let statuses = validate_with(|_, _| Vec::new());
+        assert_eq!(statuses, vec![ Some(Status::Bogus(Problem::MissingDenial)) ]);
+    }
+
+    #[test]
+    fn unsigned_denial_of_ds() {
+        let statuses = validate_with(|_, _| vec![ answer("dog.", delegation_nsec()) ]);
+        assert_eq!(statuses, vec![ Some(Status::Bogus(Problem::MissingSignature)) ]);
+    }
+}
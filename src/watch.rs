@@ -0,0 +1,191 @@
+//! Repeating queries on an interval, and spotting what changed between
+//! one round of responses and the next.
+
+use std::time::Duration;
+
+use dns::Answer;
+
+
+/// How an answer differs from the ones in the previous round.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Change {
+
+    /// The same record was in the previous round too.
+    Unchanged,
+
+    /// The record was not in the previous round.
+    Added,
+
+    /// The record was in the previous round, but not this one.
+    Removed,
+
+    /// The record was in the previous round with a TTL that does not
+    /// account for the time since then. Contains the previous TTL.
+    TTL(u32),
+}
+
+/// An answer from one round of a watch, along with how it changed.
+#[derive(PartialEq, Debug, Clone)]
+pub struct WatchedAnswer {
+
+    /// The answer, or the previous round’s answer if it was removed.
+    pub answer: Answer,
+
+    /// How it changed since the previous round.
+    pub change: Change,
+}
+
+
+/// Compares the answers from one round against the ones from the round
+/// before, returning this round’s answers followed by any that went away.
+/// There are no changes in the first round, as there is nothing to compare
+/// against.
+///
+/// A resolver that caches records counts their TTLs down, so a TTL is only
+/// counted as having changed if it is neither the same as before nor what
+/// it would have been after the given number of seconds, give or take a
+/// second.
+pub fn compare_rounds(previous: Option<&[Answer]>, current: &[Answer], elapsed_secs: u64) -> Vec<WatchedAnswer> {
+    let previous = match previous {
+        Some(p) => p,
+        None => {
+            return current.iter().map(|answer| WatchedAnswer { answer: answer.clone(), change: Change::Unchanged }).collect();
+        }
+    };
+
+    let mut unmatched = previous.iter().collect::<Vec<_>>();
+    let mut watched = Vec::new();
+
+    for answer in current {
+        let change = match unmatched.iter().position(|p| same_record(p, answer)) {
+            Some(index) => {
+                let previous_ttl = ttl(unmatched.remove(index));
+                let expected_ttl = u64::from(previous_ttl).saturating_sub(elapsed_secs);
+                let current_ttl = u64::from(ttl(answer));
+
+                if current_ttl == u64::from(previous_ttl) || (current_ttl + 1 >= expected_ttl && current_ttl <= expected_ttl + 1) {
+                    Change::Unchanged
+                }
+                else {
+                    Change::TTL(previous_ttl)
+                }
+            }
+            None => {
+                Change::Added
+            }
+        };
+
+        watched.push(WatchedAnswer { answer: answer.clone(), change });
+    }
+
+    for answer in unmatched {
+        watched.push(WatchedAnswer { answer: answer.clone(), change: Change::Removed });
+    }
+
+    watched
+}
+
+/// Parses the interval between rounds, which is a number followed by a
+/// unit of `ms`, `s`, `m`, or `h`, or just a number of seconds.
+pub fn parse_interval(input: &str) -> Option<Duration> {
+    let split = input.find(|c: char| ! c.is_ascii_digit()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number = number.parse::<u64>().ok().filter(|n| *n > 0)?;
+
+    match unit {
+        "ms"       => Some(Duration::from_millis(number)),
+        "s" | ""   => Some(Duration::from_secs(number)),
+        "m"        => number.checked_mul(60).map(Duration::from_secs),
+        "h"        => number.checked_mul(60 * 60).map(Duration::from_secs),
+        _          => None,
+    }
+}
+
+
+/// Whether two answers have the same owner name, class, and record data,
+/// regardless of their TTLs.
+fn same_record(first: &Answer, second: &Answer) -> bool {
+    match (first, second) {
+        (Answer::Standard { qname: n1, qclass: c1, record: r1, .. },
+         Answer::Standard { qname: n2, qclass: c2, record: r2, .. }) => {
+            n1 == n2 && c1 == c2 && r1 == r2
+        }
+        _ => false,
+    }
+}
+
+fn ttl(answer: &Answer) -> u32 {
+    match answer {
+        Answer::Standard { ttl, .. }  => *ttl,
+        Answer::Pseudo { .. }         => 0,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+    use dns::QClass;
+    use dns::record::{Record, A};
+
+    fn a(ttl: u32, last_octet: u8) -> Answer {
+        let record = Record::A(A { address: Ipv4Addr::new(127, 0, 0, last_octet) });
+        Answer::Standard { qname: "lookup.dog.".parse().unwrap(), qclass: QClass::IN, ttl, record }
+    }
+
+    fn changes(watched: Vec<WatchedAnswer>) -> Vec<Change> {
+        watched.into_iter().map(|w| w.change).collect()
+    }
+
+    #[test]
+    fn first_round() {
+        assert_eq!(changes(compare_rounds(None, &[ a(300, 1) ], 0)),
+                   vec![ Change::Unchanged ]);
+    }
+
+    #[test]
+    fn nothing_changed() {
+        assert_eq!(changes(compare_rounds(Some(&[ a(300, 1) ]), &[ a(300, 1) ], 5)),
+                   vec![ Change::Unchanged ]);
+    }
+
+    #[test]
+    fn counting_down() {
+        assert_eq!(changes(compare_rounds(Some(&[ a(300, 1) ]), &[ a(295, 1) ], 5)),
+                   vec![ Change::Unchanged ]);
+    }
+
+    #[test]
+    fn ttl_changed() {
+        assert_eq!(changes(compare_rounds(Some(&[ a(300, 1) ]), &[ a(60, 1) ], 5)),
+                   vec![ Change::TTL(300) ]);
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let watched = compare_rounds(Some(&[ a(300, 1) ]), &[ a(300, 2) ], 5);
+        assert_eq!(watched, vec![
+            WatchedAnswer { answer: a(300, 2), change: Change::Added },
+            WatchedAnswer { answer: a(300, 1), change: Change::Removed },
+        ]);
+    }
+
+    #[test]
+    fn intervals() {
+        assert_eq!(parse_interval("5s"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_interval("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_interval("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_interval("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_interval("1h"), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn invalid_intervals() {
+        assert_eq!(parse_interval("0s"), None);
+        assert_eq!(parse_interval("s"), None);
+        assert_eq!(parse_interval("5 weeks"), None);
+        assert_eq!(parse_interval("18446744073709551615h"), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Finding out whether a zone has a wildcard record, by asking for a few
+//! names under a domain that are made up on the spot, so they could only
+//! have records if a wildcard synthesises them (RFC 4592). Wildcards are
+//! a common source of answers for names nobody expected to exist.
+//!
+//! The domain itself gets asked for as well, as a name that exists with
+//! no records, such as an empty non-terminal, stops a wildcard above it
+//! from matching the names below it.
+
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+
+use dns::{Answer, ErrorCode, Name, Response, TypeInt};
+use dns::record::Record;
+use dns_transport::Error as TransportError;
+
+use log::*;
+
+use crate::lookup::LookupError;
+
+
+/// How many made-up names get asked for under each domain.
+pub const PROBE_COUNT: usize = 3;
+
+/// What was found under a domain.
+#[derive(PartialEq, Debug)]
+pub struct WildcardProbe {
+
+    /// The domain the made-up names were under.
+    pub domain: Name,
+
+    /// The type of record that was asked for.
+    pub qtype: TypeInt,
+
+    /// Whether the domain itself exists, and has records of the type.
+    pub domain_presence: Presence,
+
+    /// The made-up names, and what was found at each of them.
+    pub probes: Vec<Probe>,
+}
+
+/// One of the made-up names, and the records found at it.
+#[derive(PartialEq, Debug)]
+pub struct Probe {
+
+    /// The made-up name.
+    pub name: Name,
+
+    /// Whether the name exists, and has records of the type.
+    pub presence: Presence,
+
+    /// The records in the answer section, which have the made-up name as
+    /// their owner if a wildcard synthesised them.
+    pub answers: Vec<Answer>,
+}
+
+/// Whether a name exists, going by the response to a query for it.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Presence {
+
+    /// The name has records of the type that was asked for.
+    Records,
+
+    /// The name exists, but has no records of the type that was asked
+    /// for. A name that has no records at all but has names below it,
+    /// which is called an empty non-terminal, gets this response too.
+    NoData,
+
+    /// The name does not exist.
+    NxDomain,
+}
+
+
+/// Asks for records of the given type at the domain, and at the name made
+/// out of each of the given labels under it, calling the lookup function
+/// to send each query.
+pub fn probe(domain: &Name, qtype: TypeInt, labels: &[String], mut lookup: impl FnMut(&Name, TypeInt) -> Result<Response, TransportError>) -> Result<WildcardProbe, LookupError> {
+    info!("Looking up {:?} to see whether it exists", domain);
+    let response = lookup(domain, qtype).map_err(|e| LookupError::Transport(domain.clone(), e))?;
+    let (domain_presence, _) = presence(domain, response)?;
+
+    let mut probes = Vec::new();
+    for label in labels {
+        let name = match made_up_name(label, domain) {
+            Some(name) => name,
+            None => {
+                warn!("No room for a made-up name under {:?}", domain);
+                break;
+            }
+        };
+
+        info!("Looking up made-up name {:?}", name);
+        let response = lookup(&name, qtype).map_err(|e| LookupError::Transport(name.clone(), e))?;
+        let (presence, answers) = presence(&name, response)?;
+        probes.push(Probe { name, presence, answers });
+    }
+
+    Ok(WildcardProbe { domain: domain.clone(), qtype, domain_presence, probes })
+}
+
+/// Makes up a name out of the label under the domain. The label gets cut
+/// short if the name would be too long otherwise, and there is no name at
+/// all if the domain is so long that nothing fits below it.
+fn made_up_name(label: &str, domain: &Name) -> Option<Name> {
+    let room = Name::MAX_NAME_LENGTH.saturating_sub(domain.wire_len() + 1).min(Name::MAX_LABEL_LENGTH);
+    if room == 0 {
+        return None;
+    }
+
+    let label = &label[.. label.len().min(room)];
+    format!("{}.{}", label, domain.as_str().trim_end_matches('.')).parse().ok()
+}
+
+/// Works out whether a name exists from the response to a query for it,
+/// returning the records in the answer section along with it.
+fn presence(name: &Name, response: Response) -> Result<(Presence, Vec<Answer>), LookupError> {
+    let answers = response.answers.into_iter().filter(|answer| matches!(answer, Answer::Standard { .. })).collect::<Vec<_>>();
+
+    match response.flags.error_code {
+        Some(ErrorCode::NXDomain)        => Ok((Presence::NxDomain, answers)),
+        Some(rcode)                      => Err(LookupError::Rcode(name.clone(), rcode)),
+        None if answers.is_empty()       => Ok((Presence::NoData, answers)),
+        None                             => Ok((Presence::Records, answers)),
+    }
+}
+
+/// Makes up the given number of labels that are very unlikely to have
+/// been given records of their own, so any records at them come from a
+/// wildcard.
+pub fn random_labels(count: usize) -> Vec<String> {
+    let mut rng = rand::thread_rng();
+
+    (0 .. count).map(|_| {
+        let random = (&mut rng).sample_iter(&Alphanumeric).take(12).collect::<String>();
+        format!("dog-{}", random.to_ascii_lowercase())
+    }).collect()
+}
+
+
+impl WildcardProbe {
+
+    /// Whether any of the made-up names exist, which means there is a
+    /// wildcard in the zone that matches them.
+    pub fn has_wildcard(&self) -> bool {
+        self.probes.iter().any(|probe| probe.presence != Presence::NxDomain)
+    }
+
+    /// The records the wildcard synthesises for names of the type that was
+    /// asked for, as they were returned for the first made-up name that
+    /// got any. This is empty if there is no wildcard, or if it has no
+    /// records of the type.
+    pub fn synthesised(&self) -> Vec<&Record> {
+        self.probes.iter()
+            .find(|probe| ! probe.answers.is_empty())
+            .map(|probe| probe.answers.iter().filter_map(|answer| match answer {
+                Answer::Standard { record, .. }  => Some(record),
+                Answer::Pseudo { .. }            => None,
+            }).collect())
+            .unwrap_or_default()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use crate::lookup::testing::*;
+    use dns::{qtype, QClass};
+    use dns::record::{A, MX};
+
+    fn a_record(qname: &Name) -> Answer {
+        Answer::Standard { qname: qname.clone(), qclass: QClass::IN, ttl: 300, record: Record::A(A { address: "192.0.2.1".parse().unwrap() }) }
+    }
+
+    fn labels() -> Vec<String> {
+        vec![ "one".into(), "two".into() ]
+    }
+
+    /// Answers for a zone with a wildcard A record at `*.lookup.dog`.
+    fn wildcard(qname: &Name, qtype: TypeInt) -> Result<Response, TransportError> {
+        if qtype == qtype!(A) {
+            Ok(response(qname, qtype, None, vec![ a_record(qname) ]))
+        }
+        else {
+            Ok(response(qname, qtype, None, Vec::new()))
+        }
+    }
+
+    /// Answers for a zone with no wildcard, where `lookup.dog` is an empty
+    /// non-terminal.
+    fn no_wildcard(qname: &Name, qtype: TypeInt) -> Result<Response, TransportError> {
+        if *qname == name("lookup.dog.") {
+            Ok(response(qname, qtype, None, Vec::new()))
+        }
+        else {
+            Ok(response(qname, qtype, Some(ErrorCode::NXDomain), Vec::new()))
+        }
+    }
+
+    #[test]
+    fn synthesised_records() {
+        let probe = probe(&name("lookup.dog."), qtype!(A), &labels(), wildcard).unwrap();
+
+        assert_eq!(probe.probes[0].name, name("one.lookup.dog"));
+        assert_eq!(probe.probes[0].presence, Presence::Records);
+        assert!(probe.has_wildcard());
+        assert_eq!(probe.synthesised(), vec![ &Record::A(A { address: "192.0.2.1".parse().unwrap() }) ]);
+    }
+
+    #[test]
+    fn wildcard_without_the_type() {
+        let probe = probe(&name("lookup.dog."), qtype!(MX), &labels(), wildcard).unwrap();
+
+        assert_eq!(probe.probes[0].presence, Presence::NoData);
+        assert!(probe.has_wildcard());
+        assert_eq!(probe.synthesised(), Vec::<&Record>::new());
+    }
+
+    #[test]
+    fn empty_non_terminal() {
+        let probe = probe(&name("lookup.dog."), qtype!(A), &labels(), no_wildcard).unwrap();
+
+        assert_eq!(probe.domain_presence, Presence::NoData);
+        assert_eq!(probe.probes[1].presence, Presence::NxDomain);
+        assert!(! probe.has_wildcard());
+    }
+
+    #[test]
+    fn server_failure() {
+        let result = probe(&name("lookup.dog."), qtype!(A), &labels(), |qname, qtype| {
+            Ok(response(qname, qtype, Some(ErrorCode::ServerFailure), Vec::new()))
+        });
+
+        assert!(matches!(result, Err(LookupError::Rcode(_, ErrorCode::ServerFailure))));
+    }
+
+    /// A domain made of the given labels, with every one but the last
+    /// being as long as they can be.
+    fn long_domain(last: &str) -> Name {
+        let long = "a".repeat(Name::MAX_LABEL_LENGTH);
+        name(&format!("{0}.{0}.{0}.{1}.", long, last))
+    }
+
+    #[test]
+    fn shortened_under_a_long_domain() {
+        let domain = long_domain(&"b".repeat(54));
+        let probe = probe(&domain, qtype!(A), &[ "dog-abcdefghijkl".into() ], wildcard).unwrap();
+
+        assert_eq!(probe.probes[0].name.labels().next(), Some("dog-ab"));
+        assert_eq!(probe.probes[0].name.wire_len(), Name::MAX_NAME_LENGTH);
+    }
+
+    #[test]
+    fn no_room_under_a_long_domain() {
+        let domain = long_domain(&"b".repeat(60));
+        let probe = probe(&domain, qtype!(A), &labels(), wildcard).unwrap();
+
+        assert_eq!(probe.probes, Vec::new());
+        assert!(! probe.has_wildcard());
+    }
+
+    #[test]
+    fn made_up_labels() {
+        let labels = random_labels(PROBE_COUNT);
+
+        assert_eq!(labels.len(), PROBE_COUNT);
+        assert!(labels.iter().all(|label| label.len() == 16 && label.starts_with("dog-")));
+        assert_ne!(labels[0], labels[1]);
+    }
+}
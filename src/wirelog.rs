@@ -0,0 +1,230 @@
+//! Logging every request sent and every response received to a file, one
+//! JSON object per line, so a session can be looked at again later by
+//! giving the file to `dog decode`.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use log::*;
+use serde_json::{json, Value as JsonValue};
+
+use dns::Request;
+use dns::presentation::{base64, decode_base64, type_mnemonic};
+use dns_transport::{Error as TransportError, Reply, Transport};
+
+use crate::output::{iso_timestamp, protocol_name, rcode_name};
+
+
+/// The file that every message gets appended to, which is shared between
+/// every transport.
+pub struct WireLog {
+
+    /// Where the log file is.
+    path: PathBuf,
+
+    /// The log file, once it has been opened.
+    file: Mutex<Option<File>>,
+}
+
+impl WireLog {
+
+    /// Creates a new wire log that appends to the file at the given path,
+    /// once it has been opened.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, file: Mutex::new(None) }
+    }
+
+    /// Opens the log file for appending, creating it if it does not exist.
+    pub fn open(&self) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        *self.file.lock().expect("Wire log lock poisoned") = Some(file);
+        Ok(())
+    }
+
+    /// Appends one entry to the log as a line of JSON. Failing to write it
+    /// only gets logged, as the query itself has still been sent.
+    fn write(&self, entry: &JsonValue) {
+        if let Some(file) = &mut *self.file.lock().expect("Wire log lock poisoned") {
+            if let Err(e) = writeln!(file, "{}", entry) {
+                warn!("Failed to write to wire log {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
+
+
+/// A transport that logs every request it sends, and every response it
+/// receives, before passing them along. Requests are logged as they were
+/// actually sent, after the transport underneath has finished changing
+/// them, so they only get logged once it has replied; a request that got
+/// no reply is logged as it was given to the transport.
+pub struct LoggingTransport<T = Box<dyn Transport>> {
+    inner: T,
+    log: Arc<WireLog>,
+    server: String,
+    transport: &'static str,
+}
+
+impl<T: Transport> LoggingTransport<T> {
+
+    /// Creates a new logging transport that sends requests down the given
+    /// transport, noting the server and transport name they went to.
+    pub fn new(inner: T, log: Arc<WireLog>, server: String, transport: &'static str) -> Self {
+        Self { inner, log, server, transport }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for LoggingTransport<T> {
+    async fn send_with_details(&self, request: &Request) -> Result<Reply, TransportError> {
+        let question = request.queries.iter()
+                              .map(|q| format!("{} {} {}", q.qname, q.qclass, type_mnemonic(q.qtype)))
+                              .collect::<Vec<_>>();
+
+        let sent_at = now();
+        let result = self.inner.send_with_details(request).await;
+        let sent = match &result {
+            Ok(reply) if ! reply.sent.is_empty()  => reply.sent.clone(),
+            _                                     => request.to_bytes()?,
+        };
+
+        self.log.write(&json!({
+            "timestamp": sent_at,
+            "direction": "request",
+            "server": self.server,
+            "transport": self.transport,
+            "id": request.transaction_id,
+            "question": question,
+            "wire": base64(&sent),
+        }));
+
+        let reply = result?;
+
+        self.log.write(&json!({
+            "timestamp": now(),
+            "direction": "response",
+            "server": self.server,
+            "transport": self.transport,
+            "protocol": protocol_name(reply.protocol).to_lowercase(),
+            "id": reply.response.transaction_id,
+            "status": rcode_name(reply.response.flags.error_code),
+            "answers": reply.response.answers.len(),
+            "round_trip_ms": reply.round_trip.as_millis() as u64,
+            "wire": base64(&reply.bytes),
+        }));
+
+        Ok(reply)
+    }
+}
+
+fn now() -> String {
+    iso_timestamp(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default())
+}
+
+
+/// Reads the responses back out of a wire log, if that is what the input
+/// is: every line has to be a JSON object with the direction and bytes of
+/// a message. Anything else is taken to be a single message instead.
+pub fn read_responses(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    if ! text.trim_start().starts_with('{') {
+        return None;
+    }
+
+    let mut responses = Vec::new();
+    for line in text.lines().filter(|line| ! line.trim().is_empty()) {
+        let entry = serde_json::from_str::<JsonValue>(line).ok()?;
+        let wire = decode_base64(entry.get("wire")?.as_str()?)?;
+
+        match entry.get("direction")?.as_str()? {
+            "response"  => responses.push(wire),
+            "request"   => {/* requests only get logged to show what was asked */},
+            _           => return None,
+        }
+    }
+
+    Some(responses)
+}
+
+
+// Wire logs are compared by where they are kept rather than by whether
+// they have been opened, so the options that set them up can still be
+// compared.
+
+impl PartialEq for WireLog {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl fmt::Debug for WireLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WireLog").field("path", &self.path).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A transport that changes the request before sending it, the way
+    /// randomising its case would.
+    struct Changing;
+
+    #[async_trait]
+    impl Transport for Changing {
+        async fn send_with_details(&self, request: &Request) -> Result<Reply, TransportError> {
+            let response = dns::testing::response_to(request);
+            let bytes = response.to_bytes()?;
+            let sent = vec![ 0x12, 0x34 ];
+            Ok(Reply { response, bytes, sent, round_trip: std::time::Duration::from_millis(42), attempts: 1, protocol: dns_transport::Protocol::UDP, connection: Default::default() })
+        }
+    }
+
+    #[test]
+    fn request_logged_as_sent() {
+        let path = std::env::temp_dir().join(format!("dog-wirelog-{}.jsonl", std::process::id()));
+        let log = Arc::new(WireLog::new(path.clone()));
+        log.open().unwrap();
+
+        let transport = LoggingTransport::new(Changing, log, "127.0.0.1".into(), "UDP");
+        let query = dns::Query { qname: "lookup.dog.".parse().unwrap(), qclass: dns::QClass::IN, qtype: 1 };
+        let request = Request { transaction_id: 0x1234, flags: dns::Flags::query(), queries: vec![ query ], additional: None };
+        dns_transport::Runtime::new().unwrap().block_on(transport.send_with_details(&request)).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let entries = text.lines().map(|line| serde_json::from_str::<JsonValue>(line).unwrap()).collect::<Vec<_>>();
+        assert_eq!(entries[0]["wire"], json!("EjQ="));
+        assert_eq!(entries[1]["round_trip_ms"], json!(42));
+    }
+
+    #[test]
+    fn responses_read_back() {
+        let log = "\
+{\"direction\":\"request\",\"id\":1,\"wire\":\"AAE=\"}
+{\"direction\":\"response\",\"id\":1,\"wire\":\"AAGBgA==\"}
+
+{\"direction\":\"response\",\"id\":2,\"wire\":\"AAKBgA==\"}
+";
+        assert_eq!(read_responses(log.as_bytes()),
+                   Some(vec![ vec![ 0x00, 0x01, 0x81, 0x80 ], vec![ 0x00, 0x02, 0x81, 0x80 ] ]));
+    }
+
+    #[test]
+    fn binary_message() {
+        assert_eq!(read_responses(&[ 0x12, 0x34, 0x81, 0x80, 0x00, 0x01 ]), None);
+    }
+
+    #[test]
+    fn other_json() {
+        assert_eq!(read_responses(b"{\"responses\": []}"), None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Writing out JSON values as YAML documents.
+//!
+//! The YAML output contains exactly the same data as the JSON output, so
+//! rather than building it separately, the JSON value gets written out in
+//! YAML’s block style.
+
+use serde_json::Value as JsonValue;
+
+
+/// Converts a JSON value into a YAML document, starting with the `---`
+/// document marker and ending with a newline.
+pub fn to_yaml(value: &JsonValue) -> String {
+    let mut output = String::from("---\n");
+
+    for line in block_lines(value) {
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    output
+}
+
+
+/// Returns the lines that make up a value in block style, without any
+/// indentation of their own.
+fn block_lines(value: &JsonValue) -> Vec<String> {
+    match value {
+        JsonValue::Object(map) if ! map.is_empty() => {
+            let mut lines = Vec::new();
+
+            for (key, value) in map {
+                if is_inline(value) {
+                    lines.push(format!("{}: {}", scalar(key), inline(value)));
+                }
+                else {
+                    lines.push(format!("{}:", scalar(key)));
+                    lines.extend(lines_indented(value));
+                }
+            }
+
+            lines
+        }
+        JsonValue::Array(array) if ! array.is_empty() => {
+            let mut lines = Vec::new();
+
+            for value in array {
+                if is_inline(value) {
+                    lines.push(format!("- {}", inline(value)));
+                }
+                else {
+                    // The first line of a nested block goes on the same line
+                    // as the dash, and the rest line up with it.
+                    for (index, line) in block_lines(value).into_iter().enumerate() {
+                        if index == 0 {
+                            lines.push(format!("- {}", line));
+                        }
+                        else {
+                            lines.push(format!("  {}", line));
+                        }
+                    }
+                }
+            }
+
+            lines
+        }
+        otherwise => {
+            vec![ inline(otherwise) ]
+        }
+    }
+}
+
+fn lines_indented(value: &JsonValue) -> Vec<String> {
+    block_lines(value).into_iter().map(|line| format!("  {}", line)).collect()
+}
+
+/// Whether a value fits on the same line as its key or dash, which is
+/// true for everything apart from non-empty objects and arrays.
+fn is_inline(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Object(map)   => map.is_empty(),
+        JsonValue::Array(array)  => array.is_empty(),
+        _                        => true,
+    }
+}
+
+fn inline(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null          => "null".into(),
+        JsonValue::Bool(b)       => b.to_string(),
+        JsonValue::Number(n)     => n.to_string(),
+        JsonValue::String(s)     => scalar(s),
+        JsonValue::Object(_)     => "{}".into(),
+        JsonValue::Array(_)      => "[]".into(),
+    }
+}
+
+/// Writes a string as a plain scalar if it would be read back as the same
+/// string, or double-quoted otherwise. JSON’s string escapes are all valid
+/// in YAML’s double-quoted style, so those get re-used for the quoting.
+fn scalar(input: &str) -> String {
+    let plain_chars = input.chars().all(|c| c.is_ascii_alphanumeric() || "._-/".contains(c));
+    let starts_alphabetic = input.chars().next().is_some_and(|c| c.is_ascii_alphabetic());
+    let reserved = [ "y", "n", "yes", "no", "true", "false", "on", "off", "null" ].iter().any(|r| input.eq_ignore_ascii_case(r));
+
+    if plain_chars && starts_alphabetic && ! reserved {
+        input.into()
+    }
+    else {
+        JsonValue::from(input).to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn scalars() {
+        assert_eq!(to_yaml(&json!(17)), "---\n17\n");
+        assert_eq!(to_yaml(&json!(null)), "---\nnull\n");
+        assert_eq!(to_yaml(&json!("lookup.dog.")), "---\nlookup.dog.\n");
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(scalar("127.0.0.1"), "\"127.0.0.1\"");
+        assert_eq!(scalar("yes"), "\"yes\"");
+        assert_eq!(scalar("NO"), "\"NO\"");
+        assert_eq!(scalar("v=spf1 -all"), "\"v=spf1 -all\"");
+        assert_eq!(scalar("say \"woof\"\n"), "\"say \\\"woof\\\"\\n\"");
+        assert_eq!(scalar(""), "\"\"");
+    }
+
+    #[test]
+    fn nested() {
+        let value = json!({
+            "responses": [
+                { "answers": [ { "name": "lookup.dog.", "ttl": 300 } ], "authorities": [] },
+            ],
+            "schema_version": 1,
+        });
+
+        assert_eq!(to_yaml(&value), "\
+---
+responses:
+  - answers:
+      - name: lookup.dog.
+        ttl: 300
+    authorities: []
+schema_version: 1
+");
+    }
+
+    #[test]
+    fn nested_arrays() {
+        assert_eq!(to_yaml(&json!([ [ 1, 2 ], {} ])), "---\n- - 1\n  - 2\n- {}\n");
+    }
+}